@@ -0,0 +1,54 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    grpc::generate();
+}
+
+/// Generates the tonic server for `proto/payouts.proto`. The messages are
+/// hand-written prost types in `src/grpc.rs`, so `protoc` is not needed.
+#[cfg(feature = "grpc")]
+mod grpc {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    fn unary(name: &str, route: &str, input: &str, output: &str) -> Method {
+        Method::builder()
+            .name(name)
+            .route_name(route)
+            .input_type(format!("crate::grpc::{input}"))
+            .output_type(format!("crate::grpc::{output}"))
+            .codec_path("tonic::codec::ProstCodec")
+            .build()
+    }
+
+    pub fn generate() {
+        println!("cargo:rerun-if-changed=build.rs");
+        let service = Service::builder()
+            .name("Payouts")
+            .package("chase.payouts.v1")
+            .method(unary(
+                "assign_payout",
+                "AssignPayout",
+                "AssignPayoutRequest",
+                "AssignPayoutReply",
+            ))
+            .method(unary(
+                "cancel_payout",
+                "CancelPayout",
+                "CancelPayoutRequest",
+                "CancelPayoutReply",
+            ))
+            .method(unary("list_deals", "ListDeals", "ListDealsRequest", "ListDealsReply"))
+            .method(
+                Method::builder()
+                    .name("events")
+                    .route_name("Events")
+                    .input_type("crate::grpc::EventsRequest")
+                    .output_type("crate::grpc::Event")
+                    .codec_path("tonic::codec::ProstCodec")
+                    .server_streaming()
+                    .build(),
+            )
+            .build();
+
+        Builder::new().build_client(false).compile(&[service]);
+    }
+}
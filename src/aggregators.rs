@@ -0,0 +1,384 @@
+//! Payouts routed to upstream aggregators. The platform records them in
+//! `AggregatorPayout` (with the `Aggregator` it went to and when) and the
+//! distributor leaves them alone, so they never show up in the queue or in
+//! the trader views. `GET /api/payouts/aggregated` lists them with the
+//! aggregator and its timing, next to the share of the OUT flow that went to
+//! aggregators, to traders and nowhere yet over the same window.
+//!
+//! `POST /api/payouts/:id/reclaim` takes a payout back from a stalling
+//! aggregator: while nobody has accepted it the link is removed and the
+//! payout goes back to the unassigned pool for the distributor.
+
+use axum::{
+    Extension, Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use tower_sessions::Session;
+
+use crate::{
+    ApiResult, AppState,
+    api::fetch_updated_payout,
+    auth,
+    currencies::PAYOUT_CURRENCY_SQL,
+    db::{Pagination, record_payout_audit},
+    distribution::AssignPayoutResponse,
+    errors::{ApiError, ErrorCode},
+    events::ServerEvent,
+    internal_error, outbox,
+    payout_status::PayoutStatus,
+    tenant::TenantScope,
+    timestamps::UtcTimestamp,
+};
+
+const MAX_WINDOW_HOURS: i32 = 30 * 24;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AggregatedPayoutsQuery {
+    /// Payouts created in the last `hours` (default 24).
+    hours: Option<i32>,
+    aggregator_id: Option<String>,
+    page: Option<u32>,
+    per_page: Option<u32>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AggregatedPayout {
+    id: String,
+    #[sqlx(rename = "numericId")]
+    numeric_id: i32,
+    amount: f64,
+    currency: String,
+    status: PayoutStatus,
+    #[sqlx(rename = "merchantId")]
+    merchant_id: String,
+    #[sqlx(rename = "aggregatorId")]
+    aggregator_id: Option<String>,
+    #[sqlx(rename = "aggregatorName")]
+    aggregator_name: Option<String>,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+    #[sqlx(rename = "routedAt")]
+    routed_at: Option<UtcTimestamp>,
+    /// From creating the payout to handing it to the aggregator.
+    #[sqlx(rename = "routedAfterSeconds")]
+    routed_after_seconds: Option<f64>,
+    /// Accepted or cancelled, whichever happened.
+    #[sqlx(rename = "finishedAt")]
+    finished_at: Option<UtcTimestamp>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FlowShare {
+    total: i64,
+    #[sqlx(rename = "totalAmount")]
+    total_amount: f64,
+    aggregated: i64,
+    #[sqlx(rename = "aggregatedAmount")]
+    aggregated_amount: f64,
+    /// Assigned to a trader and not routed upstream.
+    traders: i64,
+    #[sqlx(rename = "tradersAmount")]
+    traders_amount: f64,
+    unassigned: i64,
+    #[sqlx(rename = "unassignedAmount")]
+    unassigned_amount: f64,
+    /// Percentages of `total`, `null` when the window is empty.
+    #[sqlx(skip)]
+    aggregated_share: Option<f64>,
+    #[sqlx(skip)]
+    traders_share: Option<f64>,
+    #[sqlx(skip)]
+    unassigned_share: Option<f64>,
+}
+
+impl FlowShare {
+    fn with_shares(self) -> Self {
+        let share = |count: i64| (self.total > 0).then(|| count as f64 * 100.0 / self.total as f64);
+        Self {
+            aggregated_share: share(self.aggregated),
+            traders_share: share(self.traders),
+            unassigned_share: share(self.unassigned),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AggregatorSummary {
+    #[sqlx(rename = "aggregatorId")]
+    aggregator_id: Option<String>,
+    #[sqlx(rename = "aggregatorName")]
+    aggregator_name: Option<String>,
+    count: i64,
+    amount: f64,
+    #[sqlx(rename = "avgRoutedAfterSeconds")]
+    avg_routed_after_seconds: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AggregatedPayoutsResponse {
+    hours: i32,
+    items: Vec<AggregatedPayout>,
+    pagination: Pagination,
+    share: FlowShare,
+    aggregators: Vec<AggregatorSummary>,
+}
+
+/// Routed payouts within the window and the caller's merchants; `$3` narrows
+/// them to one aggregator.
+const AGGREGATED_FROM: &str = r#"
+    FROM "AggregatorPayout" ap
+    JOIN "Payout" p ON p."id" = ap."payoutId"
+    LEFT JOIN "Aggregator" ag ON ag."id" = ap."aggregatorId"
+    WHERE p."direction" = 'OUT'
+      AND p."createdAt" >= LOCALTIMESTAMP - make_interval(hours => $1)
+      AND ($2::text[] IS NULL OR p."merchantId" = ANY($2::text[]))
+      AND ($3::text IS NULL OR ap."aggregatorId" = $3)
+"#;
+
+pub(crate) async fn get_aggregated_payouts(
+    Query(params): Query<AggregatedPayoutsQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<AggregatedPayoutsResponse>> {
+    let hours = params.hours.unwrap_or(24);
+    if !(1..=MAX_WINDOW_HOURS).contains(&hours) {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            format!("hours must be between 1 and {MAX_WINDOW_HOURS}"),
+        )));
+    }
+    let page = params.page.unwrap_or(1).max(1);
+    let per_page = params.per_page.unwrap_or(25).clamp(1, 200);
+    let aggregator_id = params
+        .aggregator_id
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty());
+
+    let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*)::bigint {AGGREGATED_FROM}"))
+        .bind(hours)
+        .bind(scope.merchant_ids())
+        .bind(&aggregator_id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(internal_error)?;
+
+    let items = sqlx::query_as::<_, AggregatedPayout>(&format!(
+        r#"
+        SELECT
+            p."id",
+            p."numericId",
+            p."amount",
+            {PAYOUT_CURRENCY_SQL} AS "currency",
+            p."status"::text AS "status",
+            p."merchantId",
+            ap."aggregatorId",
+            COALESCE(ag."name", ap."aggregatorId") AS "aggregatorName",
+            p."createdAt",
+            ap."createdAt" AS "routedAt",
+            GREATEST(EXTRACT(EPOCH FROM (ap."createdAt" - p."createdAt")), 0)::double precision
+                AS "routedAfterSeconds",
+            COALESCE(p."acceptedAt", p."cancelledAt") AS "finishedAt"
+        {AGGREGATED_FROM}
+        ORDER BY p."createdAt" DESC, p."id"
+        LIMIT $4 OFFSET $5
+        "#
+    ))
+    .bind(hours)
+    .bind(scope.merchant_ids())
+    .bind(&aggregator_id)
+    .bind(per_page as i64)
+    .bind(((page - 1) as i64) * per_page as i64)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let aggregators = sqlx::query_as::<_, AggregatorSummary>(&format!(
+        r#"
+        SELECT
+            ap."aggregatorId",
+            MAX(COALESCE(ag."name", ap."aggregatorId")) AS "aggregatorName",
+            COUNT(*)::bigint AS "count",
+            COALESCE(SUM(p."amount"), 0)::double precision AS "amount",
+            AVG(GREATEST(EXTRACT(EPOCH FROM (ap."createdAt" - p."createdAt")), 0))::double precision
+                AS "avgRoutedAfterSeconds"
+        {AGGREGATED_FROM}
+        GROUP BY ap."aggregatorId"
+        ORDER BY "count" DESC
+        "#
+    ))
+    .bind(hours)
+    .bind(scope.merchant_ids())
+    .bind(&aggregator_id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let share = sqlx::query_as::<_, FlowShare>(
+        r#"
+        SELECT
+            COUNT(*)::bigint AS "total",
+            COALESCE(SUM(f."amount"), 0)::double precision AS "totalAmount",
+            COUNT(*) FILTER (WHERE f."aggregated")::bigint AS "aggregated",
+            COALESCE(SUM(f."amount") FILTER (WHERE f."aggregated"), 0)::double precision
+                AS "aggregatedAmount",
+            COUNT(*) FILTER (WHERE NOT f."aggregated" AND f."assigned")::bigint AS "traders",
+            COALESCE(SUM(f."amount") FILTER (WHERE NOT f."aggregated" AND f."assigned"), 0)::double precision
+                AS "tradersAmount",
+            COUNT(*) FILTER (WHERE NOT f."aggregated" AND NOT f."assigned")::bigint AS "unassigned",
+            COALESCE(SUM(f."amount") FILTER (WHERE NOT f."aggregated" AND NOT f."assigned"), 0)::double precision
+                AS "unassignedAmount"
+        FROM (
+            SELECT
+                p."amount",
+                EXISTS (
+                    SELECT 1 FROM "AggregatorPayout" ap WHERE ap."payoutId" = p."id"
+                ) AS "aggregated",
+                p."traderId" IS NOT NULL AS "assigned"
+            FROM "Payout" p
+            WHERE p."direction" = 'OUT'
+              AND p."createdAt" >= LOCALTIMESTAMP - make_interval(hours => $1)
+              AND ($2::text[] IS NULL OR p."merchantId" = ANY($2::text[]))
+        ) f
+        "#,
+    )
+    .bind(hours)
+    .bind(scope.merchant_ids())
+    .fetch_one(&state.pool)
+    .await
+    .map_err(internal_error)?
+    .with_shares();
+
+    Ok(Json(AggregatedPayoutsResponse {
+        hours,
+        items,
+        pagination: Pagination::new(total, page, per_page),
+        share,
+        aggregators,
+    }))
+}
+
+/// Removes the payout's aggregator link and clears any trader on it, so the
+/// distributor treats it like a fresh payout. Refused once the payout was
+/// accepted or left `CREATED`.
+pub(crate) async fn reclaim_payout(
+    Path(payout_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+) -> ApiResult<Json<AssignPayoutResponse>> {
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    let payout: Option<(String, String, bool, Option<String>)> = sqlx::query_as(
+        r#"
+        SELECT p."merchantId", p."status"::text, p."acceptedAt" IS NOT NULL, p."traderId"
+        FROM "Payout" p
+        WHERE p."id" = $1
+          AND p."direction" = 'OUT'
+          AND ($2::text[] IS NULL OR p."merchantId" = ANY($2::text[]))
+        FOR UPDATE
+        "#,
+    )
+    .bind(&payout_id)
+    .bind(scope.merchant_ids())
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    let Some((merchant_id, status, accepted, trader_id)) = payout else {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::PayoutNotFound,
+            format!("Payout {payout_id} not found"),
+        ));
+    };
+
+    let aggregator_ids: Vec<Option<String>> = sqlx::query_scalar(
+        r#"
+        SELECT "aggregatorId"
+        FROM "AggregatorPayout"
+        WHERE "payoutId" = $1
+        FOR UPDATE
+        "#,
+    )
+    .bind(&payout_id)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    if aggregator_ids.is_empty() {
+        return Err(ApiError::from((
+            StatusCode::NOT_FOUND,
+            format!("Payout {payout_id} is not routed to an aggregator"),
+        )));
+    }
+    if status != PayoutStatus::Created.as_str() || accepted {
+        let reached = if accepted {
+            "accepted"
+        } else {
+            status.as_str()
+        };
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            ErrorCode::PayoutNotEligible,
+            format!(
+                "Payout {payout_id} was already {reached} at the aggregator and cannot be reclaimed"
+            ),
+        ));
+    }
+
+    sqlx::query(r#"DELETE FROM "AggregatorPayout" WHERE "payoutId" = $1"#)
+        .bind(&payout_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+    if trader_id.is_some() {
+        sqlx::query(
+            r#"
+            UPDATE "Payout"
+            SET "traderId" = NULL,
+                "updatedAt" = CURRENT_TIMESTAMP
+            WHERE "id" = $1
+            "#,
+        )
+        .bind(&payout_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+    }
+    let aggregator_ids: Vec<String> = aggregator_ids.into_iter().flatten().collect();
+    record_payout_audit(
+        &mut *tx,
+        &payout_id,
+        "reclaimed",
+        actor.as_deref(),
+        trader_id.as_deref(),
+        Some(serde_json::json!({ "aggregatorIds": aggregator_ids })),
+    )
+    .await
+    .map_err(internal_error)?;
+    outbox::enqueue_event(
+        &mut tx,
+        &ServerEvent::payouts_updated("manual").for_merchants([merchant_id]),
+    )
+    .await
+    .map_err(internal_error)?;
+    tx.commit().await.map_err(internal_error)?;
+    state.outbox_notify.notify_one();
+
+    println!(
+        "[aggregators] Payout {payout_id} reclaimed from {} by {}",
+        aggregator_ids.join(", "),
+        actor.as_deref().unwrap_or("operator")
+    );
+    Ok(Json(AssignPayoutResponse {
+        success: true,
+        payout: fetch_updated_payout(&state, &payout_id).await?,
+    }))
+}
@@ -0,0 +1,2398 @@
+//! HTTP routes of the dashboard and the REST API, with the handlers that do
+//! not belong to a more specific module.
+
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Extension, Form, Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{delete, get, post, put},
+};
+use chrono::{NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::{
+    ApiResult, AppState, aggregators, assignment_latency, audit, auth, availability,
+    callback_pause, callback_sla, callback_templates,
+    callbacks::{
+        build_cancel_callback_payload, delete_callback_override, delete_trader_webhook,
+        dispatch_queued_callback, export_callbacks, get_callback_override,
+        get_dead_letter_callbacks, get_trader_webhook, retry_dead_letter_callbacks,
+        test_merchant_webhook, update_callback_override, update_trader_webhook,
+    },
+    cancel_approval, client_certs, config_snapshot,
+    currencies::{self, PAYOUT_CURRENCY_SQL},
+    db::{
+        CancelReasonCode, Pagination, PayoutDealListItem, PayoutDetails, PayoutListFilters,
+        PayoutListResponse, SortField, SortOrder, StatsSummary, TimeseriesPoint, Trader,
+        TraderListFilters, TraderListResponse, TraderSortField, UnassignedPayoutListResponse,
+        fetch_cancel_reasons, fetch_capacity_overrides, fetch_filtered_traders, fetch_open_payouts,
+        fetch_payout_deal, fetch_payouts_page, fetch_stats_summary, fetch_timeseries,
+        fetch_trader_cooldowns, fetch_unassigned_payouts_page, record_payout_audit,
+    },
+    digest,
+    distribution::{
+        AssignPayoutResponse, assign_payout, get_distribution_runs, get_distribution_worker,
+        restart_distribution_worker, run_distribution_now, simulate_distribution,
+        stop_distribution_cycle,
+    },
+    duplicates, eligibility_history, environment,
+    errors::{ApiError, ErrorCode},
+    etag::ETagged,
+    events::{ServerEvent, events, get_server_status},
+    feature_flags, filter_presets, freeze, frontend, i18n, internal_error, limit_templates,
+    merchant_api, merchant_page, notes, outbox,
+    payout_status::PayoutStatus,
+    payout_transitions, payout_version, pool_monitor, preferences, rates, reports, search,
+    settings::{
+        add_email_recipient, delete_bank_weights, delete_email_recipient, get_auto_settings,
+        get_bank_weights, get_email_notifications, get_priority_policy, send_test_email,
+        update_auto_settings, update_bank_weights, update_priority_policy, update_trader_capacity,
+        update_trader_limit,
+    },
+    settings_approval, teams,
+    tenant::{self, TenantScope},
+    timestamps::UtcTimestamp,
+    trader_auth::{self, TraderScope},
+    trader_page,
+};
+
+pub(crate) const MAX_PAYOUT_FILE_BYTES: usize = 10 * 1024 * 1024;
+
+pub(crate) fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(serve_index))
+        .route("/login", get(login_page).post(login))
+        .route("/assets/app.css", get(serve_app_css))
+        .route("/assets/app.js", get(serve_app_js))
+        .route("/logout", post(logout))
+        .route("/trader/:token", get(trader_page::trader_page))
+        .route("/merchant/:token", get(merchant_page::merchant_page))
+        .route("/api/events", get(events))
+        .route("/api/status", get(get_server_status))
+        .route("/api/debug/pool", get(pool_monitor::get_pool_debug))
+        .route("/api/traders", get(get_traders))
+        .route("/api/payouts", get(get_unassigned_payouts))
+        .route("/api/deals", get(get_all_payouts))
+        .route("/fragments/deals-table", get(get_deals_fragment))
+        .route("/fragments/traders-table", get(get_traders_fragment))
+        .route("/api/deals/:id/timeline", get(get_payout_timeline))
+        .route("/api/lookup", get(lookup_payout))
+        .route("/api/search", get(search::global_search))
+        .route(
+            "/api/filters",
+            get(filter_presets::list_filter_presets).post(filter_presets::save_filter_preset),
+        )
+        .route(
+            "/api/preferences",
+            get(preferences::get_preferences)
+                .put(preferences::update_preferences)
+                .delete(preferences::reset_preferences),
+        )
+        .route(
+            "/api/filters/:id",
+            put(filter_presets::update_filter_preset).delete(filter_presets::delete_filter_preset),
+        )
+        .route("/api/stats/timeseries", get(get_stats_timeseries))
+        .route("/api/stats/summary", get(get_stats_summary))
+        .route(
+            "/api/stats/assignment-latency",
+            get(assignment_latency::get_assignment_latency),
+        )
+        .route("/api/rates", get(get_rates))
+        .route("/api/callbacks/dead-letter", get(get_dead_letter_callbacks))
+        .route(
+            "/api/callbacks/dead-letter/retry",
+            post(retry_dead_letter_callbacks),
+        )
+        .route("/api/callbacks/export", get(export_callbacks))
+        .route("/api/audit/export", get(audit::export_audit))
+        .route("/api/reports/shift", get(reports::get_shift_report))
+        .route(
+            "/api/reports/schedule",
+            get(digest::get_report_schedule).post(digest::update_report_schedule),
+        )
+        .route("/api/reports/schedule/test", post(digest::send_test_digest))
+        .route("/api/distribution/runs", get(get_distribution_runs))
+        .route("/api/distribution/run", post(run_distribution_now))
+        .route("/api/distribution/worker", get(get_distribution_worker))
+        .route(
+            "/api/distribution/worker/restart",
+            post(restart_distribution_worker),
+        )
+        .route(
+            "/api/distribution/worker/stop",
+            post(stop_distribution_cycle),
+        )
+        .route("/api/distribution/simulate", post(simulate_distribution))
+        .route("/api/payouts/:id/assign", post(assign_payout))
+        .route("/api/payouts/:id/cancel", post(cancel_payout))
+        .route(
+            "/api/payouts/:id/cancel/request",
+            post(cancel_approval::request_cancel_approval),
+        )
+        .route(
+            "/api/payouts/:id/cancel/approve",
+            post(cancel_approval::approve_cancel_request),
+        )
+        .route(
+            "/api/payouts/:id/cancel/reject",
+            post(cancel_approval::reject_cancel_request),
+        )
+        .route("/api/payouts/bulk-cancel", post(bulk_cancel_payouts))
+        .route(
+            "/api/cancel-requests",
+            get(cancel_approval::get_cancel_requests),
+        )
+        .route("/api/payouts/duplicates", get(get_duplicate_payouts))
+        .route(
+            "/api/payouts/aggregated",
+            get(aggregators::get_aggregated_payouts),
+        )
+        .route(
+            "/api/payouts/:id/currency",
+            put(currencies::set_payout_currency),
+        )
+        .route(
+            "/api/payouts/:id/reclaim",
+            post(aggregators::reclaim_payout),
+        )
+        .route(
+            "/api/payouts/:id/duplicate/approve",
+            post(approve_duplicate_payout),
+        )
+        .route(
+            "/api/payouts/:id/files",
+            get(get_payout_files)
+                .post(upload_payout_file)
+                .layer(DefaultBodyLimit::max(MAX_PAYOUT_FILE_BYTES)),
+        )
+        .route(
+            "/api/payouts/:id/notes",
+            get(notes::get_payout_notes).post(notes::create_payout_note),
+        )
+        .route(
+            "/api/cancel-reasons",
+            get(get_cancel_reasons).post(upsert_cancel_reason),
+        )
+        .route(
+            "/api/settings/auto-distribution",
+            get(get_auto_settings).post(update_auto_settings),
+        )
+        .route(
+            "/api/settings/priority",
+            get(get_priority_policy).post(update_priority_policy),
+        )
+        .route(
+            "/api/settings/changes",
+            get(settings_approval::get_settings_changes),
+        )
+        .route(
+            "/api/settings/changes/:id/approve",
+            post(settings_approval::approve_settings_change),
+        )
+        .route(
+            "/api/settings/changes/:id/reject",
+            post(settings_approval::reject_settings_change),
+        )
+        .route("/api/feature-flags", get(feature_flags::get_feature_flags))
+        .route(
+            "/api/feature-flags/:key",
+            post(feature_flags::update_feature_flag),
+        )
+        .route("/api/config/export", get(config_snapshot::export_config))
+        .route("/api/config/import", post(config_snapshot::import_config))
+        .route("/api/traders/:id/limit", post(update_trader_limit))
+        .route(
+            "/api/traders/limits/bulk",
+            post(limit_templates::apply_limit_template),
+        )
+        .route("/api/traders/:id/capacity", post(update_trader_capacity))
+        .route(
+            "/api/traders/:id/currencies",
+            get(currencies::get_trader_currencies).post(currencies::update_trader_currencies),
+        )
+        .route("/api/traders/:id/assignments", get(get_trader_assignments))
+        .route(
+            "/api/traders/:id/eligibility-history",
+            get(eligibility_history::get_eligibility_history),
+        )
+        .route(
+            "/api/traders/:id/unavailability",
+            get(availability::get_trader_unavailability)
+                .post(availability::create_trader_unavailability),
+        )
+        .route(
+            "/api/traders/:id/unavailability/:window_id",
+            post(availability::update_trader_unavailability)
+                .delete(availability::delete_trader_unavailability),
+        )
+        .route("/api/merchants", get(callback_pause::get_merchants))
+        .route(
+            "/api/merchants/:id/callbacks/pause",
+            post(callback_pause::pause_merchant_callbacks),
+        )
+        .route(
+            "/api/merchants/:id/callbacks/resume",
+            post(callback_pause::resume_merchant_callbacks),
+        )
+        .route(
+            "/api/merchants/:id/webhook/test",
+            post(test_merchant_webhook),
+        )
+        .route(
+            "/api/merchants/:id/callback-override",
+            get(get_callback_override)
+                .post(update_callback_override)
+                .delete(delete_callback_override),
+        )
+        .route(
+            "/api/merchants/:id/callback-sla",
+            get(callback_sla::get_callback_sla),
+        )
+        .route(
+            "/api/merchants/:id/page-token",
+            post(merchant_page::issue_page_token).delete(merchant_page::revoke_page_token),
+        )
+        .route(
+            "/api/merchants/:id/callback-template",
+            get(callback_templates::get_callback_template)
+                .post(callback_templates::update_callback_template)
+                .delete(callback_templates::delete_callback_template),
+        )
+        .route(
+            "/api/merchants/:id/client-certificate",
+            get(client_certs::get_client_certificate)
+                .post(client_certs::update_client_certificate)
+                .delete(client_certs::delete_client_certificate),
+        )
+        .route(
+            "/api/traders/:id/webhook",
+            get(get_trader_webhook)
+                .post(update_trader_webhook)
+                .delete(delete_trader_webhook),
+        )
+        .route(
+            "/api/traders/:id/token",
+            post(issue_trader_token).delete(revoke_trader_token),
+        )
+        .route(
+            "/api/traders/:id/page-token",
+            post(trader_page::issue_page_token).delete(trader_page::revoke_page_token),
+        )
+        .route("/api/teams", get(teams::get_teams).post(teams::create_team))
+        .route(
+            "/api/teams/:id",
+            post(teams::update_team).delete(teams::delete_team),
+        )
+        .route("/api/teams/:id/members", post(teams::update_team_members))
+        .route("/api/bank-weights", get(get_bank_weights))
+        .route(
+            "/api/bank-weights/:bank",
+            post(update_bank_weights).delete(delete_bank_weights),
+        )
+        .route(
+            "/api/notifications/email",
+            get(get_email_notifications).post(add_email_recipient),
+        )
+        .route("/api/notifications/email/test", post(send_test_email))
+        .route(
+            "/api/notifications/email/:email",
+            delete(delete_email_recipient),
+        )
+        .route(
+            "/api/merchant/payouts/:external_reference",
+            get(merchant_api::get_merchant_payout_status),
+        )
+        .route("/api/self/pause", post(pause_self))
+        .route("/api/self/assignments", get(get_self_assignments))
+        .route(
+            "/api/self/unavailability",
+            get(availability::get_self_unavailability)
+                .post(availability::create_self_unavailability),
+        )
+        .route(
+            "/api/self/unavailability/:window_id",
+            delete(availability::delete_self_unavailability),
+        )
+}
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TimeseriesResponse {
+    hours: u32,
+    points: Vec<TimeseriesPoint>,
+}
+
+#[derive(Debug, FromRow)]
+pub(crate) struct TimelinePayout {
+    status: PayoutStatus,
+    #[sqlx(rename = "traderId")]
+    trader_id: Option<String>,
+    #[sqlx(rename = "disputeMessage")]
+    dispute_message: Option<String>,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+    #[sqlx(rename = "acceptedAt")]
+    accepted_at: Option<UtcTimestamp>,
+    #[sqlx(rename = "cancelledAt")]
+    cancelled_at: Option<UtcTimestamp>,
+    #[sqlx(rename = "updatedAt")]
+    updated_at: UtcTimestamp,
+}
+
+#[derive(Debug, FromRow)]
+pub(crate) struct PayoutAuditRow {
+    action: String,
+    source: String,
+    actor: Option<String>,
+    #[sqlx(rename = "traderId")]
+    trader_id: Option<String>,
+    details: Option<Value>,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+}
+
+#[derive(Debug, FromRow)]
+pub(crate) struct CallbackHistoryRow {
+    url: String,
+    #[sqlx(rename = "statusCode")]
+    status_code: Option<i32>,
+    error: Option<String>,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TimelineEvent {
+    at: UtcTimestamp,
+    kind: String,
+    source: Option<String>,
+    actor: Option<String>,
+    trader_id: Option<String>,
+    details: Option<Value>,
+}
+
+impl TimelineEvent {
+    fn new(at: UtcTimestamp, kind: &str, source: &str) -> Self {
+        Self {
+            at,
+            kind: kind.to_string(),
+            source: Some(source.to_string()),
+            actor: None,
+            trader_id: None,
+            details: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PayoutTimelineResponse {
+    payout_id: String,
+    status: PayoutStatus,
+    events: Vec<TimelineEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LookupQuery {
+    numeric_id: i32,
+}
+
+/// Just enough of a payout for the dashboard's jump-to-payout shortcut.
+#[derive(Debug, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PayoutLookup {
+    id: String,
+    #[sqlx(rename = "numericId")]
+    numeric_id: i32,
+    status: PayoutStatus,
+    amount: f64,
+    bank: String,
+    wallet: String,
+    #[sqlx(rename = "merchantId")]
+    merchant_id: String,
+    #[sqlx(rename = "traderId")]
+    trader_id: Option<String>,
+    archived: bool,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TimeseriesQuery {
+    hours: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PayoutListQuery {
+    pub(crate) search: Option<String>,
+    pub(crate) wallet: Option<String>,
+    pub(crate) amount: Option<f64>,
+    pub(crate) status: Option<String>,
+    pub(crate) page: Option<u32>,
+    pub(crate) per_page: Option<u32>,
+    pub(crate) sort: Option<String>,
+    pub(crate) order: Option<String>,
+    pub(crate) include_archived: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TraderListQuery {
+    search: Option<String>,
+    sort: Option<String>,
+    order: Option<String>,
+    page: Option<u32>,
+    per_page: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UnassignedPayoutListQuery {
+    page: Option<u32>,
+    per_page: Option<u32>,
+}
+
+impl TraderListQuery {
+    fn into_filters(self) -> TraderListFilters {
+        let search = self.search.and_then(|value| {
+            let trimmed = value.trim().to_string();
+            if trimmed.is_empty() {
+                None
+            } else {
+                Some(trimmed)
+            }
+        });
+
+        let sort = match self.sort.as_deref() {
+            Some("email") => TraderSortField::Email,
+            Some("balanceRub") => TraderSortField::BalanceRub,
+            Some("frozenRub") => TraderSortField::FrozenRub,
+            Some("payoutBalance") => TraderSortField::PayoutBalance,
+            Some("team") => TraderSortField::Team,
+            _ => TraderSortField::NumericId,
+        };
+
+        let order = match self.order.as_deref() {
+            Some(value) if value.eq_ignore_ascii_case("desc") => SortOrder::Desc,
+            _ => SortOrder::Asc,
+        };
+
+        TraderListFilters {
+            search,
+            sort,
+            order,
+            page: self.page.unwrap_or(1).max(1),
+            per_page: self.per_page.unwrap_or(25).clamp(1, 200),
+            merchant_ids: None,
+        }
+    }
+}
+
+impl UnassignedPayoutListQuery {
+    fn page_and_size(&self) -> (u32, u32) {
+        (
+            self.page.unwrap_or(1).max(1),
+            self.per_page.unwrap_or(25).clamp(1, 200),
+        )
+    }
+}
+
+impl PayoutListQuery {
+    /// Whether an empty result should read "nothing matches" rather than
+    /// "no payouts yet".
+    fn is_filtered(&self) -> bool {
+        [&self.search, &self.wallet, &self.status]
+            .into_iter()
+            .any(|value| value.as_deref().is_some_and(|value| !value.trim().is_empty()))
+            || self.amount.is_some()
+    }
+
+    fn into_filters(self) -> ApiResult<PayoutListFilters> {
+        let mut filters = PayoutListFilters::default();
+
+        filters.page = self.page.unwrap_or(1).max(1);
+        filters.per_page = self.per_page.unwrap_or(filters.per_page).clamp(1, 200);
+
+        filters.search = self.search.and_then(|value| {
+            let trimmed = value.trim().to_string();
+            if trimmed.is_empty() {
+                None
+            } else {
+                Some(trimmed)
+            }
+        });
+
+        filters.wallet = self.wallet.and_then(|value| {
+            let trimmed = value.trim().to_string();
+            if trimmed.is_empty() {
+                None
+            } else {
+                Some(trimmed)
+            }
+        });
+
+        filters.amount = self.amount.filter(|value| !value.is_nan());
+
+        filters.status = self
+            .status
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| {
+                PayoutStatus::parse(&value).ok_or_else(|| {
+                    ApiError::from((
+                        StatusCode::BAD_REQUEST,
+                        format!("Unknown payout status {value}"),
+                    ))
+                })
+            })
+            .transpose()?;
+
+        filters.sort = match self.sort.as_deref() {
+            Some("status") => SortField::Status,
+            _ => SortField::CreatedAt,
+        };
+
+        filters.order = match self.order.as_deref() {
+            Some(value) if value.eq_ignore_ascii_case("asc") => SortOrder::Asc,
+            Some(value) if value.eq_ignore_ascii_case("desc") => SortOrder::Desc,
+            _ => {
+                if filters.sort == SortField::Status {
+                    SortOrder::Asc
+                } else {
+                    SortOrder::Desc
+                }
+            }
+        };
+
+        filters.include_archived = self.include_archived.unwrap_or(false);
+
+        Ok(filters)
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CancelPayoutResponse {
+    pub(crate) success: bool,
+    pub(crate) status: PayoutStatus,
+    pub(crate) callback_dispatched: bool,
+    pub(crate) callback_error: Option<String>,
+    /// The payout after the change, as the deals list shows it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) payout: Option<PayoutDealListItem>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UpsertCancelReasonRequest {
+    code: String,
+    label: String,
+    active: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PayoutFileKind {
+    Proof,
+    Dispute,
+}
+
+impl PayoutFileKind {
+    fn column(self) -> &'static str {
+        match self {
+            PayoutFileKind::Proof => "proofFiles",
+            PayoutFileKind::Dispute => "disputeFiles",
+        }
+    }
+
+    fn prefix(self) -> &'static str {
+        match self {
+            PayoutFileKind::Proof => "proof",
+            PayoutFileKind::Dispute => "dispute",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UploadPayoutFileQuery {
+    kind: PayoutFileKind,
+    filename: Option<String>,
+}
+
+#[derive(Debug, FromRow)]
+pub(crate) struct PayoutFileColumns {
+    #[sqlx(rename = "proofFiles")]
+    proof_files: Option<Vec<String>>,
+    #[sqlx(rename = "disputeFiles")]
+    dispute_files: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PayoutFile {
+    key: String,
+    url: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PayoutFilesResponse {
+    payout_id: String,
+    storage_configured: bool,
+    proof_files: Vec<PayoutFile>,
+    dispute_files: Vec<PayoutFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CancelPayoutRequest {
+    pub(crate) reason: Option<String>,
+    pub(crate) reason_code: Option<String>,
+    /// See [`payout_version`].
+    #[serde(default)]
+    pub(crate) expected_updated_at: Option<UtcTimestamp>,
+}
+
+pub(crate) const MAX_BULK_CANCEL_PAYOUTS: usize = 200;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BulkCancelPayoutsRequest {
+    payout_ids: Vec<String>,
+    reason: Option<String>,
+    reason_code: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BulkCancelPayoutResult {
+    payout_id: String,
+    success: bool,
+    error: Option<String>,
+    error_code: Option<ErrorCode>,
+    callback_dispatched: bool,
+    /// Bulk cancels leave their callbacks to the outbox relay, which paces
+    /// them per merchant.
+    callback_queued: bool,
+    callback_error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BulkCancelPayoutsResponse {
+    cancelled: usize,
+    failed: usize,
+    results: Vec<BulkCancelPayoutResult>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct IndexQuery {
+    token: Option<String>,
+}
+
+/// Deal filters in the page URL (the `/api/deals` parameters) are applied to
+/// the server-rendered rows, so shared links open the same view.
+pub(crate) async fn serve_index(
+    Query(params): Query<IndexQuery>,
+    Query(deal_params): Query<PayoutListQuery>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: Option<TenantScope>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    if state.operator_login && scope.is_none() {
+        return Ok(Redirect::to("/login").into_response());
+    }
+    // `/?token=…` logs a browser in: the token moves into an HttpOnly cookie
+    // and the redirect keeps it out of the address bar and history.
+    if let Some(token) = params
+        .token
+        .filter(|_| state.tenants.is_enabled() && !state.operator_login)
+    {
+        if state.tenants.resolve(Some(&token)).is_none() {
+            return Err(ApiError::from((
+                StatusCode::UNAUTHORIZED,
+                "Unknown tenant token".to_string(),
+            )));
+        }
+        let cookie = format!(
+            "{}={}; Path=/; HttpOnly; SameSite=Strict",
+            tenant::TENANT_COOKIE,
+            token.trim()
+        );
+        return Ok(([(header::SET_COOKIE, cookie)], Redirect::to("/")).into_response());
+    }
+    let Some(scope) = scope else {
+        return Err(ApiError::from((
+            StatusCode::UNAUTHORIZED,
+            "Open the dashboard with ?token=<tenant token>".to_string(),
+        )));
+    };
+
+    // Read before loading, so every change announced up to it is included.
+    let events_version = state.event_stream.version();
+    let lang = i18n::Lang::from_headers(&headers);
+    let theme = frontend::Theme::from_headers(&headers);
+    let trader_filters = TraderListFilters {
+        merchant_ids: scope.merchant_ids().map(<[String]>::to_vec),
+        ..TraderListFilters::default()
+    };
+    let traders = load_traders_with_limits(&state, &trader_filters)
+        .await
+        .map_err(internal_error)?;
+    let policy = state.settings.priority_policy().await;
+    let payouts = fetch_unassigned_payouts_page(&state.pool, &policy, scope.merchant_ids(), 1, 25)
+        .await
+        .map_err(internal_error)?;
+    let deals_filtered = deal_params.is_filtered();
+    let deal_filters = PayoutListFilters {
+        merchant_ids: scope.merchant_ids().map(<[String]>::to_vec),
+        ..deal_params.into_filters()?
+    };
+    let mut deals = fetch_payouts_page(&state.pool, &deal_filters)
+        .await
+        .map_err(internal_error)?;
+    deals.annotate_rates(&*state.rates.read().await);
+    let deals = deals.into_response();
+    let settings = state.settings.auto_config();
+    let operator = if state.operator_login {
+        auth::current_operator(&session).await?
+    } else {
+        None
+    };
+    let csrf_token = auth::csrf_token(&session).await?;
+    let preferences = preferences::current_preferences(&state, &session, &scope).await?;
+    let summary = fetch_stats_summary(&state.pool, &policy, scope.merchant_ids())
+        .await
+        .map_err(internal_error)?;
+    let snapshot = frontend::DashboardSnapshot {
+        traders,
+        payouts,
+        deals,
+        settings,
+        tenant: scope.name().map(str::to_string),
+        operator: operator.map(|operator| operator.username),
+        csrf_token,
+        summary,
+        environment: state.environment.clone(),
+        deals_filtered,
+        deals_layout: preferences.deals_table,
+        events_version,
+    };
+    Ok(Html(frontend::render_dashboard_page(snapshot, lang, theme)).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct LoginForm {
+    username: String,
+    password: String,
+    #[serde(default)]
+    csrf: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct LogoutForm {
+    #[serde(default)]
+    csrf: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AssetQuery {
+    v: Option<String>,
+}
+
+/// Fingerprinted URLs (`?v=<current version>`) are cached for a year; other
+/// requests revalidate against the version as ETag.
+fn serve_asset(asset: &frontend::Asset, query: AssetQuery, headers: &HeaderMap) -> Response {
+    let etag = format!("\"{}\"", asset.version);
+    let cache_control = if query.v.as_deref() == Some(asset.version.as_str()) {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    };
+    let fresh = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|candidate| candidate.trim() == etag));
+    let cache_headers = [
+        (header::ETAG, etag.clone()),
+        (header::CACHE_CONTROL, cache_control.to_string()),
+    ];
+    if fresh {
+        return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+    }
+    (
+        cache_headers,
+        [(header::CONTENT_TYPE, asset.content_type)],
+        asset.body,
+    )
+        .into_response()
+}
+
+pub(crate) async fn serve_app_css(Query(query): Query<AssetQuery>, headers: HeaderMap) -> Response {
+    serve_asset(&frontend::APP_CSS, query, &headers)
+}
+
+pub(crate) async fn serve_app_js(Query(query): Query<AssetQuery>, headers: HeaderMap) -> Response {
+    serve_asset(&frontend::APP_JS, query, &headers)
+}
+
+pub(crate) async fn login_page(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    if !state.operator_login || auth::current_operator(&session).await?.is_some() {
+        return Ok(Redirect::to("/").into_response());
+    }
+    let csrf_token = auth::csrf_token(&session).await?;
+    let lang = i18n::Lang::from_headers(&headers);
+    let theme = frontend::Theme::from_headers(&headers);
+    Ok(Html(frontend::render_login_page(lang, theme, &csrf_token, None)).into_response())
+}
+
+pub(crate) async fn login(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    headers: HeaderMap,
+    Form(form): Form<LoginForm>,
+) -> ApiResult<Response> {
+    if !state.operator_login {
+        return Ok(Redirect::to("/").into_response());
+    }
+    auth::verify_csrf(&session, &form.csrf).await?;
+    let operator = auth::verify_login(&state.pool, &form.username, &form.password)
+        .await
+        .map_err(internal_error)?;
+    let Some(operator) = operator else {
+        println!("[auth] Failed login for {}", form.username.trim());
+        let lang = i18n::Lang::from_headers(&headers);
+        let theme = frontend::Theme::from_headers(&headers);
+        let page = frontend::render_login_page(lang, theme, &form.csrf, Some("login.invalid"));
+        return Ok((StatusCode::UNAUTHORIZED, Html(page)).into_response());
+    };
+    auth::sign_in(&session, &operator).await?;
+    println!("[auth] Operator {} signed in", operator.username);
+    Ok(Redirect::to("/").into_response())
+}
+
+pub(crate) async fn logout(
+    Extension(session): Extension<Session>,
+    Form(form): Form<LogoutForm>,
+) -> ApiResult<Response> {
+    auth::verify_csrf(&session, &form.csrf).await?;
+    if let Some(operator) = auth::current_operator(&session).await? {
+        println!("[auth] Operator {} signed out", operator.username);
+    }
+    auth::sign_out(&session).await?;
+    Ok(Redirect::to("/login").into_response())
+}
+
+pub(crate) async fn get_traders(
+    Query(params): Query<TraderListQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+    headers: HeaderMap,
+) -> ApiResult<ETagged<TraderListResponse>> {
+    let filters = TraderListFilters {
+        merchant_ids: scope.merchant_ids().map(<[String]>::to_vec),
+        ..params.into_filters()
+    };
+    let traders = load_traders_with_limits(&state, &filters)
+        .await
+        .map_err(internal_error)?;
+    Ok(ETagged::new(&headers, traders))
+}
+
+/// Rendered table rows for the dashboard to swap in; the pagination of the
+/// page travels in `X-Pagination` as JSON.
+fn fragment_response(rows: String, pagination: &Pagination) -> ApiResult<Response> {
+    let pagination = serde_json::to_string(pagination).map_err(internal_error)?;
+    Ok(([("x-pagination", pagination)], Html(rows)).into_response())
+}
+
+pub(crate) async fn get_traders_fragment(
+    Query(params): Query<TraderListQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let filters = TraderListFilters {
+        merchant_ids: scope.merchant_ids().map(<[String]>::to_vec),
+        ..params.into_filters()
+    };
+    let empty_key = if filters.search.is_some() {
+        "traders.empty-filtered"
+    } else {
+        "traders.empty"
+    };
+    let traders = load_traders_with_limits(&state, &filters)
+        .await
+        .map_err(internal_error)?;
+    let rows = frontend::render_trader_rows(
+        traders.items,
+        i18n::Lang::from_headers(&headers),
+        empty_key,
+        filters.sort == TraderSortField::Team,
+    );
+    fragment_response(rows, &traders.pagination)
+}
+
+pub(crate) async fn get_unassigned_payouts(
+    Query(params): Query<UnassignedPayoutListQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+    headers: HeaderMap,
+) -> ApiResult<ETagged<UnassignedPayoutListResponse>> {
+    let (page, per_page) = params.page_and_size();
+    let policy = state.settings.priority_policy().await;
+    fetch_unassigned_payouts_page(&state.pool, &policy, scope.merchant_ids(), page, per_page)
+        .await
+        .map(|payouts| ETagged::new(&headers, payouts))
+        .map_err(internal_error)
+}
+
+/// Open payouts flagged as possible duplicates, with the payout each one
+/// repeats. `$1` is the merchant scope.
+pub(crate) const DUPLICATE_PAYOUTS_QUERY: &str = r#"
+    SELECT
+        p."id",
+        p."numericId",
+        p."amount",
+        p."wallet",
+        p."merchantId",
+        p."createdAt",
+        df."flaggedAt",
+        df."duplicateOf",
+        o."numericId" AS "duplicateOfNumericId",
+        o."status"::text AS "duplicateOfStatus",
+        o."createdAt" AS "duplicateOfCreatedAt"
+    FROM "PayoutDuplicateFlag" df
+    JOIN "Payout" p ON p."id" = df."payoutId"
+    LEFT JOIN "Payout" o ON o."id" = df."duplicateOf"
+    WHERE df."approvedAt" IS NULL
+      AND p."status" = 'CREATED'
+      AND ($1::text[] IS NULL OR p."merchantId" = ANY($1::text[]))
+"#;
+
+#[derive(Debug, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DuplicatePayout {
+    id: String,
+    #[sqlx(rename = "numericId")]
+    numeric_id: i32,
+    amount: Option<f64>,
+    wallet: Option<String>,
+    #[sqlx(rename = "merchantId")]
+    merchant_id: Option<String>,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+    #[sqlx(rename = "flaggedAt")]
+    flagged_at: UtcTimestamp,
+    #[sqlx(rename = "duplicateOf")]
+    duplicate_of: String,
+    #[sqlx(rename = "duplicateOfNumericId")]
+    duplicate_of_numeric_id: Option<i32>,
+    #[sqlx(rename = "duplicateOfStatus")]
+    duplicate_of_status: Option<PayoutStatus>,
+    #[sqlx(rename = "duplicateOfCreatedAt")]
+    duplicate_of_created_at: Option<UtcTimestamp>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DuplicatePayoutListResponse {
+    items: Vec<DuplicatePayout>,
+    pagination: Pagination,
+}
+
+pub(crate) async fn get_duplicate_payouts(
+    Query(params): Query<UnassignedPayoutListQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<DuplicatePayoutListResponse>> {
+    let (page, per_page) = params.page_and_size();
+    let config = state.settings.auto_config();
+    if config.duplicate_window_minutes > 0 {
+        let mut tx = state.pool.begin().await.map_err(internal_error)?;
+        duplicates::flag_duplicates(&mut tx, config.duplicate_window_minutes)
+            .await
+            .map_err(internal_error)?;
+        tx.commit().await.map_err(internal_error)?;
+    }
+
+    let total: i64 = sqlx::query_scalar(&format!(
+        "SELECT COUNT(*)::bigint FROM ({DUPLICATE_PAYOUTS_QUERY}) d"
+    ))
+    .bind(scope.merchant_ids())
+    .fetch_one(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let items = sqlx::query_as::<_, DuplicatePayout>(&format!(
+        r#"{DUPLICATE_PAYOUTS_QUERY} ORDER BY df."flaggedAt" DESC LIMIT $2 OFFSET $3"#
+    ))
+    .bind(scope.merchant_ids())
+    .bind(per_page as i64)
+    .bind(((page - 1) as i64) * per_page as i64)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    Ok(Json(DuplicatePayoutListResponse {
+        items,
+        pagination: Pagination::new(total, page, per_page),
+    }))
+}
+
+/// Clears the duplicate flag so auto distribution picks the payout up again.
+/// Rejecting a duplicate is done by cancelling the payout.
+pub(crate) async fn approve_duplicate_payout(
+    Path(payout_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+) -> ApiResult<Json<AssignPayoutResponse>> {
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    let merchant_id: Option<Option<String>> = sqlx::query_scalar(
+        r#"
+        UPDATE "PayoutDuplicateFlag" df
+        SET "approvedAt" = CURRENT_TIMESTAMP,
+            "approvedBy" = $2
+        FROM "Payout" p
+        WHERE df."payoutId" = $1
+          AND p."id" = df."payoutId"
+          AND df."approvedAt" IS NULL
+          AND ($3::text[] IS NULL OR p."merchantId" = ANY($3::text[]))
+        RETURNING p."merchantId"
+        "#,
+    )
+    .bind(&payout_id)
+    .bind(actor.as_deref())
+    .bind(scope.merchant_ids())
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    let Some(merchant_id) = merchant_id else {
+        return Err(ApiError::from((
+            StatusCode::NOT_FOUND,
+            "Payout is not flagged as a duplicate".to_string(),
+        )));
+    };
+
+    record_payout_audit(
+        &mut *tx,
+        &payout_id,
+        "duplicate-approved",
+        actor.as_deref(),
+        None,
+        None,
+    )
+    .await
+    .map_err(internal_error)?;
+    outbox::enqueue_event(
+        &mut tx,
+        &ServerEvent::payouts_updated("manual").for_merchants(merchant_id),
+    )
+    .await
+    .map_err(internal_error)?;
+    tx.commit().await.map_err(internal_error)?;
+    state.outbox_notify.notify_one();
+
+    println!(
+        "[duplicates] Payout {payout_id} approved by {}",
+        actor.as_deref().unwrap_or("operator")
+    );
+    Ok(Json(AssignPayoutResponse {
+        success: true,
+        payout: None,
+    }))
+}
+
+pub(crate) async fn get_all_payouts(
+    Query(params): Query<PayoutListQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+    headers: HeaderMap,
+) -> ApiResult<ETagged<PayoutListResponse>> {
+    list_deals_internal(&state, params, &scope)
+        .await
+        .map(|deals| ETagged::new(&headers, deals))
+}
+
+pub(crate) async fn get_deals_fragment(
+    Query(params): Query<PayoutListQuery>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let filtered = params.is_filtered();
+    let layout = preferences::current_preferences(&state, &session, &scope)
+        .await?
+        .deals_table;
+    let deals = list_deals_internal(&state, params, &scope).await?;
+    let rows = frontend::render_deal_rows(
+        deals.items,
+        layout,
+        i18n::Lang::from_headers(&headers),
+        if filtered {
+            "deals.empty-filtered"
+        } else {
+            "deals.empty"
+        },
+    );
+    fragment_response(rows, &deals.pagination)
+}
+
+pub(crate) async fn list_deals_internal(
+    state: &AppState,
+    params: PayoutListQuery,
+    scope: &TenantScope,
+) -> ApiResult<PayoutListResponse> {
+    let filters = PayoutListFilters {
+        merchant_ids: scope.merchant_ids().map(<[String]>::to_vec),
+        ..params.into_filters()?
+    };
+    let mut data = fetch_payouts_page(&state.pool, &filters)
+        .await
+        .map_err(internal_error)?;
+    data.annotate_rates(&*state.rates.read().await);
+    Ok(data.into_response())
+}
+
+pub(crate) async fn get_rates(State(state): State<AppState>) -> Json<rates::RateSnapshot> {
+    Json(state.rates.read().await.clone())
+}
+
+/// Merges Payout lifecycle columns, the service audit trail and callback
+/// history. Status changes made by the platform only carry `updatedAt`, so
+/// completion/dispute times are the last update of the row.
+pub(crate) async fn get_payout_timeline(
+    Path(payout_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<PayoutTimelineResponse>> {
+    let payout = sqlx::query_as::<_, TimelinePayout>(
+        r#"
+        SELECT
+            p."status"::text AS "status",
+            p."traderId",
+            p."disputeMessage",
+            p."createdAt",
+            p."acceptedAt",
+            p."cancelledAt",
+            p."updatedAt"
+        FROM "Payout" p
+        WHERE p."id" = $1
+          AND ($2::text[] IS NULL OR p."merchantId" = ANY($2::text[]))
+        "#,
+    )
+    .bind(&payout_id)
+    .bind(scope.merchant_ids())
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(ApiError::payout_not_found)?;
+
+    let audit = sqlx::query_as::<_, PayoutAuditRow>(
+        r#"
+        SELECT "action", "source", "actor", "traderId", "details", "createdAt"
+        FROM "PayoutAuditLog"
+        WHERE "payoutId" = $1
+        ORDER BY "createdAt"
+        "#,
+    )
+    .bind(&payout_id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let callbacks = sqlx::query_as::<_, CallbackHistoryRow>(
+        r#"
+        SELECT "url", "statusCode", "error", "createdAt"
+        FROM "PayoutCallbackHistory"
+        WHERE "payoutId" = $1
+        ORDER BY "createdAt"
+        "#,
+    )
+    .bind(&payout_id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let notes = notes::load_notes(&state.pool, &payout_id)
+        .await
+        .map_err(internal_error)?;
+
+    let mut events = vec![TimelineEvent::new(payout.created_at, "created", "platform")];
+
+    let audited_cancel = audit.iter().any(|row| row.action == "cancelled");
+    events.extend(audit.into_iter().map(|row| TimelineEvent {
+        at: row.created_at,
+        kind: row.action,
+        source: Some(row.source),
+        actor: row.actor,
+        trader_id: row.trader_id,
+        details: row.details,
+    }));
+
+    if let Some(accepted_at) = payout.accepted_at {
+        events.push(TimelineEvent {
+            trader_id: payout.trader_id.clone(),
+            ..TimelineEvent::new(accepted_at, "accepted", "trader")
+        });
+    }
+    if let Some(cancelled_at) = payout.cancelled_at.filter(|_| !audited_cancel) {
+        events.push(TimelineEvent::new(cancelled_at, "cancelled", "platform"));
+    }
+    let terminal = match payout.status {
+        status if status.is_completed() => Some("completed"),
+        PayoutStatus::Failed => Some("failed"),
+        status if status.is_disputed() => Some("disputed"),
+        PayoutStatus::Expired => Some("expired"),
+        _ => None,
+    };
+    if let Some(kind) = terminal {
+        events.push(TimelineEvent {
+            details: payout
+                .dispute_message
+                .clone()
+                .filter(|_| kind == "disputed")
+                .map(|message| serde_json::json!({ "message": message })),
+            ..TimelineEvent::new(payout.updated_at, kind, "platform")
+        });
+    }
+
+    events.extend(callbacks.into_iter().map(|row| TimelineEvent {
+        details: Some(serde_json::json!({
+            "url": row.url,
+            "statusCode": row.status_code,
+            "error": row.error,
+        })),
+        ..TimelineEvent::new(row.created_at, "callback", "service")
+    }));
+
+    events.extend(notes.into_iter().map(|note| TimelineEvent {
+        actor: note.author,
+        details: Some(serde_json::json!({ "message": note.body })),
+        ..TimelineEvent::new(note.created_at, "note", "manual")
+    }));
+
+    events.sort_by_key(|event| event.at);
+
+    Ok(Json(PayoutTimelineResponse {
+        payout_id,
+        status: payout.status,
+        events,
+    }))
+}
+
+pub(crate) async fn lookup_payout(
+    Query(params): Query<LookupQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<PayoutLookup>> {
+    let payout = sqlx::query_as::<_, PayoutLookup>(
+        r#"
+        SELECT
+            p."id",
+            p."numericId",
+            p."status"::text AS "status",
+            p."amount",
+            p."bank",
+            p."wallet",
+            p."merchantId",
+            p."traderId",
+            EXISTS (
+                SELECT 1 FROM "PayoutArchive" pa WHERE pa."payoutId" = p."id"
+            ) AS "archived",
+            p."createdAt"
+        FROM "Payout" p
+        WHERE p."numericId" = $1
+          AND ($2::text[] IS NULL OR p."merchantId" = ANY($2::text[]))
+        "#,
+    )
+    .bind(params.numeric_id)
+    .bind(scope.merchant_ids())
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(ApiError::payout_not_found)?;
+
+    Ok(Json(payout))
+}
+
+pub(crate) async fn get_stats_timeseries(
+    Query(params): Query<TimeseriesQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<TimeseriesResponse>> {
+    let hours = params.hours.unwrap_or(24).clamp(1, 168);
+    let points = fetch_timeseries(&state.pool, hours, scope.merchant_ids())
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(TimeseriesResponse { hours, points }))
+}
+
+pub(crate) async fn get_stats_summary(
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<StatsSummary>> {
+    let policy = state.settings.priority_policy().await;
+    fetch_stats_summary(&state.pool, &policy, scope.merchant_ids())
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+pub(crate) async fn cancel_payout(
+    Path(payout_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    headers: HeaderMap,
+    Json(mut request): Json<CancelPayoutRequest>,
+) -> ApiResult<Json<CancelPayoutResponse>> {
+    request.expected_updated_at = payout_version::expected(&headers, request.expected_updated_at)?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let mut response =
+        cancel_payout_internal(&state, &payout_id, request, &scope, actor.as_deref()).await?;
+    response.payout = fetch_updated_payout(&state, &payout_id).await?;
+    Ok(Json(response))
+}
+
+/// A payout just changed by an operator, for the response that confirms it.
+pub(crate) async fn fetch_updated_payout(
+    state: &AppState,
+    payout_id: &str,
+) -> ApiResult<Option<PayoutDealListItem>> {
+    let mut payout = fetch_payout_deal(&state.pool, payout_id)
+        .await
+        .map_err(internal_error)?;
+    if let Some(payout) = &mut payout {
+        payout.annotate_rate(&*state.rates.read().await);
+    }
+    Ok(payout)
+}
+
+pub(crate) async fn cancel_payout_internal(
+    state: &AppState,
+    payout_id: &str,
+    request: CancelPayoutRequest,
+    scope: &TenantScope,
+    actor: Option<&str>,
+) -> ApiResult<CancelPayoutResponse> {
+    let reason = normalize_optional_text(request.reason);
+    let reason_code = normalize_optional_text(request.reason_code);
+    validate_cancel_reason_code(&state.pool, reason_code.as_deref()).await?;
+
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    payout_version::check(&mut tx, payout_id, request.expected_updated_at).await?;
+    state
+        .cancel_approval
+        .check_direct_cancel(&mut tx, payout_id)
+        .await?;
+
+    let payout = match cancel_payout_in_tx(
+        &mut tx,
+        payout_id,
+        reason.as_deref(),
+        reason_code.as_deref(),
+        scope,
+        actor,
+    )
+    .await
+    {
+        Ok(payout) => payout,
+        Err(err) => {
+            tx.rollback().await.ok();
+            return Err(err);
+        }
+    };
+
+    let callback_id =
+        outbox::enqueue_callback(&mut tx, &payout.id, &build_cancel_callback_payload(&payout))
+            .await
+            .map_err(internal_error)?;
+    outbox::enqueue_event(
+        &mut tx,
+        &ServerEvent::payouts_updated("manual-cancel").for_merchants(payout.merchant_id.clone()),
+    )
+    .await
+    .map_err(internal_error)?;
+
+    tx.commit().await.map_err(internal_error)?;
+
+    let (callback_dispatched, callback_error) = dispatch_queued_callback(state, &callback_id).await;
+    state.outbox_notify.notify_one();
+
+    Ok(CancelPayoutResponse {
+        success: true,
+        status: PayoutStatus::Cancelled,
+        callback_dispatched,
+        callback_error,
+        payout: None,
+    })
+}
+
+pub(crate) async fn bulk_cancel_payouts(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<BulkCancelPayoutsRequest>,
+) -> ApiResult<Json<BulkCancelPayoutsResponse>> {
+    state
+        .environment
+        .ensure_unlocked(environment::SafetyLock::BulkCancel)?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let mut seen = HashSet::new();
+    let payout_ids: Vec<String> = request
+        .payout_ids
+        .into_iter()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty() && seen.insert(value.clone()))
+        .collect();
+
+    if payout_ids.is_empty() {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "At least one payout ID is required".to_string(),
+        )));
+    }
+    if payout_ids.len() > MAX_BULK_CANCEL_PAYOUTS {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            format!("At most {MAX_BULK_CANCEL_PAYOUTS} payouts can be cancelled at once"),
+        )));
+    }
+
+    let reason = normalize_optional_text(request.reason);
+    let reason_code = normalize_optional_text(request.reason_code);
+    validate_cancel_reason_code(&state.pool, reason_code.as_deref()).await?;
+
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    let mut cancelled = Vec::new();
+    let mut results = Vec::with_capacity(payout_ids.len());
+
+    for payout_id in &payout_ids {
+        let result = match state
+            .cancel_approval
+            .check_direct_cancel(&mut tx, payout_id)
+            .await
+        {
+            Ok(()) => {
+                cancel_payout_in_tx(
+                    &mut tx,
+                    payout_id,
+                    reason.as_deref(),
+                    reason_code.as_deref(),
+                    &scope,
+                    actor.as_deref(),
+                )
+                .await
+            }
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(payout) => {
+                outbox::enqueue_callback(
+                    &mut tx,
+                    &payout.id,
+                    &build_cancel_callback_payload(&payout),
+                )
+                .await
+                .map_err(internal_error)?;
+                cancelled.push(payout);
+            }
+            Err(err) if err.status != StatusCode::INTERNAL_SERVER_ERROR => {
+                results.push(BulkCancelPayoutResult {
+                    payout_id: payout_id.clone(),
+                    success: false,
+                    error: Some(err.message),
+                    error_code: Some(err.code),
+                    callback_dispatched: false,
+                    callback_queued: false,
+                    callback_error: None,
+                });
+            }
+            Err(err) => {
+                tx.rollback().await.ok();
+                return Err(err);
+            }
+        }
+    }
+
+    if !cancelled.is_empty() {
+        let merchants = cancelled
+            .iter()
+            .filter_map(|payout| payout.merchant_id.clone());
+        outbox::enqueue_event(
+            &mut tx,
+            &ServerEvent::payouts_updated("manual-bulk-cancel").for_merchants(merchants),
+        )
+        .await
+        .map_err(internal_error)?;
+    }
+
+    tx.commit().await.map_err(internal_error)?;
+
+    for payout in &cancelled {
+        results.push(BulkCancelPayoutResult {
+            payout_id: payout.id.clone(),
+            success: true,
+            error: None,
+            error_code: None,
+            callback_dispatched: false,
+            callback_queued: true,
+            callback_error: None,
+        });
+    }
+
+    state.outbox_notify.notify_one();
+
+    println!(
+        "[manual] Bulk cancel: {} cancelled, {} rejected",
+        cancelled.len(),
+        payout_ids.len() - cancelled.len()
+    );
+
+    results.sort_by_key(|result| {
+        payout_ids
+            .iter()
+            .position(|id| *id == result.payout_id)
+            .unwrap_or(usize::MAX)
+    });
+
+    Ok(Json(BulkCancelPayoutsResponse {
+        cancelled: cancelled.len(),
+        failed: payout_ids.len() - cancelled.len(),
+        results,
+    }))
+}
+
+pub(crate) async fn get_payout_files(
+    Path(payout_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<PayoutFilesResponse>> {
+    load_payout_files(&state, &payout_id, scope.merchant_ids())
+        .await
+        .map(Json)
+}
+
+pub(crate) async fn upload_payout_file(
+    Path(payout_id): Path<String>,
+    Query(params): Query<UploadPayoutFileQuery>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<Json<PayoutFilesResponse>> {
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let Some(storage) = state.storage.as_ref() else {
+        return Err(ApiError::from((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "File storage is not configured".to_string(),
+        )));
+    };
+    if body.is_empty() {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "File is empty".to_string(),
+        )));
+    }
+
+    let merchant_id: Option<Option<String>> =
+        sqlx::query_scalar(r#"SELECT "merchantId" FROM "Payout" WHERE "id" = $1"#)
+            .bind(&payout_id)
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(internal_error)?;
+    let Some(merchant_id) = merchant_id.filter(|id| scope.allows_merchant(id.as_deref())) else {
+        return Err(ApiError::payout_not_found());
+    };
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+    let key = format!(
+        "payouts/{}/{}/{}-{}",
+        payout_id,
+        params.kind.prefix(),
+        Uuid::new_v4(),
+        sanitize_file_name(params.filename.as_deref())
+    );
+
+    storage
+        .put_object(&state.http_client, &key, &content_type, body.to_vec())
+        .await
+        .map_err(|err| (StatusCode::BAD_GATEWAY, err.to_string()))?;
+
+    let column = params.kind.column();
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    sqlx::query(&format!(
+        r#"
+        UPDATE "Payout"
+        SET "{column}" = array_append(COALESCE("{column}", ARRAY[]::text[]), $2),
+            "updatedAt" = CURRENT_TIMESTAMP
+        WHERE "id" = $1
+        "#
+    ))
+    .bind(&payout_id)
+    .bind(&key)
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    record_payout_audit(
+        &mut *tx,
+        &payout_id,
+        "file-attached",
+        actor.as_deref(),
+        None,
+        Some(serde_json::json!({ "kind": params.kind.prefix(), "key": key })),
+    )
+    .await
+    .map_err(internal_error)?;
+    outbox::enqueue_event(
+        &mut tx,
+        &ServerEvent::payouts_updated("manual-file-upload").for_merchants(merchant_id),
+    )
+    .await
+    .map_err(internal_error)?;
+    tx.commit().await.map_err(internal_error)?;
+    state.outbox_notify.notify_one();
+
+    println!(
+        "[manual] Attached {} file {} to payout {}",
+        params.kind.prefix(),
+        key,
+        payout_id
+    );
+
+    load_payout_files(&state, &payout_id, scope.merchant_ids())
+        .await
+        .map(Json)
+}
+
+pub(crate) async fn load_payout_files(
+    state: &AppState,
+    payout_id: &str,
+    merchant_ids: Option<&[String]>,
+) -> ApiResult<PayoutFilesResponse> {
+    let files = sqlx::query_as::<_, PayoutFileColumns>(
+        r#"
+        SELECT "proofFiles", "disputeFiles"
+        FROM "Payout"
+        WHERE "id" = $1
+          AND ($2::text[] IS NULL OR "merchantId" = ANY($2::text[]))
+        "#,
+    )
+    .bind(payout_id)
+    .bind(merchant_ids)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let Some(files) = files else {
+        return Err(ApiError::payout_not_found());
+    };
+
+    let sign = |entries: Option<Vec<String>>| -> Vec<PayoutFile> {
+        entries
+            .unwrap_or_default()
+            .into_iter()
+            .map(|key| {
+                let url = if key.starts_with("http://") || key.starts_with("https://") {
+                    Some(key.clone())
+                } else {
+                    state.storage.as_ref().and_then(|storage| {
+                        storage
+                            .presign_get(&key)
+                            .inspect_err(|err| eprintln!("[files] Failed to sign {key}: {err:?}"))
+                            .ok()
+                    })
+                };
+                PayoutFile { key, url }
+            })
+            .collect()
+    };
+
+    Ok(PayoutFilesResponse {
+        payout_id: payout_id.to_string(),
+        storage_configured: state.storage.is_some(),
+        proof_files: sign(files.proof_files),
+        dispute_files: sign(files.dispute_files),
+    })
+}
+
+pub(crate) fn sanitize_file_name(name: Option<&str>) -> String {
+    let sanitized: String = name
+        .unwrap_or_default()
+        .trim()
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || matches!(ch, '.' | '-' | '_') {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .take(100)
+        .collect();
+    let sanitized = sanitized.trim_matches('.');
+    if sanitized.is_empty() {
+        "file".to_string()
+    } else {
+        sanitized.to_string()
+    }
+}
+
+pub(crate) async fn get_cancel_reasons(
+    State(state): State<AppState>,
+) -> ApiResult<Json<Vec<CancelReasonCode>>> {
+    fetch_cancel_reasons(&state.pool)
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+pub(crate) async fn upsert_cancel_reason(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Json(request): Json<UpsertCancelReasonRequest>,
+) -> ApiResult<Json<CancelReasonCode>> {
+    scope.require_unrestricted()?;
+    let code = request.code.trim().to_ascii_uppercase();
+    let valid_code = !code.is_empty()
+        && code.len() <= 64
+        && code
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+    if !valid_code {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "Reason code must be 1-64 characters of A-Z, 0-9 or _".to_string(),
+        )));
+    }
+
+    let label = request.label.trim();
+    if label.is_empty() {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "Label is required".to_string(),
+        )));
+    }
+
+    let reason = sqlx::query_as::<_, CancelReasonCode>(
+        r#"
+        INSERT INTO "CancelReasonCode" ("code", "label", "active")
+        VALUES ($1, $2, $3)
+        ON CONFLICT ("code") DO UPDATE
+        SET "label" = EXCLUDED."label",
+            "active" = EXCLUDED."active",
+            "updatedAt" = CURRENT_TIMESTAMP
+        RETURNING "code", "label", "active", "createdAt", "updatedAt"
+        "#,
+    )
+    .bind(&code)
+    .bind(label)
+    .bind(request.active.unwrap_or(true))
+    .fetch_one(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    println!(
+        "[settings] Cancel reason {} saved (active: {})",
+        reason.code, reason.active
+    );
+    let _ = state.event_tx.send(ServerEvent::cancel_reasons_updated());
+
+    Ok(Json(reason))
+}
+
+pub(crate) async fn validate_cancel_reason_code(
+    pool: &PgPool,
+    code: Option<&str>,
+) -> ApiResult<()> {
+    let Some(code) = code else {
+        return Ok(());
+    };
+
+    let active: Option<bool> =
+        sqlx::query_scalar(r#"SELECT "active" FROM "CancelReasonCode" WHERE "code" = $1"#)
+            .bind(code)
+            .fetch_optional(pool)
+            .await
+            .map_err(internal_error)?;
+
+    match active {
+        Some(true) => Ok(()),
+        Some(false) => Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            format!("Cancel reason code {code} is inactive"),
+        ))),
+        None => Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            format!("Unknown cancel reason code {code}"),
+        ))),
+    }
+}
+
+pub(crate) fn normalize_optional_text(value: Option<String>) -> Option<String> {
+    value
+        .as_ref()
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+        .map(|value| value.to_string())
+}
+
+/// Locks the payout, checks that it can still be cancelled and marks it
+/// `CANCELLED`. The caller owns the transaction and decides whether to commit.
+pub(crate) async fn cancel_payout_in_tx(
+    tx: &mut Transaction<'_, Postgres>,
+    payout_id: &str,
+    reason: Option<&str>,
+    reason_code: Option<&str>,
+    scope: &TenantScope,
+    actor: Option<&str>,
+) -> ApiResult<PayoutDetails> {
+    let payout = sqlx::query_as::<_, PayoutDetails>(&format!(
+        r#"
+        SELECT
+            p."id",
+            p."numericId" AS "numeric_id",
+            p."amount",
+            p."amountUsdt" AS "amount_usdt",
+            {PAYOUT_CURRENCY_SQL} AS "currency",
+            p."status"::text AS "status",
+            p."wallet",
+            p."bank",
+            p."externalReference" AS "external_reference",
+            p."merchantMetadata" AS "merchant_metadata",
+            p."proofFiles" AS "proof_files",
+            p."disputeFiles" AS "dispute_files",
+            p."disputeMessage" AS "dispute_message",
+            p."cancelReason" AS "cancel_reason",
+            p."cancelReasonCode" AS "cancel_reason_code",
+            p."merchantId" AS "merchant_id"
+        FROM "Payout" p
+        WHERE p."id" = $1
+          AND ($2::text[] IS NULL OR p."merchantId" = ANY($2::text[]))
+        FOR UPDATE OF p
+        "#
+    ))
+    .bind(payout_id)
+    .bind(scope.merchant_ids())
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(internal_error)?;
+
+    let mut payout = match payout {
+        Some(payout) => payout,
+        None => {
+            return Err(ApiError::payout_not_found());
+        }
+    };
+
+    let previous_status = payout.status;
+    payout.status = payout_transitions::transition(previous_status, PayoutStatus::Cancelled)?;
+
+    let update_result = sqlx::query!(
+        r#"
+        UPDATE "Payout"
+        SET "status" = 'CANCELLED',
+            "cancelledAt" = CURRENT_TIMESTAMP,
+            "cancelReason" = COALESCE($2, "cancelReason"),
+            "cancelReasonCode" = COALESCE($3, "cancelReasonCode"),
+            "updatedAt" = CURRENT_TIMESTAMP
+        WHERE "id" = $1
+        "#,
+        payout_id,
+        reason,
+        reason_code
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(internal_error)?;
+
+    if update_result.rows_affected() == 0 {
+        return Err(ApiError::from((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to cancel payout".to_string(),
+        )));
+    }
+
+    record_payout_audit(
+        &mut **tx,
+        payout_id,
+        "cancelled",
+        actor,
+        None,
+        Some(serde_json::json!({
+            "reasonCode": reason_code,
+            "reason": reason,
+            "previousStatus": previous_status,
+        })),
+    )
+    .await
+    .map_err(internal_error)?;
+    freeze::release_payouts(tx, &[payout_id.to_string()])
+        .await
+        .map_err(internal_error)?;
+
+    if let Some(reason_value) = reason {
+        payout.cancel_reason = Some(reason_value.to_string());
+    }
+
+    if let Some(code_value) = reason_code {
+        payout.cancel_reason_code = Some(code_value.to_string());
+    }
+
+    Ok(payout)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TraderTokenResponse {
+    trader_id: String,
+    token: String,
+}
+
+pub(crate) async fn issue_trader_token(
+    Path(trader_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<TraderTokenResponse>> {
+    scope.require_unrestricted()?;
+    let exists: bool =
+        sqlx::query_scalar(r#"SELECT EXISTS (SELECT 1 FROM "User" WHERE "id" = $1)"#)
+            .bind(&trader_id)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(internal_error)?;
+    if !exists {
+        return Err(ApiError::trader_not_found());
+    }
+
+    let token = trader_auth::issue_token(&state.pool, &trader_id)
+        .await
+        .map_err(internal_error)?;
+    println!("[self] Issued a new self-service token for trader {trader_id}");
+    Ok(Json(TraderTokenResponse { trader_id, token }))
+}
+
+pub(crate) async fn revoke_trader_token(
+    Path(trader_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<StatusCode> {
+    scope.require_unrestricted()?;
+    let revoked = trader_auth::revoke_token(&state.pool, &trader_id)
+        .await
+        .map_err(internal_error)?;
+    if !revoked {
+        return Err(ApiError::from((
+            StatusCode::NOT_FOUND,
+            "Trader has no active token".to_string(),
+        )));
+    }
+    println!("[self] Revoked the self-service token of trader {trader_id}");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SelfPauseRequest {
+    #[serde(default = "default_true")]
+    paused: bool,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+pub(crate) fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SelfPauseResponse {
+    trader_id: String,
+    paused: bool,
+    paused_at: Option<UtcTimestamp>,
+}
+
+/// Lets a trader stop (or resume) receiving new payouts. Payouts already
+/// assigned to the trader are not touched.
+pub(crate) async fn pause_self(
+    State(state): State<AppState>,
+    trader: TraderScope,
+    Json(request): Json<SelfPauseRequest>,
+) -> ApiResult<Json<SelfPauseResponse>> {
+    let trader_id = trader.trader_id().to_string();
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+
+    let paused_at = if request.paused {
+        let paused_at: UtcTimestamp = sqlx::query_scalar(
+            r#"
+            INSERT INTO "TraderPause" ("traderId", "reason")
+            VALUES ($1, $2)
+            ON CONFLICT ("traderId") DO UPDATE
+            SET "reason" = EXCLUDED."reason"
+            RETURNING "pausedAt"
+            "#,
+        )
+        .bind(&trader_id)
+        .bind(normalize_optional_text(request.reason))
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+        Some(paused_at)
+    } else {
+        sqlx::query(r#"DELETE FROM "TraderPause" WHERE "traderId" = $1"#)
+            .bind(&trader_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal_error)?;
+        None
+    };
+
+    outbox::enqueue_event(&mut tx, &ServerEvent::traders_updated())
+        .await
+        .map_err(internal_error)?;
+    tx.commit().await.map_err(internal_error)?;
+    state.outbox_notify.notify_one();
+
+    println!(
+        "[self] Trader {trader_id} {} new payouts",
+        if request.paused { "paused" } else { "resumed" }
+    );
+    Ok(Json(SelfPauseResponse {
+        trader_id,
+        paused: request.paused,
+        paused_at,
+    }))
+}
+
+/// Payouts a trader holds now or was assigned by this service before. The
+/// assignment time comes from the audit log (the platform's `acceptedAt` for
+/// payouts assigned elsewhere) and the date range applies to it.
+pub(crate) const TRADER_ASSIGNMENTS_FROM: &str = r#"
+    FROM "Payout" p
+    LEFT JOIN (
+        SELECT a."payoutId", MAX(a."createdAt") AS "assignedAt"
+        FROM "PayoutAuditLog" a
+        WHERE a."traderId" = $1 AND a."action" = 'assigned'
+        GROUP BY a."payoutId"
+    ) assigned ON assigned."payoutId" = p."id"
+    WHERE p."direction" = 'OUT'
+      AND (p."traderId" = $1 OR assigned."payoutId" IS NOT NULL)
+      AND ($2::text[] IS NULL OR p."merchantId" = ANY($2::text[]))
+      AND ($3::timestamp IS NULL OR COALESCE(assigned."assignedAt", p."acceptedAt", p."createdAt") >= $3)
+      AND ($4::timestamp IS NULL OR COALESCE(assigned."assignedAt", p."acceptedAt", p."createdAt") < $4)
+"#;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TraderAssignmentsQuery {
+    /// First day to include (UTC).
+    from: Option<NaiveDate>,
+    /// Last day to include (UTC).
+    to: Option<NaiveDate>,
+    page: Option<u32>,
+    per_page: Option<u32>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TraderAssignment {
+    id: String,
+    #[sqlx(rename = "numericId")]
+    numeric_id: i32,
+    amount: f64,
+    #[sqlx(rename = "amountUsdt")]
+    amount_usdt: f64,
+    currency: String,
+    status: PayoutStatus,
+    bank: String,
+    wallet: String,
+    #[sqlx(rename = "merchantId")]
+    merchant_id: String,
+    #[sqlx(rename = "externalReference")]
+    external_reference: Option<String>,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+    #[sqlx(rename = "assignedAt")]
+    assigned_at: Option<UtcTimestamp>,
+    #[sqlx(rename = "acceptedAt")]
+    accepted_at: Option<UtcTimestamp>,
+    #[sqlx(rename = "cancelledAt")]
+    cancelled_at: Option<UtcTimestamp>,
+    /// False once the payout moved to another trader.
+    current: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TraderAssignmentsResponse {
+    trader_id: String,
+    items: Vec<TraderAssignment>,
+    total_amount: f64,
+    pagination: Pagination,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SelfAssignment {
+    id: String,
+    #[sqlx(rename = "numericId")]
+    pub(crate) numeric_id: i32,
+    pub(crate) amount: f64,
+    #[sqlx(rename = "amountUsdt")]
+    amount_usdt: f64,
+    pub(crate) currency: String,
+    pub(crate) status: PayoutStatus,
+    pub(crate) bank: String,
+    pub(crate) wallet: String,
+    #[sqlx(rename = "createdAt")]
+    pub(crate) created_at: UtcTimestamp,
+    #[sqlx(rename = "acceptedAt")]
+    pub(crate) accepted_at: Option<UtcTimestamp>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SelfAssignmentsResponse {
+    trader_id: String,
+    paused: bool,
+    paused_at: Option<UtcTimestamp>,
+    items: Vec<SelfAssignment>,
+}
+
+/// The trader's own open queue, oldest first.
+pub(crate) async fn get_self_assignments(
+    State(state): State<AppState>,
+    trader: TraderScope,
+) -> ApiResult<Json<SelfAssignmentsResponse>> {
+    let items = fetch_self_assignments(&state.pool, trader.trader_id())
+        .await
+        .map_err(internal_error)?;
+    let paused_at: Option<UtcTimestamp> =
+        sqlx::query_scalar(r#"SELECT "pausedAt" FROM "TraderPause" WHERE "traderId" = $1"#)
+            .bind(trader.trader_id())
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(internal_error)?;
+
+    Ok(Json(SelfAssignmentsResponse {
+        trader_id: trader.trader_id().to_string(),
+        paused: paused_at.is_some(),
+        paused_at,
+        items,
+    }))
+}
+
+/// Payouts on the trader that are not final yet.
+pub(crate) async fn fetch_self_assignments(
+    pool: &PgPool,
+    trader_id: &str,
+) -> sqlx::Result<Vec<SelfAssignment>> {
+    sqlx::query_as::<_, SelfAssignment>(&format!(
+        r#"
+        SELECT
+            p."id",
+            p."numericId",
+            p."amount",
+            p."amountUsdt",
+            {PAYOUT_CURRENCY_SQL} AS "currency",
+            p."status"::text AS "status",
+            p."bank",
+            p."wallet",
+            p."createdAt",
+            p."acceptedAt"
+        FROM "Payout" p
+        WHERE p."traderId" = $1
+          AND p."direction" = 'OUT'
+          AND p."status"::text <> ALL($2::text[])
+        ORDER BY p."createdAt"
+        "#
+    ))
+    .bind(trader_id)
+    .bind(PayoutStatus::final_names())
+    .fetch_all(pool)
+    .await
+}
+
+pub(crate) async fn get_trader_assignments(
+    Path(trader_id): Path<String>,
+    Query(params): Query<TraderAssignmentsQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<TraderAssignmentsResponse>> {
+    if let (Some(from), Some(to)) = (params.from, params.to)
+        && from > to
+    {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "from must not be after to".to_string(),
+        )));
+    }
+    ensure_trader_in_scope(&state.pool, &trader_id, scope.merchant_ids()).await?;
+    let page = params.page.unwrap_or(1).max(1);
+    let per_page = params.per_page.unwrap_or(25).clamp(1, 200);
+    let from = params.from.map(|date| date.and_time(NaiveTime::MIN));
+    let until = params
+        .to
+        .and_then(|date| date.succ_opt())
+        .map(|date| date.and_time(NaiveTime::MIN));
+
+    let (total, total_amount): (i64, f64) = sqlx::query_as(&format!(
+        r#"SELECT COUNT(*)::bigint, COALESCE(SUM(p."amount"), 0)::double precision {TRADER_ASSIGNMENTS_FROM}"#
+    ))
+    .bind(&trader_id)
+    .bind(scope.merchant_ids())
+    .bind(from)
+    .bind(until)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let items = sqlx::query_as::<_, TraderAssignment>(&format!(
+        r#"
+        SELECT
+            p."id",
+            p."numericId",
+            p."amount",
+            p."amountUsdt",
+            {PAYOUT_CURRENCY_SQL} AS "currency",
+            p."status"::text AS "status",
+            p."bank",
+            p."wallet",
+            p."merchantId",
+            p."externalReference",
+            p."createdAt",
+            COALESCE(assigned."assignedAt", p."acceptedAt") AS "assignedAt",
+            p."acceptedAt",
+            p."cancelledAt",
+            COALESCE(p."traderId" = $1, FALSE) AS "current"
+        {TRADER_ASSIGNMENTS_FROM}
+        ORDER BY COALESCE(assigned."assignedAt", p."acceptedAt", p."createdAt") DESC, p."id"
+        LIMIT $5 OFFSET $6
+        "#
+    ))
+    .bind(&trader_id)
+    .bind(scope.merchant_ids())
+    .bind(from)
+    .bind(until)
+    .bind(per_page as i64)
+    .bind(((page - 1) as i64) * per_page as i64)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    Ok(Json(TraderAssignmentsResponse {
+        trader_id,
+        items,
+        total_amount,
+        pagination: Pagination::new(total, page, per_page),
+    }))
+}
+
+/// A tenant may only manage traders that work with one of its merchants.
+pub(crate) async fn ensure_trader_in_scope(
+    pool: &PgPool,
+    trader_id: &str,
+    merchant_ids: Option<&[String]>,
+) -> ApiResult<()> {
+    let Some(merchant_ids) = merchant_ids else {
+        return Ok(());
+    };
+    let linked: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM "TraderMerchant" tm
+            WHERE tm."traderId" = $1
+              AND tm."merchantId" = ANY($2::text[])
+        )
+        "#,
+    )
+    .bind(trader_id)
+    .bind(merchant_ids)
+    .fetch_one(pool)
+    .await
+    .map_err(internal_error)?;
+
+    if !linked {
+        return Err(ApiError::trader_not_found());
+    }
+    Ok(())
+}
+
+pub(crate) async fn load_traders_with_limits(
+    state: &AppState,
+    filters: &TraderListFilters,
+) -> Result<TraderListResponse> {
+    let (records, total) = fetch_filtered_traders(&state.pool, filters).await?;
+    let config = state.settings.auto_config();
+    let cooldowns = fetch_trader_cooldowns(&state.pool, config.assignment_cooldown_seconds).await?;
+    let open_payouts = fetch_open_payouts(&state.pool)
+        .await
+        .context("Failed to count open payouts")?;
+    let capacity_overrides = fetch_capacity_overrides(&state.pool)
+        .await
+        .context("Failed to load trader capacity")?;
+    let limits = state.settings.limits().await;
+    let teams = teams::Teams::load(&state.pool)
+        .await
+        .context("Failed to load trader teams")?;
+    let currencies = currencies::TraderCurrencies::load(&state.pool)
+        .await
+        .context("Failed to load trader currencies")?;
+    let mut unavailability = availability::next_windows(&state.pool)
+        .await
+        .context("Failed to load trader unavailability")?;
+
+    let items = records
+        .into_iter()
+        .map(|record| Trader {
+            min_amount: limits.get(&record.id).and_then(|limit| limit.min_amount),
+            max_amount: limits.get(&record.id).and_then(|limit| limit.max_amount),
+            cooldown_remaining_seconds: cooldowns.get(&record.id).copied(),
+            open_payouts: open_payouts.get(&record.id).copied().unwrap_or_default(),
+            max_open_payouts: config.open_payout_cap(&capacity_overrides, &record.id),
+            team: teams.summary_of(&record.id),
+            currencies: currencies.of(&record.id).to_vec(),
+            unavailability: unavailability.remove(&record.id),
+            id: record.id,
+            email: record.email,
+            numeric_id: record.numeric_id,
+            balance_rub: record.balance_rub,
+            frozen_rub: record.frozen_rub,
+            payout_balance: record.payout_balance,
+        })
+        .collect();
+
+    Ok(TraderListResponse {
+        items,
+        pagination: Pagination::new(total, filters.page, filters.per_page),
+    })
+}
@@ -0,0 +1,101 @@
+//! Archiving of old settled payouts. The platform owns the `Payout` rows, so
+//! archived payouts are only recorded in the service-side `PayoutArchive`
+//! table and hidden from the deals list unless `includeArchived=true`.
+//!
+//! Configured from the environment: `ARCHIVE_AFTER_DAYS` (default 90, `0`
+//! disables archiving) and `ARCHIVE_INTERVAL_MINUTES` (default 60).
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use sqlx::PgPool;
+use tokio::time::{self, MissedTickBehavior};
+
+use crate::{env::non_empty_env, payout_status::PayoutStatus};
+
+const ARCHIVE_BATCH_SIZE: i64 = 1000;
+
+/// Only payouts in a final status (`$3`) are archived; open payouts stay
+/// visible however old they are.
+const ARCHIVE_BATCH_QUERY: &str = r#"
+    INSERT INTO "PayoutArchive" ("payoutId")
+    SELECT p."id"
+    FROM "Payout" p
+    WHERE p."direction" = 'OUT'
+      AND p."status"::text = ANY($3::text[])
+      AND p."createdAt" < LOCALTIMESTAMP - make_interval(days => $1)
+      AND NOT EXISTS (SELECT 1 FROM "PayoutArchive" pa WHERE pa."payoutId" = p."id")
+    ORDER BY p."createdAt"
+    LIMIT $2
+    ON CONFLICT ("payoutId") DO NOTHING
+"#;
+
+#[derive(Debug, Clone)]
+pub(crate) struct ArchiveConfig {
+    after_days: u32,
+    interval: Duration,
+}
+
+impl ArchiveConfig {
+    pub(crate) fn from_env() -> Result<Self> {
+        let after_days = match non_empty_env("ARCHIVE_AFTER_DAYS") {
+            Some(value) => value
+                .parse::<u32>()
+                .context("ARCHIVE_AFTER_DAYS must be a non-negative integer")?,
+            None => 90,
+        };
+        let interval_minutes = match non_empty_env("ARCHIVE_INTERVAL_MINUTES") {
+            Some(value) => value
+                .parse::<u64>()
+                .context("ARCHIVE_INTERVAL_MINUTES must be a positive integer")?
+                .max(1),
+            None => 60,
+        };
+        Ok(Self {
+            after_days,
+            interval: Duration::from_secs(interval_minutes * 60),
+        })
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.after_days > 0
+    }
+}
+
+async fn archive_old_payouts(pool: &PgPool, after_days: u32) -> Result<u64> {
+    let mut archived = 0;
+    loop {
+        let inserted = sqlx::query(ARCHIVE_BATCH_QUERY)
+            .bind(after_days as i32)
+            .bind(ARCHIVE_BATCH_SIZE)
+            .bind(PayoutStatus::final_names())
+            .execute(pool)
+            .await
+            .context("Failed to archive payouts")?
+            .rows_affected();
+        archived += inserted;
+        if inserted < ARCHIVE_BATCH_SIZE as u64 {
+            return Ok(archived);
+        }
+    }
+}
+
+pub(crate) async fn archive_worker(pool: PgPool, config: ArchiveConfig) {
+    if !config.is_enabled() {
+        return;
+    }
+    let mut interval = time::interval(config.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+        match archive_old_payouts(&pool, config.after_days).await {
+            Ok(0) => {}
+            Ok(archived) => println!(
+                "[archive] Archived {archived} payout(s) older than {} days",
+                config.after_days
+            ),
+            Err(err) => eprintln!("[archive] {err:#}"),
+        }
+    }
+}
@@ -0,0 +1,326 @@
+//! Assignment latency: the time from a payout's creation to its first
+//! assignment, as recorded by the `assigned` audit entry. Assigning fast is
+//! what this service is for, so the latency is tracked against an SLO.
+//!
+//! `GET /api/stats/assignment-latency` reports p50/p95/p99 over rolling
+//! windows, by default the last hour, day and week; `?windows=15,60` picks
+//! others (in minutes, up to a week). A worker checks the last
+//! `ASSIGNMENT_SLO_WINDOW_MINUTES` (default 60) every minute and publishes the
+//! p95 in `/api/status`. Once at least `ASSIGNMENT_SLO_MIN_PAYOUTS` (default
+//! 10) assignments have a p95 above `ASSIGNMENT_SLO_P95_SECONDS` (default
+//! 300, `0` disables the alert) it raises an `assignment-slo-breached` event,
+//! and an email when alerts are configured; `assignment-slo-recovered`
+//! follows when it is back.
+
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use tokio::time::{self, MissedTickBehavior};
+
+use crate::{
+    ApiResult, AppState, env::non_empty_env, errors::ApiError, events::ServerEvent, internal_error,
+    tenant::TenantScope,
+};
+
+const DEFAULT_WINDOW_MINUTES: [i32; 3] = [60, 24 * 60, 7 * 24 * 60];
+const MAX_WINDOW_MINUTES: i32 = 7 * 24 * 60;
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Percentiles over the first assignment of payouts first assigned within
+/// each window. Reassignments do not restart the clock.
+const LATENCY_QUERY: &str = r#"
+    WITH first_assignment AS (
+        SELECT a."payoutId", MIN(a."createdAt") AS "assignedAt"
+        FROM "PayoutAuditLog" a
+        WHERE a."action" = 'assigned'
+        GROUP BY a."payoutId"
+        HAVING MIN(a."createdAt") >= LOCALTIMESTAMP - make_interval(mins => $3)
+    ),
+    latencies AS (
+        SELECT
+            fa."assignedAt",
+            GREATEST(EXTRACT(EPOCH FROM (fa."assignedAt" - p."createdAt")), 0)::double precision
+                AS "seconds"
+        FROM first_assignment fa
+        JOIN "Payout" p ON p."id" = fa."payoutId"
+        WHERE p."direction" = 'OUT'
+          AND ($2::text[] IS NULL OR p."merchantId" = ANY($2::text[]))
+    )
+    SELECT
+        w."minutes" AS "windowMinutes",
+        COUNT(l."seconds")::bigint AS "assigned",
+        percentile_cont(0.5) WITHIN GROUP (ORDER BY l."seconds") AS "p50Seconds",
+        percentile_cont(0.95) WITHIN GROUP (ORDER BY l."seconds") AS "p95Seconds",
+        percentile_cont(0.99) WITHIN GROUP (ORDER BY l."seconds") AS "p99Seconds"
+    FROM UNNEST($1::integer[]) AS w("minutes")
+    LEFT JOIN latencies l
+        ON l."assignedAt" >= LOCALTIMESTAMP - make_interval(mins => w."minutes")
+    GROUP BY w."minutes"
+    ORDER BY w."minutes"
+"#;
+
+#[derive(Debug, Clone)]
+pub(crate) struct AssignmentSloConfig {
+    /// Target p95 in seconds, `0` when alerting is off.
+    p95_seconds: f64,
+    window_minutes: i32,
+    min_payouts: i64,
+}
+
+impl AssignmentSloConfig {
+    pub(crate) fn from_env() -> Result<Self> {
+        let p95_seconds = match non_empty_env("ASSIGNMENT_SLO_P95_SECONDS") {
+            Some(value) => value
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite() && *value >= 0.0)
+                .context("ASSIGNMENT_SLO_P95_SECONDS must be a non-negative number")?,
+            None => 300.0,
+        };
+        let window_minutes = match non_empty_env("ASSIGNMENT_SLO_WINDOW_MINUTES") {
+            Some(value) => value
+                .parse::<i32>()
+                .ok()
+                .filter(|value| (1..=MAX_WINDOW_MINUTES).contains(value))
+                .context("ASSIGNMENT_SLO_WINDOW_MINUTES must be between 1 and 10080")?,
+            None => 60,
+        };
+        let min_payouts = match non_empty_env("ASSIGNMENT_SLO_MIN_PAYOUTS") {
+            Some(value) => value
+                .parse::<i64>()
+                .ok()
+                .filter(|value| *value > 0)
+                .context("ASSIGNMENT_SLO_MIN_PAYOUTS must be a positive integer")?,
+            None => 10,
+        };
+        Ok(Self {
+            p95_seconds,
+            window_minutes,
+            min_payouts,
+        })
+    }
+
+    pub(crate) fn is_alerting(&self) -> bool {
+        self.p95_seconds > 0.0
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        format!(
+            "alert when p95 exceeds {} s over {} min with at least {} assignment(s)",
+            self.p95_seconds, self.window_minutes, self.min_payouts
+        )
+    }
+
+    fn is_breached(&self, window: &LatencyWindow) -> bool {
+        self.is_alerting()
+            && window.assigned >= self.min_payouts
+            && window.p95_seconds.is_some_and(|p95| p95 > self.p95_seconds)
+    }
+}
+
+/// The configuration and the worker's latest reading, shared with
+/// `/api/status`.
+#[derive(Debug, Clone)]
+pub(crate) struct AssignmentSlo {
+    config: AssignmentSloConfig,
+    latest: Arc<Mutex<Option<LatencyStatus>>>,
+}
+
+impl AssignmentSlo {
+    pub(crate) fn new(config: AssignmentSloConfig) -> Self {
+        Self {
+            config,
+            latest: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// `None` until the worker's first check.
+    pub(crate) fn status(&self) -> Option<LatencyStatus> {
+        self.latest
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LatencyStatus {
+    window_minutes: i32,
+    assigned: i64,
+    p95_seconds: Option<f64>,
+    slo_seconds: Option<f64>,
+    breached: bool,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LatencyWindow {
+    #[sqlx(rename = "windowMinutes")]
+    window_minutes: i32,
+    assigned: i64,
+    #[sqlx(rename = "p50Seconds")]
+    p50_seconds: Option<f64>,
+    #[sqlx(rename = "p95Seconds")]
+    p95_seconds: Option<f64>,
+    #[sqlx(rename = "p99Seconds")]
+    p99_seconds: Option<f64>,
+    #[sqlx(skip)]
+    breached: bool,
+}
+
+async fn fetch_windows(
+    pool: &PgPool,
+    windows: &[i32],
+    merchant_ids: Option<&[String]>,
+) -> Result<Vec<LatencyWindow>, sqlx::Error> {
+    sqlx::query_as::<_, LatencyWindow>(LATENCY_QUERY)
+        .bind(windows)
+        .bind(merchant_ids)
+        .bind(windows.iter().copied().max().unwrap_or(MAX_WINDOW_MINUTES))
+        .fetch_all(pool)
+        .await
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct LatencyQuery {
+    /// Comma-separated window lengths in minutes.
+    windows: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AssignmentLatencyResponse {
+    slo_seconds: Option<f64>,
+    min_payouts: i64,
+    windows: Vec<LatencyWindow>,
+}
+
+pub(crate) async fn get_assignment_latency(
+    Query(params): Query<LatencyQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<AssignmentLatencyResponse>> {
+    let config = &state.assignment_slo.config;
+    let windows: BTreeSet<i32> = match params.windows.as_deref().map(str::trim) {
+        Some(list) if !list.is_empty() => list
+            .split(',')
+            .map(|value| {
+                value
+                    .trim()
+                    .parse::<i32>()
+                    .ok()
+                    .filter(|minutes| (1..=MAX_WINDOW_MINUTES).contains(minutes))
+                    .ok_or_else(|| {
+                        ApiError::from((
+                            StatusCode::BAD_REQUEST,
+                            format!(
+                                "Invalid window {value:?}, expected minutes between 1 and 10080"
+                            ),
+                        ))
+                    })
+            })
+            .collect::<ApiResult<_>>()?,
+        _ => DEFAULT_WINDOW_MINUTES
+            .into_iter()
+            .chain([config.window_minutes])
+            .collect(),
+    };
+    let windows: Vec<i32> = windows.into_iter().collect();
+
+    let mut windows = fetch_windows(&state.pool, &windows, scope.merchant_ids())
+        .await
+        .map_err(internal_error)?;
+    for window in &mut windows {
+        window.breached = config.is_breached(window);
+    }
+
+    Ok(Json(AssignmentLatencyResponse {
+        slo_seconds: config.is_alerting().then_some(config.p95_seconds),
+        min_payouts: config.min_payouts,
+        windows,
+    }))
+}
+
+async fn check(state: &AppState, breached: &mut bool) -> Result<()> {
+    let slo = &state.assignment_slo;
+    let config = &slo.config;
+    let window = fetch_windows(&state.pool, &[config.window_minutes], None)
+        .await
+        .context("Failed to measure assignment latency")?
+        .into_iter()
+        .next()
+        .context("Assignment latency query returned no window")?;
+    let above = config.is_breached(&window);
+    let measured = window.assigned >= config.min_payouts;
+    let p95 = window.p95_seconds.unwrap_or_default();
+    let data = serde_json::json!({
+        "p95Seconds": p95,
+        "sloSeconds": config.p95_seconds,
+        "assigned": window.assigned,
+        "windowMinutes": config.window_minutes,
+    });
+    if above && !*breached {
+        *breached = true;
+        eprintln!(
+            "[assignment-slo] p95 assignment latency {p95:.0} s over {} assignment(s) in {} min exceeds {} s",
+            window.assigned, config.window_minutes, config.p95_seconds
+        );
+        let _ = state
+            .event_tx
+            .send(ServerEvent::assignment_slo_breached(data));
+        if let Some(email) = &state.email {
+            email
+                .send(
+                    &state.pool,
+                    "Assignment latency above SLO",
+                    &format!(
+                        "Payouts assigned in the last {} min waited {p95:.0} s at p95 ({} assignment(s)), above the {} s target. See /api/stats/assignment-latency.",
+                        config.window_minutes, window.assigned, config.p95_seconds
+                    ),
+                )
+                .await?;
+        }
+    } else if measured && !above && *breached {
+        *breached = false;
+        println!("[assignment-slo] p95 assignment latency is back within SLO at {p95:.0} s");
+        let _ = state
+            .event_tx
+            .send(ServerEvent::assignment_slo_recovered(data));
+    }
+
+    *slo.latest.lock().unwrap_or_else(|err| err.into_inner()) = Some(LatencyStatus {
+        window_minutes: window.window_minutes,
+        assigned: window.assigned,
+        p95_seconds: window.p95_seconds,
+        slo_seconds: config.is_alerting().then_some(config.p95_seconds),
+        breached: *breached,
+    });
+    Ok(())
+}
+
+/// Like the callback SLA, a breach holds until enough assignments show the
+/// latency back under the target.
+pub(crate) async fn slo_worker(state: AppState) {
+    let mut interval = time::interval(CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut breached = false;
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = check(&state, &mut breached).await {
+            eprintln!("[assignment-slo] {err:#}");
+        }
+    }
+}
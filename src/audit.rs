@@ -0,0 +1,258 @@
+//! Compliance view of the distribution audit: the settings change log and
+//! `GET /api/audit/export`, a CSV of assignments, cancellations and settings
+//! changes with the actor and the values before and after each change.
+//!
+//! The export is hash-chained. Every row carries `prevHash` and
+//! `hash = sha256(prevHash || row)`, where `row` is the CSV line up to and
+//! excluding the `prevHash` column and the first row chains from
+//! [`GENESIS_HASH`]. Rows are ordered by `(createdAt, id)`, so exporting a
+//! closed period again yields the same file. The chain is computed at export
+//! time over the rows as they are stored then: it proves an export was not
+//! edited afterwards, and tampering with the tables only shows up against a
+//! copy exported earlier, whose hashes stop matching from the changed row on.
+
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::{NaiveDate, NaiveTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::FromRow;
+use tokio_stream::{StreamExt, wrappers::ReceiverStream};
+use uuid::Uuid;
+
+use crate::{
+    ApiResult, AppState, callbacks::csv_field, errors::ApiError, redaction, tenant::TenantScope,
+    timestamps::UtcTimestamp,
+};
+
+pub(crate) const GENESIS_HASH: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+const AUDIT_EXPORT_HEADER: &str =
+    "createdAt,id,category,action,source,actor,target,merchantId,before,after,prevHash,hash\n";
+
+/// Assignments and cancellations from `PayoutAuditLog`, with the previous
+/// assignee looked up from the trail itself, merged with `SettingsAuditLog`.
+const AUDIT_EXPORT_QUERY: &str = r#"
+    SELECT *
+    FROM (
+        SELECT
+            a."createdAt",
+            a."id",
+            'payout' AS "category",
+            a."action",
+            a."source",
+            a."actor",
+            a."payoutId" AS "target",
+            p."merchantId",
+            CASE a."action"
+                WHEN 'assigned' THEN jsonb_build_object('traderId', prev."traderId")
+                ELSE jsonb_build_object(
+                    'status', a."details"->'previousStatus',
+                    'traderId', prev."traderId"
+                )
+            END AS "before",
+            CASE a."action"
+                WHEN 'assigned' THEN jsonb_build_object('traderId', a."traderId")
+                ELSE jsonb_build_object(
+                    'status', 'CANCELLED',
+                    'reasonCode', a."details"->'reasonCode',
+                    'reason', a."details"->'reason'
+                )
+            END AS "after"
+        FROM "PayoutAuditLog" a
+        LEFT JOIN "Payout" p ON p."id" = a."payoutId"
+        LEFT JOIN LATERAL (
+            SELECT earlier."traderId"
+            FROM "PayoutAuditLog" earlier
+            WHERE earlier."payoutId" = a."payoutId"
+              AND earlier."action" = 'assigned'
+              AND (earlier."createdAt", earlier."id") < (a."createdAt", a."id")
+            ORDER BY earlier."createdAt" DESC, earlier."id" DESC
+            LIMIT 1
+        ) prev ON TRUE
+        WHERE a."action" IN ('assigned', 'cancelled')
+          AND ($1::timestamp IS NULL OR a."createdAt" >= $1)
+          AND a."createdAt" < COALESCE($2, LOCALTIMESTAMP)
+        UNION ALL
+        SELECT
+            s."createdAt",
+            s."id",
+            'settings' AS "category",
+            s."section" AS "action",
+            'manual' AS "source",
+            s."actor",
+            s."target",
+            NULL AS "merchantId",
+            s."before",
+            s."after"
+        FROM "SettingsAuditLog" s
+        WHERE ($1::timestamp IS NULL OR s."createdAt" >= $1)
+          AND s."createdAt" < COALESCE($2, LOCALTIMESTAMP)
+    ) events
+    ORDER BY events."createdAt", events."id"
+"#;
+
+/// Appends a settings change to the audit trail. `target` names the trader
+/// or bank for per-entity settings.
+pub(crate) async fn record_settings_change<'e, E>(
+    executor: E,
+    section: &str,
+    target: Option<&str>,
+    actor: Option<&str>,
+    before: Value,
+    after: Value,
+) -> sqlx::Result<()>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query(
+        r#"
+        INSERT INTO "SettingsAuditLog" ("id", "section", "target", "actor", "before", "after")
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(section)
+    .bind(target)
+    .bind(actor)
+    .bind(redaction::json(&before))
+    .bind(redaction::json(&after))
+    .execute(executor)
+    .await
+    .map(|_| ())
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AuditExportQuery {
+    /// First day to include (UTC).
+    from: Option<NaiveDate>,
+    /// Last day to include (UTC); up to now when omitted.
+    to: Option<NaiveDate>,
+    format: Option<String>,
+}
+
+#[derive(Debug, FromRow)]
+struct AuditExportRow {
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+    id: String,
+    category: String,
+    action: String,
+    source: String,
+    actor: Option<String>,
+    target: Option<String>,
+    #[sqlx(rename = "merchantId")]
+    merchant_id: Option<String>,
+    before: Option<Value>,
+    after: Option<Value>,
+}
+
+impl AuditExportRow {
+    /// The row's CSV fields that the hash covers.
+    fn chained_fields(&self) -> String {
+        let json = |value: &Option<Value>| value.as_ref().map(Value::to_string).unwrap_or_default();
+        [
+            self.created_at.to_string(),
+            csv_field(&self.id),
+            csv_field(&self.category),
+            csv_field(&self.action),
+            csv_field(&self.source),
+            csv_field(self.actor.as_deref().unwrap_or_default()),
+            csv_field(self.target.as_deref().unwrap_or_default()),
+            csv_field(self.merchant_id.as_deref().unwrap_or_default()),
+            csv_field(&json(&self.before)),
+            csv_field(&json(&self.after)),
+        ]
+        .join(",")
+    }
+}
+
+/// Hashes a row onto the chain and returns its CSV line and the new head.
+fn chain_line(prev_hash: &str, row: &AuditExportRow) -> (String, String) {
+    let fields = row.chained_fields();
+    let mut hasher = Sha256::new();
+    hasher.update(prev_hash.as_bytes());
+    hasher.update(fields.as_bytes());
+    let hash = hex::encode(hasher.finalize());
+    (format!("{fields},{prev_hash},{hash}\n"), hash)
+}
+
+/// Streams the hash-chained audit export. Settings are global, so only
+/// unrestricted tenants may export.
+pub(crate) async fn export_audit(
+    Query(params): Query<AuditExportQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Response> {
+    scope.require_unrestricted()?;
+    match params.format.as_deref().map(str::trim) {
+        None | Some("") | Some("csv") => {}
+        Some(other) => {
+            return Err(ApiError::from((
+                StatusCode::BAD_REQUEST,
+                format!("Unsupported export format {other}"),
+            )));
+        }
+    }
+    if let (Some(from), Some(to)) = (params.from, params.to)
+        && from > to
+    {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "from must not be after to".to_string(),
+        )));
+    }
+    let from = params.from.map(|date| date.and_time(NaiveTime::MIN));
+    // Without an end date the query stops at its own start time, so rows
+    // written while the export streams are left for the next one.
+    let until = params
+        .to
+        .and_then(|date| date.succ_opt())
+        .map(|date| date.and_time(NaiveTime::MIN));
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<String, sqlx::Error>>(64);
+    let pool = state.pool.clone();
+    tokio::spawn(async move {
+        if tx.send(Ok(AUDIT_EXPORT_HEADER.to_string())).await.is_err() {
+            return;
+        }
+        let mut head = GENESIS_HASH.to_string();
+        let mut rows = sqlx::query_as::<_, AuditExportRow>(AUDIT_EXPORT_QUERY)
+            .bind(from)
+            .bind(until)
+            .fetch(&pool);
+        while let Some(row) = rows.next().await {
+            let line = row.map(|row| {
+                let (line, hash) = chain_line(&head, &row);
+                head = hash;
+                line
+            });
+            let failed = line.is_err();
+            if let Err(err) = &line {
+                eprintln!("[audit] CSV export failed: {err}");
+            }
+            if tx.send(line).await.is_err() || failed {
+                return;
+            }
+        }
+    });
+
+    let filename = format!("audit-{}.csv", Utc::now().format("%Y%m%d-%H%M%S"));
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response())
+}
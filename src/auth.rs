@@ -0,0 +1,432 @@
+//! Operator login for the dashboard and the JSON API. Enabled with
+//! `OPERATOR_LOGIN=true`; without it the service keeps relying on tenant
+//! tokens only.
+//!
+//! Operators live in `OperatorUser` with argon2 password hashes and are added
+//! with `chase-linker add-operator <username> [tenant]` (the password is read
+//! from `OPERATOR_PASSWORD` or stdin). An operator with a `tenant` gets that
+//! tenant's merchant scope, one without is unrestricted.
+//!
+//! Sessions are kept in `OperatorSession` behind a signed cookie. Configured
+//! from the environment: `SESSION_SECRET` (the cookie signing secret, a random
+//! key is generated per start when unset), `SESSION_TTL_HOURS` (default 12,
+//! counted from the last activity) and `SESSION_COOKIE_SECURE`.
+
+use std::io::{self, BufRead};
+
+use anyhow::{Context, Result, anyhow, bail};
+use argon2::{
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+    password_hash::SaltString,
+};
+use axum::{async_trait, http::StatusCode, http::request::Parts};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use sqlx::PgPool;
+use tower_sessions::{
+    Expiry, Session, SessionManagerLayer, SessionStore,
+    cookie::{
+        Key, SameSite,
+        time::{Duration as CookieDuration, OffsetDateTime},
+    },
+    service::SignedCookie,
+    session::{Id, Record},
+    session_store,
+};
+use uuid::Uuid;
+
+use crate::{
+    ApiResult,
+    env::{flag_env, non_empty_env},
+    errors::{ApiError, ErrorCode},
+    internal_error,
+    tenant::TenantScope,
+};
+
+pub(crate) const SESSION_COOKIE: &str = "operator_session";
+const OPERATOR_KEY: &str = "operator";
+const CSRF_KEY: &str = "csrf";
+
+#[derive(Clone)]
+pub(crate) struct AuthConfig {
+    enabled: bool,
+    key: Key,
+    random_key: bool,
+    ttl_hours: i64,
+    secure_cookie: bool,
+}
+
+impl AuthConfig {
+    pub(crate) fn from_env() -> Result<Self> {
+        let secret = non_empty_env("SESSION_SECRET");
+        let key = match &secret {
+            Some(secret) if secret.len() < 32 => {
+                bail!("SESSION_SECRET must be at least 32 characters long")
+            }
+            Some(secret) => Key::from(&Sha512::digest(secret.as_bytes())),
+            None => Key::generate(),
+        };
+        let ttl_hours = match non_empty_env("SESSION_TTL_HOURS") {
+            Some(value) => value
+                .parse::<i64>()
+                .ok()
+                .filter(|hours| *hours > 0)
+                .context("SESSION_TTL_HOURS must be a positive integer")?,
+            None => 12,
+        };
+        Ok(Self {
+            enabled: flag_env("OPERATOR_LOGIN", false)?,
+            key,
+            random_key: secret.is_none(),
+            ttl_hours,
+            secure_cookie: flag_env("SESSION_COOKIE_SECURE", false)?,
+        })
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Without `SESSION_SECRET` every restart signs out all operators.
+    pub(crate) fn uses_random_key(&self) -> bool {
+        self.random_key
+    }
+
+    pub(crate) fn session_layer(
+        &self,
+        pool: PgPool,
+    ) -> SessionManagerLayer<PgSessionStore, SignedCookie> {
+        SessionManagerLayer::new(PgSessionStore { pool })
+            .with_name(SESSION_COOKIE)
+            .with_http_only(true)
+            .with_same_site(SameSite::Strict)
+            .with_secure(self.secure_cookie)
+            .with_expiry(Expiry::OnInactivity(CookieDuration::hours(self.ttl_hours)))
+            .with_signed(self.key.clone())
+    }
+}
+
+/// The operator stored in a logged-in session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Operator {
+    pub(crate) user_id: String,
+    pub(crate) username: String,
+    pub(crate) tenant: Option<String>,
+}
+
+impl Operator {
+    /// How the operator is recorded as an audit actor.
+    pub(crate) fn actor(&self) -> String {
+        format!("operator:{}", self.username)
+    }
+}
+
+/// Session records in `OperatorSession`; expired rows are pruned whenever a
+/// new session is created.
+#[derive(Debug, Clone)]
+pub(crate) struct PgSessionStore {
+    pool: PgPool,
+}
+
+fn backend_error(err: sqlx::Error) -> session_store::Error {
+    session_store::Error::Backend(err.to_string())
+}
+
+/// Only the session data goes into the JSON column; the id and expiry have
+/// their own columns.
+fn encode_data(record: &Record) -> session_store::Result<serde_json::Value> {
+    serde_json::to_value(&record.data).map_err(|err| session_store::Error::Encode(err.to_string()))
+}
+
+#[async_trait]
+impl SessionStore for PgSessionStore {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        sqlx::query(r#"DELETE FROM "OperatorSession" WHERE "expiresAt" <= CURRENT_TIMESTAMP"#)
+            .execute(&self.pool)
+            .await
+            .map_err(backend_error)?;
+        loop {
+            let inserted = sqlx::query(
+                r#"
+                INSERT INTO "OperatorSession" ("id", "data", "expiresAt")
+                VALUES ($1, $2, to_timestamp($3))
+                ON CONFLICT ("id") DO NOTHING
+                "#,
+            )
+            .bind(record.id.to_string())
+            .bind(encode_data(record)?)
+            .bind(record.expiry_date.unix_timestamp() as f64)
+            .execute(&self.pool)
+            .await
+            .map_err(backend_error)?
+            .rows_affected();
+            if inserted > 0 {
+                return Ok(());
+            }
+            record.id = Id::default();
+        }
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO "OperatorSession" ("id", "data", "expiresAt")
+            VALUES ($1, $2, to_timestamp($3))
+            ON CONFLICT ("id") DO UPDATE
+            SET "data" = EXCLUDED."data", "expiresAt" = EXCLUDED."expiresAt"
+            "#,
+        )
+        .bind(record.id.to_string())
+        .bind(encode_data(record)?)
+        .bind(record.expiry_date.unix_timestamp() as f64)
+        .execute(&self.pool)
+        .await
+        .map_err(backend_error)?;
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        let row: Option<(serde_json::Value, i64)> = sqlx::query_as(
+            r#"
+            SELECT "data", EXTRACT(EPOCH FROM "expiresAt")::BIGINT
+            FROM "OperatorSession"
+            WHERE "id" = $1 AND "expiresAt" > CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(session_id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(backend_error)?;
+        let Some((data, expires_at)) = row else {
+            return Ok(None);
+        };
+        Ok(Some(Record {
+            id: *session_id,
+            data: serde_json::from_value(data)
+                .map_err(|err| session_store::Error::Decode(err.to_string()))?,
+            expiry_date: OffsetDateTime::from_unix_timestamp(expires_at)
+                .map_err(|err| session_store::Error::Decode(err.to_string()))?,
+        }))
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        sqlx::query(r#"DELETE FROM "OperatorSession" WHERE "id" = $1"#)
+            .bind(session_id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(backend_error)?;
+        Ok(())
+    }
+}
+
+fn session_error(err: tower_sessions::session::Error) -> ApiError {
+    internal_error(format!("Session error: {err}"))
+}
+
+/// The logged-in operator of the request, if any.
+pub(crate) async fn session_operator(parts: &Parts) -> ApiResult<Option<Operator>> {
+    let Some(session) = parts.extensions.get::<Session>() else {
+        return Ok(None);
+    };
+    current_operator(session).await
+}
+
+pub(crate) async fn current_operator(session: &Session) -> ApiResult<Option<Operator>> {
+    session.get(OPERATOR_KEY).await.map_err(session_error)
+}
+
+/// Whose per-user dashboard state (saved filters and the like) a request
+/// reads and writes: the logged-in operator, otherwise the tenant of the
+/// token, otherwise the one shared dashboard.
+pub(crate) async fn preference_owner(session: &Session, scope: &TenantScope) -> ApiResult<String> {
+    if let Some(operator) = current_operator(session).await? {
+        return Ok(format!("operator:{}", operator.user_id));
+    }
+    Ok(match scope.name() {
+        Some(tenant) => format!("tenant:{tenant}"),
+        None => "shared".to_string(),
+    })
+}
+
+/// Who to record as the actor of an audited change: the logged-in operator,
+/// otherwise the tenant of the token.
+pub(crate) async fn audit_actor(
+    session: &Session,
+    scope: &TenantScope,
+) -> ApiResult<Option<String>> {
+    if let Some(operator) = current_operator(session).await? {
+        return Ok(Some(operator.actor()));
+    }
+    Ok(tenant_actor(scope))
+}
+
+/// The signed-in operator taking part in a four-eyes step. Tenant tokens are
+/// shared and cannot tell two people apart, so they never count as one.
+pub(crate) async fn require_operator(session: &Session, message: &str) -> ApiResult<Operator> {
+    current_operator(session)
+        .await?
+        .ok_or_else(|| ApiError::from((StatusCode::FORBIDDEN, message.to_string())))
+}
+
+/// The audit actor of a caller without a session, such as a gRPC client.
+pub(crate) fn tenant_actor(scope: &TenantScope) -> Option<String> {
+    scope.name().map(|tenant| format!("tenant:{tenant}"))
+}
+
+/// Returns the session's CSRF token, creating one on first use.
+pub(crate) async fn csrf_token(session: &Session) -> ApiResult<String> {
+    if let Some(token) = session.get::<String>(CSRF_KEY).await.map_err(session_error)? {
+        return Ok(token);
+    }
+    let token = new_csrf_token();
+    session.insert(CSRF_KEY, &token).await.map_err(session_error)?;
+    Ok(token)
+}
+
+fn new_csrf_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+pub(crate) async fn verify_csrf(session: &Session, submitted: &str) -> ApiResult<()> {
+    let expected = session.get::<String>(CSRF_KEY).await.map_err(session_error)?;
+    match expected {
+        Some(expected) if !submitted.is_empty() && expected == submitted => Ok(()),
+        _ => Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            ErrorCode::CsrfRejected,
+            "Invalid or missing CSRF token",
+        )),
+    }
+}
+
+/// Stores the operator in the session under a fresh session id and CSRF
+/// token, so nothing issued before the login stays valid.
+pub(crate) async fn sign_in(session: &Session, operator: &Operator) -> ApiResult<()> {
+    session.cycle_id().await.map_err(session_error)?;
+    session.insert(OPERATOR_KEY, operator).await.map_err(session_error)?;
+    session
+        .insert(CSRF_KEY, new_csrf_token())
+        .await
+        .map_err(session_error)?;
+    Ok(())
+}
+
+pub(crate) async fn sign_out(session: &Session) -> ApiResult<()> {
+    session.flush().await.map_err(session_error)
+}
+
+#[derive(sqlx::FromRow)]
+struct OperatorRow {
+    id: String,
+    username: String,
+    #[sqlx(rename = "passwordHash")]
+    password_hash: String,
+    tenant: Option<String>,
+}
+
+/// Checks the credentials and records the login. Unknown users and wrong
+/// passwords are indistinguishable to the caller.
+pub(crate) async fn verify_login(
+    pool: &PgPool,
+    username: &str,
+    password: &str,
+) -> Result<Option<Operator>> {
+    let row: Option<OperatorRow> = sqlx::query_as(
+        r#"
+        SELECT "id", "username", "passwordHash", "tenant"
+        FROM "OperatorUser"
+        WHERE "username" = $1
+        "#,
+    )
+    .bind(username.trim())
+    .fetch_optional(pool)
+    .await
+    .context("Failed to load operator")?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    let password = password.to_string();
+    let hash = row.password_hash.clone();
+    let valid = tokio::task::spawn_blocking(move || {
+        PasswordHash::new(&hash)
+            .map(|parsed| {
+                Argon2::default()
+                    .verify_password(password.as_bytes(), &parsed)
+                    .is_ok()
+            })
+            .unwrap_or(false)
+    })
+    .await
+    .context("Password verification task failed")?;
+    if !valid {
+        return Ok(None);
+    }
+
+    sqlx::query(r#"UPDATE "OperatorUser" SET "lastLoginAt" = CURRENT_TIMESTAMP WHERE "id" = $1"#)
+        .bind(&row.id)
+        .execute(pool)
+        .await
+        .context("Failed to record operator login")?;
+    Ok(Some(Operator {
+        user_id: row.id,
+        username: row.username,
+        tenant: row.tenant,
+    }))
+}
+
+fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::encode_b64(Uuid::new_v4().as_bytes())
+        .map_err(|err| anyhow!("Failed to build password salt: {err}"))?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|err| anyhow!("Failed to hash password: {err}"))
+}
+
+/// `add-operator <username> [tenant]`: creates the operator or resets its
+/// password and tenant.
+pub(crate) async fn add_operator_command(pool: &PgPool, args: &[String]) -> Result<()> {
+    let (username, tenant) = match args {
+        [username] => (username.trim(), None),
+        [username, tenant] => (username.trim(), Some(tenant.trim())),
+        _ => bail!("Usage: chase-linker add-operator <username> [tenant]"),
+    };
+    if username.is_empty() {
+        bail!("Username must not be empty");
+    }
+    let password = match non_empty_env("OPERATOR_PASSWORD") {
+        Some(password) => password,
+        None => {
+            eprintln!("Password for {username}:");
+            let mut line = String::new();
+            io::stdin()
+                .lock()
+                .read_line(&mut line)
+                .context("Failed to read password")?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    if password.len() < 8 {
+        bail!("Password must be at least 8 characters long");
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO "OperatorUser" ("id", "username", "passwordHash", "tenant")
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT ("username") DO UPDATE
+        SET "passwordHash" = EXCLUDED."passwordHash", "tenant" = EXCLUDED."tenant"
+        "#,
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(username)
+    .bind(hash_password(&password)?)
+    .bind(tenant.filter(|tenant| !tenant.is_empty()))
+    .execute(pool)
+    .await
+    .context("Failed to save operator")?;
+    println!("[auth] Operator {username} saved");
+    Ok(())
+}
@@ -0,0 +1,179 @@
+//! Automatic cancellation of payouts nobody took. A payout still `CREATED`
+//! and unassigned `AUTO_CANCEL_AFTER_HOURS` after its creation (default 0,
+//! which disables the policy) is cancelled with the `UNASSIGNED_TIMEOUT`
+//! reason code, and the merchant gets the usual `CANCELED` callback through
+//! the outbox. The worker checks every `AUTO_CANCEL_INTERVAL_MINUTES`
+//! (default 5) and logs each payout it cancels; the audit log records them
+//! as `cancelled` with that reason code.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use tokio::time::{self, MissedTickBehavior};
+
+use crate::{
+    AppState, api::cancel_payout_in_tx, callbacks::build_cancel_callback_payload,
+    env::non_empty_env, events::ServerEvent, outbox, tenant::TenantScope,
+};
+
+const REASON_CODE: &str = "UNASSIGNED_TIMEOUT";
+const BATCH_SIZE: i64 = 100;
+
+/// Payouts the distributor could still pick up: the conditions of the
+/// unassigned queue, aged past `$1` hours.
+const STALE_PAYOUTS_QUERY: &str = r#"
+    SELECT p."id"
+    FROM "Payout" p
+    WHERE p."direction" = 'OUT'
+      AND p."status" = 'CREATED'
+      AND p."acceptedAt" IS NULL
+      AND p."traderId" IS NULL
+      AND p."createdAt" < LOCALTIMESTAMP - make_interval(hours => $1)
+      AND NOT EXISTS (SELECT 1 FROM "AggregatorPayout" ap WHERE ap."payoutId" = p."id")
+    ORDER BY p."createdAt"
+    LIMIT $2
+"#;
+
+/// Re-checks one payout under its row lock, so a payout assigned since the
+/// scan is left alone. Payouts locked by an assignment are skipped for now.
+const LOCK_STALE_PAYOUT_QUERY: &str = r#"
+    SELECT p."id"
+    FROM "Payout" p
+    WHERE p."id" = $1
+      AND p."status" = 'CREATED'
+      AND p."acceptedAt" IS NULL
+      AND p."traderId" IS NULL
+      AND p."createdAt" < LOCALTIMESTAMP - make_interval(hours => $2)
+    FOR UPDATE SKIP LOCKED
+"#;
+
+#[derive(Debug, Clone)]
+pub(crate) struct AutoCancelConfig {
+    after_hours: u32,
+    interval: Duration,
+}
+
+impl AutoCancelConfig {
+    pub(crate) fn from_env() -> Result<Self> {
+        let after_hours = match non_empty_env("AUTO_CANCEL_AFTER_HOURS") {
+            Some(value) => value
+                .parse::<u32>()
+                .ok()
+                .filter(|hours| *hours <= 24 * 365)
+                .context("AUTO_CANCEL_AFTER_HOURS must be an integer between 0 and 8760")?,
+            None => 0,
+        };
+        let interval_minutes = match non_empty_env("AUTO_CANCEL_INTERVAL_MINUTES") {
+            Some(value) => value
+                .parse::<u64>()
+                .context("AUTO_CANCEL_INTERVAL_MINUTES must be a positive integer")?
+                .max(1),
+            None => 5,
+        };
+        Ok(Self {
+            after_hours,
+            interval: Duration::from_secs(interval_minutes * 60),
+        })
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.after_hours > 0
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        format!(
+            "cancel payouts unassigned for {} h, checked every {} min",
+            self.after_hours,
+            self.interval.as_secs() / 60
+        )
+    }
+}
+
+/// Cancels one payout in its own transaction. `false` when it no longer
+/// qualifies.
+async fn cancel_stale_payout(state: &AppState, payout_id: &str, after_hours: u32) -> Result<bool> {
+    let mut tx = state.pool.begin().await?;
+    let locked: Option<String> = sqlx::query_scalar(LOCK_STALE_PAYOUT_QUERY)
+        .bind(payout_id)
+        .bind(after_hours as i32)
+        .fetch_optional(&mut *tx)
+        .await?;
+    if locked.is_none() {
+        return Ok(false);
+    }
+
+    let reason = format!("Not assigned within {after_hours} h");
+    let payout = cancel_payout_in_tx(
+        &mut tx,
+        payout_id,
+        Some(&reason),
+        Some(REASON_CODE),
+        &TenantScope::unrestricted(),
+        Some("auto"),
+    )
+    .await
+    .map_err(|err| anyhow!(err.message))?;
+    outbox::enqueue_callback(&mut tx, &payout.id, &build_cancel_callback_payload(&payout)).await?;
+    outbox::enqueue_event(
+        &mut tx,
+        &ServerEvent::payouts_updated("auto-cancel").for_merchants(payout.merchant_id.clone()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    println!(
+        "[auto-cancel] Cancelled payout {payout_id} (merchant {}), unassigned for over {after_hours} h",
+        payout.merchant_id.as_deref().unwrap_or("-")
+    );
+    Ok(true)
+}
+
+async fn cancel_stale_payouts(state: &AppState, after_hours: u32) -> Result<u64> {
+    let mut cancelled = 0;
+    loop {
+        let payout_ids: Vec<String> = sqlx::query_scalar(STALE_PAYOUTS_QUERY)
+            .bind(after_hours as i32)
+            .bind(BATCH_SIZE)
+            .fetch_all(&state.pool)
+            .await
+            .context("Failed to load stale payouts")?;
+
+        let mut batch = 0;
+        for payout_id in &payout_ids {
+            match cancel_stale_payout(state, payout_id, after_hours).await {
+                Ok(true) => batch += 1,
+                Ok(false) => {}
+                Err(err) => eprintln!("[auto-cancel] Failed to cancel payout {payout_id}: {err:#}"),
+            }
+        }
+        if batch > 0 {
+            state.outbox_notify.notify_one();
+        }
+        cancelled += batch;
+        // A short or fruitless batch means the rest is locked or failing;
+        // leave it to the next check instead of spinning on it.
+        if payout_ids.len() < BATCH_SIZE as usize || batch == 0 {
+            return Ok(cancelled);
+        }
+    }
+}
+
+pub(crate) async fn auto_cancel_worker(state: AppState, config: AutoCancelConfig) {
+    if !config.is_enabled() {
+        return;
+    }
+    let mut interval = time::interval(config.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+        match cancel_stale_payouts(&state, config.after_hours).await {
+            Ok(0) => {}
+            Ok(cancelled) => println!(
+                "[auto-cancel] Cancelled {cancelled} payout(s) unassigned for over {} h",
+                config.after_hours
+            ),
+            Err(err) => eprintln!("[auto-cancel] {err:#}"),
+        }
+    }
+}
@@ -0,0 +1,403 @@
+//! Planned trader unavailability: vacations and other known absences, kept as
+//! windows in `TraderUnavailability`. A trader inside one of their windows is
+//! not eligible for payouts, neither from the distributor nor by hand, and
+//! comes back by themselves once it ends.
+//!
+//! Operators manage the windows through `/api/traders/:id/unavailability`,
+//! traders through `/api/self/unavailability`. Only windows that have not
+//! ended are listed; the traders table shows each trader's next one. A worker
+//! announces windows starting and ending so dashboards refresh the traders.
+
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{Context, Result};
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use tokio::time::{self, MissedTickBehavior};
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::{
+    ApiResult, AppState,
+    api::{ensure_trader_in_scope, normalize_optional_text},
+    auth,
+    errors::ApiError,
+    events::ServerEvent,
+    internal_error,
+    settings::audit_change,
+    tenant::TenantScope,
+    timestamps::UtcTimestamp,
+    trader_auth::TraderScope,
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// Windows may be planned up to a year ahead and last up to 90 days.
+const MAX_LEAD_DAYS: i64 = 365;
+const MAX_WINDOW_DAYS: i64 = 90;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UnavailabilityWindow {
+    pub(crate) id: String,
+    #[sqlx(rename = "traderId")]
+    pub(crate) trader_id: String,
+    #[sqlx(rename = "startsAt")]
+    pub(crate) starts_at: UtcTimestamp,
+    #[sqlx(rename = "endsAt")]
+    pub(crate) ends_at: UtcTimestamp,
+    pub(crate) reason: Option<String>,
+    #[sqlx(rename = "createdBy")]
+    created_by: Option<String>,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+}
+
+/// Windows that have not ended yet, earliest first.
+async fn load_windows(
+    pool: &PgPool,
+    trader_id: Option<&str>,
+) -> sqlx::Result<Vec<UnavailabilityWindow>> {
+    sqlx::query_as::<_, UnavailabilityWindow>(
+        r#"
+        SELECT "id", "traderId", "startsAt", "endsAt", "reason", "createdBy", "createdAt"
+        FROM "TraderUnavailability"
+        WHERE "endsAt" > LOCALTIMESTAMP
+          AND ($1::text IS NULL OR "traderId" = $1)
+        ORDER BY "startsAt", "endsAt"
+        "#,
+    )
+    .bind(trader_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Each trader's next (or current) window, for the traders table.
+pub(crate) async fn next_windows(
+    pool: &PgPool,
+) -> sqlx::Result<HashMap<String, UnavailabilityWindow>> {
+    let mut next = HashMap::new();
+    for window in load_windows(pool, None).await? {
+        next.entry(window.trader_id.clone()).or_insert(window);
+    }
+    Ok(next)
+}
+
+async fn load_window(
+    pool: &PgPool,
+    trader_id: &str,
+    window_id: &str,
+) -> ApiResult<UnavailabilityWindow> {
+    sqlx::query_as::<_, UnavailabilityWindow>(
+        r#"
+        SELECT "id", "traderId", "startsAt", "endsAt", "reason", "createdBy", "createdAt"
+        FROM "TraderUnavailability"
+        WHERE "id" = $1 AND "traderId" = $2
+        "#,
+    )
+    .bind(window_id)
+    .bind(trader_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(|| {
+        ApiError::from((
+            StatusCode::NOT_FOUND,
+            "Unavailability window not found".to_string(),
+        ))
+    })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UnavailabilityRequest {
+    starts_at: UtcTimestamp,
+    ends_at: UtcTimestamp,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+impl UnavailabilityRequest {
+    fn validated(self) -> ApiResult<Self> {
+        let invalid =
+            |message: &str| ApiError::from((StatusCode::BAD_REQUEST, message.to_string()));
+        let now = Utc::now();
+        if self.ends_at <= self.starts_at {
+            return Err(invalid("endsAt must be after startsAt"));
+        }
+        if *self.ends_at <= now {
+            return Err(invalid("endsAt must be in the future"));
+        }
+        if *self.starts_at > now + chrono::Duration::days(MAX_LEAD_DAYS) {
+            return Err(invalid("startsAt must be within a year"));
+        }
+        if *self.ends_at - *self.starts_at > chrono::Duration::days(MAX_WINDOW_DAYS) {
+            return Err(invalid("A window may last at most 90 days"));
+        }
+        Ok(Self {
+            reason: normalize_optional_text(self.reason),
+            ..self
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UnavailabilityResponse {
+    trader_id: String,
+    windows: Vec<UnavailabilityWindow>,
+}
+
+async fn list(pool: &PgPool, trader_id: String) -> ApiResult<Json<UnavailabilityResponse>> {
+    let windows = load_windows(pool, Some(&trader_id))
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(UnavailabilityResponse { trader_id, windows }))
+}
+
+async fn create(
+    state: &AppState,
+    trader_id: &str,
+    request: UnavailabilityRequest,
+    actor: Option<&str>,
+) -> ApiResult<UnavailabilityWindow> {
+    let request = request.validated()?;
+    let window_id = Uuid::new_v4().to_string();
+    sqlx::query(
+        r#"
+        INSERT INTO "TraderUnavailability"
+            ("id", "traderId", "startsAt", "endsAt", "reason", "createdBy")
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(&window_id)
+    .bind(trader_id)
+    .bind(request.starts_at.naive_utc())
+    .bind(request.ends_at.naive_utc())
+    .bind(&request.reason)
+    .bind(actor)
+    .execute(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let window = load_window(&state.pool, trader_id, &window_id).await?;
+    println!(
+        "[availability] Trader {trader_id} unavailable from {} to {}",
+        window.starts_at, window.ends_at
+    );
+    audit_change(
+        &state.pool,
+        "trader-unavailability",
+        Some(trader_id),
+        actor,
+        Option::<UnavailabilityWindow>::None,
+        &window,
+    )
+    .await;
+    let _ = state.event_tx.send(ServerEvent::traders_updated());
+    Ok(window)
+}
+
+async fn delete(
+    state: &AppState,
+    trader_id: &str,
+    window_id: &str,
+    actor: Option<&str>,
+) -> ApiResult<StatusCode> {
+    let previous = load_window(&state.pool, trader_id, window_id).await?;
+    sqlx::query(r#"DELETE FROM "TraderUnavailability" WHERE "id" = $1"#)
+        .bind(window_id)
+        .execute(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    println!(
+        "[availability] Trader {trader_id} window {} to {} removed",
+        previous.starts_at, previous.ends_at
+    );
+    audit_change(
+        &state.pool,
+        "trader-unavailability",
+        Some(trader_id),
+        actor,
+        &previous,
+        Option::<UnavailabilityWindow>::None,
+    )
+    .await;
+    let _ = state.event_tx.send(ServerEvent::traders_updated());
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub(crate) async fn get_trader_unavailability(
+    Path(trader_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<UnavailabilityResponse>> {
+    ensure_trader_in_scope(&state.pool, &trader_id, scope.merchant_ids()).await?;
+    list(&state.pool, trader_id).await
+}
+
+pub(crate) async fn create_trader_unavailability(
+    Path(trader_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<UnavailabilityRequest>,
+) -> ApiResult<(StatusCode, Json<UnavailabilityWindow>)> {
+    ensure_trader_in_scope(&state.pool, &trader_id, scope.merchant_ids()).await?;
+    let known: bool = sqlx::query_scalar(r#"SELECT EXISTS (SELECT 1 FROM "User" WHERE "id" = $1)"#)
+        .bind(&trader_id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    if !known {
+        return Err(ApiError::trader_not_found());
+    }
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let window = create(&state, &trader_id, request, actor.as_deref()).await?;
+    Ok((StatusCode::CREATED, Json(window)))
+}
+
+/// Moves a window or changes its reason.
+pub(crate) async fn update_trader_unavailability(
+    Path((trader_id, window_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<UnavailabilityRequest>,
+) -> ApiResult<Json<UnavailabilityWindow>> {
+    ensure_trader_in_scope(&state.pool, &trader_id, scope.merchant_ids()).await?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let request = request.validated()?;
+    let previous = load_window(&state.pool, &trader_id, &window_id).await?;
+    sqlx::query(
+        r#"
+        UPDATE "TraderUnavailability"
+        SET "startsAt" = $2,
+            "endsAt" = $3,
+            "reason" = $4
+        WHERE "id" = $1
+        "#,
+    )
+    .bind(&window_id)
+    .bind(request.starts_at.naive_utc())
+    .bind(request.ends_at.naive_utc())
+    .bind(&request.reason)
+    .execute(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let window = load_window(&state.pool, &trader_id, &window_id).await?;
+    println!(
+        "[availability] Trader {trader_id} window moved to {} - {}",
+        window.starts_at, window.ends_at
+    );
+    audit_change(
+        &state.pool,
+        "trader-unavailability",
+        Some(&trader_id),
+        actor.as_deref(),
+        &previous,
+        &window,
+    )
+    .await;
+    let _ = state.event_tx.send(ServerEvent::traders_updated());
+    Ok(Json(window))
+}
+
+pub(crate) async fn delete_trader_unavailability(
+    Path((trader_id, window_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+) -> ApiResult<StatusCode> {
+    ensure_trader_in_scope(&state.pool, &trader_id, scope.merchant_ids()).await?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    delete(&state, &trader_id, &window_id, actor.as_deref()).await
+}
+
+pub(crate) async fn get_self_unavailability(
+    State(state): State<AppState>,
+    trader: TraderScope,
+) -> ApiResult<Json<UnavailabilityResponse>> {
+    list(&state.pool, trader.trader_id().to_string()).await
+}
+
+pub(crate) async fn create_self_unavailability(
+    State(state): State<AppState>,
+    trader: TraderScope,
+    Json(request): Json<UnavailabilityRequest>,
+) -> ApiResult<(StatusCode, Json<UnavailabilityWindow>)> {
+    let trader_id = trader.trader_id();
+    let window = create(&state, trader_id, request, Some(trader_id)).await?;
+    Ok((StatusCode::CREATED, Json(window)))
+}
+
+pub(crate) async fn delete_self_unavailability(
+    Path(window_id): Path<String>,
+    State(state): State<AppState>,
+    trader: TraderScope,
+) -> ApiResult<StatusCode> {
+    let trader_id = trader.trader_id();
+    delete(&state, trader_id, &window_id, Some(trader_id)).await
+}
+
+/// Windows that started or ended in `(since, until]`, as the trader, whether
+/// it started and when it ends.
+async fn boundaries(
+    pool: &PgPool,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Result<Vec<(String, bool, UtcTimestamp)>> {
+    sqlx::query_as::<_, (String, bool, UtcTimestamp)>(
+        r#"
+        SELECT "traderId", TRUE, "endsAt"
+        FROM "TraderUnavailability"
+        WHERE "startsAt" > $1 AND "startsAt" <= $2 AND "endsAt" > $2
+        UNION ALL
+        SELECT "traderId", FALSE, "endsAt"
+        FROM "TraderUnavailability"
+        WHERE "endsAt" > $1 AND "endsAt" <= $2
+        "#,
+    )
+    .bind(since.naive_utc())
+    .bind(until.naive_utc())
+    .fetch_all(pool)
+    .await
+    .context("Failed to check trader unavailability")
+}
+
+/// Eligibility follows the windows by itself; this only tells dashboards
+/// when a trader leaves or rejoins the traders table.
+pub(crate) async fn boundary_worker(state: AppState) {
+    let mut interval = time::interval(CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut since = Utc::now();
+
+    loop {
+        interval.tick().await;
+        let until = Utc::now();
+        match boundaries(&state.pool, since, until).await {
+            Ok(changes) => {
+                for (trader_id, started, ends_at) in &changes {
+                    if *started {
+                        println!(
+                            "[availability] Trader {trader_id} is unavailable until {ends_at}"
+                        );
+                    } else {
+                        println!("[availability] Trader {trader_id} is available again");
+                    }
+                }
+                if !changes.is_empty() {
+                    let _ = state.event_tx.send(ServerEvent::traders_updated());
+                }
+                since = until;
+            }
+            Err(err) => eprintln!("[availability] {err:#}"),
+        }
+    }
+}
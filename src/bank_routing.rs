@@ -0,0 +1,92 @@
+//! Per-bank routing weights. `TraderBankWeight` can give traders a weight for
+//! a bank, e.g. SBER: trader A 70, trader B 30. Payouts of such a bank only
+//! go to the traders listed for it, shared in proportion to their weights;
+//! payouts of banks without weights keep the regular rotation.
+//!
+//! Shares are kept with smooth weighted round-robin, whose credits live next
+//! to the rotation index and carry over between cycles, so a 70/30 split also
+//! holds when every cycle only sees a single SBER payout.
+
+use std::collections::HashMap;
+
+use sqlx::PgPool;
+
+/// Trader weights keyed by normalized bank name, then trader id.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BankWeights {
+    banks: HashMap<String, HashMap<String, f64>>,
+}
+
+/// Where the distributor continues: the next trader of the regular rotation,
+/// the next team and the position within each team when balancing across
+/// teams, and the smooth round-robin credits per bank and trader.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RotationState {
+    pub(crate) next_index: usize,
+    pub(crate) next_team_index: usize,
+    /// Keyed by team id, "" for traders without a team.
+    pub(crate) team_cursors: HashMap<String, usize>,
+    credits: HashMap<String, HashMap<String, f64>>,
+}
+
+pub(crate) fn normalize_bank(bank: &str) -> String {
+    bank.trim().to_uppercase()
+}
+
+impl BankWeights {
+    pub(crate) async fn load(pool: &PgPool) -> sqlx::Result<Self> {
+        let rows: Vec<(String, String, f64)> = sqlx::query_as(
+            r#"
+            SELECT "bank", "traderId", "weight"
+            FROM "TraderBankWeight"
+            WHERE "weight" > 0
+            "#,
+        )
+        .fetch_all(pool)
+        .await?;
+        let mut banks: HashMap<String, HashMap<String, f64>> = HashMap::new();
+        for (bank, trader_id, weight) in rows {
+            banks.entry(bank).or_default().insert(trader_id, weight);
+        }
+        Ok(Self { banks })
+    }
+
+    /// The weights of the payout's bank, `None` when it routes as usual.
+    pub(crate) fn for_bank(&self, bank: Option<&str>) -> Option<&HashMap<String, f64>> {
+        self.banks.get(&normalize_bank(bank?))
+    }
+
+    /// Picks among `candidates` (trader ids that can take the payout) by
+    /// smooth weighted round-robin and updates the credits in `state`.
+    /// Traders without a weight for the bank are never picked.
+    pub(crate) fn pick<'a>(
+        &self,
+        state: &mut RotationState,
+        bank: &str,
+        candidates: impl IntoIterator<Item = &'a str>,
+    ) -> Option<&'a str> {
+        let bank = normalize_bank(bank);
+        let weights = self.banks.get(&bank)?;
+        let credits = state.credits.entry(bank).or_default();
+
+        let mut total = 0.0;
+        let mut best: Option<(&'a str, f64)> = None;
+        for trader_id in candidates {
+            let Some(weight) = weights.get(trader_id).copied() else {
+                continue;
+            };
+            let credit = credits.entry(trader_id.to_string()).or_default();
+            *credit += weight;
+            total += weight;
+            if best.is_none_or(|(_, best_credit)| *credit > best_credit) {
+                best = Some((trader_id, *credit));
+            }
+        }
+
+        let (selected, _) = best?;
+        if let Some(credit) = credits.get_mut(selected) {
+            *credit -= total;
+        }
+        Some(selected)
+    }
+}
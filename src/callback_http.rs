@@ -0,0 +1,288 @@
+//! HTTP client used for merchant callbacks. Configured from the environment:
+//! `CALLBACK_TIMEOUT_SECONDS` (default 15), `CALLBACK_CONNECT_TIMEOUT_SECONDS`
+//! (default 5), `CALLBACK_PROXY_URL` (an `http`, `https`, `socks5` or
+//! `socks5h` URL) for endpoints that are only reachable through a proxy and
+//! `CALLBACK_CA_BUNDLE`, a PEM file with extra root certificates for merchants
+//! behind a private CA.
+//!
+//! Merchants can additionally get their own request timeout, extra headers,
+//! a proxy that replaces `CALLBACK_PROXY_URL` and a webhook URL that replaces
+//! the platform's `merchantWebhookUrl` through `MerchantCallbackOverride`.
+//! Merchants that require mutual TLS get a client certificate, see
+//! [`crate::client_certs`]. Each distinct proxy and certificate gets its own
+//! client, built on first use and cached in [`CallbackClients`].
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use axum::http::{HeaderName, HeaderValue};
+use reqwest::{Certificate, Client, Identity, Proxy, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    env::non_empty_env,
+    secrets::{self, Secrets},
+};
+
+/// Headers the service sets itself; overrides may not replace them.
+const RESERVED_HEADERS: &[&str] = &[
+    "content-type",
+    "content-length",
+    "host",
+    "x-merchant-api-key",
+    "x-idempotency-key",
+];
+const MAX_OVERRIDE_TIMEOUT_SECONDS: i32 = 120;
+const PROXY_SCHEMES: &[&str] = &["http", "https", "socks5", "socks5h"];
+
+#[derive(Debug, Clone)]
+pub(crate) struct CallbackHttpConfig {
+    timeout_seconds: u64,
+    connect_timeout_seconds: u64,
+    proxy_url: Option<String>,
+    ca_bundle: Option<String>,
+}
+
+impl CallbackHttpConfig {
+    pub(crate) fn from_env() -> Result<Self> {
+        let timeout_seconds = seconds_env("CALLBACK_TIMEOUT_SECONDS", 15)?;
+        let connect_timeout_seconds = seconds_env("CALLBACK_CONNECT_TIMEOUT_SECONDS", 5)?;
+        let proxy_url = non_empty_env("CALLBACK_PROXY_URL");
+        if let Some(url) = &proxy_url {
+            validate_proxy_url(url).map_err(|err| anyhow::anyhow!("CALLBACK_PROXY_URL: {err}"))?;
+        }
+        Ok(Self {
+            timeout_seconds,
+            connect_timeout_seconds: connect_timeout_seconds.min(timeout_seconds),
+            proxy_url,
+            ca_bundle: non_empty_env("CALLBACK_CA_BUNDLE"),
+        })
+    }
+
+    /// One-line summary for the startup log; the proxy URL is left out
+    /// because it may carry credentials.
+    pub(crate) fn describe(&self) -> String {
+        format!(
+            "timeout={}s, connect timeout={}s, proxy={}, CA bundle={}",
+            self.timeout_seconds,
+            self.connect_timeout_seconds,
+            if self.proxy_url.is_some() { "on" } else { "off" },
+            self.ca_bundle.as_deref().unwrap_or("system")
+        )
+    }
+
+    /// Builds a client that goes through `proxy_url`, or directly when it is
+    /// `None`, and presents `identity` (certificate chain and private key in
+    /// PEM) to servers that ask for a client certificate.
+    fn build_client(&self, proxy_url: Option<&str>, identity: Option<&str>) -> Result<Client> {
+        let mut builder = Client::builder()
+            .timeout(Duration::from_secs(self.timeout_seconds))
+            .connect_timeout(Duration::from_secs(self.connect_timeout_seconds));
+
+        if let Some(proxy_url) = proxy_url {
+            builder = builder.proxy(Proxy::all(proxy_url).context("Proxy URL is invalid")?);
+        }
+        if let Some(identity) = identity {
+            builder = builder.use_rustls_tls().identity(
+                Identity::from_pem(identity.as_bytes()).context("Invalid client certificate")?,
+            );
+        }
+        if let Some(path) = &self.ca_bundle {
+            let pem = fs::read(path).with_context(|| format!("Failed to read CA bundle {path}"))?;
+            let certificates = Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("CA bundle {path} is not valid PEM"))?;
+            if certificates.is_empty() {
+                bail!("CA bundle {path} contains no certificates");
+            }
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        builder.build().context("Failed to build callback HTTP client")
+    }
+}
+
+/// Callback HTTP clients keyed by proxy and client certificate. Clients keep
+/// their connection pools, so merchants with the same settings share one.
+#[derive(Clone)]
+pub(crate) struct CallbackClients {
+    config: CallbackHttpConfig,
+    default: Client,
+    by_settings: Arc<Mutex<HashMap<String, Client>>>,
+}
+
+impl CallbackClients {
+    /// Builds the default client up front so a bad CA bundle or proxy fails
+    /// startup.
+    pub(crate) fn new(config: CallbackHttpConfig) -> Result<Self> {
+        let default = config.build_client(config.proxy_url.as_deref(), None)?;
+        Ok(Self {
+            config,
+            default,
+            by_settings: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// The client for callbacks without a merchant proxy, also used for
+    /// trader webhooks.
+    pub(crate) fn default_client(&self) -> &Client {
+        &self.default
+    }
+
+    /// The client for a merchant's proxy and client certificate; the default
+    /// one when it has neither.
+    pub(crate) fn client_for(
+        &self,
+        proxy_url: Option<&str>,
+        identity: Option<&str>,
+    ) -> Result<Client> {
+        if proxy_url.is_none() && identity.is_none() {
+            return Ok(self.default.clone());
+        }
+        let proxy_url = proxy_url.or(self.config.proxy_url.as_deref());
+        // The key material itself is not kept in the map key.
+        let key = format!(
+            "{}|{}",
+            proxy_url.unwrap_or_default(),
+            identity
+                .map(|identity| hex::encode(Sha256::digest(identity.as_bytes())))
+                .unwrap_or_default()
+        );
+        let mut clients = self
+            .by_settings
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        if let Some(client) = clients.get(&key) {
+            return Ok(client.clone());
+        }
+        let client = self.config.build_client(proxy_url, identity)?;
+        clients.insert(key, client.clone());
+        Ok(client)
+    }
+}
+
+/// Accepts the proxy schemes reqwest can connect through.
+fn validate_proxy_url(url: &str) -> Result<(), String> {
+    let parsed = Url::parse(url).map_err(|_| "proxy URL is not a valid URL".to_string())?;
+    if !PROXY_SCHEMES.contains(&parsed.scheme()) {
+        return Err(format!(
+            "proxy URL must use one of {}",
+            PROXY_SCHEMES.join(", ")
+        ));
+    }
+    if parsed.host_str().is_none_or(str::is_empty) {
+        return Err("proxy URL must include a host".to_string());
+    }
+    Proxy::all(url).map_err(|err| format!("proxy URL is invalid: {err}"))?;
+    Ok(())
+}
+
+/// Per-merchant callback settings stored in `MerchantCallbackOverride`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CallbackOverride {
+    #[serde(default)]
+    pub(crate) headers: BTreeMap<String, String>,
+    pub(crate) timeout_seconds: Option<i32>,
+    /// Used instead of the payout's `merchantWebhookUrl` when set.
+    #[serde(default)]
+    pub(crate) webhook_url: Option<String>,
+    /// Used instead of `CALLBACK_PROXY_URL` when set.
+    #[serde(default)]
+    pub(crate) proxy_url: Option<String>,
+}
+
+impl CallbackOverride {
+    /// Normalizes header names and rejects values reqwest would refuse to send.
+    pub(crate) fn sanitized(self) -> Result<Self, String> {
+        let mut headers = BTreeMap::new();
+        for (name, value) in self.headers {
+            let name = name.trim().to_ascii_lowercase();
+            if HeaderName::from_bytes(name.as_bytes()).is_err() {
+                return Err(format!("Invalid header name {name}"));
+            }
+            if RESERVED_HEADERS.contains(&name.as_str()) {
+                return Err(format!("Header {name} is set by the service and cannot be overridden"));
+            }
+            if HeaderValue::from_str(&value).is_err() {
+                return Err(format!("Invalid value for header {name}"));
+            }
+            headers.insert(name, value);
+        }
+        if let Some(timeout) = self.timeout_seconds
+            && !(1..=MAX_OVERRIDE_TIMEOUT_SECONDS).contains(&timeout)
+        {
+            return Err(format!(
+                "timeoutSeconds must be between 1 and {MAX_OVERRIDE_TIMEOUT_SECONDS}"
+            ));
+        }
+        let webhook_url = self
+            .webhook_url
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty());
+        if let Some(url) = &webhook_url
+            && !Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+        {
+            return Err("webhookUrl must be an absolute http(s) URL".to_string());
+        }
+        let proxy_url = self
+            .proxy_url
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty());
+        if let Some(url) = &proxy_url {
+            validate_proxy_url(url).map_err(|err| format!("proxyUrl: {err}"))?;
+        }
+        Ok(Self {
+            headers,
+            timeout_seconds: self.timeout_seconds,
+            webhook_url,
+            proxy_url,
+        })
+    }
+
+    /// Header values as stored, sealed with [`secrets`](crate::secrets) since
+    /// they often carry merchant credentials.
+    pub(crate) fn stored_headers(&self, secrets: Option<&Secrets>) -> Result<Value> {
+        secrets::seal_values(secrets, &serde_json::to_value(&self.headers)?)
+    }
+
+    /// Reads headers written by [`Self::stored_headers`].
+    pub(crate) fn headers_from_stored(
+        stored: Option<&Value>,
+        secrets: Option<&Secrets>,
+    ) -> Result<BTreeMap<String, String>> {
+        let Some(stored) = stored else {
+            return Ok(BTreeMap::new());
+        };
+        let headers = secrets::open_values(secrets, stored)
+            .context("Failed to decrypt callback override headers")?;
+        Ok(serde_json::from_value(headers).unwrap_or_default())
+    }
+
+    pub(crate) fn apply(&self, mut request: RequestBuilder) -> RequestBuilder {
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        if let Some(timeout) = self.timeout_seconds {
+            request = request.timeout(Duration::from_secs(timeout.max(1) as u64));
+        }
+        request
+    }
+}
+
+fn seconds_env(name: &str, default: u64) -> Result<u64> {
+    Ok(non_empty_env(name)
+        .map(|value| value.parse::<u64>())
+        .transpose()
+        .with_context(|| format!("{name} must be a positive integer"))?
+        .unwrap_or(default)
+        .max(1))
+}
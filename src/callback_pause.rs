@@ -0,0 +1,222 @@
+//! Pausing a merchant's callbacks while its endpoint is under maintenance.
+//! The relay leaves a paused merchant's callbacks pending in the outbox
+//! instead of sending them into errors and dead-lettering them; on resume
+//! they go out in their original order.
+//!
+//! `POST /api/merchants/:id/callbacks/pause` (with an optional `reason`) and
+//! `POST /api/merchants/:id/callbacks/resume` switch a merchant, and
+//! `GET /api/merchants` lists the merchants with their pause state and the
+//! callbacks waiting for them.
+
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use tower_sessions::Session;
+
+use crate::{
+    ApiResult, AppState, api::normalize_optional_text, auth, errors::ApiError, internal_error,
+    settings::audit_change, tenant::TenantScope, timestamps::UtcTimestamp,
+};
+
+const AUDIT_SECTION: &str = "merchant-callback-pause";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CallbackPause {
+    reason: Option<String>,
+    paused_by: Option<String>,
+    paused_at: UtcTimestamp,
+}
+
+#[derive(Debug, FromRow)]
+struct MerchantRow {
+    id: String,
+    name: Option<String>,
+    reason: Option<String>,
+    #[sqlx(rename = "pausedBy")]
+    paused_by: Option<String>,
+    #[sqlx(rename = "pausedAt")]
+    paused_at: Option<UtcTimestamp>,
+    #[sqlx(rename = "queuedCallbacks")]
+    queued_callbacks: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MerchantView {
+    id: String,
+    name: Option<String>,
+    /// `None` while callbacks flow.
+    callbacks_paused: Option<CallbackPause>,
+    /// Callbacks in the outbox that have not been sent yet.
+    queued_callbacks: i64,
+}
+
+impl From<MerchantRow> for MerchantView {
+    fn from(row: MerchantRow) -> Self {
+        Self {
+            callbacks_paused: row.paused_at.map(|paused_at| CallbackPause {
+                reason: row.reason,
+                paused_by: row.paused_by,
+                paused_at,
+            }),
+            id: row.id,
+            name: row.name,
+            queued_callbacks: row.queued_callbacks,
+        }
+    }
+}
+
+const MERCHANTS_QUERY: &str = r#"
+    SELECT
+        m."id",
+        m."name",
+        cp."reason",
+        cp."pausedBy",
+        cp."pausedAt",
+        (
+            SELECT COUNT(*)
+            FROM "OutboxMessage" o
+            JOIN "Payout" p ON p."id" = o."payload"->>'payoutId'
+            WHERE o."kind" = 'callback'
+              AND o."status" = 'pending'
+              AND p."merchantId" = m."id"
+        ) AS "queuedCallbacks"
+    FROM "Merchant" m
+    LEFT JOIN "MerchantCallbackPause" cp ON cp."merchantId" = m."id"
+    WHERE ($1::text[] IS NULL OR m."id" = ANY($1::text[]))
+      AND ($2::text IS NULL OR m."id" = $2)
+    ORDER BY m."name" NULLS LAST, m."id"
+"#;
+
+async fn fetch_merchants(
+    pool: &PgPool,
+    merchant_ids: Option<&[String]>,
+    merchant_id: Option<&str>,
+) -> sqlx::Result<Vec<MerchantView>> {
+    let rows = sqlx::query_as::<_, MerchantRow>(MERCHANTS_QUERY)
+        .bind(merchant_ids)
+        .bind(merchant_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(MerchantView::from).collect())
+}
+
+/// The merchant as the caller may see it; tenants only reach their own.
+async fn load_merchant(
+    state: &AppState,
+    scope: &TenantScope,
+    merchant_id: &str,
+) -> ApiResult<MerchantView> {
+    fetch_merchants(&state.pool, scope.merchant_ids(), Some(merchant_id))
+        .await
+        .map_err(internal_error)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| ApiError::from((StatusCode::NOT_FOUND, "Merchant not found".to_string())))
+}
+
+pub(crate) async fn get_merchants(
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<Vec<MerchantView>>> {
+    fetch_merchants(&state.pool, scope.merchant_ids(), None)
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PauseCallbacksRequest {
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+pub(crate) async fn pause_merchant_callbacks(
+    Path(merchant_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<PauseCallbacksRequest>,
+) -> ApiResult<Json<MerchantView>> {
+    let before = load_merchant(&state, &scope, &merchant_id).await?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let reason = normalize_optional_text(request.reason);
+
+    sqlx::query(
+        r#"
+        INSERT INTO "MerchantCallbackPause" ("merchantId", "reason", "pausedBy")
+        VALUES ($1, $2, $3)
+        ON CONFLICT ("merchantId") DO UPDATE
+        SET "reason" = EXCLUDED."reason"
+        "#,
+    )
+    .bind(&merchant_id)
+    .bind(&reason)
+    .bind(&actor)
+    .execute(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let after = load_merchant(&state, &scope, &merchant_id).await?;
+    println!(
+        "[callbacks] Paused callbacks for merchant {merchant_id}{}, {} queued",
+        reason
+            .as_deref()
+            .map(|reason| format!(" ({reason})"))
+            .unwrap_or_default(),
+        after.queued_callbacks
+    );
+    audit_change(
+        &state.pool,
+        AUDIT_SECTION,
+        Some(&merchant_id),
+        actor.as_deref(),
+        &before.callbacks_paused,
+        &after.callbacks_paused,
+    )
+    .await;
+    Ok(Json(after))
+}
+
+/// Lifts the pause and wakes the relay so the queued callbacks drain.
+pub(crate) async fn resume_merchant_callbacks(
+    Path(merchant_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+) -> ApiResult<Json<MerchantView>> {
+    let before = load_merchant(&state, &scope, &merchant_id).await?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+
+    let resumed = sqlx::query(r#"DELETE FROM "MerchantCallbackPause" WHERE "merchantId" = $1"#)
+        .bind(&merchant_id)
+        .execute(&state.pool)
+        .await
+        .map_err(internal_error)?
+        .rows_affected()
+        > 0;
+
+    let after = load_merchant(&state, &scope, &merchant_id).await?;
+    if resumed {
+        state.outbox_notify.notify_one();
+        println!(
+            "[callbacks] Resumed callbacks for merchant {merchant_id}, releasing {} queued",
+            after.queued_callbacks
+        );
+        audit_change(
+            &state.pool,
+            AUDIT_SECTION,
+            Some(&merchant_id),
+            actor.as_deref(),
+            &before.callbacks_paused,
+            &after.callbacks_paused,
+        )
+        .await;
+    }
+    Ok(Json(after))
+}
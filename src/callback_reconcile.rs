@@ -0,0 +1,97 @@
+//! Reconciliation of cancel callbacks that could not be sent because the
+//! merchant had no webhook URL or API token yet. The relay dead-letters them
+//! right away since retrying cannot help; this job re-checks them every
+//! `CALLBACK_RECONCILE_INTERVAL_SECONDS` (default 300, `0` disables it) and
+//! puts them back in the outbox once both are configured, through a callback
+//! override or on the payout and the merchant.
+
+use std::{env, time::Duration};
+
+use anyhow::{Context, Result};
+use tokio::time::{self, MissedTickBehavior};
+
+use crate::{
+    AppState,
+    callbacks::{MISSING_TOKEN_ERROR, MISSING_WEBHOOK_URL_ERROR},
+};
+
+/// Dead-lettered CANCELED callbacks that failed for missing configuration
+/// and whose merchant now has both a webhook URL and a token.
+const RECONCILE_QUERY: &str = r#"
+    UPDATE "OutboxMessage"
+    SET "status" = 'pending',
+        "attempts" = 0,
+        "nextAttemptAt" = CURRENT_TIMESTAMP,
+        "leasedUntil" = NULL,
+        "failedAt" = NULL
+    WHERE "id" IN (
+        SELECT o."id"
+        FROM "OutboxMessage" o
+        JOIN "Payout" p ON p."id" = o."payload"->>'payoutId'
+        LEFT JOIN "Merchant" m ON m."id" = p."merchantId"
+        LEFT JOIN "MerchantCallbackOverride" co ON co."merchantId" = p."merchantId"
+        WHERE o."kind" = 'callback'
+          AND o."status" = 'failed'
+          AND o."lastError" = ANY($1::text[])
+          AND o."payload"->'body'->>'event' = 'CANCELED'
+          AND NULLIF(BTRIM(COALESCE(co."webhookUrl", p."merchantWebhookUrl")), '') IS NOT NULL
+          AND NULLIF(BTRIM(m."token"), '') IS NOT NULL
+    )
+"#;
+
+#[derive(Debug, Clone)]
+pub(crate) struct ReconcileConfig {
+    interval_seconds: u64,
+}
+
+impl ReconcileConfig {
+    pub(crate) fn from_env() -> Result<Self> {
+        let interval_seconds = match env::var("CALLBACK_RECONCILE_INTERVAL_SECONDS")
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+        {
+            Some(value) => value
+                .parse::<u64>()
+                .context("CALLBACK_RECONCILE_INTERVAL_SECONDS must be a non-negative integer")?,
+            None => 300,
+        };
+        Ok(Self { interval_seconds })
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.interval_seconds > 0
+    }
+}
+
+async fn requeue_reconfigured(state: &AppState) -> Result<u64> {
+    let requeued = sqlx::query(RECONCILE_QUERY)
+        .bind([MISSING_WEBHOOK_URL_ERROR, MISSING_TOKEN_ERROR])
+        .execute(&state.pool)
+        .await
+        .context("Failed to requeue reconfigured callbacks")?
+        .rows_affected();
+    if requeued > 0 {
+        state.outbox_notify.notify_one();
+    }
+    Ok(requeued)
+}
+
+pub(crate) async fn reconcile_worker(state: AppState, config: ReconcileConfig) {
+    if !config.is_enabled() {
+        return;
+    }
+    let mut interval = time::interval(Duration::from_secs(config.interval_seconds));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+        match requeue_reconfigured(&state).await {
+            Ok(0) => {}
+            Ok(requeued) => println!(
+                "[callbacks] Requeued {requeued} cancel callback(s) now that their merchant is configured"
+            ),
+            Err(err) => eprintln!("[callbacks] {err:#}"),
+        }
+    }
+}
@@ -0,0 +1,350 @@
+//! Callback delivery SLA per merchant. Every callback request the relay sends
+//! is recorded in `CallbackDelivery` with its outcome and its delivery
+//! latency, the time from queueing the callback to the end of the attempt.
+//! Callbacks that could not be sent at all (no webhook URL or token) are a
+//! configuration problem and are left out.
+//!
+//! `GET /api/merchants/:id/callback-sla` reports the success rate and the
+//! p95 latency of delivered callbacks over rolling windows. A worker checks
+//! the last `CALLBACK_SLA_WINDOW_MINUTES` (default 60) and raises a
+//! `callback-sla-breached` event, and an email when alerts are configured,
+//! once a merchant with at least `CALLBACK_SLA_MIN_ATTEMPTS` (default 20)
+//! attempts falls below `CALLBACK_SLA_SUCCESS_THRESHOLD` percent (default 95,
+//! `0` disables the alert); `callback-sla-recovered` follows when it is back.
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use tokio::time::{self, MissedTickBehavior};
+use uuid::Uuid;
+
+use crate::{
+    ApiResult, AppState, callbacks::CallbackDispatchResult, env::non_empty_env, errors::ApiError,
+    events::ServerEvent, internal_error, tenant::TenantScope,
+};
+
+/// Longer windows reported next to the alert window; deliveries are kept for
+/// the longest one.
+const REPORT_WINDOW_MINUTES: [i32; 2] = [24 * 60, 7 * 24 * 60];
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub(crate) struct CallbackSlaConfig {
+    /// Percent of attempts that have to succeed, `0` when alerting is off.
+    success_threshold: f64,
+    window_minutes: i32,
+    min_attempts: i64,
+}
+
+impl CallbackSlaConfig {
+    pub(crate) fn from_env() -> Result<Self> {
+        let success_threshold = match non_empty_env("CALLBACK_SLA_SUCCESS_THRESHOLD") {
+            Some(value) => value
+                .parse::<f64>()
+                .ok()
+                .filter(|value| (0.0..=100.0).contains(value))
+                .context("CALLBACK_SLA_SUCCESS_THRESHOLD must be a percentage between 0 and 100")?,
+            None => 95.0,
+        };
+        let window_minutes = match non_empty_env("CALLBACK_SLA_WINDOW_MINUTES") {
+            Some(value) => value
+                .parse::<i32>()
+                .ok()
+                .filter(|value| (1..=REPORT_WINDOW_MINUTES[1]).contains(value))
+                .context("CALLBACK_SLA_WINDOW_MINUTES must be between 1 and 10080")?,
+            None => 60,
+        };
+        let min_attempts = match non_empty_env("CALLBACK_SLA_MIN_ATTEMPTS") {
+            Some(value) => value
+                .parse::<i64>()
+                .ok()
+                .filter(|value| *value > 0)
+                .context("CALLBACK_SLA_MIN_ATTEMPTS must be a positive integer")?,
+            None => 20,
+        };
+        Ok(Self {
+            success_threshold,
+            window_minutes,
+            min_attempts,
+        })
+    }
+
+    pub(crate) fn is_alerting(&self) -> bool {
+        self.success_threshold > 0.0
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        format!(
+            "alert below {}% over {} min with at least {} attempt(s)",
+            self.success_threshold, self.window_minutes, self.min_attempts
+        )
+    }
+
+    fn windows(&self) -> Vec<i32> {
+        let windows: BTreeSet<i32> = REPORT_WINDOW_MINUTES
+            .into_iter()
+            .chain([self.window_minutes])
+            .collect();
+        windows.into_iter().collect()
+    }
+
+    fn is_below(&self, attempts: i64, delivered: i64) -> bool {
+        self.is_alerting()
+            && attempts >= self.min_attempts
+            && success_rate(attempts, delivered).is_some_and(|rate| rate < self.success_threshold)
+    }
+}
+
+fn success_rate(attempts: i64, delivered: i64) -> Option<f64> {
+    (attempts > 0).then(|| delivered as f64 * 100.0 / attempts as f64)
+}
+
+/// Records a sent callback for the SLA. `outbox_id` dates the queueing; a
+/// failed write only loses a data point, so it is logged and ignored.
+pub(crate) async fn record_delivery(
+    pool: &PgPool,
+    outbox_id: &str,
+    payout_id: &str,
+    result: &CallbackDispatchResult,
+) {
+    if !result.attempted {
+        return;
+    }
+    let recorded = sqlx::query(
+        r#"
+        INSERT INTO "CallbackDelivery"
+            ("id", "outboxId", "merchantId", "delivered", "statusCode", "latencyMs")
+        SELECT
+            $1,
+            $2,
+            p."merchantId",
+            $4,
+            $5,
+            GREATEST(EXTRACT(EPOCH FROM (LOCALTIMESTAMP - o."createdAt")) * 1000, 0)::integer
+        FROM "Payout" p
+        LEFT JOIN "OutboxMessage" o ON o."id" = $2
+        WHERE p."id" = $3
+        "#,
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(outbox_id)
+    .bind(payout_id)
+    .bind(result.was_delivered())
+    .bind(result.status_code.map(i32::from))
+    .execute(pool)
+    .await;
+    if let Err(err) = recorded {
+        eprintln!("[callback-sla] Failed to record delivery of {outbox_id}: {err}");
+    }
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SlaWindow {
+    #[sqlx(rename = "windowMinutes")]
+    pub(crate) window_minutes: i32,
+    pub(crate) attempts: i64,
+    pub(crate) delivered: i64,
+    #[sqlx(skip)]
+    pub(crate) success_rate: Option<f64>,
+    /// Over delivered callbacks only.
+    #[sqlx(rename = "p95LatencyMs")]
+    pub(crate) p95_latency_ms: Option<f64>,
+    #[sqlx(skip)]
+    pub(crate) below_threshold: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CallbackSlaResponse {
+    merchant_id: String,
+    success_threshold: f64,
+    min_attempts: i64,
+    windows: Vec<SlaWindow>,
+}
+
+pub(crate) async fn get_callback_sla(
+    Path(merchant_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<CallbackSlaResponse>> {
+    if !scope.allows_merchant(Some(&merchant_id)) {
+        return Err(ApiError::from((
+            StatusCode::NOT_FOUND,
+            "Merchant not found".to_string(),
+        )));
+    }
+    let exists: bool =
+        sqlx::query_scalar(r#"SELECT EXISTS (SELECT 1 FROM "Merchant" WHERE "id" = $1)"#)
+            .bind(&merchant_id)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(internal_error)?;
+    if !exists {
+        return Err(ApiError::from((
+            StatusCode::NOT_FOUND,
+            "Merchant not found".to_string(),
+        )));
+    }
+
+    let config = &state.callback_sla;
+    let windows = fetch_windows(&state.pool, config, &merchant_id)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(CallbackSlaResponse {
+        merchant_id,
+        success_threshold: config.success_threshold,
+        min_attempts: config.min_attempts,
+        windows,
+    }))
+}
+
+/// The merchant's delivery stats over each reported window, shortest first.
+pub(crate) async fn fetch_windows(
+    pool: &PgPool,
+    config: &CallbackSlaConfig,
+    merchant_id: &str,
+) -> sqlx::Result<Vec<SlaWindow>> {
+    let mut windows = sqlx::query_as::<_, SlaWindow>(
+        r#"
+        SELECT
+            w."minutes" AS "windowMinutes",
+            COUNT(d."id")::bigint AS "attempts",
+            COUNT(d."id") FILTER (WHERE d."delivered")::bigint AS "delivered",
+            percentile_cont(0.95) WITHIN GROUP (ORDER BY d."latencyMs")
+                FILTER (WHERE d."delivered") AS "p95LatencyMs"
+        FROM UNNEST($2::integer[]) AS w("minutes")
+        LEFT JOIN "CallbackDelivery" d
+            ON d."merchantId" = $1
+           AND d."createdAt" >= LOCALTIMESTAMP - make_interval(mins => w."minutes")
+        GROUP BY w."minutes"
+        ORDER BY w."minutes"
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(config.windows())
+    .fetch_all(pool)
+    .await?;
+    for window in &mut windows {
+        window.success_rate = success_rate(window.attempts, window.delivered);
+        window.below_threshold = config.is_below(window.attempts, window.delivered);
+    }
+    Ok(windows)
+}
+
+#[derive(Debug, FromRow)]
+struct MerchantWindow {
+    #[sqlx(rename = "merchantId")]
+    merchant_id: String,
+    attempts: i64,
+    delivered: i64,
+}
+
+async fn check(state: &AppState, breached: &mut HashSet<String>) -> Result<()> {
+    let config = &state.callback_sla;
+    sqlx::query(
+        r#"
+        DELETE FROM "CallbackDelivery"
+        WHERE "createdAt" < LOCALTIMESTAMP - make_interval(mins => $1)
+        "#,
+    )
+    .bind(REPORT_WINDOW_MINUTES[1])
+    .execute(&state.pool)
+    .await
+    .context("Failed to prune callback deliveries")?;
+    if !config.is_alerting() {
+        return Ok(());
+    }
+
+    let merchants = sqlx::query_as::<_, MerchantWindow>(
+        r#"
+        SELECT
+            "merchantId",
+            COUNT(*)::bigint AS "attempts",
+            COUNT(*) FILTER (WHERE "delivered")::bigint AS "delivered"
+        FROM "CallbackDelivery"
+        WHERE "createdAt" >= LOCALTIMESTAMP - make_interval(mins => $1)
+        GROUP BY "merchantId"
+        HAVING COUNT(*) >= $2
+        "#,
+    )
+    .bind(config.window_minutes)
+    .bind(config.min_attempts)
+    .fetch_all(&state.pool)
+    .await
+    .context("Failed to load callback delivery rates")?;
+
+    for merchant in merchants {
+        let rate = success_rate(merchant.attempts, merchant.delivered).unwrap_or_default();
+        let below = config.is_below(merchant.attempts, merchant.delivered);
+        let data = serde_json::json!({
+            "merchantId": merchant.merchant_id,
+            "successRate": rate,
+            "threshold": config.success_threshold,
+            "attempts": merchant.attempts,
+            "windowMinutes": config.window_minutes,
+        });
+        if below && breached.insert(merchant.merchant_id.clone()) {
+            eprintln!(
+                "[callback-sla] Merchant {} below SLA: {rate:.1}% of {} callback(s) delivered in {} min",
+                merchant.merchant_id, merchant.attempts, config.window_minutes
+            );
+            let _ = state.event_tx.send(ServerEvent::callback_sla_breached(
+                &merchant.merchant_id,
+                data,
+            ));
+            if let Some(email) = &state.email {
+                email
+                    .send(
+                        &state.pool,
+                        "Merchant callbacks below SLA",
+                        &format!(
+                            "Only {rate:.1}% of {} callback(s) to merchant {} were delivered in the last {} min, below the {}% threshold. See /api/merchants/{}/callback-sla.",
+                            merchant.attempts,
+                            merchant.merchant_id,
+                            config.window_minutes,
+                            config.success_threshold,
+                            merchant.merchant_id
+                        ),
+                    )
+                    .await?;
+            }
+        } else if !below && breached.remove(&merchant.merchant_id) {
+            println!(
+                "[callback-sla] Merchant {} is back within SLA at {rate:.1}%",
+                merchant.merchant_id
+            );
+            let _ = state.event_tx.send(ServerEvent::callback_sla_recovered(
+                &merchant.merchant_id,
+                data,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Merchants stay flagged until enough attempts show them above the
+/// threshold again; a merchant that stops receiving callbacks keeps its flag.
+pub(crate) async fn sla_worker(state: AppState) {
+    let mut interval = time::interval(CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut breached = HashSet::new();
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = check(&state, &mut breached).await {
+            eprintln!("[callback-sla] {err:#}");
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Per-merchant callback payload templates. Merchants that expect other
+//! field names or an extra envelope get a Tera template in
+//! `MerchantCallbackTemplate`. It is rendered with the standard payload
+//! (`event` and `payout`, see [`PayoutCallbackPayload`]) as context and has
+//! to produce JSON. Merchants without a template receive the standard payload
+//! unchanged, which is also what [`DEFAULT_TEMPLATE`] renders.
+
+use std::error::Error as _;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::FromRow;
+use tera::{Context, Tera};
+
+use crate::{
+    ApiResult, AppState, callbacks::PayoutCallbackPayload, errors::ApiError, internal_error,
+    tenant::TenantScope, timestamps::UtcTimestamp,
+};
+
+pub(crate) const DEFAULT_TEMPLATE: &str =
+    r#"{"event": {{ event | json_encode() }}, "payout": {{ payout | json_encode() }}}"#;
+const MAX_TEMPLATE_BYTES: usize = 16 * 1024;
+
+/// Renders a template with the standard payload as context and parses the
+/// result as JSON.
+pub(crate) fn render(template: &str, payload: &Value) -> Result<Value, String> {
+    let context = Context::from_value(payload.clone()).map_err(describe)?;
+    let rendered = Tera::one_off(template, &context, false).map_err(describe)?;
+    serde_json::from_str(&rendered)
+        .map_err(|err| format!("Template did not render valid JSON: {err}"))
+}
+
+/// Tera keeps the useful part of an error (the line, the unknown variable)
+/// in its sources.
+fn describe(err: tera::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+fn render_sample(template: &str) -> Result<Value, String> {
+    let sample =
+        serde_json::to_value(PayoutCallbackPayload::sample()).map_err(|err| err.to_string())?;
+    render(template, &sample)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CallbackTemplateResponse {
+    merchant_id: String,
+    template: String,
+    /// `false` while the merchant receives the standard payload.
+    custom: bool,
+    /// The template rendered for a sample cancelled payout.
+    preview: Value,
+    updated_at: Option<UtcTimestamp>,
+}
+
+#[derive(Debug, FromRow)]
+struct CallbackTemplateRow {
+    template: String,
+    #[sqlx(rename = "updatedAt")]
+    updated_at: UtcTimestamp,
+}
+
+impl CallbackTemplateResponse {
+    fn from_row(merchant_id: String, row: Option<CallbackTemplateRow>) -> Self {
+        let (template, custom, updated_at) = match row {
+            Some(row) => (row.template, true, Some(row.updated_at)),
+            None => (DEFAULT_TEMPLATE.to_string(), false, None),
+        };
+        // A stored template rendered when it was saved, but a failing preview
+        // should still show up instead of failing the request.
+        let preview = render_sample(&template).unwrap_or_else(Value::String);
+        Self {
+            merchant_id,
+            template,
+            custom,
+            preview,
+            updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct UpdateCallbackTemplateRequest {
+    template: String,
+}
+
+pub(crate) async fn get_callback_template(
+    Path(merchant_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<CallbackTemplateResponse>> {
+    scope.require_unrestricted()?;
+    let row = sqlx::query_as::<_, CallbackTemplateRow>(
+        r#"
+        SELECT "template", "updatedAt"
+        FROM "MerchantCallbackTemplate"
+        WHERE "merchantId" = $1
+        "#,
+    )
+    .bind(&merchant_id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(internal_error)?;
+    Ok(Json(CallbackTemplateResponse::from_row(merchant_id, row)))
+}
+
+/// Stores the merchant's template after rendering it for a sample payout, so
+/// a template that fails or does not produce JSON is rejected up front.
+pub(crate) async fn update_callback_template(
+    Path(merchant_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Json(request): Json<UpdateCallbackTemplateRequest>,
+) -> ApiResult<Json<CallbackTemplateResponse>> {
+    scope.require_unrestricted()?;
+    let template = request.template.trim().to_string();
+    if template.is_empty() {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "Template must not be empty".to_string(),
+        )));
+    }
+    if template.len() > MAX_TEMPLATE_BYTES {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            format!("Template must be at most {MAX_TEMPLATE_BYTES} bytes"),
+        )));
+    }
+    render_sample(&template).map_err(|err| (StatusCode::UNPROCESSABLE_ENTITY, err))?;
+
+    let exists: bool =
+        sqlx::query_scalar(r#"SELECT EXISTS (SELECT 1 FROM "Merchant" WHERE "id" = $1)"#)
+            .bind(&merchant_id)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(internal_error)?;
+    if !exists {
+        return Err(ApiError::from((
+            StatusCode::NOT_FOUND,
+            "Merchant not found".to_string(),
+        )));
+    }
+
+    let row = sqlx::query_as::<_, CallbackTemplateRow>(
+        r#"
+        INSERT INTO "MerchantCallbackTemplate" ("merchantId", "template")
+        VALUES ($1, $2)
+        ON CONFLICT ("merchantId") DO UPDATE
+        SET "template" = EXCLUDED."template",
+            "updatedAt" = CURRENT_TIMESTAMP
+        RETURNING "template", "updatedAt"
+        "#,
+    )
+    .bind(&merchant_id)
+    .bind(&template)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    println!("[callbacks] Updated payload template for merchant {merchant_id}");
+    Ok(Json(CallbackTemplateResponse::from_row(
+        merchant_id,
+        Some(row),
+    )))
+}
+
+/// Goes back to the standard payload.
+pub(crate) async fn delete_callback_template(
+    Path(merchant_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<StatusCode> {
+    scope.require_unrestricted()?;
+    sqlx::query(r#"DELETE FROM "MerchantCallbackTemplate" WHERE "merchantId" = $1"#)
+        .bind(&merchant_id)
+        .execute(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    println!("[callbacks] Removed payload template for merchant {merchant_id}");
+    Ok(StatusCode::NO_CONTENT)
+}
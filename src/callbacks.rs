@@ -0,0 +1,1109 @@
+//! Merchant callbacks: building and dispatching them, the dead-letter queue,
+//! exports, per-merchant overrides and the webhook endpoints.
+
+use anyhow::{Context, Result};
+use axum::{
+    Json,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::{NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use tokio_stream::{StreamExt, wrappers::ReceiverStream};
+use uuid::Uuid;
+
+use crate::{
+    ApiResult, AppState,
+    api::default_true,
+    callback_http, callback_sla, callback_templates,
+    client_certs::ClientCertificate,
+    db::{Pagination, PayoutDetails},
+    secrets::{self, Secrets},
+    db_retry,
+    errors::ApiError,
+    internal_error, merchant_api, outbox, redaction,
+    payout_status::PayoutStatus,
+    tenant::TenantScope,
+    timestamps::UtcTimestamp,
+    trader_webhook,
+};
+
+/// Queued callbacks that already failed at least once and are waiting for the
+/// outbox relay to retry them, and callbacks that ran out of retries (the
+/// dead-letter queue).
+pub(crate) const CALLBACK_QUEUE_COUNTS_QUERY: &str = r#"
+    SELECT
+        COUNT(*) FILTER (WHERE "status" = 'pending' AND "attempts" > 0)::bigint,
+        COUNT(*) FILTER (WHERE "status" = 'failed')::bigint
+    FROM "OutboxMessage"
+    WHERE "kind" = 'callback'
+"#;
+
+/// Dead-lettered callbacks with the payout they belong to. `$1` restricts the
+/// list to a tenant's merchants, `$2` to specific outbox ids.
+pub(crate) const DEAD_LETTER_CALLBACKS_QUERY: &str = r#"
+    SELECT
+        o."id",
+        o."payload"->>'payoutId' AS "payoutId",
+        p."merchantId",
+        o."payload"->'body'->>'event' AS "event",
+        o."attempts",
+        o."lastError",
+        o."createdAt",
+        o."failedAt"
+    FROM "OutboxMessage" o
+    LEFT JOIN "Payout" p
+        ON p."id" = o."payload"->>'payoutId'
+    WHERE o."kind" = 'callback'
+      AND o."status" = 'failed'
+      AND ($1::text[] IS NULL OR p."merchantId" = ANY($1::text[]))
+      AND ($2::text[] IS NULL OR o."id" = ANY($2::text[]))
+"#;
+
+/// Errors of callbacks that were never sent because the merchant was not set
+/// up yet; [`crate::callback_reconcile`] requeues them once it is.
+pub(crate) const MISSING_WEBHOOK_URL_ERROR: &str = "Merchant webhook URL is not configured";
+pub(crate) const MISSING_TOKEN_ERROR: &str = "Merchant token is not configured";
+
+#[derive(Debug)]
+pub(crate) struct CallbackDispatchResult {
+    /// `false` when the request was never sent (missing webhook URL or token);
+    /// such callbacks are not retried.
+    pub(crate) attempted: bool,
+    delivered: bool,
+    pub(crate) status_code: Option<u16>,
+    response_body: Option<String>,
+    pub(crate) error: Option<String>,
+    url: Option<String>,
+}
+
+impl CallbackDispatchResult {
+    pub(crate) fn not_attempted(reason: impl Into<String>, url: Option<String>) -> Self {
+        Self {
+            attempted: false,
+            delivered: false,
+            status_code: None,
+            response_body: None,
+            error: Some(reason.into()),
+            url,
+        }
+    }
+
+    pub(crate) fn was_delivered(&self) -> bool {
+        self.delivered
+    }
+}
+
+/// Where and how a queued callback is delivered, read when it is sent so a
+/// changed webhook URL or token applies to pending retries.
+#[derive(Debug, Default)]
+pub(crate) struct CallbackTarget {
+    pub(crate) webhook_url: Option<String>,
+    pub(crate) merchant_token: Option<String>,
+    pub(crate) overrides: callback_http::CallbackOverride,
+    /// The merchant's payload template, see [`callback_templates`].
+    pub(crate) payload_template: Option<String>,
+    /// Presented to merchants that require mutual TLS.
+    pub(crate) client_certificate: Option<ClientCertificate>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PayoutCallbackPayload {
+    event: String,
+    payout: PayoutCallbackBody,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PayoutCallbackBody {
+    id: String,
+    bank: String,
+    amount: f64,
+    status: String,
+    wallet: String,
+    metadata: Value,
+    #[serde(rename = "numericId")]
+    numeric_id: i32,
+    #[serde(rename = "amountUsdt")]
+    amount_usdt: f64,
+    currency: String,
+    #[serde(default)]
+    proof_files: Vec<String>,
+    #[serde(rename = "cancelReason")]
+    cancel_reason: Option<String>,
+    #[serde(default)]
+    dispute_files: Vec<String>,
+    #[serde(rename = "disputeMessage")]
+    dispute_message: Option<String>,
+    #[serde(rename = "cancelReasonCode")]
+    cancel_reason_code: Option<String>,
+    #[serde(rename = "externalReference")]
+    external_reference: Option<String>,
+}
+
+impl PayoutCallbackPayload {
+    /// A made-up cancelled payout, for validating and previewing templates.
+    pub(crate) fn sample() -> Self {
+        Self {
+            event: PayoutStatus::Cancelled.callback_name().to_string(),
+            payout: PayoutCallbackBody {
+                id: "sample-payout".to_string(),
+                bank: "SBER".to_string(),
+                amount: 1500.0,
+                status: PayoutStatus::Cancelled.callback_name().to_string(),
+                wallet: "2200000000000000".to_string(),
+                metadata: serde_json::json!({ "orderId": "sample-order" }),
+                numeric_id: 1,
+                amount_usdt: 16.5,
+                currency: "RUB".to_string(),
+                proof_files: Vec::new(),
+                cancel_reason: Some("Sample reason".to_string()),
+                dispute_files: Vec::new(),
+                dispute_message: None,
+                cancel_reason_code: None,
+                external_reference: Some("sample-reference".to_string()),
+            },
+        }
+    }
+}
+
+pub(crate) fn build_cancel_callback_payload(payout: &PayoutDetails) -> PayoutCallbackPayload {
+    let metadata = payout
+        .merchant_metadata
+        .clone()
+        .unwrap_or_else(|| Value::Object(Default::default()));
+    let proof_files = payout.proof_files.clone().unwrap_or_default();
+    let dispute_files = payout.dispute_files.clone().unwrap_or_default();
+
+    PayoutCallbackPayload {
+        event: PayoutStatus::Cancelled.callback_name().to_string(),
+        payout: PayoutCallbackBody {
+            id: payout.id.clone(),
+            bank: payout.bank.clone(),
+            amount: payout.amount,
+            status: PayoutStatus::Cancelled.callback_name().to_string(),
+            wallet: payout.wallet.clone(),
+            metadata,
+            numeric_id: payout.numeric_id,
+            amount_usdt: payout.amount_usdt,
+            currency: payout.currency.clone(),
+            proof_files,
+            cancel_reason: payout.cancel_reason.clone(),
+            dispute_files,
+            dispute_message: payout.dispute_message.clone(),
+            cancel_reason_code: payout.cancel_reason_code.clone(),
+            external_reference: payout.external_reference.clone(),
+        },
+    }
+}
+
+/// Sends one queued callback, shaped by the merchant's payload template when
+/// there is one. Only the outbox relay calls this; handlers go through
+/// [`outbox::dispatch_now`] so the delivery state stays in one place.
+pub(crate) async fn dispatch_payout_callback(
+    state: &AppState,
+    payout_id: &str,
+    target: &CallbackTarget,
+    payload: &Value,
+    idempotency_key: &str,
+) -> Result<CallbackDispatchResult> {
+    let rendered = match &target.payload_template {
+        Some(template) => callback_templates::render(template, payload),
+        None => Ok(payload.clone()),
+    };
+    let (payload, result) = match rendered {
+        Ok(body) => {
+            let result = send_callback(state, target, &body, idempotency_key).await;
+            (body, result)
+        }
+        Err(err) => (
+            payload.clone(),
+            CallbackDispatchResult::not_attempted(
+                format!("Callback template failed: {err}"),
+                target.webhook_url.clone(),
+            ),
+        ),
+    };
+    // A failed log would leave the message leased and send it again later.
+    db_retry::with_retry("Callback log", || {
+        log_payout_callback(&state.pool, payout_id, &payload, &result)
+    })
+    .await?;
+    callback_sla::record_delivery(&state.pool, idempotency_key, payout_id, &result).await;
+    Ok(result)
+}
+
+/// Posts a callback payload to the target without recording it anywhere.
+pub(crate) async fn send_callback(
+    state: &AppState,
+    target: &CallbackTarget,
+    payload: &Value,
+    idempotency_key: &str,
+) -> CallbackDispatchResult {
+    let webhook_url = target
+        .webhook_url
+        .as_ref()
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+        .map(|value| value.to_string());
+
+    let webhook_url = match webhook_url {
+        Some(url) => url,
+        None => {
+            return CallbackDispatchResult::not_attempted(
+                MISSING_WEBHOOK_URL_ERROR,
+                Some("(missing-webhook-url)".to_string()),
+            );
+        }
+    };
+
+    let api_key = target
+        .merchant_token
+        .as_ref()
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+        .map(|value| value.to_string());
+
+    let api_key = match api_key {
+        Some(key) => key,
+        None => {
+            return CallbackDispatchResult::not_attempted(
+                MISSING_TOKEN_ERROR,
+                Some(webhook_url.clone()),
+            );
+        }
+    };
+
+    let client = target
+        .client_certificate
+        .as_ref()
+        .map(|certificate| certificate.identity_pem(state.secrets.as_ref()))
+        .transpose()
+        .and_then(|identity| {
+            state
+                .callback_clients
+                .client_for(target.overrides.proxy_url.as_deref(), identity.as_deref())
+        });
+    let client = match client {
+        Ok(client) => client,
+        Err(err) => {
+            return CallbackDispatchResult::not_attempted(
+                format!("Callback client is unusable: {err:#}"),
+                Some(webhook_url),
+            );
+        }
+    };
+    let request = client
+        .post(&webhook_url)
+        .header(merchant_api::API_KEY_HEADER, api_key)
+        .header("x-idempotency-key", idempotency_key)
+        .json(payload);
+    let response = target.overrides.apply(request).send().await;
+    callback_result(webhook_url, response).await
+}
+
+/// Turns the outcome of a callback-style POST into a dispatch result.
+pub(crate) async fn callback_result(
+    webhook_url: String,
+    response: reqwest::Result<reqwest::Response>,
+) -> CallbackDispatchResult {
+    match response {
+        Ok(resp) => {
+            let status = resp.status();
+            let status_code = status.as_u16();
+            let body = resp.text().await.unwrap_or_default();
+            CallbackDispatchResult {
+                attempted: true,
+                delivered: status.is_success(),
+                status_code: Some(status_code),
+                response_body: if body.is_empty() { None } else { Some(body) },
+                error: if status.is_success() {
+                    None
+                } else {
+                    Some(format!("HTTP {}", status_code))
+                },
+                url: Some(webhook_url.clone()),
+            }
+        }
+        Err(err) => CallbackDispatchResult {
+            attempted: true,
+            delivered: false,
+            status_code: None,
+            response_body: None,
+            // reqwest puts the URL in the message, query string and all.
+            error: Some(err.without_url().to_string()),
+            url: Some(webhook_url.clone()),
+        },
+    }
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeadLetterCallback {
+    id: String,
+    #[sqlx(rename = "payoutId")]
+    payout_id: Option<String>,
+    #[sqlx(rename = "merchantId")]
+    merchant_id: Option<String>,
+    event: Option<String>,
+    attempts: i32,
+    #[sqlx(rename = "lastError")]
+    last_error: Option<String>,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+    #[sqlx(rename = "failedAt")]
+    failed_at: Option<UtcTimestamp>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeadLetterListResponse {
+    items: Vec<DeadLetterCallback>,
+    pagination: Pagination,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeadLetterListQuery {
+    page: Option<u32>,
+    per_page: Option<u32>,
+}
+
+pub(crate) async fn get_dead_letter_callbacks(
+    Query(params): Query<DeadLetterListQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<DeadLetterListResponse>> {
+    let page = params.page.unwrap_or(1).max(1);
+    let per_page = params.per_page.unwrap_or(25).clamp(1, 200);
+
+    let total: i64 = sqlx::query_scalar(&format!(
+        "SELECT COUNT(*)::bigint FROM ({DEAD_LETTER_CALLBACKS_QUERY}) d"
+    ))
+    .bind(scope.merchant_ids())
+    .bind(None::<Vec<String>>)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let items = sqlx::query_as::<_, DeadLetterCallback>(&format!(
+        r#"{DEAD_LETTER_CALLBACKS_QUERY} ORDER BY o."failedAt" DESC NULLS LAST LIMIT $3 OFFSET $4"#
+    ))
+    .bind(scope.merchant_ids())
+    .bind(None::<Vec<String>>)
+    .bind(per_page as i64)
+    .bind(((page - 1) as i64) * per_page as i64)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    Ok(Json(DeadLetterListResponse {
+        items,
+        pagination: Pagination::new(total, page, per_page),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RetryDeadLettersRequest {
+    /// Outbox ids to retry; all dead letters visible to the caller when omitted.
+    #[serde(default)]
+    ids: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RetryDeadLettersResponse {
+    requeued: u64,
+}
+
+/// Moves dead-lettered callbacks back into the outbox with a fresh retry
+/// budget.
+pub(crate) async fn retry_dead_letter_callbacks(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Json(request): Json<RetryDeadLettersRequest>,
+) -> ApiResult<Json<RetryDeadLettersResponse>> {
+    let result = sqlx::query(&format!(
+        r#"
+        UPDATE "OutboxMessage"
+        SET "status" = 'pending',
+            "attempts" = 0,
+            "nextAttemptAt" = CURRENT_TIMESTAMP,
+            "leasedUntil" = NULL,
+            "failedAt" = NULL
+        WHERE "id" IN (SELECT d."id" FROM ({DEAD_LETTER_CALLBACKS_QUERY}) d)
+        "#
+    ))
+    .bind(scope.merchant_ids())
+    .bind(request.ids)
+    .execute(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let requeued = result.rows_affected();
+    if requeued > 0 {
+        state.outbox_notify.notify_one();
+    }
+    println!("[callbacks] Requeued {requeued} dead-lettered callback(s)");
+    Ok(Json(RetryDeadLettersResponse { requeued }))
+}
+
+/// Callback attempts for the CSV export, oldest first. `$6` is the exclusive
+/// upper bound, i.e. the day after `to`.
+pub(crate) const CALLBACK_EXPORT_QUERY: &str = r#"
+    SELECT
+        h."createdAt",
+        h."payoutId",
+        p."merchantId",
+        h."url",
+        h."statusCode",
+        h."error",
+        h."response"
+    FROM "PayoutCallbackHistory" h
+    JOIN "Payout" p ON p."id" = h."payoutId"
+    WHERE ($1::text[] IS NULL OR p."merchantId" = ANY($1::text[]))
+      AND ($2::text IS NULL OR h."payoutId" = $2)
+      AND ($3::text IS NULL OR p."merchantId" = $3)
+      AND ($4::int IS NULL OR h."statusCode" = $4)
+      AND ($5::timestamp IS NULL OR h."createdAt" >= $5)
+      AND ($6::timestamp IS NULL OR h."createdAt" < $6)
+    ORDER BY h."createdAt", h."id"
+"#;
+
+pub(crate) const CALLBACK_EXPORT_HEADER: &str =
+    "createdAt,payoutId,merchantId,url,statusCode,error,response\n";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CallbackExportQuery {
+    payout_id: Option<String>,
+    merchant_id: Option<String>,
+    status_code: Option<i32>,
+    /// First day to include (UTC).
+    from: Option<NaiveDate>,
+    /// Last day to include (UTC).
+    to: Option<NaiveDate>,
+}
+
+#[derive(Debug, FromRow)]
+pub(crate) struct CallbackExportRow {
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+    #[sqlx(rename = "payoutId")]
+    payout_id: String,
+    #[sqlx(rename = "merchantId")]
+    merchant_id: String,
+    url: String,
+    #[sqlx(rename = "statusCode")]
+    status_code: Option<i32>,
+    error: Option<String>,
+    response: Option<String>,
+}
+
+impl CallbackExportRow {
+    fn to_csv_line(&self) -> String {
+        let status_code = self
+            .status_code
+            .map(|code| code.to_string())
+            .unwrap_or_default();
+        let fields = [
+            self.created_at.to_string(),
+            csv_field(&self.payout_id),
+            csv_field(&self.merchant_id),
+            csv_field(&self.url),
+            status_code,
+            csv_field(self.error.as_deref().unwrap_or_default()),
+            csv_field(self.response.as_deref().unwrap_or_default()),
+        ];
+        let mut line = fields.join(",");
+        line.push('\n');
+        line
+    }
+}
+
+/// Quotes a CSV field when needed. Values starting with a formula character
+/// are prefixed with `'` so spreadsheets do not evaluate merchant responses.
+pub(crate) fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{value}")
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+/// Streams callback attempts as CSV so support can pull long periods without
+/// the whole history being buffered in memory.
+pub(crate) async fn export_callbacks(
+    Query(params): Query<CallbackExportQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Response> {
+    if let (Some(from), Some(to)) = (params.from, params.to)
+        && from > to
+    {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "from must not be after to".to_string(),
+        )));
+    }
+    let non_empty = |value: Option<String>| {
+        value
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    let payout_id = non_empty(params.payout_id);
+    let merchant_id = non_empty(params.merchant_id);
+    let from = params.from.map(|date| date.and_time(NaiveTime::MIN));
+    let until = params
+        .to
+        .and_then(|date| date.succ_opt())
+        .map(|date| date.and_time(NaiveTime::MIN));
+    let merchant_ids = scope.merchant_ids().map(<[String]>::to_vec);
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<String, sqlx::Error>>(64);
+    let pool = state.pool.clone();
+    tokio::spawn(async move {
+        if tx
+            .send(Ok(CALLBACK_EXPORT_HEADER.to_string()))
+            .await
+            .is_err()
+        {
+            return;
+        }
+        let mut rows = sqlx::query_as::<_, CallbackExportRow>(CALLBACK_EXPORT_QUERY)
+            .bind(merchant_ids)
+            .bind(payout_id)
+            .bind(merchant_id)
+            .bind(params.status_code)
+            .bind(from)
+            .bind(until)
+            .fetch(&pool);
+        while let Some(row) = rows.next().await {
+            let line = row.map(|row| row.to_csv_line());
+            let failed = line.is_err();
+            if let Err(err) = &line {
+                eprintln!("[callbacks] CSV export failed: {err}");
+            }
+            if tx.send(line).await.is_err() || failed {
+                return;
+            }
+        }
+    });
+
+    let filename = format!("callbacks-{}.csv", Utc::now().format("%Y%m%d-%H%M%S"));
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response())
+}
+
+/// Delivers a callback queued by the current request so the response can
+/// report its result. Failures stay in the outbox and are retried by the
+/// relay.
+pub(crate) async fn dispatch_queued_callback(
+    state: &AppState,
+    outbox_id: &str,
+) -> (bool, Option<String>) {
+    match outbox::dispatch_now(state, outbox_id).await {
+        Ok(Some(result)) => (result.was_delivered(), result.error.clone()),
+        Ok(None) => (false, Some("Callback is queued for delivery".to_string())),
+        Err(err) => (false, Some(err.to_string())),
+    }
+}
+
+pub(crate) async fn log_payout_callback(
+    pool: &PgPool,
+    payout_id: &str,
+    payload: &Value,
+    result: &CallbackDispatchResult,
+) -> Result<()> {
+    let url = redaction::url(result.url.as_deref().unwrap_or_default());
+    let payload = redaction::json(payload);
+    let response_text = result.response_body.as_deref().map(redaction::body);
+    let error_text = result.error.as_deref();
+    let status_code = result.status_code.map(i32::from);
+
+    sqlx::query!(
+        r#"
+        INSERT INTO "PayoutCallbackHistory"
+            ("id", "payoutId", "url", "payload", "response", "statusCode", "error")
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        Uuid::new_v4().to_string(),
+        payout_id,
+        url,
+        payload,
+        response_text,
+        status_code,
+        error_text
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record payout callback log")?;
+
+    Ok(())
+}
+
+#[derive(Debug, FromRow)]
+pub(crate) struct TraderWebhookRow {
+    url: String,
+    enabled: bool,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+    #[sqlx(rename = "updatedAt")]
+    updated_at: UtcTimestamp,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TraderWebhookResponse {
+    trader_id: String,
+    url: String,
+    enabled: bool,
+    /// Only returned when the secret was just created or rotated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret: Option<String>,
+    created_at: UtcTimestamp,
+    updated_at: UtcTimestamp,
+}
+
+impl TraderWebhookResponse {
+    fn from_row(trader_id: String, row: TraderWebhookRow, secret: Option<String>) -> Self {
+        Self {
+            trader_id,
+            url: row.url,
+            enabled: row.enabled,
+            secret,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UpdateTraderWebhookRequest {
+    url: String,
+    #[serde(default = "default_true")]
+    enabled: bool,
+    #[serde(default)]
+    rotate_secret: bool,
+}
+
+pub(crate) async fn get_trader_webhook(
+    Path(trader_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<TraderWebhookResponse>> {
+    scope.require_unrestricted()?;
+    let row = sqlx::query_as::<_, TraderWebhookRow>(
+        r#"
+        SELECT "url", "enabled", "createdAt", "updatedAt"
+        FROM "TraderWebhook"
+        WHERE "traderId" = $1
+        "#,
+    )
+    .bind(&trader_id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(|| (StatusCode::NOT_FOUND, "Trader has no webhook".to_string()))?;
+    Ok(Json(TraderWebhookResponse::from_row(trader_id, row, None)))
+}
+
+/// Creates or updates the trader's webhook. A signing secret is generated on
+/// creation and on `rotateSecret`, and returned only in that response.
+pub(crate) async fn update_trader_webhook(
+    Path(trader_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Json(request): Json<UpdateTraderWebhookRequest>,
+) -> ApiResult<Json<TraderWebhookResponse>> {
+    scope.require_unrestricted()?;
+    let url =
+        trader_webhook::validate_url(&request.url).map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+    let exists: bool =
+        sqlx::query_scalar(r#"SELECT EXISTS (SELECT 1 FROM "User" WHERE "id" = $1)"#)
+            .bind(&trader_id)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(internal_error)?;
+    if !exists {
+        return Err(ApiError::trader_not_found());
+    }
+
+    let candidate_secret = trader_webhook::new_secret();
+    let sealed_secret =
+        secrets::seal(state.secrets.as_ref(), &candidate_secret).map_err(internal_error)?;
+    let (url, enabled, created_at, updated_at, secret_changed): (
+        String,
+        bool,
+        UtcTimestamp,
+        UtcTimestamp,
+        bool,
+    ) = sqlx::query_as(
+        r#"
+        INSERT INTO "TraderWebhook" ("traderId", "url", "secret", "enabled")
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT ("traderId") DO UPDATE
+        SET "url" = EXCLUDED."url",
+            "enabled" = EXCLUDED."enabled",
+            "secret" = CASE WHEN $5 THEN EXCLUDED."secret" ELSE "TraderWebhook"."secret" END,
+            "updatedAt" = CURRENT_TIMESTAMP
+        RETURNING "url", "enabled", "createdAt", "updatedAt", "secret" = $3
+        "#,
+    )
+    .bind(&trader_id)
+    .bind(&url)
+    .bind(&sealed_secret)
+    .bind(request.enabled)
+    .bind(request.rotate_secret)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(internal_error)?;
+    let row = TraderWebhookRow {
+        url,
+        enabled,
+        created_at,
+        updated_at,
+    };
+
+    println!(
+        "[trader-webhooks] Updated webhook of trader {trader_id} (enabled={}{})",
+        row.enabled,
+        if secret_changed { ", new secret" } else { "" }
+    );
+    Ok(Json(TraderWebhookResponse::from_row(
+        trader_id,
+        row,
+        secret_changed.then_some(candidate_secret),
+    )))
+}
+
+pub(crate) async fn delete_trader_webhook(
+    Path(trader_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<StatusCode> {
+    scope.require_unrestricted()?;
+    let result = sqlx::query(r#"DELETE FROM "TraderWebhook" WHERE "traderId" = $1"#)
+        .bind(&trader_id)
+        .execute(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::from((
+            StatusCode::NOT_FOUND,
+            "Trader has no webhook".to_string(),
+        )));
+    }
+    println!("[trader-webhooks] Removed webhook of trader {trader_id}");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CallbackOverrideResponse {
+    merchant_id: String,
+    #[serde(flatten)]
+    overrides: callback_http::CallbackOverride,
+    updated_at: Option<UtcTimestamp>,
+}
+
+#[derive(Debug, FromRow)]
+pub(crate) struct CallbackOverrideRow {
+    headers: Value,
+    #[sqlx(rename = "timeoutSeconds")]
+    timeout_seconds: Option<i32>,
+    #[sqlx(rename = "webhookUrl")]
+    webhook_url: Option<String>,
+    #[sqlx(rename = "proxyUrl")]
+    proxy_url: Option<String>,
+    #[sqlx(rename = "updatedAt")]
+    updated_at: UtcTimestamp,
+}
+
+impl CallbackOverrideResponse {
+    fn from_row(
+        merchant_id: String,
+        row: Option<CallbackOverrideRow>,
+        secrets: Option<&Secrets>,
+    ) -> Result<Self> {
+        Ok(match row {
+            Some(row) => Self {
+                merchant_id,
+                overrides: callback_http::CallbackOverride {
+                    headers: callback_http::CallbackOverride::headers_from_stored(
+                        Some(&row.headers),
+                        secrets,
+                    )?,
+                    timeout_seconds: row.timeout_seconds,
+                    webhook_url: row.webhook_url,
+                    proxy_url: row.proxy_url,
+                },
+                updated_at: Some(row.updated_at),
+            },
+            None => Self {
+                merchant_id,
+                overrides: Default::default(),
+                updated_at: None,
+            },
+        })
+    }
+}
+
+/// Override headers often carry merchant credentials, so only unrestricted
+/// tenants may read or change them.
+pub(crate) async fn get_callback_override(
+    Path(merchant_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<CallbackOverrideResponse>> {
+    scope.require_unrestricted()?;
+    let row = sqlx::query_as::<_, CallbackOverrideRow>(
+        r#"
+        SELECT "headers", "timeoutSeconds", "webhookUrl", "proxyUrl", "updatedAt"
+        FROM "MerchantCallbackOverride"
+        WHERE "merchantId" = $1
+        "#,
+    )
+    .bind(&merchant_id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(internal_error)?;
+    CallbackOverrideResponse::from_row(merchant_id, row, state.secrets.as_ref())
+        .map(Json)
+        .map_err(internal_error)
+}
+
+pub(crate) async fn update_callback_override(
+    Path(merchant_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Json(request): Json<callback_http::CallbackOverride>,
+) -> ApiResult<Json<CallbackOverrideResponse>> {
+    scope.require_unrestricted()?;
+    let overrides = request
+        .sanitized()
+        .map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+
+    let exists: bool =
+        sqlx::query_scalar(r#"SELECT EXISTS (SELECT 1 FROM "Merchant" WHERE "id" = $1)"#)
+            .bind(&merchant_id)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(internal_error)?;
+    if !exists {
+        return Err(ApiError::from((
+            StatusCode::NOT_FOUND,
+            "Merchant not found".to_string(),
+        )));
+    }
+
+    let headers = overrides
+        .stored_headers(state.secrets.as_ref())
+        .map_err(internal_error)?;
+    let row = sqlx::query_as::<_, CallbackOverrideRow>(
+        r#"
+        INSERT INTO "MerchantCallbackOverride"
+            ("merchantId", "headers", "timeoutSeconds", "webhookUrl", "proxyUrl")
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT ("merchantId") DO UPDATE
+        SET "headers" = EXCLUDED."headers",
+            "timeoutSeconds" = EXCLUDED."timeoutSeconds",
+            "webhookUrl" = EXCLUDED."webhookUrl",
+            "proxyUrl" = EXCLUDED."proxyUrl",
+            "updatedAt" = CURRENT_TIMESTAMP
+        RETURNING "headers", "timeoutSeconds", "webhookUrl", "proxyUrl", "updatedAt"
+        "#,
+    )
+    .bind(&merchant_id)
+    .bind(headers)
+    .bind(overrides.timeout_seconds)
+    .bind(&overrides.webhook_url)
+    .bind(&overrides.proxy_url)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    println!(
+        "[callbacks] Updated override for merchant {merchant_id}: {} header(s), timeout={:?}, webhook URL {}, proxy {}",
+        overrides.headers.len(),
+        overrides.timeout_seconds,
+        if overrides.webhook_url.is_some() {
+            "overridden"
+        } else {
+            "from platform"
+        },
+        if overrides.proxy_url.is_some() {
+            "overridden"
+        } else {
+            "global"
+        }
+    );
+    CallbackOverrideResponse::from_row(merchant_id, Some(row), state.secrets.as_ref())
+        .map(Json)
+        .map_err(internal_error)
+}
+
+pub(crate) async fn delete_callback_override(
+    Path(merchant_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<StatusCode> {
+    scope.require_unrestricted()?;
+    sqlx::query(r#"DELETE FROM "MerchantCallbackOverride" WHERE "merchantId" = $1"#)
+        .bind(&merchant_id)
+        .execute(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    println!("[callbacks] Removed override for merchant {merchant_id}");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, FromRow)]
+pub(crate) struct WebhookTestTargetRow {
+    token: Option<String>,
+    headers: Option<Value>,
+    #[sqlx(rename = "timeoutSeconds")]
+    timeout_seconds: Option<i32>,
+    #[sqlx(rename = "webhookUrl")]
+    override_url: Option<String>,
+    #[sqlx(rename = "proxyUrl")]
+    proxy_url: Option<String>,
+    certificate: Option<String>,
+    #[sqlx(rename = "privateKey")]
+    private_key: Option<String>,
+    #[sqlx(rename = "platformWebhookUrl")]
+    platform_url: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WebhookTestResponse {
+    merchant_id: String,
+    url: Option<String>,
+    /// `override` or `platform`, depending on where the URL came from.
+    url_source: Option<&'static str>,
+    delivered: bool,
+    status_code: Option<u16>,
+    response_body: Option<String>,
+    error: Option<String>,
+}
+
+/// Sends a test callback to the merchant with the same credentials, headers
+/// and URL resolution as real callbacks. The platform keeps the webhook URL
+/// per payout, so without an override the latest known one is used. The
+/// attempt is not recorded in the callback history.
+pub(crate) async fn test_merchant_webhook(
+    Path(merchant_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<WebhookTestResponse>> {
+    if !scope.allows_merchant(Some(&merchant_id)) {
+        return Err(ApiError::from((
+            StatusCode::NOT_FOUND,
+            "Merchant not found".to_string(),
+        )));
+    }
+    let row = sqlx::query_as::<_, WebhookTestTargetRow>(
+        r#"
+        SELECT
+            m."token",
+            o."headers",
+            o."timeoutSeconds",
+            o."webhookUrl",
+            o."proxyUrl",
+            c."certificate",
+            c."privateKey",
+            (
+                SELECT p."merchantWebhookUrl"
+                FROM "Payout" p
+                WHERE p."merchantId" = m."id"
+                  AND p."merchantWebhookUrl" IS NOT NULL
+                ORDER BY p."createdAt" DESC
+                LIMIT 1
+            ) AS "platformWebhookUrl"
+        FROM "Merchant" m
+        LEFT JOIN "MerchantCallbackOverride" o
+            ON o."merchantId" = m."id"
+        LEFT JOIN "MerchantClientCertificate" c
+            ON c."merchantId" = m."id"
+        WHERE m."id" = $1
+        "#,
+    )
+    .bind(&merchant_id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(|| (StatusCode::NOT_FOUND, "Merchant not found".to_string()))?;
+
+    let (webhook_url, url_source) = match (row.override_url, row.platform_url) {
+        (Some(url), _) => (Some(url), Some("override")),
+        (None, Some(url)) => (Some(url), Some("platform")),
+        (None, None) => (None, None),
+    };
+    let target = CallbackTarget {
+        webhook_url,
+        merchant_token: row.token,
+        overrides: callback_http::CallbackOverride {
+            headers: callback_http::CallbackOverride::headers_from_stored(
+                row.headers.as_ref(),
+                state.secrets.as_ref(),
+            )
+            .map_err(internal_error)?,
+            timeout_seconds: row.timeout_seconds,
+            webhook_url: None,
+            proxy_url: row.proxy_url,
+        },
+        payload_template: None,
+        client_certificate: row.certificate.zip(row.private_key).map(
+            |(certificate, encrypted_key)| ClientCertificate {
+                certificate,
+                encrypted_key,
+            },
+        ),
+    };
+    let payload = serde_json::json!({
+        "event": "TEST",
+        "merchantId": merchant_id,
+        "sentAt": Utc::now(),
+    });
+    let result = send_callback(&state, &target, &payload, &Uuid::new_v4().to_string()).await;
+
+    println!(
+        "[callbacks] Test ping to merchant {merchant_id}: {}",
+        match (&result.status_code, &result.error) {
+            (Some(code), _) => format!("HTTP {code}"),
+            (None, Some(error)) => error.clone(),
+            (None, None) => "no response".to_string(),
+        }
+    );
+    Ok(Json(WebhookTestResponse {
+        merchant_id,
+        url: target.webhook_url,
+        url_source,
+        delivered: result.delivered,
+        status_code: result.status_code,
+        response_body: result.response_body,
+        error: result.error,
+    }))
+}
@@ -0,0 +1,602 @@
+//! Cancellations that need a second operator. Cancelling a payout whose
+//! amount is above `CANCEL_APPROVAL_THRESHOLD` (in the payout's currency;
+//! default 0, which disables the rule) fails with
+//! `409 CANCEL_APPROVAL_REQUIRED`. Instead an operator files a request with
+//! `POST /api/payouts/:id/cancel/request`, every dashboard is told about it,
+//! and a different operator completes the cancellation with
+//! `POST /api/payouts/:id/cancel/approve` or turns it down with `/reject`.
+//! Each step needs a signed-in operator, so the rule needs `OPERATOR_LOGIN`;
+//! a tenant token is shared and does not tell who is behind it.
+//! Requests nobody decided on expire after `CANCEL_APPROVAL_TTL_MINUTES`
+//! (default 60). `GET /api/cancel-requests` lists them.
+//!
+//! Automatic cancellations (`auto_cancel`) are policy, not an operator's
+//! call, and are not held back.
+
+use std::{sync::LazyLock, time::Duration};
+
+use anyhow::{Context, Result};
+use axum::{
+    Extension, Json,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Postgres, Transaction};
+use tokio::time::{self, MissedTickBehavior};
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::{
+    ApiResult, AppState,
+    api::{
+        CancelPayoutResponse, cancel_payout_in_tx, fetch_updated_payout, normalize_optional_text,
+        validate_cancel_reason_code,
+    },
+    auth,
+    callbacks::{build_cancel_callback_payload, dispatch_queued_callback},
+    currencies::PAYOUT_CURRENCY_SQL,
+    db::record_payout_audit,
+    env::non_empty_env,
+    errors::{ApiError, ErrorCode},
+    events::ServerEvent,
+    internal_error, outbox,
+    payout_status::PayoutStatus,
+    payout_transitions, payout_version,
+    tenant::TenantScope,
+    timestamps::UtcTimestamp,
+};
+
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+const MAX_LISTED_REQUESTS: i64 = 200;
+
+#[derive(Debug, Clone)]
+pub(crate) struct CancelApprovalConfig {
+    threshold: f64,
+    ttl_minutes: u32,
+}
+
+impl CancelApprovalConfig {
+    pub(crate) fn from_env() -> Result<Self> {
+        let threshold = match non_empty_env("CANCEL_APPROVAL_THRESHOLD") {
+            Some(value) => value
+                .parse::<f64>()
+                .ok()
+                .filter(|threshold| threshold.is_finite() && *threshold >= 0.0)
+                .context("CANCEL_APPROVAL_THRESHOLD must be a non-negative amount")?,
+            None => 0.0,
+        };
+        let ttl_minutes = match non_empty_env("CANCEL_APPROVAL_TTL_MINUTES") {
+            Some(value) => value
+                .parse::<u32>()
+                .ok()
+                .filter(|minutes| (1..=7 * 24 * 60).contains(minutes))
+                .context("CANCEL_APPROVAL_TTL_MINUTES must be an integer between 1 and 10080")?,
+            None => 60,
+        };
+        Ok(Self {
+            threshold,
+            ttl_minutes,
+        })
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.threshold > 0.0
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        format!(
+            "cancelling payouts above {} needs a second operator, requests expire after {} min",
+            self.threshold, self.ttl_minutes
+        )
+    }
+
+    fn requires_approval(&self, amount: f64) -> bool {
+        self.is_enabled() && amount > self.threshold
+    }
+
+    /// Fails when the payout may only be cancelled through an approved
+    /// request. A missing payout passes, so the caller reports it the way it
+    /// always has.
+    pub(crate) async fn check_direct_cancel(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        payout_id: &str,
+    ) -> ApiResult<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let amount: Option<f64> =
+            sqlx::query_scalar(r#"SELECT "amount" FROM "Payout" WHERE "id" = $1"#)
+                .bind(payout_id)
+                .fetch_optional(&mut **tx)
+                .await
+                .map_err(internal_error)?;
+        match amount {
+            Some(amount) if self.requires_approval(amount) => Err(ApiError::new(
+                StatusCode::CONFLICT,
+                ErrorCode::CancelApprovalRequired,
+                format!(
+                    "Cancelling a payout above {} needs a second operator's approval, request it instead",
+                    self.threshold
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CancelRequestView {
+    id: String,
+    #[sqlx(rename = "payoutId")]
+    payout_id: String,
+    #[sqlx(rename = "merchantId")]
+    merchant_id: Option<String>,
+    amount: f64,
+    currency: String,
+    #[sqlx(rename = "requestedBy")]
+    requested_by: String,
+    /// User ID of the requesting operator, `None` on requests filed before
+    /// approvals were tied to operator sessions.
+    #[sqlx(rename = "requestedById")]
+    #[serde(skip)]
+    requested_by_id: Option<String>,
+    reason: Option<String>,
+    #[sqlx(rename = "reasonCode")]
+    reason_code: Option<String>,
+    /// `pending`, `approved`, `rejected` or `expired`.
+    status: String,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+    #[sqlx(rename = "expiresAt")]
+    expires_at: UtcTimestamp,
+    #[sqlx(rename = "decidedBy")]
+    decided_by: Option<String>,
+    #[sqlx(rename = "decidedAt")]
+    decided_at: Option<UtcTimestamp>,
+}
+
+impl CancelRequestView {
+    fn event_data(&self) -> serde_json::Value {
+        serde_json::json!({
+            "payoutId": self.payout_id,
+            "amount": self.amount,
+            "currency": self.currency,
+            "requestedBy": self.requested_by,
+            "reason": self.reason,
+            "status": self.status,
+            "decidedBy": self.decided_by,
+        })
+    }
+}
+
+static CANCEL_REQUEST_COLUMNS: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        r#"
+    cr."id",
+    cr."payoutId",
+    p."merchantId",
+    p."amount",
+    {PAYOUT_CURRENCY_SQL} AS "currency",
+    cr."requestedBy",
+    cr."requestedById",
+    cr."reason",
+    cr."reasonCode",
+    cr."status",
+    cr."createdAt",
+    cr."expiresAt",
+    cr."decidedBy",
+    cr."decidedAt"
+"#
+    )
+});
+
+/// The live request for a payout, locked, within the caller's merchants.
+async fn lock_pending_request(
+    tx: &mut Transaction<'_, Postgres>,
+    payout_id: &str,
+    scope: &TenantScope,
+) -> ApiResult<CancelRequestView> {
+    sqlx::query_as::<_, CancelRequestView>(&format!(
+        r#"
+        SELECT {columns}
+        FROM "PayoutCancelRequest" cr
+        JOIN "Payout" p ON p."id" = cr."payoutId"
+        WHERE cr."payoutId" = $1
+          AND cr."status" = 'pending'
+          AND cr."expiresAt" > CURRENT_TIMESTAMP
+          AND ($2::text[] IS NULL OR p."merchantId" = ANY($2::text[]))
+        FOR UPDATE OF cr
+        "#,
+        columns = *CANCEL_REQUEST_COLUMNS
+    ))
+    .bind(payout_id)
+    .bind(scope.merchant_ids())
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(|| {
+        ApiError::from((
+            StatusCode::NOT_FOUND,
+            "No pending cancellation request for this payout".to_string(),
+        ))
+    })
+}
+
+async fn close_request(
+    tx: &mut Transaction<'_, Postgres>,
+    request: &mut CancelRequestView,
+    status: &str,
+    actor: &str,
+) -> ApiResult<()> {
+    let decided_at: UtcTimestamp = sqlx::query_scalar(
+        r#"
+        UPDATE "PayoutCancelRequest"
+        SET "status" = $2,
+            "decidedBy" = $3,
+            "decidedAt" = CURRENT_TIMESTAMP
+        WHERE "id" = $1
+        RETURNING "decidedAt"
+        "#,
+    )
+    .bind(&request.id)
+    .bind(status)
+    .bind(actor)
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(internal_error)?;
+    request.status = status.to_string();
+    request.decided_by = Some(actor.to_string());
+    request.decided_at = Some(decided_at);
+    Ok(())
+}
+
+/// Requests and decisions are tied to a person.
+async fn require_operator(session: &Session) -> ApiResult<auth::Operator> {
+    auth::require_operator(session, "Cancellation approvals need a signed-in operator").await
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CancelRequestBody {
+    reason: Option<String>,
+    reason_code: Option<String>,
+    /// See `payout_version`.
+    #[serde(default)]
+    expected_updated_at: Option<UtcTimestamp>,
+}
+
+#[derive(Debug, FromRow)]
+struct LockedPayout {
+    status: PayoutStatus,
+    amount: f64,
+}
+
+pub(crate) async fn request_cancel_approval(
+    Path(payout_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    headers: HeaderMap,
+    Json(body): Json<CancelRequestBody>,
+) -> ApiResult<Json<CancelRequestView>> {
+    let operator = require_operator(&session).await?;
+    let actor = operator.actor();
+    let expected_updated_at = payout_version::expected(&headers, body.expected_updated_at)?;
+    let reason = normalize_optional_text(body.reason);
+    let reason_code = normalize_optional_text(body.reason_code);
+    validate_cancel_reason_code(&state.pool, reason_code.as_deref()).await?;
+
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    payout_version::check(&mut tx, &payout_id, expected_updated_at).await?;
+    let payout = sqlx::query_as::<_, LockedPayout>(
+        r#"
+        SELECT p."status"::text AS "status", p."amount"
+        FROM "Payout" p
+        WHERE p."id" = $1
+          AND ($2::text[] IS NULL OR p."merchantId" = ANY($2::text[]))
+        FOR UPDATE OF p
+        "#,
+    )
+    .bind(&payout_id)
+    .bind(scope.merchant_ids())
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(ApiError::payout_not_found)?;
+
+    payout_transitions::transition(payout.status, PayoutStatus::Cancelled)?;
+    if !state.cancel_approval.requires_approval(payout.amount) {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "This payout can be cancelled without approval".to_string(),
+        )));
+    }
+
+    // A lapsed request the worker has not caught yet must not block a new one.
+    sqlx::query(
+        r#"
+        UPDATE "PayoutCancelRequest"
+        SET "status" = 'expired', "decidedAt" = CURRENT_TIMESTAMP
+        WHERE "payoutId" = $1 AND "status" = 'pending' AND "expiresAt" <= CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(&payout_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    let pending: Option<String> = sqlx::query_scalar(
+        r#"SELECT "requestedBy" FROM "PayoutCancelRequest" WHERE "payoutId" = $1 AND "status" = 'pending'"#,
+    )
+    .bind(&payout_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    if let Some(requested_by) = pending {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            ErrorCode::CancelApprovalPending,
+            format!("{requested_by} already requested this cancellation, it awaits approval"),
+        ));
+    }
+
+    let request_id = Uuid::new_v4().to_string();
+    sqlx::query(
+        r#"
+        INSERT INTO "PayoutCancelRequest"
+            ("id", "payoutId", "requestedBy", "requestedById", "reason", "reasonCode", "expiresAt")
+        VALUES ($1, $2, $3, $4, $5, $6, CURRENT_TIMESTAMP + make_interval(mins => $7))
+        "#,
+    )
+    .bind(&request_id)
+    .bind(&payout_id)
+    .bind(&actor)
+    .bind(&operator.user_id)
+    .bind(&reason)
+    .bind(&reason_code)
+    .bind(state.cancel_approval.ttl_minutes as i32)
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    let request = lock_pending_request(&mut tx, &payout_id, &scope).await?;
+
+    record_payout_audit(
+        &mut *tx,
+        &payout_id,
+        "cancel-requested",
+        Some(&actor),
+        None,
+        Some(serde_json::json!({
+            "reasonCode": reason_code,
+            "reason": reason,
+            "expiresAt": request.expires_at,
+        })),
+    )
+    .await
+    .map_err(internal_error)?;
+    outbox::enqueue_event(
+        &mut tx,
+        &ServerEvent::cancel_approval_requested(request.merchant_id.clone(), request.event_data()),
+    )
+    .await
+    .map_err(internal_error)?;
+    tx.commit().await.map_err(internal_error)?;
+    state.outbox_notify.notify_one();
+
+    println!(
+        "[cancel-approval] {actor} requested cancelling payout {payout_id} ({} {}), expires at {}",
+        request.amount, request.currency, request.expires_at
+    );
+    Ok(Json(request))
+}
+
+pub(crate) async fn approve_cancel_request(
+    Path(payout_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+) -> ApiResult<Json<CancelPayoutResponse>> {
+    let operator = require_operator(&session).await?;
+    let actor = operator.actor();
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    let mut request = lock_pending_request(&mut tx, &payout_id, &scope).await?;
+    // Without the requester's ID there is no telling who filed it.
+    if request
+        .requested_by_id
+        .as_deref()
+        .is_none_or(|id| id == operator.user_id)
+    {
+        return Err(ApiError::from((
+            StatusCode::FORBIDDEN,
+            "A cancellation must be approved by an operator other than its requester".to_string(),
+        )));
+    }
+
+    let payout = cancel_payout_in_tx(
+        &mut tx,
+        &payout_id,
+        request.reason.as_deref(),
+        request.reason_code.as_deref(),
+        &scope,
+        Some(&actor),
+    )
+    .await?;
+    close_request(&mut tx, &mut request, "approved", &actor).await?;
+    record_payout_audit(
+        &mut *tx,
+        &payout_id,
+        "cancel-approved",
+        Some(&actor),
+        None,
+        Some(serde_json::json!({ "requestedBy": request.requested_by })),
+    )
+    .await
+    .map_err(internal_error)?;
+
+    let callback_id =
+        outbox::enqueue_callback(&mut tx, &payout.id, &build_cancel_callback_payload(&payout))
+            .await
+            .map_err(internal_error)?;
+    outbox::enqueue_event(
+        &mut tx,
+        &ServerEvent::payouts_updated("cancel-approval").for_merchants(payout.merchant_id.clone()),
+    )
+    .await
+    .map_err(internal_error)?;
+    outbox::enqueue_event(
+        &mut tx,
+        &ServerEvent::cancel_approval_closed(request.merchant_id.clone(), request.event_data()),
+    )
+    .await
+    .map_err(internal_error)?;
+    tx.commit().await.map_err(internal_error)?;
+
+    let (callback_dispatched, callback_error) =
+        dispatch_queued_callback(&state, &callback_id).await;
+    state.outbox_notify.notify_one();
+
+    println!(
+        "[cancel-approval] {actor} approved cancelling payout {payout_id}, requested by {}",
+        request.requested_by
+    );
+    Ok(Json(CancelPayoutResponse {
+        success: true,
+        status: PayoutStatus::Cancelled,
+        callback_dispatched,
+        callback_error,
+        payout: fetch_updated_payout(&state, &payout_id).await?,
+    }))
+}
+
+/// Turns a request down; its requester may also withdraw it this way.
+pub(crate) async fn reject_cancel_request(
+    Path(payout_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+) -> ApiResult<Json<CancelRequestView>> {
+    let actor = require_operator(&session).await?.actor();
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    let mut request = lock_pending_request(&mut tx, &payout_id, &scope).await?;
+    close_request(&mut tx, &mut request, "rejected", &actor).await?;
+    record_payout_audit(
+        &mut *tx,
+        &payout_id,
+        "cancel-rejected",
+        Some(&actor),
+        None,
+        Some(serde_json::json!({ "requestedBy": request.requested_by })),
+    )
+    .await
+    .map_err(internal_error)?;
+    outbox::enqueue_event(
+        &mut tx,
+        &ServerEvent::cancel_approval_closed(request.merchant_id.clone(), request.event_data()),
+    )
+    .await
+    .map_err(internal_error)?;
+    tx.commit().await.map_err(internal_error)?;
+    state.outbox_notify.notify_one();
+
+    println!(
+        "[cancel-approval] {actor} rejected cancelling payout {payout_id}, requested by {}",
+        request.requested_by
+    );
+    Ok(Json(request))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CancelRequestsQuery {
+    /// Defaults to `pending`; `all` lists every status.
+    #[serde(default)]
+    status: Option<String>,
+}
+
+pub(crate) async fn get_cancel_requests(
+    Query(query): Query<CancelRequestsQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<Vec<CancelRequestView>>> {
+    let status = normalize_optional_text(query.status);
+    let status = match status.as_deref() {
+        None | Some("pending") => Some("pending"),
+        Some("all") => None,
+        Some(status @ ("approved" | "rejected" | "expired")) => Some(status),
+        Some(other) => {
+            return Err(ApiError::from((
+                StatusCode::BAD_REQUEST,
+                format!("Unknown cancellation request status {other}"),
+            )));
+        }
+    };
+    let query = format!(
+        r#"
+        SELECT {columns}
+        FROM "PayoutCancelRequest" cr
+        JOIN "Payout" p ON p."id" = cr."payoutId"
+        WHERE ($1::text IS NULL OR cr."status" = $1)
+          AND ($2::text[] IS NULL OR p."merchantId" = ANY($2::text[]))
+        ORDER BY cr."createdAt" DESC
+        LIMIT $3
+        "#,
+        columns = *CANCEL_REQUEST_COLUMNS
+    );
+    sqlx::query_as::<_, CancelRequestView>(&query)
+        .bind(status)
+        .bind(scope.merchant_ids())
+        .bind(MAX_LISTED_REQUESTS)
+        .fetch_all(&state.pool)
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+async fn expire_requests(state: &AppState) -> Result<Vec<CancelRequestView>> {
+    let expired = sqlx::query_as::<_, CancelRequestView>(&format!(
+        r#"
+        WITH expired AS (
+            UPDATE "PayoutCancelRequest"
+            SET "status" = 'expired', "decidedAt" = CURRENT_TIMESTAMP
+            WHERE "status" = 'pending' AND "expiresAt" <= CURRENT_TIMESTAMP
+            RETURNING *
+        )
+        SELECT {columns}
+        FROM expired cr
+        JOIN "Payout" p ON p."id" = cr."payoutId"
+        "#,
+        columns = *CANCEL_REQUEST_COLUMNS
+    ))
+    .fetch_all(&state.pool)
+    .await
+    .context("Failed to expire cancellation requests")?;
+    Ok(expired)
+}
+
+pub(crate) async fn expiry_worker(state: AppState) {
+    if !state.cancel_approval.is_enabled() {
+        return;
+    }
+    let mut interval = time::interval(EXPIRY_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+        match expire_requests(&state).await {
+            Ok(expired) => {
+                for request in expired {
+                    println!(
+                        "[cancel-approval] Request by {} to cancel payout {} expired unapproved",
+                        request.requested_by, request.payout_id
+                    );
+                    let _ = state.event_tx.send(ServerEvent::cancel_approval_closed(
+                        request.merchant_id.clone(),
+                        request.event_data(),
+                    ));
+                }
+            }
+            Err(err) => eprintln!("[cancel-approval] {err:#}"),
+        }
+    }
+}
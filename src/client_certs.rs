@@ -0,0 +1,193 @@
+//! Client certificates for merchants whose callback endpoints require mutual
+//! TLS. The certificate chain and private key are uploaded in PEM through
+//! `/api/merchants/:id/client-certificate`; the key is stored encrypted with
+//! [`Secrets`] and never returned. Callbacks to the merchant then go through a
+//! client that presents the certificate, see
+//! [`CallbackClients`](crate::callback_http::CallbackClients).
+
+use anyhow::{Context, Result};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::{
+    ApiResult, AppState, errors::ApiError, internal_error, secrets::Secrets, tenant::TenantScope,
+    timestamps::UtcTimestamp,
+};
+
+const MAX_PEM_BYTES: usize = 64 * 1024;
+
+/// A merchant's stored certificate, loaded with the callback target.
+#[derive(Debug, Clone)]
+pub(crate) struct ClientCertificate {
+    pub(crate) certificate: String,
+    pub(crate) encrypted_key: String,
+}
+
+impl ClientCertificate {
+    /// The certificate chain followed by the decrypted key, as reqwest reads
+    /// an identity.
+    pub(crate) fn identity_pem(&self, secrets: Option<&Secrets>) -> Result<String> {
+        let secrets = secrets.context("SECRETS_KEY is not configured")?;
+        let key = secrets
+            .decrypt(&self.encrypted_key)
+            .context("Failed to decrypt client key")?;
+        Ok(identity_pem(&self.certificate, &key))
+    }
+}
+
+fn identity_pem(certificate: &str, key: &str) -> String {
+    format!("{}\n{}\n", certificate.trim(), key.trim())
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ClientCertificateResponse {
+    merchant_id: String,
+    configured: bool,
+    certificate: Option<String>,
+    updated_at: Option<UtcTimestamp>,
+}
+
+#[derive(Debug, FromRow)]
+struct ClientCertificateRow {
+    certificate: String,
+    #[sqlx(rename = "updatedAt")]
+    updated_at: UtcTimestamp,
+}
+
+impl ClientCertificateResponse {
+    fn from_row(merchant_id: String, row: Option<ClientCertificateRow>) -> Self {
+        Self {
+            merchant_id,
+            configured: row.is_some(),
+            certificate: row.as_ref().map(|row| row.certificate.clone()),
+            updated_at: row.map(|row| row.updated_at),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UpdateClientCertificateRequest {
+    certificate: String,
+    private_key: String,
+}
+
+pub(crate) async fn get_client_certificate(
+    Path(merchant_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<ClientCertificateResponse>> {
+    scope.require_unrestricted()?;
+    let row = sqlx::query_as::<_, ClientCertificateRow>(
+        r#"
+        SELECT "certificate", "updatedAt"
+        FROM "MerchantClientCertificate"
+        WHERE "merchantId" = $1
+        "#,
+    )
+    .bind(&merchant_id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(internal_error)?;
+    Ok(Json(ClientCertificateResponse::from_row(merchant_id, row)))
+}
+
+/// Stores the merchant's certificate once a client could be built with it,
+/// so a key that does not match or cannot be parsed is rejected up front.
+pub(crate) async fn update_client_certificate(
+    Path(merchant_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Json(request): Json<UpdateClientCertificateRequest>,
+) -> ApiResult<Json<ClientCertificateResponse>> {
+    scope.require_unrestricted()?;
+    let Some(secrets) = state.secrets.as_ref() else {
+        return Err(ApiError::from((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "SECRETS_KEY is not configured, client keys cannot be stored".to_string(),
+        )));
+    };
+    let certificate = request.certificate.trim().to_string();
+    let private_key = request.private_key.trim().to_string();
+    if !certificate.contains("-----BEGIN CERTIFICATE-----") {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "certificate must be a PEM certificate chain".to_string(),
+        )));
+    }
+    if !private_key.contains("PRIVATE KEY-----") {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "privateKey must be a PEM private key".to_string(),
+        )));
+    }
+    if certificate.len() + private_key.len() > MAX_PEM_BYTES {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            format!("Certificate and key must be at most {MAX_PEM_BYTES} bytes"),
+        )));
+    }
+    state
+        .callback_clients
+        .client_for(None, Some(&identity_pem(&certificate, &private_key)))
+        .map_err(|err| (StatusCode::UNPROCESSABLE_ENTITY, format!("{err:#}")))?;
+
+    let exists: bool =
+        sqlx::query_scalar(r#"SELECT EXISTS (SELECT 1 FROM "Merchant" WHERE "id" = $1)"#)
+            .bind(&merchant_id)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(internal_error)?;
+    if !exists {
+        return Err(ApiError::from((
+            StatusCode::NOT_FOUND,
+            "Merchant not found".to_string(),
+        )));
+    }
+
+    let encrypted_key = secrets.encrypt(&private_key).map_err(internal_error)?;
+    let row = sqlx::query_as::<_, ClientCertificateRow>(
+        r#"
+        INSERT INTO "MerchantClientCertificate" ("merchantId", "certificate", "privateKey")
+        VALUES ($1, $2, $3)
+        ON CONFLICT ("merchantId") DO UPDATE
+        SET "certificate" = EXCLUDED."certificate",
+            "privateKey" = EXCLUDED."privateKey",
+            "updatedAt" = CURRENT_TIMESTAMP
+        RETURNING "certificate", "updatedAt"
+        "#,
+    )
+    .bind(&merchant_id)
+    .bind(&certificate)
+    .bind(&encrypted_key)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    println!("[callbacks] Updated client certificate for merchant {merchant_id}");
+    Ok(Json(ClientCertificateResponse::from_row(
+        merchant_id,
+        Some(row),
+    )))
+}
+
+pub(crate) async fn delete_client_certificate(
+    Path(merchant_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<StatusCode> {
+    scope.require_unrestricted()?;
+    sqlx::query(r#"DELETE FROM "MerchantClientCertificate" WHERE "merchantId" = $1"#)
+        .bind(&merchant_id)
+        .execute(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    println!("[callbacks] Removed client certificate for merchant {merchant_id}");
+    Ok(StatusCode::NO_CONTENT)
+}
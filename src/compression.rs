@@ -0,0 +1,75 @@
+//! Response compression for the SSR page and the large JSON lists.
+//! `RESPONSE_COMPRESSION` lists the encodings to offer, `gzip,deflate` by
+//! default, `off` to disable; `RESPONSE_COMPRESSION_MIN_BYTES` (default 1024)
+//! skips small responses where compression costs more than it saves. The SSE
+//! stream, images and gRPC are never compressed: a compressed event stream
+//! would buffer events until enough bytes arrive.
+
+use anyhow::{Context, Result, bail};
+use tower_http::compression::{
+    CompressionLayer, Predicate,
+    predicate::{NotForContentType, SizeAbove},
+};
+
+use crate::env::{non_empty_env, parse_flag};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CompressionConfig {
+    gzip: bool,
+    deflate: bool,
+    min_bytes: u16,
+}
+
+impl CompressionConfig {
+    pub(crate) fn from_env() -> Result<Self> {
+        let encodings = non_empty_env("RESPONSE_COMPRESSION")
+            .map(|value| value.to_ascii_lowercase())
+            .unwrap_or_else(|| "gzip,deflate".to_string());
+        let (mut gzip, mut deflate) = (false, false);
+        if parse_flag(&encodings) != Some(false) {
+            for encoding in encodings.split(',').map(str::trim) {
+                match encoding {
+                    "gzip" => gzip = true,
+                    "deflate" => deflate = true,
+                    other => bail!("Unknown RESPONSE_COMPRESSION encoding {other}"),
+                }
+            }
+        }
+        let min_bytes = match non_empty_env("RESPONSE_COMPRESSION_MIN_BYTES") {
+            Some(value) => value
+                .parse()
+                .context("RESPONSE_COMPRESSION_MIN_BYTES must be between 0 and 65535")?,
+            None => 1024,
+        };
+        Ok(Self {
+            gzip,
+            deflate,
+            min_bytes,
+        })
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.gzip || self.deflate
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        let encodings: Vec<&str> = [("gzip", self.gzip), ("deflate", self.deflate)]
+            .into_iter()
+            .filter_map(|(name, enabled)| enabled.then_some(name))
+            .collect();
+        format!("{} above {} bytes", encodings.join(", "), self.min_bytes)
+    }
+
+    /// With every encoding off the layer passes responses through untouched.
+    pub(crate) fn layer(self) -> CompressionLayer<impl Predicate> {
+        CompressionLayer::new()
+            .gzip(self.gzip)
+            .deflate(self.deflate)
+            .compress_when(
+                SizeAbove::new(self.min_bytes)
+                    .and(NotForContentType::SSE)
+                    .and(NotForContentType::IMAGES)
+                    .and(NotForContentType::GRPC),
+            )
+    }
+}
@@ -0,0 +1,495 @@
+//! Snapshot and restore of the service configuration, for moving it between
+//! environments. `GET /api/config/export` returns one JSON document with the
+//! auto distribution and priority settings, trader limits, open-payout caps
+//! and currencies, bank routing weights, alert recipients and the digest
+//! schedule; `POST /api/config/import` applies such a document.
+//!
+//! A section present in the imported document replaces the current one as a
+//! whole, a section left out is kept. The document is validated before
+//! anything is written, the stored sections are replaced in one transaction.
+//! Entries of traders missing in this environment are skipped and listed in
+//! the response. Env-based settings (SMTP, Telegram, secrets) are not part
+//! of the snapshot.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use axum::{
+    Json,
+    extract::{Extension, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tower_sessions::Session;
+
+use crate::{
+    ApiResult, AppState, auth, bank_routing,
+    currencies::{self, TraderCurrencies, TraderCurrency},
+    db::fetch_capacity_overrides,
+    digest::{self, UpdateScheduleRequest},
+    email_alerts,
+    environment::SafetyLock,
+    errors::ApiError,
+    events::ServerEvent,
+    internal_error,
+    settings::{AmountRange, AutoDistributionConfig, PriorityPolicy, audit_change},
+    settings_approval,
+    tenant::TenantScope,
+};
+
+/// Bumped when a section changes incompatibly.
+const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ConfigSnapshot {
+    #[serde(default)]
+    version: Option<u32>,
+    /// Informational, ignored on import.
+    #[serde(default)]
+    exported_at: Option<DateTime<Utc>>,
+    auto_distribution: Option<AutoDistributionConfig>,
+    priority_policy: Option<PriorityPolicy>,
+    /// Trader id to RUB amount range.
+    trader_limits: Option<BTreeMap<String, AmountRange>>,
+    /// Trader id to own open-payout cap.
+    trader_capacity: Option<BTreeMap<String, u32>>,
+    trader_currencies: Option<BTreeMap<String, Vec<TraderCurrency>>>,
+    /// Bank to trader id to routing weight.
+    bank_weights: Option<BTreeMap<String, BTreeMap<String, f64>>>,
+    email_recipients: Option<Vec<String>>,
+    report_schedule: Option<UpdateScheduleRequest>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImportConfigResponse {
+    /// Sections the document replaced.
+    sections: Vec<&'static str>,
+    /// Traders referenced by the document but unknown here.
+    skipped_traders: Vec<String>,
+}
+
+async fn snapshot(state: &AppState) -> ApiResult<ConfigSnapshot> {
+    let limits = state.settings.limits().await;
+    let capacity = fetch_capacity_overrides(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    let trader_currencies = TraderCurrencies::load(&state.pool)
+        .await
+        .map_err(internal_error)?
+        .into_map();
+    let bank_weights: Vec<(String, String, f64)> = sqlx::query_as(
+        r#"SELECT "bank", "traderId", "weight" FROM "TraderBankWeight" ORDER BY "bank", "traderId""#,
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(internal_error)?;
+    let mut banks: BTreeMap<String, BTreeMap<String, f64>> = BTreeMap::new();
+    for (bank, trader_id, weight) in bank_weights {
+        banks.entry(bank).or_default().insert(trader_id, weight);
+    }
+    let email_recipients: Vec<String> =
+        sqlx::query_scalar(r#"SELECT "email" FROM "EmailRecipient" ORDER BY "email""#)
+            .fetch_all(&state.pool)
+            .await
+            .map_err(internal_error)?;
+    let schedule = digest::load_schedule(&state.pool)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(ConfigSnapshot {
+        version: Some(SNAPSHOT_VERSION),
+        exported_at: Some(Utc::now()),
+        auto_distribution: Some(state.settings.auto_config()),
+        priority_policy: Some(state.settings.priority_policy().await),
+        trader_limits: Some(limits.into_iter().collect()),
+        trader_capacity: Some(capacity.into_iter().collect()),
+        trader_currencies: Some(trader_currencies.into_iter().collect()),
+        bank_weights: Some(banks),
+        email_recipients: Some(email_recipients),
+        report_schedule: Some(schedule.to_request()),
+    })
+}
+
+/// Configuration is shared by all tenants, so only unrestricted tenants may
+/// export or import it.
+pub(crate) async fn export_config(
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<ConfigSnapshot>> {
+    scope.require_unrestricted()?;
+    Ok(Json(snapshot(&state).await?))
+}
+
+fn bad_request(section: &str, err: String) -> ApiError {
+    ApiError::from((StatusCode::BAD_REQUEST, format!("{section}: {err}")))
+}
+
+/// Drops the entries of traders not in `known`, remembering them.
+fn known_only<T>(
+    entries: BTreeMap<String, T>,
+    known: &BTreeSet<String>,
+    skipped: &mut BTreeSet<String>,
+) -> BTreeMap<String, T> {
+    entries
+        .into_iter()
+        .filter(|(trader_id, _)| {
+            let found = known.contains(trader_id);
+            if !found {
+                skipped.insert(trader_id.clone());
+            }
+            found
+        })
+        .collect()
+}
+
+pub(crate) async fn import_config(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(document): Json<ConfigSnapshot>,
+) -> ApiResult<Json<ImportConfigResponse>> {
+    scope.require_unrestricted()?;
+    state
+        .environment
+        .ensure_unlocked(SafetyLock::ConfigImport)?;
+    if let Some(version) = document.version
+        && version != SNAPSHOT_VERSION
+    {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported snapshot version {version}, expected {SNAPSHOT_VERSION}"),
+        )));
+    }
+    let actor = auth::audit_actor(&session, &scope).await?;
+
+    // Validate every section before anything is written.
+    let auto_distribution = document
+        .auto_distribution
+        .map(AutoDistributionConfig::sanitized)
+        .transpose()
+        .map_err(|err| bad_request("autoDistribution", err))?;
+    let priority_policy = document
+        .priority_policy
+        .map(PriorityPolicy::sanitized)
+        .transpose()
+        .map_err(|err| bad_request("priorityPolicy", err))?;
+    if state.settings.requires_approvals() {
+        let mut risks = Vec::new();
+        if let Some(config) = &auto_distribution {
+            risks.extend(settings_approval::auto_config_risks(
+                &state.settings.auto_config(),
+                config,
+            ));
+        }
+        if let Some(policy) = &priority_policy {
+            risks.extend(settings_approval::priority_policy_risks(
+                &state.settings.priority_policy().await,
+                policy,
+            ));
+        }
+        if !risks.is_empty() {
+            return Err(ApiError::from((
+                StatusCode::CONFLICT,
+                format!(
+                    "The import needs a second admin's approval ({}); make these changes in the settings instead",
+                    risks.join(", ")
+                ),
+            )));
+        }
+    }
+    let trader_limits = document
+        .trader_limits
+        .map(|limits| {
+            let mut sanitized = BTreeMap::new();
+            for (trader_id, range) in limits {
+                let range = range
+                    .validated()
+                    .map_err(|err| format!("trader {trader_id}: {err}"))?;
+                if range != AmountRange::default() {
+                    sanitized.insert(trader_id.trim().to_string(), range);
+                }
+            }
+            Ok(sanitized)
+        })
+        .transpose()
+        .map_err(|err| bad_request("traderLimits", err))?;
+    let trader_capacity = document
+        .trader_capacity
+        .map(|caps| {
+            caps.into_iter()
+                .filter(|(_, cap)| *cap > 0)
+                .map(|(trader_id, cap)| {
+                    i32::try_from(cap)
+                        .map(|_| (trader_id.trim().to_string(), cap))
+                        .map_err(|_| format!("cap of trader {trader_id} is too large"))
+                })
+                .collect::<Result<BTreeMap<_, _>, _>>()
+        })
+        .transpose()
+        .map_err(|err| bad_request("traderCapacity", err))?;
+    let trader_currencies = document
+        .trader_currencies
+        .map(|by_trader| {
+            by_trader
+                .into_iter()
+                .filter(|(_, entries)| !entries.is_empty())
+                .map(|(trader_id, entries)| {
+                    currencies::sanitize_currencies(entries)
+                        .map(|entries| (trader_id.trim().to_string(), entries))
+                })
+                .collect::<Result<BTreeMap<_, _>, _>>()
+        })
+        .transpose()
+        .map_err(|err| bad_request("traderCurrencies", err))?;
+    let bank_weights = document
+        .bank_weights
+        .map(|banks| {
+            let mut sanitized: BTreeMap<String, BTreeMap<String, f64>> = BTreeMap::new();
+            for (bank, weights) in banks {
+                let bank = bank_routing::normalize_bank(&bank);
+                if bank.is_empty() {
+                    return Err("bank is required".to_string());
+                }
+                for (trader_id, weight) in weights {
+                    if !weight.is_finite() || weight < 0.0 {
+                        return Err(format!(
+                            "weight of trader {trader_id} for {bank} must be zero or a positive number"
+                        ));
+                    }
+                    if weight > 0.0 {
+                        sanitized
+                            .entry(bank.clone())
+                            .or_default()
+                            .insert(trader_id.trim().to_string(), weight);
+                    }
+                }
+            }
+            Ok(sanitized)
+        })
+        .transpose()
+        .map_err(|err| bad_request("bankWeights", err))?;
+    let email_recipients = document
+        .email_recipients
+        .map(|emails| {
+            emails
+                .iter()
+                .map(|email| email_alerts::normalize_address(email))
+                .collect::<Result<BTreeSet<_>, _>>()
+        })
+        .transpose()
+        .map_err(|err| bad_request("emailRecipients", err))?;
+    let report_schedule = match document.report_schedule {
+        Some(request) => {
+            let current = digest::load_schedule(&state.pool)
+                .await
+                .map_err(internal_error)?;
+            let updated = digest::updated_schedule(&state, &current, request)
+                .map_err(|err| bad_request("reportSchedule", err.message))?;
+            Some(updated)
+        }
+        None => None,
+    };
+
+    let mut referenced: BTreeSet<String> = BTreeSet::new();
+    referenced.extend(
+        trader_limits
+            .iter()
+            .flat_map(|limits| limits.keys().cloned()),
+    );
+    referenced.extend(trader_capacity.iter().flat_map(|caps| caps.keys().cloned()));
+    referenced.extend(
+        trader_currencies
+            .iter()
+            .flat_map(|by_trader| by_trader.keys().cloned()),
+    );
+    referenced.extend(
+        bank_weights
+            .iter()
+            .flat_map(|banks| banks.values().flat_map(|weights| weights.keys().cloned())),
+    );
+    let referenced: Vec<String> = referenced.into_iter().collect();
+    let known: BTreeSet<String> =
+        sqlx::query_scalar::<_, String>(r#"SELECT "id" FROM "User" WHERE "id" = ANY($1::text[])"#)
+            .bind(&referenced)
+            .fetch_all(&state.pool)
+            .await
+            .map_err(internal_error)?
+            .into_iter()
+            .collect();
+    let mut skipped: BTreeSet<String> = BTreeSet::new();
+    let trader_limits = trader_limits.map(|limits| known_only(limits, &known, &mut skipped));
+    let trader_capacity = trader_capacity.map(|caps| known_only(caps, &known, &mut skipped));
+    let trader_currencies =
+        trader_currencies.map(|by_trader| known_only(by_trader, &known, &mut skipped));
+    let bank_weights = bank_weights.map(|banks| {
+        banks
+            .into_iter()
+            .map(|(bank, weights)| (bank, known_only(weights, &known, &mut skipped)))
+            .filter(|(_, weights)| !weights.is_empty())
+            .collect::<BTreeMap<_, _>>()
+    });
+
+    let before = snapshot(&state).await?;
+    let mut sections: Vec<&'static str> = Vec::new();
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    if let Some(caps) = &trader_capacity {
+        let trader_ids: Vec<&str> = caps.keys().map(String::as_str).collect();
+        let values: Vec<i32> = caps.values().map(|cap| *cap as i32).collect();
+        sqlx::query(r#"DELETE FROM "TraderCapacity""#)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal_error)?;
+        sqlx::query(
+            r#"
+            INSERT INTO "TraderCapacity" ("traderId", "maxOpenPayouts")
+            SELECT batch."traderId", batch."maxOpenPayouts"
+            FROM UNNEST($1::text[], $2::int[]) AS batch("traderId", "maxOpenPayouts")
+            "#,
+        )
+        .bind(&trader_ids)
+        .bind(&values)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+        sections.push("traderCapacity");
+    }
+    if let Some(by_trader) = &trader_currencies {
+        let mut trader_ids: Vec<&str> = Vec::new();
+        let mut codes: Vec<&str> = Vec::new();
+        let mut max_amounts: Vec<Option<f64>> = Vec::new();
+        for (trader_id, entries) in by_trader {
+            for entry in entries {
+                trader_ids.push(trader_id);
+                codes.push(&entry.currency);
+                max_amounts.push(entry.max_amount);
+            }
+        }
+        sqlx::query(r#"DELETE FROM "TraderCurrency""#)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal_error)?;
+        sqlx::query(
+            r#"
+            INSERT INTO "TraderCurrency" ("traderId", "currency", "maxAmount")
+            SELECT batch."traderId", batch."currency", batch."maxAmount"
+            FROM UNNEST($1::text[], $2::text[], $3::double precision[])
+                AS batch("traderId", "currency", "maxAmount")
+            "#,
+        )
+        .bind(&trader_ids)
+        .bind(&codes)
+        .bind(&max_amounts)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+        sections.push("traderCurrencies");
+    }
+    if let Some(banks) = &bank_weights {
+        let mut bank_names: Vec<&str> = Vec::new();
+        let mut trader_ids: Vec<&str> = Vec::new();
+        let mut weights: Vec<f64> = Vec::new();
+        for (bank, entries) in banks {
+            for (trader_id, weight) in entries {
+                bank_names.push(bank);
+                trader_ids.push(trader_id);
+                weights.push(*weight);
+            }
+        }
+        sqlx::query(r#"DELETE FROM "TraderBankWeight""#)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal_error)?;
+        sqlx::query(
+            r#"
+            INSERT INTO "TraderBankWeight" ("bank", "traderId", "weight")
+            SELECT batch."bank", batch."traderId", batch."weight"
+            FROM UNNEST($1::text[], $2::text[], $3::double precision[])
+                AS batch("bank", "traderId", "weight")
+            "#,
+        )
+        .bind(&bank_names)
+        .bind(&trader_ids)
+        .bind(&weights)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+        sections.push("bankWeights");
+    }
+    if let Some(emails) = &email_recipients {
+        let emails: Vec<&str> = emails.iter().map(String::as_str).collect();
+        sqlx::query(r#"DELETE FROM "EmailRecipient" WHERE "email" <> ALL($1::text[])"#)
+            .bind(&emails)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal_error)?;
+        sqlx::query(
+            r#"
+            INSERT INTO "EmailRecipient" ("email")
+            SELECT UNNEST($1::text[])
+            ON CONFLICT ("email") DO NOTHING
+            "#,
+        )
+        .bind(&emails)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+        sections.push("emailRecipients");
+    }
+    if let Some(schedule) = &report_schedule {
+        digest::store_schedule(&mut *tx, schedule)
+            .await
+            .map_err(internal_error)?;
+        sections.push("reportSchedule");
+    }
+    tx.commit().await.map_err(internal_error)?;
+
+    // The in-memory settings follow once the stored ones are in place.
+    if let Some(config) = auto_distribution {
+        state.settings.update_auto_config(config).await?;
+        sections.push("autoDistribution");
+    }
+    if let Some(policy) = priority_policy {
+        state.settings.update_priority_policy(policy).await?;
+        sections.push("priorityPolicy");
+    }
+    if let Some(limits) = trader_limits {
+        state
+            .settings
+            .replace_trader_limits(limits.into_iter().collect::<HashMap<_, _>>())
+            .await;
+        sections.push("traderLimits");
+    }
+    if trader_capacity.is_some() || trader_currencies.is_some() {
+        let _ = state.event_tx.send(ServerEvent::limits_updated());
+    }
+    if bank_weights.is_some() {
+        let _ = state.event_tx.send(ServerEvent::settings_updated());
+    }
+
+    let skipped_traders: Vec<String> = skipped.into_iter().collect();
+    println!(
+        "[settings] Imported configuration: sections=[{}] skipped traders={}",
+        sections.join(", "),
+        skipped_traders.len()
+    );
+    if let Some(schedule) = &report_schedule {
+        println!("[digest] {}", schedule.describe());
+    }
+    let after = snapshot(&state).await?;
+    audit_change(
+        &state.pool,
+        "config-import",
+        None,
+        actor.as_deref(),
+        &before,
+        &after,
+    )
+    .await;
+
+    Ok(Json(ImportConfigResponse {
+        sections,
+        skipped_traders,
+    }))
+}
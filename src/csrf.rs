@@ -0,0 +1,130 @@
+//! Protection of mutating requests against cross-site forgery. Every
+//! non-GET request must come from the dashboard's own origin: a browser
+//! `Origin` (or, failing that, `Referer`) has to match the `Host` header or one
+//! of `ALLOWED_ORIGINS` (comma-separated, for dashboards served through a
+//! proxy under another name). Requests without either header come from
+//! scripts, not browsers, and pass.
+//!
+//! Mutating `/api/*` requests additionally need the session's CSRF token in
+//! `X-CSRF-Token`; the SSR page embeds it for the dashboard JS. Requests with
+//! an `Authorization` header are exempt since a foreign page cannot attach
+//! one. `CSRF_PROTECTION=false` turns the token check off for legacy scripts
+//! in single-tenant mode; the origin check always applies.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use reqwest::Url;
+use tower_sessions::Session;
+
+use crate::{
+    auth,
+    env::{flag_env, non_empty_env},
+    errors::{ApiError, ErrorCode},
+};
+
+const CSRF_HEADER: &str = "x-csrf-token";
+
+#[derive(Debug, Clone)]
+pub(crate) struct CsrfConfig {
+    require_token: bool,
+    allowed_origins: Arc<Vec<String>>,
+}
+
+impl CsrfConfig {
+    pub(crate) fn from_env() -> Result<Self> {
+        let require_token = flag_env("CSRF_PROTECTION", true)?;
+        let allowed_origins = non_empty_env("ALLOWED_ORIGINS")
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(|origin| origin.trim().trim_end_matches('/').to_ascii_lowercase())
+                    .filter(|origin| !origin.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Self {
+            require_token,
+            allowed_origins: Arc::new(allowed_origins),
+        })
+    }
+
+    pub(crate) fn requires_token(&self) -> bool {
+        self.require_token
+    }
+
+    fn is_allowed_origin(&self, origin: &str, headers: &HeaderMap) -> bool {
+        let Ok(url) = Url::parse(origin) else {
+            return false;
+        };
+        let normalized = url.origin().ascii_serialization();
+        if self.allowed_origins.contains(&normalized) {
+            return true;
+        }
+        let authority = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return false,
+        };
+        headers
+            .get(header::HOST)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|host| host.eq_ignore_ascii_case(&authority))
+    }
+}
+
+fn forbidden(message: &str) -> Response {
+    ApiError::new(StatusCode::FORBIDDEN, ErrorCode::CsrfRejected, message).into_response()
+}
+
+/// Middleware applied to the whole router, inside the session layer.
+pub(crate) async fn protect(
+    State(config): State<CsrfConfig>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return next.run(request).await;
+    }
+
+    let headers = request.headers();
+    let source = headers
+        .get(header::ORIGIN)
+        .or_else(|| headers.get(header::REFERER))
+        .map(|value| value.to_str().unwrap_or_default().to_string());
+    if let Some(source) = source
+        && !config.is_allowed_origin(&source, headers)
+    {
+        eprintln!(
+            "[csrf] Rejected {} {} from origin {source}",
+            request.method(),
+            request.uri().path()
+        );
+        return forbidden("Cross-origin requests are not allowed");
+    }
+
+    let needs_token = config.require_token
+        && request.uri().path().starts_with("/api/")
+        && !headers.contains_key(header::AUTHORIZATION);
+    if needs_token {
+        let submitted = headers
+            .get(CSRF_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let Some(session) = request.extensions().get::<Session>() else {
+            return forbidden("Invalid or missing CSRF token");
+        };
+        if let Err(rejection) = auth::verify_csrf(session, &submitted).await {
+            return rejection.into_response();
+        }
+    }
+
+    next.run(request).await
+}
@@ -0,0 +1,404 @@
+//! Payout currencies. The platform's payouts are RUB unless a currency is
+//! set in the service's `PayoutCurrency` table through
+//! `PUT /api/payouts/:id/currency`; balances, freezes and the per-trader limit
+//! on `/api/traders/:id/limit` are kept in RUB and only apply to RUB payouts.
+//!
+//! A trader takes payouts in another currency only once it is enabled for the
+//! trader through `/api/traders/:id/currencies`, optionally with a max amount
+//! per payout in that currency. The list is replaced as a whole.
+
+use std::collections::HashMap;
+
+use axum::{
+    Json,
+    extract::{Extension, Path, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+use tower_sessions::Session;
+
+use crate::{
+    ApiResult, AppState,
+    api::ensure_trader_in_scope,
+    auth,
+    db::record_payout_audit,
+    errors::{ApiError, ErrorCode},
+    events::ServerEvent,
+    internal_error, outbox,
+    settings::{AmountRange, audit_change},
+    tenant::TenantScope,
+};
+
+/// Currency of payouts without one and of trader balances.
+pub(crate) const BASE_CURRENCY: &str = "RUB";
+
+/// The currency of payout `p` in SQL, [`BASE_CURRENCY`] unless the payout
+/// has a `PayoutCurrency` row.
+pub(crate) const PAYOUT_CURRENCY_SQL: &str = r#"COALESCE(
+    (SELECT pc."currency" FROM "PayoutCurrency" pc WHERE pc."payoutId" = p."id"),
+    'RUB'
+)"#;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TraderCurrency {
+    pub(crate) currency: String,
+    /// Max amount per payout in this currency, `None` for no limit.
+    #[sqlx(rename = "maxAmount")]
+    pub(crate) max_amount: Option<f64>,
+}
+
+#[derive(Debug, FromRow)]
+struct TraderCurrencyRow {
+    #[sqlx(rename = "traderId")]
+    trader_id: String,
+    #[sqlx(flatten)]
+    currency: TraderCurrency,
+}
+
+/// Upper-cases a currency code and checks it looks like one (`USD`, `USDT`).
+pub(crate) fn normalize_currency(value: &str) -> Result<String, String> {
+    let code = value.trim().to_ascii_uppercase();
+    if (3..=5).contains(&code.len()) && code.bytes().all(|byte| byte.is_ascii_uppercase()) {
+        Ok(code)
+    } else {
+        Err(format!("{value:?} is not a currency code"))
+    }
+}
+
+pub(crate) fn is_base(currency: &str) -> bool {
+    currency.eq_ignore_ascii_case(BASE_CURRENCY)
+}
+
+/// The currencies every trader takes besides RUB, loaded once per cycle.
+#[derive(Debug, Default)]
+pub(crate) struct TraderCurrencies {
+    by_trader: HashMap<String, Vec<TraderCurrency>>,
+}
+
+impl TraderCurrencies {
+    pub(crate) async fn load(pool: &PgPool) -> sqlx::Result<Self> {
+        let rows = sqlx::query_as::<_, TraderCurrencyRow>(
+            r#"
+            SELECT "traderId", "currency", "maxAmount"
+            FROM "TraderCurrency"
+            ORDER BY "traderId", "currency"
+            "#,
+        )
+        .fetch_all(pool)
+        .await?;
+        let mut by_trader: HashMap<String, Vec<TraderCurrency>> = HashMap::new();
+        for row in rows {
+            by_trader
+                .entry(row.trader_id)
+                .or_default()
+                .push(row.currency);
+        }
+        Ok(Self { by_trader })
+    }
+
+    pub(crate) fn into_map(self) -> HashMap<String, Vec<TraderCurrency>> {
+        self.by_trader
+    }
+
+    pub(crate) fn of(&self, trader_id: &str) -> &[TraderCurrency] {
+        self.by_trader.get(trader_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether the trader takes a payout of `amount` in `currency`. RUB
+    /// payouts are left to the RUB limit and balance checks.
+    pub(crate) fn accepts(&self, trader_id: &str, currency: &str, amount: f64) -> bool {
+        is_base(currency)
+            || self.of(trader_id).iter().any(|entry| {
+                entry.currency.eq_ignore_ascii_case(currency)
+                    && entry.max_amount.is_none_or(|max| amount <= max)
+            })
+    }
+}
+
+/// Amount limits as the distributor checks them: the RUB ranges from the
+/// settings and the currencies each trader takes.
+pub(crate) struct AmountLimits<'a> {
+    pub(crate) rub: &'a HashMap<String, AmountRange>,
+    pub(crate) currencies: &'a TraderCurrencies,
+}
+
+impl AmountLimits<'_> {
+    pub(crate) fn allows(&self, trader_id: &str, currency: &str, amount: f64) -> bool {
+        if is_base(currency) {
+            self.rub
+                .get(trader_id)
+                .is_none_or(|range| range.contains(amount))
+        } else {
+            self.currencies.accepts(trader_id, currency, amount)
+        }
+    }
+}
+
+/// Why the trader cannot take the payout, for manual assignment.
+pub(crate) async fn check_trader_currency(
+    tx: &mut Transaction<'_, Postgres>,
+    trader_id: &str,
+    currency: &str,
+    amount: f64,
+) -> ApiResult<()> {
+    if is_base(currency) {
+        return Ok(());
+    }
+    let entry: Option<Option<f64>> = sqlx::query_scalar(
+        r#"SELECT "maxAmount" FROM "TraderCurrency" WHERE "traderId" = $1 AND "currency" = UPPER($2)"#,
+    )
+    .bind(trader_id)
+    .bind(currency)
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(internal_error)?;
+    match entry {
+        None => Err(ApiError::trader_ineligible(format!(
+            "Trader does not take {currency} payouts"
+        ))),
+        Some(Some(max)) if amount > max => Err(ApiError::trader_ineligible(format!(
+            "Amount exceeds the trader's {currency} limit of {max:.2}"
+        ))),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Normalizes a trader's currency list as it is stored, sorted by code.
+pub(crate) fn sanitize_currencies(
+    entries: Vec<TraderCurrency>,
+) -> Result<Vec<TraderCurrency>, String> {
+    let mut currencies: Vec<TraderCurrency> = Vec::new();
+    for entry in entries {
+        let currency = normalize_currency(&entry.currency)?;
+        if is_base(&currency) {
+            return Err(format!(
+                "{BASE_CURRENCY} payouts are limited through /api/traders/:id/limit"
+            ));
+        }
+        if currencies
+            .iter()
+            .any(|existing| existing.currency == currency)
+        {
+            return Err(format!("{currency} is listed twice"));
+        }
+        currencies.push(TraderCurrency {
+            currency,
+            max_amount: entry.max_amount.filter(|value| *value > 0.0),
+        });
+    }
+    currencies.sort_by(|a, b| a.currency.cmp(&b.currency));
+    Ok(currencies)
+}
+
+async fn load_trader_currencies(pool: &PgPool, trader_id: &str) -> ApiResult<Vec<TraderCurrency>> {
+    sqlx::query_as::<_, TraderCurrency>(
+        r#"
+        SELECT "currency", "maxAmount"
+        FROM "TraderCurrency"
+        WHERE "traderId" = $1
+        ORDER BY "currency"
+        "#,
+    )
+    .bind(trader_id)
+    .fetch_all(pool)
+    .await
+    .map_err(internal_error)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TraderCurrenciesResponse {
+    trader_id: String,
+    base_currency: &'static str,
+    currencies: Vec<TraderCurrency>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct UpdateTraderCurrenciesRequest {
+    currencies: Vec<TraderCurrency>,
+}
+
+pub(crate) async fn get_trader_currencies(
+    Path(trader_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<TraderCurrenciesResponse>> {
+    ensure_trader_in_scope(&state.pool, &trader_id, scope.merchant_ids()).await?;
+    let currencies = load_trader_currencies(&state.pool, &trader_id).await?;
+    Ok(Json(TraderCurrenciesResponse {
+        trader_id,
+        base_currency: BASE_CURRENCY,
+        currencies,
+    }))
+}
+
+pub(crate) async fn update_trader_currencies(
+    Path(trader_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<UpdateTraderCurrenciesRequest>,
+) -> ApiResult<Json<TraderCurrenciesResponse>> {
+    ensure_trader_in_scope(&state.pool, &trader_id, scope.merchant_ids()).await?;
+    let currencies =
+        sanitize_currencies(request.currencies).map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let previous = load_trader_currencies(&state.pool, &trader_id).await?;
+
+    let codes: Vec<&str> = currencies
+        .iter()
+        .map(|entry| entry.currency.as_str())
+        .collect();
+    let max_amounts: Vec<Option<f64>> = currencies.iter().map(|entry| entry.max_amount).collect();
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    sqlx::query(r#"DELETE FROM "TraderCurrency" WHERE "traderId" = $1"#)
+        .bind(&trader_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+    sqlx::query(
+        r#"
+        INSERT INTO "TraderCurrency" ("traderId", "currency", "maxAmount")
+        SELECT $1, batch."currency", batch."maxAmount"
+        FROM UNNEST($2::text[], $3::double precision[]) AS batch("currency", "maxAmount")
+        "#,
+    )
+    .bind(&trader_id)
+    .bind(&codes)
+    .bind(&max_amounts)
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    tx.commit().await.map_err(internal_error)?;
+
+    println!(
+        "[settings] Updated trader currencies: trader={} currencies=[{}]",
+        trader_id,
+        codes.join(", ")
+    );
+    audit_change(
+        &state.pool,
+        "trader-currencies",
+        Some(&trader_id),
+        actor.as_deref(),
+        &previous,
+        &currencies,
+    )
+    .await;
+    let _ = state.event_tx.send(ServerEvent::limits_updated());
+
+    Ok(Json(TraderCurrenciesResponse {
+        trader_id,
+        base_currency: BASE_CURRENCY,
+        currencies,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SetPayoutCurrencyRequest {
+    currency: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PayoutCurrencyResponse {
+    payout_id: String,
+    currency: String,
+}
+
+/// `PUT /api/payouts/:id/currency`. Sets the currency of a payout the
+/// platform created in something other than RUB. Only open, unassigned
+/// payouts can change currency, so nobody holds one under the old currency's
+/// checks.
+pub(crate) async fn set_payout_currency(
+    Path(payout_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<SetPayoutCurrencyRequest>,
+) -> ApiResult<Json<PayoutCurrencyResponse>> {
+    let currency =
+        normalize_currency(&request.currency).map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    let payout: Option<(String, bool)> = sqlx::query_as(
+        r#"
+        SELECT
+            p."merchantId",
+            p."status" = 'CREATED' AND p."traderId" IS NULL AND p."acceptedAt" IS NULL
+        FROM "Payout" p
+        WHERE p."id" = $1
+          AND p."direction" = 'OUT'
+          AND ($2::text[] IS NULL OR p."merchantId" = ANY($2::text[]))
+        FOR UPDATE
+        "#,
+    )
+    .bind(&payout_id)
+    .bind(scope.merchant_ids())
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    let Some((merchant_id, open)) = payout else {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::PayoutNotFound,
+            format!("Payout {payout_id} not found"),
+        ));
+    };
+    if !open {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            ErrorCode::PayoutNotEligible,
+            format!("Payout {payout_id} is already assigned or closed"),
+        ));
+    }
+
+    if is_base(&currency) {
+        sqlx::query(r#"DELETE FROM "PayoutCurrency" WHERE "payoutId" = $1"#)
+            .bind(&payout_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal_error)?;
+    } else {
+        sqlx::query(
+            r#"
+            INSERT INTO "PayoutCurrency" ("payoutId", "currency")
+            VALUES ($1, $2)
+            ON CONFLICT ("payoutId") DO UPDATE
+            SET "currency" = EXCLUDED."currency",
+                "updatedAt" = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(&payout_id)
+        .bind(&currency)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+    }
+    record_payout_audit(
+        &mut *tx,
+        &payout_id,
+        "currency-set",
+        actor.as_deref(),
+        None,
+        Some(serde_json::json!({ "currency": currency })),
+    )
+    .await
+    .map_err(internal_error)?;
+    outbox::enqueue_event(
+        &mut tx,
+        &ServerEvent::payouts_updated("manual").for_merchants([merchant_id]),
+    )
+    .await
+    .map_err(internal_error)?;
+    tx.commit().await.map_err(internal_error)?;
+    state.outbox_notify.notify_one();
+
+    println!("[currencies] Payout {payout_id} set to {currency}");
+    Ok(Json(PayoutCurrencyResponse {
+        payout_id,
+        currency,
+    }))
+}
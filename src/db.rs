@@ -0,0 +1,1565 @@
+//! Queries and row types shared by the handlers, the distributor and the
+//! dashboard, plus the service schema created at startup.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::LazyLock,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    availability::UnavailabilityWindow,
+    currencies::{PAYOUT_CURRENCY_SQL, TraderCurrency},
+    payout_status::PayoutStatus,
+    rates, redaction,
+    settings::PriorityPolicy,
+    teams::TeamSummary,
+    timestamps::UtcTimestamp,
+};
+
+pub(crate) const ELIGIBLE_TRADERS_QUERY: &str = r#"
+    SELECT DISTINCT
+        u."id",
+        u."email",
+        u."numericId",
+        u."balanceRub",
+        u."frozenRub",
+        u."payoutBalance"
+    FROM "Payout" p
+    JOIN "TraderMerchant" tm
+        ON tm."merchantId" = p."merchantId"
+    JOIN "User" u
+        ON u."id" = tm."traderId"
+    WHERE p."direction" = 'OUT'
+      AND p."status" = 'CREATED'
+      AND (p."traderId" IS NULL OR p."traderId" = u."id")
+      AND tm."isMerchantEnabled" = TRUE
+      AND tm."isFeeOutEnabled" = TRUE
+      AND COALESCE(u."balanceRub", 0) > 0
+      AND u."trafficEnabled" = TRUE
+      AND u."banned" = FALSE
+      AND NOT EXISTS (
+          SELECT 1
+          FROM "TraderPause" tp
+          WHERE tp."traderId" = u."id"
+      )
+      AND NOT EXISTS (
+          SELECT 1
+          FROM "TraderUnavailability" tu
+          WHERE tu."traderId" = u."id"
+            AND tu."startsAt" <= LOCALTIMESTAMP
+            AND tu."endsAt" > LOCALTIMESTAMP
+      )
+    ORDER BY u."numericId"
+"#;
+
+pub(crate) static UNASSIGNED_PAYOUTS_QUERY: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        r#"
+    SELECT
+        p."id",
+        p."numericId",
+        p."amount",
+        {PAYOUT_CURRENCY_SQL} AS "currency",
+        p."bank",
+        p."externalReference",
+        p."merchantId",
+        COALESCE(
+            ($1::double precision IS NOT NULL AND p."amount" >= $1)
+            OR p."merchantId" = ANY($2::text[])
+            OR (
+                $3::integer IS NOT NULL
+                AND p."createdAt" <= LOCALTIMESTAMP - make_interval(mins => $3)
+            ),
+            FALSE
+        ) AS "priority",
+        EXISTS (
+            SELECT 1
+            FROM "PayoutDuplicateFlag" df
+            WHERE df."payoutId" = p."id"
+              AND df."approvedAt" IS NULL
+        ) AS "duplicate"
+    FROM "Payout" p
+    LEFT JOIN "AggregatorPayout" ap
+        ON ap."payoutId" = p."id"
+    WHERE p."direction" = 'OUT'
+      AND p."status" = 'CREATED'
+      AND p."acceptedAt" IS NULL
+      AND p."traderId" IS NULL
+      AND ap."payoutId" IS NULL
+      AND ($4::text[] IS NULL OR p."merchantId" = ANY($4::text[]))
+    ORDER BY "priority" DESC, p."createdAt"
+"#
+    )
+});
+
+/// Activity since the start of the current day (database time). A payout
+/// counts as assigned today when this service assigned it today or the
+/// platform accepted it today.
+pub(crate) const TODAY_TOTALS_QUERY: &str = r#"
+    WITH day AS (
+        SELECT date_trunc('day', CURRENT_TIMESTAMP)::timestamp AS "start"
+    ),
+    touched AS (
+        SELECT
+            p."amount",
+            p."status"::text AS "status",
+            p."cancelledAt" >= day."start" AS "cancelledToday",
+            p."updatedAt" >= day."start" AS "updatedToday",
+            COALESCE(p."acceptedAt" >= day."start", FALSE)
+                OR EXISTS (
+                    SELECT 1
+                    FROM "PayoutAuditLog" a
+                    WHERE a."payoutId" = p."id"
+                      AND a."action" = 'assigned'
+                      AND a."createdAt" >= day."start"
+                ) AS "assignedToday"
+        FROM "Payout" p
+        CROSS JOIN day
+        WHERE p."direction" = 'OUT'
+          AND (
+              p."updatedAt" >= day."start"
+              OR p."acceptedAt" >= day."start"
+              OR p."cancelledAt" >= day."start"
+              OR EXISTS (
+                  SELECT 1
+                  FROM "PayoutAuditLog" a
+                  WHERE a."payoutId" = p."id"
+                    AND a."action" = 'assigned'
+                    AND a."createdAt" >= day."start"
+              )
+          )
+          AND ($1::text[] IS NULL OR p."merchantId" = ANY($1::text[]))
+    )
+    SELECT
+        COUNT(*) FILTER (WHERE "assignedToday") AS "assignedToday",
+        COALESCE(SUM("amount") FILTER (WHERE "assignedToday"), 0)::double precision AS "assignedTodayAmount",
+        COUNT(*) FILTER (WHERE "status" = 'CANCELLED' AND "cancelledToday") AS "cancelledToday",
+        COALESCE(SUM("amount") FILTER (WHERE "status" = 'CANCELLED' AND "cancelledToday"), 0)::double precision
+            AS "cancelledTodayAmount",
+        COUNT(*) FILTER (WHERE "status" IN ('COMPLETED', 'SUCCESS') AND "updatedToday") AS "completedToday",
+        COALESCE(SUM("amount") FILTER (WHERE "status" IN ('COMPLETED', 'SUCCESS') AND "updatedToday"), 0)::double precision
+            AS "completedTodayAmount"
+    FROM touched
+"#;
+
+pub(crate) static CLAIM_UNASSIGNED_PAYOUTS_QUERY: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        r#"
+    SELECT
+        p."id",
+        p."numericId",
+        p."amount",
+        {PAYOUT_CURRENCY_SQL} AS "currency",
+        p."bank",
+        p."externalReference",
+        p."merchantId",
+        COALESCE(
+            ($1::double precision IS NOT NULL AND p."amount" >= $1)
+            OR p."merchantId" = ANY($2::text[])
+            OR (
+                $3::integer IS NOT NULL
+                AND p."createdAt" <= LOCALTIMESTAMP - make_interval(mins => $3)
+            ),
+            FALSE
+        ) AS "priority",
+        FALSE AS "duplicate"
+    FROM "Payout" p
+    LEFT JOIN "AggregatorPayout" ap
+        ON ap."payoutId" = p."id"
+    WHERE p."direction" = 'OUT'
+      AND p."status" = 'CREATED'
+      AND p."acceptedAt" IS NULL
+      AND p."traderId" IS NULL
+      AND ap."payoutId" IS NULL
+      AND NOT EXISTS (
+          SELECT 1
+          FROM "PayoutDuplicateFlag" df
+          WHERE df."payoutId" = p."id"
+            AND df."approvedAt" IS NULL
+      )
+    ORDER BY "priority" DESC, p."createdAt"
+    LIMIT $4
+    FOR UPDATE OF p SKIP LOCKED
+"#
+    )
+});
+
+/// Payouts a trader is still working on, used as the load for the
+/// least-loaded simulation strategy and for the open-payout cap. `$1` takes
+/// the final statuses.
+pub(crate) const OPEN_PAYOUTS_PER_TRADER_QUERY: &str = r#"
+    SELECT p."traderId", COUNT(*)::bigint AS "open"
+    FROM "Payout" p
+    WHERE p."direction" = 'OUT'
+      AND p."traderId" IS NOT NULL
+      AND p."status"::text <> ALL($1::text[])
+    GROUP BY p."traderId"
+"#;
+
+/// Traders whose last assignment is less than `$1` seconds ago, with the
+/// seconds of cooldown they have left.
+pub(crate) const TRADER_COOLDOWNS_QUERY: &str = r#"
+    SELECT
+        a."traderId",
+        CEIL(EXTRACT(EPOCH FROM (
+            MAX(a."createdAt") + make_interval(secs => $1) - LOCALTIMESTAMP
+        )))::bigint AS "remaining"
+    FROM "PayoutAuditLog" a
+    WHERE a."action" = 'assigned'
+      AND a."traderId" IS NOT NULL
+      AND a."createdAt" > LOCALTIMESTAMP - make_interval(secs => $1)
+    GROUP BY a."traderId"
+"#;
+
+/// Tables owned by this service rather than the platform schema. Every
+/// statement must be idempotent: they run on each startup.
+pub(crate) const SERVICE_SCHEMA_STATEMENTS: &[&str] = &[
+    r#"
+    CREATE TABLE IF NOT EXISTS "CancelReasonCode" (
+        "code" TEXT PRIMARY KEY,
+        "label" TEXT NOT NULL,
+        "active" BOOLEAN NOT NULL DEFAULT TRUE,
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        "updatedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "DistributionRun" (
+        "id" TEXT PRIMARY KEY,
+        "source" TEXT NOT NULL,
+        "startedAt" TIMESTAMP(3) NOT NULL,
+        "finishedAt" TIMESTAMP(3) NOT NULL,
+        "durationMs" INTEGER NOT NULL,
+        "claimed" INTEGER NOT NULL DEFAULT 0,
+        "assigned" INTEGER NOT NULL DEFAULT 0,
+        "skipped" INTEGER NOT NULL DEFAULT 0,
+        "remaining" INTEGER NOT NULL DEFAULT 0,
+        "note" TEXT,
+        "error" TEXT
+    )
+    "#,
+    r#"
+    CREATE INDEX IF NOT EXISTS "DistributionRun_startedAt_idx"
+        ON "DistributionRun" ("startedAt" DESC)
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "PayoutAuditLog" (
+        "id" TEXT PRIMARY KEY,
+        "payoutId" TEXT NOT NULL,
+        "action" TEXT NOT NULL,
+        "source" TEXT NOT NULL,
+        "actor" TEXT,
+        "traderId" TEXT,
+        "details" JSONB,
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE INDEX IF NOT EXISTS "PayoutAuditLog_payoutId_createdAt_idx"
+        ON "PayoutAuditLog" ("payoutId", "createdAt")
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "PayoutNote" (
+        "id" TEXT PRIMARY KEY,
+        "payoutId" TEXT NOT NULL,
+        "author" TEXT,
+        "body" TEXT NOT NULL,
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE INDEX IF NOT EXISTS "PayoutNote_payoutId_createdAt_idx"
+        ON "PayoutNote" ("payoutId", "createdAt")
+    "#,
+    r#"
+    CREATE INDEX IF NOT EXISTS "PayoutAuditLog_traderId_action_idx"
+        ON "PayoutAuditLog" ("traderId", "action")
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "SettingsAuditLog" (
+        "id" TEXT PRIMARY KEY,
+        "section" TEXT NOT NULL,
+        "target" TEXT,
+        "actor" TEXT,
+        "before" JSONB,
+        "after" JSONB,
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE INDEX IF NOT EXISTS "SettingsAuditLog_createdAt_idx"
+        ON "SettingsAuditLog" ("createdAt")
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "OutboxMessage" (
+        "id" TEXT PRIMARY KEY,
+        "kind" TEXT NOT NULL,
+        "payload" JSONB NOT NULL,
+        "status" TEXT NOT NULL DEFAULT 'pending',
+        "attempts" INTEGER NOT NULL DEFAULT 0,
+        "nextAttemptAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        "leasedUntil" TIMESTAMP(3),
+        "lastError" TEXT,
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        "dispatchedAt" TIMESTAMP(3)
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "MerchantCallbackOverride" (
+        "merchantId" TEXT PRIMARY KEY,
+        "headers" JSONB NOT NULL DEFAULT '{}'::jsonb,
+        "timeoutSeconds" INTEGER,
+        "updatedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "TraderApiToken" (
+        "traderId" TEXT PRIMARY KEY,
+        "tokenHash" TEXT NOT NULL UNIQUE,
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        "lastUsedAt" TIMESTAMP(3)
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "MerchantPageToken" (
+        "merchantId" TEXT PRIMARY KEY,
+        "tokenHash" TEXT NOT NULL UNIQUE,
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        "lastUsedAt" TIMESTAMP(3)
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "TraderPageToken" (
+        "traderId" TEXT PRIMARY KEY,
+        "tokenHash" TEXT NOT NULL UNIQUE,
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        "lastUsedAt" TIMESTAMP(3)
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "TraderPause" (
+        "traderId" TEXT PRIMARY KEY,
+        "reason" TEXT,
+        "pausedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE INDEX IF NOT EXISTS "OutboxMessage_pending_idx"
+        ON "OutboxMessage" ("nextAttemptAt")
+        WHERE "status" = 'pending'
+    "#,
+    r#"
+    ALTER TABLE "OutboxMessage" ADD COLUMN IF NOT EXISTS "failedAt" TIMESTAMP(3)
+    "#,
+    // Currency of payouts that are not in RUB; the platform's "Payout" table
+    // is left as it is.
+    r#"
+    CREATE TABLE IF NOT EXISTS "PayoutCurrency" (
+        "payoutId" TEXT PRIMARY KEY,
+        "currency" TEXT NOT NULL,
+        "updatedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "TraderCurrency" (
+        "traderId" TEXT NOT NULL,
+        "currency" TEXT NOT NULL,
+        "maxAmount" DOUBLE PRECISION,
+        "updatedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY ("traderId", "currency")
+    )
+    "#,
+    r#"
+    ALTER TABLE "MerchantCallbackOverride" ADD COLUMN IF NOT EXISTS "webhookUrl" TEXT
+    "#,
+    r#"
+    ALTER TABLE "MerchantCallbackOverride" ADD COLUMN IF NOT EXISTS "proxyUrl" TEXT
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "CallbackDelivery" (
+        "id" TEXT PRIMARY KEY,
+        "outboxId" TEXT NOT NULL,
+        "merchantId" TEXT NOT NULL,
+        "delivered" BOOLEAN NOT NULL,
+        "statusCode" INTEGER,
+        "latencyMs" INTEGER,
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE INDEX IF NOT EXISTS "CallbackDelivery_merchantId_createdAt_idx"
+        ON "CallbackDelivery" ("merchantId", "createdAt")
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "MerchantCallbackTemplate" (
+        "merchantId" TEXT PRIMARY KEY,
+        "template" TEXT NOT NULL,
+        "updatedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "MerchantClientCertificate" (
+        "merchantId" TEXT PRIMARY KEY,
+        "certificate" TEXT NOT NULL,
+        "privateKey" TEXT NOT NULL,
+        "updatedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "PayoutArchive" (
+        "payoutId" TEXT PRIMARY KEY,
+        "archivedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "PayoutFreeze" (
+        "payoutId" TEXT PRIMARY KEY,
+        "traderId" TEXT NOT NULL,
+        "amount" DOUBLE PRECISION NOT NULL,
+        "frozenAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "PayoutDuplicateFlag" (
+        "payoutId" TEXT PRIMARY KEY,
+        "duplicateOf" TEXT NOT NULL,
+        "flaggedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        "approvedAt" TIMESTAMP(3),
+        "approvedBy" TEXT
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "OperatorUser" (
+        "id" TEXT PRIMARY KEY,
+        "username" TEXT NOT NULL UNIQUE,
+        "passwordHash" TEXT NOT NULL,
+        "tenant" TEXT,
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        "lastLoginAt" TIMESTAMP(3)
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "OperatorSession" (
+        "id" TEXT PRIMARY KEY,
+        "data" JSONB NOT NULL,
+        "expiresAt" TIMESTAMPTZ NOT NULL
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "TraderCapacity" (
+        "traderId" TEXT PRIMARY KEY,
+        "maxOpenPayouts" INTEGER NOT NULL,
+        "updatedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "TraderBankWeight" (
+        "bank" TEXT NOT NULL,
+        "traderId" TEXT NOT NULL,
+        "weight" DOUBLE PRECISION NOT NULL,
+        "updatedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY ("bank", "traderId")
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "TraderTeam" (
+        "id" TEXT PRIMARY KEY,
+        "name" TEXT NOT NULL,
+        "enabled" BOOLEAN NOT NULL DEFAULT TRUE,
+        "maxOpenPayouts" INTEGER,
+        "maxAssignmentsPerCycle" INTEGER,
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        "updatedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "TraderTeamMember" (
+        "traderId" TEXT PRIMARY KEY,
+        "teamId" TEXT NOT NULL REFERENCES "TraderTeam" ("id") ON DELETE CASCADE,
+        "updatedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE INDEX IF NOT EXISTS "TraderTeamMember_teamId_idx"
+        ON "TraderTeamMember" ("teamId")
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "EmailRecipient" (
+        "email" TEXT PRIMARY KEY,
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "ReportSchedule" (
+        "id" TEXT PRIMARY KEY,
+        "enabled" BOOLEAN NOT NULL DEFAULT FALSE,
+        "sendAt" TEXT NOT NULL,
+        "timezone" TEXT NOT NULL DEFAULT 'UTC',
+        "channels" TEXT[] NOT NULL DEFAULT ARRAY[]::TEXT[],
+        "lastSentOn" DATE,
+        "updatedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "FeatureFlag" (
+        "key" TEXT PRIMARY KEY,
+        "enabled" BOOLEAN NOT NULL,
+        "updatedBy" TEXT,
+        "updatedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "AlertState" (
+        "key" TEXT PRIMARY KEY,
+        "value" BIGINT NOT NULL,
+        "updatedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "TraderWebhook" (
+        "traderId" TEXT PRIMARY KEY,
+        "url" TEXT NOT NULL,
+        "secret" TEXT NOT NULL,
+        "enabled" BOOLEAN NOT NULL DEFAULT TRUE,
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        "updatedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "DealFilterPreset" (
+        "id" TEXT PRIMARY KEY,
+        "owner" TEXT NOT NULL,
+        "name" TEXT NOT NULL,
+        "filters" JSONB NOT NULL,
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        "updatedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        UNIQUE ("owner", "name")
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "UserPreference" (
+        "owner" TEXT PRIMARY KEY,
+        "value" JSONB NOT NULL,
+        "updatedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "TraderUnavailability" (
+        "id" TEXT PRIMARY KEY,
+        "traderId" TEXT NOT NULL,
+        "startsAt" TIMESTAMP(3) NOT NULL,
+        "endsAt" TIMESTAMP(3) NOT NULL,
+        "reason" TEXT,
+        "createdBy" TEXT,
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE INDEX IF NOT EXISTS "TraderUnavailability_traderId_endsAt_idx"
+        ON "TraderUnavailability" ("traderId", "endsAt")
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "MerchantCallbackPause" (
+        "merchantId" TEXT PRIMARY KEY,
+        "reason" TEXT,
+        "pausedBy" TEXT,
+        "pausedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "PayoutCancelRequest" (
+        "id" TEXT PRIMARY KEY,
+        "payoutId" TEXT NOT NULL,
+        "requestedBy" TEXT NOT NULL,
+        "reason" TEXT,
+        "reasonCode" TEXT,
+        "status" TEXT NOT NULL DEFAULT 'pending',
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        "expiresAt" TIMESTAMP(3) NOT NULL,
+        "decidedBy" TEXT,
+        "decidedAt" TIMESTAMP(3)
+    )
+    "#,
+    r#"
+    ALTER TABLE "PayoutCancelRequest" ADD COLUMN IF NOT EXISTS "requestedById" TEXT
+    "#,
+    r#"
+    CREATE UNIQUE INDEX IF NOT EXISTS "PayoutCancelRequest_pending_payoutId_key"
+        ON "PayoutCancelRequest" ("payoutId")
+        WHERE "status" = 'pending'
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "SettingsChangeRequest" (
+        "id" TEXT PRIMARY KEY,
+        "section" TEXT NOT NULL,
+        "before" JSONB NOT NULL,
+        "after" JSONB NOT NULL,
+        "risks" TEXT[] NOT NULL DEFAULT '{}',
+        "requestedBy" TEXT NOT NULL,
+        "status" TEXT NOT NULL DEFAULT 'pending',
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        "decidedBy" TEXT,
+        "decidedAt" TIMESTAMP(3)
+    )
+    "#,
+    r#"
+    ALTER TABLE "SettingsChangeRequest" ADD COLUMN IF NOT EXISTS "requestedById" TEXT
+    "#,
+    r#"
+    CREATE UNIQUE INDEX IF NOT EXISTS "SettingsChangeRequest_pending_section_key"
+        ON "SettingsChangeRequest" ("section")
+        WHERE "status" = 'pending'
+    "#,
+    r#"
+    CREATE TABLE IF NOT EXISTS "TraderEligibilityChange" (
+        "id" TEXT PRIMARY KEY,
+        "traderId" TEXT NOT NULL,
+        "eligible" BOOLEAN NOT NULL,
+        "reasons" TEXT[] NOT NULL DEFAULT '{}',
+        "merchantsAdded" TEXT[] NOT NULL DEFAULT '{}',
+        "merchantsRemoved" TEXT[] NOT NULL DEFAULT '{}',
+        "recentAssignments" INTEGER,
+        "alerted" BOOLEAN NOT NULL DEFAULT FALSE,
+        "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    r#"
+    CREATE INDEX IF NOT EXISTS "TraderEligibilityChange_traderId_createdAt_idx"
+        ON "TraderEligibilityChange" ("traderId", "createdAt")
+    "#,
+    r#"
+    CREATE INDEX IF NOT EXISTS "TraderEligibilityChange_createdAt_idx"
+        ON "TraderEligibilityChange" ("createdAt")
+    "#,
+    r#"
+    INSERT INTO "CancelReasonCode" ("code", "label")
+    VALUES
+        ('MERCHANT_REQUEST', 'Отмена по запросу мерчанта'),
+        ('INVALID_DETAILS', 'Неверные реквизиты'),
+        ('DUPLICATE', 'Дубликат выплаты'),
+        ('NO_TRADER', 'Нет доступного трейдера'),
+        ('UNASSIGNED_TIMEOUT', 'Не назначена вовремя'),
+        ('OTHER', 'Другое')
+    ON CONFLICT ("code") DO NOTHING
+    "#,
+];
+
+/// Hourly queue/throughput buckets. The Payout table has no dedicated assignment
+/// timestamp, so assignments are bucketed by `acceptedAt` and the backlog is
+/// reconstructed from payouts created inside the window.
+pub(crate) const TIMESERIES_QUERY: &str = r#"
+    WITH buckets AS (
+        SELECT generate_series(
+            date_trunc('hour', CURRENT_TIMESTAMP)::timestamp - make_interval(hours => $1 - 1),
+            date_trunc('hour', CURRENT_TIMESTAMP)::timestamp,
+            INTERVAL '1 hour'
+        ) AS "bucket"
+    ),
+    window_payouts AS (
+        SELECT p."createdAt", p."acceptedAt", p."cancelledAt", p."traderId", p."status"
+        FROM "Payout" p
+        WHERE p."direction" = 'OUT'
+          AND p."createdAt" >= (SELECT MIN("bucket") FROM buckets)
+          AND ($2::text[] IS NULL OR p."merchantId" = ANY($2::text[]))
+    ),
+    counts AS (
+        SELECT
+            b."bucket",
+            COUNT(wp."createdAt") FILTER (
+                WHERE wp."createdAt" >= b."bucket"
+                  AND wp."createdAt" < b."bucket" + INTERVAL '1 hour'
+            ) AS "created",
+            COUNT(wp."acceptedAt") FILTER (
+                WHERE wp."traderId" IS NOT NULL
+                  AND wp."acceptedAt" >= b."bucket"
+                  AND wp."acceptedAt" < b."bucket" + INTERVAL '1 hour'
+            ) AS "assigned",
+            COUNT(wp."cancelledAt") FILTER (
+                WHERE wp."status" = 'CANCELLED'
+                  AND wp."cancelledAt" >= b."bucket"
+                  AND wp."cancelledAt" < b."bucket" + INTERVAL '1 hour'
+            ) AS "cancelled",
+            COUNT(wp."createdAt") FILTER (
+                WHERE wp."createdAt" < b."bucket" + INTERVAL '1 hour'
+                  AND (wp."traderId" IS NULL OR wp."acceptedAt" >= b."bucket" + INTERVAL '1 hour')
+                  AND (wp."cancelledAt" IS NULL OR wp."cancelledAt" >= b."bucket" + INTERVAL '1 hour')
+            ) AS "backlog"
+        FROM buckets b
+        LEFT JOIN window_payouts wp ON TRUE
+        GROUP BY b."bucket"
+    )
+    SELECT
+        "bucket",
+        "created",
+        "assigned",
+        "cancelled",
+        "backlog",
+        CASE
+            WHEN "created" = 0 THEN NULL
+            ELSE "cancelled"::double precision / "created"
+        END AS "cancellation_rate"
+    FROM counts
+    ORDER BY "bucket"
+"#;
+
+#[derive(Debug, FromRow, Clone)]
+pub(crate) struct TraderRecord {
+    pub(crate) id: String,
+    pub(crate) email: String,
+    #[sqlx(rename = "numericId")]
+    pub(crate) numeric_id: i32,
+    #[sqlx(rename = "balanceRub")]
+    pub(crate) balance_rub: Option<f64>,
+    #[sqlx(rename = "frozenRub")]
+    pub(crate) frozen_rub: Option<f64>,
+    #[sqlx(rename = "payoutBalance")]
+    pub(crate) payout_balance: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Trader {
+    pub(crate) id: String,
+    pub(crate) email: String,
+    pub(crate) numeric_id: i32,
+    pub(crate) balance_rub: Option<f64>,
+    pub(crate) frozen_rub: Option<f64>,
+    pub(crate) payout_balance: Option<f64>,
+    pub(crate) min_amount: Option<f64>,
+    pub(crate) max_amount: Option<f64>,
+    /// Seconds until the distributor considers the trader again.
+    pub(crate) cooldown_remaining_seconds: Option<i64>,
+    pub(crate) open_payouts: i64,
+    /// The trader's own cap or the global default, `None` when unlimited.
+    pub(crate) max_open_payouts: Option<u32>,
+    pub(crate) team: Option<TeamSummary>,
+    /// Currencies the trader takes besides RUB.
+    pub(crate) currencies: Vec<TraderCurrency>,
+    /// The trader's next planned absence, see [`availability`](crate::availability).
+    pub(crate) unavailability: Option<UnavailabilityWindow>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub(crate) struct UnassignedPayout {
+    pub(crate) id: String,
+    #[sqlx(rename = "numericId")]
+    #[serde(rename = "numericId")]
+    pub(crate) numeric_id: i32,
+    pub(crate) amount: Option<f64>,
+    pub(crate) currency: String,
+    pub(crate) bank: Option<String>,
+    #[sqlx(rename = "externalReference")]
+    #[serde(rename = "externalReference")]
+    pub(crate) external_reference: Option<String>,
+    #[sqlx(rename = "merchantId")]
+    #[serde(rename = "merchantId")]
+    pub(crate) merchant_id: Option<String>,
+    pub(crate) priority: bool,
+    /// Flagged as a possible duplicate and waiting for an operator.
+    pub(crate) duplicate: bool,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PayoutDealListItem {
+    pub(crate) id: String,
+    #[sqlx(rename = "numericId")]
+    #[serde(rename = "numericId")]
+    pub(crate) numeric_id: i32,
+    pub(crate) amount: f64,
+    #[sqlx(rename = "amountUsdt")]
+    #[serde(rename = "amountUsdt")]
+    pub(crate) amount_usdt: f64,
+    pub(crate) currency: String,
+    pub(crate) status: PayoutStatus,
+    pub(crate) wallet: String,
+    pub(crate) bank: String,
+    #[sqlx(rename = "externalReference")]
+    #[serde(rename = "externalReference")]
+    pub(crate) external_reference: Option<String>,
+    #[sqlx(rename = "merchantId")]
+    #[serde(rename = "merchantId")]
+    pub(crate) merchant_id: String,
+    #[sqlx(rename = "traderId")]
+    #[serde(rename = "traderId")]
+    pub(crate) trader_id: Option<String>,
+    #[sqlx(rename = "createdAt")]
+    #[serde(rename = "createdAt")]
+    pub(crate) created_at: UtcTimestamp,
+    #[sqlx(rename = "cancelReason")]
+    #[serde(rename = "cancelReason")]
+    pub(crate) cancel_reason: Option<String>,
+    #[sqlx(rename = "cancelReasonCode")]
+    #[serde(rename = "cancelReasonCode")]
+    pub(crate) cancel_reason_code: Option<String>,
+    /// Minutes the trader has to accept an assignment.
+    #[sqlx(rename = "acceptanceTime")]
+    #[serde(rename = "acceptanceTime")]
+    acceptance_time: Option<i32>,
+    /// Last assignment recorded in the audit log.
+    #[sqlx(rename = "assignedAt")]
+    #[serde(rename = "assignedAt")]
+    assigned_at: Option<UtcTimestamp>,
+    #[sqlx(rename = "acceptedAt")]
+    #[serde(rename = "acceptedAt")]
+    accepted_at: Option<UtcTimestamp>,
+    /// The payout's version, see [`payout_version`](crate::payout_version).
+    #[sqlx(rename = "updatedAt")]
+    #[serde(rename = "updatedAt")]
+    pub(crate) updated_at: UtcTimestamp,
+    /// Seconds left to accept, negative once overdue. Only set while the
+    /// payout is assigned but not yet accepted.
+    #[sqlx(rename = "acceptanceRemainingSeconds")]
+    #[serde(rename = "acceptanceRemainingSeconds")]
+    pub(crate) acceptance_remaining_seconds: Option<i64>,
+    /// How far `amountUsdt` is from the current rate, in percent. Only set for
+    /// open payouts: settled ones were priced at an older rate.
+    #[sqlx(skip)]
+    #[serde(rename = "rateDeviationPercent")]
+    pub(crate) rate_deviation_percent: Option<f64>,
+    #[sqlx(skip)]
+    #[serde(rename = "rateMismatch")]
+    pub(crate) rate_mismatch: bool,
+    pub(crate) archived: bool,
+}
+
+impl PayoutDealListItem {
+    pub(crate) fn annotate_rate(&mut self, rates: &rates::RateSnapshot) {
+        if self.status.is_final() {
+            return;
+        }
+        if let Some((deviation, mismatch)) = rates.check(self.amount, self.amount_usdt) {
+            self.rate_deviation_percent = Some(deviation);
+            self.rate_mismatch = mismatch;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Pagination {
+    pub(crate) total: i64,
+    pub(crate) page: u32,
+    pub(crate) per_page: u32,
+    pub(crate) total_pages: u32,
+}
+
+impl Pagination {
+    pub(crate) fn new(total: i64, page: u32, per_page: u32) -> Self {
+        let total_pages = if total == 0 {
+            0
+        } else {
+            ((total as f64) / (per_page as f64)).ceil() as u32
+        };
+        Self {
+            total,
+            page,
+            per_page,
+            total_pages,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PayoutListResponse {
+    pub(crate) items: Vec<PayoutDealListItem>,
+    pub(crate) pagination: Pagination,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TraderListResponse {
+    pub(crate) items: Vec<Trader>,
+    pub(crate) pagination: Pagination,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UnassignedPayoutListResponse {
+    pub(crate) items: Vec<UnassignedPayout>,
+    pub(crate) pagination: Pagination,
+    total_amount: f64,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TimeseriesPoint {
+    bucket: UtcTimestamp,
+    created: i64,
+    assigned: i64,
+    cancelled: i64,
+    backlog: i64,
+    cancellation_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TodayTotals {
+    #[sqlx(rename = "assignedToday")]
+    pub(crate) assigned_today: i64,
+    #[sqlx(rename = "assignedTodayAmount")]
+    pub(crate) assigned_today_amount: f64,
+    #[sqlx(rename = "cancelledToday")]
+    pub(crate) cancelled_today: i64,
+    #[sqlx(rename = "cancelledTodayAmount")]
+    pub(crate) cancelled_today_amount: f64,
+    #[sqlx(rename = "completedToday")]
+    pub(crate) completed_today: i64,
+    #[sqlx(rename = "completedTodayAmount")]
+    pub(crate) completed_today_amount: f64,
+}
+
+/// Figures behind the dashboard metric cards, computed in SQL for the
+/// caller's scope.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StatsSummary {
+    pub(crate) eligible_traders: i64,
+    pub(crate) unassigned_count: i64,
+    pub(crate) unassigned_amount: f64,
+    #[serde(flatten)]
+    pub(crate) today: TodayTotals,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct PayoutListData {
+    pub(crate) items: Vec<PayoutDealListItem>,
+    pub(crate) total: i64,
+    page: u32,
+    per_page: u32,
+}
+
+impl PayoutListData {
+    pub(crate) fn annotate_rates(&mut self, rates: &rates::RateSnapshot) {
+        for item in &mut self.items {
+            item.annotate_rate(rates);
+        }
+    }
+
+    pub(crate) fn into_response(self) -> PayoutListResponse {
+        PayoutListResponse {
+            items: self.items,
+            pagination: Pagination::new(self.total, self.page, self.per_page),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TraderSortField {
+    NumericId,
+    Email,
+    BalanceRub,
+    FrozenRub,
+    PayoutBalance,
+    /// By team name, traders without a team last.
+    Team,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct TraderListFilters {
+    pub(crate) search: Option<String>,
+    pub(crate) sort: TraderSortField,
+    pub(crate) order: SortOrder,
+    pub(crate) page: u32,
+    pub(crate) per_page: u32,
+    /// Tenant allowlist; set from the caller's scope, never from the query.
+    pub(crate) merchant_ids: Option<Vec<String>>,
+}
+
+impl Default for TraderListFilters {
+    fn default() -> Self {
+        Self {
+            search: None,
+            sort: TraderSortField::NumericId,
+            order: SortOrder::Asc,
+            page: 1,
+            per_page: 25,
+            merchant_ids: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SortField {
+    CreatedAt,
+    Status,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SortOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct PayoutListFilters {
+    pub(crate) search: Option<String>,
+    pub(crate) wallet: Option<String>,
+    pub(crate) amount: Option<f64>,
+    pub(crate) status: Option<PayoutStatus>,
+    pub(crate) page: u32,
+    pub(crate) per_page: u32,
+    pub(crate) sort: SortField,
+    pub(crate) order: SortOrder,
+    pub(crate) include_archived: bool,
+    /// Tenant allowlist; set from the caller's scope, never from the query.
+    pub(crate) merchant_ids: Option<Vec<String>>,
+}
+
+impl Default for PayoutListFilters {
+    fn default() -> Self {
+        Self {
+            search: None,
+            wallet: None,
+            amount: None,
+            status: None,
+            page: 1,
+            per_page: 25,
+            sort: SortField::CreatedAt,
+            order: SortOrder::Desc,
+            include_archived: false,
+            merchant_ids: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub(crate) struct PayoutDetails {
+    pub(crate) id: String,
+    pub(crate) numeric_id: i32,
+    pub(crate) amount: f64,
+    pub(crate) amount_usdt: f64,
+    pub(crate) currency: String,
+    pub(crate) status: PayoutStatus,
+    pub(crate) wallet: String,
+    pub(crate) bank: String,
+    pub(crate) external_reference: Option<String>,
+    pub(crate) merchant_metadata: Option<Value>,
+    pub(crate) proof_files: Option<Vec<String>>,
+    pub(crate) dispute_files: Option<Vec<String>>,
+    pub(crate) dispute_message: Option<String>,
+    pub(crate) cancel_reason: Option<String>,
+    pub(crate) cancel_reason_code: Option<String>,
+    pub(crate) merchant_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CancelReasonCode {
+    pub(crate) code: String,
+    label: String,
+    pub(crate) active: bool,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+    #[sqlx(rename = "updatedAt")]
+    updated_at: UtcTimestamp,
+}
+
+/// Eligible trader ids with the merchants they currently serve.
+pub(crate) async fn eligible_trader_snapshot(
+    pool: &PgPool,
+) -> sqlx::Result<BTreeMap<String, Vec<String>>> {
+    let rows: Vec<(String, Vec<String>)> = sqlx::query_as(&format!(
+        r#"
+        SELECT t."id", ARRAY(
+            SELECT tm."merchantId"
+            FROM "TraderMerchant" tm
+            WHERE tm."traderId" = t."id"
+              AND tm."isMerchantEnabled" = TRUE
+              AND tm."isFeeOutEnabled" = TRUE
+            ORDER BY tm."merchantId"
+        )
+        FROM ({ELIGIBLE_TRADERS_QUERY}) t
+        "#
+    ))
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().collect())
+}
+
+pub(crate) async fn fetch_cancel_reasons(pool: &PgPool) -> Result<Vec<CancelReasonCode>> {
+    sqlx::query_as::<_, CancelReasonCode>(
+        r#"
+        SELECT "code", "label", "active", "createdAt", "updatedAt"
+        FROM "CancelReasonCode"
+        ORDER BY "active" DESC, "code"
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch cancel reason codes")
+}
+
+/// Appends a manual action to the payout audit trail. `actor` comes from
+/// [`auth::audit_actor`](crate::auth::audit_actor) (`operator:<user>` or
+/// `tenant:<name>`), or is `auto` for workers. Auto assignments are written
+/// in bulk by the distribution cycle.
+pub(crate) async fn record_payout_audit<'e, E>(
+    executor: E,
+    payout_id: &str,
+    action: &str,
+    actor: Option<&str>,
+    trader_id: Option<&str>,
+    details: Option<Value>,
+) -> sqlx::Result<()>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query(
+        r#"
+        INSERT INTO "PayoutAuditLog"
+            ("id", "payoutId", "action", "source", "actor", "traderId", "details")
+        VALUES ($1, $2, $3, 'manual', $4, $5, $6)
+        "#,
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(payout_id)
+    .bind(action)
+    .bind(actor)
+    .bind(trader_id)
+    .bind(details.as_ref().map(redaction::json))
+    .execute(executor)
+    .await
+    .map(|_| ())
+}
+
+pub(crate) async fn ensure_service_schema(pool: &PgPool) -> Result<()> {
+    for statement in SERVICE_SCHEMA_STATEMENTS {
+        sqlx::query(statement)
+            .execute(pool)
+            .await
+            .context("Failed to apply service schema")?;
+    }
+    Ok(())
+}
+
+pub(crate) async fn fetch_traders(pool: &PgPool) -> Result<Vec<TraderRecord>> {
+    sqlx::query_as::<_, TraderRecord>(ELIGIBLE_TRADERS_QUERY)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch eligible traders")
+}
+
+pub(crate) async fn fetch_open_payouts(pool: &PgPool) -> sqlx::Result<HashMap<String, i64>> {
+    let rows = sqlx::query_as::<_, (String, i64)>(OPEN_PAYOUTS_PER_TRADER_QUERY)
+        .bind(PayoutStatus::final_names())
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().collect())
+}
+
+/// Per-trader open-payout caps from `TraderCapacity`.
+pub(crate) async fn fetch_capacity_overrides(pool: &PgPool) -> sqlx::Result<HashMap<String, u32>> {
+    let rows = sqlx::query_as::<_, (String, i32)>(
+        r#"SELECT "traderId", "maxOpenPayouts" FROM "TraderCapacity""#,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(trader_id, cap)| (trader_id, cap.max(0) as u32))
+        .collect())
+}
+
+/// Remaining cooldown per trader; empty when the cooldown is disabled.
+pub(crate) async fn fetch_trader_cooldowns(
+    pool: &PgPool,
+    seconds: u32,
+) -> Result<HashMap<String, i64>> {
+    if seconds == 0 {
+        return Ok(HashMap::new());
+    }
+    let rows = sqlx::query_as::<_, (String, i64)>(TRADER_COOLDOWNS_QUERY)
+        .bind(f64::from(seconds))
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch trader cooldowns")?;
+    Ok(rows
+        .into_iter()
+        .filter(|(_, remaining)| *remaining > 0)
+        .collect())
+}
+
+pub(crate) async fn fetch_filtered_traders(
+    pool: &PgPool,
+    filters: &TraderListFilters,
+) -> Result<(Vec<TraderRecord>, i64)> {
+    let mut count_builder: QueryBuilder<Postgres> = QueryBuilder::new(format!(
+        "SELECT COUNT(*)::bigint AS total FROM ({ELIGIBLE_TRADERS_QUERY}) t WHERE TRUE"
+    ));
+    apply_trader_search(&mut count_builder, filters);
+
+    let total: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(pool)
+        .await
+        .context("Failed to count eligible traders")?;
+
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(format!(
+        "SELECT t.* FROM ({ELIGIBLE_TRADERS_QUERY}) t WHERE TRUE"
+    ));
+    apply_trader_search(&mut builder, filters);
+
+    let column = match filters.sort {
+        TraderSortField::NumericId => "t.\"numericId\"",
+        TraderSortField::Email => "t.\"email\"",
+        TraderSortField::BalanceRub => "t.\"balanceRub\"",
+        TraderSortField::FrozenRub => "t.\"frozenRub\"",
+        TraderSortField::PayoutBalance => "t.\"payoutBalance\"",
+        TraderSortField::Team => {
+            r#"(
+                SELECT tt."name"
+                FROM "TraderTeamMember" ttm
+                JOIN "TraderTeam" tt ON tt."id" = ttm."teamId"
+                WHERE ttm."traderId" = t."id"
+            )"#
+        }
+    };
+    let direction = match filters.order {
+        SortOrder::Asc => "ASC NULLS LAST",
+        SortOrder::Desc => "DESC NULLS LAST",
+    };
+    builder.push(format!(
+        " ORDER BY {column} {direction}, t.\"numericId\" ASC"
+    ));
+
+    let offset = ((filters.page.saturating_sub(1)) as i64) * filters.per_page as i64;
+    builder.push(" LIMIT ").push_bind(filters.per_page as i64);
+    builder.push(" OFFSET ").push_bind(offset);
+
+    let records = builder
+        .build_query_as::<TraderRecord>()
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch eligible traders")?;
+
+    Ok((records, total))
+}
+
+pub(crate) fn apply_trader_search(
+    builder: &mut QueryBuilder<Postgres>,
+    filters: &TraderListFilters,
+) {
+    if let Some(merchant_ids) = filters.merchant_ids.as_ref() {
+        builder.push(
+            " AND EXISTS (SELECT 1 FROM \"TraderMerchant\" tm WHERE tm.\"traderId\" = t.\"id\" AND tm.\"isMerchantEnabled\" = TRUE AND tm.\"merchantId\" = ANY(",
+        );
+        builder.push_bind(merchant_ids.clone()).push("))");
+    }
+
+    if let Some(search) = filters.search.as_ref() {
+        let like = format!("%{}%", search);
+        builder
+            .push(" AND (t.\"email\" ILIKE ")
+            .push_bind(like.clone());
+        builder
+            .push(" OR t.\"numericId\"::text ILIKE ")
+            .push_bind(like);
+        builder.push(")");
+    }
+}
+
+pub(crate) async fn fetch_unassigned_payouts_page(
+    pool: &PgPool,
+    policy: &PriorityPolicy,
+    merchant_ids: Option<&[String]>,
+    page: u32,
+    per_page: u32,
+) -> Result<UnassignedPayoutListResponse> {
+    let (total, total_amount): (i64, f64) = sqlx::query_as(&format!(
+        r#"SELECT COUNT(*)::bigint, COALESCE(SUM(q."amount"), 0)::double precision FROM ({query}) q"#, query = *UNASSIGNED_PAYOUTS_QUERY
+    ))
+    .bind(policy.amount_threshold)
+    .bind(&policy.merchant_ids)
+    .bind(policy.max_age_minutes_i32())
+    .bind(merchant_ids)
+    .fetch_one(pool)
+    .await
+    .context("Failed to count unassigned payouts")?;
+
+    let offset = ((page.saturating_sub(1)) as i64) * per_page as i64;
+    let items = sqlx::query_as::<_, UnassignedPayout>(&format!(
+        "{query} LIMIT $5 OFFSET $6",
+        query = *UNASSIGNED_PAYOUTS_QUERY
+    ))
+    .bind(policy.amount_threshold)
+    .bind(&policy.merchant_ids)
+    .bind(policy.max_age_minutes_i32())
+    .bind(merchant_ids)
+    .bind(per_page as i64)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch unassigned payouts")?;
+
+    Ok(UnassignedPayoutListResponse {
+        items,
+        pagination: Pagination::new(total, page, per_page),
+        total_amount,
+    })
+}
+
+/// Locks up to `limit` unassigned payouts for this cycle, priority lanes
+/// first; the rest of the backlog stays free for the platform.
+pub(crate) async fn claim_unassigned_payouts(
+    tx: &mut Transaction<'_, Postgres>,
+    policy: &PriorityPolicy,
+    limit: u32,
+) -> Result<Vec<UnassignedPayout>> {
+    sqlx::query_as::<_, UnassignedPayout>(&CLAIM_UNASSIGNED_PAYOUTS_QUERY)
+        .bind(policy.amount_threshold)
+        .bind(&policy.merchant_ids)
+        .bind(policy.max_age_minutes_i32())
+        .bind(i64::from(limit))
+        .fetch_all(&mut **tx)
+        .await
+        .context("Failed to claim unassigned payouts")
+}
+
+pub(crate) async fn fetch_stats_summary(
+    pool: &PgPool,
+    policy: &PriorityPolicy,
+    merchant_ids: Option<&[String]>,
+) -> Result<StatsSummary> {
+    let eligible_traders: i64 = sqlx::query_scalar(&format!(
+        r#"
+        SELECT COUNT(*)::bigint
+        FROM ({ELIGIBLE_TRADERS_QUERY}) t
+        WHERE $1::text[] IS NULL
+           OR EXISTS (
+               SELECT 1
+               FROM "TraderMerchant" tm
+               WHERE tm."traderId" = t."id"
+                 AND tm."isMerchantEnabled" = TRUE
+                 AND tm."merchantId" = ANY($1::text[])
+           )
+        "#
+    ))
+    .bind(merchant_ids)
+    .fetch_one(pool)
+    .await
+    .context("Failed to count eligible traders")?;
+
+    let (unassigned_count, unassigned_amount): (i64, f64) = sqlx::query_as(&format!(
+        r#"SELECT COUNT(*)::bigint, COALESCE(SUM(q."amount"), 0)::double precision FROM ({query}) q"#, query = *UNASSIGNED_PAYOUTS_QUERY
+    ))
+    .bind(policy.amount_threshold)
+    .bind(&policy.merchant_ids)
+    .bind(policy.max_age_minutes_i32())
+    .bind(merchant_ids)
+    .fetch_one(pool)
+    .await
+    .context("Failed to count unassigned payouts")?;
+
+    let today = sqlx::query_as::<_, TodayTotals>(TODAY_TOTALS_QUERY)
+        .bind(merchant_ids)
+        .fetch_one(pool)
+        .await
+        .context("Failed to aggregate today's payouts")?;
+
+    Ok(StatsSummary {
+        eligible_traders,
+        unassigned_count,
+        unassigned_amount,
+        today,
+    })
+}
+
+pub(crate) async fn fetch_timeseries(
+    pool: &PgPool,
+    hours: u32,
+    merchant_ids: Option<&[String]>,
+) -> Result<Vec<TimeseriesPoint>> {
+    sqlx::query_as::<_, TimeseriesPoint>(TIMESERIES_QUERY)
+        .bind(hours as i32)
+        .bind(merchant_ids)
+        .fetch_all(pool)
+        .await
+        .context("Failed to aggregate payout timeseries")
+}
+
+/// The columns of [`PayoutDealListItem`]; callers add the `WHERE` clause.
+static PAYOUT_DEAL_SELECT: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        r#"
+    SELECT
+        p."id",
+        p."numericId",
+        p."amount",
+        p."amountUsdt",
+        {PAYOUT_CURRENCY_SQL} AS "currency",
+        p."status"::text AS "status",
+        p."wallet",
+        p."bank",
+        p."externalReference",
+        p."merchantId",
+        p."traderId",
+        p."createdAt",
+        p."cancelReason",
+        p."cancelReasonCode",
+        p."acceptanceTime",
+        assignment."assignedAt",
+        p."acceptedAt",
+        p."updatedAt",
+        CASE
+            WHEN p."traderId" IS NOT NULL
+              AND p."acceptedAt" IS NULL
+              AND p."status" = 'CREATED'
+              AND p."acceptanceTime" IS NOT NULL
+              AND assignment."assignedAt" IS NOT NULL
+            THEN FLOOR(EXTRACT(EPOCH FROM (
+                assignment."assignedAt"
+                    + make_interval(mins => p."acceptanceTime")
+                    - LOCALTIMESTAMP
+            )))::bigint
+        END AS "acceptanceRemainingSeconds",
+        EXISTS (
+            SELECT 1 FROM "PayoutArchive" pa WHERE pa."payoutId" = p."id"
+        ) AS "archived"
+    FROM "Payout" p
+    LEFT JOIN LATERAL (
+        SELECT MAX(a."createdAt") AS "assignedAt"
+        FROM "PayoutAuditLog" a
+        WHERE a."payoutId" = p."id" AND a."action" = 'assigned'
+    ) assignment ON TRUE
+"#
+    )
+});
+
+/// One payout as the deals list shows it, e.g. to answer a change to it.
+pub(crate) async fn fetch_payout_deal(
+    pool: &PgPool,
+    payout_id: &str,
+) -> Result<Option<PayoutDealListItem>> {
+    sqlx::query_as::<_, PayoutDealListItem>(&format!(
+        r#"{select} WHERE p."id" = $1 AND p."direction" = 'OUT'"#,
+        select = *PAYOUT_DEAL_SELECT
+    ))
+    .bind(payout_id)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch payout")
+}
+
+pub(crate) async fn fetch_payouts_page(
+    pool: &PgPool,
+    filters: &PayoutListFilters,
+) -> Result<PayoutListData> {
+    let mut count_builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        r#"SELECT COUNT(*)::bigint AS total FROM "Payout" p WHERE p."direction" = 'OUT'"#,
+    );
+    apply_payout_filters(&mut count_builder, filters);
+
+    let total: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(pool)
+        .await
+        .context("Failed to count payouts")?;
+
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(PAYOUT_DEAL_SELECT.as_str());
+    builder.push(r#" WHERE p."direction" = 'OUT'"#);
+
+    apply_payout_filters(&mut builder, filters);
+    apply_payout_sort(&mut builder, filters);
+
+    let per_page = filters.per_page.min(200);
+    let offset = ((filters.page.saturating_sub(1)) as i64) * per_page as i64;
+
+    builder.push(" LIMIT ").push_bind(per_page as i64);
+    builder.push(" OFFSET ").push_bind(offset.max(0));
+
+    let items = builder
+        .build_query_as::<PayoutDealListItem>()
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch payouts list")?;
+
+    Ok(PayoutListData {
+        items,
+        total,
+        page: filters.page,
+        per_page,
+    })
+}
+
+pub(crate) fn apply_payout_filters(
+    builder: &mut QueryBuilder<Postgres>,
+    filters: &PayoutListFilters,
+) {
+    if let Some(merchant_ids) = filters.merchant_ids.as_ref() {
+        builder
+            .push(" AND p.\"merchantId\" = ANY(")
+            .push_bind(merchant_ids.clone())
+            .push(")");
+    }
+
+    if !filters.include_archived {
+        builder.push(
+            " AND NOT EXISTS (SELECT 1 FROM \"PayoutArchive\" pa WHERE pa.\"payoutId\" = p.\"id\")",
+        );
+    }
+
+    if let Some(search) = filters.search.as_ref() {
+        let like = format!("%{}%", search);
+        builder.push(" AND (");
+        builder.push("p.\"id\" ILIKE ").push_bind(like.clone());
+        builder
+            .push(" OR p.\"externalReference\" ILIKE ")
+            .push_bind(like.clone());
+        builder
+            .push(" OR p.\"numericId\"::text ILIKE ")
+            .push_bind(like);
+        builder.push(")");
+    }
+
+    if let Some(wallet) = filters.wallet.as_ref() {
+        let like = format!("%{}%", wallet);
+        builder.push(" AND p.\"wallet\" ILIKE ").push_bind(like);
+    }
+
+    if let Some(amount) = filters.amount {
+        builder.push(" AND p.\"amount\" = ").push_bind(amount);
+    }
+
+    if let Some(status) = filters.status.as_ref() {
+        builder
+            .push(" AND p.\"status\" = ")
+            .push_bind(status.as_str());
+        builder.push("::\"PayoutStatus\"");
+    }
+}
+
+pub(crate) fn apply_payout_sort(builder: &mut QueryBuilder<Postgres>, filters: &PayoutListFilters) {
+    match filters.sort {
+        SortField::Status => {
+            builder.push(" ORDER BY p.\"status\" ");
+            match filters.order {
+                SortOrder::Asc => {
+                    builder.push("ASC");
+                }
+                SortOrder::Desc => {
+                    builder.push("DESC");
+                }
+            }
+            builder.push(", p.\"createdAt\" DESC");
+        }
+        SortField::CreatedAt => {
+            builder.push(" ORDER BY p.\"createdAt\" ");
+            match filters.order {
+                SortOrder::Asc => {
+                    builder.push("ASC");
+                }
+                SortOrder::Desc => {
+                    builder.push("DESC");
+                }
+            }
+        }
+    }
+}
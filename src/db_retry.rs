@@ -0,0 +1,83 @@
+//! Retries for database work that failed on a blip. Serialization failures,
+//! deadlocks, dropped connections and pool timeouts are transient: the same
+//! statement usually succeeds a moment later, so [`with_retry`] runs the
+//! operation again after a short, jittered backoff instead of failing the
+//! whole distribution cycle or losing a callback log. Everything else
+//! (constraint violations, bad SQL, decode errors) is returned right away.
+//!
+//! `DB_RETRY_ATTEMPTS` sets how often an operation runs in total (default 3,
+//! `1` disables retries).
+
+use std::{env, future::Future, time::Duration};
+
+use anyhow::Result;
+use uuid::Uuid;
+
+/// The first retry waits up to this long, each further one twice as long.
+const BASE_DELAY_MS: u64 = 50;
+const MAX_DELAY_MS: u64 = 2000;
+
+fn max_attempts() -> u32 {
+    env::var("DB_RETRY_ATTEMPTS")
+        .ok()
+        .and_then(|value| value.trim().parse::<u32>().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(3)
+}
+
+/// Full jitter: a random delay up to the exponential cap, so workers that
+/// failed together do not retry in lockstep.
+fn backoff(attempt: u32) -> Duration {
+    let cap = (BASE_DELAY_MS << attempt.clamp(1, 10).saturating_sub(1)).min(MAX_DELAY_MS);
+    Duration::from_millis(Uuid::new_v4().as_u128() as u64 % (cap + 1))
+}
+
+/// Whether an error is worth retrying. Looks through the whole `anyhow`
+/// chain, so errors wrapped with `.context(..)` are classified too.
+pub(crate) fn is_transient(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<sqlx::Error>())
+        .any(is_transient_sqlx)
+}
+
+fn is_transient_sqlx(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut | sqlx::Error::WorkerCrashed => true,
+        sqlx::Error::Database(db) => db.code().is_some_and(|code| {
+            // serialization_failure, deadlock_detected, lock_not_available,
+            // admin/crash shutdown, cannot_connect_now and connection exceptions
+            matches!(
+                code.as_ref(),
+                "40001" | "40P01" | "55P03" | "57P01" | "57P02" | "57P03"
+            ) || code.starts_with("08")
+        }),
+        _ => false,
+    }
+}
+
+/// Runs `operation` until it succeeds, fails permanently or runs out of
+/// attempts. The operation must be safe to repeat: open a fresh transaction
+/// inside it rather than retrying statements of one that already failed.
+pub(crate) async fn with_retry<T, F, Fut>(label: &str, mut operation: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let attempts = max_attempts();
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt < attempts && is_transient(&err) => {
+                let delay = backoff(attempt);
+                eprintln!(
+                    "[db] {label} failed on attempt {attempt}/{attempts}, retrying in {} ms: {err:#}",
+                    delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
@@ -0,0 +1,648 @@
+//! Daily digest. Once a day at the configured local time the service sums up
+//! the previous 24 hours (payout volumes, assignments per trader, cancellations
+//! and failures) and pushes it through the notification channels: email to the
+//! alert recipients (see `email_alerts`) and Telegram (see `telegram`).
+//!
+//! The schedule is kept in `ReportSchedule` and managed through
+//! `GET`/`POST /api/reports/schedule`; `POST /api/reports/schedule/test` sends
+//! the digest for the last 24 hours right away. At most one digest goes out
+//! per local day, also with several instances running, and a schedule saved
+//! after today's time has passed starts tomorrow.
+
+use std::time::Duration as StdDuration;
+
+use anyhow::{Context, Result};
+use axum::{
+    Json,
+    extract::{Extension, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgExecutor, PgPool};
+use tokio::time::{self, MissedTickBehavior};
+use tower_sessions::Session;
+
+use crate::{
+    ApiResult, AppState, auth,
+    errors::ApiError,
+    feature_flags::Feature,
+    internal_error,
+    reports::{self, ShiftReport},
+    settings::{self, parse_window_time},
+    tenant::TenantScope,
+};
+
+const SCHEDULE_ID: &str = "daily-digest";
+const CHECK_INTERVAL: StdDuration = StdDuration::from_secs(60);
+const DIGEST_STUCK_MINUTES: u32 = 30;
+/// Traders listed by name; the rest are summed up in one line.
+const DIGEST_TRADER_LINES: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DigestChannel {
+    Email,
+    Telegram,
+}
+
+impl DigestChannel {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Email => "email",
+            Self::Telegram => "telegram",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "email" => Some(Self::Email),
+            "telegram" => Some(Self::Telegram),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DigestSchedule {
+    enabled: bool,
+    /// Local time of day, `HH:MM`.
+    time: String,
+    timezone: String,
+    channels: Vec<DigestChannel>,
+    /// Local date of the last digest sent by the schedule.
+    last_sent_on: Option<NaiveDate>,
+}
+
+impl Default for DigestSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            time: "09:00".to_string(),
+            timezone: "UTC".to_string(),
+            channels: Vec::new(),
+            last_sent_on: None,
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct ScheduleRow {
+    enabled: bool,
+    #[sqlx(rename = "sendAt")]
+    send_at: String,
+    timezone: String,
+    channels: Vec<String>,
+    #[sqlx(rename = "lastSentOn")]
+    last_sent_on: Option<NaiveDate>,
+}
+
+impl DigestSchedule {
+    fn tz(&self) -> Tz {
+        self.timezone.parse().unwrap_or(Tz::UTC)
+    }
+
+    fn send_time(&self) -> NaiveTime {
+        parse_window_time(&self.time).unwrap_or_default()
+    }
+
+    /// When the digest for local `date` is due. Falls back to the earlier
+    /// instant around DST changes.
+    fn scheduled_at(&self, date: NaiveDate) -> Option<DateTime<Utc>> {
+        self.tz()
+            .from_local_datetime(&date.and_time(self.send_time()))
+            .earliest()
+            .map(|at| at.with_timezone(&Utc))
+    }
+
+    /// The local date whose digest is due at `now` and not sent yet.
+    fn due_date(&self, now: DateTime<Utc>) -> Option<NaiveDate> {
+        if !self.enabled {
+            return None;
+        }
+        let local = now.with_timezone(&self.tz());
+        let today = local.date_naive();
+        let sent = self.last_sent_on.is_some_and(|sent| sent >= today);
+        (!sent && local.time() >= self.send_time()).then_some(today)
+    }
+
+    fn next_send_at(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if !self.enabled {
+            return None;
+        }
+        let today = now.with_timezone(&self.tz()).date_naive();
+        if self.last_sent_on.is_some_and(|sent| sent >= today) {
+            self.scheduled_at(today.succ_opt()?)
+        } else {
+            self.scheduled_at(today)
+        }
+    }
+}
+
+pub(crate) async fn load_schedule(pool: &PgPool) -> Result<DigestSchedule> {
+    let row = sqlx::query_as::<_, ScheduleRow>(
+        r#"
+        SELECT "enabled", "sendAt", "timezone", "channels", "lastSentOn"
+        FROM "ReportSchedule"
+        WHERE "id" = $1
+        "#,
+    )
+    .bind(SCHEDULE_ID)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to load the report schedule")?;
+    Ok(
+        row.map_or_else(DigestSchedule::default, |row| DigestSchedule {
+            enabled: row.enabled,
+            time: row.send_at,
+            timezone: row.timezone,
+            channels: row
+                .channels
+                .iter()
+                .filter_map(|channel| DigestChannel::parse(channel))
+                .collect(),
+            last_sent_on: row.last_sent_on,
+        }),
+    )
+}
+
+fn available_channels(state: &AppState) -> Vec<DigestChannel> {
+    let mut channels = Vec::new();
+    if state.email.is_some() {
+        channels.push(DigestChannel::Email);
+    }
+    if state.telegram.is_some() {
+        channels.push(DigestChannel::Telegram);
+    }
+    channels
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ScheduleResponse {
+    #[serde(flatten)]
+    schedule: DigestSchedule,
+    next_send_at: Option<DateTime<Utc>>,
+    /// Channels configured on this instance.
+    available_channels: Vec<DigestChannel>,
+}
+
+impl ScheduleResponse {
+    fn new(state: &AppState, schedule: DigestSchedule) -> Self {
+        Self {
+            next_send_at: schedule.next_send_at(Utc::now()),
+            available_channels: available_channels(state),
+            schedule,
+        }
+    }
+}
+
+/// Also the `reportSchedule` section of a configuration snapshot.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UpdateScheduleRequest {
+    enabled: Option<bool>,
+    time: Option<String>,
+    timezone: Option<String>,
+    channels: Option<Vec<DigestChannel>>,
+}
+
+impl DigestSchedule {
+    /// The request that sets this schedule again.
+    pub(crate) fn to_request(&self) -> UpdateScheduleRequest {
+        UpdateScheduleRequest {
+            enabled: Some(self.enabled),
+            time: Some(self.time.clone()),
+            timezone: Some(self.timezone.clone()),
+            channels: Some(self.channels.clone()),
+        }
+    }
+
+    /// One-line summary for the logs.
+    pub(crate) fn describe(&self) -> String {
+        if !self.enabled {
+            return "Daily digest disabled".to_string();
+        }
+        let channels: Vec<&str> = self
+            .channels
+            .iter()
+            .map(|channel| channel.as_str())
+            .collect();
+        format!(
+            "Daily digest at {} {} via {}",
+            self.time,
+            self.timezone,
+            channels.join(", ")
+        )
+    }
+}
+
+pub(crate) async fn get_report_schedule(
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<ScheduleResponse>> {
+    scope.require_unrestricted()?;
+    let schedule = load_schedule(&state.pool).await.map_err(internal_error)?;
+    Ok(Json(ScheduleResponse::new(&state, schedule)))
+}
+
+/// `before` with the request applied. Fields left out keep their current
+/// value.
+pub(crate) fn updated_schedule(
+    state: &AppState,
+    before: &DigestSchedule,
+    request: UpdateScheduleRequest,
+) -> ApiResult<DigestSchedule> {
+    let mut after = before.clone();
+    if let Some(enabled) = request.enabled {
+        after.enabled = enabled;
+    }
+    if let Some(time) = request.time {
+        after.time = parse_window_time(&time)
+            .map_err(|err| (StatusCode::BAD_REQUEST, err))?
+            .format("%H:%M")
+            .to_string();
+    }
+    if let Some(timezone) = request.timezone {
+        let timezone = timezone.trim();
+        after.timezone = timezone
+            .parse::<Tz>()
+            .map_err(|_| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Unknown timezone {timezone}"),
+                )
+            })?
+            .name()
+            .to_string();
+    }
+    if let Some(channels) = request.channels {
+        after.channels.clear();
+        for channel in channels {
+            if !after.channels.contains(&channel) {
+                after.channels.push(channel);
+            }
+        }
+    }
+    if after.enabled {
+        if after.channels.is_empty() {
+            return Err(ApiError::from((
+                StatusCode::BAD_REQUEST,
+                "Pick at least one channel for the digest".to_string(),
+            )));
+        }
+        let available = available_channels(state);
+        if let Some(channel) = after
+            .channels
+            .iter()
+            .find(|channel| !available.contains(channel))
+        {
+            return Err(ApiError::from((
+                StatusCode::BAD_REQUEST,
+                format!("The {} channel is not configured", channel.as_str()),
+            )));
+        }
+    }
+    // Today's time already passed: the first digest goes out tomorrow.
+    if let Some(today) = after.due_date(Utc::now()) {
+        after.last_sent_on = Some(today);
+    }
+    Ok(after)
+}
+
+pub(crate) async fn store_schedule<'e>(
+    executor: impl PgExecutor<'e>,
+    schedule: &DigestSchedule,
+) -> sqlx::Result<()> {
+    let channels: Vec<&str> = schedule
+        .channels
+        .iter()
+        .map(|channel| channel.as_str())
+        .collect();
+    sqlx::query(
+        r#"
+        INSERT INTO "ReportSchedule" ("id", "enabled", "sendAt", "timezone", "channels", "lastSentOn")
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT ("id") DO UPDATE
+        SET "enabled" = EXCLUDED."enabled",
+            "sendAt" = EXCLUDED."sendAt",
+            "timezone" = EXCLUDED."timezone",
+            "channels" = EXCLUDED."channels",
+            "lastSentOn" = EXCLUDED."lastSentOn",
+            "updatedAt" = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(SCHEDULE_ID)
+    .bind(schedule.enabled)
+    .bind(&schedule.time)
+    .bind(&schedule.timezone)
+    .bind(&channels)
+    .bind(schedule.last_sent_on)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+pub(crate) async fn update_report_schedule(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<UpdateScheduleRequest>,
+) -> ApiResult<Json<ScheduleResponse>> {
+    scope.require_unrestricted()?;
+    let before = load_schedule(&state.pool).await.map_err(internal_error)?;
+    let after = updated_schedule(&state, &before, request)?;
+    store_schedule(&state.pool, &after)
+        .await
+        .map_err(internal_error)?;
+
+    let actor = auth::audit_actor(&session, &scope).await?;
+    settings::audit_change(
+        &state.pool,
+        "report-schedule",
+        None,
+        actor.as_deref(),
+        &before,
+        &after,
+    )
+    .await;
+    println!("[digest] {}", after.describe());
+    Ok(Json(ScheduleResponse::new(&state, after)))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TestDigestResponse {
+    channels: Vec<DigestChannel>,
+}
+
+/// Sends the digest for the last 24 hours to the schedule's channels, also
+/// while the schedule is disabled.
+pub(crate) async fn send_test_digest(
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<TestDigestResponse>> {
+    scope.require_unrestricted()?;
+    let schedule = load_schedule(&state.pool).await.map_err(internal_error)?;
+    if schedule.channels.is_empty() {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "The schedule has no channels".to_string(),
+        )));
+    }
+    let to = Utc::now();
+    let digest = compose_digest(&state.pool, to - Duration::hours(24), to, schedule.tz())
+        .await
+        .map_err(internal_error)?;
+    let errors = deliver(&state, &schedule.channels, &digest).await;
+    if !errors.is_empty() {
+        return Err(ApiError::from((StatusCode::BAD_GATEWAY, errors.join("; "))));
+    }
+    Ok(Json(TestDigestResponse {
+        channels: schedule.channels,
+    }))
+}
+
+struct Digest {
+    subject: String,
+    body: String,
+}
+
+#[derive(Debug, Default, FromRow)]
+struct DigestVolumes {
+    created: i64,
+    #[sqlx(rename = "createdAmount")]
+    created_amount: f64,
+    completed: i64,
+    #[sqlx(rename = "completedAmount")]
+    completed_amount: f64,
+}
+
+async fn compose_digest(
+    pool: &PgPool,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    tz: Tz,
+) -> Result<Digest> {
+    let (from_utc, to_utc) = (from.naive_utc(), to.naive_utc());
+    let report =
+        reports::build_shift_report(pool, from_utc, to_utc, DIGEST_STUCK_MINUTES, None).await?;
+    let volumes = sqlx::query_as::<_, DigestVolumes>(
+        r#"
+        SELECT
+            COUNT(*) FILTER (WHERE "createdAt" >= $1 AND "createdAt" < $2)::bigint AS "created",
+            COALESCE(SUM("amount") FILTER (WHERE "createdAt" >= $1 AND "createdAt" < $2), 0)::double precision
+                AS "createdAmount",
+            COUNT(*) FILTER (
+                WHERE "status" IN ('COMPLETED', 'SUCCESS') AND "updatedAt" >= $1 AND "updatedAt" < $2
+            )::bigint AS "completed",
+            COALESCE(SUM("amount") FILTER (
+                WHERE "status" IN ('COMPLETED', 'SUCCESS') AND "updatedAt" >= $1 AND "updatedAt" < $2
+            ), 0)::double precision AS "completedAmount"
+        FROM "Payout"
+        WHERE ("createdAt" >= $1 AND "createdAt" < $2)
+           OR ("updatedAt" >= $1 AND "updatedAt" < $2)
+        "#,
+    )
+    .bind(from_utc)
+    .bind(to_utc)
+    .fetch_one(pool)
+    .await
+    .context("Failed to load payout volumes")?;
+    let failed_runs: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*)::bigint
+        FROM "DistributionRun"
+        WHERE "error" IS NOT NULL
+          AND "startedAt" >= $1
+          AND "startedAt" < $2
+        "#,
+    )
+    .bind(from_utc)
+    .bind(to_utc)
+    .fetch_one(pool)
+    .await
+    .context("Failed to count failed distribution runs")?;
+
+    let local_from = from.with_timezone(&tz);
+    let local_to = to.with_timezone(&tz);
+    Ok(Digest {
+        subject: format!("Daily digest for {}", local_to.format("%Y-%m-%d")),
+        body: digest_body(
+            &format!(
+                "{} - {} ({})",
+                local_from.format("%Y-%m-%d %H:%M"),
+                local_to.format("%Y-%m-%d %H:%M"),
+                tz.name()
+            ),
+            &volumes,
+            &report,
+            failed_runs,
+        ),
+    })
+}
+
+fn digest_body(
+    period: &str,
+    volumes: &DigestVolumes,
+    report: &ShiftReport,
+    failed_runs: i64,
+) -> String {
+    let assignments = &report.assignments;
+    let cancellations = &report.cancellations;
+    let mut lines = vec![
+        format!("Payouts {period}"),
+        String::new(),
+        "Volumes".to_string(),
+        format!(
+            "  Created: {} for {:.2}",
+            volumes.created, volumes.created_amount
+        ),
+        format!(
+            "  Completed: {} for {:.2}",
+            volumes.completed, volumes.completed_amount
+        ),
+        format!(
+            "  Cancelled: {} for {:.2}",
+            cancellations.total, cancellations.amount
+        ),
+        String::new(),
+        format!(
+            "Distribution: {} assigned for {:.2}, {} automatically, {} by hand",
+            assignments.total,
+            assignments.amount,
+            assignments.automatic,
+            assignments.total - assignments.automatic
+        ),
+    ];
+    for trader in assignments.traders.iter().take(DIGEST_TRADER_LINES) {
+        lines.push(format!(
+            "  {}: {} for {:.2}",
+            trader
+                .email
+                .as_deref()
+                .or(trader.trader_id.as_deref())
+                .unwrap_or("unknown trader"),
+            trader.count,
+            trader.amount
+        ));
+    }
+    if let Some(rest) = assignments.traders.get(DIGEST_TRADER_LINES..) {
+        lines.push(format!(
+            "  {} more trader(s): {}",
+            rest.len(),
+            rest.iter().map(|trader| trader.count).sum::<i64>()
+        ));
+    }
+    lines.push(String::new());
+    lines.push("Failures".to_string());
+    lines.push(format!(
+        "  Failed callbacks: {}",
+        report.failed_callbacks.total
+    ));
+    lines.push(format!("  Failed distribution runs: {failed_runs}"));
+    for reason in &cancellations.reasons {
+        lines.push(format!(
+            "  Cancelled with {}: {}",
+            reason
+                .label
+                .as_deref()
+                .or(reason.code.as_deref())
+                .unwrap_or("no reason"),
+            reason.count
+        ));
+    }
+    lines.push(format!("  Open disputes: {}", report.disputes.total));
+    lines.push(format!(
+        "  Waiting for acceptance over {} min: {}",
+        report.stuck_minutes, report.stuck.total
+    ));
+    lines.join("\n")
+}
+
+/// Sends the digest to every channel and returns the errors.
+async fn deliver(state: &AppState, channels: &[DigestChannel], digest: &Digest) -> Vec<String> {
+    let mut errors = Vec::new();
+    for channel in channels {
+        let result = match channel {
+            DigestChannel::Email => match state.email.as_ref() {
+                Some(email) => email
+                    .send(&state.pool, &digest.subject, &digest.body)
+                    .await
+                    .map(|_| ()),
+                None => Err(anyhow::anyhow!("SMTP is not configured")),
+            },
+            DigestChannel::Telegram => match state.telegram.as_ref() {
+                Some(telegram) => {
+                    telegram
+                        .send(
+                            &state.http_client,
+                            &format!("{}\n\n{}", digest.subject, digest.body),
+                        )
+                        .await
+                }
+                None => Err(anyhow::anyhow!("Telegram is not configured")),
+            },
+        };
+        if let Err(err) = result {
+            errors.push(format!("{}: {err:#}", channel.as_str()));
+        }
+    }
+    errors
+}
+
+pub(crate) async fn digest_worker(state: AppState) {
+    let mut interval = time::interval(CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = send_if_due(&state).await {
+            eprintln!("[digest] {err:#}");
+        }
+    }
+}
+
+async fn send_if_due(state: &AppState) -> Result<()> {
+    let schedule = load_schedule(&state.pool).await?;
+    let now = Utc::now();
+    let Some(date) = schedule.due_date(now) else {
+        return Ok(());
+    };
+    // Left unclaimed, so switching the flag back on still sends today's.
+    if !state.feature_flags.is_enabled(Feature::DailyDigest).await {
+        return Ok(());
+    }
+    // Claims the day first, so another instance does not send it again.
+    let claimed = sqlx::query(
+        r#"
+        UPDATE "ReportSchedule"
+        SET "lastSentOn" = $2
+        WHERE "id" = $1
+          AND "enabled"
+          AND ("lastSentOn" IS NULL OR "lastSentOn" < $2)
+        "#,
+    )
+    .bind(SCHEDULE_ID)
+    .bind(date)
+    .execute(&state.pool)
+    .await
+    .context("Failed to claim the daily digest")?;
+    if claimed.rows_affected() == 0 {
+        return Ok(());
+    }
+
+    let to = schedule.scheduled_at(date).unwrap_or(now);
+    let digest = compose_digest(&state.pool, to - Duration::hours(24), to, schedule.tz()).await?;
+    let errors = deliver(state, &schedule.channels, &digest).await;
+    if errors.is_empty() {
+        println!("[digest] Sent the daily digest for {date}");
+    } else {
+        for error in errors {
+            eprintln!("[digest] {error}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,114 @@
+//! Outbound limits for the webhooks the outbox relay sends. At most
+//! `CALLBACK_CONCURRENCY` (default 8) merchant callbacks and trader webhooks
+//! are in flight at once. Merchant callbacks are also rate limited per
+//! merchant with a token bucket: `CALLBACK_RATE_PER_SECOND` (default 5, `0`
+//! disables the limit) refilled up to `CALLBACK_RATE_BURST` tokens (default
+//! 10). A callback over its merchant's rate is not sent; it stays in the
+//! outbox until a token is free.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::env::non_empty_env;
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+#[derive(Clone)]
+pub(crate) struct CallbackDispatcher {
+    concurrency: usize,
+    rate_per_second: f64,
+    burst: f64,
+    permits: Arc<Semaphore>,
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+}
+
+impl CallbackDispatcher {
+    pub(crate) fn from_env() -> Result<Self> {
+        let concurrency = match non_empty_env("CALLBACK_CONCURRENCY") {
+            Some(value) => value
+                .parse::<usize>()
+                .ok()
+                .filter(|value| *value > 0)
+                .context("CALLBACK_CONCURRENCY must be a positive integer")?,
+            None => 8,
+        };
+        let rate_per_second = number_env("CALLBACK_RATE_PER_SECOND", 5.0)?;
+        let burst = number_env("CALLBACK_RATE_BURST", 10.0)?.max(1.0);
+        Ok(Self {
+            concurrency,
+            rate_per_second,
+            burst,
+            permits: Arc::new(Semaphore::new(concurrency)),
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        let rate = if self.rate_per_second > 0.0 {
+            format!(
+                "{}/s per merchant, bursts of {}",
+                self.rate_per_second, self.burst
+            )
+        } else {
+            "no per-merchant rate limit".to_string()
+        };
+        format!("{} concurrent request(s), {rate}", self.concurrency)
+    }
+
+    /// Waits for a free delivery slot; the request runs while the permit is
+    /// held.
+    pub(crate) async fn acquire(&self) -> OwnedSemaphorePermit {
+        Arc::clone(&self.permits)
+            .acquire_owned()
+            .await
+            .expect("dispatcher semaphore is never closed")
+    }
+
+    /// Takes a token from the merchant's bucket, or returns how long until
+    /// the next one.
+    pub(crate) fn take_token(&self, merchant_id: &str) -> Result<(), Duration> {
+        if self.rate_per_second <= 0.0 {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|err| err.into_inner());
+        let bucket = buckets
+            .entry(merchant_id.to_string())
+            .or_insert_with(|| Bucket {
+                tokens: self.burst,
+                refilled_at: now,
+            });
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate_per_second).min(self.burst);
+        bucket.refilled_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.rate_per_second,
+            ))
+        }
+    }
+}
+
+fn number_env(name: &str, default: f64) -> Result<f64> {
+    match non_empty_env(name) {
+        Some(value) => value
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite() && *value >= 0.0)
+            .with_context(|| format!("{name} must be a non-negative number")),
+        None => Ok(default),
+    }
+}
@@ -0,0 +1,1400 @@
+//! Payout distribution. [`Distributor`] owns the rotation and the worker
+//! status, runs the automatic cycles and serves manual runs, simulations and
+//! single assignments.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use anyhow::{Context, Result};
+use axum::{
+    Extension, Json,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+use tokio::{
+    sync::{Mutex, Notify, RwLock},
+    task::JoinHandle,
+    time,
+};
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::{
+    ApiResult, AppState,
+    api::{ensure_trader_in_scope, fetch_updated_payout},
+    auth, bank_routing,
+    currencies::{self, AmountLimits, PAYOUT_CURRENCY_SQL, TraderCurrencies},
+    db::{
+        Pagination, PayoutDealListItem, TraderRecord, UNASSIGNED_PAYOUTS_QUERY, UnassignedPayout,
+        claim_unassigned_payouts, fetch_capacity_overrides, fetch_open_payouts,
+        fetch_trader_cooldowns, fetch_traders, record_payout_audit,
+    },
+    db_retry, duplicates,
+    errors::{ApiError, ErrorCode},
+    events::{ServerEvent, WorkerStatus},
+    feature_flags::{Feature, FeatureFlags},
+    freeze, internal_error, outbox, payout_version,
+    settings::{AutoDistributionConfig, MAX_INTERVAL_SECONDS, SettingsService, audit_change},
+    teams,
+    tenant::TenantScope,
+    timestamps::UtcTimestamp,
+    trader_webhook,
+};
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DistributionRun {
+    id: String,
+    source: String,
+    #[sqlx(rename = "startedAt")]
+    started_at: UtcTimestamp,
+    #[sqlx(rename = "finishedAt")]
+    finished_at: UtcTimestamp,
+    #[sqlx(rename = "durationMs")]
+    duration_ms: i32,
+    claimed: i32,
+    assigned: i32,
+    skipped: i32,
+    remaining: i32,
+    note: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DistributionRunListResponse {
+    items: Vec<DistributionRun>,
+    pagination: Pagination,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DistributionRunListQuery {
+    source: Option<String>,
+    page: Option<u32>,
+    per_page: Option<u32>,
+}
+
+/// What a single distribution cycle did. `skipped` counts claimed payouts
+/// that were left unassigned (no suitable trader or changed concurrently).
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CycleOutcome {
+    claimed: usize,
+    assigned: usize,
+    skipped: usize,
+    remaining: usize,
+    note: Option<String>,
+}
+
+impl CycleOutcome {
+    fn with_note(note: &str) -> Self {
+        Self {
+            note: Some(note.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// A cycle stopped before writing anything; what it claimed stays queued.
+    fn cancelled(claimed: usize) -> Self {
+        Self {
+            claimed,
+            remaining: claimed,
+            ..Self::with_note("cancelled")
+        }
+    }
+}
+
+/// How often the assignment loop checks for cancellation, in payouts.
+const CANCEL_CHECK_BATCH: usize = 50;
+
+/// Payouts locked per cycle when `maxPayoutsPerCycle` is not set.
+const CLAIM_BATCH: u32 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum DistributionStrategy {
+    RoundRobin,
+    Weighted,
+    LeastLoaded,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SimulateDistributionRequest {
+    strategy: DistributionStrategy,
+    /// Hypothetical per-trader max amounts. Traders not listed keep their
+    /// current limit; a `null` value removes the max. Min amounts stay.
+    #[serde(default)]
+    limits: HashMap<String, Option<f64>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SimulatedTraderAllocation {
+    trader_id: String,
+    email: String,
+    numeric_id: i32,
+    min_amount: Option<f64>,
+    max_amount: Option<f64>,
+    open_payouts: i64,
+    payout_count: usize,
+    payout_amount: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SimulateDistributionResponse {
+    strategy: DistributionStrategy,
+    queue_count: usize,
+    queue_amount: f64,
+    assigned_count: usize,
+    assigned_amount: f64,
+    unassigned_count: usize,
+    unassigned_amount: f64,
+    traders: Vec<SimulatedTraderAllocation>,
+}
+
+pub(crate) async fn get_distribution_runs(
+    Query(params): Query<DistributionRunListQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<DistributionRunListResponse>> {
+    scope.require_unrestricted()?;
+    let page = params.page.unwrap_or(1).max(1);
+    let per_page = params.per_page.unwrap_or(25).clamp(1, 200);
+    let source = params
+        .source
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty());
+
+    let total: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*)::bigint
+        FROM "DistributionRun"
+        WHERE ($1::text IS NULL OR "source" = $1)
+        "#,
+    )
+    .bind(&source)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let items = sqlx::query_as::<_, DistributionRun>(
+        r#"
+        SELECT *
+        FROM "DistributionRun"
+        WHERE ($1::text IS NULL OR "source" = $1)
+        ORDER BY "startedAt" DESC
+        LIMIT $2 OFFSET $3
+        "#,
+    )
+    .bind(&source)
+    .bind(per_page as i64)
+    .bind(((page - 1) as i64) * per_page as i64)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    Ok(Json(DistributionRunListResponse {
+        items,
+        pagination: Pagination::new(total, page, per_page),
+    }))
+}
+
+pub(crate) async fn run_distribution_now(
+    State(distributor): State<Distributor>,
+    State(settings): State<SettingsService>,
+    scope: TenantScope,
+) -> ApiResult<Json<CycleOutcome>> {
+    scope.require_unrestricted()?;
+    let config = settings.auto_config();
+    println!("[manual] Distribution cycle requested from the dashboard");
+    distributor
+        .run_cycle(&config, "manual")
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum WorkerState {
+    /// Distributing on its interval.
+    Running,
+    /// Alive but switched off or outside the distribution windows.
+    Paused,
+    /// The task is gone; only a restart brings it back.
+    Stopped,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WorkerView {
+    state: WorkerState,
+    enabled: bool,
+    in_schedule: bool,
+    started_at: Option<DateTime<Utc>>,
+    restarts: u32,
+    /// Set while a cycle runs; a cycle running for long means it is wedged.
+    cycle_started_at: Option<DateTime<Utc>>,
+    last_cycle_at: Option<DateTime<Utc>>,
+    next_tick_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    last_error_at: Option<DateTime<Utc>>,
+}
+
+impl Distributor {
+    async fn worker_view(&self) -> WorkerView {
+        let config = self.settings.auto_config();
+        let status = self.worker_status().await;
+        let state = if !self.worker_alive().await {
+            WorkerState::Stopped
+        } else if config.enabled && status.in_schedule {
+            WorkerState::Running
+        } else {
+            WorkerState::Paused
+        };
+        WorkerView {
+            state,
+            enabled: config.enabled,
+            in_schedule: status.in_schedule,
+            started_at: status.started_at,
+            restarts: status.restarts,
+            cycle_started_at: status.cycle_started_at,
+            last_cycle_at: status.last_cycle_at,
+            next_tick_at: status.next_tick_at.filter(|_| config.enabled),
+            last_error: status.last_error,
+            last_error_at: status.last_error_at,
+        }
+    }
+}
+
+pub(crate) async fn get_distribution_worker(
+    State(distributor): State<Distributor>,
+    scope: TenantScope,
+) -> ApiResult<Json<WorkerView>> {
+    scope.require_unrestricted()?;
+    Ok(Json(distributor.worker_view().await))
+}
+
+/// Recovers a wedged worker without restarting the service. A cycle in
+/// progress is aborted and rolled back; the new worker ticks right away.
+pub(crate) async fn restart_distribution_worker(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+) -> ApiResult<Json<WorkerView>> {
+    scope.require_unrestricted()?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let distributor = &state.distributor;
+    let before = distributor.worker_view().await;
+    distributor.start_worker().await;
+    let after = distributor.worker_view().await;
+    println!(
+        "[auto] Worker restarted by {} (was {:?})",
+        actor.as_deref().unwrap_or("an operator"),
+        before.state
+    );
+    audit_change(
+        &state.pool,
+        "distribution-worker",
+        None,
+        actor.as_deref(),
+        &before,
+        &after,
+    )
+    .await;
+    Ok(Json(after))
+}
+
+/// Stops the cycles in progress, automatic and manual, at their next check.
+/// They roll back and leave their payouts queued; the worker keeps its
+/// schedule, so switch auto distribution off to stop it for good.
+pub(crate) async fn stop_distribution_cycle(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+) -> ApiResult<Json<WorkerView>> {
+    scope.require_unrestricted()?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let distributor = &state.distributor;
+    let before = distributor.worker_view().await;
+    distributor.cancel_cycles();
+    let after = distributor.worker_view().await;
+    println!(
+        "[auto] Cycle stop requested by {}",
+        actor.as_deref().unwrap_or("an operator")
+    );
+    audit_change(
+        &state.pool,
+        "distribution-cycle",
+        None,
+        actor.as_deref(),
+        &before,
+        &after,
+    )
+    .await;
+    Ok(Json(after))
+}
+
+pub(crate) async fn simulate_distribution(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Json(payload): Json<SimulateDistributionRequest>,
+) -> ApiResult<Json<SimulateDistributionResponse>> {
+    scope.require_unrestricted()?;
+    for (trader_id, limit) in &payload.limits {
+        if limit.is_some_and(|value| !value.is_finite() || value <= 0.0) {
+            return Err(ApiError::from((
+                StatusCode::BAD_REQUEST,
+                format!("Limit for trader {trader_id} must be a positive number"),
+            )));
+        }
+    }
+
+    let policy = state.settings.priority_policy().await;
+    let teams = teams::Teams::load(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    let traders = fetch_traders(&state.pool)
+        .await
+        .map_err(internal_error)?
+        .into_iter()
+        .filter(|trader| !teams.is_disabled(&trader.id))
+        .collect::<Vec<_>>();
+    let payouts = sqlx::query_as::<_, UnassignedPayout>(&UNASSIGNED_PAYOUTS_QUERY)
+        .bind(policy.amount_threshold)
+        .bind(&policy.merchant_ids)
+        .bind(policy.max_age_minutes_i32())
+        .bind(None::<Vec<String>>)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(internal_error)?
+        .into_iter()
+        .filter(|payout| !payout.duplicate)
+        .collect::<Vec<_>>();
+    let open_payouts = fetch_open_payouts(&state.pool)
+        .await
+        .map_err(internal_error)?;
+
+    let mut limits = state.settings.limits().await;
+    for (trader_id, limit) in payload.limits {
+        limits.entry(trader_id).or_default().max_amount = limit;
+    }
+    let currencies = TraderCurrencies::load(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    let bank_weights = bank_routing::BankWeights::load(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    let rotation = state.distributor.rotation().await;
+
+    Ok(Json(simulate_allocation(
+        payload.strategy,
+        &payouts,
+        &traders,
+        &AmountLimits {
+            rub: &limits,
+            currencies: &currencies,
+        },
+        &open_payouts,
+        &bank_weights,
+        rotation,
+    )))
+}
+
+/// Replays the queue in one pass without writing anything. Per-cycle caps and
+/// team quotas are ignored so strategies are compared on the whole backlog;
+/// payouts of banks with routing weights follow the weights whatever the
+/// strategy.
+pub(crate) fn simulate_allocation(
+    strategy: DistributionStrategy,
+    payouts: &[UnassignedPayout],
+    traders: &[TraderRecord],
+    limits: &AmountLimits,
+    open_payouts: &HashMap<String, i64>,
+    bank_weights: &bank_routing::BankWeights,
+    mut rotation: bank_routing::RotationState,
+) -> SimulateDistributionResponse {
+    let mut allocations: Vec<SimulatedTraderAllocation> = traders
+        .iter()
+        .map(|trader| {
+            let limit = limits.rub.get(&trader.id).copied().unwrap_or_default();
+            SimulatedTraderAllocation {
+                trader_id: trader.id.clone(),
+                email: trader.email.clone(),
+                numeric_id: trader.numeric_id,
+                min_amount: limit.min_amount,
+                max_amount: limit.max_amount,
+                open_payouts: open_payouts.get(&trader.id).copied().unwrap_or_default(),
+                payout_count: 0,
+                payout_amount: 0.0,
+            }
+        })
+        .collect();
+    // Weighted strategy shares the queue in proportion to spendable balance.
+    let weights: Vec<f64> = traders
+        .iter()
+        .map(|trader| {
+            (trader.balance_rub.unwrap_or_default() - trader.frozen_rub.unwrap_or_default())
+                .max(0.0)
+        })
+        .collect();
+
+    let mut queue_amount = 0.0;
+    let mut unassigned_count = 0;
+    let mut unassigned_amount = 0.0;
+
+    for payout in payouts {
+        let amount = payout.amount.unwrap_or_default();
+        queue_amount += amount;
+
+        let accepts =
+            |idx: usize| amount > 0.0 && limits.allows(&traders[idx].id, &payout.currency, amount);
+
+        let weighted_bank = payout
+            .bank
+            .as_deref()
+            .filter(|bank| bank_weights.for_bank(Some(bank)).is_some());
+        let selected = match (weighted_bank, strategy) {
+            (Some(bank), _) => {
+                let candidates = (0..traders.len())
+                    .filter(|&idx| accepts(idx))
+                    .map(|idx| traders[idx].id.as_str());
+                bank_weights
+                    .pick(&mut rotation, bank, candidates)
+                    .and_then(|trader_id| traders.iter().position(|trader| trader.id == trader_id))
+            }
+            (None, DistributionStrategy::RoundRobin) => (0..traders.len())
+                .map(|offset| (rotation.next_index + offset) % traders.len())
+                .find(|&idx| accepts(idx)),
+            (None, DistributionStrategy::Weighted) => (0..traders.len())
+                .filter(|&idx| accepts(idx) && weights[idx] > 0.0)
+                .min_by(|&a, &b| {
+                    let share_a = (allocations[a].payout_amount + amount) / weights[a];
+                    let share_b = (allocations[b].payout_amount + amount) / weights[b];
+                    share_a.total_cmp(&share_b)
+                }),
+            (None, DistributionStrategy::LeastLoaded) => (0..traders.len())
+                .filter(|&idx| accepts(idx))
+                .min_by_key(|&idx| {
+                    allocations[idx].open_payouts + allocations[idx].payout_count as i64
+                }),
+        };
+
+        match selected {
+            Some(idx) => {
+                if weighted_bank.is_none() {
+                    rotation.next_index = (idx + 1) % traders.len();
+                }
+                allocations[idx].payout_count += 1;
+                allocations[idx].payout_amount += amount;
+            }
+            None => {
+                unassigned_count += 1;
+                unassigned_amount += amount;
+            }
+        }
+    }
+
+    SimulateDistributionResponse {
+        strategy,
+        queue_count: payouts.len(),
+        queue_amount,
+        assigned_count: payouts.len() - unassigned_count,
+        assigned_amount: queue_amount - unassigned_amount,
+        unassigned_count,
+        unassigned_amount,
+        traders: allocations,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AssignPayoutRequest {
+    trader_id: String,
+    /// See [`payout_version`].
+    #[serde(default)]
+    expected_updated_at: Option<UtcTimestamp>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AssignPayoutResponse {
+    pub(crate) success: bool,
+    /// The payout after the change, as the deals list shows it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) payout: Option<PayoutDealListItem>,
+}
+
+pub(crate) async fn assign_payout(
+    Path(payout_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    headers: HeaderMap,
+    Json(request): Json<AssignPayoutRequest>,
+) -> ApiResult<Json<AssignPayoutResponse>> {
+    let expected_updated_at = payout_version::expected(&headers, request.expected_updated_at)?;
+    ensure_trader_in_scope(&state.pool, &request.trader_id, scope.merchant_ids()).await?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    assign_payout_internal(
+        &state,
+        &payout_id,
+        &request.trader_id,
+        expected_updated_at,
+        &scope,
+        actor.as_deref(),
+    )
+    .await?;
+    let payout = fetch_updated_payout(&state, &payout_id).await?;
+    Ok(Json(AssignPayoutResponse {
+        success: true,
+        payout,
+    }))
+}
+
+/// Runs distribution cycles, on the worker's schedule or on demand. Shares the
+/// round-robin position between both so manual runs continue the rotation.
+#[derive(Clone)]
+pub(crate) struct Distributor {
+    pool: PgPool,
+    settings: SettingsService,
+    feature_flags: FeatureFlags,
+    round_robin: Arc<Mutex<bank_routing::RotationState>>,
+    worker_status: Arc<RwLock<WorkerStatus>>,
+    /// The auto distribution worker task, see [`Distributor::start_worker`].
+    worker: Arc<Mutex<Option<JoinHandle<()>>>>,
+    /// Bumped to cancel the cycles in progress, see [`CycleCancel`].
+    cancel_generation: Arc<AtomicU64>,
+    outbox_notify: Arc<Notify>,
+}
+
+/// Lets a cycle stop between its stages and every [`CANCEL_CHECK_BATCH`]
+/// payouts, rolling back instead of writing. A cycle is cancelled by
+/// [`Distributor::cancel_cycles`]; an automatic one also when auto
+/// distribution is switched off while it runs.
+struct CycleCancel<'a> {
+    distributor: &'a Distributor,
+    generation: u64,
+    auto: bool,
+}
+
+impl CycleCancel<'_> {
+    fn is_cancelled(&self) -> bool {
+        self.distributor.cancel_generation.load(Ordering::SeqCst) != self.generation
+            || (self.auto && !self.distributor.settings.auto_config().enabled)
+    }
+}
+
+impl Distributor {
+    pub(crate) fn new(
+        pool: PgPool,
+        settings: SettingsService,
+        feature_flags: FeatureFlags,
+        outbox_notify: Arc<Notify>,
+    ) -> Self {
+        Self {
+            pool,
+            settings,
+            feature_flags,
+            round_robin: Arc::new(Mutex::new(bank_routing::RotationState::default())),
+            worker_status: Arc::new(RwLock::new(WorkerStatus::default())),
+            worker: Arc::new(Mutex::new(None)),
+            cancel_generation: Arc::new(AtomicU64::new(0)),
+            outbox_notify,
+        }
+    }
+
+    pub(crate) async fn worker_status(&self) -> WorkerStatus {
+        self.worker_status.read().await.clone()
+    }
+
+    pub(crate) async fn rotation(&self) -> bank_routing::RotationState {
+        self.round_robin.lock().await.clone()
+    }
+
+    /// Spawns the auto distribution worker, aborting the running one first.
+    /// A cycle cut short this way rolls its transaction back.
+    pub(crate) async fn start_worker(&self) {
+        let mut worker = self.worker.lock().await;
+        let restart = match worker.take() {
+            Some(handle) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        };
+        {
+            let mut status = self.worker_status.write().await;
+            status.started_at = Some(Utc::now());
+            status.cycle_started_at = None;
+            if restart {
+                status.restarts += 1;
+            }
+        }
+        *worker = Some(tokio::spawn(self.clone().run_worker()));
+    }
+
+    /// Cancels every cycle in progress; cycles started later are unaffected.
+    pub(crate) fn cancel_cycles(&self) {
+        self.cancel_generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Whether the worker task is alive; it only ends by panicking.
+    async fn worker_alive(&self) -> bool {
+        self.worker
+            .lock()
+            .await
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+    }
+
+    async fn run_worker(self) {
+        let mut config_rx = self.settings.subscribe();
+        let worker_status = &self.worker_status;
+        let mut current = config_rx.borrow().clone();
+        let mut schedule = CycleSchedule::new(&current);
+        let mut schedule_open = true;
+
+        loop {
+            tokio::select! {
+                _ = schedule.tick() => {
+                    let in_schedule = current.is_within_schedule(Utc::now());
+                    {
+                        let mut status = worker_status.write().await;
+                        status.in_schedule = in_schedule;
+                        if current.enabled && in_schedule {
+                            status.last_cycle_at = Some(Utc::now());
+                        }
+                    }
+                    if current.enabled && in_schedule != schedule_open {
+                        println!(
+                            "[auto] {} distribution window ({})",
+                            if in_schedule { "Entered" } else { "Outside of" },
+                            current.timezone
+                        );
+                        schedule_open = in_schedule;
+                    }
+                    if current.enabled && in_schedule {
+                        worker_status.write().await.cycle_started_at = Some(Utc::now());
+                        let result = self.run_cycle(&current, "auto").await;
+                        let mut status = worker_status.write().await;
+                        status.cycle_started_at = None;
+                        if let Err(err) = result {
+                            eprintln!("[auto] Distribution error: {err:?}");
+                            status.last_error = Some(format!("{err:#}"));
+                            status.last_error_at = Some(Utc::now());
+                        }
+                    }
+                    worker_status.write().await.next_tick_at = Some(schedule.next_at());
+                }
+                changed = config_rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    current = config_rx.borrow().clone();
+                    schedule = CycleSchedule::new(&current);
+                    worker_status.write().await.next_tick_at = Some(schedule.next_at());
+                    println!(
+                        "[settings] Updated auto distribution config: enabled={}, interval={}s (jitter {}s{}), per-trader cap={:?}, cycle cap={:?}, windows={}",
+                        current.enabled,
+                        current.interval_seconds,
+                        current.interval_jitter_seconds,
+                        if current.interval_anchored { ", anchored" } else { "" },
+                        current.max_assignments_per_trader_per_cycle,
+                        current.max_payouts_per_cycle,
+                        current.windows.len()
+                    );
+                }
+            }
+        }
+    }
+
+    /// Runs one distribution cycle and records it in `DistributionRun`. Idle
+    /// cycles (nothing in the queue) are not recorded to keep the history useful.
+    pub(crate) async fn run_cycle(
+        &self,
+        config: &AutoDistributionConfig,
+        source: &str,
+    ) -> Result<CycleOutcome> {
+        let started_at = Utc::now();
+        let cancel = CycleCancel {
+            distributor: self,
+            generation: self.cancel_generation.load(Ordering::SeqCst),
+            auto: source == "auto",
+        };
+        let result =
+            db_retry::with_retry("Distribution cycle", || self.distribute(config, &cancel)).await;
+        let finished_at = Utc::now();
+
+        let idle = matches!(&result, Ok(outcome) if outcome.claimed == 0 && outcome.note.is_none());
+        if !idle
+            && let Err(err) = db_retry::with_retry("Distribution run log", || {
+                record_distribution_run(&self.pool, source, started_at, finished_at, &result)
+            })
+            .await
+        {
+            eprintln!("[{source}] Failed to record distribution run: {err:?}");
+        }
+
+        result
+    }
+}
+
+/// When the worker's cycles are due. Ticks fall on a fixed grid of the
+/// interval, so slow cycles do not make the schedule drift, and a tick missed
+/// while a cycle ran is skipped. The grid starts with the first tick right
+/// away, or with `interval_anchored` at multiples of the interval since the
+/// Unix epoch (an interval of 1800 ticks at :00 and :30 UTC), which lines up
+/// every environment with the same interval. `interval_jitter_seconds` delays
+/// each tick by a random amount up to that many seconds, so they do not all
+/// hit the database at once; the grid itself stays put.
+struct CycleSchedule {
+    period: chrono::Duration,
+    jitter_ms: u64,
+    /// The grid point of the next tick.
+    base: DateTime<Utc>,
+    next: DateTime<Utc>,
+}
+
+impl CycleSchedule {
+    fn new(config: &AutoDistributionConfig) -> Self {
+        // `sanitized` already bounds the interval; a bad value must not
+        // panic the worker either.
+        let period = i64::try_from(config.interval_seconds.clamp(1, MAX_INTERVAL_SECONDS))
+            .ok()
+            .and_then(chrono::Duration::try_seconds)
+            .unwrap_or_else(|| chrono::Duration::seconds(1));
+        let now = Utc::now();
+        let base = if config.interval_anchored {
+            let period_ms = period.num_milliseconds();
+            let next_ms = (now.timestamp_millis().div_euclid(period_ms) + 1) * period_ms;
+            DateTime::from_timestamp_millis(next_ms).unwrap_or(now)
+        } else {
+            now
+        };
+        let jitter_ms = config.interval_jitter_seconds.saturating_mul(1000);
+        Self {
+            period,
+            jitter_ms,
+            base,
+            next: base + random_delay(jitter_ms),
+        }
+    }
+
+    fn next_at(&self) -> DateTime<Utc> {
+        self.next
+    }
+
+    /// Waits for the next tick. Cancel-safe: the schedule only moves on once
+    /// the wait is over.
+    async fn tick(&mut self) {
+        let wait = (self.next - Utc::now()).to_std().unwrap_or_default();
+        time::sleep(wait).await;
+        let now = Utc::now();
+        while self.base <= now {
+            self.base += self.period;
+        }
+        self.next = self.base + random_delay(self.jitter_ms);
+    }
+}
+
+fn random_delay(max_ms: u64) -> chrono::Duration {
+    if max_ms == 0 {
+        return chrono::Duration::zero();
+    }
+    chrono::Duration::milliseconds((Uuid::new_v4().as_u128() % (u128::from(max_ms) + 1)) as i64)
+}
+
+pub(crate) async fn record_distribution_run(
+    pool: &PgPool,
+    source: &str,
+    started_at: DateTime<Utc>,
+    finished_at: DateTime<Utc>,
+    result: &Result<CycleOutcome>,
+) -> Result<()> {
+    let (outcome, error) = match result {
+        Ok(outcome) => (outcome.clone(), None),
+        Err(err) => (CycleOutcome::default(), Some(format!("{err:#}"))),
+    };
+    let duration_ms = (finished_at - started_at)
+        .num_milliseconds()
+        .clamp(0, i32::MAX as i64) as i32;
+
+    sqlx::query(
+        r#"
+        INSERT INTO "DistributionRun"
+            ("id", "source", "startedAt", "finishedAt", "durationMs",
+             "claimed", "assigned", "skipped", "remaining", "note", "error")
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        "#,
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(source)
+    .bind(started_at.naive_utc())
+    .bind(finished_at.naive_utc())
+    .bind(duration_ms)
+    .bind(outcome.claimed as i32)
+    .bind(outcome.assigned as i32)
+    .bind(outcome.skipped as i32)
+    .bind(outcome.remaining as i32)
+    .bind(outcome.note)
+    .bind(error)
+    .execute(pool)
+    .await
+    .context("Failed to insert distribution run")?;
+
+    Ok(())
+}
+
+impl Distributor {
+    async fn distribute(
+        &self,
+        config: &AutoDistributionConfig,
+        cancel: &CycleCancel<'_>,
+    ) -> Result<CycleOutcome> {
+        let pool = &self.pool;
+        let traders = fetch_traders(pool).await?;
+        if traders.is_empty() {
+            println!("[auto] No eligible traders available. Skipping distribution.");
+            return Ok(CycleOutcome::with_note("no eligible traders"));
+        }
+
+        let policy = self.settings.priority_policy().await;
+        let bank_weights = bank_routing::BankWeights::load(pool)
+            .await
+            .context("Failed to load bank weights")?;
+        let teams = teams::Teams::load(pool)
+            .await
+            .context("Failed to load trader teams")?;
+        let currencies = TraderCurrencies::load(pool)
+            .await
+            .context("Failed to load trader currencies")?;
+        let open_payouts = fetch_open_payouts(pool)
+            .await
+            .context("Failed to count open payouts")?;
+        let capacity_overrides = fetch_capacity_overrides(pool)
+            .await
+            .context("Failed to load trader capacity")?;
+        let mut cooling: HashSet<String> =
+            fetch_trader_cooldowns(pool, config.assignment_cooldown_seconds)
+                .await?
+                .into_keys()
+                .collect();
+        if cancel.is_cancelled() {
+            println!("[auto] Distribution cycle cancelled before claiming payouts.");
+            return Ok(CycleOutcome::cancelled(0));
+        }
+
+        let mut tx = pool.begin().await?;
+
+        let flagged = duplicates::flag_duplicates(&mut tx, config.duplicate_window_minutes).await?;
+        if !flagged.is_empty() {
+            outbox::enqueue_event(&mut tx, &ServerEvent::payouts_updated("duplicates")).await?;
+        }
+
+        let claim_limit = config.max_payouts_per_cycle.unwrap_or(CLAIM_BATCH);
+        let payouts = claim_unassigned_payouts(&mut tx, &policy, claim_limit).await?;
+        if payouts.is_empty() {
+            tx.commit().await?;
+            println!("[auto] No unassigned payouts to distribute.");
+            return Ok(CycleOutcome::default());
+        }
+
+        let limits_snapshot = self.settings.limits().await;
+        let amount_limits = AmountLimits {
+            rub: &limits_snapshot,
+            currencies: &currencies,
+        };
+        // Balances and freezes are in RUB, other currencies skip them.
+        let foreign_payouts: HashSet<&str> = payouts
+            .iter()
+            .filter(|payout| !currencies::is_base(&payout.currency))
+            .map(|payout| payout.id.as_str())
+            .collect();
+
+        let mut round_robin_guard = self.round_robin.lock().await;
+        let mut rotation = round_robin_guard.clone();
+
+        let mut assignments: Vec<(String, String, i32, i32, f64)> = Vec::new();
+        let mut assigned_per_trader: HashMap<&str, u32> = HashMap::new();
+        let team_open_payouts = teams.open_payouts(&open_payouts);
+        let mut assigned_per_team: HashMap<String, u32> = HashMap::new();
+        let mut amount_per_trader: HashMap<&str, f64> = HashMap::new();
+        let mut skipped = 0usize;
+
+        for (index, payout) in payouts.iter().enumerate() {
+            if index > 0 && index % CANCEL_CHECK_BATCH == 0 && cancel.is_cancelled() {
+                break;
+            }
+            if config
+                .max_payouts_per_cycle
+                .is_some_and(|cap| assignments.len() >= cap as usize)
+            {
+                break;
+            }
+
+            let amount = payout.amount.unwrap_or_default();
+            if amount <= 0.0 {
+                skipped += 1;
+                continue;
+            }
+
+            let foreign = foreign_payouts.contains(payout.id.as_str());
+            let fits = |trader: &TraderRecord| {
+                let allowed = amount_limits.allows(&trader.id, &payout.currency, amount);
+                let below_cap = config
+                    .max_assignments_per_trader_per_cycle
+                    .is_none_or(|cap| {
+                        assigned_per_trader
+                            .get(trader.id.as_str())
+                            .copied()
+                            .unwrap_or_default()
+                            < cap
+                    });
+                let has_capacity = config
+                    .open_payout_cap(&capacity_overrides, &trader.id)
+                    .is_none_or(|cap| {
+                        open_payouts.get(&trader.id).copied().unwrap_or_default()
+                            + i64::from(
+                                assigned_per_trader
+                                    .get(trader.id.as_str())
+                                    .copied()
+                                    .unwrap_or_default(),
+                            )
+                            < i64::from(cap)
+                    });
+                let covered = foreign
+                    || config.balance_covers(
+                        trader.balance_rub,
+                        trader.frozen_rub,
+                        amount_per_trader
+                            .get(trader.id.as_str())
+                            .copied()
+                            .unwrap_or_default(),
+                        amount,
+                    );
+                let team_has_room =
+                    teams.has_room(&trader.id, &team_open_payouts, &assigned_per_team);
+                allowed
+                    && below_cap
+                    && has_capacity
+                    && covered
+                    && team_has_room
+                    && !cooling.contains(&trader.id)
+            };
+
+            let selected: Option<&TraderRecord> = match payout.bank.as_deref() {
+                Some(bank) if bank_weights.for_bank(Some(bank)).is_some() => {
+                    let candidates = traders
+                        .iter()
+                        .filter(|trader| fits(trader))
+                        .map(|trader| trader.id.as_str());
+                    bank_weights
+                        .pick(&mut rotation, bank, candidates)
+                        .and_then(|trader_id| traders.iter().find(|trader| trader.id == trader_id))
+                }
+                _ if config.balance_across_teams => teams
+                    .pick(&mut rotation, &traders, |idx| fits(&traders[idx]))
+                    .map(|idx| &traders[idx]),
+                _ => {
+                    let found = (0..traders.len())
+                        .map(|offset| (rotation.next_index + offset) % traders.len())
+                        .find(|&idx| fits(&traders[idx]));
+                    if let Some(idx) = found {
+                        rotation.next_index = (idx + 1) % traders.len();
+                    }
+                    found.map(|idx| &traders[idx])
+                }
+            };
+
+            if let Some(trader) = selected {
+                if config.assignment_cooldown_seconds > 0 {
+                    cooling.insert(trader.id.clone());
+                }
+                *assigned_per_trader.entry(trader.id.as_str()).or_default() += 1;
+                if !foreign {
+                    *amount_per_trader.entry(trader.id.as_str()).or_default() += amount;
+                }
+                if let Some(team) = teams.team_of(&trader.id) {
+                    *assigned_per_team.entry(team.id.clone()).or_default() += 1;
+                }
+                assignments.push((
+                    payout.id.clone(),
+                    trader.id.clone(),
+                    payout.numeric_id,
+                    trader.numeric_id,
+                    amount,
+                ));
+            } else {
+                skipped += 1;
+                println!(
+                    "[auto] Skipped payout {} (amount {:.2} {}) - no trader with spare capacity accepts this amount",
+                    payout.id, amount, payout.currency
+                );
+            }
+        }
+
+        if cancel.is_cancelled() {
+            tx.rollback().await?;
+            println!(
+                "[auto] Distribution cycle cancelled, {} claimed payouts left queued.",
+                payouts.len()
+            );
+            return Ok(CycleOutcome::cancelled(payouts.len()));
+        }
+
+        if assignments.is_empty() {
+            tx.commit().await?;
+            println!("[auto] No assignments created in this cycle.");
+            *round_robin_guard = rotation;
+            return Ok(CycleOutcome {
+                claimed: payouts.len(),
+                skipped,
+                remaining: payouts.len(),
+                ..CycleOutcome::default()
+            });
+        }
+
+        let payout_ids: Vec<String> = assignments
+            .iter()
+            .map(|(payout_id, ..)| payout_id.clone())
+            .collect();
+        let trader_ids: Vec<String> = assignments
+            .iter()
+            .map(|(_, trader_id, ..)| trader_id.clone())
+            .collect();
+
+        let updated: Vec<String> = sqlx::query_scalar(
+            r#"
+        UPDATE "Payout" p
+        SET "traderId" = batch."traderId",
+            "acceptanceTime" = 40,
+            "updatedAt" = CURRENT_TIMESTAMP
+        FROM UNNEST($1::text[], $2::text[]) AS batch("payoutId", "traderId")
+        WHERE p."id" = batch."payoutId"
+          AND p."traderId" IS NULL
+          AND p."direction" = 'OUT'
+          AND p."status" = 'CREATED'
+          AND p."acceptedAt" IS NULL
+          AND NOT EXISTS (
+              SELECT 1
+              FROM "AggregatorPayout" ap
+              WHERE ap."payoutId" = p."id"
+          )
+        RETURNING p."id"
+        "#,
+        )
+        .bind(&payout_ids)
+        .bind(&trader_ids)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let updated: HashSet<String> = updated.into_iter().collect();
+        let applied = updated.len();
+
+        let (audit_payout_ids, audit_trader_ids): (Vec<String>, Vec<String>) = assignments
+            .iter()
+            .filter(|(payout_id, ..)| updated.contains(payout_id))
+            .map(|(payout_id, trader_id, ..)| (payout_id.clone(), trader_id.clone()))
+            .unzip();
+        let audit_ids: Vec<String> = audit_payout_ids
+            .iter()
+            .map(|_| Uuid::new_v4().to_string())
+            .collect();
+        sqlx::query(
+            r#"
+        INSERT INTO "PayoutAuditLog" ("id", "payoutId", "action", "source", "actor", "traderId")
+        SELECT batch."id", batch."payoutId", 'assigned', 'auto', 'auto', batch."traderId"
+        FROM UNNEST($1::text[], $2::text[], $3::text[]) AS batch("id", "payoutId", "traderId")
+        "#,
+        )
+        .bind(&audit_ids)
+        .bind(&audit_payout_ids)
+        .bind(&audit_trader_ids)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to record assignment audit")?;
+        let assigned: Vec<(String, String)> = audit_payout_ids
+            .iter()
+            .cloned()
+            .zip(audit_trader_ids.iter().cloned())
+            .collect();
+        trader_webhook::enqueue_assignments(&mut tx, &assigned).await?;
+
+        if config.freeze_on_assign && self.feature_flags.is_enabled(Feature::BalanceFreeze).await {
+            let frozen: Vec<(String, String, f64)> = assignments
+                .iter()
+                .filter(|(payout_id, ..)| {
+                    updated.contains(payout_id) && !foreign_payouts.contains(payout_id.as_str())
+                })
+                .map(|(payout_id, trader_id, _, _, amount)| {
+                    (payout_id.clone(), trader_id.clone(), *amount)
+                })
+                .collect();
+            freeze::freeze_assignments(&mut tx, &frozen)
+                .await
+                .context("Failed to freeze assigned amounts")?;
+        }
+
+        for (payout_id, trader_id, payout_numeric, trader_numeric, _) in &assignments {
+            if updated.contains(payout_id) {
+                println!(
+                    "[auto] Assigned payout {} (numericId {}) to trader {} (numericId {})",
+                    payout_id, payout_numeric, trader_id, trader_numeric
+                );
+            } else {
+                println!(
+                    "[auto] Skipped payout {} (numericId {}) - it was changed concurrently",
+                    payout_id, payout_numeric
+                );
+            }
+        }
+
+        let remaining = payouts.len() - applied;
+        if applied > 0 {
+            let merchants = payouts
+                .iter()
+                .filter(|payout| updated.contains(&payout.id))
+                .filter_map(|payout| payout.merchant_id.clone());
+            outbox::enqueue_event(
+                &mut tx,
+                &ServerEvent::payouts_updated("auto").for_merchants(merchants),
+            )
+            .await?;
+            outbox::enqueue_event(
+                &mut tx,
+                &ServerEvent::distribution_cycle(applied, remaining),
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+        *round_robin_guard = rotation;
+        drop(round_robin_guard);
+
+        if applied > 0 {
+            self.outbox_notify.notify_one();
+            println!(
+                "[auto] Distribution cycle completed with {applied} assignments, {remaining} payouts left in backlog."
+            );
+        } else {
+            println!("[auto] Distribution cycle completed without changes.");
+        }
+
+        Ok(CycleOutcome {
+            claimed: payouts.len(),
+            assigned: applied,
+            skipped: skipped + (assignments.len() - applied),
+            remaining,
+            note: None,
+        })
+    }
+}
+
+pub(crate) async fn assign_payout_internal(
+    state: &AppState,
+    payout_id: &str,
+    trader_id: &str,
+    expected_updated_at: Option<UtcTimestamp>,
+    scope: &TenantScope,
+    actor: Option<&str>,
+) -> ApiResult<()> {
+    if trader_id.trim().is_empty() {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "Trader ID is required".to_string(),
+        )));
+    }
+
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    payout_version::check(&mut tx, payout_id, expected_updated_at).await?;
+
+    let result: Option<(Option<String>, Option<f64>, String)> = sqlx::query_as(&format!(
+        r#"
+        UPDATE "Payout" p
+        SET "traderId" = $1,
+            "acceptanceTime" = 40,
+            "updatedAt" = CURRENT_TIMESTAMP
+        WHERE "id" = $2
+          AND "direction" = 'OUT'
+          AND "status" = 'CREATED'
+          AND "acceptedAt" IS NULL
+          AND "traderId" IS NULL
+          AND NOT EXISTS (
+              SELECT 1
+              FROM "AggregatorPayout" ap
+              WHERE ap."payoutId" = p."id"
+          )
+          AND ($3::text[] IS NULL OR "merchantId" = ANY($3::text[]))
+        RETURNING
+            "merchantId",
+            "amount",
+            {PAYOUT_CURRENCY_SQL}
+        "#
+    ))
+    .bind(trader_id)
+    .bind(payout_id)
+    .bind(scope.merchant_ids())
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    let Some((merchant_id, amount, currency)) = result else {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            ErrorCode::PayoutNotEligible,
+            "Payout is not eligible for assignment",
+        ));
+    };
+
+    check_trader_eligible(&mut tx, trader_id, merchant_id.as_deref()).await?;
+    let config = state.settings.auto_config();
+    let amount = amount.unwrap_or_default();
+    let foreign = !currencies::is_base(&currency);
+    if foreign {
+        currencies::check_trader_currency(&mut tx, trader_id, &currency, amount).await?;
+    } else if let Some(limit) = state.settings.limits().await.get(trader_id)
+        && !limit.contains(amount)
+    {
+        return Err(ApiError::trader_ineligible(format!(
+            "Amount is outside the trader's limit ({limit})"
+        )));
+    }
+    if config.require_sufficient_balance && !foreign {
+        let balances: Option<(Option<f64>, Option<f64>)> =
+            sqlx::query_as(r#"SELECT "balanceRub", "frozenRub" FROM "User" WHERE "id" = $1"#)
+                .bind(trader_id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(internal_error)?;
+        let (balance_rub, frozen_rub) = balances.unwrap_or_default();
+        if !config.balance_covers(balance_rub, frozen_rub, 0.0, amount) {
+            return Err(ApiError::new(
+                StatusCode::CONFLICT,
+                ErrorCode::InsufficientBalance,
+                "Trader balance does not cover this payout",
+            ));
+        }
+    }
+    if config.freeze_on_assign
+        && !foreign
+        && state.feature_flags.is_enabled(Feature::BalanceFreeze).await
+    {
+        freeze::freeze_assignments(
+            &mut tx,
+            &[(payout_id.to_string(), trader_id.to_string(), amount)],
+        )
+        .await
+        .map_err(internal_error)?;
+    }
+
+    record_payout_audit(
+        &mut *tx,
+        payout_id,
+        "assigned",
+        actor,
+        Some(trader_id),
+        None,
+    )
+    .await
+    .map_err(internal_error)?;
+    trader_webhook::enqueue_assignments(&mut tx, &[(payout_id.to_string(), trader_id.to_string())])
+        .await
+        .map_err(internal_error)?;
+    outbox::enqueue_event(
+        &mut tx,
+        &ServerEvent::payouts_updated("manual").for_merchants(merchant_id),
+    )
+    .await
+    .map_err(internal_error)?;
+    tx.commit().await.map_err(internal_error)?;
+    state.outbox_notify.notify_one();
+
+    println!("[manual] Assigned payout {payout_id} to trader {trader_id}");
+
+    Ok(())
+}
+
+#[derive(Debug, FromRow)]
+struct TraderEligibility {
+    banned: bool,
+    #[sqlx(rename = "trafficEnabled")]
+    traffic_enabled: bool,
+    paused: bool,
+    unavailable: bool,
+    #[sqlx(rename = "merchantLinked")]
+    merchant_linked: bool,
+}
+
+/// The checks of `ELIGIBLE_TRADERS_QUERY` that do not depend on the
+/// distribution settings, for one trader and the merchant of the payout being
+/// assigned. Locks the trader row until the assignment commits, so a ban
+/// cannot slip in between.
+async fn check_trader_eligible(
+    tx: &mut Transaction<'_, Postgres>,
+    trader_id: &str,
+    merchant_id: Option<&str>,
+) -> ApiResult<()> {
+    let eligibility: Option<TraderEligibility> = sqlx::query_as(
+        r#"
+        SELECT
+            u."banned",
+            u."trafficEnabled",
+            EXISTS (
+                SELECT 1 FROM "TraderPause" tp WHERE tp."traderId" = u."id"
+            ) AS "paused",
+            EXISTS (
+                SELECT 1
+                FROM "TraderUnavailability" tu
+                WHERE tu."traderId" = u."id"
+                  AND tu."startsAt" <= LOCALTIMESTAMP
+                  AND tu."endsAt" > LOCALTIMESTAMP
+            ) AS "unavailable",
+            EXISTS (
+                SELECT 1
+                FROM "TraderMerchant" tm
+                WHERE tm."traderId" = u."id"
+                  AND tm."merchantId" = $2
+                  AND tm."isMerchantEnabled" = TRUE
+                  AND tm."isFeeOutEnabled" = TRUE
+            ) AS "merchantLinked"
+        FROM "User" u
+        WHERE u."id" = $1
+        FOR SHARE OF u
+        "#,
+    )
+    .bind(trader_id)
+    .bind(merchant_id)
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(internal_error)?;
+
+    let Some(eligibility) = eligibility else {
+        return Err(ApiError::trader_not_found());
+    };
+    let reason = if eligibility.banned {
+        "Trader is banned"
+    } else if !eligibility.traffic_enabled {
+        "Trader has traffic disabled"
+    } else if eligibility.paused {
+        "Trader is paused"
+    } else if eligibility.unavailable {
+        "Trader is unavailable (planned absence)"
+    } else if !eligibility.merchant_linked {
+        "Trader does not take payouts of this merchant"
+    } else {
+        return Ok(());
+    };
+    Err(ApiError::trader_ineligible(reason))
+}
@@ -0,0 +1,100 @@
+//! Detection of likely duplicate payouts: an open payout with the same
+//! merchant, wallet and amount as an earlier, not cancelled payout created
+//! within `duplicateWindowMinutes` (initially from `DUPLICATE_WINDOW_MINUTES`,
+//! `0` disables detection).
+//!
+//! Flags live in `PayoutDuplicateFlag`. A flagged payout is skipped by auto
+//! distribution until an operator approves it or cancels it.
+
+use std::env;
+
+use anyhow::{Context, Result};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+const FLAG_DUPLICATES_QUERY: &str = r#"
+    WITH candidates AS (
+        SELECT
+            p."id",
+            (
+                SELECT q."id"
+                FROM "Payout" q
+                WHERE q."direction" = 'OUT'
+                  AND q."merchantId" = p."merchantId"
+                  AND q."wallet" = p."wallet"
+                  AND q."amount" = p."amount"
+                  AND q."status" <> 'CANCELLED'
+                  AND (q."createdAt", q."id") < (p."createdAt", p."id")
+                  AND q."createdAt" >= p."createdAt" - make_interval(mins => $1)
+                ORDER BY q."createdAt", q."id"
+                LIMIT 1
+            ) AS "duplicateOf"
+        FROM "Payout" p
+        WHERE p."direction" = 'OUT'
+          AND p."status" = 'CREATED'
+          AND p."traderId" IS NULL
+          AND NOT EXISTS (
+              SELECT 1 FROM "PayoutDuplicateFlag" df WHERE df."payoutId" = p."id"
+          )
+    )
+    INSERT INTO "PayoutDuplicateFlag" ("payoutId", "duplicateOf")
+    SELECT "id", "duplicateOf"
+    FROM candidates
+    WHERE "duplicateOf" IS NOT NULL
+    ON CONFLICT ("payoutId") DO NOTHING
+    RETURNING "payoutId", "duplicateOf"
+"#;
+
+pub(crate) fn window_from_env() -> Result<u32> {
+    match env::var("DUPLICATE_WINDOW_MINUTES") {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse()
+            .context("DUPLICATE_WINDOW_MINUTES must be a non-negative integer"),
+        _ => Ok(0),
+    }
+}
+
+/// Flags newly detected duplicates and records them in the payout audit log.
+/// Returns the ids of the payouts flagged by this pass.
+pub(crate) async fn flag_duplicates(
+    tx: &mut Transaction<'_, Postgres>,
+    window_minutes: u32,
+) -> Result<Vec<String>> {
+    if window_minutes == 0 {
+        return Ok(Vec::new());
+    }
+    let flagged: Vec<(String, String)> = sqlx::query_as(FLAG_DUPLICATES_QUERY)
+        .bind(window_minutes.min(i32::MAX as u32) as i32)
+        .fetch_all(&mut **tx)
+        .await
+        .context("Failed to flag duplicate payouts")?;
+    if flagged.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let (payout_ids, duplicate_of): (Vec<String>, Vec<String>) = flagged.into_iter().unzip();
+    let audit_ids: Vec<String> = payout_ids
+        .iter()
+        .map(|_| Uuid::new_v4().to_string())
+        .collect();
+    sqlx::query(
+        r#"
+        INSERT INTO "PayoutAuditLog" ("id", "payoutId", "action", "source", "details")
+        SELECT batch."id", batch."payoutId", 'duplicate-flagged', 'service',
+               jsonb_build_object('duplicateOf', batch."duplicateOf")
+        FROM UNNEST($1::text[], $2::text[], $3::text[]) AS batch("id", "payoutId", "duplicateOf")
+        "#,
+    )
+    .bind(&audit_ids)
+    .bind(&payout_ids)
+    .bind(&duplicate_of)
+    .execute(&mut **tx)
+    .await
+    .context("Failed to record duplicate audit")?;
+
+    for (payout_id, original) in payout_ids.iter().zip(&duplicate_of) {
+        println!("[duplicates] Flagged payout {payout_id} as a possible duplicate of {original}");
+    }
+    Ok(payout_ids)
+}
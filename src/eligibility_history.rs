@@ -0,0 +1,426 @@
+//! Why traders enter and leave the eligible set. A worker snapshots, for
+//! every trader linked to a merchant, each condition of
+//! `db::ELIGIBLE_TRADERS_QUERY` separately and compares consecutive
+//! snapshots. When a trader appears in or drops out of the set a row goes to
+//! `TraderEligibilityChange` with the conditions that kept it out:
+//! `banned`, `traffic-disabled`, `zero-balance`, `paused`, `unavailable`,
+//! `merchant-link-removed` (no enabled merchant link left) or
+//! `no-open-payouts`. `GET /api/traders/:id/eligibility-history` lists them.
+//!
+//! The first snapshot after a start is only a baseline. A trader with at least
+//! `ELIGIBILITY_ALERT_MIN_ASSIGNMENTS` (default 20, `0` disables the alert)
+//! assignments in the last `ELIGIBILITY_ALERT_WINDOW_HOURS` (default 24) who
+//! drops out for any reason but an empty queue raises a
+//! `trader-eligibility-dropped` event, and an email when alerts are
+//! configured.
+
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{Context, Result};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use tokio::time::{self, MissedTickBehavior};
+use uuid::Uuid;
+
+use crate::{
+    ApiResult, AppState, api::ensure_trader_in_scope, env::non_empty_env, events::ServerEvent,
+    internal_error, tenant::TenantScope, timestamps::UtcTimestamp,
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const RETENTION_DAYS: i32 = 30;
+const NO_OPEN_PAYOUTS: &str = "no-open-payouts";
+
+#[derive(Debug, Clone)]
+pub(crate) struct EligibilityAlertConfig {
+    /// Assignments in the window that make a trader high-volume, `0` when
+    /// alerting is off.
+    min_assignments: i64,
+    window_hours: i32,
+}
+
+impl EligibilityAlertConfig {
+    pub(crate) fn from_env() -> Result<Self> {
+        let min_assignments = match non_empty_env("ELIGIBILITY_ALERT_MIN_ASSIGNMENTS") {
+            Some(value) => value
+                .parse::<i64>()
+                .ok()
+                .filter(|value| *value >= 0)
+                .context("ELIGIBILITY_ALERT_MIN_ASSIGNMENTS must be a non-negative integer")?,
+            None => 20,
+        };
+        let window_hours = match non_empty_env("ELIGIBILITY_ALERT_WINDOW_HOURS") {
+            Some(value) => value
+                .parse::<i32>()
+                .ok()
+                .filter(|value| (1..=24 * 7).contains(value))
+                .context("ELIGIBILITY_ALERT_WINDOW_HOURS must be between 1 and 168")?,
+            None => 24,
+        };
+        Ok(Self {
+            min_assignments,
+            window_hours,
+        })
+    }
+
+    pub(crate) fn is_alerting(&self) -> bool {
+        self.min_assignments > 0
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        format!(
+            "alert when a trader with at least {} assignment(s) in {} h drops out",
+            self.min_assignments, self.window_hours
+        )
+    }
+}
+
+/// One trader's eligibility conditions at a snapshot.
+#[derive(Debug, Clone, FromRow)]
+struct TraderConditions {
+    id: String,
+    banned: bool,
+    #[sqlx(rename = "trafficEnabled")]
+    traffic_enabled: bool,
+    #[sqlx(rename = "hasBalance")]
+    has_balance: bool,
+    paused: bool,
+    unavailable: bool,
+    /// Merchants linked with both the merchant and fee-out switches on.
+    #[sqlx(rename = "merchantIds")]
+    merchant_ids: Vec<String>,
+    #[sqlx(rename = "hasOpenPayouts")]
+    has_open_payouts: bool,
+}
+
+impl TraderConditions {
+    /// A trader whose last merchant link was deleted is no longer in the
+    /// snapshot; it keeps its other conditions.
+    fn unlinked(&self) -> Self {
+        Self {
+            merchant_ids: Vec::new(),
+            has_open_payouts: false,
+            ..self.clone()
+        }
+    }
+
+    /// The conditions keeping the trader out, empty when it is eligible.
+    fn blockers(&self) -> Vec<String> {
+        let mut blockers = Vec::new();
+        if self.banned {
+            blockers.push("banned");
+        }
+        if !self.traffic_enabled {
+            blockers.push("traffic-disabled");
+        }
+        if !self.has_balance {
+            blockers.push("zero-balance");
+        }
+        if self.paused {
+            blockers.push("paused");
+        }
+        if self.unavailable {
+            blockers.push("unavailable");
+        }
+        if self.merchant_ids.is_empty() {
+            blockers.push("merchant-link-removed");
+        } else if !self.has_open_payouts {
+            blockers.push(NO_OPEN_PAYOUTS);
+        }
+        blockers.into_iter().map(str::to_string).collect()
+    }
+}
+
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct EligibilityChange {
+    id: String,
+    #[sqlx(rename = "traderId")]
+    trader_id: String,
+    /// `true` when the trader appeared in the eligible set, `false` when it
+    /// dropped out.
+    eligible: bool,
+    /// The conditions that kept the trader out: after the change for a
+    /// dropout, before it for an appearance.
+    reasons: Vec<String>,
+    #[sqlx(rename = "merchantsAdded")]
+    merchants_added: Vec<String>,
+    #[sqlx(rename = "merchantsRemoved")]
+    merchants_removed: Vec<String>,
+    /// Assignments in the alert window, only counted for dropouts.
+    #[sqlx(rename = "recentAssignments")]
+    recent_assignments: Option<i64>,
+    alerted: bool,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct EligibilityHistoryQuery {
+    limit: Option<i64>,
+}
+
+pub(crate) async fn get_eligibility_history(
+    Path(trader_id): Path<String>,
+    Query(params): Query<EligibilityHistoryQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<Vec<EligibilityChange>>> {
+    ensure_trader_in_scope(&state.pool, &trader_id, scope.merchant_ids()).await?;
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+    sqlx::query_as::<_, EligibilityChange>(
+        r#"
+        SELECT
+            c."id",
+            c."traderId",
+            c."eligible",
+            c."reasons",
+            ARRAY(
+                SELECT m FROM unnest(c."merchantsAdded") m
+                WHERE $2::text[] IS NULL OR m = ANY($2::text[])
+            ) AS "merchantsAdded",
+            ARRAY(
+                SELECT m FROM unnest(c."merchantsRemoved") m
+                WHERE $2::text[] IS NULL OR m = ANY($2::text[])
+            ) AS "merchantsRemoved",
+            c."recentAssignments"::bigint AS "recentAssignments",
+            c."alerted",
+            c."createdAt"
+        FROM "TraderEligibilityChange" c
+        WHERE c."traderId" = $1
+        ORDER BY c."createdAt" DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(&trader_id)
+    .bind(scope.merchant_ids())
+    .bind(limit)
+    .fetch_all(&state.pool)
+    .await
+    .map(Json)
+    .map_err(internal_error)
+}
+
+async fn snapshot(pool: &PgPool) -> Result<HashMap<String, TraderConditions>> {
+    let rows = sqlx::query_as::<_, TraderConditions>(
+        r#"
+        SELECT
+            u."id",
+            COALESCE(u."banned", FALSE) AS "banned",
+            COALESCE(u."trafficEnabled", FALSE) AS "trafficEnabled",
+            COALESCE(u."balanceRub", 0) > 0 AS "hasBalance",
+            EXISTS (
+                SELECT 1 FROM "TraderPause" tp WHERE tp."traderId" = u."id"
+            ) AS "paused",
+            EXISTS (
+                SELECT 1
+                FROM "TraderUnavailability" tu
+                WHERE tu."traderId" = u."id"
+                  AND tu."startsAt" <= LOCALTIMESTAMP
+                  AND tu."endsAt" > LOCALTIMESTAMP
+            ) AS "unavailable",
+            ARRAY(
+                SELECT tm."merchantId"
+                FROM "TraderMerchant" tm
+                WHERE tm."traderId" = u."id"
+                  AND tm."isMerchantEnabled" = TRUE
+                  AND tm."isFeeOutEnabled" = TRUE
+                ORDER BY tm."merchantId"
+            ) AS "merchantIds",
+            EXISTS (
+                SELECT 1
+                FROM "Payout" p
+                JOIN "TraderMerchant" tm ON tm."merchantId" = p."merchantId"
+                WHERE tm."traderId" = u."id"
+                  AND tm."isMerchantEnabled" = TRUE
+                  AND tm."isFeeOutEnabled" = TRUE
+                  AND p."direction" = 'OUT'
+                  AND p."status" = 'CREATED'
+                  AND (p."traderId" IS NULL OR p."traderId" = u."id")
+            ) AS "hasOpenPayouts"
+        FROM "User" u
+        WHERE EXISTS (
+            SELECT 1 FROM "TraderMerchant" tm WHERE tm."traderId" = u."id"
+        )
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to snapshot trader eligibility")?;
+    Ok(rows.into_iter().map(|row| (row.id.clone(), row)).collect())
+}
+
+async fn recent_assignments(
+    pool: &PgPool,
+    trader_id: &str,
+    config: &EligibilityAlertConfig,
+) -> Result<i64> {
+    sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*)::bigint
+        FROM "PayoutAuditLog" a
+        WHERE a."traderId" = $1
+          AND a."action" = 'assigned'
+          AND a."createdAt" >= LOCALTIMESTAMP - make_interval(hours => $2)
+        "#,
+    )
+    .bind(trader_id)
+    .bind(config.window_hours)
+    .fetch_one(pool)
+    .await
+    .context("Failed to count recent assignments")
+}
+
+async fn record_change(
+    state: &AppState,
+    config: &EligibilityAlertConfig,
+    previous: &TraderConditions,
+    current: &TraderConditions,
+) -> Result<()> {
+    let eligible = current.blockers().is_empty();
+    let reasons = if eligible {
+        previous.blockers()
+    } else {
+        current.blockers()
+    };
+    let merchants_added: Vec<&str> = current
+        .merchant_ids
+        .iter()
+        .filter(|id| !previous.merchant_ids.contains(id))
+        .map(String::as_str)
+        .collect();
+    let merchants_removed: Vec<&str> = previous
+        .merchant_ids
+        .iter()
+        .filter(|id| !current.merchant_ids.contains(id))
+        .map(String::as_str)
+        .collect();
+    let recent = if eligible || !config.is_alerting() {
+        None
+    } else {
+        Some(recent_assignments(&state.pool, &current.id, config).await?)
+    };
+    let alerted = recent.is_some_and(|count| count >= config.min_assignments)
+        && reasons.iter().any(|reason| reason != NO_OPEN_PAYOUTS);
+
+    sqlx::query(
+        r#"
+        INSERT INTO "TraderEligibilityChange"
+            ("id", "traderId", "eligible", "reasons", "merchantsAdded", "merchantsRemoved",
+             "recentAssignments", "alerted")
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(&current.id)
+    .bind(eligible)
+    .bind(&reasons)
+    .bind(&merchants_added)
+    .bind(&merchants_removed)
+    .bind(recent.map(|count| count as i32))
+    .bind(alerted)
+    .execute(&state.pool)
+    .await
+    .context("Failed to record eligibility change")?;
+
+    let verb = if eligible {
+        "became eligible"
+    } else {
+        "dropped out"
+    };
+    println!(
+        "[eligibility] Trader {} {verb}: {}",
+        current.id,
+        reasons.join(", ")
+    );
+    if !alerted {
+        return Ok(());
+    }
+
+    let count = recent.unwrap_or_default();
+    eprintln!(
+        "[eligibility] High-volume trader {} dropped out after {count} assignment(s) in {} h: {}",
+        current.id,
+        config.window_hours,
+        reasons.join(", ")
+    );
+    let _ = state.event_tx.send(ServerEvent::trader_eligibility_dropped(
+        previous.merchant_ids.clone(),
+        serde_json::json!({
+            "traderId": current.id,
+            "reasons": reasons,
+            "recentAssignments": count,
+            "windowHours": config.window_hours,
+        }),
+    ));
+    if let Some(email) = &state.email {
+        email
+            .send(
+                &state.pool,
+                "High-volume trader dropped out",
+                &format!(
+                    "Trader {} had {count} assignment(s) in the last {} h and is no longer eligible for payouts: {}. See /api/traders/{}/eligibility-history.",
+                    current.id,
+                    config.window_hours,
+                    reasons.join(", "),
+                    current.id
+                ),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+async fn check(
+    state: &AppState,
+    config: &EligibilityAlertConfig,
+    previous: &mut Option<HashMap<String, TraderConditions>>,
+) -> Result<()> {
+    let mut current = snapshot(&state.pool).await?;
+    if let Some(previous) = previous.as_ref() {
+        for (trader_id, before) in previous {
+            let after = current
+                .entry(trader_id.clone())
+                .or_insert_with(|| before.unlinked());
+            if before.blockers().is_empty() != after.blockers().is_empty() {
+                record_change(state, config, before, after).await?;
+            }
+        }
+        for (trader_id, after) in &current {
+            if !previous.contains_key(trader_id) && after.blockers().is_empty() {
+                record_change(state, config, &after.unlinked(), after).await?;
+            }
+        }
+    }
+    // Unlinked traders stay in the snapshot so relinking them is recorded.
+    *previous = Some(current);
+
+    sqlx::query(
+        r#"
+        DELETE FROM "TraderEligibilityChange"
+        WHERE "createdAt" < LOCALTIMESTAMP - make_interval(days => $1)
+        "#,
+    )
+    .bind(RETENTION_DAYS)
+    .execute(&state.pool)
+    .await
+    .context("Failed to prune eligibility history")?;
+    Ok(())
+}
+
+pub(crate) async fn history_worker(state: AppState, config: EligibilityAlertConfig) {
+    let mut interval = time::interval(CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut previous = None;
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = check(&state, &config, &mut previous).await {
+            eprintln!("[eligibility] {err:#}");
+        }
+    }
+}
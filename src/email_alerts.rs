@@ -0,0 +1,348 @@
+//! Email alerts over SMTP. Recipients are kept in `EmailRecipient` and managed
+//! through `/api/notifications/email`; alerts go out once per incident:
+//!
+//! - the unassigned backlog stays above `ALERT_BACKLOG_THRESHOLD` payouts
+//!   (`0` disables the rule) for `ALERT_BACKLOG_MINUTES` (default 15);
+//! - the callback dead-letter queue grows (`ALERT_DEAD_LETTER=false` disables);
+//! - auto distribution stops while operators left it enabled: the worker
+//!   stalls, or a restart brings the service up with distribution off
+//!   (`ALERT_AUTO_DISTRIBUTION=false` disables).
+//!
+//! SMTP is configured with `SMTP_HOST`, `SMTP_PORT`, `SMTP_FROM`,
+//! `SMTP_USERNAME`/`SMTP_PASSWORD` and `SMTP_TLS` (`starttls` by default,
+//! `tls` or `none`). Without `SMTP_HOST` no alerts are sent. Conditions are
+//! checked every `ALERT_CHECK_INTERVAL_SECONDS` (default 60).
+
+use std::{env, time::Duration};
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{Mailbox, header::ContentType},
+    transport::smtp::authentication::Credentials,
+};
+use sqlx::PgPool;
+use tokio::time::{self, MissedTickBehavior};
+
+use crate::{
+    AppState,
+    env::{flag_env, non_empty_env},
+    events::collect_server_status,
+    feature_flags::Feature,
+};
+
+/// Set by the settings endpoint: whether operators want auto distribution on.
+const AUTO_EXPECTED_KEY: &str = "auto-distribution-expected";
+const DEAD_LETTER_COUNT_KEY: &str = "dead-letter-count";
+
+#[derive(Debug, Clone, Copy)]
+enum SmtpTls {
+    StartTls,
+    Implicit,
+    None,
+}
+
+#[derive(Debug, Clone)]
+struct AlertRules {
+    backlog_threshold: i64,
+    backlog_minutes: i64,
+    dead_letter: bool,
+    auto_distribution: bool,
+    interval: Duration,
+}
+
+#[derive(Clone)]
+pub(crate) struct EmailAlerts {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    host: String,
+    rules: AlertRules,
+}
+
+impl EmailAlerts {
+    /// Returns `Ok(None)` when `SMTP_HOST` is not set.
+    pub(crate) fn from_env() -> Result<Option<Self>> {
+        let Some(host) = non_empty_env("SMTP_HOST") else {
+            return Ok(None);
+        };
+        let from: Mailbox = non_empty_env("SMTP_FROM")
+            .context("SMTP_FROM is required when SMTP_HOST is set")?
+            .parse()
+            .context("SMTP_FROM is not a valid address")?;
+        let tls = match non_empty_env("SMTP_TLS")
+            .map(|value| value.to_ascii_lowercase())
+            .as_deref()
+        {
+            None | Some("starttls") => SmtpTls::StartTls,
+            Some("tls") => SmtpTls::Implicit,
+            Some("none") => SmtpTls::None,
+            Some(other) => bail!("Unknown SMTP_TLS mode {other}"),
+        };
+        let mut builder = match tls {
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)
+                .context("Invalid SMTP_HOST")?,
+            SmtpTls::Implicit => {
+                AsyncSmtpTransport::<Tokio1Executor>::relay(&host).context("Invalid SMTP_HOST")?
+            }
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host),
+        };
+        if let Some(port) = non_empty_env("SMTP_PORT") {
+            builder = builder.port(port.parse().context("SMTP_PORT must be a port number")?);
+        }
+        if let Some(username) = non_empty_env("SMTP_USERNAME") {
+            let password = env::var("SMTP_PASSWORD").unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        let rules = AlertRules {
+            backlog_threshold: number_env("ALERT_BACKLOG_THRESHOLD", 0)?,
+            backlog_minutes: number_env("ALERT_BACKLOG_MINUTES", 15)?.max(1),
+            dead_letter: flag_env("ALERT_DEAD_LETTER", true)?,
+            auto_distribution: flag_env("ALERT_AUTO_DISTRIBUTION", true)?,
+            interval: Duration::from_secs(
+                number_env("ALERT_CHECK_INTERVAL_SECONDS", 60)?.max(1) as u64
+            ),
+        };
+        Ok(Some(Self {
+            transport: builder.timeout(Some(Duration::from_secs(15))).build(),
+            from,
+            host,
+            rules,
+        }))
+    }
+
+    /// One-line summary for the startup log.
+    pub(crate) fn describe(&self) -> String {
+        format!(
+            "SMTP {}, backlog rule {}, dead-letter rule {}, auto distribution rule {}",
+            self.host,
+            if self.rules.backlog_threshold > 0 {
+                format!(
+                    "> {} for {} min",
+                    self.rules.backlog_threshold, self.rules.backlog_minutes
+                )
+            } else {
+                "off".to_string()
+            },
+            if self.rules.dead_letter { "on" } else { "off" },
+            if self.rules.auto_distribution {
+                "on"
+            } else {
+                "off"
+            }
+        )
+    }
+
+    /// Sends one message to every recipient. Returns how many it went to.
+    pub(crate) async fn send(&self, pool: &PgPool, subject: &str, body: &str) -> Result<usize> {
+        let recipients = list_recipients(pool).await?;
+        if recipients.is_empty() {
+            println!("[email] No recipients configured, dropped alert: {subject}");
+            return Ok(0);
+        }
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .subject(format!("[chase-linker] {subject}"))
+            .header(ContentType::TEXT_PLAIN);
+        for recipient in &recipients {
+            builder = builder.to(recipient
+                .parse()
+                .with_context(|| format!("Invalid recipient {recipient}"))?);
+        }
+        let message = builder
+            .body(body.to_string())
+            .context("Failed to build email")?;
+        self.transport
+            .send(message)
+            .await
+            .context("Failed to send email")?;
+        println!(
+            "[email] Sent \"{subject}\" to {} recipient(s)",
+            recipients.len()
+        );
+        Ok(recipients.len())
+    }
+}
+
+pub(crate) fn normalize_address(email: &str) -> Result<String, String> {
+    let email = email.trim().to_ascii_lowercase();
+    email
+        .parse::<lettre::Address>()
+        .map(|_| email.clone())
+        .map_err(|_| format!("{email} is not a valid email address"))
+}
+
+async fn list_recipients(pool: &PgPool) -> Result<Vec<String>> {
+    sqlx::query_scalar(r#"SELECT "email" FROM "EmailRecipient" ORDER BY "email""#)
+        .fetch_all(pool)
+        .await
+        .context("Failed to load email recipients")
+}
+
+async fn read_state(pool: &PgPool, key: &str) -> Result<Option<i64>> {
+    sqlx::query_scalar(r#"SELECT "value" FROM "AlertState" WHERE "key" = $1"#)
+        .bind(key)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to read alert state")
+}
+
+async fn write_state(pool: &PgPool, key: &str, value: i64) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO "AlertState" ("key", "value")
+        VALUES ($1, $2)
+        ON CONFLICT ("key") DO UPDATE
+        SET "value" = EXCLUDED."value", "updatedAt" = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(key)
+    .bind(value)
+    .execute(pool)
+    .await
+    .context("Failed to write alert state")?;
+    Ok(())
+}
+
+/// Remembers whether operators turned auto distribution on, so a restart
+/// that comes up with it off is reported.
+pub(crate) async fn record_auto_distribution_expected(pool: &PgPool, enabled: bool) {
+    if let Err(err) = write_state(pool, AUTO_EXPECTED_KEY, i64::from(enabled)).await {
+        eprintln!("[email] {err:#}");
+    }
+}
+
+/// What has already been reported, so each incident is mailed once.
+#[derive(Debug, Default)]
+struct Incidents {
+    backlog_since: Option<DateTime<Utc>>,
+    backlog_reported: bool,
+    auto_reported: bool,
+}
+
+pub(crate) async fn alert_worker(state: AppState) {
+    let Some(alerts) = state.email.clone() else {
+        return;
+    };
+    let mut interval = time::interval(alerts.rules.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut incidents = Incidents::default();
+
+    loop {
+        interval.tick().await;
+        if !state.feature_flags.is_enabled(Feature::EmailAlerts).await {
+            continue;
+        }
+        if let Err(err) = check(&state, &alerts, &mut incidents).await {
+            eprintln!("[email] {err:#}");
+        }
+    }
+}
+
+async fn check(state: &AppState, alerts: &EmailAlerts, incidents: &mut Incidents) -> Result<()> {
+    let rules = &alerts.rules;
+    let now = Utc::now();
+
+    if rules.backlog_threshold > 0 {
+        let backlog: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)::bigint
+            FROM "Payout"
+            WHERE "direction" = 'OUT' AND "status" = 'CREATED' AND "traderId" IS NULL
+            "#,
+        )
+        .fetch_one(&state.pool)
+        .await
+        .context("Failed to count the backlog")?;
+        if backlog > rules.backlog_threshold {
+            let since = *incidents.backlog_since.get_or_insert(now);
+            if !incidents.backlog_reported
+                && now - since >= chrono::Duration::minutes(rules.backlog_minutes)
+            {
+                alerts
+                    .send(
+                        &state.pool,
+                        "Unassigned backlog is growing",
+                        &format!(
+                            "{backlog} payouts are waiting for a trader, above the threshold of {} since {} UTC.",
+                            rules.backlog_threshold,
+                            since.format("%Y-%m-%d %H:%M")
+                        ),
+                    )
+                    .await?;
+                incidents.backlog_reported = true;
+            }
+        } else {
+            incidents.backlog_since = None;
+            incidents.backlog_reported = false;
+        }
+    }
+
+    if rules.dead_letter {
+        let failed: i64 = sqlx::query_scalar(
+            r#"SELECT COUNT(*)::bigint FROM "OutboxMessage" WHERE "kind" = 'callback' AND "status" = 'failed'"#,
+        )
+        .fetch_one(&state.pool)
+        .await
+        .context("Failed to count dead-letter callbacks")?;
+        let previous = read_state(&state.pool, DEAD_LETTER_COUNT_KEY).await?;
+        if let Some(previous) = previous
+            && failed > previous
+        {
+            alerts
+                .send(
+                    &state.pool,
+                    "Merchant callbacks failed",
+                    &format!(
+                        "{} more callback(s) gave up after all retries, {failed} in the dead-letter queue now. Retry them from /api/callbacks/dead-letter.",
+                        failed - previous
+                    ),
+                )
+                .await?;
+        }
+        if previous != Some(failed) {
+            write_state(&state.pool, DEAD_LETTER_COUNT_KEY, failed).await?;
+        }
+    }
+
+    if rules.auto_distribution {
+        let expected = read_state(&state.pool, AUTO_EXPECTED_KEY).await? == Some(1);
+        let status = collect_server_status(state).await;
+        let running = status.worker.enabled && !status.worker.stalled;
+        if expected && !running {
+            if !incidents.auto_reported {
+                let reason = if status.worker.enabled {
+                    "the distribution worker has stalled"
+                } else {
+                    "it is switched off, most likely after a restart"
+                };
+                alerts
+                    .send(
+                        &state.pool,
+                        "Auto distribution stopped",
+                        &format!(
+                            "Auto distribution was enabled by an operator, but {reason}. Check /api/settings/auto-distribution."
+                        ),
+                    )
+                    .await?;
+                incidents.auto_reported = true;
+            }
+        } else {
+            incidents.auto_reported = false;
+        }
+    }
+
+    Ok(())
+}
+
+fn number_env(name: &str, default: i64) -> Result<i64> {
+    match non_empty_env(name) {
+        Some(value) => value
+            .parse::<i64>()
+            .ok()
+            .filter(|value| *value >= 0)
+            .with_context(|| format!("{name} must be a non-negative integer")),
+        None => Ok(default),
+    }
+}
@@ -0,0 +1,34 @@
+//! Reading configuration from the environment. Blank variables count as
+//! unset, and every on/off switch accepts the same words, so
+//! `FEATURE_FLAGS=audit=yes` and `OPERATOR_LOGIN=on` mean what they say.
+
+use anyhow::{Result, bail};
+
+/// The trimmed value of `name`, `None` when it is unset or blank.
+pub(crate) fn non_empty_env(name: &str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// The switch `name`, `default` when it is unset or blank. Anything other
+/// than the words [`parse_flag`] knows is a configuration error.
+pub(crate) fn flag_env(name: &str, default: bool) -> Result<bool> {
+    let Some(value) = non_empty_env(name) else {
+        return Ok(default);
+    };
+    match parse_flag(&value) {
+        Some(enabled) => Ok(enabled),
+        None => bail!("{name} must be on or off, got {value}"),
+    }
+}
+
+/// `true|1|yes|on` or `false|0|no|off`, ignoring case.
+pub(crate) fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Some(true),
+        "false" | "0" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
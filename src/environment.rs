@@ -0,0 +1,160 @@
+//! Which deployment this is. `APP_ENVIRONMENT` names it (`prod` or
+//! `production`, `stage` or `staging`, or any other label such as `dev`);
+//! the dashboard shows the label in a banner across the top so nobody
+//! mistakes production for a test stand. Unset, no banner is shown.
+//!
+//! Production also turns on safety locks for actions that are easy to fire
+//! by mistake against live payouts. They are refused server-side with
+//! `423 ENVIRONMENT_LOCKED` unless listed in `SAFETY_UNLOCK`
+//! (comma-separated, or `all`):
+//!
+//! - `bulk-cancel`: `POST /api/payouts/bulk-cancel`;
+//! - `auto-cancel`: the `auto_cancel` policy does not run;
+//! - `config-import`: `POST /api/config/import`.
+
+use std::collections::BTreeSet;
+
+use anyhow::{Result, bail};
+use axum::http::StatusCode;
+use serde::Serialize;
+
+use crate::{
+    ApiResult,
+    env::non_empty_env,
+    errors::{ApiError, ErrorCode},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum SafetyLock {
+    BulkCancel,
+    AutoCancel,
+    ConfigImport,
+}
+
+impl SafetyLock {
+    const ALL: [Self; 3] = [Self::BulkCancel, Self::AutoCancel, Self::ConfigImport];
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::BulkCancel => "bulk-cancel",
+            Self::AutoCancel => "auto-cancel",
+            Self::ConfigImport => "config-import",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|lock| lock.as_str() == value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum EnvironmentKind {
+    Production,
+    Staging,
+    Other,
+}
+
+impl EnvironmentKind {
+    pub(crate) fn code(self) -> &'static str {
+        match self {
+            Self::Production => "production",
+            Self::Staging => "staging",
+            Self::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Environment {
+    label: Option<String>,
+    kind: EnvironmentKind,
+    /// Locks in force; only production has any.
+    locks: BTreeSet<SafetyLock>,
+}
+
+impl Environment {
+    pub(crate) fn from_env() -> Result<Self> {
+        let label = non_empty_env("APP_ENVIRONMENT");
+        let kind = match label.as_deref().map(str::to_ascii_lowercase).as_deref() {
+            Some("prod" | "production") => EnvironmentKind::Production,
+            Some("stage" | "staging") => EnvironmentKind::Staging,
+            _ => EnvironmentKind::Other,
+        };
+        let mut unlocked = BTreeSet::new();
+        for value in non_empty_env("SAFETY_UNLOCK")
+            .unwrap_or_default()
+            .split(',')
+            .map(|value| value.trim().to_ascii_lowercase())
+            .filter(|value| !value.is_empty())
+        {
+            if value == "all" {
+                unlocked.extend(SafetyLock::ALL);
+                continue;
+            }
+            match SafetyLock::parse(&value) {
+                Some(lock) => {
+                    unlocked.insert(lock);
+                }
+                None => bail!(
+                    "SAFETY_UNLOCK has unknown lock {value}, expected all, bulk-cancel, auto-cancel or config-import"
+                ),
+            }
+        }
+        let locks = if kind == EnvironmentKind::Production {
+            SafetyLock::ALL
+                .into_iter()
+                .filter(|lock| !unlocked.contains(lock))
+                .collect()
+        } else {
+            BTreeSet::new()
+        };
+        Ok(Self { label, kind, locks })
+    }
+
+    pub(crate) fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    pub(crate) fn kind(&self) -> EnvironmentKind {
+        self.kind
+    }
+
+    pub(crate) fn locks(&self) -> impl Iterator<Item = SafetyLock> + '_ {
+        self.locks.iter().copied()
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        let label = self.label.as_deref().unwrap_or("-");
+        if self.kind != EnvironmentKind::Production {
+            return format!("{label}, no safety locks");
+        }
+        if self.locks.is_empty() {
+            return format!("{label}, all safety locks unlocked by SAFETY_UNLOCK");
+        }
+        let locks: Vec<&str> = self.locks.iter().map(|lock| lock.as_str()).collect();
+        format!("{label}, locked: {}", locks.join(", "))
+    }
+
+    pub(crate) fn is_locked(&self, lock: SafetyLock) -> bool {
+        self.locks.contains(&lock)
+    }
+
+    pub(crate) fn ensure_unlocked(&self, lock: SafetyLock) -> ApiResult<()> {
+        if !self.is_locked(lock) {
+            return Ok(());
+        }
+        Err(ApiError::new(
+            StatusCode::LOCKED,
+            ErrorCode::EnvironmentLocked,
+            format!(
+                "{} is locked on {}, set SAFETY_UNLOCK={} to allow it",
+                lock.as_str(),
+                self.label.as_deref().unwrap_or("production"),
+                lock.as_str()
+            ),
+        ))
+    }
+}
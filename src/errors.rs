@@ -0,0 +1,140 @@
+//! API errors. Every failed API call answers with the same JSON envelope,
+//! `{"error": {"code": "PAYOUT_NOT_ELIGIBLE", "message": "..."}}`, so the
+//! dashboard and integrators branch on the stable `code` instead of parsing
+//! the message, which is free text and may change or be translated.
+//!
+//! Failures without a dedicated [`ErrorCode`] get the generic code of their
+//! HTTP status (`BAD_REQUEST`, `NOT_FOUND`, ...). Codes are only ever added;
+//! a published code keeps its meaning.
+
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub(crate) enum ErrorCode {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict,
+    Unprocessable,
+    Unavailable,
+    UpstreamFailed,
+    Internal,
+    PayoutNotFound,
+    /// The payout is no longer open for (re)assignment.
+    PayoutNotEligible,
+    PayoutAlreadyCancelled,
+    /// The payout's status does not allow cancelling it, see
+    /// `payout_transitions`.
+    PayoutNotCancellable,
+    /// The payout's `updatedAt` is not the one the caller expected, see
+    /// `payout_version`.
+    PayoutModified,
+    /// The payout's amount needs a second operator to cancel it, see
+    /// `cancel_approval`.
+    CancelApprovalRequired,
+    /// A cancellation request for the payout already awaits approval.
+    CancelApprovalPending,
+    /// Any other status change the payout's status does not allow.
+    InvalidTransition,
+    TraderNotFound,
+    /// The trader may not take the payout: banned, traffic off, paused, not
+    /// working with its merchant, or the amount limit or currencies exclude it.
+    TraderIneligible,
+    InsufficientBalance,
+    /// A safety lock of the production environment refuses the action, see
+    /// `environment`.
+    EnvironmentLocked,
+    /// A mutating request came from a foreign origin or without the
+    /// session's CSRF token, see `csrf`.
+    CsrfRejected,
+}
+
+impl ErrorCode {
+    /// The generic code for an HTTP status.
+    fn for_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::BAD_REQUEST => Self::BadRequest,
+            StatusCode::UNAUTHORIZED => Self::Unauthorized,
+            StatusCode::FORBIDDEN => Self::Forbidden,
+            StatusCode::NOT_FOUND => Self::NotFound,
+            StatusCode::CONFLICT => Self::Conflict,
+            StatusCode::UNPROCESSABLE_ENTITY => Self::Unprocessable,
+            StatusCode::SERVICE_UNAVAILABLE => Self::Unavailable,
+            StatusCode::BAD_GATEWAY => Self::UpstreamFailed,
+            _ => Self::Internal,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ApiError {
+    pub(crate) status: StatusCode,
+    pub(crate) code: ErrorCode,
+    pub(crate) message: String,
+}
+
+impl ApiError {
+    pub(crate) fn new(status: StatusCode, code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn payout_not_found() -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::PayoutNotFound,
+            "Payout not found",
+        )
+    }
+
+    pub(crate) fn trader_not_found() -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::TraderNotFound,
+            "Trader not found",
+        )
+    }
+
+    pub(crate) fn trader_ineligible(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, ErrorCode::TraderIneligible, message)
+    }
+}
+
+impl From<(StatusCode, String)> for ApiError {
+    fn from((status, message): (StatusCode, String)) -> Self {
+        Self::new(status, ErrorCode::for_status(status), message)
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorEnvelope<'a> {
+    error: ErrorBody<'a>,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    code: ErrorCode,
+    message: &'a str,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let envelope = ErrorEnvelope {
+            error: ErrorBody {
+                code: self.code,
+                message: &self.message,
+            },
+        };
+        (self.status, Json(envelope)).into_response()
+    }
+}
@@ -0,0 +1,59 @@
+//! Conditional GETs for the lists the dashboard reloads after every SSE event.
+//! Responses carry a strong `ETag` (a hash of the serialized body) and
+//! `Cache-Control: no-cache`, so browsers revalidate each time and get an
+//! empty `304 Not Modified` while the list is unchanged.
+
+use axum::{
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::internal_error;
+
+/// A JSON body answered with 304 when the request already holds it.
+pub(crate) struct ETagged<T> {
+    if_none_match: Option<HeaderValue>,
+    value: T,
+}
+
+impl<T> ETagged<T> {
+    pub(crate) fn new(headers: &HeaderMap, value: T) -> Self {
+        Self {
+            if_none_match: headers.get(header::IF_NONE_MATCH).cloned(),
+            value,
+        }
+    }
+}
+
+fn matches(if_none_match: &HeaderValue, etag: &str) -> bool {
+    if_none_match.to_str().is_ok_and(|value| {
+        value
+            .split(',')
+            .map(|candidate| candidate.trim().trim_start_matches("W/"))
+            .any(|candidate| candidate == "*" || candidate == etag)
+    })
+}
+
+impl<T: Serialize> IntoResponse for ETagged<T> {
+    fn into_response(self) -> Response {
+        let body = match serde_json::to_vec(&self.value) {
+            Ok(body) => body,
+            Err(err) => return internal_error(err).into_response(),
+        };
+        let digest = Sha256::digest(&body);
+        let etag = format!("\"{}\"", hex::encode(&digest[..16]));
+        let headers = [
+            (header::ETAG, etag.clone()),
+            (header::CACHE_CONTROL, "no-cache".to_string()),
+        ];
+        if self
+            .if_none_match
+            .is_some_and(|value| matches(&value, &etag))
+        {
+            return (StatusCode::NOT_MODIFIED, headers).into_response();
+        }
+        (headers, [(header::CONTENT_TYPE, "application/json")], body).into_response()
+    }
+}
@@ -0,0 +1,623 @@
+//! Server-sent events for the dashboard and the status report behind
+//! `/api/status`.
+//!
+//! Every event makes every open dashboard refetch its tables, so events are
+//! not streamed as sent: [`coalesce_worker`] holds them for `SSE_COALESCE_MS`
+//! (default 500, `0` streams them as they come), drops repeats of an identical
+//! event and numbers the rest with a growing `version`. The dashboard skips
+//! events no newer than the data it already loaded.
+//!
+//! The version doubles as the SSE event id. The last [`REPLAY_EVENTS`] events
+//! are kept, so a client reconnecting with `Last-Event-ID` (or
+//! `?lastEventId=`, for clients that open a new `EventSource`) gets what it
+//! missed; when the gap is no longer covered, or the server restarted, it gets
+//! a `resync` event and reloads everything instead.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    convert::Infallible,
+    env,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::HeaderMap,
+    response::sse::{Event as SseEvent, KeepAlive, Sse},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time::{self, Instant, MissedTickBehavior},
+};
+use tokio_stream::{StreamExt, wrappers::BroadcastStream};
+
+use crate::{
+    AppState, assignment_latency::LatencyStatus, callbacks::CALLBACK_QUEUE_COUNTS_QUERY,
+    db::eligible_trader_snapshot, tenant::TenantScope,
+};
+
+pub(crate) const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+pub(crate) const TRADER_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(10);
+/// Recent events kept for clients resuming with `Last-Event-ID`.
+const REPLAY_EVENTS: usize = 256;
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ServerEvent {
+    #[serde(rename = "type")]
+    pub(crate) event_type: String,
+    pub(crate) message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) data: Option<Value>,
+    #[serde(skip)]
+    pub(crate) audience: EventAudience,
+    /// Set by [`EventStream`]; heartbeats carry none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) version: Option<u64>,
+}
+
+/// Which SSE subscribers an event is delivered to.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum EventAudience {
+    #[default]
+    All,
+    /// Only tenants whose allowlist contains one of these merchants.
+    Merchants(Vec<String>),
+    /// Cross-tenant information such as distribution cycle totals.
+    Unrestricted,
+}
+
+impl ServerEvent {
+    fn new(event_type: impl Into<String>, message: Option<String>) -> Self {
+        Self {
+            event_type: event_type.into(),
+            message,
+            data: None,
+            audience: EventAudience::All,
+            version: None,
+        }
+    }
+
+    pub(crate) fn for_merchants<I, S>(mut self, merchant_ids: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut ids: Vec<String> = merchant_ids.into_iter().map(Into::into).collect();
+        ids.sort();
+        ids.dedup();
+        self.audience = EventAudience::Merchants(ids);
+        self
+    }
+
+    fn heartbeat(status: &ServerStatus) -> Self {
+        Self {
+            event_type: "heartbeat".to_string(),
+            message: None,
+            data: serde_json::to_value(status).ok(),
+            audience: EventAudience::All,
+            version: None,
+        }
+    }
+
+    fn is_heartbeat(&self) -> bool {
+        self.event_type == "heartbeat"
+    }
+
+    /// Tells a resuming client that the missed events are gone.
+    fn resync(version: u64) -> Self {
+        Self {
+            version: Some(version),
+            ..Self::new("resync", None)
+        }
+    }
+
+    fn is_repeat_of(&self, other: &ServerEvent) -> bool {
+        self.event_type == other.event_type
+            && self.message == other.message
+            && self.data == other.data
+            && self.audience == other.audience
+    }
+
+    pub(crate) fn payouts_updated(source: &str) -> Self {
+        Self::new("payouts-updated", Some(format!("source={}", source)))
+    }
+
+    pub(crate) fn settings_updated() -> Self {
+        Self::new("settings-updated", None)
+    }
+
+    pub(crate) fn limits_updated() -> Self {
+        Self::new("limits-updated", None)
+    }
+
+    pub(crate) fn traders_updated() -> Self {
+        Self::new("traders-updated", None)
+    }
+
+    /// Sent by the trader snapshot worker; only tenants serving one of the
+    /// affected traders' merchants receive it.
+    fn eligible_traders_changed(
+        added: &[String],
+        removed: &[String],
+        merchants: Vec<String>,
+    ) -> Self {
+        Self {
+            data: Some(serde_json::json!({ "added": added, "removed": removed })),
+            ..Self::new(
+                "traders-updated",
+                Some(format!("added={}, removed={}", added.len(), removed.len())),
+            )
+        }
+        .for_merchants(merchants)
+    }
+
+    pub(crate) fn cancel_reasons_updated() -> Self {
+        Self::new("cancel-reasons-updated", None)
+    }
+
+    pub(crate) fn callback_sla_breached(merchant_id: &str, data: Value) -> Self {
+        Self {
+            data: Some(data),
+            ..Self::new(
+                "callback-sla-breached",
+                Some(format!("merchant={merchant_id}")),
+            )
+        }
+        .for_merchants([merchant_id])
+    }
+
+    pub(crate) fn callback_sla_recovered(merchant_id: &str, data: Value) -> Self {
+        Self {
+            data: Some(data),
+            ..Self::new(
+                "callback-sla-recovered",
+                Some(format!("merchant={merchant_id}")),
+            )
+        }
+        .for_merchants([merchant_id])
+    }
+
+    /// A cancellation awaits a second operator, see `cancel_approval`.
+    pub(crate) fn cancel_approval_requested(merchant_id: Option<String>, data: Value) -> Self {
+        Self {
+            data: Some(data),
+            ..Self::new("cancel-approval-requested", None)
+        }
+        .for_merchants(merchant_id)
+    }
+
+    /// A cancellation request was approved, rejected or expired.
+    pub(crate) fn cancel_approval_closed(merchant_id: Option<String>, data: Value) -> Self {
+        Self {
+            data: Some(data),
+            ..Self::new("cancel-approval-closed", None)
+        }
+        .for_merchants(merchant_id)
+    }
+
+    /// A high-volume trader left the eligible set, see `eligibility_history`.
+    pub(crate) fn trader_eligibility_dropped(merchant_ids: Vec<String>, data: Value) -> Self {
+        Self {
+            data: Some(data),
+            ..Self::new("trader-eligibility-dropped", None)
+        }
+        .for_merchants(merchant_ids)
+    }
+
+    /// Assignment latency is measured across all merchants, see
+    /// `assignment_latency`.
+    pub(crate) fn assignment_slo_breached(data: Value) -> Self {
+        Self {
+            data: Some(data),
+            audience: EventAudience::Unrestricted,
+            ..Self::new("assignment-slo-breached", None)
+        }
+    }
+
+    pub(crate) fn assignment_slo_recovered(data: Value) -> Self {
+        Self {
+            data: Some(data),
+            audience: EventAudience::Unrestricted,
+            ..Self::new("assignment-slo-recovered", None)
+        }
+    }
+
+    /// A settings change awaits a second admin, see `settings_approval`.
+    pub(crate) fn settings_change_requested(data: Value) -> Self {
+        Self {
+            data: Some(data),
+            audience: EventAudience::Unrestricted,
+            ..Self::new("settings-change-requested", None)
+        }
+    }
+
+    /// A pending settings change was approved or rejected.
+    pub(crate) fn settings_change_closed(data: Value) -> Self {
+        Self {
+            data: Some(data),
+            audience: EventAudience::Unrestricted,
+            ..Self::new("settings-change-closed", None)
+        }
+    }
+
+    pub(crate) fn distribution_cycle(assigned: usize, remaining: usize) -> Self {
+        Self {
+            audience: EventAudience::Unrestricted,
+            ..Self::new(
+                "distribution-cycle",
+                Some(format!("assigned={assigned}, remaining={remaining}")),
+            )
+        }
+    }
+}
+
+/// What SSE and gRPC subscribers read, fed by [`coalesce_worker`] from
+/// `AppState::event_tx`.
+#[derive(Debug, Clone)]
+pub(crate) struct EventStream {
+    tx: broadcast::Sender<ServerEvent>,
+    version: Arc<AtomicU64>,
+    /// Numbered events, oldest first.
+    recent: Arc<Mutex<VecDeque<ServerEvent>>>,
+    window: Duration,
+}
+
+impl EventStream {
+    pub(crate) fn from_env() -> Result<Self> {
+        let window_ms = match env::var("SSE_COALESCE_MS") {
+            Ok(value) if !value.trim().is_empty() => value
+                .trim()
+                .parse::<u64>()
+                .context("SSE_COALESCE_MS must be a non-negative integer")?,
+            _ => 500,
+        };
+        let (tx, _) = broadcast::channel(100);
+        Ok(Self {
+            tx,
+            version: Arc::new(AtomicU64::new(0)),
+            recent: Arc::new(Mutex::new(VecDeque::with_capacity(REPLAY_EVENTS))),
+            window: Duration::from_millis(window_ms),
+        })
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        if self.window.is_zero() {
+            "events are streamed as they come".to_string()
+        } else {
+            format!(
+                "identical events within {} ms are coalesced",
+                self.window.as_millis()
+            )
+        }
+    }
+
+    /// Subscribes and returns the events after `last_event_id` to replay
+    /// first. Taken under the replay lock, so nothing falls in between.
+    pub(crate) fn subscribe_since(
+        &self,
+        last_event_id: Option<u64>,
+    ) -> (broadcast::Receiver<ServerEvent>, Vec<ServerEvent>) {
+        let recent = self.recent.lock().unwrap_or_else(|err| err.into_inner());
+        let rx = self.tx.subscribe();
+        let version = self.version();
+        let replay = match last_event_id {
+            None => Vec::new(),
+            Some(last) if last == version => Vec::new(),
+            Some(last)
+                if last < version
+                    && recent
+                        .front()
+                        .and_then(|event| event.version)
+                        .is_some_and(|oldest| oldest <= last + 1) =>
+            {
+                recent
+                    .iter()
+                    .filter(|event| event.version.is_some_and(|version| version > last))
+                    .cloned()
+                    .collect()
+            }
+            // Older than the buffer, or from before a restart.
+            Some(_) => vec![ServerEvent::resync(version)],
+        };
+        (rx, replay)
+    }
+
+    pub(crate) fn receiver_count(&self) -> usize {
+        self.tx.receiver_count()
+    }
+
+    /// The version of the latest streamed event. Data read after this call
+    /// reflects at least every change announced up to it.
+    pub(crate) fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    fn publish(&self, mut event: ServerEvent) {
+        if event.is_heartbeat() {
+            let _ = self.tx.send(event);
+            return;
+        }
+        let mut recent = self.recent.lock().unwrap_or_else(|err| err.into_inner());
+        event.version = Some(self.version.fetch_add(1, Ordering::SeqCst) + 1);
+        if recent.len() == REPLAY_EVENTS {
+            recent.pop_front();
+        }
+        recent.push_back(event.clone());
+        let _ = self.tx.send(event);
+    }
+}
+
+/// Forwards events to the [`EventStream`]. The first event opens a window;
+/// events arriving within it are streamed together when it closes, each
+/// identical event once. Heartbeats pass straight through.
+pub(crate) async fn coalesce_worker(state: AppState) {
+    let stream = state.event_stream.clone();
+    let mut rx = state.event_tx.subscribe();
+
+    loop {
+        let first = match rx.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                eprintln!("[events] Coalescer lagged, {skipped} event(s) lost");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        if first.is_heartbeat() || stream.window.is_zero() {
+            stream.publish(first);
+            continue;
+        }
+
+        let deadline = Instant::now() + stream.window;
+        let mut pending = vec![first];
+        while let Ok(received) = time::timeout_at(deadline, rx.recv()).await {
+            match received {
+                Ok(event) if event.is_heartbeat() => stream.publish(event),
+                Ok(event) => {
+                    if !pending.iter().any(|held| held.is_repeat_of(&event)) {
+                        pending.push(event);
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!("[events] Coalescer lagged, {skipped} event(s) lost");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+        for event in pending {
+            stream.publish(event);
+        }
+    }
+}
+
+/// Updated by the auto distribution worker on every tick.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WorkerStatus {
+    pub(crate) next_tick_at: Option<DateTime<Utc>>,
+    pub(crate) last_cycle_at: Option<DateTime<Utc>>,
+    pub(crate) in_schedule: bool,
+    /// When the running worker task was spawned.
+    pub(crate) started_at: Option<DateTime<Utc>>,
+    pub(crate) restarts: u32,
+    /// Set while a cycle runs.
+    pub(crate) cycle_started_at: Option<DateTime<Utc>>,
+    pub(crate) last_error: Option<String>,
+    pub(crate) last_error_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WorkerStatusView {
+    pub(crate) enabled: bool,
+    in_schedule: bool,
+    interval_seconds: u64,
+    next_tick_at: Option<DateTime<Utc>>,
+    last_cycle_at: Option<DateTime<Utc>>,
+    pub(crate) stalled: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DatabaseStatus {
+    ok: bool,
+    size: u32,
+    idle: usize,
+    max_connections: u32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ServerStatus {
+    server_time: DateTime<Utc>,
+    degraded: bool,
+    pub(crate) worker: WorkerStatusView,
+    database: DatabaseStatus,
+    pending_callback_retries: Option<i64>,
+    dead_letter_callbacks: Option<i64>,
+    /// Over the SLO window, `None` until the first check.
+    assignment_latency: Option<LatencyStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct EventsQuery {
+    last_event_id: Option<u64>,
+}
+
+pub(crate) async fn events(
+    Query(query): Query<EventsQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+    headers: HeaderMap,
+) -> Sse<impl tokio_stream::Stream<Item = Result<SseEvent, Infallible>>> {
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .or(query.last_event_id);
+    let event_stream = state.event_stream.clone();
+    let (rx, replay) = event_stream.subscribe_since(last_event_id);
+    let live = BroadcastStream::new(rx).map(move |result| match result {
+        Ok(event) => event,
+        Err(err) => {
+            eprintln!("SSE subscriber lagged: {err}");
+            ServerEvent::resync(event_stream.version())
+        }
+    });
+    let stream = tokio_stream::iter(replay)
+        .chain(live)
+        .filter_map(move |event| {
+            if !scope.can_receive(&event.audience) {
+                return None;
+            }
+            let mut sse = SseEvent::default();
+            if let Some(version) = event.version {
+                sse = sse.id(version.to_string());
+            }
+            match sse.json_data(event) {
+                Ok(evt) => Some(Ok(evt)),
+                Err(err) => {
+                    eprintln!("Failed to serialize SSE event: {err}");
+                    None
+                }
+            }
+        });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+pub(crate) async fn get_server_status(State(state): State<AppState>) -> Json<ServerStatus> {
+    Json(collect_server_status(&state).await)
+}
+
+/// Broadcasts a `heartbeat` event so the dashboard can tell a degraded
+/// backend apart from a dropped SSE connection.
+pub(crate) async fn heartbeat_task(state: AppState) {
+    let mut interval = time::interval(HEARTBEAT_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+        if state.event_stream.receiver_count() == 0 {
+            continue;
+        }
+        let status = collect_server_status(&state).await;
+        if status.degraded {
+            eprintln!(
+                "[status] Backend degraded: database ok={}, worker stalled={}",
+                status.database.ok, status.worker.stalled
+            );
+        }
+        let _ = state.event_tx.send(ServerEvent::heartbeat(&status));
+    }
+}
+
+/// Watches the eligible-trader set and emits `traders-updated` when a trader
+/// becomes eligible or drops out, so dashboards don't have to poll
+/// `/api/traders`. Nothing is queried while no one is subscribed; the first
+/// snapshot after that only sets the baseline.
+pub(crate) async fn trader_snapshot_worker(state: AppState) {
+    let mut interval = time::interval(TRADER_SNAPSHOT_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut previous: Option<BTreeMap<String, Vec<String>>> = None;
+
+    loop {
+        interval.tick().await;
+        if state.event_stream.receiver_count() == 0 {
+            previous = None;
+            continue;
+        }
+        let current = match eligible_trader_snapshot(&state.pool).await {
+            Ok(current) => current,
+            Err(err) => {
+                eprintln!("[traders] Failed to snapshot eligible traders: {err}");
+                continue;
+            }
+        };
+        if let Some(previous) = previous.as_ref() {
+            let added: Vec<String> = current
+                .keys()
+                .filter(|id| !previous.contains_key(*id))
+                .cloned()
+                .collect();
+            let removed: Vec<String> = previous
+                .keys()
+                .filter(|id| !current.contains_key(*id))
+                .cloned()
+                .collect();
+            if !added.is_empty() || !removed.is_empty() {
+                let merchants: Vec<String> = added
+                    .iter()
+                    .filter_map(|id| current.get(id))
+                    .chain(removed.iter().filter_map(|id| previous.get(id)))
+                    .flatten()
+                    .cloned()
+                    .collect();
+                println!(
+                    "[traders] Eligible set changed: {} added, {} removed",
+                    added.len(),
+                    removed.len()
+                );
+                let _ = state.event_tx.send(ServerEvent::eligible_traders_changed(
+                    &added, &removed, merchants,
+                ));
+            }
+        }
+        previous = Some(current);
+    }
+}
+
+pub(crate) async fn collect_server_status(state: &AppState) -> ServerStatus {
+    let now = Utc::now();
+    let config = state.settings.auto_config();
+    let worker = state.distributor.worker_status().await;
+
+    // A tick that is overdue by more than one full interval means the worker
+    // is stuck inside a cycle.
+    let stalled = config.enabled
+        && worker.next_tick_at.is_some_and(|next| {
+            now - next > chrono::Duration::seconds(config.interval_seconds.max(1) as i64)
+        });
+
+    let callback_counts = time::timeout(
+        Duration::from_secs(2),
+        sqlx::query_as::<_, (i64, i64)>(CALLBACK_QUEUE_COUNTS_QUERY).fetch_one(&state.pool),
+    )
+    .await
+    .ok()
+    .and_then(|result| result.ok());
+
+    let database = DatabaseStatus {
+        ok: callback_counts.is_some(),
+        size: state.pool.size(),
+        idle: state.pool.num_idle(),
+        max_connections: state.pool.options().get_max_connections(),
+    };
+
+    ServerStatus {
+        server_time: now,
+        degraded: !database.ok || stalled,
+        worker: WorkerStatusView {
+            enabled: config.enabled,
+            in_schedule: worker.in_schedule,
+            interval_seconds: config.interval_seconds,
+            next_tick_at: worker.next_tick_at.filter(|_| config.enabled),
+            last_cycle_at: worker.last_cycle_at,
+            stalled,
+        },
+        database,
+        pending_callback_retries: callback_counts.map(|(pending, _)| pending),
+        dead_letter_callbacks: callback_counts.map(|(_, dead)| dead),
+        assignment_latency: state.assignment_slo.status(),
+    }
+}
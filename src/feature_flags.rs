@@ -0,0 +1,295 @@
+//! Runtime feature flags, so risky behaviors can be switched per environment
+//! without a redeploy. Each [`Feature`] has a built-in default that
+//! `FEATURE_FLAGS` can change for the environment (`balance-freeze=off,
+//! email-alerts=on`); an operator override stored in `FeatureFlag` wins over
+//! both.
+//!
+//! Flags are read from an in-memory cache. It is updated right away on a
+//! change through `POST /api/feature-flags/:key` and reloaded every
+//! `REFRESH_INTERVAL`, so other instances follow within that time.
+//! `GET /api/feature-flags` lists every flag with its effective state.
+
+use std::{collections::HashMap, env, sync::Arc, time::Duration};
+
+use anyhow::{Context, Result, bail};
+use axum::{
+    Json,
+    extract::{Extension, Path, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use tokio::{
+    sync::RwLock,
+    time::{self, MissedTickBehavior},
+};
+use tower_sessions::Session;
+
+use crate::{
+    ApiResult, AppState, auth, env::parse_flag, errors::ApiError, events::ServerEvent,
+    internal_error, settings::audit_change, tenant::TenantScope, timestamps::UtcTimestamp,
+};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Feature {
+    /// Freezing assigned amounts on the trader's balance, see `freeze`.
+    BalanceFreeze,
+    /// Backlog and failure alerts by email, see `email_alerts`.
+    EmailAlerts,
+    /// The scheduled daily digest, see `digest`.
+    DailyDigest,
+}
+
+impl Feature {
+    pub(crate) const ALL: [Feature; 3] = [
+        Feature::BalanceFreeze,
+        Feature::EmailAlerts,
+        Feature::DailyDigest,
+    ];
+
+    pub(crate) fn key(self) -> &'static str {
+        match self {
+            Self::BalanceFreeze => "balance-freeze",
+            Self::EmailAlerts => "email-alerts",
+            Self::DailyDigest => "daily-digest",
+        }
+    }
+
+    fn parse(key: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|feature| feature.key() == key.trim())
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::BalanceFreeze => {
+                "Freeze assigned amounts on the trader balance when freezeOnAssign is set"
+            }
+            Self::EmailAlerts => "Send backlog and failure alerts by email",
+            Self::DailyDigest => "Send the scheduled daily digest",
+        }
+    }
+
+    /// Off for behaviors that have to be switched on deliberately.
+    fn built_in_default(self) -> bool {
+        match self {
+            Self::BalanceFreeze | Self::EmailAlerts | Self::DailyDigest => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct FlagRow {
+    key: String,
+    enabled: bool,
+    #[sqlx(rename = "updatedBy")]
+    updated_by: Option<String>,
+    #[sqlx(rename = "updatedAt")]
+    updated_at: UtcTimestamp,
+}
+
+#[derive(Clone)]
+pub(crate) struct FeatureFlags {
+    pool: PgPool,
+    defaults: HashMap<Feature, bool>,
+    overrides: Arc<RwLock<HashMap<Feature, FlagRow>>>,
+}
+
+impl FeatureFlags {
+    /// Reads the environment defaults from `FEATURE_FLAGS`.
+    pub(crate) fn from_env(pool: PgPool) -> Result<Self> {
+        let mut defaults: HashMap<Feature, bool> = Feature::ALL
+            .into_iter()
+            .map(|feature| (feature, feature.built_in_default()))
+            .collect();
+        if let Ok(value) = env::var("FEATURE_FLAGS") {
+            for entry in value
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+            {
+                let (key, state) = entry.split_once('=').unwrap_or((entry, "on"));
+                let feature = Feature::parse(key)
+                    .with_context(|| format!("Unknown feature flag {key:?} in FEATURE_FLAGS"))?;
+                let Some(enabled) = parse_flag(state) else {
+                    bail!(
+                        "FEATURE_FLAGS: {key} must be on or off, got {:?}",
+                        state.trim()
+                    );
+                };
+                defaults.insert(feature, enabled);
+            }
+        }
+        Ok(Self {
+            pool,
+            defaults,
+            overrides: Arc::new(RwLock::new(HashMap::new())),
+        })
+    }
+
+    /// One-line summary of the defaults for the startup log.
+    pub(crate) fn describe(&self) -> String {
+        Feature::ALL
+            .into_iter()
+            .map(|feature| {
+                let state = if self.defaults[&feature] { "on" } else { "off" };
+                format!("{}={state}", feature.key())
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub(crate) async fn is_enabled(&self, feature: Feature) -> bool {
+        match self.overrides.read().await.get(&feature) {
+            Some(row) => row.enabled,
+            None => self.defaults[&feature],
+        }
+    }
+
+    pub(crate) async fn refresh(&self) -> sqlx::Result<()> {
+        let rows = sqlx::query_as::<_, FlagRow>(
+            r#"SELECT "key", "enabled", "updatedBy", "updatedAt" FROM "FeatureFlag""#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let overrides = rows
+            .into_iter()
+            .filter_map(|row| Feature::parse(&row.key).map(|feature| (feature, row)))
+            .collect();
+        *self.overrides.write().await = overrides;
+        Ok(())
+    }
+
+    pub(crate) async fn refresh_worker(self) {
+        let mut interval = time::interval(REFRESH_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            if let Err(err) = self.refresh().await {
+                eprintln!("[flags] Failed to reload feature flags: {err}");
+            }
+        }
+    }
+
+    async fn list(&self) -> Vec<FeatureFlagState> {
+        let overrides = self.overrides.read().await;
+        Feature::ALL
+            .into_iter()
+            .map(|feature| {
+                let row = overrides.get(&feature);
+                let default = self.defaults[&feature];
+                FeatureFlagState {
+                    key: feature.key(),
+                    description: feature.description(),
+                    enabled: row.map_or(default, |row| row.enabled),
+                    default,
+                    overridden: row.is_some(),
+                    updated_by: row.and_then(|row| row.updated_by.clone()),
+                    updated_at: row.map(|row| row.updated_at),
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FeatureFlagState {
+    key: &'static str,
+    description: &'static str,
+    enabled: bool,
+    /// The environment default, used while there is no override.
+    default: bool,
+    overridden: bool,
+    updated_by: Option<String>,
+    updated_at: Option<UtcTimestamp>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct UpdateFeatureFlagRequest {
+    /// `null` drops the override and returns to the default.
+    enabled: Option<bool>,
+}
+
+/// Flags apply to every tenant, so only unrestricted tenants manage them.
+pub(crate) async fn get_feature_flags(
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<Vec<FeatureFlagState>>> {
+    scope.require_unrestricted()?;
+    Ok(Json(state.feature_flags.list().await))
+}
+
+pub(crate) async fn update_feature_flag(
+    Path(key): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<UpdateFeatureFlagRequest>,
+) -> ApiResult<Json<Vec<FeatureFlagState>>> {
+    scope.require_unrestricted()?;
+    let Some(feature) = Feature::parse(&key) else {
+        return Err(ApiError::from((
+            StatusCode::NOT_FOUND,
+            format!("Unknown feature flag {key}"),
+        )));
+    };
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let flags = &state.feature_flags;
+    let before = flags.is_enabled(feature).await;
+    match request.enabled {
+        Some(enabled) => {
+            sqlx::query(
+                r#"
+                INSERT INTO "FeatureFlag" ("key", "enabled", "updatedBy")
+                VALUES ($1, $2, $3)
+                ON CONFLICT ("key") DO UPDATE
+                SET "enabled" = EXCLUDED."enabled",
+                    "updatedBy" = EXCLUDED."updatedBy",
+                    "updatedAt" = CURRENT_TIMESTAMP
+                "#,
+            )
+            .bind(feature.key())
+            .bind(enabled)
+            .bind(&actor)
+            .execute(&state.pool)
+            .await
+            .map_err(internal_error)?;
+        }
+        None => {
+            sqlx::query(r#"DELETE FROM "FeatureFlag" WHERE "key" = $1"#)
+                .bind(feature.key())
+                .execute(&state.pool)
+                .await
+                .map_err(internal_error)?;
+        }
+    }
+    flags.refresh().await.map_err(internal_error)?;
+    let after = flags.is_enabled(feature).await;
+
+    println!(
+        "[flags] {} is {} ({})",
+        feature.key(),
+        if after { "on" } else { "off" },
+        if request.enabled.is_some() {
+            "override"
+        } else {
+            "default"
+        }
+    );
+    audit_change(
+        &state.pool,
+        "feature-flag",
+        Some(feature.key()),
+        actor.as_deref(),
+        serde_json::json!({ "enabled": before }),
+        serde_json::json!({ "enabled": after, "overridden": request.enabled.is_some() }),
+    )
+    .await;
+    let _ = state.event_tx.send(ServerEvent::settings_updated());
+    Ok(Json(flags.list().await))
+}
@@ -0,0 +1,237 @@
+//! Named filter combinations for the deals list, saved server-side so an
+//! operator finds them on any machine. Presets belong to the caller as
+//! resolved by [`auth::preference_owner`]; names are unique per owner and
+//! saving under an existing name replaces that preset.
+
+use axum::{
+    Json,
+    extract::{Extension, Path, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, types::Json as SqlJson};
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::{
+    ApiResult, AppState, auth, errors::ApiError, internal_error, payout_status::PayoutStatus,
+    tenant::TenantScope, timestamps::UtcTimestamp,
+};
+
+const MAX_NAME_CHARS: usize = 80;
+const MAX_PRESETS_PER_OWNER: i64 = 50;
+
+/// The deals filter state as the dashboard keeps it; the same fields as the
+/// `/api/deals` query.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct DealFilterState {
+    search: Option<String>,
+    wallet: Option<String>,
+    amount: Option<f64>,
+    status: Option<String>,
+    include_archived: bool,
+    sort: Option<String>,
+    order: Option<String>,
+    per_page: Option<u32>,
+}
+
+impl DealFilterState {
+    fn normalized(self) -> ApiResult<Self> {
+        let text = |value: Option<String>| {
+            value
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let sort = text(self.sort);
+        if sort
+            .as_deref()
+            .is_some_and(|sort| !matches!(sort, "createdAt" | "status"))
+        {
+            return Err(bad_request("sort must be createdAt or status"));
+        }
+        let order = text(self.order).map(|order| order.to_ascii_lowercase());
+        if order
+            .as_deref()
+            .is_some_and(|order| !matches!(order, "asc" | "desc"))
+        {
+            return Err(bad_request("order must be asc or desc"));
+        }
+        if self.amount.is_some_and(|amount| !amount.is_finite()) {
+            return Err(bad_request("amount must be a number"));
+        }
+        let status = text(self.status)
+            .map(|status| {
+                PayoutStatus::parse(&status)
+                    .map(|status| status.to_string())
+                    .ok_or_else(|| bad_request(&format!("Unknown payout status {status}")))
+            })
+            .transpose()?;
+        Ok(Self {
+            search: text(self.search),
+            wallet: text(self.wallet),
+            amount: self.amount,
+            status,
+            include_archived: self.include_archived,
+            sort,
+            order,
+            per_page: self.per_page.map(|value| value.clamp(1, 200)),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FilterPreset {
+    id: String,
+    name: String,
+    filters: SqlJson<DealFilterState>,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+    #[sqlx(rename = "updatedAt")]
+    updated_at: UtcTimestamp,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SaveFilterPresetRequest {
+    name: String,
+    #[serde(default)]
+    filters: DealFilterState,
+}
+
+impl SaveFilterPresetRequest {
+    fn validate(self) -> ApiResult<(String, DealFilterState)> {
+        let name = self.name.trim().to_string();
+        if name.is_empty() {
+            return Err(bad_request("Preset name must not be empty"));
+        }
+        if name.chars().count() > MAX_NAME_CHARS {
+            return Err(bad_request(&format!(
+                "Preset name must be at most {MAX_NAME_CHARS} characters"
+            )));
+        }
+        Ok((name, self.filters.normalized()?))
+    }
+}
+
+fn bad_request(message: &str) -> ApiError {
+    ApiError::from((StatusCode::BAD_REQUEST, message.to_string()))
+}
+
+pub(crate) async fn list_filter_presets(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+) -> ApiResult<Json<Vec<FilterPreset>>> {
+    let owner = auth::preference_owner(&session, &scope).await?;
+    let presets = sqlx::query_as::<_, FilterPreset>(
+        r#"
+        SELECT "id", "name", "filters", "createdAt", "updatedAt"
+        FROM "DealFilterPreset"
+        WHERE "owner" = $1
+        ORDER BY "name"
+        "#,
+    )
+    .bind(&owner)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(internal_error)?;
+    Ok(Json(presets))
+}
+
+pub(crate) async fn save_filter_preset(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<SaveFilterPresetRequest>,
+) -> ApiResult<Json<FilterPreset>> {
+    let owner = auth::preference_owner(&session, &scope).await?;
+    let (name, filters) = request.validate()?;
+    let count: i64 = sqlx::query_scalar(
+        r#"SELECT COUNT(*) FROM "DealFilterPreset" WHERE "owner" = $1 AND "name" <> $2"#,
+    )
+    .bind(&owner)
+    .bind(&name)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(internal_error)?;
+    if count >= MAX_PRESETS_PER_OWNER {
+        return Err(bad_request(&format!(
+            "At most {MAX_PRESETS_PER_OWNER} presets can be saved"
+        )));
+    }
+    let preset = sqlx::query_as::<_, FilterPreset>(
+        r#"
+        INSERT INTO "DealFilterPreset" ("id", "owner", "name", "filters")
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT ("owner", "name") DO UPDATE
+        SET "filters" = EXCLUDED."filters", "updatedAt" = CURRENT_TIMESTAMP
+        RETURNING "id", "name", "filters", "createdAt", "updatedAt"
+        "#,
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(&owner)
+    .bind(&name)
+    .bind(SqlJson(&filters))
+    .fetch_one(&state.pool)
+    .await
+    .map_err(internal_error)?;
+    Ok(Json(preset))
+}
+
+/// Renames a preset and replaces its filters.
+pub(crate) async fn update_filter_preset(
+    Path(preset_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<SaveFilterPresetRequest>,
+) -> ApiResult<Json<FilterPreset>> {
+    let owner = auth::preference_owner(&session, &scope).await?;
+    let (name, filters) = request.validate()?;
+    sqlx::query_as::<_, FilterPreset>(
+        r#"
+        UPDATE "DealFilterPreset"
+        SET "name" = $3, "filters" = $4, "updatedAt" = CURRENT_TIMESTAMP
+        WHERE "id" = $1 AND "owner" = $2
+        RETURNING "id", "name", "filters", "createdAt", "updatedAt"
+        "#,
+    )
+    .bind(&preset_id)
+    .bind(&owner)
+    .bind(&name)
+    .bind(SqlJson(&filters))
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|err| match &err {
+        sqlx::Error::Database(db) if db.is_unique_violation() => ApiError::from((
+            StatusCode::CONFLICT,
+            format!("A preset named {name} already exists"),
+        )),
+        _ => internal_error(err),
+    })?
+    .map(Json)
+    .ok_or_else(|| ApiError::from((StatusCode::NOT_FOUND, "Preset not found".to_string())))
+}
+
+pub(crate) async fn delete_filter_preset(
+    Path(preset_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+) -> ApiResult<StatusCode> {
+    let owner = auth::preference_owner(&session, &scope).await?;
+    let result = sqlx::query(r#"DELETE FROM "DealFilterPreset" WHERE "id" = $1 AND "owner" = $2"#)
+        .bind(&preset_id)
+        .bind(&owner)
+        .execute(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::from((
+            StatusCode::NOT_FOUND,
+            "Preset not found".to_string(),
+        )));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,92 @@
+//! Optional reservation of trader funds on assignment. With
+//! `freezeOnAssign` enabled (initially from `FREEZE_ON_ASSIGN`), assigning a
+//! payout adds its amount to the trader's `frozenRub` in the same transaction
+//! and cancelling it releases the amount again.
+//!
+//! Every freeze is recorded in `PayoutFreeze`, so a release only returns what
+//! this service froze, even if the flag was switched in between. Freezes of
+//! payouts that settle on the platform are left for its own accounting.
+
+use anyhow::Result;
+use sqlx::{Postgres, Transaction};
+
+use crate::env::flag_env;
+
+const FREEZE_QUERY: &str = r#"
+    WITH frozen AS (
+        INSERT INTO "PayoutFreeze" ("payoutId", "traderId", "amount")
+        SELECT batch."payoutId", batch."traderId", batch."amount"
+        FROM UNNEST($1::text[], $2::text[], $3::double precision[])
+            AS batch("payoutId", "traderId", "amount")
+        WHERE batch."amount" > 0
+        ON CONFLICT ("payoutId") DO NOTHING
+        RETURNING "traderId", "amount"
+    ),
+    totals AS (
+        SELECT "traderId", SUM("amount") AS "amount"
+        FROM frozen
+        GROUP BY "traderId"
+    )
+    UPDATE "User" u
+    SET "frozenRub" = COALESCE(u."frozenRub", 0) + totals."amount"
+    FROM totals
+    WHERE u."id" = totals."traderId"
+"#;
+
+const RELEASE_QUERY: &str = r#"
+    WITH released AS (
+        DELETE FROM "PayoutFreeze"
+        WHERE "payoutId" = ANY($1::text[])
+        RETURNING "traderId", "amount"
+    ),
+    totals AS (
+        SELECT "traderId", SUM("amount") AS "amount"
+        FROM released
+        GROUP BY "traderId"
+    )
+    UPDATE "User" u
+    SET "frozenRub" = GREATEST(COALESCE(u."frozenRub", 0) - totals."amount", 0)
+    FROM totals
+    WHERE u."id" = totals."traderId"
+"#;
+
+pub(crate) fn enabled_from_env() -> Result<bool> {
+    flag_env("FREEZE_ON_ASSIGN", false)
+}
+
+/// Freezes `amount` of each `(payoutId, traderId, amount)` on its trader.
+pub(crate) async fn freeze_assignments(
+    tx: &mut Transaction<'_, Postgres>,
+    assignments: &[(String, String, f64)],
+) -> sqlx::Result<()> {
+    if assignments.is_empty() {
+        return Ok(());
+    }
+    let mut payout_ids = Vec::with_capacity(assignments.len());
+    let mut trader_ids = Vec::with_capacity(assignments.len());
+    let mut amounts = Vec::with_capacity(assignments.len());
+    for (payout_id, trader_id, amount) in assignments {
+        payout_ids.push(payout_id.clone());
+        trader_ids.push(trader_id.clone());
+        amounts.push(*amount);
+    }
+    sqlx::query(FREEZE_QUERY)
+        .bind(payout_ids)
+        .bind(trader_ids)
+        .bind(amounts)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Returns whatever this service froze for the payouts to their traders.
+pub(crate) async fn release_payouts(
+    tx: &mut Transaction<'_, Postgres>,
+    payout_ids: &[String],
+) -> sqlx::Result<()> {
+    sqlx::query(RELEASE_QUERY)
+        .bind(payout_ids)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
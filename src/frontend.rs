@@ -1,14 +1,75 @@
-use crate::{AutoDistributionConfig, PayoutListResponse, Trader, UnassignedPayout};
-use chrono::NaiveDateTime;
+use crate::{
+    cookie_value, currencies,
+    db::{
+        Pagination, PayoutDealListItem, PayoutListResponse, StatsSummary, Trader,
+        TraderListResponse, UnassignedPayoutListResponse,
+    },
+    environment::Environment,
+    i18n::{self, Lang, t, tf},
+    merchant_page::MerchantPage,
+    payout_status::{self, PayoutStatus},
+    preferences::{ColumnPreference, DealColumn, DealsTableLayout},
+    reports::{ReportPayout, ShiftReport},
+    settings::{AutoDistributionConfig, MAX_INTERVAL_SECONDS},
+    timestamps::UtcTimestamp,
+    trader_page::TraderPage,
+};
+use axum::http::HeaderMap;
 use leptos::*;
 use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{collections::HashSet, sync::LazyLock};
 
 #[derive(Clone, Serialize)]
 pub(crate) struct DashboardSnapshot {
-    pub traders: Vec<Trader>,
-    pub payouts: Vec<UnassignedPayout>,
+    pub traders: TraderListResponse,
+    pub payouts: UnassignedPayoutListResponse,
     pub deals: PayoutListResponse,
     pub settings: AutoDistributionConfig,
+    pub tenant: Option<String>,
+    /// Logged-in operator, shown with a logout button.
+    pub operator: Option<String>,
+    /// Sent back by the dashboard JS in `X-CSRF-Token`, see `csrf`.
+    #[serde(skip)]
+    pub csrf_token: String,
+    pub summary: StatsSummary,
+    /// Shown as a banner; the dashboard also hides actions that are locked.
+    pub environment: Environment,
+    /// The deals list was rendered with filters taken from the page URL.
+    #[serde(skip)]
+    pub deals_filtered: bool,
+    /// The operator's deals table columns, see `preferences`.
+    #[serde(skip)]
+    pub deals_layout: DealsTableLayout,
+    /// The SSE event version the snapshot reflects, see `events`.
+    #[serde(rename = "eventsVersion")]
+    pub events_version: u64,
+}
+
+pub(crate) const THEME_COOKIE: &str = "theme";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    fn code(self) -> &'static str {
+        match self {
+            Theme::Dark => "dark",
+            Theme::Light => "light",
+        }
+    }
+
+    /// Reads the persisted theme cookie so the first paint already uses the right palette.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        match cookie_value(headers, THEME_COOKIE).as_deref() {
+            Some("light") => Theme::Light,
+            _ => Theme::Dark,
+        }
+    }
 }
 
 const STYLES: &str = r#"
@@ -26,6 +87,29 @@ const STYLES: &str = r#"
     --error: #f87171;
     --warning: #fbbf24;
     --info: #38bdf8;
+    --bg-gradient-top: #1e293b;
+    --bg-input: rgba(15, 23, 42, 0.6);
+    --shadow-panel: rgba(15, 23, 42, 0.35);
+    color-scheme: dark;
+}
+:root[data-theme='light'] {
+    --bg-primary: #f1f5f9;
+    --bg-secondary: rgba(255, 255, 255, 0.82);
+    --bg-panel: rgba(255, 255, 255, 0.92);
+    --accent: #0284c7;
+    --accent-strong: #0369a1;
+    --border-light: rgba(100, 116, 139, 0.3);
+    --text-primary: #0f172a;
+    --text-secondary: #334155;
+    --text-muted: #64748b;
+    --success: #16a34a;
+    --error: #dc2626;
+    --warning: #d97706;
+    --info: #0284c7;
+    --bg-gradient-top: #ffffff;
+    --bg-input: rgba(255, 255, 255, 0.9);
+    --shadow-panel: rgba(15, 23, 42, 0.08);
+    color-scheme: light;
 }
 * {
     box-sizing: border-box;
@@ -33,12 +117,32 @@ const STYLES: &str = r#"
 body {
     margin: 0;
     min-height: 100vh;
-    background: radial-gradient(circle at top, #1e293b, var(--bg-primary));
+    background: radial-gradient(circle at top, var(--bg-gradient-top), var(--bg-primary));
     color: var(--text-primary);
     font-family: 'Inter', 'Roboto', 'Segoe UI', sans-serif;
     display: flex;
     flex-direction: column;
 }
+.env-banner {
+    display: flex;
+    align-items: center;
+    gap: 16px;
+    padding: 8px 40px;
+    font-size: 13px;
+    background: rgba(148, 163, 184, 0.18);
+    color: var(--text-secondary);
+}
+.env-banner strong {
+    letter-spacing: 0.08em;
+}
+.env-banner[data-kind='production'] {
+    background: var(--error);
+    color: #fff;
+}
+.env-banner[data-kind='staging'] {
+    background: var(--warning);
+    color: #0f172a;
+}
 .top-bar {
     display: flex;
     align-items: flex-start;
@@ -54,6 +158,67 @@ body {
     margin: 0;
     color: var(--text-muted);
 }
+.global-search {
+    position: relative;
+    flex: 1;
+    max-width: 420px;
+    align-self: center;
+}
+.global-search input {
+    width: 100%;
+    padding: 10px 14px;
+    border-radius: 10px;
+    border: 1px solid var(--border-light);
+    background: var(--bg-input);
+    color: var(--text-primary);
+}
+.global-search-results {
+    position: absolute;
+    top: calc(100% + 6px);
+    left: 0;
+    right: 0;
+    z-index: 20;
+    max-height: 70vh;
+    overflow-y: auto;
+    padding: 8px;
+    border: 1px solid var(--border-light);
+    border-radius: 12px;
+    background: var(--bg-panel);
+    box-shadow: 0 12px 32px rgba(2, 6, 23, 0.35);
+}
+.global-search-results h4 {
+    margin: 8px 8px 4px;
+    font-size: 11px;
+    text-transform: uppercase;
+    letter-spacing: 0.08em;
+    color: var(--text-muted);
+}
+.search-hit {
+    display: flex;
+    flex-direction: column;
+    align-items: flex-start;
+    gap: 2px;
+    width: 100%;
+    padding: 6px 8px;
+    border: none;
+    border-radius: 8px;
+    background: none;
+    color: var(--text-primary);
+    text-align: left;
+    font-size: 13px;
+}
+.search-hit:hover,
+.search-hit:focus {
+    background: var(--bg-secondary);
+}
+.search-hit-sub,
+.search-empty {
+    font-size: 12px;
+    color: var(--text-muted);
+}
+.search-empty {
+    padding: 8px;
+}
 .status-block {
     display: flex;
     flex-direction: column;
@@ -70,6 +235,76 @@ body {
     font-weight: 600;
     font-size: 16px;
 }
+.status-value[data-state='ok'] {
+    color: var(--success);
+}
+.status-value[data-state='degraded'] {
+    color: var(--warning);
+}
+.status-value[data-state='down'] {
+    color: var(--error);
+}
+.dlq-badge {
+    padding: 2px 10px;
+    border-radius: 999px;
+    font-size: 12px;
+    background: rgba(248, 113, 113, 0.12);
+    border: 1px solid rgba(248, 113, 113, 0.45);
+    color: var(--error);
+    cursor: pointer;
+}
+.dlq-badge[hidden] {
+    display: none;
+}
+.toggle-row {
+    display: flex;
+    gap: 8px;
+    margin-top: 6px;
+}
+.lang-toggle,
+.theme-toggle,
+.logout-form button {
+    padding: 6px 12px;
+    font-size: 12px;
+}
+.report-link {
+    display: inline-flex;
+    align-items: center;
+    padding: 6px 12px;
+    font-size: 12px;
+    border-radius: 12px;
+    border: 1px solid var(--border-light);
+    color: var(--text-secondary);
+    text-decoration: none;
+}
+.report-link:hover {
+    color: var(--text-primary);
+}
+.logout-form {
+    margin: 0;
+}
+.login-main {
+    flex: 1;
+    display: flex;
+    align-items: center;
+    justify-content: center;
+    padding: 40px;
+}
+.login-panel {
+    width: 100%;
+    max-width: 360px;
+}
+.report-main {
+    padding-top: 0;
+}
+.report-note {
+    white-space: pre-wrap;
+}
+.login-error {
+    margin: 0;
+    color: var(--error);
+    font-size: 14px;
+}
 main {
     flex: 1;
     padding: 0 40px 40px 40px;
@@ -90,7 +325,7 @@ main {
     display: flex;
     flex-direction: column;
     gap: 8px;
-    box-shadow: 0 18px 35px rgba(15, 23, 42, 0.32);
+    box-shadow: 0 18px 35px var(--shadow-panel);
 }
 .metric-label {
     text-transform: uppercase;
@@ -147,7 +382,7 @@ main {
     display: flex;
     flex-direction: column;
     gap: 16px;
-    box-shadow: 0 18px 35px rgba(15, 23, 42, 0.35);
+    box-shadow: 0 18px 35px var(--shadow-panel);
 }
 .panel-header {
     display: flex;
@@ -180,6 +415,69 @@ main {
     border-color: rgba(74, 222, 128, 0.45);
     color: var(--success);
 }
+.badge.rate-badge {
+    margin-left: 8px;
+    padding: 2px 8px;
+    font-size: 10px;
+    background: rgba(248, 113, 113, 0.12);
+    border-color: rgba(248, 113, 113, 0.45);
+    color: var(--error);
+}
+.badge.countdown-badge {
+    margin-left: 8px;
+    padding: 2px 8px;
+    font-size: 10px;
+    font-variant-numeric: tabular-nums;
+}
+.badge.countdown-badge[data-state='urgent'] {
+    background: rgba(251, 191, 36, 0.12);
+    border-color: rgba(251, 191, 36, 0.45);
+    color: var(--warning);
+}
+.badge.countdown-badge[data-state='overdue'] {
+    background: rgba(248, 113, 113, 0.12);
+    border-color: rgba(248, 113, 113, 0.45);
+    color: var(--error);
+}
+.badge.archived-badge {
+    margin-left: 8px;
+    padding: 2px 8px;
+    font-size: 10px;
+}
+.badge.team-disabled-badge {
+    margin-left: 8px;
+    padding: 2px 8px;
+    font-size: 10px;
+    color: var(--error);
+}
+.badge.paused-badge {
+    padding: 2px 8px;
+    font-size: 10px;
+    color: var(--warning);
+}
+.badge.away-badge {
+    margin-left: 8px;
+    padding: 2px 8px;
+    font-size: 10px;
+    color: var(--warning);
+}
+.badge.priority-badge {
+    margin-left: 8px;
+    padding: 2px 8px;
+    font-size: 10px;
+    background: rgba(251, 191, 36, 0.12);
+    border-color: rgba(251, 191, 36, 0.45);
+    color: var(--warning);
+}
+.badge.duplicate-badge {
+    margin-left: 8px;
+    padding: 2px 8px;
+    font-size: 10px;
+    background: rgba(248, 113, 113, 0.12);
+    border-color: rgba(248, 113, 113, 0.45);
+    color: var(--error);
+    cursor: pointer;
+}
 .badge[data-state='off'] {
     background: rgba(148, 163, 184, 0.12);
     border-color: rgba(148, 163, 184, 0.35);
@@ -191,6 +489,33 @@ main {
     gap: 16px;
     align-items: center;
 }
+.schedule-block {
+    display: flex;
+    flex-direction: column;
+    gap: 12px;
+    margin-top: 16px;
+}
+.settings-changes {
+    margin-top: 16px;
+}
+.settings-changes li {
+    display: flex;
+    flex-wrap: wrap;
+    align-items: center;
+    gap: 8px;
+    margin-top: 8px;
+}
+.schedule-window {
+    display: flex;
+    flex-wrap: wrap;
+    gap: 12px;
+    align-items: center;
+}
+.day-toggles {
+    display: flex;
+    gap: 8px;
+    font-size: 13px;
+}
 button {
     background: linear-gradient(135deg, var(--accent), var(--accent-strong));
     border: none;
@@ -237,7 +562,7 @@ button:disabled {
     padding: 8px 12px;
     border-radius: 8px;
     border: 1px solid var(--border-light);
-    background: rgba(15, 23, 42, 0.6);
+    background: var(--bg-input);
     color: var(--text-primary);
 }
 .deal-actions {
@@ -259,180 +584,642 @@ button:disabled {
     align-items: center;
     justify-content: space-between;
 }
-.deals-pagination {
+.table-pagination {
     display: flex;
     gap: 12px;
     align-items: center;
     justify-content: flex-end;
     margin-top: 12px;
 }
-.deals-pagination button {
+.table-pagination button {
     background: rgba(56, 189, 248, 0.18);
     border: 1px solid rgba(56, 189, 248, 0.25);
     color: var(--text-primary);
     padding: 6px 12px;
     border-radius: 8px;
 }
-.deals-pagination button:disabled {
+.table-pagination button:disabled {
     opacity: 0.4;
 }
-button.danger {
-    background: linear-gradient(135deg, var(--error), #dc2626);
-    color: #fff;
-}
-#deals-sort-status.active {
-    background: rgba(56, 189, 248, 0.2);
-    border: 1px solid rgba(56, 189, 248, 0.35);
-    color: var(--accent);
+.deal-select-cell {
+    width: 32px;
+    text-align: center;
 }
-input[type='number'],
-select {
-    background: rgba(15, 23, 42, 0.65);
+.modal-dialog {
     border: 1px solid var(--border-light);
-    border-radius: 12px;
+    border-radius: 16px;
+    background: var(--bg-panel);
     color: var(--text-primary);
-    padding: 9px 12px;
-    font-size: 14px;
-    min-width: 120px;
+    padding: 24px;
+    width: min(420px, 90vw);
+    backdrop-filter: blur(12px);
 }
-input[type='number']:focus,
-select:focus {
-    outline: none;
-    border-color: var(--accent);
-    box-shadow: 0 0 0 3px rgba(56, 189, 248, 0.25);
+.modal-dialog::backdrop {
+    background: rgba(2, 6, 23, 0.6);
 }
-.table-wrapper {
-    overflow-x: auto;
-    border-radius: 12px;
+.modal-dialog form {
+    display: flex;
+    flex-direction: column;
+    gap: 16px;
 }
-table {
-    width: 100%;
-    border-collapse: collapse;
+.modal-dialog h3 {
+    margin: 0;
+    font-size: 16px;
 }
-thead {
-    background: rgba(148, 163, 184, 0.12);
+.modal-dialog textarea {
+    padding: 8px 12px;
+    border-radius: 8px;
+    border: 1px solid var(--border-light);
+    background: var(--bg-input);
+    color: var(--text-primary);
+    resize: vertical;
 }
-th, td {
-    padding: 12px 14px;
-    text-align: left;
-    border-bottom: 1px solid rgba(148, 163, 184, 0.18);
-    font-size: 14px;
-    color: var(--text-secondary);
+.files-list {
+    display: flex;
+    flex-direction: column;
+    gap: 8px;
+    font-size: 13px;
 }
-th {
+.files-list h4 {
+    margin: 8px 0 0;
+    font-size: 12px;
     text-transform: uppercase;
     letter-spacing: 0.08em;
-    font-size: 12px;
     color: var(--text-muted);
 }
-tbody tr:hover {
-    background: rgba(59, 130, 246, 0.08);
-}
-tbody tr:last-child td {
-    border-bottom: none;
+.files-list a {
+    color: var(--accent);
+    word-break: break-all;
 }
-.empty {
-    text-align: center;
-    color: var(--text-muted);
-    font-style: italic;
+.modal-dialog.wide-dialog {
+    width: min(760px, 94vw);
 }
-.limit-controls {
+.dialog-filters {
     display: flex;
-    gap: 10px;
-    align-items: center;
+    gap: 12px;
+    align-items: flex-end;
 }
-.limit-controls input {
-    max-width: 140px;
+#traders-table tbody tr[data-trader-id] {
+    cursor: pointer;
 }
-.assign-controls {
+.team-group-row td {
+    background: var(--bg-secondary);
+    color: var(--text-secondary);
+    font-weight: 600;
+}
+.deal-timeline-row td {
+    background: var(--bg-secondary);
+}
+.timeline {
+    list-style: none;
+    margin: 0 0 0 12px;
+    padding: 4px 0 4px 20px;
+    border-left: 2px solid var(--border-light);
     display: flex;
+    flex-direction: column;
     gap: 10px;
-    align-items: center;
+    font-size: 13px;
 }
-@media (max-width: 960px) {
-    .top-bar {
-        flex-direction: column;
-        align-items: flex-start;
-        padding: 28px 24px 12px 24px;
-    }
-    main {
-        padding: 0 24px 24px 24px;
-    }
-    .panel {
-        padding: 20px;
-    }
-    th, td {
-        white-space: nowrap;
-    }
+.timeline li {
+    position: relative;
 }
-@media (max-width: 640px) {
-    .controls-row {
-        flex-direction: column;
-        align-items: stretch;
-    }
-    button {
-        width: 100%;
-    }
-    .assign-controls {
-        flex-direction: column;
-        align-items: stretch;
-    }
+.timeline li::before {
+    content: '';
+    position: absolute;
+    left: -27px;
+    top: 4px;
+    width: 10px;
+    height: 10px;
+    border-radius: 50%;
+    background: var(--accent);
 }
-"#;
-
-const DASHBOARD_SCRIPT: &str = r#"
-(() => {
-    const statusBar = document.getElementById('global-status');
-    const lastUpdatedEl = document.getElementById('last-updated');
-    const metrics = {
-        traders: document.getElementById('metric-traders'),
-        payouts: document.getElementById('metric-payouts'),
-        payoutSum: document.getElementById('metric-payout-sum'),
-    };
-    const autoBadge = document.getElementById('auto-status-badge');
+.timeline li[data-kind='completed']::before {
+    background: var(--success);
+}
+.timeline li[data-kind='cancelled']::before,
+.timeline li[data-kind='failed']::before {
+    background: var(--error);
+}
+.timeline li[data-kind='disputed']::before,
+.timeline li[data-kind='expired']::before {
+    background: var(--warning);
+}
+.timeline-time {
+    color: var(--text-muted);
+    margin-right: 8px;
+}
+.timeline-meta {
+    display: block;
+    color: var(--text-muted);
+    font-size: 12px;
+    word-break: break-word;
+}
+.timeline li[data-kind='note'] .timeline-meta {
+    white-space: pre-wrap;
+}
+.note-form {
+    display: flex;
+    gap: 8px;
+    align-items: flex-end;
+    margin-top: 12px;
+}
+.note-form textarea {
+    flex: 1;
+    background: var(--bg-input);
+    border: 1px solid var(--border-light);
+    border-radius: 12px;
+    color: var(--text-primary);
+    padding: 9px 12px;
+    font: inherit;
+    resize: vertical;
+}
+.dialog-actions {
+    display: flex;
+    justify-content: flex-end;
+    gap: 12px;
+}
+.columns-list {
+    list-style: none;
+    margin: 0;
+    padding: 0;
+    display: flex;
+    flex-direction: column;
+    gap: 6px;
+}
+.column-item {
+    display: flex;
+    align-items: center;
+    gap: 8px;
+}
+.column-item label {
+    flex: 1;
+    display: flex;
+    align-items: center;
+    gap: 8px;
+    font-size: 14px;
+}
+.column-item .column-width {
+    width: 90px;
+}
+.column-item button {
+    padding: 4px 10px;
+}
+.palette-toggle {
+    padding: 6px 12px;
+    font-size: 12px;
+}
+.modal-dialog.palette-dialog {
+    width: min(520px, 94vw);
+    padding: 16px;
+}
+.palette-dialog input {
+    padding: 10px 12px;
+    border-radius: 8px;
+    border: 1px solid var(--border-light);
+    background: var(--bg-input);
+    color: var(--text-primary);
+    font-size: 15px;
+}
+.palette-list {
+    list-style: none;
+    margin: 0;
+    padding: 0;
+    display: flex;
+    flex-direction: column;
+    gap: 2px;
+}
+.palette-item {
+    display: flex;
+    justify-content: space-between;
+    align-items: center;
+    padding: 8px 12px;
+    border-radius: 8px;
+    cursor: pointer;
+    font-size: 14px;
+}
+.palette-item[aria-selected='true'] {
+    background: var(--bg-secondary);
+    color: var(--accent);
+}
+.palette-item kbd,
+.palette-hint kbd {
+    padding: 1px 6px;
+    border-radius: 4px;
+    border: 1px solid var(--border-light);
+    font-size: 11px;
+    color: var(--text-muted);
+}
+.palette-hint {
+    margin: 0;
+    font-size: 12px;
+    color: var(--text-muted);
+}
+button.danger {
+    background: linear-gradient(135deg, var(--error), #dc2626);
+    color: #fff;
+}
+th.sortable {
+    cursor: pointer;
+    user-select: none;
+}
+th.sortable:hover {
+    color: var(--text-primary);
+}
+th.sortable[data-order='asc']::after {
+    content: ' ↑';
+}
+th.sortable[data-order='desc']::after {
+    content: ' ↓';
+}
+.traders-toolbar {
+    display: flex;
+    justify-content: flex-end;
+    align-items: center;
+    gap: 12px;
+}
+.traders-toolbar input[type='search'] {
+    min-width: 260px;
+    padding: 8px 12px;
+    border-radius: 8px;
+    border: 1px solid var(--border-light);
+    background: var(--bg-input);
+    color: var(--text-primary);
+}
+#deals-sort-status.active {
+    background: rgba(56, 189, 248, 0.2);
+    border: 1px solid rgba(56, 189, 248, 0.35);
+    color: var(--accent);
+}
+input[type='number'],
+select {
+    background: var(--bg-input);
+    border: 1px solid var(--border-light);
+    border-radius: 12px;
+    color: var(--text-primary);
+    padding: 9px 12px;
+    font-size: 14px;
+    min-width: 120px;
+}
+input[type='number']:focus,
+select:focus {
+    outline: none;
+    border-color: var(--accent);
+    box-shadow: 0 0 0 3px rgba(56, 189, 248, 0.25);
+}
+.table-wrapper {
+    overflow-x: auto;
+    border-radius: 12px;
+}
+table {
+    width: 100%;
+    border-collapse: collapse;
+}
+thead {
+    background: rgba(148, 163, 184, 0.12);
+}
+th, td {
+    padding: 12px 14px;
+    text-align: left;
+    border-bottom: 1px solid rgba(148, 163, 184, 0.18);
+    font-size: 14px;
+    color: var(--text-secondary);
+}
+th {
+    text-transform: uppercase;
+    letter-spacing: 0.08em;
+    font-size: 12px;
+    color: var(--text-muted);
+}
+tbody tr:hover {
+    background: rgba(59, 130, 246, 0.08);
+}
+tbody tr:last-child td {
+    border-bottom: none;
+}
+.empty {
+    text-align: center;
+    color: var(--text-muted);
+    font-style: italic;
+}
+.charts-grid {
+    display: grid;
+    grid-template-columns: repeat(auto-fit, minmax(240px, 1fr));
+    gap: 16px;
+}
+.chart-card {
+    border: 1px solid var(--border-light);
+    border-radius: 12px;
+    padding: 12px 14px;
+    display: flex;
+    flex-direction: column;
+    gap: 6px;
+}
+.chart-header {
+    display: flex;
+    justify-content: space-between;
+    align-items: baseline;
+    font-size: 12px;
+    text-transform: uppercase;
+    letter-spacing: 0.08em;
+    color: var(--text-muted);
+}
+.chart-latest {
+    font-size: 16px;
+    font-weight: 600;
+    color: var(--text-primary);
+    text-transform: none;
+    letter-spacing: normal;
+}
+.chart-body svg {
+    display: block;
+    width: 100%;
+    height: 80px;
+}
+.chart-body .empty {
+    padding: 24px 0;
+    font-size: 13px;
+}
+.limit-controls {
+    display: flex;
+    gap: 10px;
+    align-items: center;
+}
+.limit-controls input {
+    max-width: 110px;
+}
+.traders-bulk {
+    display: flex;
+    flex-wrap: wrap;
+    align-items: center;
+    gap: 10px;
+    margin-bottom: 12px;
+}
+.traders-bulk input[type='number'] {
+    max-width: 120px;
+}
+.trader-currencies {
+    margin-top: 4px;
+    font-size: 12px;
+    color: var(--text-muted);
+}
+.assign-controls {
+    display: flex;
+    gap: 10px;
+    align-items: center;
+}
+@media (max-width: 960px) {
+    .top-bar {
+        flex-direction: column;
+        align-items: flex-start;
+        padding: 28px 24px 12px 24px;
+    }
+    main {
+        padding: 0 24px 24px 24px;
+    }
+    .panel {
+        padding: 20px;
+    }
+    th, td {
+        white-space: nowrap;
+    }
+}
+@media (max-width: 640px) {
+    .controls-row {
+        flex-direction: column;
+        align-items: stretch;
+    }
+    button {
+        width: 100%;
+    }
+    .assign-controls {
+        flex-direction: column;
+        align-items: stretch;
+    }
+}
+"#;
+
+// The interactive half of the dashboard. Table rows come from the server
+// fragment endpoints; dialogs, filters and live updates still build markup
+// here, always through `html`/`escapeHtml`. Moving this into a leptos bundle
+// that hydrates the SSR output waits until the build can target wasm32.
+const DASHBOARD_SCRIPT: &str = r#"
+(() => {
+    const i18n = globalThis.__I18N__ ?? { lang: 'ru', locale: 'ru-RU', strings: {} };
+    const uncancellableStatuses = new Set(globalThis.__PAYOUT_STATUSES__?.uncancellable ?? []);
+    const LANG_STORAGE_KEY = 'dashboard-lang';
+    const THEME_STORAGE_KEY = 'dashboard-theme';
+    const statusBar = document.getElementById('global-status');
+    const lastUpdatedEl = document.getElementById('last-updated');
+    const backendStatusEl = document.getElementById('backend-status');
+    const deadLetterBadge = document.getElementById('dlq-badge');
+    let deadLetterCount = 0;
+    // Three missed server heartbeats (sent every 15s) mean the backend is gone.
+    const HEARTBEAT_TIMEOUT_MS = 45000;
+    let heartbeatTimer = null;
+    let backendDegraded = false;
+    const metrics = {
+        traders: document.getElementById('metric-traders'),
+        payouts: document.getElementById('metric-payouts'),
+        payoutSum: document.getElementById('metric-payout-sum'),
+        assignedToday: document.getElementById('metric-assigned-today'),
+        assignedTodaySum: document.getElementById('metric-assigned-today-sum'),
+        cancelledToday: document.getElementById('metric-cancelled-today'),
+        cancelledTodaySum: document.getElementById('metric-cancelled-today-sum'),
+        completedToday: document.getElementById('metric-completed-today'),
+        completedTodaySum: document.getElementById('metric-completed-today-sum'),
+    };
+    // Today's figures change without any payout event, so they are polled too.
+    const SUMMARY_REFRESH_MS = 60000;
+    const autoBadge = document.getElementById('auto-status-badge');
     const settingsDescription = document.getElementById('settings-description');
+    const statsControls = {
+        hours: document.getElementById('stats-hours'),
+        assigned: document.getElementById('chart-assigned'),
+        backlog: document.getElementById('chart-backlog'),
+        cancelRate: document.getElementById('chart-cancel-rate'),
+        flowShare: document.getElementById('stats-flow-share'),
+    };
     const dealsControls = {
         search: document.getElementById('deals-search'),
         wallet: document.getElementById('deals-wallet'),
         amount: document.getElementById('deals-amount'),
         status: document.getElementById('deals-status'),
         perPage: document.getElementById('deals-per-page'),
+        includeArchived: document.getElementById('deals-include-archived'),
         sortStatus: document.getElementById('deals-sort-status'),
         reset: document.getElementById('deals-reset'),
+        bulkCancel: document.getElementById('deals-bulk-cancel'),
+        selectAll: document.getElementById('deals-select-all'),
         prev: document.getElementById('deals-prev'),
         next: document.getElementById('deals-next'),
         pageInfo: document.getElementById('deals-page-info'),
     };
+    const tradersPager = {
+        prev: document.getElementById('traders-prev'),
+        next: document.getElementById('traders-next'),
+        pageInfo: document.getElementById('traders-page-info'),
+    };
+    const payoutsPager = {
+        prev: document.getElementById('payouts-prev'),
+        next: document.getElementById('payouts-next'),
+        pageInfo: document.getElementById('payouts-page-info'),
+    };
+    const TRADER_OPTIONS_LIMIT = 200;
 
     let currentTraders = [];
+    // Kept across pages, so a template can go to traders from several pages.
+    const selectedTraders = new Set();
+    let traderOptions = [];
+    let tradersFilters = {
+        search: '',
+        sort: 'numericId',
+        order: 'asc',
+        page: 1,
+        perPage: 25,
+    };
+    let tradersPagination = {
+        page: 1,
+        totalPages: 0,
+        total: 0,
+        perPage: 25,
+    };
+    let tradersFilterTimer = null;
     let currentPayouts = [];
+    let payoutsPagination = {
+        page: 1,
+        totalPages: 0,
+        total: 0,
+        perPage: 25,
+    };
     let currentDeals = [];
+    const selectedDeals = new Set();
+    const expandedTimelines = new Set();
+    let cancelReasons = [];
     let dealsPagination = {
         page: 1,
         totalPages: 0,
         total: 0,
         perPage: 25,
     };
-    let dealsFilters = {
+    const DEFAULT_DEALS_FILTERS = {
         search: '',
         wallet: '',
         amount: '',
         status: '',
+        includeArchived: false,
         sort: 'createdAt',
         order: 'desc',
         page: 1,
         perPage: 25,
     };
+    // The page URL carries the deals filters, so a copied link opens the same view.
+    let dealsFilters = { ...DEFAULT_DEALS_FILTERS, ...dealsFiltersFromParams(new URLSearchParams(location.search)) };
+    let filterPresets = [];
     let isLoading = false;
     let isDealsLoading = false;
+    let isStatsLoading = false;
     let reloadScheduled = false;
+    // Event versions (see `events`): the newest one the loaded data reflects
+    // and the newest one announced over SSE.
+    let loadedVersion = 0;
+    let announcedVersion = 0;
     let dealsFilterTimer = null;
 
-    function setStatus(type, message) {
-        if (!statusBar) {
-            return;
+    function t(key, params) {
+        let text = i18n.strings?.[key] ?? key;
+        if (params) {
+            for (const [name, value] of Object.entries(params)) {
+                text = text.split(`{${name}}`).join(String(value));
+            }
         }
-        statusBar.textContent = message;
+        return text;
+    }
+
+    function persistLanguage(lang) {
+        try {
+            localStorage.setItem(LANG_STORAGE_KEY, lang);
+        } catch (storageError) {
+            console.debug('localStorage недоступен:', storageError);
+        }
+        document.cookie = `lang=${lang}; path=/; max-age=31536000; SameSite=Lax`;
+    }
+
+    function applyStoredLanguage() {
+        let stored = null;
+        try {
+            stored = localStorage.getItem(LANG_STORAGE_KEY);
+        } catch (storageError) {
+            console.debug('localStorage недоступен:', storageError);
+        }
+        const cookieLang = readCookie('lang');
+        if (stored && stored !== i18n.lang && cookieLang !== stored) {
+            persistLanguage(stored);
+            window.location.reload();
+            return true;
+        }
+        return false;
+    }
+
+    function readCookie(name) {
+        return document.cookie
+            .split(';')
+            .map(part => part.trim().split('='))
+            .find(([cookieName]) => cookieName === name)?.[1];
+    }
+
+    function currentTheme() {
+        return document.documentElement.getAttribute('data-theme') === 'light' ? 'light' : 'dark';
+    }
+
+    function applyTheme(theme) {
+        document.documentElement.setAttribute('data-theme', theme);
+        try {
+            localStorage.setItem(THEME_STORAGE_KEY, theme);
+        } catch (storageError) {
+            console.debug('localStorage недоступен:', storageError);
+        }
+        document.cookie = `theme=${theme}; path=/; max-age=31536000; SameSite=Lax`;
+        const toggle = document.getElementById('theme-toggle');
+        if (toggle) {
+            toggle.textContent = theme === 'light' ? t('page.theme-dark') : t('page.theme-light');
+        }
+    }
+
+    function initThemeToggle() {
+        let stored = null;
+        try {
+            stored = localStorage.getItem(THEME_STORAGE_KEY);
+        } catch (storageError) {
+            console.debug('localStorage недоступен:', storageError);
+        }
+        if ((stored === 'light' || stored === 'dark') && stored !== currentTheme()) {
+            applyTheme(stored);
+        }
+        const toggle = document.getElementById('theme-toggle');
+        if (!toggle) {
+            return;
+        }
+        toggle.addEventListener('click', () => {
+            applyTheme(currentTheme() === 'light' ? 'dark' : 'light');
+        });
+    }
+
+    function initLanguageToggle() {
+        const toggle = document.getElementById('lang-toggle');
+        if (!toggle) {
+            return;
+        }
+        toggle.addEventListener('click', () => {
+            persistLanguage(i18n.lang === 'ru' ? 'en' : 'ru');
+            window.location.reload();
+        });
+    }
+
+    function setStatus(type, message) {
+        if (!statusBar) {
+            return;
+        }
+        statusBar.textContent = message;
         statusBar.setAttribute('data-type', type);
         statusBar.classList.add('visible');
         if (type !== 'error') {
@@ -447,7 +1234,108 @@ const DASHBOARD_SCRIPT: &str = r#"
             return;
         }
         const now = new Date();
-        lastUpdatedEl.textContent = now.toLocaleString('ru-RU');
+        lastUpdatedEl.textContent = now.toLocaleString(i18n.locale);
+    }
+
+    function setBackendState(state, text, details) {
+        if (!backendStatusEl) {
+            return;
+        }
+        backendStatusEl.setAttribute('data-state', state);
+        backendStatusEl.textContent = text;
+        backendStatusEl.title = details ?? '';
+    }
+
+    function armHeartbeatWatchdog() {
+        clearTimeout(heartbeatTimer);
+        heartbeatTimer = setTimeout(() => {
+            setBackendState('down', t('backend.no-heartbeat'));
+        }, HEARTBEAT_TIMEOUT_MS);
+    }
+
+    function renderHeartbeat(status) {
+        if (!status) {
+            return;
+        }
+        armHeartbeatWatchdog();
+        const worker = status.worker ?? {};
+        const database = status.database ?? {};
+        let details = [
+            worker.enabled && worker.nextTickAt
+                ? t('backend.next-tick', { time: new Date(worker.nextTickAt).toLocaleTimeString(i18n.locale) })
+                : t('backend.worker-off'),
+            t('backend.pool', {
+                size: database.size ?? 0,
+                idle: database.idle ?? 0,
+                max: database.maxConnections ?? 0,
+            }),
+            t('backend.callback-retries', { count: status.pendingCallbackRetries ?? '-' }),
+        ];
+        const latency = status.assignmentLatency;
+        if (latency?.p95Seconds != null) {
+            details.push(t('backend.assignment-latency', {
+                seconds: Math.round(latency.p95Seconds),
+                slo: latency.sloSeconds ?? '-',
+            }));
+        }
+        details = details.join('\n');
+
+        renderDeadLetterBadge(status.deadLetterCallbacks);
+
+        if (!status.degraded) {
+            backendDegraded = false;
+            setBackendState('ok', t('backend.ok'), details);
+            return;
+        }
+        const reasons = [];
+        if (!database.ok) {
+            reasons.push(t('backend.db-down'));
+        }
+        if (worker.stalled) {
+            reasons.push(t('backend.worker-stalled'));
+        }
+        setBackendState('degraded', t('backend.degraded'), `${reasons.join(', ')}\n${details}`);
+        if (!backendDegraded) {
+            setStatus('warning', t('status.backend-degraded', { reasons: reasons.join(', ') }));
+        }
+        backendDegraded = true;
+    }
+
+    function renderDeadLetterBadge(count) {
+        if (!deadLetterBadge || typeof count !== 'number') {
+            return;
+        }
+        deadLetterCount = count;
+        deadLetterBadge.hidden = count === 0;
+        deadLetterBadge.textContent = t('dlq.badge', { count });
+        deadLetterBadge.title = t('dlq.retry-hint');
+    }
+
+    async function retryDeadLetters() {
+        if (!deadLetterCount || !window.confirm(t('dlq.retry-confirm', { count: deadLetterCount }))) {
+            return;
+        }
+        try {
+            const result = await fetchJson('/api/callbacks/dead-letter/retry', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({}),
+            });
+            setStatus('success', t('dlq.retry-done', { count: result?.requeued ?? 0 }));
+            await loadServerStatus();
+        } catch (error) {
+            console.error('Ошибка повтора колбэков:', error);
+            setStatus('error', t('dlq.retry-failed', { error: error.message }));
+        }
+    }
+
+    async function loadServerStatus() {
+        try {
+            renderHeartbeat(await fetchJson('/api/status'));
+        } catch (error) {
+            console.error('Ошибка загрузки состояния сервера:', error);
+            setBackendState('down', t('backend.unreachable'));
+        }
     }
 
     function formatAmount(value) {
@@ -458,12 +1346,18 @@ const DASHBOARD_SCRIPT: &str = r#"
         if (Number.isNaN(num)) {
             return '-';
         }
-        return num.toLocaleString('ru-RU', {
+        return num.toLocaleString(i18n.locale, {
             minimumFractionDigits: 2,
             maximumFractionDigits: 2,
         });
     }
 
+    // Same as formatAmount, with the currency code unless it is RUB.
+    function formatMoney(value, currency) {
+        const amount = formatAmount(value);
+        return currency && currency !== 'RUB' && amount !== '-' ? `${amount} ${currency}` : amount;
+    }
+
     function formatDateTime(value) {
         if (!value) {
             return '-';
@@ -472,19 +1366,143 @@ const DASHBOARD_SCRIPT: &str = r#"
         if (Number.isNaN(date.getTime())) {
             return value;
         }
-        return date.toLocaleString('ru-RU');
+        return date.toLocaleString(i18n.locale);
+    }
+
+    // The raw UTC value, shown as a tooltip next to local times; matches the
+    // server-rendered `YYYY-MM-DD HH:MM:SS UTC`.
+    function formatUtc(value) {
+        const date = value ? new Date(value) : null;
+        if (!date || Number.isNaN(date.getTime())) {
+            return '';
+        }
+        return `${date.toISOString().slice(0, 19).replace('T', ' ')} UTC`;
+    }
+
+    // Server-rendered times come in UTC; show them in the browser's zone.
+    function localizeTimes(root) {
+        root.querySelectorAll('time.local-time[datetime]').forEach(time => {
+            time.textContent = formatDateTime(time.dateTime);
+        });
+    }
+
+    function updateMetrics(summary) {
+        if (!summary) {
+            return;
+        }
+        const setText = (element, value) => {
+            if (element) {
+                element.textContent = value;
+            }
+        };
+        setText(metrics.traders, String(summary.eligibleTraders ?? 0));
+        setText(metrics.payouts, String(summary.unassignedCount ?? 0));
+        setText(metrics.payoutSum, formatAmount(summary.unassignedAmount ?? 0));
+        setText(metrics.assignedToday, String(summary.assignedToday ?? 0));
+        setText(metrics.assignedTodaySum, formatAmount(summary.assignedTodayAmount ?? 0));
+        setText(metrics.cancelledToday, String(summary.cancelledToday ?? 0));
+        setText(metrics.cancelledTodaySum, formatAmount(summary.cancelledTodayAmount ?? 0));
+        setText(metrics.completedToday, String(summary.completedToday ?? 0));
+        setText(metrics.completedTodaySum, formatAmount(summary.completedTodayAmount ?? 0));
+    }
+
+    async function loadSummary() {
+        try {
+            updateMetrics(await fetchJson('/api/stats/summary'));
+        } catch (error) {
+            console.error('Ошибка загрузки сводки:', error);
+        }
+    }
+
+    function readPagination(response, fallback) {
+        const pagination = response?.pagination ?? {};
+        return {
+            page: Number(pagination.page ?? fallback.page ?? 1),
+            totalPages: Number(pagination.totalPages ?? 0),
+            total: Number(pagination.total ?? 0),
+            perPage: Number(pagination.perPage ?? fallback.perPage ?? 25),
+        };
+    }
+
+    function updatePager(controls, pagination) {
+        if (controls.pageInfo) {
+            if (pagination.totalPages > 0) {
+                controls.pageInfo.textContent = t('pagination.page-info', {
+                    page: pagination.page,
+                    pages: pagination.totalPages,
+                    total: pagination.total,
+                });
+            } else {
+                controls.pageInfo.textContent = t('pagination.page-info', { page: 0, pages: 0, total: 0 });
+            }
+        }
+
+        if (controls.prev) {
+            controls.prev.disabled = pagination.page <= 1;
+        }
+        if (controls.next) {
+            controls.next.disabled =
+                pagination.totalPages === 0 || pagination.page >= pagination.totalPages;
+        }
+    }
+
+    // Every HTML string rendered from API data goes through `html`: values
+    // interpolated into it are escaped unless they are `html` results
+    // themselves (or arrays of them), so server data can never become markup.
+    const HTML_ESCAPES = {
+        '&': '&amp;',
+        '<': '&lt;',
+        '>': '&gt;',
+        '"': '&quot;',
+        "'": '&#39;',
+        '`': '&#96;',
+    };
+
+    class SafeHtml {
+        constructor(value) {
+            this.value = value;
+        }
+
+        toString() {
+            return this.value;
+        }
+    }
+
+    function escapeHtml(value) {
+        return String(value ?? '').replace(/[&<>"'`]/g, ch => HTML_ESCAPES[ch]);
     }
 
-    function updateMetrics(traders, payouts) {
-        if (metrics.traders) {
-            metrics.traders.textContent = traders.length.toString();
+    function htmlValue(value) {
+        if (value instanceof SafeHtml) {
+            return value.value;
         }
-        if (metrics.payouts) {
-            metrics.payouts.textContent = payouts.length.toString();
+        if (Array.isArray(value)) {
+            return value.map(htmlValue).join('');
         }
-        if (metrics.payoutSum) {
-            const total = payouts.reduce((acc, payout) => acc + Number(payout.amount ?? 0), 0);
-            metrics.payoutSum.textContent = formatAmount(total);
+        if (value === null || value === undefined || value === false) {
+            return '';
+        }
+        return escapeHtml(value);
+    }
+
+    function html(strings, ...values) {
+        let result = strings[0];
+        values.forEach((value, index) => {
+            result += htmlValue(value) + strings[index + 1];
+        });
+        return new SafeHtml(result);
+    }
+
+    function setHtml(element, content) {
+        element.innerHTML = htmlValue(content);
+    }
+
+    function safeUrl(value) {
+        try {
+            const url = new URL(value, window.location.href);
+            return url.protocol === 'https:' || url.protocol === 'http:' ? url.href : null;
+        } catch (error) {
+            return null;
         }
     }
 
@@ -492,14 +1510,51 @@ const DASHBOARD_SCRIPT: &str = r#"
         if (!tbody) {
             return;
         }
-        tbody.innerHTML = `<tr><td class="empty" colspan="${colspan}">${message}</td></tr>`;
+        setHtml(tbody, html`<tr><td class="empty" colspan="${colspan}">${message}</td></tr>`);
+    }
+
+    const csrfToken = document.querySelector('meta[name="csrf-token"]')?.content || '';
+
+    // API errors come as `{ error: { code, message } }`; the code is kept on
+    // the thrown error for callers that handle a failure specifically.
+    async function responseError(response) {
+        const text = await response.text();
+        let body = null;
+        try {
+            body = JSON.parse(text);
+        } catch (error) {
+            body = null;
+        }
+        const error = new Error(body?.error?.message || text || response.statusText);
+        error.code = body?.error?.code || null;
+        error.status = response.status;
+        return error;
+    }
+
+    async function fetchFragment(url) {
+        const response = await fetch(url);
+        if (!response.ok) {
+            throw await responseError(response);
+        }
+        let pagination = null;
+        try {
+            pagination = JSON.parse(response.headers.get('X-Pagination') || 'null');
+        } catch (error) {
+            pagination = null;
+        }
+        return { html: await response.text(), pagination };
     }
 
     async function fetchJson(url, options) {
+        const method = (options?.method || 'GET').toUpperCase();
+        if (method !== 'GET' && method !== 'HEAD') {
+            const headers = new Headers(options.headers || {});
+            headers.set('X-CSRF-Token', csrfToken);
+            options = { ...options, headers };
+        }
         const response = await fetch(url, options);
         if (!response.ok) {
-            const text = await response.text();
-            throw new Error(text || response.statusText);
+            throw await responseError(response);
         }
         if (response.status === 204) {
             return null;
@@ -507,39 +1562,29 @@ const DASHBOARD_SCRIPT: &str = r#"
         return response.json();
     }
 
-    function renderTraders(traders) {
-        currentTraders = Array.isArray(traders) ? traders : [];
+    // Rows come from `/fragments/traders-table`; on the first render the
+    // server-rendered rows are kept and only the pager state is read.
+    function renderTraders(fragment) {
+        tradersPagination = readPagination(fragment, tradersFilters);
+        tradersFilters.page = tradersPagination.page;
+        updatePager(tradersPager, tradersPagination);
         const tbody = document.querySelector('#traders-table tbody');
         if (!tbody) {
             return;
         }
-        if (!currentTraders.length) {
-            renderEmpty(tbody, 6, 'Нет подходящих трейдеров');
+        if (fragment?.html !== undefined) {
+            setHtml(tbody, new SafeHtml(fragment.html));
+        }
+        localizeTimes(tbody);
+        currentTraders = Array.from(tbody.querySelectorAll('tr[data-trader-id]')).map(row => ({
+            id: row.dataset.traderId,
+            email: row.dataset.email,
+        }));
+        if (!currentTraders.length && tradersPagination.page > tradersPagination.totalPages && tradersPagination.totalPages > 0) {
+            tradersFilters.page = tradersPagination.totalPages;
+            loadTraders();
             return;
         }
-        tbody.innerHTML = currentTraders.map(trader => {
-            const balance = formatAmount(trader.balanceRub);
-            const frozen = formatAmount(trader.frozenRub);
-            const payoutBalance = formatAmount(trader.payoutBalance);
-            const limitValue = trader.maxAmount === null || trader.maxAmount === undefined
-                ? ''
-                : Number(trader.maxAmount).toFixed(2);
-            return `
-                <tr>
-                    <td>${trader.numericId}</td>
-                    <td>${trader.email}</td>
-                    <td>${balance}</td>
-                    <td>${frozen}</td>
-                    <td>${payoutBalance}</td>
-                    <td>
-                        <div class="limit-controls">
-                            <input type="number" min="0" step="0.01" value="${limitValue}" id="limit-input-${trader.id}" placeholder="Без лимита" />
-                            <button class="save-limit" data-trader-id="${trader.id}">Сохранить</button>
-                        </div>
-                    </td>
-                </tr>
-            `;
-        }).join('');
 
         tbody.querySelectorAll('.save-limit').forEach(button => {
             button.addEventListener('click', async (event) => {
@@ -547,677 +1592,2671 @@ const DASHBOARD_SCRIPT: &str = r#"
                 await saveTraderLimit(traderId);
             });
         });
+        tbody.querySelectorAll('.trader-select').forEach(checkbox => {
+            checkbox.checked = selectedTraders.has(checkbox.dataset.traderId);
+            checkbox.addEventListener('change', (event) => {
+                const traderId = event.currentTarget.getAttribute('data-trader-id');
+                if (event.currentTarget.checked) {
+                    selectedTraders.add(traderId);
+                } else {
+                    selectedTraders.delete(traderId);
+                }
+                updateTraderSelectionControls();
+            });
+        });
+        updateTraderSelectionControls();
     }
 
-    function renderPayouts(payouts) {
-        currentPayouts = Array.isArray(payouts) ? payouts : [];
-        const tbody = document.querySelector('#payouts-table tbody');
-        if (!tbody) {
-            return;
+    function updateTraderSelectionControls() {
+        const all = !!document.getElementById('traders-bulk-all')?.checked;
+        const apply = document.getElementById('traders-bulk-apply');
+        if (apply) {
+            apply.textContent = all
+                ? t('limits-bulk.apply-all')
+                : t('limits-bulk.apply', { count: selectedTraders.size });
+            apply.disabled = !all && selectedTraders.size === 0;
         }
-        if (!currentPayouts.length) {
-            renderEmpty(tbody, 5, 'Нет нераспределенных выплат');
-            return;
+        const selectAll = document.getElementById('traders-select-all');
+        if (selectAll) {
+            selectAll.disabled = all || currentTraders.length === 0;
+            selectAll.checked = currentTraders.length > 0
+                && currentTraders.every(trader => selectedTraders.has(trader.id));
         }
-
-        const traderOptions = currentTraders.map(trader => `
-            <option value="${trader.id}">
-                ${trader.email} (ID: ${trader.numericId})
-            </option>
-        `).join('');
-
-        tbody.innerHTML = currentPayouts.map(payout => {
-            const amount = formatAmount(payout.amount);
-            const bank = payout.bank ?? '-';
-            const external = payout.externalReference ?? '-';
-            return `
-                <tr>
-                    <td>${payout.numericId}</td>
-                    <td>${amount}</td>
-                    <td>${bank}</td>
-                    <td>${external}</td>
-                    <td>
-                        <div class="assign-controls">
-                            <select id="assign-select-${payout.id}">
-                                <option value="">Выберите трейдера</option>
-                                ${traderOptions}
-                            </select>
-                            <button class="assign-button" data-payout-id="${payout.id}">Привязать</button>
-                        </div>
-                    </td>
-                </tr>
-            `;
-        }).join('');
-
-        tbody.querySelectorAll('.assign-button').forEach(button => {
-            button.addEventListener('click', async (event) => {
-                const payoutId = event.currentTarget.getAttribute('data-payout-id');
-                await assignPayout(payoutId);
-            });
+        document.querySelectorAll('#traders-table .trader-select').forEach(checkbox => {
+            checkbox.disabled = all;
         });
     }
 
-    function renderDeals(response) {
-        const tbody = document.querySelector('#deals-table tbody');
-        if (!tbody) {
-            return;
+    function formatLimitRange(range) {
+        const min = range?.minAmount ?? null;
+        const max = range?.maxAmount ?? null;
+        if (min !== null && max !== null) {
+            return `${formatMoney(min)} – ${formatMoney(max)}`;
         }
-
-        const items = Array.isArray(response?.items) ? response.items : [];
-        currentDeals = items;
-
-        if (response?.pagination) {
-            const pagination = response.pagination;
-            dealsPagination = {
-                page: Number(pagination.page ?? dealsFilters.page ?? 1),
-                totalPages: Number(pagination.totalPages ?? 0),
-                total: Number(pagination.total ?? 0),
-                perPage: Number(pagination.perPage ?? dealsFilters.perPage ?? 25),
-            };
-            dealsFilters.page = dealsPagination.page;
-            dealsFilters.perPage = dealsPagination.perPage;
-        } else {
-            dealsPagination.totalPages = items.length ? 1 : 0;
-            dealsPagination.total = items.length;
+        if (min !== null) {
+            return `≥ ${formatMoney(min)}`;
         }
-
-        if (!items.length) {
-            renderEmpty(tbody, 9, 'Нет выплат по заданным фильтрам');
-            updateDealsPagination();
-            syncDealsFiltersToControls();
-            return;
+        if (max !== null) {
+            return `≤ ${formatMoney(max)}`;
         }
-
-        tbody.innerHTML = items.map(deal => {
-            const amount = formatAmount(deal.amount);
-            const external = deal.externalReference ?? '-';
-            const cancelReason = deal.cancelReason ?? '-';
-            const createdAt = formatDateTime(deal.createdAt);
-            const disableCancel = ['CANCELLED', 'COMPLETED', 'SUCCESS', 'FAILED'].includes(deal.status ?? '');
-            const cancelTitle = disableCancel
-                ? 'Отмена недоступна для этого статуса'
-                : 'Отменить выплату';
-            return `
-                <tr>
-                    <td>${deal.numericId}</td>
-                    <td><span class="mono">${deal.id}</span></td>
-                    <td>${external}</td>
-                    <td>${deal.wallet}</td>
-                    <td>${deal.bank}</td>
-                    <td>${amount}</td>
-                    <td>${deal.status}</td>
-                    <td>${createdAt}</td>
-                    <td>
-                        <div class="deal-actions">
-                            <span class="deal-reason">${cancelReason}</span>
-                            <button
-                                class="danger cancel-deal"
-                                data-deal-id="${deal.id}"
-                                title="${cancelTitle}"
-                                ${disableCancel ? 'disabled' : ''}
-                            >Отменить</button>
-                        </div>
-                    </td>
-                </tr>
-            `;
-        }).join('');
-
-        tbody.querySelectorAll('.cancel-deal').forEach(button => {
-            button.addEventListener('click', async (event) => {
-                const dealId = event.currentTarget.getAttribute('data-deal-id');
-                await cancelDeal(dealId);
-            });
-        });
-
-        updateDealsPagination();
-        syncDealsFiltersToControls();
+        return t('limits-bulk.no-range');
     }
 
-    function updateDealsPagination() {
-        const pageInfo = dealsControls.pageInfo;
-        if (pageInfo) {
-            if (dealsPagination.totalPages > 0) {
-                pageInfo.textContent = `${dealsPagination.page} / ${dealsPagination.totalPages} (всего ${dealsPagination.total})`;
-            } else {
-                pageInfo.textContent = '0 / 0 (всего 0)';
-            }
+    function readLimitTemplate() {
+        const parse = id => {
+            const value = document.getElementById(id)?.value.trim() ?? '';
+            return value === '' ? null : Number(value);
+        };
+        const minAmount = parse('traders-bulk-min');
+        const maxAmount = parse('traders-bulk-max');
+        const maxOpenPayouts = parse('traders-bulk-cap');
+        if ([minAmount, maxAmount].some(value => value !== null && (Number.isNaN(value) || value < 0))) {
+            setStatus('warning', t('status.limit-invalid'));
+            return null;
         }
-
-        if (dealsControls.prev) {
-            dealsControls.prev.disabled = dealsPagination.page <= 1;
+        if (minAmount && maxAmount && minAmount > maxAmount) {
+            setStatus('warning', t('status.limit-range-invalid'));
+            return null;
         }
-        if (dealsControls.next) {
-            const noMorePages =
-                dealsPagination.totalPages === 0 || dealsPagination.page >= dealsPagination.totalPages;
-            dealsControls.next.disabled = noMorePages;
+        if (maxOpenPayouts !== null && (!Number.isInteger(maxOpenPayouts) || maxOpenPayouts < 0)) {
+            setStatus('warning', t('status.limits-bulk-cap-invalid'));
+            return null;
         }
-    }
-
-    function syncDealsFiltersToControls() {
-        if (dealsControls.search) {
-            dealsControls.search.value = dealsFilters.search ?? '';
+        // Empty fields keep what the traders have.
+        const template = {};
+        if (minAmount !== null || maxAmount !== null) {
+            template.limit = { minAmount, maxAmount };
         }
-        if (dealsControls.wallet) {
-            dealsControls.wallet.value = dealsFilters.wallet ?? '';
+        if (maxOpenPayouts !== null) {
+            template.maxOpenPayouts = maxOpenPayouts;
         }
-        if (dealsControls.amount) {
-            dealsControls.amount.value = dealsFilters.amount ?? '';
+        if (!Object.keys(template).length) {
+            setStatus('warning', t('status.limits-bulk-empty'));
+            return null;
         }
-        if (dealsControls.status) {
-            dealsControls.status.value = dealsFilters.status ?? '';
+        return template;
+    }
+
+    function openLimitsPreview(preview) {
+        const changes = (preview?.traders ?? []).filter(trader => trader.changed);
+        const dialog = document.getElementById('limits-bulk-dialog');
+        const title = t('limits-bulk.preview', {
+            affected: preview?.affected ?? 0,
+            total: preview?.traders?.length ?? 0,
+        });
+        if (!dialog || typeof dialog.showModal !== 'function') {
+            return Promise.resolve(window.confirm(title));
         }
-        if (dealsControls.perPage) {
-            dealsControls.perPage.value = String(dealsFilters.perPage ?? 25);
+        const summary = document.getElementById('limits-bulk-summary');
+        if (summary) {
+            summary.textContent = title;
         }
-        syncDealsSortIndicator();
+        const cap = value => value ?? t('limits-bulk.default-cap');
+        const change = (before, after) => (before === after ? after : `${before} → ${after}`);
+        const tbody = document.querySelector('#limits-bulk-table tbody');
+        if (tbody) {
+            setHtml(tbody, changes.map(trader => html`<tr>
+                <td>${trader.numericId}</td>
+                <td>${trader.email}</td>
+                <td>${change(formatLimitRange(trader.previousLimit), formatLimitRange(trader.limit))}</td>
+                <td>${change(cap(trader.previousMaxOpenPayouts), cap(trader.maxOpenPayouts))}</td>
+            </tr>`));
+        }
+        return new Promise(resolve => {
+            dialog.addEventListener('close', () => resolve(dialog.returnValue === 'confirm'), { once: true });
+            dialog.returnValue = '';
+            dialog.showModal();
+        });
     }
 
-    function syncDealsSortIndicator() {
-        if (!dealsControls.sortStatus) {
+    async function applyLimitTemplate() {
+        const template = readLimitTemplate();
+        if (!template) {
             return;
         }
-        if (dealsFilters.sort === 'status') {
-            dealsControls.sortStatus.classList.add('active');
-            dealsControls.sortStatus.setAttribute('data-order', dealsFilters.order);
-            dealsControls.sortStatus.textContent =
-                dealsFilters.order === 'asc' ? 'Статус ↑' : 'Статус ↓';
-        } else {
-            dealsControls.sortStatus.classList.remove('active');
-            dealsControls.sortStatus.removeAttribute('data-order');
-            dealsControls.sortStatus.textContent = 'Сортировка по статусу';
+        const all = !!document.getElementById('traders-bulk-all')?.checked;
+        const payload = all
+            ? { all: true, search: tradersFilters.search, ...template }
+            : { traderIds: Array.from(selectedTraders), ...template };
+        const post = body => fetchJson('/api/traders/limits/bulk', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify(body),
+        });
+        try {
+            const preview = await post({ ...payload, preview: true });
+            if (!preview?.affected) {
+                setStatus('info', t('status.limits-bulk-nothing'));
+                return;
+            }
+            if (!(await openLimitsPreview(preview))) {
+                return;
+            }
+            const result = await post(payload);
+            setStatus('success', t('status.limits-bulk-saved', { count: result?.affected ?? 0 }));
+            selectedTraders.clear();
+            await Promise.all([loadData(false), loadDeals(false)]);
+        } catch (error) {
+            console.error('Ошибка применения шаблона лимитов:', error);
+            setStatus('error', t('status.limits-bulk-failed', { error: error.message }));
         }
     }
 
-    function scheduleDealsReload() {
-        if (dealsFilterTimer) {
-            clearTimeout(dealsFilterTimer);
+    function tradersQueryString() {
+        const params = new URLSearchParams();
+        if (tradersFilters.search) {
+            params.set('search', tradersFilters.search);
         }
-        dealsFilterTimer = setTimeout(() => {
-            dealsFilters.page = 1;
-            loadDeals(true);
-        }, 350);
+        params.set('sort', tradersFilters.sort);
+        params.set('order', tradersFilters.order);
+        params.set('page', tradersFilters.page.toString());
+        params.set('perPage', tradersFilters.perPage.toString());
+        return params.toString();
     }
 
-    async function loadDeals(showStatus = false) {
-        if (isDealsLoading) {
-            return;
-        }
-        isDealsLoading = true;
-        try {
-            if (showStatus) {
-                setStatus('info', 'Обновляем список выплат...');
-            }
-            const params = new URLSearchParams();
-            if (dealsFilters.search) {
-                params.set('search', dealsFilters.search);
-            }
-            if (dealsFilters.wallet) {
-                params.set('wallet', dealsFilters.wallet);
-            }
-            if (dealsFilters.amount) {
-                const num = Number(dealsFilters.amount);
-                if (!Number.isNaN(num)) {
-                    params.set('amount', String(num));
-                }
-            }
-            if (dealsFilters.status) {
-                params.set('status', dealsFilters.status);
-            }
-            params.set('page', String(dealsFilters.page ?? 1));
-            params.set('perPage', String(dealsFilters.perPage ?? 25));
-            params.set('sort', dealsFilters.sort ?? 'createdAt');
-            params.set('order', dealsFilters.order ?? 'desc');
+    function payoutsQueryString() {
+        const params = new URLSearchParams();
+        params.set('page', payoutsPagination.page.toString());
+        params.set('perPage', payoutsPagination.perPage.toString());
+        return params.toString();
+    }
 
-            const query = params.toString();
-            const response = await fetchJson(`/api/deals${query ? `?${query}` : ''}`);
-            renderDeals(response);
-            if (showStatus) {
-                setStatus('success', 'Список выплат обновлен.');
+    function syncTradersSortIndicators() {
+        document.querySelectorAll('#traders-table th.sortable').forEach(th => {
+            if (th.getAttribute('data-sort') === tradersFilters.sort) {
+                th.setAttribute('data-order', tradersFilters.order);
+            } else {
+                th.removeAttribute('data-order');
             }
+        });
+        const groupToggle = document.getElementById('traders-group-team');
+        if (groupToggle) {
+            groupToggle.checked = tradersFilters.sort === 'team';
+        }
+    }
+
+    async function loadTraders() {
+        try {
+            const traders = await fetchFragment(`/fragments/traders-table?${tradersQueryString()}`);
+            renderTraders(traders);
         } catch (error) {
-            console.error('Ошибка загрузки выплат:', error);
-            const tbody = document.querySelector('#deals-table tbody');
-            renderEmpty(tbody, 9, 'Не удалось загрузить выплаты');
-            if (showStatus) {
-                setStatus('error', 'Не удалось загрузить выплаты: ' + error.message);
-            }
-        } finally {
-            isDealsLoading = false;
+            console.error('Ошибка загрузки трейдеров:', error);
+            const tbody = document.querySelector('#traders-table tbody');
+            renderEmpty(tbody, 10, t('traders.load-error'));
         }
     }
 
-    async function cancelDeal(dealId) {
-        if (!dealId) {
+    // The assignments endpoint counts days in UTC.
+    function todayIso() {
+        return new Date().toISOString().slice(0, 10);
+    }
+
+    function renderAssignments(response) {
+        const tbody = document.querySelector('#assignments-table tbody');
+        const summary = document.getElementById('assignments-summary');
+        const items = Array.isArray(response?.items) ? response.items : [];
+        const pagination = readPagination(response, { page: 1, perPage: 100 });
+        if (summary) {
+            summary.textContent = t('assignments.summary', {
+                count: pagination.total,
+                amount: formatAmount(response?.totalAmount ?? 0),
+            });
+        }
+        if (!tbody) {
             return;
         }
-        const deal = currentDeals.find(item => item.id === dealId);
-        if (deal && ['CANCELLED', 'COMPLETED', 'SUCCESS', 'FAILED'].includes(deal.status ?? '')) {
-            setStatus('warning', 'Эту выплату нельзя отменить.');
+        if (!items.length) {
+            renderEmpty(tbody, 6, t('assignments.empty'));
             return;
         }
-        const confirmed = window.confirm('Вы уверены, что хотите отменить выплату?');
-        if (!confirmed) {
+        setHtml(tbody, items.map(item => html`
+            <tr>
+                <td>${item.numericId}</td>
+                <td>${formatMoney(item.amount, item.currency)}</td>
+                <td>${item.status}${item.current ? '' : html` <span class="deal-reason">(${t('assignments.moved')})</span>`}</td>
+                <td>${item.bank}</td>
+                <td title="${formatUtc(item.assignedAt)}">${formatDateTime(item.assignedAt)}</td>
+                <td title="${formatUtc(item.cancelledAt ?? item.acceptedAt)}">${formatDateTime(item.cancelledAt ?? item.acceptedAt)}</td>
+            </tr>
+        `));
+    }
+
+    async function loadAssignments() {
+        const dialog = document.getElementById('assignments-dialog');
+        const traderId = dialog?.dataset.traderId;
+        if (!traderId) {
             return;
         }
-        let reason = window.prompt('Причина отмены (необязательно):', '');
-        if (reason === null) {
-            reason = '';
+        const params = new URLSearchParams({ perPage: '100' });
+        const from = document.getElementById('assignments-from')?.value;
+        const to = document.getElementById('assignments-to')?.value;
+        if (from) {
+            params.set('from', from);
         }
-        const payload = {};
-        if (reason.trim()) {
-            payload.reason = reason.trim();
+        if (to) {
+            params.set('to', to);
         }
         try {
-            const result = await fetchJson(`/api/payouts/${dealId}/cancel`, {
-                method: 'POST',
-                headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify(payload),
-            });
-            if (result?.callbackDispatched) {
-                setStatus('success', 'Выплата отменена.');
-            } else if (result?.callbackError) {
-                setStatus('warning', 'Выплата отменена, но колбэк не доставлен: ' + result.callbackError);
-            } else {
-                setStatus('success', 'Выплата отменена.');
+            renderAssignments(await fetchJson(`/api/traders/${encodeURIComponent(traderId)}/assignments?${params.toString()}`));
+        } catch (error) {
+            console.error('Ошибка загрузки истории трейдера:', error);
+            renderEmpty(document.querySelector('#assignments-table tbody'), 6, t('assignments.load-error', { error: error.message }));
+        }
+    }
+
+    async function loadEligibilityHistory(traderId) {
+        const tbody = document.querySelector('#eligibility-table tbody');
+        if (!tbody) {
+            return;
+        }
+        try {
+            const changes = await fetchJson(`/api/traders/${encodeURIComponent(traderId)}/eligibility-history`);
+            if (!changes.length) {
+                renderEmpty(tbody, 4, t('eligibility.empty'));
+                return;
             }
-            await loadDeals(false);
-            await loadData(false);
+            setHtml(tbody, changes.map(change => html`
+                <tr>
+                    <td title="${formatUtc(change.createdAt)}">${formatDateTime(change.createdAt)}</td>
+                    <td>${t(change.eligible ? 'eligibility.appeared' : 'eligibility.dropped')}</td>
+                    <td>${change.reasons.map(reason => t(`eligibility.reason.${reason}`)).join(', ')}</td>
+                    <td>${[
+                        ...change.merchantsAdded.map(id => `+${id}`),
+                        ...change.merchantsRemoved.map(id => `−${id}`),
+                    ].join(', ')}</td>
+                </tr>
+            `));
         } catch (error) {
-            console.error('Ошибка отмены выплаты:', error);
-            setStatus('error', 'Не удалось отменить выплату: ' + error.message);
+            console.error('Ошибка загрузки истории доступности трейдера:', error);
+            renderEmpty(tbody, 4, t('assignments.load-error', { error: error.message }));
         }
     }
 
-    function initDealsControls() {
-        if (dealsControls.search) {
-            dealsControls.search.addEventListener('input', (event) => {
-                dealsFilters.search = event.target.value.trim();
-                scheduleDealsReload();
-            });
+    async function openAssignmentsDialog(traderId) {
+        const dialog = document.getElementById('assignments-dialog');
+        if (!traderId || !dialog) {
+            return;
         }
-        if (dealsControls.wallet) {
-            dealsControls.wallet.addEventListener('input', (event) => {
-                dealsFilters.wallet = event.target.value.trim();
-                scheduleDealsReload();
-            });
+        const trader = currentTraders.find(item => item.id === traderId)
+            ?? traderOptions.find(item => item.id === traderId);
+        const title = document.getElementById('assignments-dialog-title');
+        if (title) {
+            title.textContent = t('assignments.title', { trader: trader?.email ?? traderId });
         }
-        if (dealsControls.amount) {
-            dealsControls.amount.addEventListener('input', (event) => {
-                dealsFilters.amount = event.target.value.trim();
-                scheduleDealsReload();
-            });
+        const today = todayIso();
+        const from = document.getElementById('assignments-from');
+        const to = document.getElementById('assignments-to');
+        if (from) {
+            from.value = today;
         }
-        if (dealsControls.status) {
-            dealsControls.status.addEventListener('change', (event) => {
-                dealsFilters.status = event.target.value.trim();
-                dealsFilters.page = 1;
-                loadDeals(true);
-            });
+        if (to) {
+            to.value = today;
         }
-        if (dealsControls.perPage) {
-            dealsControls.perPage.addEventListener('change', (event) => {
-                const value = Number(event.target.value);
-                dealsFilters.perPage = Number.isNaN(value) ? 25 : value;
-                dealsFilters.page = 1;
-                loadDeals(true);
-            });
+        dialog.dataset.traderId = traderId;
+        await Promise.all([loadAssignments(), loadEligibilityHistory(traderId)]);
+        if (!dialog.open) {
+            dialog.showModal();
         }
-        if (dealsControls.sortStatus) {
-            dealsControls.sortStatus.addEventListener('click', () => {
-                if (dealsFilters.sort === 'status') {
-                    dealsFilters.order = dealsFilters.order === 'asc' ? 'desc' : 'asc';
+    }
+
+    function initTradersControls() {
+        document.querySelector('#traders-table tbody')?.addEventListener('click', (event) => {
+            if (event.target.closest('input, button')) {
+                return;
+            }
+            const row = event.target.closest('tr[data-trader-id]');
+            if (row) {
+                openAssignmentsDialog(row.getAttribute('data-trader-id'));
+            }
+        });
+        document.getElementById('assignments-from')?.addEventListener('change', loadAssignments);
+        document.getElementById('traders-bulk-apply')?.addEventListener('click', applyLimitTemplate);
+        document.getElementById('traders-bulk-all')?.addEventListener('change', updateTraderSelectionControls);
+        document.getElementById('traders-select-all')?.addEventListener('change', (event) => {
+            const checked = event.currentTarget.checked;
+            currentTraders.forEach(trader => {
+                if (checked) {
+                    selectedTraders.add(trader.id);
                 } else {
-                    dealsFilters.sort = 'status';
-                    dealsFilters.order = 'asc';
+                    selectedTraders.delete(trader.id);
                 }
-                dealsFilters.page = 1;
-                syncDealsSortIndicator();
-                loadDeals(true);
             });
-        }
-        if (dealsControls.reset) {
-            dealsControls.reset.addEventListener('click', () => {
-                dealsFilters = {
-                    search: '',
-                    wallet: '',
-                    amount: '',
-                    status: '',
-                    sort: 'createdAt',
-                    order: 'desc',
-                    page: 1,
-                    perPage: 25,
-                };
-                syncDealsFiltersToControls();
-                loadDeals(true);
+            document.querySelectorAll('#traders-table .trader-select').forEach(checkbox => {
+                checkbox.checked = checked;
             });
-        }
-        if (dealsControls.prev) {
-            dealsControls.prev.addEventListener('click', () => {
-                if (dealsFilters.page > 1) {
-                    dealsFilters.page -= 1;
-                    loadDeals(true);
+            updateTraderSelectionControls();
+        });
+        document.getElementById('assignments-to')?.addEventListener('change', loadAssignments);
+        const search = document.getElementById('traders-search');
+        if (search) {
+            search.addEventListener('input', (event) => {
+                tradersFilters.search = event.target.value.trim();
+                tradersFilters.page = 1;
+                if (tradersFilterTimer) {
+                    clearTimeout(tradersFilterTimer);
                 }
+                tradersFilterTimer = setTimeout(loadTraders, 350);
             });
         }
-        if (dealsControls.next) {
-            dealsControls.next.addEventListener('click', () => {
-                if (dealsPagination.totalPages && dealsFilters.page < dealsPagination.totalPages) {
-                    dealsFilters.page += 1;
-                    loadDeals(true);
+        // Grouping is sorting by team; the server adds a header row per team.
+        document.getElementById('traders-group-team')?.addEventListener('change', (event) => {
+            tradersFilters.sort = event.target.checked ? 'team' : 'numericId';
+            tradersFilters.order = 'asc';
+            tradersFilters.page = 1;
+            syncTradersSortIndicators();
+            loadTraders();
+        });
+        document.querySelectorAll('#traders-table th.sortable').forEach(th => {
+            th.addEventListener('click', () => {
+                const field = th.getAttribute('data-sort');
+                if (tradersFilters.sort === field) {
+                    tradersFilters.order = tradersFilters.order === 'asc' ? 'desc' : 'asc';
+                } else {
+                    tradersFilters.sort = field;
+                    tradersFilters.order = 'asc';
+                }
+                tradersFilters.page = 1;
+                syncTradersSortIndicators();
+                loadTraders();
+            });
+        });
+        if (tradersPager.prev) {
+            tradersPager.prev.addEventListener('click', () => {
+                if (tradersFilters.page > 1) {
+                    tradersFilters.page -= 1;
+                    loadTraders();
                 }
             });
         }
-    }
-
-    function renderSettings(settings) {
-        const checkbox = document.getElementById('auto-enabled');
-        const intervalInput = document.getElementById('auto-interval');
-        const enabled = Boolean(settings?.enabled);
-        const interval = Number(settings?.intervalSeconds ?? 30) || 30;
-
-        if (checkbox) {
-            checkbox.checked = enabled;
-        }
-        if (intervalInput) {
-            intervalInput.value = interval;
-        }
-        if (autoBadge) {
-            autoBadge.textContent = enabled ? 'Активно' : 'Выключено';
-            autoBadge.setAttribute('data-state', enabled ? 'on' : 'off');
-        }
-        if (settingsDescription) {
-            settingsDescription.textContent = enabled
-                ? `Автораспределение выполняется каждые ${interval} секунд.`
-                : 'Автораспределение выключено.';
+        if (tradersPager.next) {
+            tradersPager.next.addEventListener('click', () => {
+                if (tradersFilters.page < tradersPagination.totalPages) {
+                    tradersFilters.page += 1;
+                    loadTraders();
+                }
+            });
         }
+        syncTradersSortIndicators();
     }
 
-    async function loadData(showStatus = true) {
-        if (isLoading) {
-            return;
-        }
-        isLoading = true;
+    async function loadPayouts() {
         try {
-            if (showStatus) {
-                setStatus('info', 'Обновляем данные...');
-            }
-            const [traders, payouts, settings] = await Promise.all([
-                fetchJson('/api/traders'),
-                fetchJson('/api/payouts'),
-                fetchJson('/api/settings/auto-distribution'),
-            ]);
-            renderTraders(traders);
+            const payouts = await fetchJson(`/api/payouts?${payoutsQueryString()}`);
             renderPayouts(payouts);
-            renderSettings(settings);
-            updateMetrics(traders, payouts);
-            markUpdated();
-            if (showStatus) {
-                setStatus('success', 'Данные обновлены');
-            }
+            await loadSummary();
         } catch (error) {
-            console.error('Ошибка при загрузке данных:', error);
-            const tradersBody = document.querySelector('#traders-table tbody');
-            const payoutsBody = document.querySelector('#payouts-table tbody');
-            renderEmpty(tradersBody, 6, 'Ошибка загрузки трейдеров');
-            renderEmpty(payoutsBody, 5, 'Ошибка загрузки выплат');
-            setStatus('error', 'Не удалось загрузить данные: ' + error.message);
-        } finally {
-            isLoading = false;
+            console.error('Ошибка загрузки выплат:', error);
+            const tbody = document.querySelector('#payouts-table tbody');
+            renderEmpty(tbody, 5, t('payouts.load-error'));
         }
     }
 
-    async function assignPayout(payoutId) {
-        const select = document.getElementById(`assign-select-${payoutId}`);
-        const traderId = select?.value;
-
-        if (!traderId) {
-            setStatus('warning', 'Выберите трейдера для привязки.');
-            return;
+    function initPayoutsControls() {
+        if (payoutsPager.prev) {
+            payoutsPager.prev.addEventListener('click', () => {
+                if (payoutsPagination.page > 1) {
+                    payoutsPagination.page -= 1;
+                    loadPayouts();
+                }
+            });
         }
-
-        try {
-            await fetchJson(`/api/payouts/${payoutId}/assign`, {
-                method: 'POST',
-                headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ traderId }),
+        if (payoutsPager.next) {
+            payoutsPager.next.addEventListener('click', () => {
+                if (payoutsPagination.page < payoutsPagination.totalPages) {
+                    payoutsPagination.page += 1;
+                    loadPayouts();
+                }
             });
-            setStatus('success', 'Выплата успешно распределена.');
-            await Promise.all([loadData(false), loadDeals(false)]);
-        } catch (error) {
-            console.error('Ошибка привязки выплаты:', error);
-            setStatus('error', 'Не удалось привязать выплату: ' + error.message);
         }
     }
 
-    async function saveTraderLimit(traderId) {
-        if (!traderId) {
+    function renderPayouts(response) {
+        currentPayouts = Array.isArray(response?.items) ? response.items : [];
+        payoutsPagination = readPagination(response, payoutsPagination);
+        updatePager(payoutsPager, payoutsPagination);
+        const tbody = document.querySelector('#payouts-table tbody');
+        if (!tbody) {
             return;
         }
-        const input = document.getElementById(`limit-input-${traderId}`);
-        if (!input) {
+        if (!currentPayouts.length && payoutsPagination.page > payoutsPagination.totalPages && payoutsPagination.totalPages > 0) {
+            payoutsPagination.page = payoutsPagination.totalPages;
+            loadPayouts();
             return;
         }
-        const raw = input.value.trim();
-        const maxAmount = raw === '' ? null : Number(raw);
-
-        if (maxAmount !== null && (Number.isNaN(maxAmount) || maxAmount < 0)) {
-            setStatus('warning', 'Укажите неотрицательное число или оставьте поле пустым.');
+        if (!currentPayouts.length) {
+            renderEmpty(tbody, 5, t('payouts.empty'));
             return;
         }
 
-        try {
-            await fetchJson(`/api/traders/${traderId}/limit`, {
-                method: 'POST',
-                headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ maxAmount }),
-            });
-            setStatus('success', 'Лимит трейдера обновлен.');
-            await Promise.all([loadData(false), loadDeals(false)]);
-        } catch (error) {
-            console.error('Ошибка сохранения лимита:', error);
-            setStatus('error', 'Не удалось сохранить лимит: ' + error.message);
-        }
-    }
+        const optionsHtml = traderOptions.map(trader => html`
+            <option value="${trader.id}">
+                ${trader.email} (ID: ${trader.numericId})
+            </option>
+        `);
 
-    async function saveSettings() {
-        const checkbox = document.getElementById('auto-enabled');
-        const intervalInput = document.getElementById('auto-interval');
-        const enabled = !!checkbox?.checked;
-        const intervalSeconds = Number(intervalInput?.value) || 1;
+        setHtml(tbody, currentPayouts.map(payout => {
+            const amount = formatMoney(payout.amount, payout.currency);
+            const bank = payout.bank ?? '-';
+            const external = payout.externalReference ?? '-';
+            const priorityBadge = payout.priority
+                ? html`<span class="badge priority-badge">${t('payouts.priority')}</span>`
+                : '';
+            const duplicateBadge = payout.duplicate
+                ? html`<button class="badge duplicate-badge approve-duplicate" data-payout-id="${payout.id}" type="button" title="${t('payouts.duplicate-hint')}">${t('payouts.duplicate')}</button>`
+                : '';
+            return html`
+                <tr>
+                    <td>${payout.numericId}${priorityBadge}${duplicateBadge}</td>
+                    <td>${amount}</td>
+                    <td>${bank}</td>
+                    <td>${external}</td>
+                    <td>
+                        <div class="assign-controls">
+                            <select id="assign-select-${payout.id}">
+                                <option value="">${t('payouts.select-trader')}</option>
+                                ${optionsHtml}
+                            </select>
+                            <button class="assign-button" data-payout-id="${payout.id}">${t('payouts.assign')}</button>
+                        </div>
+                    </td>
+                </tr>
+            `;
+        }));
 
-        try {
-            const result = await fetchJson('/api/settings/auto-distribution', {
-                method: 'POST',
-                headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ enabled, intervalSeconds }),
+        tbody.querySelectorAll('.assign-button').forEach(button => {
+            button.addEventListener('click', async (event) => {
+                const payoutId = event.currentTarget.getAttribute('data-payout-id');
+                await assignPayout(payoutId);
             });
-            renderSettings(result);
-            setStatus('success', 'Настройки сохранены.');
-            markUpdated();
-            await Promise.all([loadData(false), loadDeals(false)]);
-        } catch (error) {
-            console.error('Ошибка сохранения настроек:', error);
-            setStatus('error', 'Не удалось сохранить настройки: ' + error.message);
-        }
+        });
+        tbody.querySelectorAll('.approve-duplicate').forEach(button => {
+            button.addEventListener('click', async (event) => {
+                const payoutId = event.currentTarget.getAttribute('data-payout-id');
+                await approveDuplicate(payoutId);
+            });
+        });
     }
 
-    function scheduleReload() {
-        if (reloadScheduled) {
+    // Rows come from `/fragments/deals-table`, see `renderTraders`.
+    function renderDeals(fragment) {
+        const tbody = document.querySelector('#deals-table tbody');
+        if (!tbody) {
             return;
         }
-        reloadScheduled = true;
-        setTimeout(async () => {
+
+        if (fragment?.html !== undefined) {
+            setHtml(tbody, new SafeHtml(fragment.html));
+        }
+        localizeTimes(tbody);
+        startCountdowns();
+        currentDeals = Array.from(tbody.querySelectorAll('tr[data-deal-row]')).map(row => ({
+            id: row.dataset.dealRow,
+            status: row.dataset.status,
+            numericId: Number(row.dataset.numericId),
+            updatedAt: row.dataset.updatedAt,
+        }));
+
+        dealsPagination = readPagination(fragment, dealsFilters);
+        dealsFilters.page = dealsPagination.page;
+        dealsFilters.perPage = dealsPagination.perPage;
+
+        if (!currentDeals.length) {
+            pruneDealSelection();
+            updateDealSelectionControls();
+            updateDealsPagination();
+            syncDealsFiltersToControls();
+            return;
+        }
+
+        tbody.querySelectorAll('.deal-select:not(:disabled)').forEach(checkbox => {
+            checkbox.checked = selectedDeals.has(checkbox.dataset.dealId);
+        });
+
+        tbody.querySelectorAll('.cancel-deal').forEach(button => {
+            button.addEventListener('click', async (event) => {
+                const dealId = event.currentTarget.getAttribute('data-deal-id');
+                await cancelDeal(dealId);
+            });
+        });
+
+        tbody.querySelectorAll('.deal-files').forEach(button => {
+            button.addEventListener('click', async (event) => {
+                const dealId = event.currentTarget.getAttribute('data-deal-id');
+                await openFilesDialog(dealId);
+            });
+        });
+
+        tbody.querySelectorAll('.deal-timeline').forEach(button => {
+            button.addEventListener('click', async (event) => {
+                const dealId = event.currentTarget.getAttribute('data-deal-id');
+                await toggleTimeline(dealId);
+            });
+        });
+
+        bindDealSelection(tbody);
+        restoreTimelines();
+
+        updateDealsPagination();
+        syncDealsFiltersToControls();
+    }
+
+    function describeTimelineEvent(event) {
+        const parts = [];
+        if (event.source) {
+            parts.push(t(`timeline.source.${event.source}`));
+        }
+        if (event.actor) {
+            parts.push(t('timeline.actor', { actor: event.actor }));
+        }
+        if (event.traderId) {
+            parts.push(t('timeline.trader', { id: event.traderId }));
+        }
+        const details = event.details ?? {};
+        if (event.kind === 'callback') {
+            parts.push(details.statusCode ? `HTTP ${details.statusCode}` : t('timeline.callback-not-sent'));
+            if (details.error) {
+                parts.push(details.error);
+            }
+        }
+        if (details.reasonCode) {
+            parts.push(details.reasonCode);
+        }
+        if (details.reason) {
+            parts.push(details.reason);
+        }
+        if (event.kind === 'file-attached' && details.kind) {
+            parts.push(t(`files.${details.kind}`));
+        }
+        if (details.message) {
+            parts.push(details.message);
+        }
+        return parts.join(' · ');
+    }
+
+    function renderNoteForm(dealId) {
+        const form = document.createElement('form');
+        form.className = 'note-form';
+        const input = document.createElement('textarea');
+        input.rows = 2;
+        input.maxLength = 2000;
+        input.placeholder = t('notes.placeholder');
+        const button = document.createElement('button');
+        button.type = 'submit';
+        button.textContent = t('notes.add');
+        form.append(input, button);
+        form.addEventListener('submit', async (event) => {
+            event.preventDefault();
+            const body = input.value.trim();
+            if (!body) {
+                return;
+            }
+            button.disabled = true;
             try {
-                await Promise.all([loadData(false), loadDeals(false)]);
-            } finally {
-                reloadScheduled = false;
+                await fetchJson(`/api/payouts/${encodeURIComponent(dealId)}/notes`, {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({ body }),
+                });
+                await loadTimeline(dealId);
+            } catch (error) {
+                console.error('Ошибка сохранения заметки:', error);
+                setStatus('error', t('notes.save-failed', { error: error.message }));
+                button.disabled = false;
             }
-        }, 400);
+        });
+        return form;
     }
 
-    function initEventSource() {
+    function renderTimeline(cell, response) {
+        const events = Array.isArray(response?.events) ? response.events : [];
+        cell.replaceChildren();
+        if (!events.length) {
+            cell.textContent = t('timeline.empty');
+            return;
+        }
+        const list = document.createElement('ol');
+        list.className = 'timeline';
+        events.forEach(event => {
+            const item = document.createElement('li');
+            item.dataset.kind = event.kind;
+            const time = document.createElement('span');
+            time.className = 'timeline-time';
+            time.textContent = formatDateTime(event.at);
+            time.title = formatUtc(event.at);
+            const label = document.createElement('strong');
+            label.textContent = t(`timeline.kind.${event.kind}`);
+            const meta = document.createElement('span');
+            meta.className = 'timeline-meta';
+            meta.textContent = describeTimelineEvent(event);
+            item.append(time, label, meta);
+            list.append(item);
+        });
+        cell.append(list, renderNoteForm(response.payoutId));
+    }
+
+    async function loadTimeline(dealId) {
+        const row = document.querySelector(`#deals-table tr[data-deal-row="${CSS.escape(dealId)}"]`);
+        if (!row) {
+            return;
+        }
+        let timelineRow = row.nextElementSibling;
+        if (!timelineRow?.classList.contains('deal-timeline-row')) {
+            timelineRow = document.createElement('tr');
+            timelineRow.className = 'deal-timeline-row';
+            const cell = document.createElement('td');
+            cell.colSpan = dealsColumnCount();
+            cell.textContent = t('timeline.loading');
+            timelineRow.append(cell);
+            row.after(timelineRow);
+        }
+        const cell = timelineRow.firstElementChild;
         try {
-            const eventSource = new EventSource('/api/events');
-            eventSource.onmessage = (event) => {
-                try {
-                    const payload = JSON.parse(event.data);
-                    if (payload?.type) {
-                        setStatus('info', 'Получено обновление: ' + payload.type);
-                    } else {
-                        setStatus('info', 'Получено обновление данных.');
-                    }
-                } catch (parseError) {
-                    console.debug('Не удалось разобрать событие SSE:', parseError);
-                    setStatus('info', 'Получено обновление данных.');
-                }
-                scheduleReload();
-            };
-            eventSource.onerror = () => {
-                setStatus('warning', 'SSE соединение потеряно. Переподключение...');
-                eventSource.close();
-                setTimeout(initEventSource, 5000);
-            };
+            renderTimeline(cell, await fetchJson(`/api/deals/${encodeURIComponent(dealId)}/timeline`));
         } catch (error) {
-            console.error('Не удалось открыть SSE соединение:', error);
+            console.error('Ошибка загрузки истории выплаты:', error);
+            cell.textContent = t('timeline.load-failed', { error: error.message });
         }
     }
 
-    const initialData = globalThis.__INITIAL_DASHBOARD__;
-    if (initialData) {
+    async function toggleTimeline(dealId) {
+        if (!dealId) {
+            return;
+        }
+        if (expandedTimelines.has(dealId)) {
+            expandedTimelines.delete(dealId);
+            const row = document.querySelector(`#deals-table tr[data-deal-row="${CSS.escape(dealId)}"]`);
+            if (row?.nextElementSibling?.classList.contains('deal-timeline-row')) {
+                row.nextElementSibling.remove();
+            }
+            return;
+        }
+        expandedTimelines.add(dealId);
+        await loadTimeline(dealId);
+    }
+
+    async function jumpToPayout(numericId) {
         try {
-            currentTraders = Array.isArray(initialData.traders) ? initialData.traders : [];
-            currentPayouts = Array.isArray(initialData.payouts) ? initialData.payouts : [];
-            if (initialData.deals?.pagination) {
-                dealsFilters.perPage = Number(initialData.deals.pagination.perPage ?? dealsFilters.perPage);
-                dealsFilters.page = Number(initialData.deals.pagination.page ?? dealsFilters.page);
+            const payout = await fetchJson(`/api/lookup?numericId=${encodeURIComponent(numericId)}`);
+            dealsFilters = {
+                ...dealsFilters,
+                search: payout.id,
+                wallet: '',
+                amount: '',
+                status: '',
+                includeArchived: Boolean(payout.archived),
+                page: 1,
+            };
+            syncDealsFiltersToControls();
+            await loadDeals(false);
+            if (!expandedTimelines.has(payout.id)) {
+                await toggleTimeline(payout.id);
             }
-            renderTraders(currentTraders);
-            renderPayouts(currentPayouts);
-            if (initialData.deals) {
-                renderDeals(initialData.deals);
+            document
+                .querySelector(`#deals-table tr[data-deal-row="${CSS.escape(payout.id)}"]`)
+                ?.scrollIntoView({ block: 'center', behavior: 'smooth' });
+            setStatus('success', t('status.payout-found', { id: payout.numericId, status: payout.status }));
+        } catch (error) {
+            console.error('Ошибка поиска выплаты:', error);
+            setStatus('error', t('status.payout-lookup-failed', { id: numericId, error: error.message }));
+        }
+    }
+
+    function restoreTimelines() {
+        const visible = new Set(currentDeals.map(deal => deal.id));
+        Array.from(expandedTimelines).forEach(dealId => {
+            if (visible.has(dealId)) {
+                loadTimeline(dealId);
             } else {
-                const dealsBody = document.querySelector('#deals-table tbody');
-                renderEmpty(dealsBody, 9, 'Нет данных о выплатах');
+                expandedTimelines.delete(dealId);
             }
-            renderSettings(initialData.settings);
-            updateMetrics(currentTraders, currentPayouts);
-            syncDealsFiltersToControls();
-            markUpdated();
-            setStatus('info', 'Показаны данные на момент загрузки.');
+        });
+    }
+
+    function isDealCancellable(deal) {
+        return !uncancellableStatuses.has(deal?.status ?? '');
+    }
+
+    function pruneDealSelection() {
+        const visible = new Set(currentDeals.filter(isDealCancellable).map(deal => deal.id));
+        Array.from(selectedDeals).forEach(id => {
+            if (!visible.has(id)) {
+                selectedDeals.delete(id);
+            }
+        });
+    }
+
+    function updateDealSelectionControls() {
+        if (dealsControls.bulkCancel) {
+            dealsControls.bulkCancel.textContent = t('deals.bulk-cancel', { count: selectedDeals.size });
+            dealsControls.bulkCancel.disabled = selectedDeals.size === 0;
+        }
+        if (dealsControls.selectAll) {
+            const selectable = currentDeals.filter(isDealCancellable);
+            dealsControls.selectAll.disabled = selectable.length === 0;
+            dealsControls.selectAll.checked =
+                selectable.length > 0 && selectable.every(deal => selectedDeals.has(deal.id));
+        }
+    }
+
+    function bindDealSelection(tbody) {
+        pruneDealSelection();
+        tbody.querySelectorAll('.deal-select').forEach(checkbox => {
+            checkbox.addEventListener('change', (event) => {
+                const dealId = event.currentTarget.getAttribute('data-deal-id');
+                if (event.currentTarget.checked) {
+                    selectedDeals.add(dealId);
+                } else {
+                    selectedDeals.delete(dealId);
+                }
+                updateDealSelectionControls();
+            });
+        });
+        updateDealSelectionControls();
+    }
+
+    async function bulkCancelDeals() {
+        const payoutIds = Array.from(selectedDeals);
+        if (!payoutIds.length) {
+            return;
+        }
+        const reasonPayload = await openCancelDialog(t('status.bulk-cancel-confirm', { count: payoutIds.length }));
+        if (!reasonPayload) {
+            return;
+        }
+        const payload = { payoutIds, ...reasonPayload };
+        try {
+            const result = await fetchJson('/api/payouts/bulk-cancel', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify(payload),
+            });
+            const results = Array.isArray(result?.results) ? result.results : [];
+            const undelivered = results.filter(item => item.success && !item.callbackDispatched && !item.callbackQueued).length;
+            const params = {
+                cancelled: result?.cancelled ?? 0,
+                failed: result?.failed ?? 0,
+                callbacks: undelivered,
+            };
+            results.filter(item => !item.success).forEach(item => {
+                console.warn('Выплата не отменена:', item.payoutId, item.error);
+            });
+            if (undelivered > 0 || params.failed > 0) {
+                setStatus('warning', t(undelivered > 0 ? 'status.bulk-cancel-callback-failed' : 'status.bulk-cancel-done', params));
+            } else {
+                setStatus('success', t('status.bulk-cancel-done', params));
+            }
+            selectedDeals.clear();
+            await loadDeals(false);
+            await loadData(false);
         } catch (error) {
-            console.error('Ошибка применения начальных данных:', error);
+            console.error('Ошибка массовой отмены выплат:', error);
+            setStatus('error', t('status.cancel-failed', { error: error.message }));
         }
     }
 
-    async function bootstrap() {
-        const saveButton = document.getElementById('save-settings');
-        if (saveButton) {
-            saveButton.addEventListener('click', saveSettings);
+    function renderCancelReasonOptions() {
+        const select = document.getElementById('cancel-reason-code');
+        if (!select) {
+            return;
         }
-        initDealsControls();
-        if (!initialData) {
-            syncDealsFiltersToControls();
+        const previous = select.value;
+        const options = cancelReasons
+            .filter(reason => reason.active)
+            .map(reason => html`<option value="${reason.code}">${reason.code} — ${reason.label}</option>`);
+        setHtml(select, html`<option value="">${t('cancel-dialog.no-code')}</option>${options}`);
+        select.value = cancelReasons.some(reason => reason.active && reason.code === previous) ? previous : '';
+    }
+
+    // Merchants with their callback pause state (`/api/merchants`).
+    let currentMerchants = [];
+
+    function renderMerchants() {
+        const tbody = document.querySelector('#merchants-table tbody');
+        if (!tbody) {
+            return;
         }
-        initEventSource();
-        await Promise.all([loadData(!initialData), loadDeals(!initialData)]);
+        if (!currentMerchants.length) {
+            renderEmpty(tbody, 4, t('merchants.empty'));
+            return;
+        }
+        setHtml(tbody, currentMerchants.map(merchant => {
+            const pause = merchant.callbacksPaused;
+            const state = pause
+                ? html`<span class="badge paused-badge" title="${pause.reason ?? ''}">${t('merchants.paused', { since: formatDateTime(pause.pausedAt) })}</span>`
+                : t('merchants.active');
+            return html`
+                <tr>
+                    <td>${merchant.name ?? merchant.id} <span class="mono">${merchant.id}</span></td>
+                    <td>${state}</td>
+                    <td>${merchant.queuedCallbacks}</td>
+                    <td>
+                        <button type="button" class="merchant-callbacks-toggle" data-merchant-id="${merchant.id}">
+                            ${pause ? t('merchants.resume') : t('merchants.pause')}
+                        </button>
+                    </td>
+                </tr>
+            `;
+        }));
     }
 
-    function start() {
-        bootstrap().catch(error => {
-            console.error('Не удалось инициализировать страницу:', error);
-            setStatus('error', 'Не удалось инициализировать страницу: ' + error.message);
+    async function loadMerchants() {
+        try {
+            const merchants = await fetchJson('/api/merchants');
+            currentMerchants = Array.isArray(merchants) ? merchants : [];
+            renderMerchants();
+        } catch (error) {
+            console.error('Ошибка загрузки мерчантов:', error);
+            renderEmpty(document.querySelector('#merchants-table tbody'), 4, t('merchants.load-error'));
+        }
+    }
+
+    async function toggleMerchantCallbacks(merchantId) {
+        const merchant = currentMerchants.find(item => item.id === merchantId);
+        if (!merchant) {
+            return;
+        }
+        const name = merchant.name ?? merchant.id;
+        const resume = Boolean(merchant.callbacksPaused);
+        let body = {};
+        if (!resume) {
+            const reason = window.prompt(t('merchants.pause-prompt', { merchant: name }), '');
+            if (reason === null) {
+                return;
+            }
+            body = { reason };
+        }
+        try {
+            const updated = await fetchJson(`/api/merchants/${encodeURIComponent(merchantId)}/callbacks/${resume ? 'resume' : 'pause'}`, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify(body),
+            });
+            currentMerchants = currentMerchants.map(item => (item.id === merchantId ? updated : item));
+            renderMerchants();
+            if (resume) {
+                setStatus('success', t('status.merchant-resumed', { merchant: name, count: updated.queuedCallbacks }));
+            } else {
+                setStatus('success', t('status.merchant-paused', { merchant: name }));
+            }
+        } catch (error) {
+            console.error('Ошибка переключения колбэков мерчанта:', error);
+            setStatus('error', t('status.merchant-toggle-failed', { error: error.message }));
+        }
+    }
+
+    function initMerchantsControls() {
+        document.querySelector('#merchants-table tbody')?.addEventListener('click', (event) => {
+            const button = event.target.closest('.merchant-callbacks-toggle');
+            if (button) {
+                toggleMerchantCallbacks(button.dataset.merchantId);
+            }
         });
     }
 
-    if (document.readyState === 'loading') {
-        document.addEventListener('DOMContentLoaded', start, { once: true });
-    } else {
-        start();
+    // Settings changes waiting for a second admin (`/api/settings/changes`).
+    let pendingSettingsChanges = [];
+
+    function renderSettingsChanges() {
+        const block = document.getElementById('settings-changes-block');
+        const list = document.getElementById('settings-changes');
+        if (!block || !list) {
+            return;
+        }
+        block.hidden = !pendingSettingsChanges.length;
+        setHtml(list, pendingSettingsChanges.map(change => html`
+            <li>
+                <span>${t('settings-changes.item', {
+                    section: change.section,
+                    requestedBy: change.requestedBy,
+                    since: formatDateTime(change.createdAt),
+                })}</span>
+                <span class="panel-subtitle">${change.risks.join(', ')}</span>
+                <button type="button" data-change-id="${change.id}" data-decision="approve">${t('settings-changes.approve')}</button>
+                <button type="button" data-change-id="${change.id}" data-decision="reject">${t('settings-changes.reject')}</button>
+            </li>
+        `));
     }
-})();
-"#;
 
-#[component]
-fn App(snapshot: DashboardSnapshot) -> impl IntoView {
-    let initial_json = serde_json::to_string(&snapshot).unwrap_or_else(|_| "{}".to_string());
-    let traders = snapshot.traders.clone();
-    let payouts = snapshot.payouts.clone();
-    let settings = snapshot.settings.clone();
+    async function loadSettingsChanges() {
+        try {
+            const changes = await fetchJson('/api/settings/changes');
+            pendingSettingsChanges = Array.isArray(changes) ? changes : [];
+        } catch (error) {
+            // Tenants limited to some merchants do not manage settings.
+            pendingSettingsChanges = [];
+        }
+        renderSettingsChanges();
+    }
 
-    let metrics_traders = traders.len();
-    let deals = snapshot.deals.clone();
-    let total_payout: f64 = payouts.iter().map(|p| p.amount.unwrap_or_default()).sum();
-    let metrics_payouts = payouts.len();
-    let total_payout_display = format_amount(Some(total_payout));
-    let traders_for_options = traders.clone();
-    let deals_items = deals.items.clone();
-    let deals_pagination = deals.pagination.clone();
-    let deals_page_info = if deals_pagination.total_pages == 0 {
-        "0 / 0 (всего 0)".to_string()
-    } else {
-        format!(
-            "{} / {} (всего {})",
-            deals_pagination.page,
-            deals_pagination.total_pages,
-            deals_pagination.total
-        )
-    };
-    let settings_description = if settings.enabled {
-        format!(
-            "Автораспределение выполняется каждые {} секунд.",
-            settings.interval_seconds.max(1)
-        )
-    } else {
-        "Автораспределение выключено.".to_string()
-    };
+    async function decideSettingsChange(changeId, decision) {
+        try {
+            await fetchJson(`/api/settings/changes/${encodeURIComponent(changeId)}/${decision}`, { method: 'POST' });
+            setStatus('success', t(decision === 'approve' ? 'status.settings-change-approved' : 'status.settings-change-rejected'));
+            await Promise.all([loadSettingsChanges(), loadData(false)]);
+        } catch (error) {
+            console.error('Ошибка решения по изменению настроек:', error);
+            setStatus('error', t('status.settings-change-failed', { error: error.message }));
+        }
+    }
 
-    let traders_view = if traders.is_empty() {
-        view! { <tr><td class="empty" colspan="6">Нет подходящих трейдеров</td></tr> }.into_view()
-    } else {
-        view! {
-            <For
-                each=move || traders.clone()
-                key=|trader| trader.id.clone()
-                children=move |trader| {
-                    let limit_value = trader
-                        .max_amount
-                        .map(|v| format!("{:.2}", v))
-                        .unwrap_or_default();
-                    view! {
-                        <tr>
-                            <td>{trader.numeric_id}</td>
-                            <td>{trader.email.clone()}</td>
-                            <td>{format_amount(trader.balance_rub)}</td>
-                            <td>{format_amount(trader.frozen_rub)}</td>
-                            <td>{format_amount(trader.payout_balance)}</td>
-                            <td>
-                                <div class="limit-controls">
-                                    <input
-                                        type="number"
-                                        min="0"
-                                        step="0.01"
-                                        value=limit_value
-                                        id={format!("limit-input-{}", trader.id)}
-                                        placeholder="Без лимита"
-                                    />
-                                    <button class="save-limit" data-trader-id={trader.id.clone()}>"Сохранить"</button>
-                                </div>
-                            </td>
-                        </tr>
-                    }
-                }
-            />
+    function initSettingsChangesControls() {
+        document.getElementById('settings-changes')?.addEventListener('click', (event) => {
+            const button = event.target.closest('button[data-change-id]');
+            if (button) {
+                decideSettingsChange(button.dataset.changeId, button.dataset.decision);
+            }
+        });
+    }
+
+    async function loadCancelReasons() {
+        try {
+            const reasons = await fetchJson('/api/cancel-reasons');
+            cancelReasons = Array.isArray(reasons) ? reasons : [];
+            renderCancelReasonOptions();
+        } catch (error) {
+            console.error('Ошибка загрузки причин отмены:', error);
         }
-        .into_view()
-    };
+    }
 
-    let payouts_view = if payouts.is_empty() {
-        view! { <tr><td class="empty" colspan="5">Нет нераспределенных выплат</td></tr> }
-            .into_view()
-    } else {
-        view! {
-            <For
-                each=move || payouts.clone()
+    function openCancelDialog(title) {
+        const dialog = document.getElementById('cancel-dialog');
+        const titleNode = document.getElementById('cancel-dialog-title');
+        const select = document.getElementById('cancel-reason-code');
+        const comment = document.getElementById('cancel-reason-comment');
+        if (!dialog || typeof dialog.showModal !== 'function') {
+            return Promise.resolve(window.confirm(title) ? {} : null);
+        }
+        if (titleNode) {
+            titleNode.textContent = title;
+        }
+        if (select) {
+            select.value = '';
+        }
+        if (comment) {
+            comment.value = '';
+        }
+        return new Promise(resolve => {
+            dialog.addEventListener('close', () => {
+                if (dialog.returnValue !== 'confirm') {
+                    resolve(null);
+                    return;
+                }
+                const payload = {};
+                const reasonCode = select?.value ?? '';
+                const reason = comment?.value.trim() ?? '';
+                if (reasonCode) {
+                    payload.reasonCode = reasonCode;
+                }
+                if (reason) {
+                    payload.reason = reason;
+                }
+                resolve(payload);
+            }, { once: true });
+            dialog.returnValue = '';
+            dialog.showModal();
+        });
+    }
+
+    function renderFileGroup(title, files) {
+        const items = Array.isArray(files) && files.length
+            ? files.map(file => {
+                const url = file.url ? safeUrl(file.url) : null;
+                return url
+                    ? html`<a href="${url}" target="_blank" rel="noopener">${file.key}</a>`
+                    : html`<span class="mono">${file.key}</span> <span class="deal-reason">(${t('files.no-link')})</span>`;
+            })
+            : html`<span class="deal-reason">${t('files.empty')}</span>`;
+        return html`<h4>${title}</h4>${items}`;
+    }
+
+    function renderFiles(response) {
+        const body = document.getElementById('files-dialog-body');
+        const upload = document.getElementById('files-upload');
+        if (body) {
+            const note = response?.storageConfigured
+                ? ''
+                : html`<span class="deal-reason">${t('files.storage-disabled')}</span>`;
+            setHtml(body, [
+                renderFileGroup(t('files.proof'), response?.proofFiles),
+                renderFileGroup(t('files.dispute'), response?.disputeFiles),
+                note,
+            ]);
+        }
+        if (upload) {
+            upload.disabled = !response?.storageConfigured;
+        }
+    }
+
+    async function openFilesDialog(dealId) {
+        const dialog = document.getElementById('files-dialog');
+        if (!dealId || !dialog) {
+            return;
+        }
+        const deal = currentDeals.find(item => item.id === dealId);
+        const title = document.getElementById('files-dialog-title');
+        if (title) {
+            title.textContent = t('files.title', { id: deal?.numericId ?? dealId });
+        }
+        const input = document.getElementById('files-input');
+        if (input) {
+            input.value = '';
+        }
+        dialog.dataset.dealId = dealId;
+        try {
+            renderFiles(await fetchJson(`/api/payouts/${dealId}/files`));
+            if (!dialog.open) {
+                dialog.showModal();
+            }
+        } catch (error) {
+            console.error('Ошибка загрузки файлов выплаты:', error);
+            setStatus('error', t('status.files-load-failed', { error: error.message }));
+        }
+    }
+
+    async function uploadPayoutFile() {
+        const dialog = document.getElementById('files-dialog');
+        const input = document.getElementById('files-input');
+        const kind = document.getElementById('files-kind')?.value ?? 'proof';
+        const dealId = dialog?.dataset.dealId;
+        const file = input?.files?.[0];
+        if (!dealId) {
+            return;
+        }
+        if (!file) {
+            setStatus('warning', t('status.file-missing'));
+            return;
+        }
+        const params = new URLSearchParams({ kind, filename: file.name });
+        try {
+            const response = await fetchJson(`/api/payouts/${dealId}/files?${params.toString()}`, {
+                method: 'POST',
+                headers: { 'Content-Type': file.type || 'application/octet-stream' },
+                body: file,
+            });
+            renderFiles(response);
+            input.value = '';
+            setStatus('success', t('status.file-uploaded'));
+        } catch (error) {
+            console.error('Ошибка загрузки файла:', error);
+            setStatus('error', t('status.file-upload-failed', { error: error.message }));
+        }
+    }
+
+    function updateDealsPagination() {
+        updatePager(dealsControls, dealsPagination);
+    }
+
+    function syncDealsFiltersToControls() {
+        if (dealsControls.search) {
+            dealsControls.search.value = dealsFilters.search ?? '';
+        }
+        if (dealsControls.wallet) {
+            dealsControls.wallet.value = dealsFilters.wallet ?? '';
+        }
+        if (dealsControls.amount) {
+            dealsControls.amount.value = dealsFilters.amount ?? '';
+        }
+        if (dealsControls.status) {
+            dealsControls.status.value = dealsFilters.status ?? '';
+        }
+        if (dealsControls.perPage) {
+            dealsControls.perPage.value = String(dealsFilters.perPage ?? 25);
+        }
+        if (dealsControls.includeArchived) {
+            dealsControls.includeArchived.checked = Boolean(dealsFilters.includeArchived);
+        }
+        syncDealsSortIndicator();
+    }
+
+    function syncDealsSortIndicator() {
+        if (!dealsControls.sortStatus) {
+            return;
+        }
+        if (dealsFilters.sort === 'status') {
+            dealsControls.sortStatus.classList.add('active');
+            dealsControls.sortStatus.setAttribute('data-order', dealsFilters.order);
+            dealsControls.sortStatus.textContent =
+                dealsFilters.order === 'asc' ? t('deals.sort-status-asc') : t('deals.sort-status-desc');
+        } else {
+            dealsControls.sortStatus.classList.remove('active');
+            dealsControls.sortStatus.removeAttribute('data-order');
+            dealsControls.sortStatus.textContent = t('deals.sort-status');
+        }
+    }
+
+    function dealsFiltersFromParams(params) {
+        const filters = {};
+        ['search', 'wallet', 'amount', 'status'].forEach(key => {
+            if (params.has(key)) {
+                filters[key] = params.get(key).trim();
+            }
+        });
+        if (params.get('includeArchived') === 'true') {
+            filters.includeArchived = true;
+        }
+        if (['createdAt', 'status'].includes(params.get('sort'))) {
+            filters.sort = params.get('sort');
+        }
+        if (['asc', 'desc'].includes(params.get('order'))) {
+            filters.order = params.get('order');
+        }
+        const page = Number(params.get('page'));
+        if (Number.isInteger(page) && page > 1) {
+            filters.page = page;
+        }
+        const perPage = Number(params.get('perPage'));
+        if (Number.isInteger(perPage) && perPage > 0) {
+            filters.perPage = perPage;
+        }
+        return filters;
+    }
+
+    // Defaults are left out unless `complete`, which keeps shared links short.
+    function dealsFiltersToParams(complete) {
+        const params = new URLSearchParams();
+        if (dealsFilters.search) {
+            params.set('search', dealsFilters.search);
+        }
+        if (dealsFilters.wallet) {
+            params.set('wallet', dealsFilters.wallet);
+        }
+        if (dealsFilters.amount) {
+            const num = Number(dealsFilters.amount);
+            if (!Number.isNaN(num)) {
+                params.set('amount', String(num));
+            }
+        }
+        if (dealsFilters.status) {
+            params.set('status', dealsFilters.status);
+        }
+        if (dealsFilters.includeArchived) {
+            params.set('includeArchived', 'true');
+        }
+        const page = dealsFilters.page ?? 1;
+        const perPage = dealsFilters.perPage ?? 25;
+        const sort = dealsFilters.sort ?? 'createdAt';
+        const order = dealsFilters.order ?? 'desc';
+        if (complete || page !== 1) {
+            params.set('page', String(page));
+        }
+        if (complete || perPage !== 25) {
+            params.set('perPage', String(perPage));
+        }
+        if (complete || sort !== 'createdAt' || order !== 'desc') {
+            params.set('sort', sort);
+            params.set('order', order);
+        }
+        return params;
+    }
+
+    function syncDealsUrl() {
+        const query = dealsFiltersToParams(false).toString();
+        const url = `${location.pathname}${query ? `?${query}` : ''}${location.hash}`;
+        if (url !== `${location.pathname}${location.search}${location.hash}`) {
+            history.replaceState(history.state, '', url);
+        }
+    }
+
+    function presetFilters() {
+        const { page, ...filters } = dealsFilters;
+        const amount = Number(filters.amount);
+        return {
+            ...filters,
+            amount: filters.amount === '' || Number.isNaN(amount) ? null : amount,
+        };
+    }
+
+    function renderFilterPresets(selectedId) {
+        const select = document.getElementById('deals-preset');
+        if (!select) {
+            return;
+        }
+        setHtml(select, [
+            html`<option value="">${t('presets.choose')}</option>`,
+            filterPresets.map(preset => html`<option value="${preset.id}">${preset.name}</option>`),
+        ]);
+        select.value = filterPresets.some(preset => preset.id === selectedId) ? selectedId : '';
+        const deleteButton = document.getElementById('deals-preset-delete');
+        if (deleteButton) {
+            deleteButton.disabled = !select.value;
+        }
+    }
+
+    async function loadFilterPresets(selectedId) {
+        try {
+            const presets = await fetchJson('/api/filters');
+            filterPresets = Array.isArray(presets) ? presets : [];
+            renderFilterPresets(selectedId);
+        } catch (error) {
+            console.error('Ошибка загрузки пресетов фильтров:', error);
+        }
+    }
+
+    function applyFilterPreset(presetId) {
+        const preset = filterPresets.find(item => item.id === presetId);
+        const deleteButton = document.getElementById('deals-preset-delete');
+        if (deleteButton) {
+            deleteButton.disabled = !preset;
+        }
+        if (!preset) {
+            return;
+        }
+        const filters = preset.filters ?? {};
+        dealsFilters = {
+            ...DEFAULT_DEALS_FILTERS,
+            search: filters.search ?? '',
+            wallet: filters.wallet ?? '',
+            amount: filters.amount === null || filters.amount === undefined ? '' : String(filters.amount),
+            status: filters.status ?? '',
+            includeArchived: Boolean(filters.includeArchived),
+            sort: filters.sort ?? DEFAULT_DEALS_FILTERS.sort,
+            order: filters.order ?? DEFAULT_DEALS_FILTERS.order,
+            perPage: filters.perPage ?? DEFAULT_DEALS_FILTERS.perPage,
+        };
+        syncDealsFiltersToControls();
+        loadDeals(true);
+    }
+
+    async function saveFilterPreset() {
+        const select = document.getElementById('deals-preset');
+        const current = filterPresets.find(item => item.id === select?.value);
+        const name = window.prompt(t('presets.name-prompt'), current?.name ?? '')?.trim();
+        if (!name) {
+            return;
+        }
+        try {
+            const preset = await fetchJson('/api/filters', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ name, filters: presetFilters() }),
+            });
+            await loadFilterPresets(preset?.id);
+            setStatus('success', t('status.preset-saved', { name }));
+        } catch (error) {
+            console.error('Ошибка сохранения пресета:', error);
+            setStatus('error', t('status.preset-save-failed', { error: error.message }));
+        }
+    }
+
+    async function deleteFilterPreset() {
+        const select = document.getElementById('deals-preset');
+        const preset = filterPresets.find(item => item.id === select?.value);
+        if (!preset || !window.confirm(t('presets.delete-confirm', { name: preset.name }))) {
+            return;
+        }
+        try {
+            await fetchJson(`/api/filters/${encodeURIComponent(preset.id)}`, { method: 'DELETE' });
+            await loadFilterPresets();
+            setStatus('success', t('status.preset-deleted', { name: preset.name }));
+        } catch (error) {
+            console.error('Ошибка удаления пресета:', error);
+            setStatus('error', t('status.preset-delete-failed', { error: error.message }));
+        }
+    }
+
+    async function copyDealsLink() {
+        syncDealsUrl();
+        try {
+            await navigator.clipboard.writeText(location.href);
+            setStatus('success', t('status.link-copied'));
+        } catch (error) {
+            window.prompt(t('presets.copy-link'), location.href);
+        }
+    }
+
+    // Acceptance countdowns start from the seconds left when the rows were
+    // rendered and tick locally, so client clock skew does not matter.
+    const COUNTDOWN_URGENT_SECONDS = 300;
+
+    function formatCountdown(seconds) {
+        if (seconds <= 0) {
+            return t('deals.acceptance-overdue');
+        }
+        return `${Math.floor(seconds / 60)}:${String(seconds % 60).padStart(2, '0')}`;
+    }
+
+    function startCountdowns() {
+        const renderedAt = Date.now();
+        document.querySelectorAll('#deals-table .countdown-badge[data-remaining]').forEach(badge => {
+            badge.dataset.deadline = String(renderedAt + Number(badge.dataset.remaining) * 1000);
+        });
+    }
+
+    function tickCountdowns() {
+        const now = Date.now();
+        document.querySelectorAll('#deals-table .countdown-badge[data-deadline]').forEach(badge => {
+            const seconds = Math.ceil((Number(badge.dataset.deadline) - now) / 1000);
+            badge.textContent = formatCountdown(seconds);
+            badge.dataset.state = seconds <= 0
+                ? 'overdue'
+                : seconds < COUNTDOWN_URGENT_SECONDS ? 'urgent' : 'pending';
+        });
+    }
+
+    function dealsColumnCount() {
+        return document.querySelectorAll('#deals-table thead th').length || 10;
+    }
+
+    function openColumnsDialog() {
+        const dialog = document.getElementById('columns-dialog');
+        if (dialog && typeof dialog.showModal === 'function' && !dialog.open) {
+            dialog.showModal();
+        }
+    }
+
+    function collectColumnLayout() {
+        return Array.from(document.querySelectorAll('#columns-list .column-item')).map(item => {
+            const width = Number(item.querySelector('.column-width')?.value);
+            return {
+                column: item.dataset.column,
+                visible: Boolean(item.querySelector('.column-visible')?.checked),
+                width: Number.isFinite(width) && width > 0 ? Math.round(width) : null,
+            };
+        });
+    }
+
+    // The header and rows are rendered by the server with the stored layout,
+    // so a reload shows the new table while the URL keeps the filters.
+    async function saveColumnLayout(reset) {
+        const layout = collectColumnLayout();
+        if (!reset && !layout.some(column => column.visible)) {
+            setStatus('warning', t('status.columns-none-visible'));
+            return;
+        }
+        try {
+            if (reset) {
+                await fetchJson('/api/preferences', { method: 'DELETE' });
+            } else {
+                await fetchJson('/api/preferences', {
+                    method: 'PUT',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({ dealsTable: layout }),
+                });
+            }
+            syncDealsUrl();
+            location.reload();
+        } catch (error) {
+            console.error('Ошибка сохранения колонок:', error);
+            setStatus('error', t('status.columns-save-failed', { error: error.message }));
+        }
+    }
+
+    function initColumnsDialog() {
+        document.getElementById('deals-columns')?.addEventListener('click', openColumnsDialog);
+        document.getElementById('columns-list')?.addEventListener('click', (event) => {
+            const button = event.target.closest('.column-up, .column-down');
+            const item = button?.closest('.column-item');
+            if (!item) {
+                return;
+            }
+            if (button.classList.contains('column-up') && item.previousElementSibling) {
+                item.previousElementSibling.before(item);
+            } else if (button.classList.contains('column-down') && item.nextElementSibling) {
+                item.nextElementSibling.after(item);
+            }
+        });
+        document.getElementById('columns-save')?.addEventListener('click', () => saveColumnLayout(false));
+        document.getElementById('columns-reset')?.addEventListener('click', () => saveColumnLayout(true));
+    }
+
+    function scheduleDealsReload() {
+        if (dealsFilterTimer) {
+            clearTimeout(dealsFilterTimer);
+        }
+        dealsFilterTimer = setTimeout(() => {
+            dealsFilters.page = 1;
+            loadDeals(true);
+        }, 350);
+    }
+
+    async function loadDeals(showStatus = false) {
+        if (isDealsLoading) {
+            return;
+        }
+        isDealsLoading = true;
+        try {
+            if (showStatus) {
+                setStatus('info', t('status.deals-loading'));
+            }
+            const query = dealsFiltersToParams(true).toString();
+            const response = await fetchFragment(`/fragments/deals-table?${query}`);
+            renderDeals(response);
+            syncDealsUrl();
+            if (showStatus) {
+                setStatus('success', t('status.deals-loaded'));
+            }
+        } catch (error) {
+            console.error('Ошибка загрузки выплат:', error);
+            const tbody = document.querySelector('#deals-table tbody');
+            renderEmpty(tbody, dealsColumnCount(), t('deals.load-error'));
+            if (showStatus) {
+                setStatus('error', t('status.deals-load-failed', { error: error.message }));
+            }
+        } finally {
+            isDealsLoading = false;
+        }
+    }
+
+    async function cancelDeal(dealId) {
+        if (!dealId) {
+            return;
+        }
+        const deal = currentDeals.find(item => item.id === dealId);
+        if (deal && !isDealCancellable(deal)) {
+            setStatus('warning', t('status.cancel-not-allowed'));
+            return;
+        }
+        const payload = await openCancelDialog(t('status.cancel-confirm'));
+        if (!payload) {
+            return;
+        }
+        try {
+            const result = await fetchJson(`/api/payouts/${dealId}/cancel`, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ ...payload, expectedUpdatedAt: deal?.updatedAt }),
+            });
+            if (result?.callbackDispatched) {
+                setStatus('success', t('status.cancelled'));
+            } else if (result?.callbackError) {
+                setStatus('warning', t('status.cancelled-callback-failed', { error: result.callbackError }));
+            } else {
+                setStatus('success', t('status.cancelled'));
+            }
+            await loadDeals(false);
+            await loadData(false);
+        } catch (error) {
+            if (error.code === 'PAYOUT_ALREADY_CANCELLED') {
+                setStatus('info', t('status.already-cancelled'));
+                await loadDeals(false);
+                await loadData(false);
+                return;
+            }
+            if (error.code === 'PAYOUT_MODIFIED') {
+                setStatus('warning', t('status.payout-modified'));
+                await loadDeals(false);
+                await loadData(false);
+                return;
+            }
+            if (error.code === 'CANCEL_APPROVAL_REQUIRED') {
+                await requestCancelApproval(dealId, { ...payload, expectedUpdatedAt: deal?.updatedAt });
+                return;
+            }
+            console.error('Ошибка отмены выплаты:', error);
+            setStatus('error', t('status.cancel-failed', { error: error.message }));
+        }
+    }
+
+    // Large payouts are cancelled by a second operator: the first files a
+    // request, whoever cancels it next is offered to approve it.
+    async function requestCancelApproval(dealId, payload) {
+        try {
+            await fetchJson(`/api/payouts/${dealId}/cancel/request`, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify(payload),
+            });
+            setStatus('info', t('status.cancel-approval-requested'));
+        } catch (error) {
+            if (error.code === 'CANCEL_APPROVAL_PENDING') {
+                if (window.confirm(t('status.cancel-approval-confirm', { message: error.message }))) {
+                    await approveCancelRequest(dealId);
+                }
+                return;
+            }
+            console.error('Ошибка запроса на отмену выплаты:', error);
+            setStatus('error', t('status.cancel-failed', { error: error.message }));
+        }
+    }
+
+    async function approveCancelRequest(dealId) {
+        try {
+            const result = await fetchJson(`/api/payouts/${dealId}/cancel/approve`, { method: 'POST' });
+            if (result?.callbackError) {
+                setStatus('warning', t('status.cancelled-callback-failed', { error: result.callbackError }));
+            } else {
+                setStatus('success', t('status.cancelled'));
+            }
+            await loadDeals(false);
+            await loadData(false);
+        } catch (error) {
+            console.error('Ошибка подтверждения отмены выплаты:', error);
+            setStatus('error', t('status.cancel-failed', { error: error.message }));
+        }
+    }
+
+    function initDealsControls() {
+        document.getElementById('deals-preset')?.addEventListener('change', (event) => {
+            applyFilterPreset(event.target.value);
+        });
+        document.getElementById('deals-preset-save')?.addEventListener('click', saveFilterPreset);
+        document.getElementById('deals-preset-delete')?.addEventListener('click', deleteFilterPreset);
+        document.getElementById('deals-copy-link')?.addEventListener('click', copyDealsLink);
+        const uploadButton = document.getElementById('files-upload');
+        if (uploadButton) {
+            uploadButton.addEventListener('click', uploadPayoutFile);
+        }
+        if (dealsControls.bulkCancel) {
+            const locks = globalThis.__INITIAL_DASHBOARD__?.environment?.locks ?? [];
+            dealsControls.bulkCancel.hidden = locks.includes('bulk-cancel');
+            dealsControls.bulkCancel.addEventListener('click', bulkCancelDeals);
+        }
+        if (dealsControls.selectAll) {
+            dealsControls.selectAll.addEventListener('change', (event) => {
+                const checked = event.currentTarget.checked;
+                currentDeals.filter(isDealCancellable).forEach(deal => {
+                    if (checked) {
+                        selectedDeals.add(deal.id);
+                    } else {
+                        selectedDeals.delete(deal.id);
+                    }
+                });
+                document.querySelectorAll('#deals-table .deal-select:not(:disabled)').forEach(checkbox => {
+                    checkbox.checked = checked;
+                });
+                updateDealSelectionControls();
+            });
+        }
+        if (dealsControls.search) {
+            dealsControls.search.addEventListener('input', (event) => {
+                dealsFilters.search = event.target.value.trim();
+                scheduleDealsReload();
+            });
+        }
+        if (dealsControls.wallet) {
+            dealsControls.wallet.addEventListener('input', (event) => {
+                dealsFilters.wallet = event.target.value.trim();
+                scheduleDealsReload();
+            });
+        }
+        if (dealsControls.amount) {
+            dealsControls.amount.addEventListener('input', (event) => {
+                dealsFilters.amount = event.target.value.trim();
+                scheduleDealsReload();
+            });
+        }
+        if (dealsControls.status) {
+            dealsControls.status.addEventListener('change', (event) => {
+                dealsFilters.status = event.target.value.trim();
+                dealsFilters.page = 1;
+                loadDeals(true);
+            });
+        }
+        if (dealsControls.perPage) {
+            dealsControls.perPage.addEventListener('change', (event) => {
+                const value = Number(event.target.value);
+                dealsFilters.perPage = Number.isNaN(value) ? 25 : value;
+                dealsFilters.page = 1;
+                loadDeals(true);
+            });
+        }
+        if (dealsControls.includeArchived) {
+            dealsControls.includeArchived.addEventListener('change', (event) => {
+                dealsFilters.includeArchived = event.target.checked;
+                dealsFilters.page = 1;
+                loadDeals(true);
+            });
+        }
+        if (dealsControls.sortStatus) {
+            dealsControls.sortStatus.addEventListener('click', () => {
+                if (dealsFilters.sort === 'status') {
+                    dealsFilters.order = dealsFilters.order === 'asc' ? 'desc' : 'asc';
+                } else {
+                    dealsFilters.sort = 'status';
+                    dealsFilters.order = 'asc';
+                }
+                dealsFilters.page = 1;
+                syncDealsSortIndicator();
+                loadDeals(true);
+            });
+        }
+        if (dealsControls.reset) {
+            dealsControls.reset.addEventListener('click', () => {
+                dealsFilters = { ...DEFAULT_DEALS_FILTERS };
+                renderFilterPresets();
+                syncDealsFiltersToControls();
+                loadDeals(true);
+            });
+        }
+        if (dealsControls.prev) {
+            dealsControls.prev.addEventListener('click', () => {
+                if (dealsFilters.page > 1) {
+                    dealsFilters.page -= 1;
+                    loadDeals(true);
+                }
+            });
+        }
+        if (dealsControls.next) {
+            dealsControls.next.addEventListener('click', () => {
+                if (dealsPagination.totalPages && dealsFilters.page < dealsPagination.totalPages) {
+                    dealsFilters.page += 1;
+                    loadDeals(true);
+                }
+            });
+        }
+    }
+
+    function buildChart(points, key, options) {
+        const width = 300;
+        const height = 80;
+        const values = points.map(point => Number(point[key] ?? 0));
+        const max = Math.max(options.max ?? 0, ...values, 1);
+        const step = width / Math.max(values.length, 1);
+        const label = (point, value) =>
+            html`<title>${formatDateTime(point.bucket)}: ${options.format(value)}</title>`;
+
+        if (options.type === 'bar') {
+            const bars = values.map((value, index) => {
+                const barHeight = (value / max) * (height - 4);
+                const x = index * step + step * 0.15;
+                return html`<rect x="${x.toFixed(2)}" y="${(height - barHeight).toFixed(2)}" width="${(step * 0.7).toFixed(2)}" height="${barHeight.toFixed(2)}" fill="${options.color}">${label(points[index], value)}</rect>`;
+            });
+            return html`<svg viewBox="0 0 ${width} ${height}" preserveAspectRatio="none">${bars}</svg>`;
+        }
+
+        const coords = values.map((value, index) => {
+            const x = index * step + step / 2;
+            const y = height - 2 - (value / max) * (height - 4);
+            return [x, y];
+        });
+        const line = coords.map(([x, y]) => `${x.toFixed(2)},${y.toFixed(2)}`).join(' ');
+        const dots = coords.map(([x, y], index) =>
+            html`<circle cx="${x.toFixed(2)}" cy="${y.toFixed(2)}" r="2" fill="${options.color}">${label(points[index], values[index])}</circle>`
+        );
+        return html`<svg viewBox="0 0 ${width} ${height}" preserveAspectRatio="none"><polyline points="${line}" fill="none" stroke="${options.color}" stroke-width="2" vector-effect="non-scaling-stroke" />${dots}</svg>`;
+    }
+
+    function renderChart(container, points, key, options) {
+        if (!container) {
+            return;
+        }
+        const body = container.querySelector('.chart-body');
+        const latest = container.querySelector('.chart-latest');
+        if (!points.length) {
+            if (body) {
+                setHtml(body, html`<div class="empty">${t('stats.empty')}</div>`);
+            }
+            if (latest) {
+                latest.textContent = '-';
+            }
+            return;
+        }
+        if (body) {
+            setHtml(body, buildChart(points, key, options));
+        }
+        if (latest) {
+            latest.textContent = options.format(Number(points[points.length - 1][key] ?? 0));
+        }
+    }
+
+    function renderTimeseries(response) {
+        const points = Array.isArray(response?.points) ? response.points : [];
+        const styles = getComputedStyle(document.documentElement);
+        const color = name => styles.getPropertyValue(name).trim() || '#38bdf8';
+        const formatCount = value => String(Math.round(value));
+        const formatPercent = value => `${(value * 100).toFixed(1)}%`;
+
+        renderChart(statsControls.assigned, points, 'assigned', {
+            type: 'bar',
+            color: color('--accent'),
+            format: formatCount,
+        });
+        renderChart(statsControls.backlog, points, 'backlog', {
+            type: 'line',
+            color: color('--warning'),
+            format: formatCount,
+        });
+        renderChart(statsControls.cancelRate, points, 'cancellationRate', {
+            type: 'line',
+            color: color('--error'),
+            max: 1,
+            format: formatPercent,
+        });
+    }
+
+    async function loadFlowShare(hours) {
+        if (!statsControls.flowShare) {
+            return;
+        }
+        try {
+            const { share } = await fetchJson(`/api/payouts/aggregated?hours=${encodeURIComponent(hours)}&perPage=1`);
+            const percent = value => (value ?? 0).toFixed(1);
+            statsControls.flowShare.textContent = t('stats.flow-share', {
+                aggregated: share.aggregated,
+                aggregatedShare: percent(share.aggregatedShare),
+                traders: share.traders,
+                tradersShare: percent(share.tradersShare),
+                unassigned: share.unassigned,
+                unassignedShare: percent(share.unassignedShare),
+            });
+        } catch (error) {
+            console.error('Ошибка загрузки доли агрегаторов:', error);
+            statsControls.flowShare.textContent = '';
+        }
+    }
+
+    async function loadTimeseries() {
+        if (isStatsLoading || !statsControls.assigned) {
+            return;
+        }
+        isStatsLoading = true;
+        try {
+            const hours = statsControls.hours?.value || '24';
+            const response = await fetchJson(`/api/stats/timeseries?hours=${encodeURIComponent(hours)}`);
+            renderTimeseries(response);
+            loadFlowShare(hours);
+        } catch (error) {
+            console.error('Ошибка загрузки статистики:', error);
+            [statsControls.assigned, statsControls.backlog, statsControls.cancelRate].forEach(container => {
+                const body = container?.querySelector('.chart-body');
+                if (body) {
+                    setHtml(body, html`<div class="empty">${t('stats.load-error')}</div>`);
+                }
+            });
+        } finally {
+            isStatsLoading = false;
+        }
+    }
+
+    function buildWindowRow(slot) {
+        const row = document.createElement('div');
+        row.className = 'schedule-window';
+        const days = Array.isArray(slot?.days) ? slot.days : [];
+        const toggles = [1, 2, 3, 4, 5, 6, 7].map(day => html`
+            <label><input type="checkbox" class="window-day" value="${day}" ${days.includes(day) ? 'checked' : ''} />${t(`days.${day}`)}</label>
+        `);
+        setHtml(row, html`
+            <div class="day-toggles">${toggles}</div>
+            <input type="time" class="window-start" value="${slot?.start ?? '09:00'}" />
+            <span>—</span>
+            <input type="time" class="window-end" value="${slot?.end ?? '18:00'}" />
+            <button type="button" class="danger remove-window">${t('settings.remove-window')}</button>
+        `);
+        row.querySelector('.remove-window')?.addEventListener('click', () => row.remove());
+        return row;
+    }
+
+    function renderWindows(windows) {
+        const container = document.getElementById('schedule-windows');
+        if (!container) {
+            return;
+        }
+        container.replaceChildren();
+        (Array.isArray(windows) ? windows : []).forEach(slot => {
+            container.appendChild(buildWindowRow(slot));
+        });
+    }
+
+    function collectWindows() {
+        const rows = document.querySelectorAll('#schedule-windows .schedule-window');
+        return Array.from(rows).map(row => ({
+            days: Array.from(row.querySelectorAll('.window-day:checked')).map(input => Number(input.value)),
+            start: row.querySelector('.window-start')?.value ?? '',
+            end: row.querySelector('.window-end')?.value ?? '',
+        }));
+    }
+
+    function renderSettings(settings) {
+        const checkbox = document.getElementById('auto-enabled');
+        const intervalInput = document.getElementById('auto-interval');
+        const perTraderInput = document.getElementById('auto-max-per-trader');
+        const enabled = Boolean(settings?.enabled);
+        const interval = Number(settings?.intervalSeconds ?? 30) || 30;
+        const perTraderCap = settings?.maxAssignmentsPerTraderPerCycle ?? null;
+        const perCycleCap = settings?.maxPayoutsPerCycle ?? null;
+        const perCycleInput = document.getElementById('auto-max-per-cycle');
+        const requireBalanceInput = document.getElementById('auto-require-balance');
+        const reserveInput = document.getElementById('auto-balance-reserve');
+        const maxOpenInput = document.getElementById('auto-max-open');
+        const cooldownInput = document.getElementById('auto-cooldown');
+        const timezone = settings?.timezone ?? 'UTC';
+        const windows = Array.isArray(settings?.windows) ? settings.windows : [];
+        const timezoneInput = document.getElementById('auto-timezone');
+
+        if (checkbox) {
+            checkbox.checked = enabled;
+        }
+        if (intervalInput) {
+            intervalInput.value = interval;
+        }
+        if (perTraderInput) {
+            perTraderInput.value = perTraderCap === null ? '' : String(perTraderCap);
+        }
+        if (perCycleInput) {
+            perCycleInput.value = perCycleCap === null ? '' : String(perCycleCap);
+        }
+        if (requireBalanceInput) {
+            requireBalanceInput.checked = Boolean(settings?.requireSufficientBalance);
+        }
+        const balanceTeamsInput = document.getElementById('auto-balance-teams');
+        if (balanceTeamsInput) {
+            balanceTeamsInput.checked = Boolean(settings?.balanceAcrossTeams);
+        }
+        if (reserveInput) {
+            reserveInput.value = String(settings?.balanceReserveRub ?? 0);
+        }
+        if (maxOpenInput) {
+            const maxOpen = settings?.maxOpenPayoutsPerTrader ?? null;
+            maxOpenInput.value = maxOpen === null ? '' : String(maxOpen);
+        }
+        if (cooldownInput) {
+            cooldownInput.value = String(settings?.assignmentCooldownSeconds ?? 0);
+        }
+        if (timezoneInput) {
+            timezoneInput.value = timezone;
+        }
+        renderWindows(windows);
+        if (autoBadge) {
+            autoBadge.textContent = enabled ? t('settings.badge.on') : t('settings.badge.off');
+            autoBadge.setAttribute('data-state', enabled ? 'on' : 'off');
+        }
+        if (settingsDescription) {
+            if (!enabled) {
+                settingsDescription.textContent = t('settings.description.disabled');
+            } else if (windows.length) {
+                settingsDescription.textContent = t('settings.description.scheduled', { interval, timezone });
+            } else {
+                settingsDescription.textContent = t('settings.description.enabled', { interval });
+            }
+        }
+    }
+
+    async function loadData(showStatus = true) {
+        if (isLoading) {
+            return;
+        }
+        isLoading = true;
+        try {
+            if (showStatus) {
+                setStatus('info', t('status.data-loading'));
+            }
+            const [options, traders, payouts, settings, summary] = await Promise.all([
+                fetchJson(`/api/traders?perPage=${TRADER_OPTIONS_LIMIT}`),
+                fetchFragment(`/fragments/traders-table?${tradersQueryString()}`),
+                fetchJson(`/api/payouts?${payoutsQueryString()}`),
+                fetchJson('/api/settings/auto-distribution'),
+                fetchJson('/api/stats/summary'),
+            ]);
+            traderOptions = Array.isArray(options?.items) ? options.items : [];
+            renderTraders(traders);
+            renderPayouts(payouts);
+            renderSettings(settings);
+            updateMetrics(summary);
+            markUpdated();
+            if (showStatus) {
+                setStatus('success', t('status.data-loaded'));
+            }
+        } catch (error) {
+            console.error('Ошибка при загрузке данных:', error);
+            const tradersBody = document.querySelector('#traders-table tbody');
+            const payoutsBody = document.querySelector('#payouts-table tbody');
+            renderEmpty(tradersBody, 10, t('traders.load-error'));
+            renderEmpty(payoutsBody, 5, t('payouts.load-error'));
+            setStatus('error', t('status.data-load-failed', { error: error.message }));
+        } finally {
+            isLoading = false;
+        }
+    }
+
+    async function assignPayout(payoutId) {
+        const select = document.getElementById(`assign-select-${payoutId}`);
+        const traderId = select?.value;
+
+        if (!traderId) {
+            setStatus('warning', t('status.select-trader'));
+            return;
+        }
+
+        try {
+            await fetchJson(`/api/payouts/${payoutId}/assign`, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ traderId }),
+            });
+            setStatus('success', t('status.assigned'));
+            await Promise.all([loadData(false), loadDeals(false)]);
+        } catch (error) {
+            if (error.code === 'PAYOUT_NOT_ELIGIBLE') {
+                setStatus('warning', t('status.assign-taken'));
+                await Promise.all([loadData(false), loadDeals(false)]);
+                return;
+            }
+            console.error('Ошибка привязки выплаты:', error);
+            setStatus('error', t('status.assign-failed', { error: error.message }));
+        }
+    }
+
+    async function approveDuplicate(payoutId) {
+        if (!window.confirm(t('payouts.duplicate-confirm'))) {
+            return;
+        }
+        try {
+            await fetchJson(`/api/payouts/${payoutId}/duplicate/approve`, { method: 'POST' });
+            setStatus('success', t('status.duplicate-approved'));
+            await loadData(false);
+        } catch (error) {
+            console.error('Ошибка подтверждения выплаты:', error);
+            setStatus('error', t('status.duplicate-approve-failed', { error: error.message }));
+        }
+    }
+
+    async function saveTraderLimit(traderId) {
+        if (!traderId) {
+            return;
+        }
+        const minInput = document.getElementById(`limit-min-input-${traderId}`);
+        const input = document.getElementById(`limit-input-${traderId}`);
+        if (!input || !minInput) {
+            return;
+        }
+        const parse = value => (value.trim() === '' ? null : Number(value.trim()));
+        const minAmount = parse(minInput.value);
+        const maxAmount = parse(input.value);
+
+        if ([minAmount, maxAmount].some(value => value !== null && (Number.isNaN(value) || value < 0))) {
+            setStatus('warning', t('status.limit-invalid'));
+            return;
+        }
+        if (minAmount && maxAmount && minAmount > maxAmount) {
+            setStatus('warning', t('status.limit-range-invalid'));
+            return;
+        }
+
+        try {
+            await fetchJson(`/api/traders/${traderId}/limit`, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ minAmount, maxAmount }),
+            });
+            setStatus('success', t('status.limit-saved'));
+            await Promise.all([loadData(false), loadDeals(false)]);
+        } catch (error) {
+            console.error('Ошибка сохранения лимита:', error);
+            setStatus('error', t('status.limit-save-failed', { error: error.message }));
+        }
+    }
+
+    async function saveSettings() {
+        const checkbox = document.getElementById('auto-enabled');
+        const intervalInput = document.getElementById('auto-interval');
+        const enabled = !!checkbox?.checked;
+        const intervalSeconds = Number(intervalInput?.value) || 1;
+        const perTraderRaw = document.getElementById('auto-max-per-trader')?.value.trim() ?? '';
+        const maxAssignmentsPerTraderPerCycle = perTraderRaw === '' ? null : Number(perTraderRaw);
+
+        if (
+            maxAssignmentsPerTraderPerCycle !== null
+            && (!Number.isInteger(maxAssignmentsPerTraderPerCycle) || maxAssignmentsPerTraderPerCycle < 1)
+        ) {
+            setStatus('warning', t('status.per-trader-cap-invalid'));
+            return;
+        }
+
+        const perCycleRaw = document.getElementById('auto-max-per-cycle')?.value.trim() ?? '';
+        const maxPayoutsPerCycle = perCycleRaw === '' ? null : Number(perCycleRaw);
+        if (
+            maxPayoutsPerCycle !== null
+            && (!Number.isInteger(maxPayoutsPerCycle) || maxPayoutsPerCycle < 1)
+        ) {
+            setStatus('warning', t('status.per-cycle-cap-invalid'));
+            return;
+        }
+
+        const requireSufficientBalance = !!document.getElementById('auto-require-balance')?.checked;
+        const balanceAcrossTeams = !!document.getElementById('auto-balance-teams')?.checked;
+        const reserveRaw = document.getElementById('auto-balance-reserve')?.value.trim() ?? '';
+        const balanceReserveRub = reserveRaw === '' ? 0 : Number(reserveRaw);
+        if (!Number.isFinite(balanceReserveRub) || balanceReserveRub < 0) {
+            setStatus('warning', t('status.balance-reserve-invalid'));
+            return;
+        }
+
+        const maxOpenRaw = document.getElementById('auto-max-open')?.value.trim() ?? '';
+        const maxOpenPayoutsPerTrader = maxOpenRaw === '' ? null : Number(maxOpenRaw);
+        if (
+            maxOpenPayoutsPerTrader !== null
+            && (!Number.isInteger(maxOpenPayoutsPerTrader) || maxOpenPayoutsPerTrader < 1)
+        ) {
+            setStatus('warning', t('status.open-cap-invalid'));
+            return;
+        }
+
+        const cooldownRaw = document.getElementById('auto-cooldown')?.value.trim() ?? '';
+        const assignmentCooldownSeconds = cooldownRaw === '' ? 0 : Number(cooldownRaw);
+        if (!Number.isInteger(assignmentCooldownSeconds) || assignmentCooldownSeconds < 0) {
+            setStatus('warning', t('status.cooldown-invalid'));
+            return;
+        }
+
+        const timezone = document.getElementById('auto-timezone')?.value.trim() || 'UTC';
+        const windows = collectWindows();
+        if (windows.some(slot => !slot.start || !slot.end)) {
+            setStatus('warning', t('status.window-invalid'));
+            return;
+        }
+
+        try {
+            const result = await fetchJson('/api/settings/auto-distribution', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({
+                    enabled,
+                    intervalSeconds,
+                    // Omitted caps keep their value; 0 removes them.
+                    maxAssignmentsPerTraderPerCycle: maxAssignmentsPerTraderPerCycle ?? 0,
+                    maxPayoutsPerCycle: maxPayoutsPerCycle ?? 0,
+                    timezone,
+                    windows,
+                    requireSufficientBalance,
+                    balanceReserveRub,
+                    assignmentCooldownSeconds,
+                    maxOpenPayoutsPerTrader: maxOpenPayoutsPerTrader ?? 0,
+                    balanceAcrossTeams,
+                }),
+            });
+            if (result?.status === 'pending') {
+                // Filed for a second admin; the form goes back to what applies.
+                setStatus('info', t('status.settings-change-pending', { risks: result.risks.join(', ') }));
+                await Promise.all([loadSettingsChanges(), loadData(false)]);
+                return;
+            }
+            renderSettings(result);
+            setStatus('success', t('status.settings-saved'));
+            markUpdated();
+            await Promise.all([loadData(false), loadDeals(false)]);
+        } catch (error) {
+            console.error('Ошибка сохранения настроек:', error);
+            setStatus('error', t('status.settings-save-failed', { error: error.message }));
+        }
+    }
+
+    function scheduleReload() {
+        if (reloadScheduled) {
+            return;
+        }
+        reloadScheduled = true;
+        setTimeout(async () => {
+            const version = announcedVersion;
+            try {
+                await Promise.all([loadData(false), loadDeals(false), loadTimeseries()]);
+                loadedVersion = Math.max(loadedVersion, version);
+            } finally {
+                reloadScheduled = false;
+            }
+            // Events that arrived while loading may not be reflected yet.
+            if (announcedVersion > loadedVersion) {
+                scheduleReload();
+            }
+        }, 400);
+    }
+
+    function initEventSource() {
+        try {
+            // A new EventSource does not send Last-Event-ID, so the resume
+            // point goes in the query.
+            const since = Math.max(loadedVersion, announcedVersion);
+            const eventSource = new EventSource(since ? `/api/events?lastEventId=${since}` : '/api/events');
+            eventSource.onmessage = (event) => {
+                try {
+                    const payload = JSON.parse(event.data);
+                    if (payload?.type === 'heartbeat') {
+                        renderHeartbeat(payload.data);
+                        return;
+                    }
+                    if (payload?.type === 'resync') {
+                        // The missed events are gone (or the server restarted
+                        // and counts from scratch): reload everything.
+                        loadedVersion = 0;
+                        announcedVersion = Number(payload.version ?? 0);
+                        scheduleReload();
+                        return;
+                    }
+                    if (typeof payload?.version === 'number') {
+                        if (payload.version <= loadedVersion) {
+                            return;
+                        }
+                        announcedVersion = Math.max(announcedVersion, payload.version);
+                    }
+                    if (payload?.type === 'cancel-reasons-updated') {
+                        loadCancelReasons();
+                    }
+                    if (payload?.type === 'distribution-cycle' && payload.message) {
+                        const summary = Object.fromEntries(
+                            payload.message.split(',').map(part => part.trim().split('=')),
+                        );
+                        setStatus('info', t('status.cycle-summary', summary));
+                    } else if (payload?.type === 'callback-sla-breached' && payload.data) {
+                        setStatus('error', t('status.callback-sla-breached', {
+                            ...payload.data,
+                            successRate: Number(payload.data.successRate).toFixed(1),
+                        }));
+                    } else if (payload?.type === 'callback-sla-recovered' && payload.data) {
+                        setStatus('success', t('status.callback-sla-recovered', {
+                            ...payload.data,
+                            successRate: Number(payload.data.successRate).toFixed(1),
+                        }));
+                    } else if (payload?.type === 'cancel-approval-requested' && payload.data) {
+                        setStatus('warning', t('status.cancel-approval-pending', payload.data));
+                    } else if (payload?.type === 'cancel-approval-closed' && payload.data) {
+                        setStatus('info', t(`status.cancel-approval-${payload.data.status}`, payload.data));
+                    } else if (payload?.type === 'settings-change-requested' && payload.data) {
+                        setStatus('warning', t('status.settings-change-requested', {
+                            ...payload.data,
+                            risks: payload.data.risks.join(', '),
+                        }));
+                        loadSettingsChanges();
+                    } else if (payload?.type === 'settings-change-closed' && payload.data) {
+                        setStatus('info', t(`status.settings-change-${payload.data.status}-by`, payload.data));
+                        loadSettingsChanges();
+                    } else if (payload?.type === 'trader-eligibility-dropped' && payload.data) {
+                        setStatus('warning', t('status.trader-eligibility-dropped', {
+                            ...payload.data,
+                            reasons: payload.data.reasons.map(reason => t(`eligibility.reason.${reason}`)).join(', '),
+                        }));
+                    } else if (payload?.type === 'assignment-slo-breached' && payload.data) {
+                        setStatus('error', t('status.assignment-slo-breached', {
+                            ...payload.data,
+                            p95Seconds: Math.round(payload.data.p95Seconds),
+                        }));
+                    } else if (payload?.type === 'assignment-slo-recovered' && payload.data) {
+                        setStatus('success', t('status.assignment-slo-recovered', {
+                            ...payload.data,
+                            p95Seconds: Math.round(payload.data.p95Seconds),
+                        }));
+                    } else if (payload?.type) {
+                        setStatus('info', t('status.event-received', { type: payload.type }));
+                    } else {
+                        setStatus('info', t('status.update-received'));
+                    }
+                } catch (parseError) {
+                    console.debug('Не удалось разобрать событие SSE:', parseError);
+                    setStatus('info', t('status.update-received'));
+                }
+                scheduleReload();
+            };
+            eventSource.onerror = () => {
+                setStatus('warning', t('status.sse-lost'));
+                eventSource.close();
+                setTimeout(initEventSource, 5000);
+            };
+        } catch (error) {
+            console.error('Не удалось открыть SSE соединение:', error);
+        }
+    }
+
+    if (applyStoredLanguage()) {
+        return;
+    }
+
+    const initialData = globalThis.__INITIAL_DASHBOARD__;
+    if (initialData) {
+        loadedVersion = Number(initialData.eventsVersion ?? 0);
+        announcedVersion = loadedVersion;
+        try {
+            traderOptions = Array.isArray(initialData.traders?.items) ? initialData.traders.items : [];
+            if (initialData.deals?.pagination) {
+                dealsFilters.perPage = Number(initialData.deals.pagination.perPage ?? dealsFilters.perPage);
+                dealsFilters.page = Number(initialData.deals.pagination.page ?? dealsFilters.page);
+            }
+            renderTraders({ pagination: initialData.traders?.pagination });
+            renderPayouts(initialData.payouts);
+            if (initialData.deals) {
+                renderDeals({ pagination: initialData.deals.pagination });
+            } else {
+                const dealsBody = document.querySelector('#deals-table tbody');
+                renderEmpty(dealsBody, dealsColumnCount(), t('deals.empty'));
+            }
+            renderSettings(initialData.settings);
+            updateMetrics(initialData.summary);
+            syncDealsFiltersToControls();
+            markUpdated();
+            setStatus('info', t('status.initial-data'));
+        } catch (error) {
+            console.error('Ошибка применения начальных данных:', error);
+        }
+    }
+
+    // Top bar search across payouts, traders and merchants (`/api/search`).
+    // Responses can arrive out of order, only the latest request renders.
+    let globalSearchTimer = null;
+    let globalSearchSeq = 0;
+
+    function globalSearchHits(data) {
+        const payouts = (data?.payouts ?? []).map(payout => html`<button
+                type="button"
+                class="search-hit"
+                data-kind="payout"
+                data-numeric-id="${payout.numericId}"
+            ><span>#${payout.numericId} · ${formatMoney(payout.amount, payout.currency)} · ${payout.status}</span>
+            <span class="search-hit-sub">${payout.externalReference ?? payout.id} · ${payout.wallet} · ${payout.merchantId}</span></button>`);
+        const traders = (data?.traders ?? []).map(trader => html`<button
+                type="button"
+                class="search-hit"
+                data-kind="trader"
+                data-trader-id="${trader.id}"
+            ><span>${trader.email}</span>
+            <span class="search-hit-sub">#${trader.numericId}${trader.banned ? ` · ${t('search.banned')}` : ''}${trader.trafficEnabled ? '' : ` · ${t('search.traffic-off')}`}</span></button>`);
+        const merchants = (data?.merchants ?? []).map(merchant => html`<div class="search-hit" data-kind="merchant">
+            <span>${merchant.name ?? merchant.id}</span>
+            <span class="search-hit-sub mono">${merchant.id}</span></div>`);
+        return [
+            [t('search.group.payouts'), payouts],
+            [t('search.group.traders'), traders],
+            [t('search.group.merchants'), merchants],
+        ].filter(([, items]) => items.length);
+    }
+
+    function renderGlobalSearch(results, data) {
+        const groups = globalSearchHits(data);
+        setHtml(results, groups.length
+            ? groups.map(([title, items]) => html`<h4>${title}</h4>${items}`)
+            : html`<div class="search-empty">${t('search.empty')}</div>`);
+        results.hidden = false;
+    }
+
+    async function runGlobalSearch(query) {
+        const results = document.getElementById('global-search-results');
+        if (!results) {
+            return;
+        }
+        const text = query.trim();
+        const seq = ++globalSearchSeq;
+        if (!/^#?\d+$/.test(text) && text.length < 2) {
+            results.hidden = true;
+            return;
+        }
+        try {
+            const data = await fetchJson(`/api/search?q=${encodeURIComponent(text)}`);
+            if (seq === globalSearchSeq) {
+                renderGlobalSearch(results, data);
+            }
+        } catch (error) {
+            if (seq === globalSearchSeq) {
+                console.error('Ошибка поиска:', error);
+                setHtml(results, html`<div class="search-empty">${t('search.failed', { error: error.message })}</div>`);
+                results.hidden = false;
+            }
+        }
+    }
+
+    function initGlobalSearch() {
+        const input = document.getElementById('global-search');
+        const results = document.getElementById('global-search-results');
+        if (!input || !results) {
+            return;
+        }
+        input.addEventListener('input', () => {
+            clearTimeout(globalSearchTimer);
+            globalSearchTimer = setTimeout(() => runGlobalSearch(input.value), 250);
+        });
+        input.addEventListener('keydown', (event) => {
+            if (event.key === 'Escape') {
+                results.hidden = true;
+                input.blur();
+            } else if (event.key === 'Enter') {
+                results.querySelector('button.search-hit')?.click();
+            }
+        });
+        input.addEventListener('focus', () => {
+            if (results.childElementCount && input.value.trim()) {
+                results.hidden = false;
+            }
+        });
+        results.addEventListener('click', (event) => {
+            const hit = event.target.closest('button.search-hit');
+            if (!hit) {
+                return;
+            }
+            results.hidden = true;
+            if (hit.dataset.kind === 'payout') {
+                jumpToPayout(hit.dataset.numericId);
+            } else if (hit.dataset.kind === 'trader') {
+                openAssignmentsDialog(hit.dataset.traderId);
+            }
+        });
+        document.addEventListener('click', (event) => {
+            if (!event.target.closest('.global-search')) {
+                results.hidden = true;
+            }
+        });
+    }
+
+    // Command palette (Ctrl/Cmd+K) and single-key shortcuts for operators who
+    // work the queue from the keyboard. Single keys are ignored while typing in
+    // a field or while a dialog is open.
+    const SHORTCUTS = [
+        { command: 'search', key: '/', label: 'palette.search-deals' },
+        { command: 'jump', key: 'g', label: 'palette.jump' },
+        { command: 'distribute', key: 'r', label: 'palette.distribute' },
+        { command: 'auto', key: 'a', label: 'palette.toggle-auto' },
+    ];
+    let paletteItems = [];
+    let paletteIndex = 0;
+
+    function buildPaletteItems(query) {
+        const text = query.trim();
+        const numericId = text.replace(/^#/, '');
+        const items = [];
+        if (/^\d+$/.test(numericId)) {
+            items.push({ command: 'jump', argument: numericId, label: t('palette.jump-to', { id: numericId }) });
+        }
+        if (text && !text.startsWith('#')) {
+            items.push({ command: 'search', argument: text, label: t('palette.search-for', { query: text }) });
+        }
+        const needle = text.toLowerCase();
+        SHORTCUTS
+            .filter(shortcut => !needle || t(shortcut.label).toLowerCase().includes(needle))
+            .forEach(shortcut => items.push({ command: shortcut.command, key: shortcut.key, label: t(shortcut.label) }));
+        return items;
+    }
+
+    function renderPalette() {
+        const input = document.getElementById('palette-input');
+        const list = document.getElementById('palette-list');
+        if (!input || !list) {
+            return;
+        }
+        paletteItems = buildPaletteItems(input.value);
+        paletteIndex = Math.min(paletteIndex, Math.max(paletteItems.length - 1, 0));
+        setHtml(list, paletteItems.length
+            ? paletteItems.map((item, index) => html`<li
+                    class="palette-item"
+                    role="option"
+                    data-palette-index="${index}"
+                    aria-selected="${index === paletteIndex ? 'true' : 'false'}"
+                ><span>${item.label}</span>${item.key ? html`<kbd>${item.key}</kbd>` : ''}</li>`)
+            : html`<li class="palette-item">${t('palette.no-matches')}</li>`);
+        list.querySelector('[aria-selected="true"]')?.scrollIntoView({ block: 'nearest' });
+    }
+
+    function openPalette(query) {
+        const dialog = document.getElementById('palette-dialog');
+        const input = document.getElementById('palette-input');
+        if (!dialog || !input || typeof dialog.showModal !== 'function') {
+            return;
+        }
+        input.value = query;
+        paletteIndex = 0;
+        renderPalette();
+        if (!dialog.open) {
+            dialog.showModal();
+        }
+        input.focus();
+    }
+
+    function closePalette() {
+        const dialog = document.getElementById('palette-dialog');
+        if (dialog?.open) {
+            dialog.close();
+        }
+    }
+
+    async function runPaletteItem(item) {
+        if (!item) {
+            return;
+        }
+        if (item.command === 'jump' && !item.argument) {
+            openPalette('#');
+            return;
+        }
+        closePalette();
+        switch (item.command) {
+            case 'jump':
+                await jumpToPayout(item.argument);
+                break;
+            case 'search':
+                if (item.argument !== undefined) {
+                    dealsFilters.search = item.argument;
+                    dealsFilters.page = 1;
+                    syncDealsFiltersToControls();
+                    await loadDeals(true);
+                }
+                dealsControls.search?.focus();
+                dealsControls.search?.select();
+                break;
+            case 'distribute':
+                await distributeNow();
+                break;
+            case 'auto':
+                await toggleAutoMode();
+                break;
+        }
+    }
+
+    async function distributeNow() {
+        if (!window.confirm(t('palette.distribute-confirm'))) {
+            return;
+        }
+        setStatus('info', t('status.distribution-running'));
+        try {
+            const result = await fetchJson('/api/distribution/run', { method: 'POST' });
+            if (result?.note) {
+                setStatus('warning', t('status.distribution-skipped', { note: result.note }));
+            } else {
+                setStatus('success', t('status.distribution-done', {
+                    assigned: result?.assigned ?? 0,
+                    remaining: result?.remaining ?? 0,
+                }));
+            }
+            await Promise.all([loadData(false), loadDeals(false)]);
+        } catch (error) {
+            console.error('Ошибка ручного распределения:', error);
+            setStatus('error', t('status.distribution-failed', { error: error.message }));
+        }
+    }
+
+    async function toggleAutoMode() {
+        const checkbox = document.getElementById('auto-enabled');
+        if (!checkbox) {
+            return;
+        }
+        checkbox.checked = !checkbox.checked;
+        await saveSettings();
+    }
+
+    function isTypingTarget(target) {
+        return target instanceof HTMLElement
+            && (target.isContentEditable || ['INPUT', 'TEXTAREA', 'SELECT'].includes(target.tagName));
+    }
+
+    function initShortcuts() {
+        document.getElementById('palette-open')?.addEventListener('click', () => openPalette(''));
+        const input = document.getElementById('palette-input');
+        input?.addEventListener('input', () => {
+            paletteIndex = 0;
+            renderPalette();
+        });
+        input?.addEventListener('keydown', (event) => {
+            if (event.key === 'ArrowDown' || event.key === 'ArrowUp') {
+                event.preventDefault();
+                const step = event.key === 'ArrowDown' ? 1 : -1;
+                const count = Math.max(paletteItems.length, 1);
+                paletteIndex = (paletteIndex + step + count) % count;
+                renderPalette();
+            } else if (event.key === 'Enter') {
+                event.preventDefault();
+                runPaletteItem(paletteItems[paletteIndex]);
+            }
+        });
+        document.getElementById('palette-list')?.addEventListener('click', (event) => {
+            const option = event.target.closest('[data-palette-index]');
+            if (option) {
+                runPaletteItem(paletteItems[Number(option.dataset.paletteIndex)]);
+            }
+        });
+        document.addEventListener('keydown', (event) => {
+            const otherDialogOpen = document.querySelector('dialog[open]:not(#palette-dialog)');
+            if ((event.ctrlKey || event.metaKey) && event.key.toLowerCase() === 'k') {
+                if (!otherDialogOpen) {
+                    event.preventDefault();
+                    openPalette('');
+                }
+                return;
+            }
+            if (event.ctrlKey || event.metaKey || event.altKey || event.defaultPrevented) {
+                return;
+            }
+            if (isTypingTarget(event.target) || document.querySelector('dialog[open]')) {
+                return;
+            }
+            const shortcut = SHORTCUTS.find(item => item.key === event.key);
+            if (shortcut) {
+                event.preventDefault();
+                runPaletteItem({ command: shortcut.command });
+            }
+        });
+    }
+
+    async function bootstrap() {
+        const saveButton = document.getElementById('save-settings');
+        if (saveButton) {
+            saveButton.addEventListener('click', saveSettings);
+        }
+        const addWindowButton = document.getElementById('add-window');
+        if (addWindowButton) {
+            addWindowButton.addEventListener('click', () => {
+                document.getElementById('schedule-windows')?.appendChild(buildWindowRow(null));
+            });
+        }
+        initLanguageToggle();
+        initThemeToggle();
+        deadLetterBadge?.addEventListener('click', retryDeadLetters);
+        initTradersControls();
+        initPayoutsControls();
+        initMerchantsControls();
+        initSettingsChangesControls();
+        initDealsControls();
+        initShortcuts();
+        initGlobalSearch();
+        initColumnsDialog();
+        if (statsControls.hours) {
+            statsControls.hours.addEventListener('change', () => loadTimeseries());
+        }
+        if (!initialData) {
+            syncDealsFiltersToControls();
+        }
+        initEventSource();
+        setInterval(loadSummary, SUMMARY_REFRESH_MS);
+        setInterval(tickCountdowns, 1000);
+        await Promise.all([
+            loadData(!initialData),
+            loadDeals(!initialData),
+            loadTimeseries(),
+            loadCancelReasons(),
+            loadMerchants(),
+            loadSettingsChanges(),
+            loadServerStatus(),
+            loadFilterPresets(),
+        ]);
+    }
+
+    function start() {
+        bootstrap().catch(error => {
+            console.error('Не удалось инициализировать страницу:', error);
+            setStatus('error', t('status.init-failed', { error: error.message }));
+        });
+    }
+
+    if (document.readyState === 'loading') {
+        document.addEventListener('DOMContentLoaded', start, { once: true });
+    } else {
+        start();
+    }
+})();
+"#;
+
+/// A static asset served from `/assets/`. The URL carries a fingerprint of the
+/// content, so browsers can keep it for good and refetch only after a deploy
+/// changed it.
+pub(crate) struct Asset {
+    pub(crate) content_type: &'static str,
+    pub(crate) body: &'static str,
+    pub(crate) version: String,
+    path: &'static str,
+}
+
+impl Asset {
+    fn new(path: &'static str, content_type: &'static str, body: &'static str) -> Self {
+        let digest = Sha256::digest(body.as_bytes());
+        Self {
+            content_type,
+            body,
+            version: hex::encode(&digest[..6]),
+            path,
+        }
+    }
+
+    fn url(&self) -> String {
+        format!("{}?v={}", self.path, self.version)
+    }
+}
+
+pub(crate) static APP_CSS: LazyLock<Asset> =
+    LazyLock::new(|| Asset::new("/assets/app.css", "text/css; charset=utf-8", STYLES));
+pub(crate) static APP_JS: LazyLock<Asset> = LazyLock::new(|| {
+    Asset::new(
+        "/assets/app.js",
+        "text/javascript; charset=utf-8",
+        DASHBOARD_SCRIPT,
+    )
+});
+
+#[component]
+fn App(snapshot: DashboardSnapshot, lang: Lang, theme: Theme) -> impl IntoView {
+    let initial_json = serde_json::to_string(&snapshot).unwrap_or_else(|_| "{}".to_string());
+    let i18n_json =
+        serde_json::to_string(&i18n::client_bundle(lang)).unwrap_or_else(|_| "{}".to_string());
+    let traders = snapshot.traders.items.clone();
+    let traders_pagination = snapshot.traders.pagination.clone();
+    let payouts = snapshot.payouts.items.clone();
+    let payouts_pagination = snapshot.payouts.pagination.clone();
+    let settings = snapshot.settings.clone();
+    let tenant = snapshot.tenant.clone();
+    let operator = snapshot.operator.clone();
+    let csrf_token = snapshot.csrf_token.clone();
+    let logout_token = operator.as_ref().map(|_| csrf_token.clone());
+
+    let summary = snapshot.summary.clone();
+    let deals = snapshot.deals.clone();
+    let traders_for_options = traders.clone();
+    let deals_items = deals.items.clone();
+    let deals_columns = snapshot.deals_layout.visible_columns();
+    let layout_columns = snapshot.deals_layout.columns().to_vec();
+    let deals_empty_key = if snapshot.deals_filtered {
+        "deals.empty-filtered"
+    } else {
+        "deals.empty"
+    };
+    let deals_pagination = deals.pagination.clone();
+    let traders_page_info = page_info(lang, &traders_pagination);
+    let payouts_page_info = page_info(lang, &payouts_pagination);
+    let deals_page_info = page_info(lang, &deals_pagination);
+    let settings_description = if settings.enabled && !settings.windows.is_empty() {
+        tf(
+            lang,
+            "settings.description.scheduled",
+            &[
+                ("interval", settings.interval_seconds.max(1).to_string()),
+                ("timezone", settings.timezone.clone()),
+            ],
+        )
+    } else if settings.enabled {
+        tf(
+            lang,
+            "settings.description.enabled",
+            &[("interval", settings.interval_seconds.max(1).to_string())],
+        )
+    } else {
+        t(lang, "settings.description.disabled").to_string()
+    };
+
+    let payouts_view = if payouts.is_empty() {
+        view! { <tr><td class="empty" colspan="5">{t(lang, "payouts.empty")}</td></tr> }
+            .into_view()
+    } else {
+        view! {
+            <For
+                each=move || payouts.clone()
                 key=|payout| payout.id.clone()
                 children=move |payout| {
                     let options: Vec<_> = traders_for_options
@@ -1230,20 +4269,942 @@ fn App(snapshot: DashboardSnapshot) -> impl IntoView {
                             }
                         })
                         .collect();
+                    let priority_badge = payout.priority.then(|| {
+                        view! { <span class="badge priority-badge">{t(lang, "payouts.priority")}</span> }
+                    });
+                    let duplicate_badge = payout.duplicate.then(|| {
+                        view! {
+                            <button
+                                class="badge duplicate-badge approve-duplicate"
+                                data-payout-id={payout.id.clone()}
+                                type="button"
+                                title=t(lang, "payouts.duplicate-hint")
+                            >
+                                {t(lang, "payouts.duplicate")}
+                            </button>
+                        }
+                    });
+                    view! {
+                        <tr>
+                            <td>{payout.numeric_id}{priority_badge}{duplicate_badge}</td>
+                            <td>{format_money(payout.amount, &payout.currency)}</td>
+                            <td>{payout.bank.clone().unwrap_or_else(|| "-".to_string())}</td>
+                            <td>{payout.external_reference.clone().unwrap_or_else(|| "-".to_string())}</td>
+                            <td>
+                                <div class="assign-controls">
+                                    <select id={format!("assign-select-{}", payout.id)}>
+                                        <option value="">{t(lang, "payouts.select-trader")}</option>
+                                        {options.into_view()}
+                                    </select>
+                                    <button class="assign-button" data-payout-id={payout.id.clone()}>{t(lang, "payouts.assign")}</button>
+                                </div>
+                            </td>
+                        </tr>
+                    }
+                }
+            />
+        }
+        .into_view()
+    };
+
+    let initial_data_script = format!(
+        "window.__INITIAL_DASHBOARD__ = {};\nwindow.__I18N__ = {};\nwindow.__PAYOUT_STATUSES__ = {};",
+        initial_json,
+        i18n_json,
+        payout_status::client_bundle()
+    );
+
+    let theme_toggle_text = match theme {
+        Theme::Dark => t(lang, "page.theme-light"),
+        Theme::Light => t(lang, "page.theme-dark"),
+    };
+
+    let environment = &snapshot.environment;
+    let page_title = match environment.label() {
+        Some(label) => format!("[{}] Chase Linker Dashboard", label.to_uppercase()),
+        None => "Chase Linker Dashboard".to_string(),
+    };
+    let environment_banner = environment.label().map(|label| {
+        let locks: Vec<&str> = environment.locks().map(|lock| lock.as_str()).collect();
+        let locked = (!locks.is_empty())
+            .then(|| tf(lang, "environment.locked", &[("locks", locks.join(", "))]));
+        view! {
+            <div class="env-banner" data-kind=environment.kind().code()>
+                <strong>{label.to_uppercase()}</strong>
+                {locked.map(|text| view! { <span>{text}</span> })}
+            </div>
+        }
+    });
+
+    let badge_state = if settings.enabled { "on" } else { "off" };
+    let badge_text = if settings.enabled {
+        t(lang, "settings.badge.on")
+    } else {
+        t(lang, "settings.badge.off")
+    };
+
+    view! {
+        <html lang=lang.code() data-theme=theme.code()>
+            <head>
+                <meta charset="UTF-8" />
+                <meta name="csrf-token" content=csrf_token />
+                <title>{page_title}</title>
+                <link rel="stylesheet" href=APP_CSS.url() />
+            </head>
+            <body>
+                {environment_banner}
+                <header class="top-bar">
+                    <div>
+                        <h1>{t(lang, "page.title")}</h1>
+                        <p>{t(lang, "page.subtitle")}</p>
+                    </div>
+                    <div class="global-search">
+                        <input
+                            id="global-search"
+                            type="search"
+                            autocomplete="off"
+                            placeholder=t(lang, "search.placeholder")
+                        />
+                        <div id="global-search-results" class="global-search-results" hidden=true></div>
+                    </div>
+                    <div class="status-block">
+                        <span class="status-label">{t(lang, "page.updated")}</span>
+                        <span class="status-value" id="last-updated">-</span>
+                        {tenant
+                            .map(|name| {
+                                view! {
+                                    <span class="status-label">{t(lang, "page.tenant")}</span>
+                                    <span class="status-value" id="tenant-name">{name}</span>
+                                }
+                            })}
+                        {operator
+                            .map(|name| {
+                                view! {
+                                    <span class="status-label">{t(lang, "page.operator")}</span>
+                                    <span class="status-value" id="operator-name">{name}</span>
+                                }
+                            })}
+                        <span class="status-label">{t(lang, "page.backend")}</span>
+                        <span class="status-value" id="backend-status" data-state="unknown">
+                            "-"
+                        </span>
+                        <button id="dlq-badge" class="dlq-badge" type="button" hidden=true></button>
+                        <div class="toggle-row">
+                            <button id="theme-toggle" class="theme-toggle" type="button">
+                                {theme_toggle_text}
+                            </button>
+                            <button id="lang-toggle" class="lang-toggle" type="button">
+                                {t(lang, "page.language-toggle")}
+                            </button>
+                            <button id="palette-open" class="palette-toggle" type="button" title=t(lang, "palette.hint")>
+                                {t(lang, "palette.open")}
+                            </button>
+                            <a class="report-link" href="/api/reports/shift?format=html" target="_blank" rel="noopener">
+                                {t(lang, "page.shift-report")}
+                            </a>
+                            {logout_token
+                                .map(|token| {
+                                    view! {
+                                        <form class="logout-form" method="post" action="/logout">
+                                            <input type="hidden" name="csrf" value=token />
+                                            <button type="submit">{t(lang, "page.logout")}</button>
+                                        </form>
+                                    }
+                                })}
+                        </div>
+                    </div>
+                </header>
+                <main>
+                    <div id="global-status" class="status-banner" role="status"></div>
+                    <section class="metrics-grid">
+                        <article class="metric-card">
+                            <span class="metric-label">{t(lang, "metrics.traders.label")}</span>
+                            <span class="metric-value" id="metric-traders">{summary.eligible_traders}</span>
+                            <span class="metric-sub">{t(lang, "metrics.traders.sub")}</span>
+                        </article>
+                        <article class="metric-card">
+                            <span class="metric-label">{t(lang, "metrics.payouts.label")}</span>
+                            <span class="metric-value" id="metric-payouts">{summary.unassigned_count}</span>
+                            <span class="metric-sub">{t(lang, "metrics.payouts.sub")}</span>
+                        </article>
+                        <article class="metric-card">
+                            <span class="metric-label">{t(lang, "metrics.sum.label")}</span>
+                            <span class="metric-value" id="metric-payout-sum">{format_amount(Some(summary.unassigned_amount))}</span>
+                            <span class="metric-sub">{t(lang, "metrics.sum.sub")}</span>
+                        </article>
+                        <article class="metric-card">
+                            <span class="metric-label">{t(lang, "metrics.assigned-today.label")}</span>
+                            <span class="metric-value" id="metric-assigned-today">{summary.today.assigned_today}</span>
+                            <span class="metric-sub">
+                                {t(lang, "metrics.today.amount")}
+                                " "
+                                <span id="metric-assigned-today-sum">{format_amount(Some(summary.today.assigned_today_amount))}</span>
+                            </span>
+                        </article>
+                        <article class="metric-card">
+                            <span class="metric-label">{t(lang, "metrics.cancelled-today.label")}</span>
+                            <span class="metric-value" id="metric-cancelled-today">{summary.today.cancelled_today}</span>
+                            <span class="metric-sub">
+                                {t(lang, "metrics.today.amount")}
+                                " "
+                                <span id="metric-cancelled-today-sum">{format_amount(Some(summary.today.cancelled_today_amount))}</span>
+                            </span>
+                        </article>
+                        <article class="metric-card">
+                            <span class="metric-label">{t(lang, "metrics.completed-today.label")}</span>
+                            <span class="metric-value" id="metric-completed-today">{summary.today.completed_today}</span>
+                            <span class="metric-sub">
+                                {t(lang, "metrics.today.amount")}
+                                " "
+                                <span id="metric-completed-today-sum">{format_amount(Some(summary.today.completed_today_amount))}</span>
+                            </span>
+                        </article>
+                    </section>
+
+                    <section class="panel">
+                        <div class="panel-header">
+                            <div>
+                                <h2>{t(lang, "settings.title")}</h2>
+                                <p id="settings-description" class="panel-subtitle">{settings_description}</p>
+                            </div>
+                            <span id="auto-status-badge" class="badge" data-state=badge_state>{badge_text}</span>
+                        </div>
+                        <div class="controls-row">
+                            <label>
+                                <input type="checkbox" id="auto-enabled" checked=settings.enabled />
+                                {t(lang, "settings.enable")}
+                            </label>
+                            <label>
+                                {t(lang, "settings.interval")}
+                                <input
+                                    type="number"
+                                    id="auto-interval"
+                                    min="1"
+                                    max=MAX_INTERVAL_SECONDS.to_string()
+                                    value={settings.interval_seconds.max(1).to_string()}
+                                />
+                            </label>
+                            <label>
+                                {t(lang, "settings.per-trader-cap")}
+                                <input
+                                    type="number"
+                                    id="auto-max-per-trader"
+                                    min="1"
+                                    step="1"
+                                    placeholder=t(lang, "settings.no-cap")
+                                    value={settings
+                                        .max_assignments_per_trader_per_cycle
+                                        .map(|value| value.to_string())
+                                        .unwrap_or_default()}
+                                />
+                            </label>
+                            <label>
+                                {t(lang, "settings.per-cycle-cap")}
+                                <input
+                                    type="number"
+                                    id="auto-max-per-cycle"
+                                    min="1"
+                                    step="1"
+                                    placeholder=t(lang, "settings.no-cap")
+                                    value={settings
+                                        .max_payouts_per_cycle
+                                        .map(|value| value.to_string())
+                                        .unwrap_or_default()}
+                                />
+                            </label>
+                            <label>
+                                <input
+                                    type="checkbox"
+                                    id="auto-require-balance"
+                                    checked=settings.require_sufficient_balance
+                                />
+                                {t(lang, "settings.require-balance")}
+                            </label>
+                            <label>
+                                <input
+                                    type="checkbox"
+                                    id="auto-balance-teams"
+                                    checked=settings.balance_across_teams
+                                />
+                                {t(lang, "settings.balance-teams")}
+                            </label>
+                            <label>
+                                {t(lang, "settings.balance-reserve")}
+                                <input
+                                    type="number"
+                                    id="auto-balance-reserve"
+                                    min="0"
+                                    step="0.01"
+                                    value={settings.balance_reserve_rub.to_string()}
+                                />
+                            </label>
+                            <label>
+                                {t(lang, "settings.max-open-payouts")}
+                                <input
+                                    type="number"
+                                    id="auto-max-open"
+                                    min="1"
+                                    step="1"
+                                    placeholder=t(lang, "settings.no-cap")
+                                    value={settings
+                                        .max_open_payouts_per_trader
+                                        .map(|value| value.to_string())
+                                        .unwrap_or_default()}
+                                />
+                            </label>
+                            <label>
+                                {t(lang, "settings.cooldown")}
+                                <input
+                                    type="number"
+                                    id="auto-cooldown"
+                                    min="0"
+                                    step="1"
+                                    value={settings.assignment_cooldown_seconds.to_string()}
+                                />
+                            </label>
+                            <button id="save-settings">{t(lang, "common.save")}</button>
+                        </div>
+                        <div class="schedule-block">
+                            <div class="controls-row">
+                                <strong>{t(lang, "settings.schedule")}</strong>
+                                <label>
+                                    {t(lang, "settings.timezone")}
+                                    <input
+                                        type="text"
+                                        id="auto-timezone"
+                                        placeholder="Europe/Moscow"
+                                        value={settings.timezone.clone()}
+                                    />
+                                </label>
+                                <button id="add-window" type="button">{t(lang, "settings.add-window")}</button>
+                            </div>
+                            <div id="schedule-windows"></div>
+                            <p class="panel-subtitle">{t(lang, "settings.schedule-hint")}</p>
+                        </div>
+                        <div id="settings-changes-block" class="settings-changes" hidden=true>
+                            <strong>{t(lang, "settings-changes.title")}</strong>
+                            <ul id="settings-changes"></ul>
+                        </div>
+                    </section>
+
+                    <section class="panel" id="stats-panel">
+                        <div class="panel-header">
+                            <h2>{t(lang, "stats.title")}</h2>
+                            <div class="input-control">
+                                <label for="stats-hours">{t(lang, "stats.period")}</label>
+                                <select id="stats-hours">
+                                    <option value="24" selected=true>{t(lang, "stats.hours-24")}</option>
+                                    <option value="72">{t(lang, "stats.hours-72")}</option>
+                                    <option value="168">{t(lang, "stats.hours-168")}</option>
+                                </select>
+                            </div>
+                        </div>
+                        <div class="charts-grid">
+                            <article class="chart-card" id="chart-assigned">
+                                <div class="chart-header">
+                                    <span>{t(lang, "stats.assigned")}</span>
+                                    <span class="chart-latest">-</span>
+                                </div>
+                                <div class="chart-body"></div>
+                            </article>
+                            <article class="chart-card" id="chart-backlog">
+                                <div class="chart-header">
+                                    <span>{t(lang, "stats.backlog")}</span>
+                                    <span class="chart-latest">-</span>
+                                </div>
+                                <div class="chart-body"></div>
+                            </article>
+                            <article class="chart-card" id="chart-cancel-rate">
+                                <div class="chart-header">
+                                    <span>{t(lang, "stats.cancel-rate")}</span>
+                                    <span class="chart-latest">-</span>
+                                </div>
+                                <div class="chart-body"></div>
+                            </article>
+                        </div>
+                        <p id="stats-flow-share" class="panel-subtitle"></p>
+                    </section>
+
+                    <section class="panel">
+                        <div class="panel-header">
+                            <h2>{t(lang, "traders.title")}</h2>
+                            <div class="traders-toolbar">
+                                <input
+                                    id="traders-search"
+                                    type="search"
+                                    placeholder=t(lang, "traders.search-placeholder")
+                                    value=""
+                                />
+                                <label>
+                                    <input type="checkbox" id="traders-group-team" />
+                                    {t(lang, "traders.group-by-team")}
+                                </label>
+                            </div>
+                        </div>
+                        <div class="traders-bulk" title=t(lang, "limits-bulk.hint")>
+                            <span class="panel-subtitle">{t(lang, "limits-bulk.title")}</span>
+                            <input
+                                id="traders-bulk-min"
+                                type="number"
+                                min="0"
+                                step="0.01"
+                                placeholder=t(lang, "traders.min-amount")
+                            />
+                            <input
+                                id="traders-bulk-max"
+                                type="number"
+                                min="0"
+                                step="0.01"
+                                placeholder=t(lang, "traders.max-amount")
+                            />
+                            <input
+                                id="traders-bulk-cap"
+                                type="number"
+                                min="0"
+                                step="1"
+                                placeholder=t(lang, "limits-bulk.cap")
+                                title=t(lang, "limits-bulk.cap-hint")
+                            />
+                            <label>
+                                <input type="checkbox" id="traders-bulk-all" />
+                                {t(lang, "limits-bulk.all")}
+                            </label>
+                            <button id="traders-bulk-apply" type="button" disabled=true>
+                                {tf(lang, "limits-bulk.apply", &[("count", "0".to_string())])}
+                            </button>
+                        </div>
+                        <div class="table-wrapper">
+                            <table id="traders-table">
+                                <thead>
+                                    <tr>
+                                        <th class="deal-select-cell">
+                                            <input
+                                                type="checkbox"
+                                                id="traders-select-all"
+                                                title=t(lang, "traders.select-all")
+                                            />
+                                        </th>
+                                        <th class="sortable" data-sort="numericId">numericId</th>
+                                        <th class="sortable" data-sort="email">Email</th>
+                                        <th class="sortable" data-sort="team">{t(lang, "traders.team")}</th>
+                                        <th class="sortable" data-sort="balanceRub">{t(lang, "traders.balance")}</th>
+                                        <th class="sortable" data-sort="frozenRub">{t(lang, "traders.frozen")}</th>
+                                        <th class="sortable" data-sort="payoutBalance">{t(lang, "traders.payout-balance")}</th>
+                                        <th>{t(lang, "traders.open-payouts")}</th>
+                                        <th>{t(lang, "traders.cooldown")}</th>
+                                        <th>{t(lang, "traders.amount-range")}</th>
+                                    </tr>
+                                </thead>
+                                <tbody><TraderRows traders=traders lang=lang empty_key="traders.empty" /></tbody>
+                            </table>
+                        </div>
+                        <div class="table-pagination">
+                            <span id="traders-page-info">{traders_page_info}</span>
+                            <button id="traders-prev" type="button" disabled={traders_pagination.page <= 1}>{t(lang, "pagination.prev")}</button>
+                            <button
+                                id="traders-next"
+                                type="button"
+                                disabled={traders_pagination.total_pages == 0
+                                    || traders_pagination.page >= traders_pagination.total_pages}
+                            >{t(lang, "pagination.next")}</button>
+                        </div>
+                    </section>
+
+                    <section class="panel">
+                        <div class="panel-header">
+                            <h2>{t(lang, "payouts.title")}</h2>
+                        </div>
+                        <div class="table-wrapper">
+                            <table id="payouts-table">
+                                <thead>
+                                    <tr>
+                                        <th>numericId</th>
+                                        <th>{t(lang, "common.amount")}</th>
+                                        <th>{t(lang, "common.bank")}</th>
+                                        <th>External Reference</th>
+                                        <th>{t(lang, "common.actions")}</th>
+                                    </tr>
+                                </thead>
+                                <tbody>{payouts_view}</tbody>
+                            </table>
+                        </div>
+                        <div class="table-pagination">
+                            <span id="payouts-page-info">{payouts_page_info}</span>
+                            <button id="payouts-prev" type="button" disabled={payouts_pagination.page <= 1}>{t(lang, "pagination.prev")}</button>
+                            <button
+                                id="payouts-next"
+                                type="button"
+                                disabled={payouts_pagination.total_pages == 0
+                                    || payouts_pagination.page >= payouts_pagination.total_pages}
+                            >{t(lang, "pagination.next")}</button>
+                        </div>
+                    </section>
+
+                    <section class="panel">
+                        <div class="panel-header">
+                            <h2>{t(lang, "merchants.title")}</h2>
+                        </div>
+                        <div class="table-wrapper">
+                            <table id="merchants-table">
+                                <thead>
+                                    <tr>
+                                        <th>{t(lang, "merchants.name")}</th>
+                                        <th>{t(lang, "merchants.callbacks")}</th>
+                                        <th>{t(lang, "merchants.queued")}</th>
+                                        <th>{t(lang, "common.actions")}</th>
+                                    </tr>
+                                </thead>
+                                <tbody></tbody>
+                            </table>
+                        </div>
+                    </section>
+
+                    <section class="panel">
+                        <div class="panel-header">
+                            <h2>{t(lang, "deals.title")}</h2>
+                        </div>
+                        <div class="filters-grid">
+                            <div class="input-control">
+                                <label for="deals-search">{t(lang, "deals.search")}</label>
+                                <input
+                                    id="deals-search"
+                                    type="text"
+                                    placeholder="numericId / externalRef / id"
+                                    value=""
+                                />
+                            </div>
+                            <div class="input-control">
+                                <label for="deals-wallet">{t(lang, "deals.wallet")}</label>
+                                <input
+                                    id="deals-wallet"
+                                    type="text"
+                                    placeholder=t(lang, "deals.wallet-placeholder")
+                                    value=""
+                                />
+                            </div>
+                            <div class="input-control">
+                                <label for="deals-amount">{t(lang, "common.amount")}</label>
+                                <input
+                                    id="deals-amount"
+                                    type="number"
+                                    step="0.01"
+                                    min="0"
+                                    placeholder=t(lang, "common.amount")
+                                    value=""
+                                />
+                            </div>
+                            <div class="input-control">
+                                <label for="deals-status">{t(lang, "common.status")}</label>
+                                <select id="deals-status">
+                                    <option value="">{t(lang, "deals.status-all")}</option>
+                                    {PayoutStatus::ALL
+                                        .into_iter()
+                                        .map(|status| {
+                                            view! {
+                                                <option value=status.as_str()>{status.as_str()}</option>
+                                            }
+                                        })
+                                        .collect_view()}
+                                </select>
+                            </div>
+                            <div class="input-control">
+                                <label for="deals-per-page">{t(lang, "deals.per-page")}</label>
+                                <select id="deals-per-page">
+                                    <option value="25" selected={deals_pagination.per_page == 25}>25</option>
+                                    <option value="50" selected={deals_pagination.per_page == 50}>50</option>
+                                    <option value="100" selected={deals_pagination.per_page == 100}>100</option>
+                                </select>
+                            </div>
+                        </div>
+                        <div class="deals-toolbar">
+                            <select id="deals-preset" title=t(lang, "presets.title")>
+                                <option value="">{t(lang, "presets.choose")}</option>
+                            </select>
+                            <button id="deals-preset-save" type="button">{t(lang, "presets.save")}</button>
+                            <button id="deals-preset-delete" type="button" disabled=true>{t(lang, "presets.delete")}</button>
+                            <button id="deals-copy-link" type="button">{t(lang, "presets.copy-link")}</button>
+                            <button id="deals-columns" type="button">{t(lang, "columns.open")}</button>
+                            <label>
+                                <input type="checkbox" id="deals-include-archived" />
+                                {t(lang, "deals.include-archived")}
+                            </label>
+                            <button id="deals-sort-status" type="button">{t(lang, "deals.sort-status")}</button>
+                            <button id="deals-reset" type="button">{t(lang, "deals.reset")}</button>
+                            <button id="deals-bulk-cancel" class="danger" type="button" disabled=true>
+                                {tf(lang, "deals.bulk-cancel", &[("count", "0".to_string())])}
+                            </button>
+                        </div>
+                        <div class="table-wrapper">
+                            <table id="deals-table">
+                                <thead>
+                                    <tr>
+                                        <th class="deal-select-cell">
+                                            <input
+                                                type="checkbox"
+                                                id="deals-select-all"
+                                                title=t(lang, "deals.select-all")
+                                            />
+                                        </th>
+                                        {deals_columns
+                                            .iter()
+                                            .map(|preference| {
+                                                view! {
+                                                    <th
+                                                        data-column=preference.column.code()
+                                                        style:width=column_width(preference)
+                                                    >
+                                                        {deal_column_label(lang, preference.column)}
+                                                    </th>
+                                                }
+                                            })
+                                            .collect_view()}
+                                    </tr>
+                                </thead>
+                                <tbody>
+                                    <DealRows
+                                        deals=deals_items
+                                        columns=deals_columns.clone()
+                                        lang=lang
+                                        empty_key=deals_empty_key
+                                    />
+                                </tbody>
+                            </table>
+                        </div>
+                        <div class="table-pagination">
+                            <span id="deals-page-info">{deals_page_info.clone()}</span>
+                            <button id="deals-prev" type="button" disabled={deals_pagination.page <= 1}>{t(lang, "pagination.prev")}</button>
+                            <button
+                                id="deals-next"
+                                type="button"
+                                disabled={deals_pagination.total_pages == 0
+                                    || deals_pagination.page >= deals_pagination.total_pages}
+                            >{t(lang, "pagination.next")}</button>
+                        </div>
+                    </section>
+                </main>
+                <dialog id="cancel-dialog" class="modal-dialog">
+                    <form method="dialog">
+                        <h3 id="cancel-dialog-title">{t(lang, "status.cancel-confirm")}</h3>
+                        <div class="input-control">
+                            <label for="cancel-reason-code">{t(lang, "cancel-dialog.code")}</label>
+                            <select id="cancel-reason-code">
+                                <option value="">{t(lang, "cancel-dialog.no-code")}</option>
+                            </select>
+                        </div>
+                        <div class="input-control">
+                            <label for="cancel-reason-comment">{t(lang, "cancel-dialog.comment")}</label>
+                            <textarea
+                                id="cancel-reason-comment"
+                                rows="3"
+                                placeholder=t(lang, "cancel-dialog.comment-placeholder")
+                            ></textarea>
+                        </div>
+                        <div class="dialog-actions">
+                            <button type="submit" value="close">{t(lang, "common.close")}</button>
+                            <button type="submit" value="confirm" class="danger">{t(lang, "cancel-dialog.confirm")}</button>
+                        </div>
+                    </form>
+                </dialog>
+                <dialog id="assignments-dialog" class="modal-dialog wide-dialog">
+                    <form method="dialog">
+                        <h3 id="assignments-dialog-title">{t(lang, "assignments.heading")}</h3>
+                        <div class="dialog-filters">
+                            <div class="input-control">
+                                <label for="assignments-from">{t(lang, "assignments.from")}</label>
+                                <input id="assignments-from" type="date" />
+                            </div>
+                            <div class="input-control">
+                                <label for="assignments-to">{t(lang, "assignments.to")}</label>
+                                <input id="assignments-to" type="date" />
+                            </div>
+                        </div>
+                        <span id="assignments-summary" class="panel-subtitle"></span>
+                        <div class="table-wrapper">
+                            <table id="assignments-table">
+                                <thead>
+                                    <tr>
+                                        <th>numericId</th>
+                                        <th>{t(lang, "assignments.amount")}</th>
+                                        <th>{t(lang, "assignments.status")}</th>
+                                        <th>{t(lang, "assignments.bank")}</th>
+                                        <th>{t(lang, "assignments.assigned-at")}</th>
+                                        <th>{t(lang, "assignments.finished-at")}</th>
+                                    </tr>
+                                </thead>
+                                <tbody></tbody>
+                            </table>
+                        </div>
+                        <h4>{t(lang, "eligibility.heading")}</h4>
+                        <div class="table-wrapper">
+                            <table id="eligibility-table">
+                                <thead>
+                                    <tr>
+                                        <th>{t(lang, "eligibility.at")}</th>
+                                        <th>{t(lang, "eligibility.change")}</th>
+                                        <th>{t(lang, "eligibility.reasons")}</th>
+                                        <th>{t(lang, "eligibility.merchants")}</th>
+                                    </tr>
+                                </thead>
+                                <tbody></tbody>
+                            </table>
+                        </div>
+                        <div class="dialog-actions">
+                            <button type="submit" value="close">{t(lang, "common.close")}</button>
+                        </div>
+                    </form>
+                </dialog>
+                <dialog id="limits-bulk-dialog" class="modal-dialog wide-dialog">
+                    <form method="dialog">
+                        <h3>{t(lang, "limits-bulk.title")}</h3>
+                        <span id="limits-bulk-summary" class="panel-subtitle"></span>
+                        <div class="table-wrapper">
+                            <table id="limits-bulk-table">
+                                <thead>
+                                    <tr>
+                                        <th>numericId</th>
+                                        <th>Email</th>
+                                        <th>{t(lang, "traders.amount-range")}</th>
+                                        <th>{t(lang, "limits-bulk.cap")}</th>
+                                    </tr>
+                                </thead>
+                                <tbody></tbody>
+                            </table>
+                        </div>
+                        <div class="dialog-actions">
+                            <button type="submit" value="close">{t(lang, "common.close")}</button>
+                            <button type="submit" value="confirm">{t(lang, "limits-bulk.confirm")}</button>
+                        </div>
+                    </form>
+                </dialog>
+                <dialog id="files-dialog" class="modal-dialog">
+                    <form method="dialog">
+                        <h3 id="files-dialog-title">{t(lang, "deals.files")}</h3>
+                        <div id="files-dialog-body" class="files-list"></div>
+                        <div class="input-control">
+                            <label for="files-kind">{t(lang, "files.kind")}</label>
+                            <select id="files-kind">
+                                <option value="proof">{t(lang, "files.proof")}</option>
+                                <option value="dispute">{t(lang, "files.dispute")}</option>
+                            </select>
+                        </div>
+                        <div class="input-control">
+                            <label for="files-input">{t(lang, "files.file")}</label>
+                            <input id="files-input" type="file" />
+                        </div>
+                        <div class="dialog-actions">
+                            <button type="submit" value="close">{t(lang, "common.close")}</button>
+                            <button type="button" id="files-upload">{t(lang, "files.upload")}</button>
+                        </div>
+                    </form>
+                </dialog>
+                <dialog id="columns-dialog" class="modal-dialog">
+                    <form method="dialog">
+                        <h3>{t(lang, "columns.title")}</h3>
+                        <ul id="columns-list" class="columns-list">
+                            {layout_columns
+                                .iter()
+                                .map(|preference| {
+                                    view! {
+                                        <li class="column-item" data-column=preference.column.code()>
+                                            <label>
+                                                <input type="checkbox" class="column-visible" checked=preference.visible />
+                                                {deal_column_label(lang, preference.column)}
+                                            </label>
+                                            <input
+                                                type="number"
+                                                class="column-width"
+                                                min="40"
+                                                max="1200"
+                                                step="10"
+                                                placeholder=t(lang, "columns.auto-width")
+                                                title=t(lang, "columns.width")
+                                                value=preference.width.map(|width| width.to_string()).unwrap_or_default()
+                                            />
+                                            <button type="button" class="column-up" title=t(lang, "columns.up")>"↑"</button>
+                                            <button type="button" class="column-down" title=t(lang, "columns.down")>"↓"</button>
+                                        </li>
+                                    }
+                                })
+                                .collect_view()}
+                        </ul>
+                        <div class="dialog-actions">
+                            <button type="submit" value="close">{t(lang, "common.close")}</button>
+                            <button type="button" id="columns-reset">{t(lang, "columns.reset")}</button>
+                            <button type="button" id="columns-save">{t(lang, "columns.save")}</button>
+                        </div>
+                    </form>
+                </dialog>
+                <dialog id="palette-dialog" class="modal-dialog palette-dialog">
+                    <form method="dialog">
+                        <input
+                            id="palette-input"
+                            type="text"
+                            autocomplete="off"
+                            placeholder=t(lang, "palette.placeholder")
+                        />
+                        <ul id="palette-list" class="palette-list" role="listbox"></ul>
+                        <p class="palette-hint">{t(lang, "palette.hint")}</p>
+                    </form>
+                </dialog>
+                <script inner_html=initial_data_script></script>
+                <script src=APP_JS.url()></script>
+            </body>
+        </html>
+    }
+}
+
+fn deal_column_label(lang: Lang, column: DealColumn) -> &'static str {
+    match column {
+        DealColumn::NumericId => "numericId",
+        DealColumn::Id => "ID",
+        DealColumn::ExternalReference => "External Reference",
+        DealColumn::Wallet => "Wallet",
+        DealColumn::Bank => t(lang, "common.bank"),
+        DealColumn::Amount => t(lang, "common.amount"),
+        DealColumn::Status => t(lang, "common.status"),
+        DealColumn::CreatedAt => t(lang, "deals.created"),
+        DealColumn::Actions => t(lang, "common.actions"),
+    }
+}
+
+fn column_width(preference: &ColumnPreference) -> Option<String> {
+    preference.width.map(|width| format!("{width}px"))
+}
+
+/// Rows of the traders table, shared by the page and `/fragments/traders-table`.
+/// `grouped` puts a header row before each team, the rows being sorted by team.
+#[component]
+fn TraderRows(
+    traders: Vec<Trader>,
+    lang: Lang,
+    empty_key: &'static str,
+    #[prop(optional)] grouped: bool,
+) -> impl IntoView {
+    if traders.is_empty() {
+        view! { <tr><td class="empty" colspan="10">{t(lang, empty_key)}</td></tr> }.into_view()
+    } else {
+        let group_starts: HashSet<String> = traders
+            .iter()
+            .enumerate()
+            .filter(|(idx, trader)| {
+                grouped
+                    && (*idx == 0
+                        || traders[idx - 1].team.as_ref().map(|team| &team.id)
+                            != trader.team.as_ref().map(|team| &team.id))
+            })
+            .map(|(_, trader)| trader.id.clone())
+            .collect();
+        view! {
+            <For
+                each=move || traders.clone()
+                key=|trader| trader.id.clone()
+                children=move |trader| {
+                    let group_header = group_starts.contains(&trader.id).then(|| {
+                        let (name, disabled) = match &trader.team {
+                            Some(team) => (team.name.clone(), !team.enabled),
+                            None => (t(lang, "traders.no-team").to_string(), false),
+                        };
+                        view! {
+                            <tr class="team-group-row">
+                                <td colspan="10">
+                                    {name}
+                                    {disabled.then(|| view! {
+                                        <span class="badge team-disabled-badge">{t(lang, "traders.team-disabled")}</span>
+                                    })}
+                                </td>
+                            </tr>
+                        }
+                    });
+                    let team_name = trader
+                        .team
+                        .as_ref()
+                        .map(|team| team.name.clone())
+                        .unwrap_or_else(|| "-".to_string());
+                    let min_value = trader
+                        .min_amount
+                        .map(|v| format!("{:.2}", v))
+                        .unwrap_or_default();
+                    let limit_value = trader
+                        .max_amount
+                        .map(|v| format!("{:.2}", v))
+                        .unwrap_or_default();
+                    let other_currencies = (!trader.currencies.is_empty()).then(|| {
+                        let list = trader
+                            .currencies
+                            .iter()
+                            .map(|entry| match entry.max_amount {
+                                Some(max) => format!("{} ≤ {:.2}", entry.currency, max),
+                                None => entry.currency.clone(),
+                            })
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        view! {
+                            <div class="trader-currencies" title=t(lang, "traders.currencies-hint")>
+                                {tf(lang, "traders.currencies", &[("list", list)])}
+                            </div>
+                        }
+                    });
+                    let open_payouts = match trader.max_open_payouts {
+                        Some(cap) => format!("{} / {}", trader.open_payouts, cap),
+                        None => trader.open_payouts.to_string(),
+                    };
+                    let away_badge = trader.unavailability.as_ref().map(|window| {
+                        let title = window
+                            .reason
+                            .clone()
+                            .unwrap_or_else(|| t(lang, "traders.away-hint").to_string());
+                        view! {
+                            <span class="badge away-badge" title=title>
+                                {t(lang, "traders.away")}" "
+                                <time class="local-time" datetime=window.starts_at.to_string()>
+                                    {format_timestamp(&window.starts_at)}
+                                </time>
+                                " – "
+                                <time class="local-time" datetime=window.ends_at.to_string()>
+                                    {format_timestamp(&window.ends_at)}
+                                </time>
+                            </span>
+                        }
+                    });
+                    let cooldown = match trader.cooldown_remaining_seconds {
+                        Some(seconds) => tf(lang, "traders.cooldown.value", &[("seconds", seconds.to_string())]),
+                        None => "-".to_string(),
+                    };
                     view! {
-                        <tr>
-                            <td>{payout.numeric_id}</td>
-                            <td>{format_amount(payout.amount)}</td>
-                            <td>{payout.bank.clone().unwrap_or_else(|| "-".to_string())}</td>
-                            <td>{payout.external_reference.clone().unwrap_or_else(|| "-".to_string())}</td>
+                        {group_header}
+                        <tr data-trader-id={trader.id.clone()} data-email={trader.email.clone()}>
+                            <td class="deal-select-cell">
+                                <input type="checkbox" class="trader-select" data-trader-id={trader.id.clone()} />
+                            </td>
+                            <td>{trader.numeric_id}</td>
+                            <td>{trader.email.clone()}{away_badge}</td>
+                            <td>{team_name}</td>
+                            <td>{format_amount(trader.balance_rub)}</td>
+                            <td>{format_amount(trader.frozen_rub)}</td>
+                            <td>{format_amount(trader.payout_balance)}</td>
+                            <td>{open_payouts}</td>
+                            <td>{cooldown}</td>
                             <td>
-                                <div class="assign-controls">
-                                    <select id={format!("assign-select-{}", payout.id)}>
-                                        <option value="">"Выберите трейдера"</option>
-                                        {options.into_view()}
-                                    </select>
-                                    <button class="assign-button" data-payout-id={payout.id.clone()}>"Привязать"</button>
+                                <div class="limit-controls" title=t(lang, "traders.no-limit")>
+                                    <input
+                                        type="number"
+                                        min="0"
+                                        step="0.01"
+                                        value=min_value
+                                        id={format!("limit-min-input-{}", trader.id)}
+                                        placeholder=t(lang, "traders.min-amount")
+                                    />
+                                    <input
+                                        type="number"
+                                        min="0"
+                                        step="0.01"
+                                        value=limit_value
+                                        id={format!("limit-input-{}", trader.id)}
+                                        placeholder=t(lang, "traders.max-amount")
+                                    />
+                                    <button class="save-limit" data-trader-id={trader.id.clone()}>{t(lang, "common.save")}</button>
                                 </div>
+                                {other_currencies}
                             </td>
                         </tr>
                     }
@@ -1251,15 +5212,26 @@ fn App(snapshot: DashboardSnapshot) -> impl IntoView {
             />
         }
         .into_view()
-    };
+    }
+}
 
-    let deals_view = if deals_items.is_empty() {
-        view! { <tr><td class="empty" colspan="9">Нет данных о выплатах</td></tr> }
+/// Rows of the deals table, shared by the page and `/fragments/deals-table`.
+#[component]
+fn DealRows(
+    deals: Vec<PayoutDealListItem>,
+    columns: Vec<ColumnPreference>,
+    lang: Lang,
+    empty_key: &'static str,
+) -> impl IntoView {
+    if deals.is_empty() {
+        // One more for the selection checkbox.
+        let colspan = (columns.len() + 1).to_string();
+        view! { <tr><td class="empty" colspan=colspan>{t(lang, empty_key)}</td></tr> }
             .into_view()
     } else {
         view! {
             <For
-                each=move || deals_items.clone()
+                each=move || deals.clone()
                 key=|deal| deal.id.clone()
                 children=move |deal| {
                     let external_reference = deal
@@ -1270,263 +5242,814 @@ fn App(snapshot: DashboardSnapshot) -> impl IntoView {
                         .cancel_reason
                         .clone()
                         .unwrap_or_else(|| "-".to_string());
-                    let disable_cancel = matches!(
-                        deal.status.as_str(),
-                        "CANCELLED" | "COMPLETED" | "SUCCESS" | "FAILED"
-                    );
-                    let created_at = format_timestamp(&deal.created_at);
-                    let amount_display = format_amount(Some(deal.amount));
+                    let disable_cancel = !deal.status.is_cancellable();
+                    let created_at = deal.created_at.to_string();
+                    let created_at_utc = format_timestamp(&deal.created_at);
+                    let amount_display = format_money(Some(deal.amount), &deal.currency);
+                    let countdown_badge = deal.acceptance_remaining_seconds.map(|seconds| {
+                        view! {
+                            <span
+                                class="badge countdown-badge"
+                                data-remaining=seconds
+                                data-state=countdown_state(seconds)
+                                title=t(lang, "deals.acceptance-countdown")
+                            >
+                                {format_countdown(lang, seconds)}
+                            </span>
+                        }
+                    });
+                    let archived_badge = deal.archived.then(|| {
+                        view! {
+                            <span class="badge archived-badge">{t(lang, "deals.archived")}</span>
+                        }
+                    });
+                    let rate_badge = deal.rate_mismatch.then(|| {
+                        let deviation = deal
+                            .rate_deviation_percent
+                            .map(|value| format!("{value:+.1}"))
+                            .unwrap_or_else(|| "-".to_string());
+                        view! {
+                            <span
+                                class="badge rate-badge"
+                                title=tf(lang, "deals.rate-mismatch", &[("deviation", deviation)])
+                            >
+                                {t(lang, "deals.rate-flag")}
+                            </span>
+                        }
+                    });
+                    let cells = columns
+                        .iter()
+                        .map(|preference| match preference.column {
+                            DealColumn::NumericId => view! { <td>{deal.numeric_id}</td> },
+                            DealColumn::Id => view! { <td><span class="mono">{deal.id.clone()}</span></td> },
+                            DealColumn::ExternalReference => view! { <td>{external_reference.clone()}</td> },
+                            DealColumn::Wallet => view! { <td>{deal.wallet.clone()}</td> },
+                            DealColumn::Bank => view! { <td>{deal.bank.clone()}</td> },
+                            DealColumn::Amount => view! { <td>{amount_display.clone()}{rate_badge.clone()}</td> },
+                            DealColumn::Status => view! {
+                                <td>{deal.status.as_str()}{countdown_badge.clone()}{archived_badge.clone()}</td>
+                            },
+                            DealColumn::CreatedAt => view! {
+                                <td>
+                                    <time class="local-time" datetime=created_at.clone() title=created_at_utc.clone()>
+                                        {created_at_utc.clone()}
+                                    </time>
+                                </td>
+                            },
+                            DealColumn::Actions => view! {
+                                <td>
+                                    <div class="deal-actions">
+                                        <span class="deal-reason">{cancel_reason.clone()}</span>
+                                        <button
+                                            class="danger cancel-deal"
+                                            data-deal-id={deal.id.clone()}
+                                            disabled=disable_cancel
+                                            title=if disable_cancel {
+                                                t(lang, "deals.cancel-unavailable")
+                                            } else {
+                                                t(lang, "deals.cancel-title")
+                                            }
+                                            type="button"
+                                        >{t(lang, "deals.cancel")}</button>
+                                        <button class="deal-files" data-deal-id={deal.id.clone()} type="button">
+                                            {t(lang, "deals.files")}
+                                        </button>
+                                        <button class="deal-timeline" data-deal-id={deal.id.clone()} type="button">
+                                            {t(lang, "deals.timeline")}
+                                        </button>
+                                    </div>
+                                </td>
+                            },
+                        })
+                        .collect_view();
                     view! {
-                        <tr>
-                            <td>{deal.numeric_id}</td>
-                            <td><span class="mono">{deal.id.clone()}</span></td>
-                            <td>{external_reference}</td>
-                            <td>{deal.wallet.clone()}</td>
-                            <td>{deal.bank.clone()}</td>
-                            <td>{amount_display}</td>
-                            <td>{deal.status.clone()}</td>
-                            <td>{created_at}</td>
-                            <td>
-                                <div class="deal-actions">
-                                    <span class="deal-reason">{cancel_reason}</span>
-                                    <button
-                                        class="danger cancel-deal"
-                                        data-deal-id={deal.id.clone()}
-                                        disabled=disable_cancel
-                                        type="button"
-                                    >"Отменить"</button>
-                                </div>
+                        <tr
+                            data-deal-row={deal.id.clone()}
+                            data-status={deal.status.as_str()}
+                            data-numeric-id={deal.numeric_id}
+                            data-updated-at={deal.updated_at.to_string()}
+                        >
+                            <td class="deal-select-cell">
+                                <input
+                                    type="checkbox"
+                                    class="deal-select"
+                                    data-deal-id={deal.id.clone()}
+                                    disabled=disable_cancel
+                                />
                             </td>
+                            {cells}
                         </tr>
                     }
                 }
             />
         }
         .into_view()
-    };
+    }
+}
 
-    let initial_data_script = format!("window.__INITIAL_DASHBOARD__ = {};", initial_json);
-    let dashboard_script = DASHBOARD_SCRIPT.to_string();
+pub(crate) fn render_trader_rows(
+    traders: Vec<Trader>,
+    lang: Lang,
+    empty_key: &'static str,
+    grouped: bool,
+) -> String {
+    leptos::ssr::render_to_string(move || {
+        view! { <TraderRows traders=traders.clone() lang=lang empty_key=empty_key grouped=grouped /> }
+    })
+    .to_string()
+}
 
-    let badge_state = if settings.enabled { "on" } else { "off" };
-    let badge_text = if settings.enabled {
-        "Активно"
-    } else {
-        "Выключено"
-    };
+pub(crate) fn render_deal_rows(
+    deals: Vec<PayoutDealListItem>,
+    layout: DealsTableLayout,
+    lang: Lang,
+    empty_key: &'static str,
+) -> String {
+    let columns = layout.visible_columns();
+    leptos::ssr::render_to_string(move || {
+        view! { <DealRows deals=deals.clone() columns=columns.clone() lang=lang empty_key=empty_key /> }
+    })
+    .to_string()
+}
 
+pub(crate) fn render_dashboard_page(
+    snapshot: DashboardSnapshot,
+    lang: Lang,
+    theme: Theme,
+) -> String {
+    let html = leptos::ssr::render_to_string(move || {
+        view! { <App snapshot=snapshot.clone() lang=lang theme=theme /> }
+    });
+    format!("<!DOCTYPE html>{html}")
+}
+
+#[component]
+fn LoginPage(lang: Lang, theme: Theme, csrf_token: String, error: Option<&'static str>) -> impl IntoView {
     view! {
-        <html lang="ru">
+        <html lang=lang.code() data-theme=theme.code()>
             <head>
                 <meta charset="UTF-8" />
                 <title>Chase Linker Dashboard</title>
-                <style>{STYLES}</style>
+                <link rel="stylesheet" href=APP_CSS.url() />
+            </head>
+            <body>
+                <main class="login-main">
+                    <form class="panel login-panel" method="post" action="/login">
+                        <div class="panel-header">
+                            <h2>{t(lang, "login.title")}</h2>
+                        </div>
+                        {error.map(|key| view! { <p class="login-error" role="alert">{t(lang, key)}</p> })}
+                        <input type="hidden" name="csrf" value=csrf_token />
+                        <div class="input-control">
+                            <label for="login-username">{t(lang, "login.username")}</label>
+                            <input id="login-username" name="username" type="text" autocomplete="username" required=true autofocus=true />
+                        </div>
+                        <div class="input-control">
+                            <label for="login-password">{t(lang, "login.password")}</label>
+                            <input id="login-password" name="password" type="password" autocomplete="current-password" required=true />
+                        </div>
+                        <button type="submit">{t(lang, "login.submit")}</button>
+                    </form>
+                </main>
+            </body>
+        </html>
+    }
+}
+
+pub(crate) fn render_login_page(
+    lang: Lang,
+    theme: Theme,
+    csrf_token: &str,
+    error: Option<&'static str>,
+) -> String {
+    let csrf_token = csrf_token.to_string();
+    let html = leptos::ssr::render_to_string(move || {
+        view! { <LoginPage lang=lang theme=theme csrf_token=csrf_token.clone() error=error /> }
+    });
+    format!("<!DOCTYPE html>{html}")
+}
+
+/// Payouts of the shift report's open lists, disputes or stuck payouts.
+#[component]
+fn ReportPayoutRows(payouts: Vec<ReportPayout>, lang: Lang, dispute: bool) -> impl IntoView {
+    if payouts.is_empty() {
+        return view! { <tr><td class="empty" colspan="5">{t(lang, "report.none")}</td></tr> }
+            .into_view();
+    }
+    payouts
+        .into_iter()
+        .map(|payout| {
+            let detail = if dispute {
+                payout.dispute_message.clone().unwrap_or_else(|| "-".to_string())
+            } else {
+                payout.trader_id.clone().unwrap_or_else(|| t(lang, "report.unassigned").to_string())
+            };
+            view! {
+                <tr>
+                    <td>{payout.numeric_id}</td>
+                    <td>{format_money(payout.amount, &payout.currency)}</td>
+                    <td>{payout.merchant_id.clone().unwrap_or_else(|| "-".to_string())}</td>
+                    <td>{detail}</td>
+                    <td>{format_timestamp(if dispute { &payout.updated_at } else { &payout.created_at })}</td>
+                </tr>
+            }
+        })
+        .collect_view()
+}
+
+#[component]
+fn ShiftReportPage(report: ShiftReport, lang: Lang, theme: Theme) -> impl IntoView {
+    let period = tf(
+        lang,
+        "report.period",
+        &[
+            ("from", format_timestamp(&report.from)),
+            ("to", format_timestamp(&report.to)),
+        ],
+    );
+    let more = move |total: i64, shown: usize| {
+        (total > shown as i64).then(|| {
+            view! {
+                <p class="panel-subtitle">
+                    {tf(lang, "report.more", &[("count", (total - shown as i64).to_string())])}
+                </p>
+            }
+        })
+    };
+    let assignments = report.assignments.clone();
+    let cancellations = report.cancellations.clone();
+    let failed_more = more(report.failed_callbacks.total, report.failed_callbacks.items.len());
+    let disputes_more = more(report.disputes.total, report.disputes.items.len());
+    let stuck_more = more(report.stuck.total, report.stuck.items.len());
+    let notes_more = more(report.notes.total, report.notes.items.len());
+    view! {
+        <html lang=lang.code() data-theme=theme.code()>
+            <head>
+                <meta charset="UTF-8" />
+                <title>{t(lang, "report.title")}</title>
+                <link rel="stylesheet" href=APP_CSS.url() />
             </head>
             <body>
                 <header class="top-bar">
                     <div>
-                        <h1>Распределение выплат</h1>
-                        <p>Управляйте автораспределением и следите за очередью выплат в реальном времени.</p>
+                        <h1>{t(lang, "report.title")}</h1>
+                        <p>{period}</p>
                     </div>
                     <div class="status-block">
-                        <span class="status-label">Обновлено</span>
-                        <span class="status-value" id="last-updated">-</span>
+                        <span class="status-label">{t(lang, "report.generated")}</span>
+                        <span class="status-value">{format_timestamp(&report.generated_at)}</span>
                     </div>
                 </header>
-                <main>
-                    <div id="global-status" class="status-banner" role="status"></div>
-                    <section class="metrics-grid">
-                        <article class="metric-card">
-                            <span class="metric-label">Активные трейдеры</span>
-                            <span class="metric-value" id="metric-traders">{metrics_traders}</span>
-                            <span class="metric-sub">Количество трейдеров, готовых принять выплаты</span>
-                        </article>
-                        <article class="metric-card">
-                            <span class="metric-label">Нераспределенных выплат</span>
-                            <span class="metric-value" id="metric-payouts">{metrics_payouts}</span>
-                            <span class="metric-sub">Текущая очередь выплат без исполнителя</span>
-                        </article>
-                        <article class="metric-card">
-                            <span class="metric-label">Сумма к распределению</span>
-                            <span class="metric-value" id="metric-payout-sum">{total_payout_display}</span>
-                            <span class="metric-sub">Совокупный объем ожидающих выплат</span>
-                        </article>
+                <main class="report-main">
+                    <section class="panel">
+                        <div class="panel-header">
+                            <h2>{t(lang, "report.assignments")}</h2>
+                            <span class="panel-subtitle">
+                                {tf(
+                                    lang,
+                                    "report.assignments-total",
+                                    &[
+                                        ("count", assignments.total.to_string()),
+                                        ("amount", format_amount(Some(assignments.amount))),
+                                        ("automatic", assignments.automatic.to_string()),
+                                    ],
+                                )}
+                            </span>
+                        </div>
+                        <table>
+                            <thead>
+                                <tr>
+                                    <th>{t(lang, "report.trader")}</th>
+                                    <th>{t(lang, "report.count")}</th>
+                                    <th>{t(lang, "common.amount")}</th>
+                                    <th>{t(lang, "report.automatic")}</th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {if assignments.traders.is_empty() {
+                                    view! { <tr><td class="empty" colspan="4">{t(lang, "report.none")}</td></tr> }.into_view()
+                                } else {
+                                    assignments
+                                        .traders
+                                        .into_iter()
+                                        .map(|trader| view! {
+                                            <tr>
+                                                <td>{trader.email.or(trader.trader_id).unwrap_or_else(|| "-".to_string())}</td>
+                                                <td>{trader.count}</td>
+                                                <td>{format_amount(Some(trader.amount))}</td>
+                                                <td>{trader.automatic}</td>
+                                            </tr>
+                                        })
+                                        .collect_view()
+                                }}
+                            </tbody>
+                        </table>
                     </section>
 
                     <section class="panel">
                         <div class="panel-header">
-                            <div>
-                                <h2>Настройки автоматического распределения</h2>
-                                <p id="settings-description" class="panel-subtitle">{settings_description}</p>
-                            </div>
-                            <span id="auto-status-badge" class="badge" data-state=badge_state>{badge_text}</span>
-                        </div>
-                        <div class="controls-row">
-                            <label>
-                                <input type="checkbox" id="auto-enabled" checked=settings.enabled />
-                                " Включить распределение"
-                            </label>
-                            <label>
-                                "Интервал (сек):"
-                                <input
-                                    type="number"
-                                    id="auto-interval"
-                                    min="1"
-                                    value={settings.interval_seconds.max(1).to_string()}
-                                />
-                            </label>
-                            <button id="save-settings">Сохранить</button>
+                            <h2>{t(lang, "report.cancellations")}</h2>
+                            <span class="panel-subtitle">
+                                {tf(
+                                    lang,
+                                    "report.cancellations-total",
+                                    &[
+                                        ("count", cancellations.total.to_string()),
+                                        ("amount", format_amount(Some(cancellations.amount))),
+                                    ],
+                                )}
+                            </span>
                         </div>
+                        <table>
+                            <thead>
+                                <tr>
+                                    <th>{t(lang, "report.reason")}</th>
+                                    <th>{t(lang, "report.count")}</th>
+                                    <th>{t(lang, "common.amount")}</th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {if cancellations.reasons.is_empty() {
+                                    view! { <tr><td class="empty" colspan="3">{t(lang, "report.none")}</td></tr> }.into_view()
+                                } else {
+                                    cancellations
+                                        .reasons
+                                        .into_iter()
+                                        .map(|reason| {
+                                            let label = match (reason.label, reason.code) {
+                                                (Some(label), _) => label,
+                                                (None, Some(code)) => code,
+                                                (None, None) => t(lang, "report.no-reason").to_string(),
+                                            };
+                                            view! {
+                                                <tr>
+                                                    <td>{label}</td>
+                                                    <td>{reason.count}</td>
+                                                    <td>{format_amount(Some(reason.amount))}</td>
+                                                </tr>
+                                            }
+                                        })
+                                        .collect_view()
+                                }}
+                            </tbody>
+                        </table>
                     </section>
 
                     <section class="panel">
                         <div class="panel-header">
-                            <h2>Доступные трейдеры</h2>
-                        </div>
-                        <div class="table-wrapper">
-                            <table id="traders-table">
-                                <thead>
-                                    <tr>
-                                        <th>numericId</th>
-                                        <th>Email</th>
-                                        <th>Рублевый баланс</th>
-                                        <th>Заморожено RUB</th>
-                                        <th>Payout баланс</th>
-                                        <th>Макс сумма</th>
-                                    </tr>
-                                </thead>
-                                <tbody>{traders_view}</tbody>
-                            </table>
+                            <h2>{t(lang, "report.failed-callbacks")}</h2>
+                            <span class="panel-subtitle">{report.failed_callbacks.total}</span>
                         </div>
+                        <table>
+                            <thead>
+                                <tr>
+                                    <th>{t(lang, "report.payout")}</th>
+                                    <th>{t(lang, "report.merchant")}</th>
+                                    <th>{t(lang, "report.event")}</th>
+                                    <th>{t(lang, "report.error")}</th>
+                                    <th>{t(lang, "report.failed-at")}</th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {if report.failed_callbacks.items.is_empty() {
+                                    view! { <tr><td class="empty" colspan="5">{t(lang, "report.none")}</td></tr> }.into_view()
+                                } else {
+                                    report
+                                        .failed_callbacks
+                                        .items
+                                        .into_iter()
+                                        .map(|callback| view! {
+                                            <tr>
+                                                <td class="mono">{callback.payout_id.unwrap_or_else(|| "-".to_string())}</td>
+                                                <td>{callback.merchant_id.unwrap_or_else(|| "-".to_string())}</td>
+                                                <td>{callback.event.unwrap_or_else(|| "-".to_string())}</td>
+                                                <td>{callback.last_error.unwrap_or_else(|| "-".to_string())}</td>
+                                                <td>{callback.failed_at.as_ref().map(format_timestamp).unwrap_or_else(|| "-".to_string())}</td>
+                                            </tr>
+                                        })
+                                        .collect_view()
+                                }}
+                            </tbody>
+                        </table>
+                        {failed_more}
                     </section>
 
                     <section class="panel">
                         <div class="panel-header">
-                            <h2>Нераспределенные выплаты</h2>
+                            <h2>{t(lang, "report.disputes")}</h2>
+                            <span class="panel-subtitle">{report.disputes.total}</span>
                         </div>
-                        <div class="table-wrapper">
-                            <table id="payouts-table">
-                                <thead>
-                                    <tr>
-                                        <th>numericId</th>
-                                        <th>Сумма</th>
-                                        <th>Банк</th>
-                                        <th>External Reference</th>
-                                        <th>Действия</th>
-                                    </tr>
-                                </thead>
-                                <tbody>{payouts_view}</tbody>
-                            </table>
+                        <table>
+                            <thead>
+                                <tr>
+                                    <th>numericId</th>
+                                    <th>{t(lang, "common.amount")}</th>
+                                    <th>{t(lang, "report.merchant")}</th>
+                                    <th>{t(lang, "report.dispute-message")}</th>
+                                    <th>{t(lang, "report.updated-at")}</th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                <ReportPayoutRows payouts=report.disputes.items lang=lang dispute=true />
+                            </tbody>
+                        </table>
+                        {disputes_more}
+                    </section>
+
+                    <section class="panel">
+                        <div class="panel-header">
+                            <h2>{tf(lang, "report.stuck", &[("minutes", report.stuck_minutes.to_string())])}</h2>
+                            <span class="panel-subtitle">{report.stuck.total}</span>
                         </div>
+                        <table>
+                            <thead>
+                                <tr>
+                                    <th>numericId</th>
+                                    <th>{t(lang, "common.amount")}</th>
+                                    <th>{t(lang, "report.merchant")}</th>
+                                    <th>{t(lang, "report.trader")}</th>
+                                    <th>{t(lang, "report.created-at")}</th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                <ReportPayoutRows payouts=report.stuck.items lang=lang dispute=false />
+                            </tbody>
+                        </table>
+                        {stuck_more}
                     </section>
 
                     <section class="panel">
                         <div class="panel-header">
-                            <h2>Все выплаты</h2>
+                            <h2>{t(lang, "report.notes")}</h2>
+                            <span class="panel-subtitle">{report.notes.total}</span>
                         </div>
-                        <div class="filters-grid">
-                            <div class="input-control">
-                                <label for="deals-search">Поиск</label>
-                                <input
-                                    id="deals-search"
-                                    type="text"
-                                    placeholder="numericId / externalRef / id"
-                                    value=""
-                                />
-                            </div>
-                            <div class="input-control">
-                                <label for="deals-wallet">Кошелек</label>
-                                <input
-                                    id="deals-wallet"
-                                    type="text"
-                                    placeholder="Номер кошелька"
-                                    value=""
-                                />
-                            </div>
-                            <div class="input-control">
-                                <label for="deals-amount">Сумма</label>
-                                <input
-                                    id="deals-amount"
-                                    type="number"
-                                    step="0.01"
-                                    min="0"
-                                    placeholder="Сумма"
-                                    value=""
-                                />
-                            </div>
-                            <div class="input-control">
-                                <label for="deals-status">Статус</label>
-                                <select id="deals-status">
-                                    <option value="">Все</option>
-                                    <option value="CREATED">CREATED</option>
-                                    <option value="ACTIVE">ACTIVE</option>
-                                    <option value="AVAILABLE">AVAILABLE</option>
-                                    <option value="CHECKING">CHECKING</option>
-                                    <option value="PROCESSING">PROCESSING</option>
-                                    <option value="CANCELLED">CANCELLED</option>
-                                    <option value="COMPLETED">COMPLETED</option>
-                                    <option value="SUCCESS">SUCCESS</option>
-                                    <option value="FAILED">FAILED</option>
-                                    <option value="DISPUTED">DISPUTED</option>
-                                    <option value="EXPIRED">EXPIRED</option>
-                                    <option value="DISPUTE">DISPUTE</option>
-                                </select>
-                            </div>
-                            <div class="input-control">
-                                <label for="deals-per-page">На странице</label>
-                                <select id="deals-per-page">
-                                    <option value="25" selected={deals_pagination.per_page == 25}>25</option>
-                                    <option value="50" selected={deals_pagination.per_page == 50}>50</option>
-                                    <option value="100" selected={deals_pagination.per_page == 100}>100</option>
-                                </select>
-                            </div>
+                        <table>
+                            <thead>
+                                <tr>
+                                    <th>numericId</th>
+                                    <th>{t(lang, "report.author")}</th>
+                                    <th>{t(lang, "report.note")}</th>
+                                    <th>{t(lang, "report.created-at")}</th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {if report.notes.items.is_empty() {
+                                    view! { <tr><td class="empty" colspan="4">{t(lang, "report.none")}</td></tr> }.into_view()
+                                } else {
+                                    report
+                                        .notes
+                                        .items
+                                        .into_iter()
+                                        .map(|note| view! {
+                                            <tr>
+                                                <td>{note.numeric_id}</td>
+                                                <td>{note.author.unwrap_or_else(|| "-".to_string())}</td>
+                                                <td class="report-note">{note.body}</td>
+                                                <td>{format_timestamp(&note.created_at)}</td>
+                                            </tr>
+                                        })
+                                        .collect_view()
+                                }}
+                            </tbody>
+                        </table>
+                        {notes_more}
+                    </section>
+                </main>
+            </body>
+        </html>
+    }
+}
+
+pub(crate) fn render_shift_report_page(report: ShiftReport, lang: Lang, theme: Theme) -> String {
+    let html = leptos::ssr::render_to_string(move || {
+        view! { <ShiftReportPage report=report.clone() lang=lang theme=theme /> }
+    });
+    format!("<!DOCTYPE html>{html}")
+}
+
+#[component]
+fn TraderStatusPage(page: TraderPage, lang: Lang, theme: Theme) -> impl IntoView {
+    let subtitle = format!("{} (ID: {})", page.email, page.numeric_id);
+    let open_payouts = match page.max_open_payouts {
+        Some(cap) => format!("{} / {cap}", page.assignments.len()),
+        None => page.assignments.len().to_string(),
+    };
+    let amount_range = match (page.min_amount, page.max_amount) {
+        (None, None) => t(lang, "trader-page.unlimited").to_string(),
+        (min, max) => format!("{} – {}", format_amount(min), format_amount(max)),
+    };
+    let currencies = if page.currencies.is_empty() {
+        currencies::BASE_CURRENCY.to_string()
+    } else {
+        std::iter::once(currencies::BASE_CURRENCY.to_string())
+            .chain(
+                page.currencies
+                    .iter()
+                    .map(|currency| match currency.max_amount {
+                        Some(max) => format!("{} (≤ {max:.2})", currency.currency),
+                        None => currency.currency.clone(),
+                    }),
+            )
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let receiving = page.traffic_enabled && !page.paused;
+    let state_text = if !page.traffic_enabled {
+        t(lang, "trader-page.traffic-off")
+    } else if page.paused {
+        t(lang, "trader-page.paused")
+    } else {
+        t(lang, "trader-page.receiving")
+    };
+    let cooldown = page
+        .cooldown_remaining_seconds
+        .filter(|seconds| *seconds > 0)
+        .map(|seconds| {
+            tf(
+                lang,
+                "trader-page.cooldown",
+                &[("seconds", seconds.to_string())],
+            )
+        });
+    let away = page.unavailability.as_ref().map(|window| {
+        tf(
+            lang,
+            "trader-page.away",
+            &[
+                ("from", format_timestamp(&window.starts_at)),
+                ("to", format_timestamp(&window.ends_at)),
+            ],
+        )
+    });
+    let assignments = page.assignments.clone();
+    view! {
+        <html lang=lang.code() data-theme=theme.code()>
+            <head>
+                <meta charset="UTF-8" />
+                <meta http-equiv="refresh" content="60" />
+                <meta name="referrer" content="no-referrer" />
+                <title>{t(lang, "trader-page.title")}</title>
+                <link rel="stylesheet" href=APP_CSS.url() />
+            </head>
+            <body>
+                <header class="top-bar">
+                    <div>
+                        <h1>{t(lang, "trader-page.title")}</h1>
+                        <p>{subtitle}</p>
+                    </div>
+                    <div class="status-block">
+                        <span class="status-label">{t(lang, "report.generated")}</span>
+                        <span class="status-value">{format_timestamp(&page.generated_at)}</span>
+                    </div>
+                </header>
+                <main class="report-main">
+                    <section class="panel">
+                        <div class="panel-header">
+                            <h2>{t(lang, "trader-page.state")}</h2>
+                            <span class="badge" data-state=if receiving { "on" } else { "off" }>
+                                {state_text}
+                            </span>
                         </div>
-                        <div class="deals-toolbar">
-                            <button id="deals-sort-status" type="button">Сортировка по статусу</button>
-                            <button id="deals-reset" type="button">Сбросить фильтры</button>
+                        {cooldown.map(|text| view! { <p class="panel-subtitle">{text}</p> })}
+                        {away.map(|text| view! { <p class="panel-subtitle">{text}</p> })}
+                        <table>
+                            <tbody>
+                                <tr>
+                                    <th>{t(lang, "trader-page.open-payouts")}</th>
+                                    <td>{open_payouts}</td>
+                                </tr>
+                                <tr>
+                                    <th>{t(lang, "trader-page.amount-range")}</th>
+                                    <td>{amount_range}</td>
+                                </tr>
+                                <tr>
+                                    <th>{t(lang, "trader-page.currencies")}</th>
+                                    <td>{currencies}</td>
+                                </tr>
+                                <tr>
+                                    <th>{t(lang, "traders.balance")}</th>
+                                    <td>{format_amount(page.balance_rub)}</td>
+                                </tr>
+                            </tbody>
+                        </table>
+                    </section>
+
+                    <section class="panel">
+                        <div class="panel-header">
+                            <h2>{t(lang, "trader-page.assignments")}</h2>
+                            <span class="panel-subtitle">{assignments.len()}</span>
                         </div>
-                        <div class="table-wrapper">
-                            <table id="deals-table">
-                                <thead>
-                                    <tr>
-                                        <th>numericId</th>
-                                        <th>ID</th>
-                                        <th>External Reference</th>
-                                        <th>Wallet</th>
-                                        <th>Банк</th>
-                                        <th>Сумма</th>
-                                        <th>Статус</th>
-                                        <th>Создана</th>
-                                        <th>Действия</th>
-                                    </tr>
-                                </thead>
-                                <tbody>{deals_view}</tbody>
-                            </table>
+                        <table>
+                            <thead>
+                                <tr>
+                                    <th>numericId</th>
+                                    <th>{t(lang, "common.amount")}</th>
+                                    <th>{t(lang, "common.bank")}</th>
+                                    <th>{t(lang, "trader-page.wallet")}</th>
+                                    <th>{t(lang, "common.status")}</th>
+                                    <th>{t(lang, "report.created-at")}</th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {if assignments.is_empty() {
+                                    view! { <tr><td class="empty" colspan="6">{t(lang, "trader-page.none")}</td></tr> }.into_view()
+                                } else {
+                                    assignments
+                                        .into_iter()
+                                        .map(|payout| view! {
+                                            <tr>
+                                                <td>{payout.numeric_id}</td>
+                                                <td>{format_money(Some(payout.amount), &payout.currency)}</td>
+                                                <td>{payout.bank}</td>
+                                                <td class="mono">{payout.wallet}</td>
+                                                <td>{payout.status.as_str()}</td>
+                                                <td>{format_timestamp(&payout.created_at)}</td>
+                                            </tr>
+                                        })
+                                        .collect_view()
+                                }}
+                            </tbody>
+                        </table>
+                    </section>
+                </main>
+            </body>
+        </html>
+    }
+}
+
+pub(crate) fn render_trader_page(page: TraderPage, lang: Lang, theme: Theme) -> String {
+    let html = leptos::ssr::render_to_string(move || {
+        view! { <TraderStatusPage page=page.clone() lang=lang theme=theme /> }
+    });
+    format!("<!DOCTYPE html>{html}")
+}
+
+#[component]
+fn MerchantStatusPage(page: MerchantPage, lang: Lang, theme: Theme) -> impl IntoView {
+    let subtitle = match &page.name {
+        Some(name) => format!("{name} ({})", page.merchant_id),
+        None => page.merchant_id.clone(),
+    };
+    let healthy = page.callbacks_dead_lettered == 0
+        && !page.sla_windows.iter().any(|window| window.below_threshold);
+    let shown = tf(
+        lang,
+        "merchant-page.shown",
+        &[
+            ("shown", page.payouts.len().to_string()),
+            ("total", page.total_payouts.to_string()),
+        ],
+    );
+    let retrying = tf(
+        lang,
+        "merchant-page.retrying",
+        &[("count", page.callbacks_retrying.to_string())],
+    );
+    let dead_lettered = tf(
+        lang,
+        "merchant-page.dead-lettered",
+        &[("count", page.callbacks_dead_lettered.to_string())],
+    );
+    let windows = page.sla_windows.clone();
+    let payouts = page.payouts.clone();
+    view! {
+        <html lang=lang.code() data-theme=theme.code()>
+            <head>
+                <meta charset="UTF-8" />
+                <meta http-equiv="refresh" content="60" />
+                <meta name="referrer" content="no-referrer" />
+                <title>{t(lang, "merchant-page.title")}</title>
+                <link rel="stylesheet" href=APP_CSS.url() />
+            </head>
+            <body>
+                <header class="top-bar">
+                    <div>
+                        <h1>{t(lang, "merchant-page.title")}</h1>
+                        <p>{subtitle}</p>
+                    </div>
+                    <div class="status-block">
+                        <span class="status-label">{t(lang, "report.generated")}</span>
+                        <span class="status-value">{format_timestamp(&page.generated_at)}</span>
+                    </div>
+                </header>
+                <main class="report-main">
+                    <section class="panel">
+                        <div class="panel-header">
+                            <h2>{t(lang, "merchant-page.callbacks")}</h2>
+                            <span class="badge" data-state=if healthy { "on" } else { "off" }>
+                                {t(lang, if healthy { "merchant-page.healthy" } else { "merchant-page.degraded" })}
+                            </span>
                         </div>
-                        <div class="deals-pagination">
-                            <span id="deals-page-info">{deals_page_info.clone()}</span>
-                            <button id="deals-prev" type="button" disabled={deals_pagination.page <= 1}>"Назад"</button>
-                            <button
-                                id="deals-next"
-                                type="button"
-                                disabled={deals_pagination.total_pages == 0
-                                    || deals_pagination.page >= deals_pagination.total_pages}
-                            >"Вперед"</button>
+                        <p class="panel-subtitle">{retrying}</p>
+                        <p class="panel-subtitle">{dead_lettered}</p>
+                        <table>
+                            <thead>
+                                <tr>
+                                    <th>{t(lang, "merchant-page.window")}</th>
+                                    <th>{t(lang, "merchant-page.attempts")}</th>
+                                    <th>{t(lang, "merchant-page.delivered")}</th>
+                                    <th>{t(lang, "merchant-page.p95")}</th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {windows
+                                    .into_iter()
+                                    .map(|window| {
+                                        let delivered = match window.success_rate {
+                                            Some(rate) => view! {
+                                                <span class="badge" data-state=if window.below_threshold { "off" } else { "on" }>
+                                                    {format!("{} ({rate:.1}%)", window.delivered)}
+                                                </span>
+                                            }
+                                            .into_view(),
+                                            None => "-".into_view(),
+                                        };
+                                        let latency = window
+                                            .p95_latency_ms
+                                            .map(|ms| format!("{:.1} s", ms / 1000.0))
+                                            .unwrap_or_else(|| "-".to_string());
+                                        view! {
+                                            <tr>
+                                                <td>{format_window(lang, window.window_minutes)}</td>
+                                                <td>{window.attempts}</td>
+                                                <td>{delivered}</td>
+                                                <td>{latency}</td>
+                                            </tr>
+                                        }
+                                    })
+                                    .collect_view()}
+                            </tbody>
+                        </table>
+                    </section>
+
+                    <section class="panel">
+                        <div class="panel-header">
+                            <h2>{t(lang, "merchant-page.payouts")}</h2>
+                            <span class="panel-subtitle">{shown}</span>
                         </div>
+                        <table>
+                            <thead>
+                                <tr>
+                                    <th>numericId</th>
+                                    <th>{t(lang, "merchant-page.reference")}</th>
+                                    <th>{t(lang, "common.amount")}</th>
+                                    <th>{t(lang, "common.status")}</th>
+                                    <th>{t(lang, "merchant-page.reason")}</th>
+                                    <th>{t(lang, "report.created-at")}</th>
+                                    <th>{t(lang, "report.updated-at")}</th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {if payouts.is_empty() {
+                                    view! { <tr><td class="empty" colspan="7">{t(lang, "merchant-page.none")}</td></tr> }.into_view()
+                                } else {
+                                    payouts
+                                        .into_iter()
+                                        .map(|payout| view! {
+                                            <tr>
+                                                <td>{payout.numeric_id}</td>
+                                                <td class="mono">{payout.external_reference.unwrap_or_else(|| "-".to_string())}</td>
+                                                <td>{format_money(Some(payout.amount), &payout.currency)}</td>
+                                                <td>{payout.status.callback_name()}</td>
+                                                <td>{payout.cancel_reason.unwrap_or_default()}</td>
+                                                <td>{format_timestamp(&payout.created_at)}</td>
+                                                <td>{format_timestamp(&payout.updated_at)}</td>
+                                            </tr>
+                                        })
+                                        .collect_view()
+                                }}
+                            </tbody>
+                        </table>
                     </section>
                 </main>
-                <script inner_html=initial_data_script></script>
-                <script inner_html=dashboard_script></script>
             </body>
         </html>
     }
 }
 
-pub(crate) fn render_dashboard_page(snapshot: DashboardSnapshot) -> String {
-    let html = leptos::ssr::render_to_string(move || view! { <App snapshot=snapshot.clone() /> });
+pub(crate) fn render_merchant_page(page: MerchantPage, lang: Lang, theme: Theme) -> String {
+    let html = leptos::ssr::render_to_string(move || {
+        view! { <MerchantStatusPage page=page.clone() lang=lang theme=theme /> }
+    });
     format!("<!DOCTYPE html>{html}")
 }
 
+fn format_window(lang: Lang, minutes: i32) -> String {
+    if minutes % 60 == 0 {
+        tf(
+            lang,
+            "merchant-page.window-hours",
+            &[("hours", (minutes / 60).to_string())],
+        )
+    } else {
+        tf(
+            lang,
+            "merchant-page.window-minutes",
+            &[("minutes", minutes.to_string())],
+        )
+    }
+}
+
 fn format_amount(value: Option<f64>) -> String {
     match value {
         Some(v) => format!("{:.2}", v),
@@ -1534,6 +6057,51 @@ fn format_amount(value: Option<f64>) -> String {
     }
 }
 
-fn format_timestamp(value: &NaiveDateTime) -> String {
-    value.format("%Y-%m-%d %H:%M:%S").to_string()
+/// Like [`format_amount`], with the currency code unless it is RUB.
+fn format_money(value: Option<f64>, currency: &str) -> String {
+    match value {
+        Some(_) if !currencies::is_base(currency) => {
+            format!("{} {currency}", format_amount(value))
+        }
+        _ => format_amount(value),
+    }
+}
+
+fn page_info(lang: Lang, pagination: &Pagination) -> String {
+    let (page, pages) = if pagination.total_pages == 0 {
+        (0, 0)
+    } else {
+        (pagination.page, pagination.total_pages)
+    };
+    tf(
+        lang,
+        "pagination.page-info",
+        &[
+            ("page", page.to_string()),
+            ("pages", pages.to_string()),
+            ("total", pagination.total.to_string()),
+        ],
+    )
+}
+
+/// Assignments left unaccepted for less than this are flagged as urgent.
+const COUNTDOWN_URGENT_SECONDS: i64 = 300;
+
+fn countdown_state(seconds: i64) -> &'static str {
+    match seconds {
+        ..=0 => "overdue",
+        1..COUNTDOWN_URGENT_SECONDS => "urgent",
+        _ => "pending",
+    }
+}
+
+fn format_countdown(lang: Lang, seconds: i64) -> String {
+    if seconds <= 0 {
+        return t(lang, "deals.acceptance-overdue").to_string();
+    }
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn format_timestamp(value: &UtcTimestamp) -> String {
+    value.format("%Y-%m-%d %H:%M:%S UTC").to_string()
 }
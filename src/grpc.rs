@@ -0,0 +1,325 @@
+//! gRPC mirror of the REST API for internal orchestrators, built with
+//! `--features grpc` and served on `GRPC_PORT` (default 50051). The contract
+//! lives in `proto/payouts.proto`; callers authenticate with the same tenant
+//! token as the REST API, sent as `authorization: Bearer …` metadata. gRPC
+//! has no operator login, so with `OPERATOR_LOGIN` on the server only starts
+//! when `TENANTS` is set and every call needs a tenant token.
+
+use std::{net::SocketAddr, pin::Pin};
+
+use anyhow::{Context, Result};
+use axum::http::StatusCode;
+use tokio_stream::{Stream, StreamExt, wrappers::BroadcastStream};
+use tonic::{Request, Response, Status, metadata::MetadataMap};
+
+use crate::{
+    AppState,
+    api::{
+        CancelPayoutRequest as RestCancelRequest, PayoutListQuery, cancel_payout_internal,
+        ensure_trader_in_scope, list_deals_internal,
+    },
+    auth,
+    distribution::assign_payout_internal,
+    errors::ApiError,
+    tenant::TenantScope,
+};
+
+mod generated {
+    include!(concat!(env!("OUT_DIR"), "/chase.payouts.v1.Payouts.rs"));
+}
+
+use generated::payouts_server::{Payouts, PayoutsServer};
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct AssignPayoutRequest {
+    #[prost(string, tag = "1")]
+    pub payout_id: String,
+    #[prost(string, tag = "2")]
+    pub trader_id: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct AssignPayoutReply {
+    #[prost(bool, tag = "1")]
+    pub success: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct CancelPayoutRequest {
+    #[prost(string, tag = "1")]
+    pub payout_id: String,
+    #[prost(string, optional, tag = "2")]
+    pub reason: Option<String>,
+    #[prost(string, optional, tag = "3")]
+    pub reason_code: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct CancelPayoutReply {
+    #[prost(bool, tag = "1")]
+    pub success: bool,
+    #[prost(string, tag = "2")]
+    pub status: String,
+    #[prost(bool, tag = "3")]
+    pub callback_dispatched: bool,
+    #[prost(string, optional, tag = "4")]
+    pub callback_error: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct ListDealsRequest {
+    #[prost(string, optional, tag = "1")]
+    pub search: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    pub wallet: Option<String>,
+    #[prost(double, optional, tag = "3")]
+    pub amount: Option<f64>,
+    #[prost(string, optional, tag = "4")]
+    pub status: Option<String>,
+    #[prost(uint32, optional, tag = "5")]
+    pub page: Option<u32>,
+    #[prost(uint32, optional, tag = "6")]
+    pub per_page: Option<u32>,
+    #[prost(string, optional, tag = "7")]
+    pub sort: Option<String>,
+    #[prost(string, optional, tag = "8")]
+    pub order: Option<String>,
+    #[prost(bool, optional, tag = "9")]
+    pub include_archived: Option<bool>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct Deal {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(int32, tag = "2")]
+    pub numeric_id: i32,
+    #[prost(double, tag = "3")]
+    pub amount: f64,
+    #[prost(double, tag = "4")]
+    pub amount_usdt: f64,
+    #[prost(string, tag = "5")]
+    pub status: String,
+    #[prost(string, tag = "6")]
+    pub wallet: String,
+    #[prost(string, tag = "7")]
+    pub bank: String,
+    #[prost(string, optional, tag = "8")]
+    pub external_reference: Option<String>,
+    #[prost(string, tag = "9")]
+    pub merchant_id: String,
+    #[prost(string, optional, tag = "10")]
+    pub trader_id: Option<String>,
+    #[prost(string, tag = "11")]
+    pub created_at: String,
+    #[prost(string, optional, tag = "12")]
+    pub cancel_reason: Option<String>,
+    #[prost(string, optional, tag = "13")]
+    pub cancel_reason_code: Option<String>,
+    #[prost(double, optional, tag = "14")]
+    pub rate_deviation_percent: Option<f64>,
+    #[prost(bool, tag = "15")]
+    pub rate_mismatch: bool,
+    #[prost(bool, tag = "16")]
+    pub archived: bool,
+    #[prost(string, tag = "17")]
+    pub currency: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct ListDealsReply {
+    #[prost(message, repeated, tag = "1")]
+    pub items: Vec<Deal>,
+    #[prost(int64, tag = "2")]
+    pub total: i64,
+    #[prost(uint32, tag = "3")]
+    pub page: u32,
+    #[prost(uint32, tag = "4")]
+    pub per_page: u32,
+    #[prost(uint32, tag = "5")]
+    pub total_pages: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct EventsRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct Event {
+    #[prost(string, tag = "1")]
+    pub r#type: String,
+    #[prost(string, optional, tag = "2")]
+    pub message: Option<String>,
+    #[prost(string, optional, tag = "3")]
+    pub data_json: Option<String>,
+}
+
+fn to_status(
+    ApiError {
+        status, message, ..
+    }: ApiError,
+) -> Status {
+    match status {
+        StatusCode::BAD_REQUEST => Status::invalid_argument(message),
+        StatusCode::UNAUTHORIZED => Status::unauthenticated(message),
+        StatusCode::FORBIDDEN => Status::permission_denied(message),
+        StatusCode::NOT_FOUND => Status::not_found(message),
+        StatusCode::CONFLICT => Status::failed_precondition(message),
+        StatusCode::SERVICE_UNAVAILABLE | StatusCode::BAD_GATEWAY => Status::unavailable(message),
+        _ => Status::internal(message),
+    }
+}
+
+fn unauthenticated() -> Status {
+    Status::unauthenticated("A valid tenant token is required")
+}
+
+struct PayoutsService {
+    state: AppState,
+}
+
+impl PayoutsService {
+    fn scope(&self, metadata: &MetadataMap) -> Option<TenantScope> {
+        let token = metadata
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        self.state.tenants.resolve(token)
+    }
+}
+
+#[tonic::async_trait]
+impl Payouts for PayoutsService {
+    async fn assign_payout(
+        &self,
+        request: Request<AssignPayoutRequest>,
+    ) -> Result<Response<AssignPayoutReply>, Status> {
+        let scope = self.scope(request.metadata()).ok_or_else(unauthenticated)?;
+        let request = request.into_inner();
+        ensure_trader_in_scope(&self.state.pool, &request.trader_id, scope.merchant_ids())
+            .await
+            .map_err(to_status)?;
+        assign_payout_internal(
+            &self.state,
+            &request.payout_id,
+            &request.trader_id,
+            None,
+            &scope,
+            auth::tenant_actor(&scope).as_deref(),
+        )
+        .await
+        .map_err(to_status)?;
+        Ok(Response::new(AssignPayoutReply { success: true }))
+    }
+
+    async fn cancel_payout(
+        &self,
+        request: Request<CancelPayoutRequest>,
+    ) -> Result<Response<CancelPayoutReply>, Status> {
+        let scope = self.scope(request.metadata()).ok_or_else(unauthenticated)?;
+        let request = request.into_inner();
+        let result = cancel_payout_internal(
+            &self.state,
+            &request.payout_id,
+            RestCancelRequest {
+                reason: request.reason,
+                reason_code: request.reason_code,
+                expected_updated_at: None,
+            },
+            &scope,
+            auth::tenant_actor(&scope).as_deref(),
+        )
+        .await
+        .map_err(to_status)?;
+        Ok(Response::new(CancelPayoutReply {
+            success: result.success,
+            status: result.status.to_string(),
+            callback_dispatched: result.callback_dispatched,
+            callback_error: result.callback_error,
+        }))
+    }
+
+    async fn list_deals(
+        &self,
+        request: Request<ListDealsRequest>,
+    ) -> Result<Response<ListDealsReply>, Status> {
+        let scope = self.scope(request.metadata()).ok_or_else(unauthenticated)?;
+        let request = request.into_inner();
+        let query = PayoutListQuery {
+            search: request.search,
+            wallet: request.wallet,
+            amount: request.amount,
+            status: request.status,
+            page: request.page,
+            per_page: request.per_page,
+            sort: request.sort,
+            order: request.order,
+            include_archived: request.include_archived,
+        };
+        let list = list_deals_internal(&self.state, query, &scope)
+            .await
+            .map_err(to_status)?;
+
+        let items = list
+            .items
+            .into_iter()
+            .map(|item| Deal {
+                id: item.id,
+                numeric_id: item.numeric_id,
+                amount: item.amount,
+                amount_usdt: item.amount_usdt,
+                status: item.status.to_string(),
+                wallet: item.wallet,
+                bank: item.bank,
+                external_reference: item.external_reference,
+                merchant_id: item.merchant_id,
+                trader_id: item.trader_id,
+                created_at: item.created_at.to_string(),
+                cancel_reason: item.cancel_reason,
+                cancel_reason_code: item.cancel_reason_code,
+                rate_deviation_percent: item.rate_deviation_percent,
+                rate_mismatch: item.rate_mismatch,
+                archived: item.archived,
+                currency: item.currency,
+            })
+            .collect();
+        Ok(Response::new(ListDealsReply {
+            items,
+            total: list.pagination.total,
+            page: list.pagination.page,
+            per_page: list.pagination.per_page,
+            total_pages: list.pagination.total_pages,
+        }))
+    }
+
+    type EventsStream = Pin<Box<dyn Stream<Item = Result<Event, Status>> + Send>>;
+
+    async fn events(
+        &self,
+        request: Request<EventsRequest>,
+    ) -> Result<Response<Self::EventsStream>, Status> {
+        let scope = self.scope(request.metadata()).ok_or_else(unauthenticated)?;
+        let (rx, _) = self.state.event_stream.subscribe_since(None);
+        let stream = BroadcastStream::new(rx).filter_map(move |result| match result {
+            Ok(event) if !scope.can_receive(&event.audience) => None,
+            Ok(event) => Some(Ok(Event {
+                r#type: event.event_type,
+                message: event.message,
+                data_json: event.data.map(|data| data.to_string()),
+            })),
+            Err(err) => {
+                eprintln!("[grpc] Event subscriber lagged: {err}");
+                None
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+pub(crate) async fn serve(state: AppState, addr: SocketAddr) -> Result<()> {
+    println!("[grpc] Listening on {addr}");
+    tonic::transport::Server::builder()
+        .add_service(PayoutsServer::new(PayoutsService { state }))
+        .serve(addr)
+        .await
+        .context("gRPC server error")
+}
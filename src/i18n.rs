@@ -0,0 +1,1308 @@
+use std::collections::BTreeMap;
+
+use axum::http::{HeaderMap, header};
+use serde::Serialize;
+
+use crate::cookie_value;
+
+pub(crate) const LANG_COOKIE: &str = "lang";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Lang {
+    #[default]
+    Ru,
+    En,
+}
+
+impl Lang {
+    pub(crate) fn code(self) -> &'static str {
+        match self {
+            Lang::Ru => "ru",
+            Lang::En => "en",
+        }
+    }
+
+    pub(crate) fn locale(self) -> &'static str {
+        match self {
+            Lang::Ru => "ru-RU",
+            Lang::En => "en-US",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let primary = value
+            .trim()
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match primary.as_str() {
+            "ru" => Some(Lang::Ru),
+            "en" => Some(Lang::En),
+            _ => None,
+        }
+    }
+
+    /// Picks the dashboard language: an explicit `lang` cookie wins, then the
+    /// highest-weighted supported `Accept-Language` entry, then Russian unless
+    /// the header refuses it with `q=0`. Entries with an invalid weight are
+    /// ignored.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        if let Some(lang) = cookie_value(headers, LANG_COOKIE).and_then(|value| Lang::parse(&value))
+        {
+            return lang;
+        }
+
+        let mut candidates: Vec<(f32, Lang)> = headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';');
+                let lang = Lang::parse(parts.next()?)?;
+                let weight = match parts.find_map(|param| {
+                    let (name, value) = param.split_once('=')?;
+                    name.trim().eq_ignore_ascii_case("q").then_some(value)
+                }) {
+                    Some(q) => q
+                        .trim()
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|q| (0.0..=1.0).contains(q))?,
+                    None => 1.0,
+                };
+                Some((weight, lang))
+            })
+            .collect();
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        if let Some((_, lang)) = candidates.iter().find(|(weight, _)| *weight > 0.0) {
+            return *lang;
+        }
+        let refused = |lang: Lang| candidates.contains(&(0.0, lang));
+        if refused(Lang::Ru) && !refused(Lang::En) {
+            Lang::En
+        } else {
+            Lang::default()
+        }
+    }
+}
+
+/// `(key, ru, en)` triples shared by the SSR views and the dashboard script.
+const TRANSLATIONS: &[(&str, &str, &str)] = &[
+    ("page.title", "Распределение выплат", "Payout distribution"),
+    (
+        "page.subtitle",
+        "Управляйте автораспределением и следите за очередью выплат в реальном времени.",
+        "Manage auto distribution and watch the payout queue in real time.",
+    ),
+    ("page.updated", "Обновлено", "Updated"),
+    ("page.backend", "Сервер", "Backend"),
+    ("page.tenant", "Группа мерчантов", "Merchant group"),
+    (
+        "environment.locked",
+        "Заблокировано: {locks}",
+        "Locked: {locks}",
+    ),
+    ("page.operator", "Оператор", "Operator"),
+    ("page.logout", "Выйти", "Log out"),
+    ("page.shift-report", "Отчёт смены", "Shift report"),
+    ("login.title", "Вход в панель", "Sign in"),
+    ("login.username", "Логин", "Username"),
+    ("login.password", "Пароль", "Password"),
+    ("login.submit", "Войти", "Sign in"),
+    ("login.invalid", "Неверный логин или пароль", "Invalid username or password"),
+    ("backend.ok", "Работает", "Healthy"),
+    ("backend.degraded", "Деградация", "Degraded"),
+    ("backend.unreachable", "Недоступен", "Unreachable"),
+    (
+        "backend.no-heartbeat",
+        "Нет сигнала от сервера",
+        "No heartbeat from server",
+    ),
+    ("backend.db-down", "база данных не отвечает", "database is not responding"),
+    (
+        "backend.worker-stalled",
+        "воркер автораспределения завис",
+        "auto distribution worker is stalled",
+    ),
+    (
+        "backend.next-tick",
+        "Следующий цикл: {time}",
+        "Next cycle: {time}",
+    ),
+    (
+        "backend.worker-off",
+        "Автораспределение выключено",
+        "Auto distribution is off",
+    ),
+    (
+        "backend.pool",
+        "Пул БД: {size}/{max}, свободно {idle}",
+        "DB pool: {size}/{max}, {idle} idle",
+    ),
+    (
+        "backend.callback-retries",
+        "Колбэки к повтору: {count}",
+        "Callbacks pending retry: {count}",
+    ),
+    (
+        "backend.assignment-latency",
+        "Время до назначения p95: {seconds} с (цель {slo} с)",
+        "Time to assign p95: {seconds} s (target {slo} s)",
+    ),
+    (
+        "dlq.badge",
+        "Недоставленные колбэки: {count}",
+        "Dead-letter callbacks: {count}",
+    ),
+    (
+        "dlq.retry-hint",
+        "Нажмите, чтобы повторить отправку всех",
+        "Click to retry all of them",
+    ),
+    (
+        "dlq.retry-confirm",
+        "Повторить отправку {count} недоставленных колбэков?",
+        "Retry {count} dead-letter callbacks?",
+    ),
+    (
+        "dlq.retry-done",
+        "Колбэков поставлено в очередь: {count}",
+        "Callbacks requeued: {count}",
+    ),
+    (
+        "dlq.retry-failed",
+        "Не удалось повторить колбэки: {error}",
+        "Failed to retry callbacks: {error}",
+    ),
+    ("page.language-toggle", "English", "Русский"),
+    ("page.theme-light", "Светлая тема", "Light theme"),
+    ("page.theme-dark", "Тёмная тема", "Dark theme"),
+    (
+        "metrics.traders.label",
+        "Активные трейдеры",
+        "Active traders",
+    ),
+    (
+        "metrics.traders.sub",
+        "Количество трейдеров, готовых принять выплаты",
+        "Traders ready to accept payouts",
+    ),
+    (
+        "metrics.payouts.label",
+        "Нераспределенных выплат",
+        "Unassigned payouts",
+    ),
+    (
+        "metrics.payouts.sub",
+        "Текущая очередь выплат без исполнителя",
+        "Current queue of payouts without a trader",
+    ),
+    (
+        "metrics.sum.label",
+        "Сумма к распределению",
+        "Amount to distribute",
+    ),
+    (
+        "metrics.sum.sub",
+        "Совокупный объем ожидающих выплат",
+        "Total volume of pending payouts",
+    ),
+    (
+        "metrics.assigned-today.label",
+        "Назначено сегодня",
+        "Assigned today",
+    ),
+    (
+        "metrics.cancelled-today.label",
+        "Отменено сегодня",
+        "Cancelled today",
+    ),
+    (
+        "metrics.completed-today.label",
+        "Завершено сегодня",
+        "Completed today",
+    ),
+    ("metrics.today.amount", "На сумму", "Amount"),
+    (
+        "settings.title",
+        "Настройки автоматического распределения",
+        "Auto distribution settings",
+    ),
+    (
+        "settings.description.enabled",
+        "Автораспределение выполняется каждые {interval} секунд.",
+        "Auto distribution runs every {interval} seconds.",
+    ),
+    (
+        "settings.description.scheduled",
+        "Автораспределение выполняется каждые {interval} секунд по расписанию ({timezone}).",
+        "Auto distribution runs every {interval} seconds on schedule ({timezone}).",
+    ),
+    (
+        "settings.description.disabled",
+        "Автораспределение выключено.",
+        "Auto distribution is disabled.",
+    ),
+    ("settings.badge.on", "Активно", "Active"),
+    ("settings.badge.off", "Выключено", "Disabled"),
+    (
+        "settings.enable",
+        " Включить распределение",
+        " Enable distribution",
+    ),
+    ("settings.interval", "Интервал (сек):", "Interval (sec):"),
+    (
+        "settings.per-trader-cap",
+        "Макс. выплат на трейдера за цикл:",
+        "Max payouts per trader per cycle:",
+    ),
+    ("settings.no-cap", "Без ограничения", "Unlimited"),
+    (
+        "settings.per-cycle-cap",
+        "Макс. выплат за цикл:",
+        "Max payouts per cycle:",
+    ),
+    (
+        "settings.require-balance",
+        "Только при достаточном балансе",
+        "Require sufficient balance",
+    ),
+    (
+        "settings.balance-teams",
+        "Сначала балансировать между командами",
+        "Balance across teams first",
+    ),
+    ("settings.balance-reserve", "Резерв баланса, ₽:", "Balance reserve, RUB:"),
+    (
+        "settings.max-open-payouts",
+        "Макс. открытых выплат на трейдера:",
+        "Max open payouts per trader:",
+    ),
+    (
+        "settings.cooldown",
+        "Пауза после назначения, с:",
+        "Cooldown after assignment, s:",
+    ),
+    ("settings.schedule", "Окна распределения", "Distribution windows"),
+    ("settings.timezone", "Часовой пояс:", "Timezone:"),
+    ("settings.add-window", "Добавить окно", "Add window"),
+    ("settings.remove-window", "Удалить", "Remove"),
+    (
+        "settings-changes.title",
+        "Ждут подтверждения",
+        "Awaiting approval",
+    ),
+    (
+        "settings-changes.item",
+        "{section}: {requestedBy}, {since}",
+        "{section}: {requestedBy}, {since}",
+    ),
+    ("settings-changes.approve", "Подтвердить", "Approve"),
+    ("settings-changes.reject", "Отклонить", "Reject"),
+    (
+        "settings.schedule-hint",
+        "Без окон распределение работает круглосуточно. Окно без отмеченных дней действует ежедневно.",
+        "Without windows distribution runs around the clock. A window with no days ticked applies every day.",
+    ),
+    ("days.1", "Пн", "Mon"),
+    ("days.2", "Вт", "Tue"),
+    ("days.3", "Ср", "Wed"),
+    ("days.4", "Чт", "Thu"),
+    ("days.5", "Пт", "Fri"),
+    ("days.6", "Сб", "Sat"),
+    ("days.7", "Вс", "Sun"),
+    ("common.save", "Сохранить", "Save"),
+    ("common.close", "Закрыть", "Close"),
+    ("common.amount", "Сумма", "Amount"),
+    ("common.bank", "Банк", "Bank"),
+    ("common.status", "Статус", "Status"),
+    ("common.actions", "Действия", "Actions"),
+    ("stats.title", "Динамика очереди", "Queue dynamics"),
+    ("stats.period", "Период", "Period"),
+    ("stats.hours-24", "24 часа", "24 hours"),
+    ("stats.hours-72", "3 дня", "3 days"),
+    ("stats.hours-168", "7 дней", "7 days"),
+    ("stats.assigned", "Назначено за час", "Assigned per hour"),
+    ("stats.backlog", "Очередь без трейдера", "Unassigned backlog"),
+    ("stats.cancel-rate", "Доля отмен", "Cancellation rate"),
+    ("stats.empty", "Нет данных за период", "No data for the period"),
+    (
+        "stats.flow-share",
+        "Поток OUT: агрегаторы {aggregated} ({aggregatedShare}%), трейдеры {traders} ({tradersShare}%), без трейдера {unassigned} ({unassignedShare}%)",
+        "OUT flow: aggregators {aggregated} ({aggregatedShare}%), traders {traders} ({tradersShare}%), unassigned {unassigned} ({unassignedShare}%)",
+    ),
+    (
+        "stats.load-error",
+        "Не удалось загрузить статистику",
+        "Failed to load statistics",
+    ),
+    ("traders.title", "Доступные трейдеры", "Available traders"),
+    ("traders.balance", "Рублевый баланс", "RUB balance"),
+    ("traders.frozen", "Заморожено RUB", "Frozen RUB"),
+    ("traders.payout-balance", "Payout баланс", "Payout balance"),
+    ("traders.amount-range", "Сумма выплаты", "Payout amount"),
+    ("traders.min-amount", "От", "Min"),
+    ("traders.max-amount", "До", "Max"),
+    ("traders.open-payouts", "Открытые выплаты", "Open payouts"),
+    ("traders.cooldown", "Пауза", "Cooldown"),
+    ("traders.cooldown.value", "{seconds} с", "{seconds} s"),
+    ("traders.no-limit", "Пустое поле — без ограничения", "Leave empty for no limit"),
+    ("limits-bulk.title", "Шаблон лимитов", "Limit template"),
+    (
+        "limits-bulk.hint",
+        "Применяется к отмеченным трейдерам; пустые поля не меняют текущие значения",
+        "Applies to the checked traders; empty fields keep the current values",
+    ),
+    ("limits-bulk.cap", "Макс. открытых", "Max open"),
+    (
+        "limits-bulk.cap-hint",
+        "Открытых выплат на трейдера; 0 — общий лимит",
+        "Open payouts per trader; 0 for the global cap",
+    ),
+    ("limits-bulk.all", "Все найденные", "All matching"),
+    (
+        "limits-bulk.apply",
+        "Применить ({count})",
+        "Apply ({count})",
+    ),
+    ("limits-bulk.apply-all", "Применить ко всем", "Apply to all"),
+    (
+        "limits-bulk.preview",
+        "Изменится у {affected} из {total} трейдеров",
+        "Changes {affected} of {total} traders",
+    ),
+    ("limits-bulk.no-range", "без ограничения", "no limit"),
+    ("limits-bulk.default-cap", "общий", "global"),
+    ("limits-bulk.confirm", "Применить", "Apply"),
+    ("traders.currencies", "Также: {list}", "Also: {list}"),
+    (
+        "traders.currencies-hint",
+        "Валюты кроме RUB, которые берёт трейдер, и лимит на выплату в них",
+        "Currencies besides RUB the trader takes, with the max amount per payout",
+    ),
+    (
+        "traders.select-all",
+        "Выбрать всех на странице",
+        "Select all on the page",
+    ),
+    ("traders.team", "Команда", "Team"),
+    ("traders.no-team", "Без команды", "No team"),
+    ("traders.team-disabled", "Отключена", "Disabled"),
+    ("traders.away", "Отсутствует", "Away"),
+    (
+        "traders.away-hint",
+        "Запланированное отсутствие: выплаты не назначаются",
+        "Planned absence: no payouts are assigned",
+    ),
+    (
+        "traders.group-by-team",
+        "Группировать по командам",
+        "Group by team",
+    ),
+    (
+        "traders.search-placeholder",
+        "Поиск по email или numericId",
+        "Search by email or numericId",
+    ),
+    (
+        "traders.empty-filtered",
+        "Нет трейдеров по заданному поиску",
+        "No traders match the search",
+    ),
+    (
+        "traders.empty",
+        "Нет подходящих трейдеров",
+        "No eligible traders",
+    ),
+    (
+        "traders.load-error",
+        "Ошибка загрузки трейдеров",
+        "Failed to load traders",
+    ),
+    (
+        "payouts.title",
+        "Нераспределенные выплаты",
+        "Unassigned payouts",
+    ),
+    ("payouts.priority", "Приоритет", "Priority"),
+    ("payouts.duplicate", "Дубликат?", "Duplicate?"),
+    (
+        "payouts.duplicate-hint",
+        "Похожа на недавнюю выплату и не распределяется автоматически. Нажмите, чтобы подтвердить.",
+        "Looks like a recent payout and is held from auto distribution. Click to approve it.",
+    ),
+    (
+        "payouts.duplicate-confirm",
+        "Подтвердить, что это не дубликат, и вернуть выплату в автораспределение?",
+        "Confirm this is not a duplicate and return the payout to auto distribution?",
+    ),
+    (
+        "payouts.select-trader",
+        "Выберите трейдера",
+        "Select a trader",
+    ),
+    ("payouts.assign", "Привязать", "Assign"),
+    (
+        "payouts.empty",
+        "Нет нераспределенных выплат",
+        "No unassigned payouts",
+    ),
+    (
+        "payouts.load-error",
+        "Ошибка загрузки выплат",
+        "Failed to load payouts",
+    ),
+    ("merchants.title", "Мерчанты", "Merchants"),
+    ("merchants.name", "Мерчант", "Merchant"),
+    ("merchants.callbacks", "Колбэки", "Callbacks"),
+    ("merchants.queued", "В очереди", "Queued"),
+    ("merchants.active", "Отправляются", "Sending"),
+    (
+        "merchants.paused",
+        "На паузе с {since}",
+        "Paused since {since}",
+    ),
+    ("merchants.pause", "Приостановить", "Pause"),
+    ("merchants.resume", "Возобновить", "Resume"),
+    (
+        "merchants.pause-prompt",
+        "Приостановить колбэки для {merchant}? Причина (необязательно):",
+        "Pause callbacks for {merchant}? Reason (optional):",
+    ),
+    ("merchants.empty", "Мерчантов нет", "No merchants"),
+    (
+        "merchants.load-error",
+        "Не удалось загрузить мерчантов",
+        "Failed to load merchants",
+    ),
+    ("deals.title", "Все выплаты", "All payouts"),
+    ("deals.search", "Поиск", "Search"),
+    ("deals.wallet", "Кошелек", "Wallet"),
+    (
+        "deals.wallet-placeholder",
+        "Номер кошелька",
+        "Wallet number",
+    ),
+    ("deals.status-all", "Все", "All"),
+    ("deals.per-page", "На странице", "Per page"),
+    (
+        "deals.sort-status",
+        "Сортировка по статусу",
+        "Sort by status",
+    ),
+    ("deals.sort-status-asc", "Статус ↑", "Status ↑"),
+    ("deals.sort-status-desc", "Статус ↓", "Status ↓"),
+    ("deals.reset", "Сбросить фильтры", "Reset filters"),
+    ("deals.created", "Создана", "Created"),
+    ("deals.cancel", "Отменить", "Cancel"),
+    ("deals.cancel-title", "Отменить выплату", "Cancel payout"),
+    (
+        "deals.cancel-unavailable",
+        "Отмена недоступна для этого статуса",
+        "Cancellation is not available for this status",
+    ),
+    ("deals.select-all", "Выбрать все", "Select all"),
+    (
+        "deals.bulk-cancel",
+        "Отменить выбранные ({count})",
+        "Cancel selected ({count})",
+    ),
+    ("deals.files", "Файлы", "Files"),
+    ("deals.timeline", "История", "Timeline"),
+    ("deals.rate-flag", "Курс", "Rate"),
+    ("deals.archived", "Архив", "Archived"),
+    ("deals.include-archived", "Показывать архивные", "Show archived"),
+    (
+        "deals.rate-mismatch",
+        "Сумма в USDT отличается от текущего курса на {deviation}%",
+        "USDT amount differs from the current rate by {deviation}%",
+    ),
+    ("timeline.loading", "Загрузка истории...", "Loading timeline..."),
+    ("timeline.empty", "История пуста.", "No events yet."),
+    (
+        "timeline.load-failed",
+        "Не удалось загрузить историю: {error}",
+        "Failed to load timeline: {error}",
+    ),
+    ("timeline.kind.created", "Создана", "Created"),
+    ("timeline.kind.assigned", "Назначена трейдеру", "Assigned to trader"),
+    ("timeline.kind.accepted", "Принята трейдером", "Accepted by trader"),
+    ("timeline.kind.cancelled", "Отменена", "Cancelled"),
+    ("timeline.kind.completed", "Завершена", "Completed"),
+    ("timeline.kind.failed", "Ошибка", "Failed"),
+    ("timeline.kind.disputed", "Открыт спор", "Disputed"),
+    ("timeline.kind.expired", "Истекла", "Expired"),
+    ("timeline.kind.callback", "Колбэк мерчанту", "Merchant callback"),
+    ("timeline.kind.file-attached", "Прикреплён файл", "File attached"),
+    (
+        "timeline.kind.duplicate-flagged",
+        "Отмечена как возможный дубликат",
+        "Flagged as a possible duplicate",
+    ),
+    (
+        "timeline.kind.duplicate-approved",
+        "Подтверждена как не дубликат",
+        "Approved as not a duplicate",
+    ),
+    ("timeline.kind.note", "Заметка", "Note"),
+    ("timeline.source.auto", "автоматически", "automatic"),
+    ("timeline.source.manual", "вручную", "manual"),
+    ("timeline.source.platform", "платформа", "platform"),
+    ("timeline.source.trader", "трейдер", "trader"),
+    ("timeline.source.service", "сервис", "service"),
+    ("timeline.actor", "кем: {actor}", "by {actor}"),
+    ("timeline.trader", "трейдер {id}", "trader {id}"),
+    ("timeline.callback-not-sent", "не отправлен", "not sent"),
+    ("report.title", "Отчёт о передаче смены", "Shift handover report"),
+    ("report.period", "С {from} по {to}", "From {from} to {to}"),
+    ("report.generated", "Сформирован", "Generated"),
+    ("report.none", "Нет", "None"),
+    ("report.more", "И ещё {count}", "And {count} more"),
+    ("report.assignments", "Назначения", "Assignments"),
+    (
+        "report.assignments-total",
+        "{count} на {amount}, автоматически {automatic}",
+        "{count} for {amount}, {automatic} automatic",
+    ),
+    ("report.trader", "Трейдер", "Trader"),
+    ("report.count", "Количество", "Count"),
+    ("report.automatic", "Автоматически", "Automatic"),
+    ("report.cancellations", "Отмены", "Cancellations"),
+    (
+        "report.cancellations-total",
+        "{count} на {amount}",
+        "{count} for {amount}",
+    ),
+    ("report.reason", "Причина", "Reason"),
+    ("report.no-reason", "Без причины", "No reason"),
+    (
+        "report.failed-callbacks",
+        "Недоставленные колбэки",
+        "Failed callbacks",
+    ),
+    ("report.payout", "Выплата", "Payout"),
+    ("report.merchant", "Мерчант", "Merchant"),
+    ("report.event", "Событие", "Event"),
+    ("report.error", "Ошибка", "Error"),
+    ("report.failed-at", "Время сбоя", "Failed at"),
+    ("report.disputes", "Открытые споры", "Unresolved disputes"),
+    ("report.dispute-message", "Сообщение", "Message"),
+    ("report.updated-at", "Обновлена", "Updated"),
+    (
+        "report.stuck",
+        "Без принятия дольше {minutes} мин",
+        "Not accepted for over {minutes} min",
+    ),
+    ("report.unassigned", "Не назначена", "Unassigned"),
+    ("report.created-at", "Создана", "Created"),
+    ("report.notes", "Заметки за смену", "Notes during the shift"),
+    ("report.author", "Автор", "Author"),
+    ("report.note", "Заметка", "Note"),
+    (
+        "notes.placeholder",
+        "Заметка для следующей смены",
+        "Note for the next shift",
+    ),
+    ("notes.add", "Добавить заметку", "Add note"),
+    (
+        "notes.save-failed",
+        "Не удалось сохранить заметку: {error}",
+        "Failed to save the note: {error}",
+    ),
+    ("assignments.heading", "История назначений", "Assignment history"),
+    ("assignments.title", "Назначения трейдера {trader}", "Payouts assigned to {trader}"),
+    ("assignments.from", "С даты", "From"),
+    ("assignments.to", "По дату", "To"),
+    ("assignments.amount", "Сумма", "Amount"),
+    ("assignments.status", "Статус", "Status"),
+    ("assignments.bank", "Банк", "Bank"),
+    ("assignments.assigned-at", "Назначена", "Assigned"),
+    ("assignments.finished-at", "Принята / отменена", "Accepted / cancelled"),
+    ("assignments.moved", "снята с трейдера", "no longer assigned"),
+    (
+        "assignments.summary",
+        "Выплат: {count}, на сумму {amount}",
+        "{count} payout(s), {amount} in total",
+    ),
+    ("assignments.empty", "За выбранный период назначений нет", "No assignments in this period"),
+    (
+        "assignments.load-error",
+        "Не удалось загрузить историю: {error}",
+        "Failed to load the history: {error}",
+    ),
+    (
+        "eligibility.heading",
+        "Изменения доступности для распределения",
+        "Eligibility changes",
+    ),
+    ("eligibility.at", "Время", "Time"),
+    ("eligibility.change", "Изменение", "Change"),
+    ("eligibility.reasons", "Причины", "Reasons"),
+    ("eligibility.merchants", "Мерчанты", "Merchants"),
+    ("eligibility.appeared", "Стал доступен", "Became eligible"),
+    (
+        "eligibility.dropped",
+        "Выпал из распределения",
+        "Dropped out",
+    ),
+    (
+        "eligibility.empty",
+        "Изменений пока нет",
+        "No changes recorded yet",
+    ),
+    ("eligibility.reason.banned", "заблокирован", "banned"),
+    (
+        "eligibility.reason.traffic-disabled",
+        "трафик выключен",
+        "traffic disabled",
+    ),
+    (
+        "eligibility.reason.zero-balance",
+        "нулевой баланс",
+        "zero balance",
+    ),
+    ("eligibility.reason.paused", "на паузе", "paused"),
+    (
+        "eligibility.reason.unavailable",
+        "недоступен по графику",
+        "scheduled unavailability",
+    ),
+    (
+        "eligibility.reason.merchant-link-removed",
+        "нет подключённых мерчантов",
+        "no merchant link left",
+    ),
+    (
+        "eligibility.reason.no-open-payouts",
+        "нет выплат в очереди",
+        "no open payouts",
+    ),
+    ("trader-page.title", "Мои выплаты", "My payouts"),
+    ("trader-page.state", "Состояние", "Status"),
+    (
+        "trader-page.receiving",
+        "Получаете выплаты",
+        "Receiving payouts",
+    ),
+    (
+        "trader-page.traffic-off",
+        "Трафик выключен",
+        "Traffic is off",
+    ),
+    ("trader-page.paused", "На паузе", "Paused"),
+    (
+        "trader-page.cooldown",
+        "Следующая выплата не раньше чем через {seconds} с",
+        "Next payout in {seconds} s at the earliest",
+    ),
+    (
+        "trader-page.away",
+        "Запланированное отсутствие: {from} — {to}",
+        "Planned absence: {from} to {to}",
+    ),
+    (
+        "trader-page.open-payouts",
+        "Открытых выплат / лимит",
+        "Open payouts / cap",
+    ),
+    ("trader-page.amount-range", "Диапазон сумм", "Amount range"),
+    ("trader-page.currencies", "Валюты", "Currencies"),
+    ("trader-page.unlimited", "без ограничений", "no limit"),
+    (
+        "trader-page.assignments",
+        "Назначенные выплаты",
+        "Assigned payouts",
+    ),
+    ("trader-page.wallet", "Реквизиты", "Wallet"),
+    (
+        "trader-page.none",
+        "Сейчас на вас нет выплат",
+        "Nothing is assigned to you right now",
+    ),
+    ("merchant-page.title", "Статус выплат", "Payout status"),
+    (
+        "merchant-page.callbacks",
+        "Доставка колбэков",
+        "Callback delivery",
+    ),
+    ("merchant-page.healthy", "В норме", "Healthy"),
+    ("merchant-page.degraded", "Есть проблемы", "Degraded"),
+    ("merchant-page.window", "Период", "Window"),
+    (
+        "merchant-page.window-minutes",
+        "{minutes} мин",
+        "{minutes} min",
+    ),
+    ("merchant-page.window-hours", "{hours} ч", "{hours} h"),
+    ("merchant-page.attempts", "Попыток", "Attempts"),
+    ("merchant-page.delivered", "Доставлено", "Delivered"),
+    ("merchant-page.p95", "Задержка p95", "p95 latency"),
+    (
+        "merchant-page.retrying",
+        "Ожидают повторной отправки: {count}",
+        "Waiting for a retry: {count}",
+    ),
+    (
+        "merchant-page.dead-lettered",
+        "Не доставлены после всех попыток: {count}",
+        "Undelivered after all retries: {count}",
+    ),
+    (
+        "merchant-page.payouts",
+        "Последние выплаты",
+        "Recent payouts",
+    ),
+    (
+        "merchant-page.shown",
+        "{shown} из {total}",
+        "{shown} of {total}",
+    ),
+    ("merchant-page.reference", "Ваш ID", "Your reference"),
+    ("merchant-page.reason", "Причина отмены", "Cancel reason"),
+    ("merchant-page.none", "Выплат пока нет", "No payouts yet"),
+    ("files.title", "Файлы выплаты #{id}", "Payout #{id} files"),
+    ("files.proof", "Подтверждения", "Proof"),
+    ("files.dispute", "Материалы спора", "Dispute evidence"),
+    ("files.kind", "Тип файла", "File type"),
+    ("files.file", "Файл", "File"),
+    ("files.upload", "Загрузить", "Upload"),
+    ("files.empty", "Нет файлов", "No files"),
+    ("files.no-link", "ссылка недоступна", "link unavailable"),
+    (
+        "files.storage-disabled",
+        "Хранилище файлов не настроено, загрузка недоступна",
+        "File storage is not configured, uploads are disabled",
+    ),
+    ("pagination.prev", "Назад", "Previous"),
+    ("pagination.next", "Вперед", "Next"),
+    (
+        "pagination.page-info",
+        "{page} / {pages} (всего {total})",
+        "{page} / {pages} ({total} total)",
+    ),
+    ("deals.empty", "Нет данных о выплатах", "No payout data"),
+    (
+        "deals.empty-filtered",
+        "Нет выплат по заданным фильтрам",
+        "No payouts match the filters",
+    ),
+    (
+        "deals.load-error",
+        "Не удалось загрузить выплаты",
+        "Failed to load payouts",
+    ),
+    (
+        "status.deals-loading",
+        "Обновляем список выплат...",
+        "Refreshing payouts...",
+    ),
+    (
+        "status.deals-loaded",
+        "Список выплат обновлен.",
+        "Payouts refreshed.",
+    ),
+    (
+        "status.deals-load-failed",
+        "Не удалось загрузить выплаты: {error}",
+        "Failed to load payouts: {error}",
+    ),
+    (
+        "status.cancel-not-allowed",
+        "Эту выплату нельзя отменить.",
+        "This payout cannot be cancelled.",
+    ),
+    (
+        "status.cancel-confirm",
+        "Вы уверены, что хотите отменить выплату?",
+        "Are you sure you want to cancel this payout?",
+    ),
+    ("cancel-dialog.code", "Код причины", "Reason code"),
+    ("cancel-dialog.no-code", "Без кода", "No code"),
+    ("cancel-dialog.comment", "Комментарий", "Comment"),
+    (
+        "cancel-dialog.comment-placeholder",
+        "Причина отмены (необязательно)",
+        "Cancellation reason (optional)",
+    ),
+    ("cancel-dialog.confirm", "Отменить выплату", "Cancel payout"),
+    ("status.cancelled", "Выплата отменена.", "Payout cancelled."),
+    (
+        "status.cancelled-callback-failed",
+        "Выплата отменена, но колбэк не доставлен: {error}",
+        "Payout cancelled, but the callback was not delivered: {error}",
+    ),
+    (
+        "status.bulk-cancel-confirm",
+        "Отменить выбранные выплаты ({count})?",
+        "Cancel the selected payouts ({count})?",
+    ),
+    (
+        "status.bulk-cancel-done",
+        "Отменено: {cancelled}, отклонено: {failed}.",
+        "Cancelled: {cancelled}, rejected: {failed}.",
+    ),
+    (
+        "status.bulk-cancel-callback-failed",
+        "Отменено: {cancelled}, отклонено: {failed}. Колбэки не доставлены: {callbacks}.",
+        "Cancelled: {cancelled}, rejected: {failed}. Callbacks not delivered: {callbacks}.",
+    ),
+    ("status.file-missing", "Выберите файл для загрузки.", "Choose a file to upload."),
+    ("status.file-uploaded", "Файл загружен.", "File uploaded."),
+    (
+        "status.file-upload-failed",
+        "Не удалось загрузить файл: {error}",
+        "Failed to upload file: {error}",
+    ),
+    (
+        "status.files-load-failed",
+        "Не удалось получить файлы выплаты: {error}",
+        "Failed to load payout files: {error}",
+    ),
+    (
+        "status.per-cycle-cap-invalid",
+        "Лимит выплат за цикл должен быть целым числом больше нуля.",
+        "The per-cycle payout cap must be a whole number greater than zero.",
+    ),
+    (
+        "status.balance-reserve-invalid",
+        "Резерв баланса должен быть неотрицательным числом.",
+        "The balance reserve must be zero or a positive number.",
+    ),
+    (
+        "status.open-cap-invalid",
+        "Лимит открытых выплат должен быть целым числом больше нуля.",
+        "The open payout cap must be a whole number greater than zero.",
+    ),
+    (
+        "status.cooldown-invalid",
+        "Пауза после назначения должна быть целым неотрицательным числом секунд.",
+        "The cooldown must be a whole non-negative number of seconds.",
+    ),
+    (
+        "status.cycle-summary",
+        "Цикл распределения: назначено {assigned}, осталось в очереди {remaining}.",
+        "Distribution cycle: {assigned} assigned, {remaining} left in the queue.",
+    ),
+    (
+        "status.callback-sla-breached",
+        "Колбэки мерчанта {merchantId}: доставлено {successRate}% из {attempts} за {windowMinutes} мин, ниже порога {threshold}%.",
+        "Callbacks to merchant {merchantId}: {successRate}% of {attempts} delivered in {windowMinutes} min, below the {threshold}% threshold.",
+    ),
+    (
+        "status.callback-sla-recovered",
+        "Колбэки мерчанта {merchantId} снова в норме: доставлено {successRate}%.",
+        "Callbacks to merchant {merchantId} are back within SLA: {successRate}% delivered.",
+    ),
+    (
+        "status.trader-eligibility-dropped",
+        "Трейдер {traderId} выпал из распределения после {recentAssignments} назначений за {windowHours} ч: {reasons}.",
+        "Trader {traderId} dropped out after {recentAssignments} assignment(s) in {windowHours} h: {reasons}.",
+    ),
+    (
+        "status.assignment-slo-breached",
+        "Назначение выплат замедлилось: p95 {p95Seconds} с по {assigned} назначениям за {windowMinutes} мин, цель {sloSeconds} с.",
+        "Assignment is slow: p95 of {p95Seconds} s over {assigned} assignments in {windowMinutes} min, target {sloSeconds} s.",
+    ),
+    (
+        "status.assignment-slo-recovered",
+        "Время до назначения снова в норме: p95 {p95Seconds} с.",
+        "Time to assign is back within SLO: p95 of {p95Seconds} s.",
+    ),
+    (
+        "status.window-invalid",
+        "Укажите время начала и окончания для каждого окна.",
+        "Set a start and end time for every window.",
+    ),
+    (
+        "status.cancel-failed",
+        "Не удалось отменить выплату: {error}",
+        "Failed to cancel payout: {error}",
+    ),
+    (
+        "status.already-cancelled",
+        "Выплата уже отменена, список обновлён.",
+        "The payout is already cancelled; the list is refreshed.",
+    ),
+    (
+        "status.merchant-paused",
+        "Колбэки для {merchant} приостановлены, они копятся в очереди",
+        "Callbacks for {merchant} are paused and queue up until resumed",
+    ),
+    (
+        "status.merchant-resumed",
+        "Колбэки для {merchant} возобновлены, в очереди: {count}",
+        "Callbacks for {merchant} resumed, {count} queued",
+    ),
+    (
+        "status.merchant-toggle-failed",
+        "Не удалось переключить колбэки мерчанта: {error}",
+        "Failed to switch the merchant's callbacks: {error}",
+    ),
+    (
+        "status.cancel-approval-requested",
+        "Выплата крупная: запрос на отмену отправлен, его должен подтвердить другой оператор.",
+        "Large payout: the cancellation was requested and needs another operator's approval.",
+    ),
+    (
+        "status.cancel-approval-confirm",
+        "{message}\n\nПодтвердить отмену?",
+        "{message}\n\nApprove the cancellation?",
+    ),
+    (
+        "status.cancel-approval-pending",
+        "{requestedBy} просит отменить выплату {payoutId} на {amount} {currency}. Нужно подтверждение.",
+        "{requestedBy} asks to cancel payout {payoutId} for {amount} {currency}. Approval needed.",
+    ),
+    (
+        "status.cancel-approval-approved",
+        "Отмена выплаты {payoutId} подтверждена ({decidedBy}).",
+        "Cancellation of payout {payoutId} approved by {decidedBy}.",
+    ),
+    (
+        "status.cancel-approval-rejected",
+        "Запрос на отмену выплаты {payoutId} отклонён ({decidedBy}).",
+        "Cancellation request for payout {payoutId} rejected by {decidedBy}.",
+    ),
+    (
+        "status.cancel-approval-expired",
+        "Запрос на отмену выплаты {payoutId} истёк без подтверждения.",
+        "Cancellation request for payout {payoutId} expired unapproved.",
+    ),
+    (
+        "status.payout-modified",
+        "Выплату только что изменили, список обновлён. Проверьте её и повторите.",
+        "The payout was just changed; the list is refreshed. Check it and try again.",
+    ),
+    (
+        "status.data-loading",
+        "Обновляем данные...",
+        "Refreshing data...",
+    ),
+    ("status.data-loaded", "Данные обновлены", "Data refreshed"),
+    (
+        "status.data-load-failed",
+        "Не удалось загрузить данные: {error}",
+        "Failed to load data: {error}",
+    ),
+    (
+        "status.select-trader",
+        "Выберите трейдера для привязки.",
+        "Select a trader to assign.",
+    ),
+    (
+        "status.assigned",
+        "Выплата успешно распределена.",
+        "Payout assigned successfully.",
+    ),
+    (
+        "status.assign-failed",
+        "Не удалось привязать выплату: {error}",
+        "Failed to assign payout: {error}",
+    ),
+    (
+        "status.assign-taken",
+        "Выплата уже распределена или отменена, список обновлён.",
+        "The payout was already assigned or cancelled; the list is refreshed.",
+    ),
+    (
+        "status.duplicate-approved",
+        "Выплата возвращена в автораспределение",
+        "Payout returned to auto distribution",
+    ),
+    (
+        "status.duplicate-approve-failed",
+        "Не удалось подтвердить выплату: {error}",
+        "Failed to approve payout: {error}",
+    ),
+    (
+        "status.limit-invalid",
+        "Укажите неотрицательное число или оставьте поле пустым.",
+        "Enter a non-negative number or leave the field empty.",
+    ),
+    (
+        "status.limit-range-invalid",
+        "Минимальная сумма больше максимальной.",
+        "The min amount is above the max amount.",
+    ),
+    (
+        "status.limit-saved",
+        "Лимит трейдера обновлен.",
+        "Trader limit updated.",
+    ),
+    (
+        "status.limits-bulk-empty",
+        "Укажите сумму или лимит открытых выплат.",
+        "Fill in an amount or the open payout cap.",
+    ),
+    (
+        "status.limits-bulk-cap-invalid",
+        "Лимит открытых выплат должен быть целым неотрицательным числом.",
+        "The open payout cap must be a whole non-negative number.",
+    ),
+    (
+        "status.limits-bulk-nothing",
+        "Шаблон ничего не меняет у выбранных трейдеров.",
+        "The template changes nothing for the selected traders.",
+    ),
+    (
+        "status.limits-bulk-saved",
+        "Лимиты обновлены у трейдеров: {count}.",
+        "Limits updated for {count} traders.",
+    ),
+    (
+        "status.limits-bulk-failed",
+        "Не удалось применить шаблон: {error}",
+        "Failed to apply the template: {error}",
+    ),
+    (
+        "status.limit-save-failed",
+        "Не удалось сохранить лимит: {error}",
+        "Failed to save limit: {error}",
+    ),
+    (
+        "status.per-trader-cap-invalid",
+        "Лимит на трейдера за цикл должен быть целым числом больше нуля.",
+        "The per-trader cycle cap must be a whole number greater than zero.",
+    ),
+    (
+        "status.settings-saved",
+        "Настройки сохранены.",
+        "Settings saved.",
+    ),
+    (
+        "status.settings-change-pending",
+        "Изменение ждёт подтверждения второго администратора: {risks}.",
+        "The change awaits a second admin's approval: {risks}.",
+    ),
+    (
+        "status.settings-change-requested",
+        "{requestedBy} просит изменить настройки ({section}): {risks}. Нужно подтверждение.",
+        "{requestedBy} asks to change the {section} settings: {risks}. Approval needed.",
+    ),
+    (
+        "status.settings-change-approved-by",
+        "{decidedBy} подтвердил изменение настроек ({section}) от {requestedBy}.",
+        "{decidedBy} approved the {section} change by {requestedBy}.",
+    ),
+    (
+        "status.settings-change-rejected-by",
+        "{decidedBy} отклонил изменение настроек ({section}) от {requestedBy}.",
+        "{decidedBy} rejected the {section} change by {requestedBy}.",
+    ),
+    (
+        "status.settings-change-approved",
+        "Изменение настроек подтверждено и применено.",
+        "Settings change approved and applied.",
+    ),
+    (
+        "status.settings-change-rejected",
+        "Изменение настроек отклонено.",
+        "Settings change rejected.",
+    ),
+    (
+        "status.settings-change-failed",
+        "Не удалось принять решение по изменению: {error}",
+        "Failed to decide on the change: {error}",
+    ),
+    (
+        "status.settings-save-failed",
+        "Не удалось сохранить настройки: {error}",
+        "Failed to save settings: {error}",
+    ),
+    (
+        "status.event-received",
+        "Получено обновление: {type}",
+        "Update received: {type}",
+    ),
+    (
+        "status.update-received",
+        "Получено обновление данных.",
+        "Data update received.",
+    ),
+    (
+        "status.backend-degraded",
+        "Сервер работает с ошибками: {reasons}",
+        "Backend is degraded: {reasons}",
+    ),
+    (
+        "status.sse-lost",
+        "SSE соединение потеряно. Переподключение...",
+        "SSE connection lost. Reconnecting...",
+    ),
+    (
+        "status.initial-data",
+        "Показаны данные на момент загрузки.",
+        "Showing data as of page load.",
+    ),
+    (
+        "status.init-failed",
+        "Не удалось инициализировать страницу: {error}",
+        "Failed to initialize the page: {error}",
+    ),
+    ("presets.title", "Сохраненные фильтры", "Saved filters"),
+    ("presets.choose", "Пресет фильтров...", "Filter preset..."),
+    ("presets.save", "Сохранить фильтры", "Save filters"),
+    ("presets.delete", "Удалить пресет", "Delete preset"),
+    ("presets.copy-link", "Ссылка на вид", "Copy link"),
+    (
+        "presets.name-prompt",
+        "Название пресета (существующий будет перезаписан):",
+        "Preset name (an existing one is overwritten):",
+    ),
+    (
+        "presets.delete-confirm",
+        "Удалить пресет «{name}»?",
+        "Delete preset \"{name}\"?",
+    ),
+    ("status.preset-saved", "Пресет «{name}» сохранен.", "Preset \"{name}\" saved."),
+    (
+        "status.preset-save-failed",
+        "Не удалось сохранить пресет: {error}",
+        "Failed to save preset: {error}",
+    ),
+    ("status.preset-deleted", "Пресет «{name}» удален.", "Preset \"{name}\" deleted."),
+    (
+        "status.preset-delete-failed",
+        "Не удалось удалить пресет: {error}",
+        "Failed to delete preset: {error}",
+    ),
+    (
+        "status.link-copied",
+        "Ссылка на текущий вид скопирована.",
+        "Link to the current view copied.",
+    ),
+    (
+        "deals.acceptance-countdown",
+        "Осталось времени на принятие трейдером",
+        "Time left for the trader to accept",
+    ),
+    ("deals.acceptance-overdue", "Просрочено", "Overdue"),
+    (
+        "search.placeholder",
+        "Поиск: выплата, трейдер, мерчант",
+        "Search payouts, traders, merchants",
+    ),
+    ("search.group.payouts", "Выплаты", "Payouts"),
+    ("search.group.traders", "Трейдеры", "Traders"),
+    ("search.group.merchants", "Мерчанты", "Merchants"),
+    ("search.empty", "Ничего не найдено", "Nothing found"),
+    ("search.failed", "Ошибка поиска: {error}", "Search failed: {error}"),
+    ("search.banned", "заблокирован", "banned"),
+    ("search.traffic-off", "трафик выключен", "traffic off"),
+    ("columns.open", "Колонки", "Columns"),
+    ("columns.title", "Колонки таблицы выплат", "Payout table columns"),
+    ("columns.width", "Ширина, px", "Width, px"),
+    ("columns.auto-width", "авто", "auto"),
+    ("columns.up", "Выше", "Move up"),
+    ("columns.down", "Ниже", "Move down"),
+    ("columns.save", "Сохранить", "Save"),
+    ("columns.reset", "По умолчанию", "Reset to default"),
+    (
+        "status.columns-none-visible",
+        "Оставьте видимой хотя бы одну колонку.",
+        "Keep at least one column visible.",
+    ),
+    (
+        "status.columns-save-failed",
+        "Не удалось сохранить колонки: {error}",
+        "Failed to save columns: {error}",
+    ),
+    ("palette.open", "Команды ⌘K", "Commands ⌘K"),
+    (
+        "palette.hint",
+        "Ctrl/⌘+K — команды, / — поиск, g — выплата по номеру, r — распределить, a — автоматический режим",
+        "Ctrl/⌘+K commands, / search, g payout by number, r distribute, a auto mode",
+    ),
+    (
+        "palette.placeholder",
+        "Номер выплаты (#123) или команда...",
+        "Payout number (#123) or command...",
+    ),
+    ("palette.no-matches", "Ничего не найдено", "No matches"),
+    ("palette.search-deals", "Поиск по выплатам", "Search payouts"),
+    ("palette.search-for", "Искать «{query}» в выплатах", "Search payouts for \"{query}\""),
+    ("palette.jump", "Перейти к выплате по номеру", "Jump to payout by number"),
+    ("palette.jump-to", "Открыть выплату #{id}", "Open payout #{id}"),
+    ("palette.distribute", "Распределить сейчас", "Distribute now"),
+    (
+        "palette.distribute-confirm",
+        "Запустить цикл распределения сейчас?",
+        "Run a distribution cycle now?",
+    ),
+    (
+        "palette.toggle-auto",
+        "Переключить автоматический режим",
+        "Toggle auto mode",
+    ),
+    (
+        "status.payout-found",
+        "Выплата #{id}, статус {status}.",
+        "Payout #{id}, status {status}.",
+    ),
+    (
+        "status.payout-lookup-failed",
+        "Выплата #{id} не найдена: {error}",
+        "Payout #{id} not found: {error}",
+    ),
+    (
+        "status.distribution-running",
+        "Запуск распределения...",
+        "Running distribution...",
+    ),
+    (
+        "status.distribution-done",
+        "Распределение завершено: назначено {assigned}, осталось {remaining}.",
+        "Distribution finished: {assigned} assigned, {remaining} remaining.",
+    ),
+    (
+        "status.distribution-skipped",
+        "Распределение не выполнено: {note}",
+        "Distribution skipped: {note}",
+    ),
+    (
+        "status.distribution-failed",
+        "Ошибка распределения: {error}",
+        "Distribution failed: {error}",
+    ),
+];
+
+pub(crate) fn t(lang: Lang, key: &'static str) -> &'static str {
+    TRANSLATIONS
+        .iter()
+        .find(|(candidate, _, _)| *candidate == key)
+        .map(|(_, ru, en)| match lang {
+            Lang::Ru => *ru,
+            Lang::En => *en,
+        })
+        .unwrap_or(key)
+}
+
+/// Translates `key` and substitutes `{name}` placeholders with `args`.
+pub(crate) fn tf(lang: Lang, key: &'static str, args: &[(&str, String)]) -> String {
+    args.iter()
+        .fold(t(lang, key).to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{name}}}"), value)
+        })
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ClientBundle {
+    lang: &'static str,
+    locale: &'static str,
+    strings: BTreeMap<&'static str, &'static str>,
+}
+
+/// The translation table for `lang`, injected into the page for the dashboard script.
+pub(crate) fn client_bundle(lang: Lang) -> ClientBundle {
+    ClientBundle {
+        lang: lang.code(),
+        locale: lang.locale(),
+        strings: TRANSLATIONS
+            .iter()
+            .map(|(key, _, _)| (*key, t(lang, key)))
+            .collect(),
+    }
+}
@@ -0,0 +1,306 @@
+//! Limit templates: `POST /api/traders/limits/bulk` applies one amount range
+//! and/or open-payout cap to many traders at once, instead of saving them
+//! trader by trader.
+//!
+//! The request names the traders (`traderIds`) or takes every trader of the
+//! traders list (`all`, narrowed by `search` like the list). A field left out
+//! of the template is kept as it is; `limit: {}` clears the range and
+//! `maxOpenPayouts: 0` falls back to the global cap, as on the per-trader
+//! endpoints. With `preview` nothing is written and the response lists what
+//! would change.
+
+use std::collections::HashMap;
+
+use axum::{
+    Json,
+    extract::{Extension, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use tower_sessions::Session;
+
+use crate::{
+    ApiResult, AppState, auth,
+    db::{
+        ELIGIBLE_TRADERS_QUERY, TraderListFilters, apply_trader_search, fetch_capacity_overrides,
+    },
+    errors::ApiError,
+    events::ServerEvent,
+    internal_error,
+    settings::{AmountRange, audit_change},
+    tenant::TenantScope,
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BulkLimitRequest {
+    #[serde(default)]
+    trader_ids: Vec<String>,
+    #[serde(default)]
+    all: bool,
+    search: Option<String>,
+    limit: Option<AmountRange>,
+    max_open_payouts: Option<u32>,
+    #[serde(default)]
+    preview: bool,
+}
+
+#[derive(Debug, FromRow)]
+struct TargetTrader {
+    id: String,
+    email: String,
+    #[sqlx(rename = "numericId")]
+    numeric_id: i32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BulkLimitChange {
+    trader_id: String,
+    email: String,
+    numeric_id: i32,
+    previous_limit: AmountRange,
+    limit: AmountRange,
+    previous_max_open_payouts: Option<u32>,
+    max_open_payouts: Option<u32>,
+    changed: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BulkLimitResponse {
+    preview: bool,
+    /// Traders whose range or cap changes (or would change).
+    affected: usize,
+    traders: Vec<BulkLimitChange>,
+}
+
+async fn load_targets(
+    pool: &PgPool,
+    request: &BulkLimitRequest,
+    merchant_ids: Option<&[String]>,
+) -> ApiResult<Vec<TargetTrader>> {
+    if request.all {
+        let filters = TraderListFilters {
+            search: request
+                .search
+                .as_deref()
+                .map(str::trim)
+                .filter(|search| !search.is_empty())
+                .map(str::to_string),
+            merchant_ids: merchant_ids.map(<[String]>::to_vec),
+            ..TraderListFilters::default()
+        };
+        let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(format!(
+            "SELECT t.\"id\", t.\"email\", t.\"numericId\" FROM ({ELIGIBLE_TRADERS_QUERY}) t WHERE TRUE"
+        ));
+        apply_trader_search(&mut builder, &filters);
+        builder.push(" ORDER BY t.\"numericId\"");
+        return builder
+            .build_query_as::<TargetTrader>()
+            .fetch_all(pool)
+            .await
+            .map_err(internal_error);
+    }
+
+    let mut trader_ids = request.trader_ids.clone();
+    trader_ids.sort();
+    trader_ids.dedup();
+    let targets = sqlx::query_as::<_, TargetTrader>(
+        r#"
+        SELECT u."id", u."email", u."numericId"
+        FROM "User" u
+        WHERE u."id" = ANY($1::text[])
+          AND (
+              $2::text[] IS NULL
+              OR EXISTS (
+                  SELECT 1
+                  FROM "TraderMerchant" tm
+                  WHERE tm."traderId" = u."id"
+                    AND tm."merchantId" = ANY($2::text[])
+              )
+          )
+        ORDER BY u."numericId"
+        "#,
+    )
+    .bind(&trader_ids)
+    .bind(merchant_ids)
+    .fetch_all(pool)
+    .await
+    .map_err(internal_error)?;
+    if let Some(missing) = trader_ids
+        .iter()
+        .find(|trader_id| !targets.iter().any(|target| &target.id == *trader_id))
+    {
+        return Err(ApiError::from((
+            StatusCode::NOT_FOUND,
+            format!("Trader not found: {missing}"),
+        )));
+    }
+    Ok(targets)
+}
+
+pub(crate) async fn apply_limit_template(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<BulkLimitRequest>,
+) -> ApiResult<Json<BulkLimitResponse>> {
+    if request.all != request.trader_ids.is_empty() {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "Pass either traderIds or all".to_string(),
+        )));
+    }
+    if request.limit.is_none() && request.max_open_payouts.is_none() {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "The template sets neither limit nor maxOpenPayouts".to_string(),
+        )));
+    }
+    let limit = request
+        .limit
+        .map(AmountRange::validated)
+        .transpose()
+        .map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+    let max_open_payouts = request
+        .max_open_payouts
+        .map(|cap| {
+            i32::try_from(cap)
+                .map(|_| (cap > 0).then_some(cap))
+                .map_err(|_| {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        "maxOpenPayouts is too large".to_string(),
+                    )
+                })
+        })
+        .transpose()?;
+
+    let targets = load_targets(&state.pool, &request, scope.merchant_ids()).await?;
+    let limits = state.settings.limits().await;
+    let caps: HashMap<String, u32> = fetch_capacity_overrides(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    let traders: Vec<BulkLimitChange> = targets
+        .into_iter()
+        .map(|target| {
+            let previous_limit = limits.get(&target.id).copied().unwrap_or_default();
+            let previous_max_open_payouts = caps.get(&target.id).copied();
+            let limit = limit.unwrap_or(previous_limit);
+            let max_open_payouts = max_open_payouts.unwrap_or(previous_max_open_payouts);
+            BulkLimitChange {
+                changed: limit != previous_limit || max_open_payouts != previous_max_open_payouts,
+                trader_id: target.id,
+                email: target.email,
+                numeric_id: target.numeric_id,
+                previous_limit,
+                limit,
+                previous_max_open_payouts,
+                max_open_payouts,
+            }
+        })
+        .collect();
+    let affected = traders.iter().filter(|trader| trader.changed).count();
+    if request.preview || affected == 0 {
+        return Ok(Json(BulkLimitResponse {
+            preview: request.preview,
+            affected,
+            traders,
+        }));
+    }
+
+    let actor = auth::audit_actor(&session, &scope).await?;
+    if let Some(limit) = limit {
+        let trader_ids: Vec<String> = traders
+            .iter()
+            .filter(|trader| trader.previous_limit != limit)
+            .map(|trader| trader.trader_id.clone())
+            .collect();
+        if !trader_ids.is_empty() {
+            state
+                .settings
+                .update_trader_limits(&trader_ids, limit)
+                .await;
+        }
+    }
+    if let Some(cap) = max_open_payouts {
+        let trader_ids: Vec<&str> = traders
+            .iter()
+            .filter(|trader| trader.previous_max_open_payouts != cap)
+            .map(|trader| trader.trader_id.as_str())
+            .collect();
+        if !trader_ids.is_empty() {
+            update_capacities(&state.pool, &trader_ids, cap).await?;
+            println!(
+                "[settings] Updated trader capacity: trader={} maxOpenPayouts={:?}",
+                trader_ids.join(","),
+                cap
+            );
+            let _ = state.event_tx.send(ServerEvent::limits_updated());
+        }
+    }
+
+    for trader in traders.iter().filter(|trader| trader.changed) {
+        if trader.limit != trader.previous_limit {
+            audit_change(
+                &state.pool,
+                "trader-limit",
+                Some(&trader.trader_id),
+                actor.as_deref(),
+                trader.previous_limit,
+                trader.limit,
+            )
+            .await;
+        }
+        if trader.max_open_payouts != trader.previous_max_open_payouts {
+            audit_change(
+                &state.pool,
+                "trader-capacity",
+                Some(&trader.trader_id),
+                actor.as_deref(),
+                serde_json::json!({ "maxOpenPayouts": trader.previous_max_open_payouts }),
+                serde_json::json!({ "maxOpenPayouts": trader.max_open_payouts }),
+            )
+            .await;
+        }
+    }
+
+    Ok(Json(BulkLimitResponse {
+        preview: false,
+        affected,
+        traders,
+    }))
+}
+
+/// Sets (or with `None` drops) the own open-payout cap of every trader.
+async fn update_capacities(pool: &PgPool, trader_ids: &[&str], cap: Option<u32>) -> ApiResult<()> {
+    match cap {
+        Some(cap) => {
+            sqlx::query(
+                r#"
+                INSERT INTO "TraderCapacity" ("traderId", "maxOpenPayouts")
+                SELECT batch."traderId", $2
+                FROM UNNEST($1::text[]) AS batch("traderId")
+                ON CONFLICT ("traderId") DO UPDATE
+                SET "maxOpenPayouts" = EXCLUDED."maxOpenPayouts",
+                    "updatedAt" = CURRENT_TIMESTAMP
+                "#,
+            )
+            .bind(trader_ids)
+            .bind(cap as i32)
+            .execute(pool)
+            .await
+            .map_err(internal_error)?;
+        }
+        None => {
+            sqlx::query(r#"DELETE FROM "TraderCapacity" WHERE "traderId" = ANY($1::text[])"#)
+                .bind(trader_ids)
+                .execute(pool)
+                .await
+                .map_err(internal_error)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,142 @@
+//! Guards against request pile-ups. When Postgres stalls, handlers wait for
+//! connections and new requests keep arriving; without a cap they queue up
+//! until the pool is exhausted for everyone. Every request therefore runs
+//! under:
+//!
+//! - a timeout, `REQUEST_TIMEOUT_SECONDS` (default 30), answered with 408;
+//! - a body size limit, `REQUEST_BODY_LIMIT_BYTES` (default 1 MiB), payout
+//!   file uploads keep their own;
+//! - a global concurrency cap, `MAX_CONCURRENT_REQUESTS` (default 64), and a
+//!   tighter one for the heavy listings and exports,
+//!   `MAX_CONCURRENT_HEAVY_REQUESTS` (default 4). A request that finds no
+//!   free slot within `QUEUE_WAIT` gets 503 with `Retry-After`.
+//!
+//! The SSE stream is exempt from the caps since it stays open for the whole
+//! session.
+
+use std::{env, sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time,
+};
+use tokio_stream::StreamExt;
+
+const QUEUE_WAIT: Duration = Duration::from_secs(2);
+const HEAVY_PATHS: &[&str] = &["/api/deals", "/api/callbacks/export", "/api/audit/export"];
+const UNLIMITED_PATHS: &[&str] = &["/api/events"];
+
+#[derive(Debug, Clone)]
+pub(crate) struct RequestLimits {
+    timeout: Duration,
+    body_limit: usize,
+    max_requests: usize,
+    max_heavy_requests: usize,
+    requests: Arc<Semaphore>,
+    heavy_requests: Arc<Semaphore>,
+}
+
+impl RequestLimits {
+    pub(crate) fn from_env() -> Result<Self> {
+        let max_requests = number_env("MAX_CONCURRENT_REQUESTS", 64)?.max(1);
+        let max_heavy_requests = number_env("MAX_CONCURRENT_HEAVY_REQUESTS", 4)?.max(1);
+        Ok(Self {
+            timeout: Duration::from_secs(number_env("REQUEST_TIMEOUT_SECONDS", 30)?.max(1) as u64),
+            body_limit: number_env("REQUEST_BODY_LIMIT_BYTES", 1024 * 1024)?.max(1024),
+            max_requests,
+            max_heavy_requests,
+            requests: Arc::new(Semaphore::new(max_requests)),
+            heavy_requests: Arc::new(Semaphore::new(max_heavy_requests)),
+        })
+    }
+
+    pub(crate) fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub(crate) fn body_limit(&self) -> usize {
+        self.body_limit
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        format!(
+            "timeout {} s, body limit {} bytes, {} concurrent requests ({} heavy)",
+            self.timeout.as_secs(),
+            self.body_limit,
+            self.max_requests,
+            self.max_heavy_requests
+        )
+    }
+}
+
+async fn acquire(semaphore: &Arc<Semaphore>) -> Option<OwnedSemaphorePermit> {
+    time::timeout(QUEUE_WAIT, Arc::clone(semaphore).acquire_owned())
+        .await
+        .ok()?
+        .ok()
+}
+
+fn overloaded(path: &str) -> Response {
+    eprintln!("[limits] Rejected {path}, too many concurrent requests");
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::RETRY_AFTER, "1")],
+        "Too many concurrent requests, try again shortly".to_string(),
+    )
+        .into_response()
+}
+
+/// Middleware applied to the whole router, outside the session layer so
+/// waiting requests do not load sessions yet.
+pub(crate) async fn limit_concurrency(
+    State(limits): State<RequestLimits>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_string();
+    if UNLIMITED_PATHS.contains(&path.as_str()) {
+        return next.run(request).await;
+    }
+    let Some(permit) = acquire(&limits.requests).await else {
+        return overloaded(&path);
+    };
+    if !HEAVY_PATHS.contains(&path.as_str()) {
+        let response = next.run(request).await;
+        drop(permit);
+        return response;
+    }
+
+    let Some(heavy_permit) = acquire(&limits.heavy_requests).await else {
+        return overloaded(&path);
+    };
+    // Exports stream their body after the handler returned, so both permits
+    // are held until the body is done.
+    next.run(request).await.map(|body| {
+        let permits = (permit, heavy_permit);
+        Body::from_stream(body.into_data_stream().map(move |chunk| {
+            let _ = &permits;
+            chunk
+        }))
+    })
+}
+
+fn number_env(name: &str, default: usize) -> Result<usize> {
+    match env::var(name)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+    {
+        Some(value) => value
+            .parse::<usize>()
+            .with_context(|| format!("{name} must be a non-negative integer")),
+        None => Ok(default),
+    }
+}
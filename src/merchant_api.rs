@@ -0,0 +1,148 @@
+//! Status lookups for merchants, so they can poll instead of relying on
+//! callbacks alone. A merchant authenticates with the token it already
+//! receives callbacks with, sent back in the `x-merchant-api-key` header, and
+//! only sees its own payouts. Statuses use the callback spelling
+//! (`CANCELED`), matching what the merchant's callback handler expects.
+
+use axum::{
+    Json, async_trait,
+    extract::{FromRequestParts, Path, State},
+    http::{StatusCode, request::Parts},
+};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+
+use crate::{
+    ApiResult, AppState, currencies::PAYOUT_CURRENCY_SQL, errors::ApiError, internal_error,
+    payout_status::PayoutStatus, timestamps::UtcTimestamp,
+};
+
+pub(crate) const API_KEY_HEADER: &str = "x-merchant-api-key";
+
+/// The merchant behind a status request.
+#[derive(Debug, Clone)]
+pub(crate) struct MerchantScope {
+    merchant_id: String,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for MerchantScope {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> ApiResult<Self> {
+        let unauthorized = || {
+            ApiError::from((
+                StatusCode::UNAUTHORIZED,
+                format!("A valid merchant API key is required in {API_KEY_HEADER}"),
+            ))
+        };
+        let api_key = parts
+            .headers
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .ok_or_else(unauthorized)?;
+
+        merchant_for_token(&state.pool, api_key)
+            .await
+            .map_err(internal_error)?
+            .map(|merchant_id| MerchantScope { merchant_id })
+            .ok_or_else(unauthorized)
+    }
+}
+
+/// The merchant a callback token belongs to. The stored token is compared
+/// as is, so the lookup can use an index on `"token"`; only the input is
+/// trimmed.
+pub(crate) async fn merchant_for_token(pool: &PgPool, token: &str) -> sqlx::Result<Option<String>> {
+    sqlx::query_scalar(r#"SELECT "id" FROM "Merchant" WHERE "token" = $1 LIMIT 1"#)
+        .bind(token.trim())
+        .fetch_optional(pool)
+        .await
+}
+
+#[derive(Debug, FromRow)]
+struct MerchantPayoutRow {
+    id: String,
+    #[sqlx(rename = "externalReference")]
+    external_reference: String,
+    status: PayoutStatus,
+    amount: f64,
+    currency: String,
+    #[sqlx(rename = "cancelReason")]
+    cancel_reason: Option<String>,
+    #[sqlx(rename = "cancelReasonCode")]
+    cancel_reason_code: Option<String>,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+    #[sqlx(rename = "updatedAt")]
+    updated_at: UtcTimestamp,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MerchantPayoutStatus {
+    id: String,
+    external_reference: String,
+    status: &'static str,
+    amount: f64,
+    currency: String,
+    cancel_reason: Option<String>,
+    cancel_reason_code: Option<String>,
+    created_at: UtcTimestamp,
+    updated_at: UtcTimestamp,
+}
+
+impl From<MerchantPayoutRow> for MerchantPayoutStatus {
+    fn from(row: MerchantPayoutRow) -> Self {
+        Self {
+            id: row.id,
+            external_reference: row.external_reference,
+            status: row.status.callback_name(),
+            amount: row.amount,
+            currency: row.currency,
+            cancel_reason: row.cancel_reason,
+            cancel_reason_code: row.cancel_reason_code,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+/// `GET /api/merchant/payouts/:externalReference`. Should a reference have
+/// been reused, the latest payout answers.
+pub(crate) async fn get_merchant_payout_status(
+    Path(external_reference): Path<String>,
+    State(state): State<AppState>,
+    merchant: MerchantScope,
+) -> ApiResult<Json<MerchantPayoutStatus>> {
+    let row = sqlx::query_as::<_, MerchantPayoutRow>(&format!(
+        r#"
+        SELECT
+            p."id",
+            p."externalReference",
+            p."status"::text AS "status",
+            p."amount",
+            {PAYOUT_CURRENCY_SQL} AS "currency",
+            p."cancelReason",
+            p."cancelReasonCode",
+            p."createdAt",
+            p."updatedAt"
+        FROM "Payout" p
+        WHERE p."direction" = 'OUT'
+          AND p."merchantId" = $1
+          AND p."externalReference" = $2
+        ORDER BY p."createdAt" DESC
+        LIMIT 1
+        "#
+    ))
+    .bind(&merchant.merchant_id)
+    .bind(external_reference.trim())
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    row.map(|row| Json(row.into()))
+        .ok_or_else(ApiError::payout_not_found)
+}
@@ -0,0 +1,238 @@
+//! Read-only status page for merchants at `/merchant/:token`. It lists the
+//! merchant's recent payouts with their statuses and shows how callback
+//! delivery to the merchant is doing, so merchants can check a payout or a
+//! missing callback themselves before contacting support.
+//!
+//! The token is a page token of its own, never the merchant's callback
+//! token: a link ends up in browser history and proxy logs, and the callback
+//! token would let its holder forge callbacks. Page tokens are issued through
+//! `POST /api/merchants/:id/page-token`, revoked with `DELETE` on the same
+//! path, and only their SHA-256 hash is stored; each merchant has at most one
+//! active token.
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{Html, IntoResponse, Response},
+};
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::{
+    ApiResult, AppState,
+    callback_sla::{self, SlaWindow},
+    db::{PayoutDealListItem, PayoutListFilters, fetch_payouts_page},
+    errors::ApiError,
+    frontend, i18n, internal_error,
+    tenant::TenantScope,
+    timestamps::UtcTimestamp,
+    trader_auth::hash_token,
+};
+
+const TOKEN_PREFIX: &str = "mpg_";
+const RECENT_PAYOUTS: u32 = 50;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MerchantPageTokenResponse {
+    merchant_id: String,
+    token: String,
+    /// Relative link to the status page.
+    url: String,
+}
+
+fn merchant_not_found() -> ApiError {
+    ApiError::from((StatusCode::NOT_FOUND, "Merchant not found".to_string()))
+}
+
+/// `POST /api/merchants/:id/page-token`. Replaces any previous token; the
+/// plain token is only returned here.
+pub(crate) async fn issue_page_token(
+    Path(merchant_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<MerchantPageTokenResponse>> {
+    if !scope.allows_merchant(Some(&merchant_id)) {
+        return Err(merchant_not_found());
+    }
+    let exists: bool =
+        sqlx::query_scalar(r#"SELECT EXISTS (SELECT 1 FROM "Merchant" WHERE "id" = $1)"#)
+            .bind(&merchant_id)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(internal_error)?;
+    if !exists {
+        return Err(merchant_not_found());
+    }
+
+    let token = format!(
+        "{TOKEN_PREFIX}{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    );
+    sqlx::query(
+        r#"
+        INSERT INTO "MerchantPageToken" ("merchantId", "tokenHash")
+        VALUES ($1, $2)
+        ON CONFLICT ("merchantId") DO UPDATE
+        SET "tokenHash" = EXCLUDED."tokenHash",
+            "createdAt" = CURRENT_TIMESTAMP,
+            "lastUsedAt" = NULL
+        "#,
+    )
+    .bind(&merchant_id)
+    .bind(hash_token(&token))
+    .execute(&state.pool)
+    .await
+    .map_err(internal_error)?;
+    println!("[merchant-page] Issued a new page token for merchant {merchant_id}");
+    Ok(Json(MerchantPageTokenResponse {
+        merchant_id,
+        url: format!("/merchant/{token}"),
+        token,
+    }))
+}
+
+/// `DELETE /api/merchants/:id/page-token`.
+pub(crate) async fn revoke_page_token(
+    Path(merchant_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<StatusCode> {
+    if !scope.allows_merchant(Some(&merchant_id)) {
+        return Err(merchant_not_found());
+    }
+    let result = sqlx::query(r#"DELETE FROM "MerchantPageToken" WHERE "merchantId" = $1"#)
+        .bind(&merchant_id)
+        .execute(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::from((
+            StatusCode::NOT_FOUND,
+            "Merchant has no active page token".to_string(),
+        )));
+    }
+    println!("[merchant-page] Revoked the page token of merchant {merchant_id}");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The merchant a page token belongs to. Records the use.
+async fn merchant_for_page_token(pool: &PgPool, token: &str) -> sqlx::Result<Option<String>> {
+    if !token.starts_with(TOKEN_PREFIX) {
+        return Ok(None);
+    }
+    sqlx::query_scalar(
+        r#"
+        UPDATE "MerchantPageToken"
+        SET "lastUsedAt" = CURRENT_TIMESTAMP
+        WHERE "tokenHash" = $1
+        RETURNING "merchantId"
+        "#,
+    )
+    .bind(hash_token(token))
+    .fetch_optional(pool)
+    .await
+}
+
+#[derive(Debug, FromRow)]
+struct CallbackQueue {
+    retrying: i64,
+    #[sqlx(rename = "deadLettered")]
+    dead_lettered: i64,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct MerchantPage {
+    pub(crate) merchant_id: String,
+    pub(crate) name: Option<String>,
+    /// Latest payouts first, at most [`RECENT_PAYOUTS`].
+    pub(crate) payouts: Vec<PayoutDealListItem>,
+    pub(crate) total_payouts: i64,
+    pub(crate) sla_windows: Vec<SlaWindow>,
+    /// Callbacks that failed at least once and wait for a retry.
+    pub(crate) callbacks_retrying: i64,
+    /// Callbacks that ran out of retries.
+    pub(crate) callbacks_dead_lettered: i64,
+    pub(crate) generated_at: UtcTimestamp,
+}
+
+pub(crate) async fn merchant_page(
+    Path(token): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let merchant_id = merchant_for_page_token(&state.pool, token.trim())
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| {
+            ApiError::from((
+                StatusCode::NOT_FOUND,
+                "Unknown or revoked merchant link".to_string(),
+            ))
+        })?;
+    let page = load_page(&state, merchant_id).await?;
+    let lang = i18n::Lang::from_headers(&headers);
+    let theme = frontend::Theme::from_headers(&headers);
+    // The token is in the URL: keep the page out of caches and referrers.
+    Ok((
+        [
+            (header::CACHE_CONTROL, "no-store"),
+            (header::REFERRER_POLICY, "no-referrer"),
+        ],
+        Html(frontend::render_merchant_page(page, lang, theme)),
+    )
+        .into_response())
+}
+
+async fn load_page(state: &AppState, merchant_id: String) -> ApiResult<MerchantPage> {
+    let name: Option<String> =
+        sqlx::query_scalar(r#"SELECT "name" FROM "Merchant" WHERE "id" = $1"#)
+            .bind(&merchant_id)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(internal_error)?;
+
+    let filters = PayoutListFilters {
+        per_page: RECENT_PAYOUTS,
+        merchant_ids: Some(vec![merchant_id.clone()]),
+        ..PayoutListFilters::default()
+    };
+    let payouts = fetch_payouts_page(&state.pool, &filters)
+        .await
+        .map_err(internal_error)?;
+    let sla_windows = callback_sla::fetch_windows(&state.pool, &state.callback_sla, &merchant_id)
+        .await
+        .map_err(internal_error)?;
+    let queue = sqlx::query_as::<_, CallbackQueue>(
+        r#"
+        SELECT
+            COUNT(*) FILTER (WHERE o."status" = 'pending' AND o."attempts" > 0)::bigint
+                AS "retrying",
+            COUNT(*) FILTER (WHERE o."status" = 'failed')::bigint AS "deadLettered"
+        FROM "OutboxMessage" o
+        JOIN "Payout" p
+            ON p."id" = o."payload"->>'payoutId'
+        WHERE o."kind" = 'callback'
+          AND p."merchantId" = $1
+        "#,
+    )
+    .bind(&merchant_id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    Ok(MerchantPage {
+        merchant_id,
+        name,
+        payouts: payouts.items,
+        total_payouts: payouts.total,
+        sla_windows,
+        callbacks_retrying: queue.retrying,
+        callbacks_dead_lettered: queue.dead_lettered,
+        generated_at: Utc::now().into(),
+    })
+}
@@ -0,0 +1,192 @@
+//! A stand-in merchant backend for development: `chase-linker mock-merchant`
+//! (or `--mock-merchant`) runs a small server that logs every callback it
+//! receives and acknowledges it, without touching the database. Point a
+//! merchant's callback override (or `Payout.merchantWebhookUrl`) at it to
+//! exercise the dispatcher and its retries.
+//!
+//! Behaviour is configured with `MOCK_MERCHANT_PORT` (default 5556),
+//! `MOCK_MERCHANT_LATENCY_MS` (a fixed delay like `200` or a range like
+//! `100-800`, default 0), `MOCK_MERCHANT_FAILURE_RATE` (share of callbacks
+//! answered with an error, `0` to `1`, default 0) and
+//! `MOCK_MERCHANT_FAILURE_STATUS` (default 500).
+
+use std::{
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::env::non_empty_env;
+
+const MAX_LOGGED_BODY_CHARS: usize = 2000;
+
+#[derive(Debug, Clone)]
+struct MockConfig {
+    port: u16,
+    latency_ms: (u64, u64),
+    failure_rate: f64,
+    failure_status: StatusCode,
+}
+
+impl MockConfig {
+    fn from_env() -> Result<Self> {
+        let port = match non_empty_env("MOCK_MERCHANT_PORT") {
+            Some(value) => value
+                .parse()
+                .context("MOCK_MERCHANT_PORT must be a port number")?,
+            None => 5556,
+        };
+        let latency_ms = match non_empty_env("MOCK_MERCHANT_LATENCY_MS") {
+            Some(value) => parse_latency(&value)
+                .context("MOCK_MERCHANT_LATENCY_MS must be milliseconds or a range like 100-800")?,
+            None => (0, 0),
+        };
+        let failure_rate = match non_empty_env("MOCK_MERCHANT_FAILURE_RATE") {
+            Some(value) => value
+                .parse::<f64>()
+                .ok()
+                .filter(|rate| (0.0..=1.0).contains(rate))
+                .context("MOCK_MERCHANT_FAILURE_RATE must be between 0 and 1")?,
+            None => 0.0,
+        };
+        let failure_status = match non_empty_env("MOCK_MERCHANT_FAILURE_STATUS") {
+            Some(value) => value
+                .parse::<u16>()
+                .ok()
+                .and_then(|code| StatusCode::from_u16(code).ok())
+                .filter(|status| !status.is_success())
+                .context("MOCK_MERCHANT_FAILURE_STATUS must be a non-2xx status code")?,
+            None => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Ok(Self {
+            port,
+            latency_ms,
+            failure_rate,
+            failure_status,
+        })
+    }
+
+    fn describe(&self) -> String {
+        let (min, max) = self.latency_ms;
+        let latency = if min == max {
+            format!("{min} ms")
+        } else {
+            format!("{min}-{max} ms")
+        };
+        format!(
+            "latency {latency}, failure rate {:.0}% ({})",
+            self.failure_rate * 100.0,
+            self.failure_status.as_u16()
+        )
+    }
+}
+
+fn parse_latency(value: &str) -> Option<(u64, u64)> {
+    match value.split_once('-') {
+        Some((min, max)) => {
+            let (min, max) = (min.trim().parse().ok()?, max.trim().parse().ok()?);
+            (min <= max).then_some((min, max))
+        }
+        None => value.parse().ok().map(|ms| (ms, ms)),
+    }
+}
+
+/// A uniform value in `[0, 1)` from the low, fully random bits of a v4 uuid;
+/// good enough for a mock.
+fn random_unit() -> f64 {
+    const BITS: u32 = 53;
+    (Uuid::new_v4().as_u128() as u64 & ((1 << BITS) - 1)) as f64 / (1u64 << BITS) as f64
+}
+
+struct MockState {
+    config: MockConfig,
+    received: AtomicU64,
+    failed: AtomicU64,
+}
+
+pub(crate) async fn run(args: &[String]) -> Result<()> {
+    if !args.is_empty() {
+        bail!("Usage: chase-linker mock-merchant (configured through MOCK_MERCHANT_* variables)");
+    }
+    let config = MockConfig::from_env()?;
+    let addr: SocketAddr = ([0, 0, 0, 0], config.port).into();
+    println!(
+        "[mock-merchant] Listening on http://{addr}, {}",
+        config.describe()
+    );
+    let state = Arc::new(MockState {
+        config,
+        received: AtomicU64::new(0),
+        failed: AtomicU64::new(0),
+    });
+    let app = Router::new().fallback(receive).with_state(state);
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .context("Failed to bind TCP listener")?;
+    axum::serve(listener, app)
+        .await
+        .context("Mock merchant error")?;
+    Ok(())
+}
+
+async fn receive(
+    State(state): State<Arc<MockState>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let number = state.received.fetch_add(1, Ordering::Relaxed) + 1;
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("-")
+            .to_string()
+    };
+    let mut text = String::from_utf8_lossy(&body).into_owned();
+    if text.chars().count() > MAX_LOGGED_BODY_CHARS {
+        text = text.chars().take(MAX_LOGGED_BODY_CHARS).collect::<String>() + "...";
+    }
+    println!(
+        "[mock-merchant] #{number} {method} {uri} idempotency key {}, signature {}: {text}",
+        header("x-idempotency-key"),
+        header("x-signature")
+    );
+
+    let (min, max) = state.config.latency_ms;
+    let latency = min + ((max - min + 1) as f64 * random_unit()) as u64;
+    if latency > 0 {
+        tokio::time::sleep(Duration::from_millis(latency)).await;
+    }
+
+    if random_unit() < state.config.failure_rate {
+        let failed = state.failed.fetch_add(1, Ordering::Relaxed) + 1;
+        let status = state.config.failure_status;
+        println!(
+            "[mock-merchant] #{number} answered {} ({failed} of {number} failed)",
+            status.as_u16()
+        );
+        return (
+            status,
+            Json(json!({ "ok": false, "error": "Simulated failure" })),
+        )
+            .into_response();
+    }
+    println!("[mock-merchant] #{number} acknowledged");
+    Json(json!({ "ok": true })).into_response()
+}
@@ -0,0 +1,126 @@
+//! Operator notes on payouts, so a shift hands over what it knows about a
+//! payout next to the payout itself. Notes are added and listed through
+//! `/api/payouts/:id/notes` and show up in the payout's timeline; they are
+//! kept with the author resolved by [`auth::audit_actor`] and cannot be
+//! edited afterwards.
+
+use axum::{
+    Json,
+    extract::{Extension, Path, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::{
+    ApiResult, AppState, auth, errors::ApiError, internal_error, tenant::TenantScope,
+    timestamps::UtcTimestamp,
+};
+
+const MAX_NOTE_CHARS: usize = 2000;
+
+#[derive(Debug, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PayoutNote {
+    pub(crate) id: String,
+    pub(crate) author: Option<String>,
+    pub(crate) body: String,
+    #[sqlx(rename = "createdAt")]
+    pub(crate) created_at: UtcTimestamp,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CreateNoteRequest {
+    body: String,
+}
+
+/// The payout's notes, oldest first.
+pub(crate) async fn load_notes(pool: &PgPool, payout_id: &str) -> sqlx::Result<Vec<PayoutNote>> {
+    sqlx::query_as::<_, PayoutNote>(
+        r#"
+        SELECT "id", "author", "body", "createdAt"
+        FROM "PayoutNote"
+        WHERE "payoutId" = $1
+        ORDER BY "createdAt", "id"
+        "#,
+    )
+    .bind(payout_id)
+    .fetch_all(pool)
+    .await
+}
+
+async fn ensure_payout_in_scope(
+    pool: &PgPool,
+    payout_id: &str,
+    scope: &TenantScope,
+) -> ApiResult<()> {
+    let merchant_id: Option<Option<String>> =
+        sqlx::query_scalar(r#"SELECT "merchantId" FROM "Payout" WHERE "id" = $1"#)
+            .bind(payout_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(internal_error)?;
+    if merchant_id.is_none_or(|merchant_id| !scope.allows_merchant(merchant_id.as_deref())) {
+        return Err(ApiError::payout_not_found());
+    }
+    Ok(())
+}
+
+pub(crate) async fn get_payout_notes(
+    Path(payout_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<Vec<PayoutNote>>> {
+    ensure_payout_in_scope(&state.pool, &payout_id, &scope).await?;
+    let notes = load_notes(&state.pool, &payout_id)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(notes))
+}
+
+pub(crate) async fn create_payout_note(
+    Path(payout_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<CreateNoteRequest>,
+) -> ApiResult<(StatusCode, Json<PayoutNote>)> {
+    let body = request.body.trim();
+    if body.is_empty() {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "Note is empty".to_string(),
+        )));
+    }
+    if body.chars().count() > MAX_NOTE_CHARS {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            format!("Note must be at most {MAX_NOTE_CHARS} characters"),
+        )));
+    }
+    ensure_payout_in_scope(&state.pool, &payout_id, &scope).await?;
+    let author = auth::audit_actor(&session, &scope).await?;
+
+    let note = sqlx::query_as::<_, PayoutNote>(
+        r#"
+        INSERT INTO "PayoutNote" ("id", "payoutId", "author", "body")
+        VALUES ($1, $2, $3, $4)
+        RETURNING "id", "author", "body", "createdAt"
+        "#,
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(&payout_id)
+    .bind(&author)
+    .bind(body)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    println!(
+        "[manual] Note added to payout {payout_id} by {}",
+        author.as_deref().unwrap_or("shared dashboard")
+    );
+    Ok((StatusCode::CREATED, Json(note)))
+}
@@ -0,0 +1,462 @@
+//! Transactional outbox for payout side effects. SSE events, merchant
+//! callbacks and trader webhooks are written to `OutboxMessage` in the same transaction as the
+//! state change, and a relay publishes them after commit.
+//!
+//! Rows are claimed with a short lease instead of a long-lived transaction,
+//! so a crash mid-dispatch only delays delivery until the lease expires. A
+//! callback whose HTTP request succeeded but whose row was not yet marked can
+//! be sent again after such a crash; every callback carries the outbox id in
+//! `x-idempotency-key` so merchants can drop the duplicate.
+
+use std::{env, time::Duration};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, Postgres, Transaction};
+use tokio::{task::JoinSet, time};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    callback_http::CallbackOverride,
+    callbacks::{CallbackDispatchResult, CallbackTarget},
+    client_certs::ClientCertificate,
+    db_retry,
+    events::{EventAudience, ServerEvent},
+    timestamps::UtcTimestamp,
+    trader_webhook,
+};
+
+const KIND_EVENT: &str = "event";
+const KIND_CALLBACK: &str = "callback";
+pub(crate) const KIND_TRADER_WEBHOOK: &str = "trader-webhook";
+const RELAY_BATCH_SIZE: i64 = 50;
+const RELAY_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Callbacks of merchants with paused callbacks stay pending, see
+/// [`callback_pause`](crate::callback_pause).
+const CLAIM_QUERY: &str = r#"
+    UPDATE "OutboxMessage" o
+    SET "leasedUntil" = CURRENT_TIMESTAMP + INTERVAL '60 seconds',
+        "attempts" = o."attempts" + 1
+    WHERE o."id" IN (
+        SELECT m."id"
+        FROM "OutboxMessage" m
+        WHERE m."status" = 'pending'
+          AND m."nextAttemptAt" <= CURRENT_TIMESTAMP
+          AND (m."leasedUntil" IS NULL OR m."leasedUntil" < CURRENT_TIMESTAMP)
+          AND ($1::text IS NULL OR m."id" = $1)
+          AND NOT (
+              m."kind" = 'callback'
+              AND EXISTS (
+                  SELECT 1
+                  FROM "Payout" p
+                  JOIN "MerchantCallbackPause" cp ON cp."merchantId" = p."merchantId"
+                  WHERE p."id" = m."payload"->>'payoutId'
+              )
+          )
+        ORDER BY m."createdAt"
+        LIMIT $2
+        FOR UPDATE SKIP LOCKED
+    )
+    RETURNING o."id", o."kind", o."payload", o."attempts", o."createdAt"
+"#;
+
+#[derive(Debug, FromRow)]
+struct OutboxRow {
+    id: String,
+    kind: String,
+    payload: Value,
+    attempts: i32,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredEvent {
+    event_type: String,
+    message: Option<String>,
+    data: Option<Value>,
+    audience: EventAudience,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredCallback {
+    payout_id: String,
+    body: Value,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredTraderWebhook {
+    trader_id: String,
+    body: Value,
+}
+
+#[derive(Debug, FromRow)]
+struct CallbackTargetRow {
+    #[sqlx(rename = "merchantId")]
+    merchant_id: String,
+    #[sqlx(rename = "merchantWebhookUrl")]
+    webhook_url: Option<String>,
+    token: Option<String>,
+    headers: Option<Value>,
+    #[sqlx(rename = "timeoutSeconds")]
+    timeout_seconds: Option<i32>,
+    #[sqlx(rename = "proxyUrl")]
+    proxy_url: Option<String>,
+    certificate: Option<String>,
+    #[sqlx(rename = "privateKey")]
+    private_key: Option<String>,
+    #[sqlx(rename = "payloadTemplate")]
+    payload_template: Option<String>,
+}
+
+enum Outcome {
+    Delivered,
+    Retry(String),
+    Failed(String),
+    /// Over the merchant's rate limit; not counted as an attempt.
+    Deferred(Duration),
+}
+
+/// What became of a callback the relay picked up.
+enum CallbackDelivery {
+    Sent(CallbackDispatchResult),
+    RateLimited(Duration),
+}
+
+fn max_attempts() -> i32 {
+    env::var("OUTBOX_MAX_ATTEMPTS")
+        .ok()
+        .and_then(|value| value.trim().parse::<i32>().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(8)
+}
+
+/// 5s, 10s, 20s, … capped at one hour.
+fn backoff(attempts: i32) -> chrono::Duration {
+    let exponent = attempts.clamp(1, 10) as u32 - 1;
+    chrono::Duration::seconds((5_i64 << exponent).min(3600))
+}
+
+async fn insert(
+    tx: &mut Transaction<'_, Postgres>,
+    kind: &str,
+    payload: Value,
+) -> Result<String> {
+    let id = Uuid::new_v4().to_string();
+    sqlx::query(
+        r#"
+        INSERT INTO "OutboxMessage" ("id", "kind", "payload")
+        VALUES ($1, $2, $3)
+        "#,
+    )
+    .bind(&id)
+    .bind(kind)
+    .bind(payload)
+    .execute(&mut **tx)
+    .await
+    .context("Failed to write outbox message")?;
+    Ok(id)
+}
+
+pub(crate) async fn enqueue_event(
+    tx: &mut Transaction<'_, Postgres>,
+    event: &ServerEvent,
+) -> Result<String> {
+    let stored = StoredEvent {
+        event_type: event.event_type.clone(),
+        message: event.message.clone(),
+        data: event.data.clone(),
+        audience: event.audience.clone(),
+    };
+    insert(tx, KIND_EVENT, serde_json::to_value(stored)?).await
+}
+
+pub(crate) async fn enqueue_callback<T: Serialize>(
+    tx: &mut Transaction<'_, Postgres>,
+    payout_id: &str,
+    body: &T,
+) -> Result<String> {
+    let stored = StoredCallback {
+        payout_id: payout_id.to_string(),
+        body: serde_json::to_value(body).context("Failed to serialize callback payload")?,
+    };
+    insert(tx, KIND_CALLBACK, serde_json::to_value(stored)?).await
+}
+
+/// Delivers one message right away so handlers can report the callback
+/// result. Returns `None` when the relay already holds the row.
+pub(crate) async fn dispatch_now(
+    state: &AppState,
+    id: &str,
+) -> Result<Option<CallbackDispatchResult>> {
+    let rows = claim(state, Some(id), 1).await?;
+    let Some(row) = rows.into_iter().next() else {
+        return Ok(None);
+    };
+    process(state, row).await
+}
+
+pub(crate) async fn relay_worker(state: AppState) {
+    let mut interval = time::interval(RELAY_POLL_INTERVAL);
+    interval.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = state.outbox_notify.notified() => {}
+        }
+
+        loop {
+            match relay_batch(&state).await {
+                Ok(count) if count as i64 == RELAY_BATCH_SIZE => continue,
+                Ok(_) => break,
+                Err(err) => {
+                    eprintln!("[outbox] Relay error: {err:?}");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Events are published in order; webhooks are sent side by side, as many at
+/// once as the [`CallbackDispatcher`](crate::dispatcher::CallbackDispatcher)
+/// allows.
+async fn relay_batch(state: &AppState) -> Result<usize> {
+    let mut rows = claim(state, None, RELAY_BATCH_SIZE).await?;
+    rows.sort_by_key(|row| row.created_at);
+    let count = rows.len();
+    let mut deliveries = JoinSet::new();
+    for row in rows {
+        if row.kind == KIND_EVENT {
+            process_logged(state, row).await;
+        } else {
+            let state = state.clone();
+            deliveries.spawn(async move { process_logged(&state, row).await });
+        }
+    }
+    while deliveries.join_next().await.is_some() {}
+    Ok(count)
+}
+
+async fn process_logged(state: &AppState, row: OutboxRow) {
+    let id = row.id.clone();
+    if let Err(err) = process(state, row).await {
+        eprintln!("[outbox] Failed to process message {id}: {err:?}");
+    }
+}
+
+async fn claim(state: &AppState, id: Option<&str>, limit: i64) -> Result<Vec<OutboxRow>> {
+    sqlx::query_as::<_, OutboxRow>(CLAIM_QUERY)
+        .bind(id)
+        .bind(limit)
+        .fetch_all(&state.pool)
+        .await
+        .context("Failed to claim outbox messages")
+}
+
+async fn process(state: &AppState, row: OutboxRow) -> Result<Option<CallbackDispatchResult>> {
+    let (outcome, callback_result) = match row.kind.as_str() {
+        KIND_EVENT => match serde_json::from_value::<StoredEvent>(row.payload.clone()) {
+            Ok(stored) => {
+                let _ = state.event_tx.send(ServerEvent {
+                    event_type: stored.event_type,
+                    message: stored.message,
+                    data: stored.data,
+                    audience: stored.audience,
+                    version: None,
+                });
+                (Outcome::Delivered, None)
+            }
+            Err(err) => (Outcome::Failed(format!("Malformed event: {err}")), None),
+        },
+        KIND_CALLBACK => match serde_json::from_value::<StoredCallback>(row.payload.clone()) {
+            Ok(stored) => match deliver_callback(state, &row.id, &stored).await? {
+                CallbackDelivery::Sent(result) => (dispatch_outcome(&result), Some(result)),
+                CallbackDelivery::RateLimited(wait) => (Outcome::Deferred(wait), None),
+            },
+            Err(err) => (Outcome::Failed(format!("Malformed callback: {err}")), None),
+        },
+        KIND_TRADER_WEBHOOK => {
+            match serde_json::from_value::<StoredTraderWebhook>(row.payload.clone()) {
+                Ok(stored) => {
+                    let _permit = state.dispatcher.acquire().await;
+                    let result =
+                        trader_webhook::deliver(state, &row.id, &stored.trader_id, &stored.body)
+                            .await?;
+                    (dispatch_outcome(&result), None)
+                }
+                Err(err) => (Outcome::Failed(format!("Malformed trader webhook: {err}")), None),
+            }
+        }
+        other => (Outcome::Failed(format!("Unknown outbox kind {other}")), None),
+    };
+
+    finish(state, &row, outcome).await?;
+    Ok(callback_result)
+}
+
+/// Requests that were sent are retried; ones that could not be sent at all
+/// (nothing configured) are not.
+fn dispatch_outcome(result: &CallbackDispatchResult) -> Outcome {
+    if result.was_delivered() {
+        Outcome::Delivered
+    } else if result.attempted {
+        Outcome::Retry(result.error.clone().unwrap_or_default())
+    } else {
+        Outcome::Failed(result.error.clone().unwrap_or_default())
+    }
+}
+
+async fn deliver_callback(
+    state: &AppState,
+    outbox_id: &str,
+    stored: &StoredCallback,
+) -> Result<CallbackDelivery> {
+    let row = sqlx::query_as::<_, CallbackTargetRow>(
+        r#"
+        SELECT
+            p."merchantId",
+            COALESCE(o."webhookUrl", p."merchantWebhookUrl") AS "merchantWebhookUrl",
+            m."token",
+            o."headers",
+            o."timeoutSeconds",
+            o."proxyUrl",
+            c."certificate",
+            c."privateKey",
+            t."template" AS "payloadTemplate"
+        FROM "Payout" p
+        LEFT JOIN "Merchant" m
+            ON m."id" = p."merchantId"
+        LEFT JOIN "MerchantCallbackOverride" o
+            ON o."merchantId" = p."merchantId"
+        LEFT JOIN "MerchantCallbackTemplate" t
+            ON t."merchantId" = p."merchantId"
+        LEFT JOIN "MerchantClientCertificate" c
+            ON c."merchantId" = p."merchantId"
+        WHERE p."id" = $1
+        "#,
+    )
+    .bind(&stored.payout_id)
+    .fetch_optional(&state.pool)
+    .await
+    .context("Failed to load callback target")?;
+
+    if let Some(row) = &row
+        && let Err(wait) = state.dispatcher.take_token(&row.merchant_id)
+    {
+        return Ok(CallbackDelivery::RateLimited(wait));
+    }
+    let target = match row {
+        Some(row) => CallbackTarget {
+            webhook_url: row.webhook_url,
+            merchant_token: row.token,
+            overrides: CallbackOverride {
+                headers: CallbackOverride::headers_from_stored(
+                    row.headers.as_ref(),
+                    state.secrets.as_ref(),
+                )?,
+                timeout_seconds: row.timeout_seconds,
+                webhook_url: None,
+                proxy_url: row.proxy_url,
+            },
+            payload_template: row.payload_template,
+            client_certificate: row.certificate.zip(row.private_key).map(
+                |(certificate, encrypted_key)| ClientCertificate {
+                    certificate,
+                    encrypted_key,
+                },
+            ),
+        },
+        None => CallbackTarget::default(),
+    };
+
+    let _permit = state.dispatcher.acquire().await;
+    crate::callbacks::dispatch_payout_callback(
+        state,
+        &stored.payout_id,
+        &target,
+        &stored.body,
+        outbox_id,
+    )
+    .await
+    .map(CallbackDelivery::Sent)
+}
+
+/// Releases a rate-limited message for a later pass and takes back the
+/// attempt its claim counted.
+async fn defer(state: &AppState, row: &OutboxRow, wait: Duration) -> Result<()> {
+    let next_attempt_at =
+        Utc::now().naive_utc() + chrono::Duration::from_std(wait).unwrap_or_default();
+    db_retry::with_retry("Outbox update", || async {
+        sqlx::query(
+            r#"
+            UPDATE "OutboxMessage"
+            SET "leasedUntil" = NULL,
+                "nextAttemptAt" = $2,
+                "attempts" = GREATEST("attempts" - 1, 0)
+            WHERE "id" = $1
+            "#,
+        )
+        .bind(&row.id)
+        .bind(next_attempt_at)
+        .execute(&state.pool)
+        .await
+        .context("Failed to defer outbox message")
+    })
+    .await?;
+    Ok(())
+}
+
+async fn finish(state: &AppState, row: &OutboxRow, outcome: Outcome) -> Result<()> {
+    let (status, next_attempt_at, error) = match outcome {
+        Outcome::Deferred(wait) => return defer(state, row, wait).await,
+        Outcome::Delivered => ("dispatched", None, None),
+        Outcome::Retry(error) if row.attempts < max_attempts() => (
+            "pending",
+            Some((Utc::now() + backoff(row.attempts)).naive_utc()),
+            Some(error),
+        ),
+        Outcome::Retry(error) | Outcome::Failed(error) => ("failed", None, Some(error)),
+    };
+    if status == "failed" {
+        eprintln!(
+            "[outbox] Giving up on {} message {} after {} attempt(s): {}",
+            row.kind,
+            row.id,
+            row.attempts,
+            error.as_deref().unwrap_or_default()
+        );
+    }
+
+    db_retry::with_retry("Outbox update", || async {
+        sqlx::query(
+            r#"
+            UPDATE "OutboxMessage"
+            SET "status" = $2,
+                "leasedUntil" = NULL,
+                "nextAttemptAt" = COALESCE($3, "nextAttemptAt"),
+                "lastError" = $4,
+                "dispatchedAt" = CASE WHEN $2 = 'dispatched' THEN CURRENT_TIMESTAMP ELSE NULL END,
+                "failedAt" = CASE WHEN $2 = 'failed' THEN CURRENT_TIMESTAMP ELSE NULL END
+            WHERE "id" = $1
+            "#,
+        )
+        .bind(&row.id)
+        .bind(status)
+        .bind(next_attempt_at)
+        .bind(&error)
+        .execute(&state.pool)
+        .await
+        .context("Failed to update outbox message")
+    })
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,169 @@
+//! The canonical payout status vocabulary, mirroring the `PayoutStatus`
+//! database enum. Statuses are spelled as in the database everywhere in the
+//! API (`CANCELLED`); `CANCELED` is accepted as an alias on input. Merchant
+//! callbacks keep their historical spelling, see [`PayoutStatus::callback_name`].
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::{
+    Decode, Postgres, Type,
+    error::BoxDynError,
+    postgres::{PgTypeInfo, PgValueRef},
+};
+
+use crate::payout_transitions;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum PayoutStatus {
+    Created,
+    Active,
+    Available,
+    Checking,
+    Processing,
+    Cancelled,
+    Completed,
+    Success,
+    Failed,
+    Disputed,
+    Expired,
+    Dispute,
+}
+
+impl PayoutStatus {
+    /// In the order of the database enum.
+    pub(crate) const ALL: [PayoutStatus; 12] = [
+        PayoutStatus::Created,
+        PayoutStatus::Active,
+        PayoutStatus::Available,
+        PayoutStatus::Checking,
+        PayoutStatus::Processing,
+        PayoutStatus::Cancelled,
+        PayoutStatus::Completed,
+        PayoutStatus::Success,
+        PayoutStatus::Failed,
+        PayoutStatus::Disputed,
+        PayoutStatus::Expired,
+        PayoutStatus::Dispute,
+    ];
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Created => "CREATED",
+            Self::Active => "ACTIVE",
+            Self::Available => "AVAILABLE",
+            Self::Checking => "CHECKING",
+            Self::Processing => "PROCESSING",
+            Self::Cancelled => "CANCELLED",
+            Self::Completed => "COMPLETED",
+            Self::Success => "SUCCESS",
+            Self::Failed => "FAILED",
+            Self::Disputed => "DISPUTED",
+            Self::Expired => "EXPIRED",
+            Self::Dispute => "DISPUTE",
+        }
+    }
+
+    /// The spelling merchants receive in callbacks (`event` and
+    /// `payout.status`), which predates the database one.
+    pub(crate) fn callback_name(self) -> &'static str {
+        match self {
+            Self::Cancelled => "CANCELED",
+            other => other.as_str(),
+        }
+    }
+
+    /// Case-insensitive, with `CANCELED` for [`PayoutStatus::Cancelled`].
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_uppercase();
+        if value == "CANCELED" {
+            return Some(Self::Cancelled);
+        }
+        Self::ALL
+            .into_iter()
+            .find(|status| status.as_str() == value)
+    }
+
+    /// Settled one way or another; the payout only moves on by hand.
+    pub(crate) fn is_final(self) -> bool {
+        matches!(
+            self,
+            Self::Cancelled | Self::Completed | Self::Success | Self::Failed | Self::Expired
+        )
+    }
+
+    /// Names of the final statuses, bound as `text[]` by queries that tell
+    /// open payouts from settled ones.
+    pub(crate) fn final_names() -> Vec<&'static str> {
+        Self::ALL
+            .into_iter()
+            .filter(|status| status.is_final())
+            .map(Self::as_str)
+            .collect()
+    }
+
+    pub(crate) fn is_completed(self) -> bool {
+        matches!(self, Self::Completed | Self::Success)
+    }
+
+    pub(crate) fn is_disputed(self) -> bool {
+        matches!(self, Self::Disputed | Self::Dispute)
+    }
+
+    pub(crate) fn is_cancellable(self) -> bool {
+        payout_transitions::allowed(self, Self::Cancelled)
+    }
+}
+
+impl fmt::Display for PayoutStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for PayoutStatus {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for PayoutStatus {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Self::parse(&value)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown payout status {value:?}")))
+    }
+}
+
+/// Decodes the database enum as well as its `::text` cast.
+impl Type<Postgres> for PayoutStatus {
+    fn type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("PayoutStatus")
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        *ty == Self::type_info() || <&str as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for PayoutStatus {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        let value = <&str as Decode<Postgres>>::decode(value)?;
+        Self::parse(value).ok_or_else(|| format!("unknown payout status {value:?}").into())
+    }
+}
+
+/// The status lists the dashboard script needs, as `window.__PAYOUT_STATUSES__`.
+pub(crate) fn client_bundle() -> serde_json::Value {
+    let names = |filter: fn(PayoutStatus) -> bool| {
+        PayoutStatus::ALL
+            .into_iter()
+            .filter(|status| filter(*status))
+            .map(PayoutStatus::as_str)
+            .collect::<Vec<_>>()
+    };
+    serde_json::json!({
+        "all": names(|_| true),
+        "uncancellable": names(|status| !status.is_cancellable()),
+    })
+}
@@ -0,0 +1,354 @@
+//! The payout status state machine: which status a payout may move to from
+//! which. Handlers that change a status check it with [`transition`] instead
+//! of matching status names, so a refused change always reads
+//! "Cannot <action> from <STATUS>".
+//!
+//! Assignment keeps a payout `CREATED` (only `traderId` is set), so it is not
+//! a transition here.
+
+use std::fmt;
+
+use axum::http::StatusCode;
+
+use crate::{
+    errors::{ApiError, ErrorCode},
+    payout_status::PayoutStatus,
+};
+
+/// The transition table. `SUCCESS` and `DISPUTE` are legacy spellings of
+/// `COMPLETED` and `DISPUTED` and are treated alike.
+pub(crate) fn allowed(from: PayoutStatus, to: PayoutStatus) -> bool {
+    use PayoutStatus::*;
+
+    match from {
+        Created | Available => matches!(to, Available | Active | Cancelled | Expired),
+        Active | Processing => matches!(
+            to,
+            Processing | Checking | Completed | Success | Failed | Cancelled | Disputed | Dispute
+        ),
+        // The trader reports the transfer as sent; only the check settles it.
+        Checking => matches!(to, Completed | Success | Failed | Disputed | Dispute),
+        Disputed | Dispute => matches!(to, Completed | Success | Failed | Cancelled),
+        Expired => matches!(to, Cancelled),
+        Cancelled | Completed | Success | Failed => false,
+    }
+}
+
+/// The status the payout moves to, or why it cannot.
+pub(crate) fn transition(
+    from: PayoutStatus,
+    to: PayoutStatus,
+) -> Result<PayoutStatus, TransitionError> {
+    if allowed(from, to) {
+        Ok(to)
+    } else {
+        Err(TransitionError { from, to })
+    }
+}
+
+/// What moving to `status` is called in errors.
+fn action(status: PayoutStatus) -> &'static str {
+    match status {
+        PayoutStatus::Created => "reopen",
+        PayoutStatus::Available => "publish",
+        PayoutStatus::Active => "accept",
+        PayoutStatus::Processing => "process",
+        PayoutStatus::Checking => "send for checking",
+        PayoutStatus::Cancelled => "cancel",
+        PayoutStatus::Completed | PayoutStatus::Success => "complete",
+        PayoutStatus::Failed => "fail",
+        PayoutStatus::Disputed | PayoutStatus::Dispute => "dispute",
+        PayoutStatus::Expired => "expire",
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TransitionError {
+    from: PayoutStatus,
+    to: PayoutStatus,
+}
+
+impl fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cannot {} from {}", action(self.to), self.from)
+    }
+}
+
+impl From<TransitionError> for ApiError {
+    fn from(err: TransitionError) -> Self {
+        let code = match (err.from, err.to) {
+            (PayoutStatus::Cancelled, PayoutStatus::Cancelled) => ErrorCode::PayoutAlreadyCancelled,
+            (_, PayoutStatus::Cancelled) => ErrorCode::PayoutNotCancellable,
+            _ => ErrorCode::InvalidTransition,
+        };
+        ApiError::new(StatusCode::BAD_REQUEST, code, err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc};
+    use serde_json::json;
+
+    use super::*;
+    use crate::settings::{AutoDistributionConfig, MAX_INTERVAL_SECONDS, PriorityPolicy};
+
+    use PayoutStatus::*;
+
+    fn config_with_windows(timezone: &str, windows: serde_json::Value) -> AutoDistributionConfig {
+        let config = AutoDistributionConfig {
+            timezone: timezone.to_string(),
+            windows: serde_json::from_value(windows).expect("windows deserialize"),
+            ..AutoDistributionConfig::default()
+        };
+        config.sanitized().expect("config is valid")
+    }
+
+    fn at(month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn allows_the_payout_lifecycle() {
+        for (from, to) in [
+            (Created, Available),
+            (Created, Active),
+            (Available, Active),
+            (Active, Processing),
+            (Active, Checking),
+            (Processing, Checking),
+            (Checking, Completed),
+            (Checking, Failed),
+            (Checking, Disputed),
+            (Disputed, Completed),
+            (Dispute, Cancelled),
+            (Created, Expired),
+            (Expired, Cancelled),
+            (Active, Cancelled),
+        ] {
+            assert!(allowed(from, to), "{from} -> {to} should be allowed");
+            assert_eq!(transition(from, to).unwrap(), to);
+        }
+    }
+
+    #[test]
+    fn rejects_leaving_a_final_status() {
+        for from in [Cancelled, Completed, Success, Failed] {
+            for to in PayoutStatus::ALL {
+                assert!(!allowed(from, to), "{from} -> {to} should be rejected");
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_skipping_steps() {
+        for (from, to) in [
+            (Created, Completed),
+            (Created, Checking),
+            (Available, Failed),
+            (Checking, Cancelled),
+            (Checking, Active),
+            (Expired, Active),
+            (Disputed, Checking),
+        ] {
+            assert!(!allowed(from, to), "{from} -> {to} should be rejected");
+        }
+    }
+
+    #[test]
+    fn names_the_refused_action() {
+        let err = transition(Completed, Cancelled).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot cancel from COMPLETED");
+        let err = transition(Created, Checking).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot send for checking from CREATED");
+    }
+
+    #[test]
+    fn priority_policy_drops_empty_values() {
+        let policy = PriorityPolicy {
+            amount_threshold: Some(-5.0),
+            merchant_ids: vec![" m2 ".into(), "m1".into(), "".into(), "m2".into()],
+            max_age_minutes: Some(0),
+        }
+        .sanitized()
+        .unwrap();
+        assert_eq!(policy.amount_threshold, None);
+        assert_eq!(policy.merchant_ids, ["m1", "m2"]);
+        assert_eq!(policy.max_age_minutes, None);
+
+        let policy = PriorityPolicy {
+            amount_threshold: Some(f64::NAN),
+            ..PriorityPolicy::default()
+        }
+        .sanitized()
+        .unwrap();
+        assert_eq!(policy.amount_threshold, None);
+    }
+
+    #[test]
+    fn priority_policy_keeps_the_age_within_i32() {
+        let within = PriorityPolicy {
+            max_age_minutes: Some(i32::MAX as u32),
+            ..PriorityPolicy::default()
+        }
+        .sanitized()
+        .unwrap();
+        assert_eq!(within.max_age_minutes_i32(), Some(i32::MAX));
+
+        let beyond = PriorityPolicy {
+            max_age_minutes: Some(i32::MAX as u32 + 1),
+            ..PriorityPolicy::default()
+        };
+        assert!(beyond.sanitized().is_err());
+    }
+
+    #[test]
+    fn auto_config_normalizes_caps_and_timezone() {
+        let config = AutoDistributionConfig {
+            interval_seconds: 0,
+            max_assignments_per_trader_per_cycle: Some(0),
+            max_payouts_per_cycle: Some(10),
+            max_open_payouts_per_trader: Some(0),
+            timezone: "  ".into(),
+            ..AutoDistributionConfig::default()
+        }
+        .sanitized()
+        .unwrap();
+        assert_eq!(config.interval_seconds, 1);
+        assert_eq!(config.max_assignments_per_trader_per_cycle, None);
+        assert_eq!(config.max_payouts_per_cycle, Some(10));
+        assert_eq!(config.max_open_payouts_per_trader, None);
+        assert_eq!(config.timezone, "UTC");
+    }
+
+    #[test]
+    fn auto_config_rejects_invalid_values() {
+        let invalid = [
+            AutoDistributionConfig {
+                interval_seconds: MAX_INTERVAL_SECONDS + 1,
+                ..AutoDistributionConfig::default()
+            },
+            AutoDistributionConfig {
+                interval_seconds: 10,
+                interval_jitter_seconds: 11,
+                ..AutoDistributionConfig::default()
+            },
+            AutoDistributionConfig {
+                timezone: "Mars/Olympus".into(),
+                ..AutoDistributionConfig::default()
+            },
+            AutoDistributionConfig {
+                balance_reserve_rub: -1.0,
+                ..AutoDistributionConfig::default()
+            },
+            AutoDistributionConfig {
+                windows: serde_json::from_value(
+                    json!([{ "days": [8], "start": "09:00", "end": "18:00" }]),
+                )
+                .unwrap(),
+                ..AutoDistributionConfig::default()
+            },
+            AutoDistributionConfig {
+                windows: serde_json::from_value(json!([{ "start": "9am", "end": "18:00" }]))
+                    .unwrap(),
+                ..AutoDistributionConfig::default()
+            },
+        ];
+        for config in invalid {
+            assert!(
+                config.clone().sanitized().is_err(),
+                "{config:?} should be rejected"
+            );
+        }
+
+        let at_limit = AutoDistributionConfig {
+            interval_seconds: MAX_INTERVAL_SECONDS,
+            interval_jitter_seconds: MAX_INTERVAL_SECONDS,
+            ..AutoDistributionConfig::default()
+        };
+        assert!(at_limit.sanitized().is_ok());
+    }
+
+    #[test]
+    fn every_weekday_means_every_day() {
+        let config = config_with_windows(
+            "UTC",
+            json!([{ "days": [7, 6, 5, 4, 3, 2, 1, 1], "start": "9:00", "end": "18:00" }]),
+        );
+        assert_eq!(
+            config.windows,
+            serde_json::from_value::<Vec<_>>(
+                json!([{ "days": [], "start": "09:00", "end": "18:00" }])
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn window_within_a_day() {
+        // 2026-10-16 is a Friday.
+        let config = config_with_windows(
+            "UTC",
+            json!([{ "days": [5], "start": "09:00", "end": "18:00" }]),
+        );
+        assert!(config.is_within_schedule(at(10, 16, 9, 0)));
+        assert!(config.is_within_schedule(at(10, 16, 17, 59)));
+        assert!(!config.is_within_schedule(at(10, 16, 18, 0)));
+        assert!(!config.is_within_schedule(at(10, 16, 8, 59)));
+        assert!(!config.is_within_schedule(at(10, 17, 12, 0)));
+    }
+
+    #[test]
+    fn window_across_midnight_belongs_to_its_start_day() {
+        let config = config_with_windows(
+            "UTC",
+            json!([{ "days": [5], "start": "22:00", "end": "06:00" }]),
+        );
+        assert!(config.is_within_schedule(at(10, 16, 23, 30)));
+        assert!(config.is_within_schedule(at(10, 17, 5, 59)));
+        assert!(!config.is_within_schedule(at(10, 17, 6, 0)));
+        assert!(!config.is_within_schedule(at(10, 16, 5, 0)));
+        assert!(!config.is_within_schedule(at(10, 17, 23, 0)));
+        assert!(!config.is_within_schedule(at(10, 16, 12, 0)));
+    }
+
+    #[test]
+    fn window_across_midnight_wraps_from_sunday_to_monday() {
+        // 2026-10-18 is a Sunday.
+        let config = config_with_windows(
+            "UTC",
+            json!([{ "days": [7], "start": "23:00", "end": "01:00" }]),
+        );
+        assert!(config.is_within_schedule(at(10, 18, 23, 15)));
+        assert!(config.is_within_schedule(at(10, 19, 0, 45)));
+        assert!(!config.is_within_schedule(at(10, 19, 23, 15)));
+    }
+
+    #[test]
+    fn window_with_equal_bounds_spans_the_day() {
+        let config = config_with_windows(
+            "UTC",
+            json!([{ "days": [5], "start": "00:00", "end": "00:00" }]),
+        );
+        assert!(config.is_within_schedule(at(10, 16, 0, 0)));
+        assert!(config.is_within_schedule(at(10, 16, 23, 59)));
+        assert!(!config.is_within_schedule(at(10, 17, 0, 0)));
+    }
+
+    #[test]
+    fn window_uses_the_configured_timezone() {
+        let config = config_with_windows(
+            "Europe/Moscow",
+            json!([{ "start": "09:00", "end": "18:00" }]),
+        );
+        assert!(config.is_within_schedule(at(10, 16, 7, 0)));
+        assert!(!config.is_within_schedule(at(10, 16, 16, 0)));
+    }
+
+    #[test]
+    fn no_windows_means_around_the_clock() {
+        let config = config_with_windows("UTC", json!([]));
+        assert!(config.is_within_schedule(at(10, 16, 3, 0)));
+    }
+}
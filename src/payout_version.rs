@@ -0,0 +1,74 @@
+//! Optimistic concurrency for operator changes to a payout. The payout's
+//! `updatedAt` is its version: the deals list reports it, and assign and
+//! cancel accept it back as `expectedUpdatedAt` in the body or as an
+//! `If-Match` header (`If-Match: "2026-01-31T10:00:00.000Z"`). When the row
+//! changed in the meantime the request fails with `409 PAYOUT_MODIFIED`
+//! instead of acting on a payout the operator has not seen. Without either
+//! the change applies as before.
+
+use axum::http::{HeaderMap, StatusCode, header};
+use sqlx::{Postgres, Transaction};
+
+use crate::{
+    ApiResult,
+    errors::{ApiError, ErrorCode},
+    internal_error,
+    timestamps::UtcTimestamp,
+};
+
+/// The version the caller expects, from the body or else the `If-Match`
+/// header.
+pub(crate) fn expected(
+    headers: &HeaderMap,
+    body: Option<UtcTimestamp>,
+) -> ApiResult<Option<UtcTimestamp>> {
+    if body.is_some() {
+        return Ok(body);
+    }
+    let Some(value) = headers.get(header::IF_MATCH) else {
+        return Ok(None);
+    };
+    let value = value
+        .to_str()
+        .ok()
+        .map(|value| value.trim().trim_start_matches("W/").trim_matches('"'))
+        .unwrap_or_default();
+    if value == "*" {
+        return Ok(None);
+    }
+    value
+        .parse::<chrono::DateTime<chrono::Utc>>()
+        .map(|value| Some(value.into()))
+        .map_err(|_| {
+            ApiError::from((
+                StatusCode::BAD_REQUEST,
+                format!("Invalid If-Match {value:?}, expected the payout's updatedAt"),
+            ))
+        })
+}
+
+/// Locks the payout and fails when its `updatedAt` is not `expected`. A
+/// missing payout passes, so the caller reports it the way it always has.
+pub(crate) async fn check(
+    tx: &mut Transaction<'_, Postgres>,
+    payout_id: &str,
+    expected: Option<UtcTimestamp>,
+) -> ApiResult<()> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+    let current: Option<UtcTimestamp> =
+        sqlx::query_scalar(r#"SELECT "updatedAt" FROM "Payout" WHERE "id" = $1 FOR UPDATE"#)
+            .bind(payout_id)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(internal_error)?;
+    match current {
+        Some(current) if current != expected => Err(ApiError::new(
+            StatusCode::CONFLICT,
+            ErrorCode::PayoutModified,
+            format!("Payout was changed at {current}, refresh and try again"),
+        )),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,238 @@
+//! Database pool sizing and diagnostics. The pool is configured with
+//! `DB_MIN_CONNECTIONS` (default 0), `DB_MAX_CONNECTIONS` (default 10) and
+//! `DB_ACQUIRE_TIMEOUT_SECONDS` (default 30).
+//!
+//! sqlx does not count how often handlers wait for a connection, so
+//! [`PoolMonitor`] samples the pool once a second: it records how many
+//! connections are in use and times a probe acquire, which waits exactly when
+//! a request would. `GET /api/debug/pool` reports those counters together with
+//! the statements this service currently runs longer than `DB_SLOW_QUERY_MS`
+//! (default 1000), taken from `pg_stat_activity`.
+
+use std::{
+    env,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, bail};
+use axum::{Json, extract::State};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{
+    FromRow, PgPool,
+    postgres::{PgConnectOptions, PgPoolOptions},
+};
+use tokio::time::{self, MissedTickBehavior};
+
+use crate::{ApiResult, AppState, internal_error, tenant::TenantScope};
+
+/// Identifies this service's connections in `pg_stat_activity`.
+const APPLICATION_NAME: &str = "chase-linker";
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// A probe acquire slower than this counts as a wait.
+const WAIT_THRESHOLD: Duration = Duration::from_millis(20);
+const MAX_SLOW_QUERIES: i64 = 10;
+
+#[derive(Debug, Clone)]
+pub(crate) struct PoolConfig {
+    min_connections: u32,
+    max_connections: u32,
+    acquire_timeout: Duration,
+    slow_query: Duration,
+}
+
+impl PoolConfig {
+    pub(crate) fn from_env() -> Result<Self> {
+        let min_connections = number_env("DB_MIN_CONNECTIONS", 0)?;
+        let max_connections = number_env("DB_MAX_CONNECTIONS", 10)?.max(1);
+        if min_connections > max_connections {
+            bail!("DB_MIN_CONNECTIONS must not exceed DB_MAX_CONNECTIONS");
+        }
+        Ok(Self {
+            min_connections,
+            max_connections,
+            acquire_timeout: Duration::from_secs(
+                number_env("DB_ACQUIRE_TIMEOUT_SECONDS", 30)?.max(1).into(),
+            ),
+            slow_query: Duration::from_millis(number_env("DB_SLOW_QUERY_MS", 1000)?.into()),
+        })
+    }
+
+    pub(crate) async fn connect(&self, database_url: &str) -> Result<PgPool> {
+        let options: PgConnectOptions = database_url.parse().context("Invalid DATABASE_URL")?;
+        PgPoolOptions::new()
+            .min_connections(self.min_connections)
+            .max_connections(self.max_connections)
+            .acquire_timeout(self.acquire_timeout)
+            .connect_with(options.application_name(APPLICATION_NAME))
+            .await
+            .context("Failed to connect to database")
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        format!(
+            "{}-{} connections, acquire timeout {} s, slow queries above {} ms",
+            self.min_connections,
+            self.max_connections,
+            self.acquire_timeout.as_secs(),
+            self.slow_query.as_millis()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PoolCounters {
+    samples: u64,
+    /// Samples that found every connection busy.
+    saturated_samples: u64,
+    peak_in_use: u32,
+    /// Probe acquires that took longer than `WAIT_THRESHOLD`.
+    waits: u64,
+    acquire_timeouts: u64,
+    last_wait_ms: u64,
+    max_wait_ms: u64,
+    last_saturated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone)]
+pub(crate) struct PoolMonitor {
+    config: PoolConfig,
+    counters: Arc<Mutex<PoolCounters>>,
+}
+
+impl PoolMonitor {
+    pub(crate) fn new(config: PoolConfig) -> Self {
+        Self {
+            config,
+            counters: Arc::new(Mutex::new(PoolCounters::default())),
+        }
+    }
+
+    fn counters(&self) -> PoolCounters {
+        self.counters
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    pub(crate) async fn sample_worker(self, pool: PgPool) {
+        let mut interval = time::interval(SAMPLE_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            interval.tick().await;
+            let in_use = pool.size().saturating_sub(pool.num_idle() as u32);
+            let started = Instant::now();
+            // The probe connection goes straight back to the pool.
+            let timed_out = matches!(pool.acquire().await, Err(sqlx::Error::PoolTimedOut));
+            let waited = started.elapsed();
+
+            let mut counters = self
+                .counters
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            counters.samples += 1;
+            counters.peak_in_use = counters.peak_in_use.max(in_use);
+            if in_use >= self.config.max_connections {
+                counters.saturated_samples += 1;
+                counters.last_saturated_at = Some(Utc::now());
+            }
+            let waited_ms = waited.as_millis() as u64;
+            counters.last_wait_ms = waited_ms;
+            counters.max_wait_ms = counters.max_wait_ms.max(waited_ms);
+            if waited >= WAIT_THRESHOLD {
+                counters.waits += 1;
+            }
+            if timed_out {
+                counters.acquire_timeouts += 1;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+struct SlowQuery {
+    pid: i32,
+    state: Option<String>,
+    #[sqlx(rename = "waitEvent")]
+    wait_event: Option<String>,
+    #[sqlx(rename = "durationMs")]
+    duration_ms: i64,
+    query: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PoolDebugView {
+    size: u32,
+    idle: usize,
+    in_use: u32,
+    min_connections: u32,
+    max_connections: u32,
+    acquire_timeout_seconds: u64,
+    slow_query_ms: u64,
+    counters: PoolCounters,
+    /// Statements of this service running longer than `slowQueryMs`.
+    slow_queries: Vec<SlowQuery>,
+}
+
+pub(crate) async fn get_pool_debug(
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<PoolDebugView>> {
+    scope.require_unrestricted()?;
+    let monitor = &state.pool_monitor;
+    let slow_queries = sqlx::query_as::<_, SlowQuery>(
+        r#"
+        SELECT
+            "pid",
+            "state",
+            "wait_event" AS "waitEvent",
+            (EXTRACT(EPOCH FROM (CURRENT_TIMESTAMP - "query_start")) * 1000)::bigint AS "durationMs",
+            LEFT("query", 500) AS "query"
+        FROM pg_stat_activity
+        WHERE "application_name" = $1
+          AND "pid" <> pg_backend_pid()
+          AND "state" <> 'idle'
+          AND "query_start" < CURRENT_TIMESTAMP - make_interval(secs => $2)
+        ORDER BY "query_start"
+        LIMIT $3
+        "#,
+    )
+    .bind(APPLICATION_NAME)
+    .bind(monitor.config.slow_query.as_secs_f64())
+    .bind(MAX_SLOW_QUERIES)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let size = state.pool.size();
+    let idle = state.pool.num_idle();
+    Ok(Json(PoolDebugView {
+        size,
+        idle,
+        in_use: size.saturating_sub(idle as u32),
+        min_connections: monitor.config.min_connections,
+        max_connections: monitor.config.max_connections,
+        acquire_timeout_seconds: monitor.config.acquire_timeout.as_secs(),
+        slow_query_ms: monitor.config.slow_query.as_millis() as u64,
+        counters: monitor.counters(),
+        slow_queries,
+    }))
+}
+
+fn number_env(name: &str, default: u32) -> Result<u32> {
+    match env::var(name)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+    {
+        Some(value) => value
+            .parse::<u32>()
+            .with_context(|| format!("{name} must be a non-negative integer")),
+        None => Ok(default),
+    }
+}
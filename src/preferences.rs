@@ -0,0 +1,235 @@
+//! Per-user dashboard preferences, stored as one JSON document per owner (see
+//! [`auth::preference_owner`]) in `UserPreference`. For now that is the
+//! layout of the deals table: which columns are shown, in which order and how
+//! wide. The page and the `/fragments/deals-table` rows are rendered with the
+//! stored layout, so a customized table appears as such on the first paint.
+
+use std::collections::HashSet;
+
+use axum::{
+    Json,
+    extract::{Extension, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, types::Json as SqlJson};
+use tower_sessions::Session;
+
+use crate::{ApiResult, AppState, auth, internal_error, tenant::TenantScope};
+
+const MIN_COLUMN_WIDTH: u16 = 40;
+const MAX_COLUMN_WIDTH: u16 = 1200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum DealColumn {
+    NumericId,
+    Id,
+    ExternalReference,
+    Wallet,
+    Bank,
+    Amount,
+    Status,
+    CreatedAt,
+    Actions,
+}
+
+impl DealColumn {
+    /// Default order of the deals table.
+    pub(crate) const ALL: [DealColumn; 9] = [
+        DealColumn::NumericId,
+        DealColumn::Id,
+        DealColumn::ExternalReference,
+        DealColumn::Wallet,
+        DealColumn::Bank,
+        DealColumn::Amount,
+        DealColumn::Status,
+        DealColumn::CreatedAt,
+        DealColumn::Actions,
+    ];
+
+    pub(crate) fn code(self) -> &'static str {
+        match self {
+            DealColumn::NumericId => "numericId",
+            DealColumn::Id => "id",
+            DealColumn::ExternalReference => "externalReference",
+            DealColumn::Wallet => "wallet",
+            DealColumn::Bank => "bank",
+            DealColumn::Amount => "amount",
+            DealColumn::Status => "status",
+            DealColumn::CreatedAt => "createdAt",
+            DealColumn::Actions => "actions",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ColumnPreference {
+    pub(crate) column: DealColumn,
+    #[serde(default = "default_visible")]
+    pub(crate) visible: bool,
+    /// Width in pixels; `None` leaves it to the browser.
+    #[serde(default)]
+    pub(crate) width: Option<u16>,
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct DealsTableLayout {
+    columns: Vec<ColumnPreference>,
+}
+
+impl Default for DealsTableLayout {
+    fn default() -> Self {
+        Self {
+            columns: DealColumn::ALL
+                .into_iter()
+                .map(|column| ColumnPreference {
+                    column,
+                    visible: true,
+                    width: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl DealsTableLayout {
+    /// Drops duplicates, clamps widths and appends columns the stored layout
+    /// does not know yet, so columns added later still show up. At least one
+    /// column always stays visible.
+    fn normalized(self) -> Self {
+        let mut seen = HashSet::new();
+        let mut columns: Vec<ColumnPreference> = self
+            .columns
+            .into_iter()
+            .filter(|preference| seen.insert(preference.column))
+            .map(|preference| ColumnPreference {
+                width: preference
+                    .width
+                    .map(|width| width.clamp(MIN_COLUMN_WIDTH, MAX_COLUMN_WIDTH)),
+                ..preference
+            })
+            .collect();
+        columns.extend(
+            DealColumn::ALL
+                .into_iter()
+                .filter(|column| !seen.contains(column))
+                .map(|column| ColumnPreference {
+                    column,
+                    visible: true,
+                    width: None,
+                }),
+        );
+        if !columns.iter().any(|preference| preference.visible) {
+            columns[0].visible = true;
+        }
+        Self { columns }
+    }
+
+    pub(crate) fn columns(&self) -> &[ColumnPreference] {
+        &self.columns
+    }
+
+    pub(crate) fn visible_columns(&self) -> Vec<ColumnPreference> {
+        self.columns
+            .iter()
+            .filter(|preference| preference.visible)
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct Preferences {
+    pub(crate) deals_table: DealsTableLayout,
+}
+
+impl Preferences {
+    fn normalized(self) -> Self {
+        Self {
+            deals_table: self.deals_table.normalized(),
+        }
+    }
+}
+
+/// The owner's preferences, defaults when nothing is stored yet.
+pub(crate) async fn load_preferences(pool: &PgPool, owner: &str) -> sqlx::Result<Preferences> {
+    let stored: Option<SqlJson<Preferences>> =
+        sqlx::query_scalar(r#"SELECT "value" FROM "UserPreference" WHERE "owner" = $1"#)
+            .bind(owner)
+            .fetch_optional(pool)
+            .await?;
+    Ok(stored
+        .map(|SqlJson(preferences)| preferences.normalized())
+        .unwrap_or_default())
+}
+
+/// Preferences of the caller, for handlers that render with them.
+pub(crate) async fn current_preferences(
+    state: &AppState,
+    session: &Session,
+    scope: &TenantScope,
+) -> ApiResult<Preferences> {
+    let owner = auth::preference_owner(session, scope).await?;
+    load_preferences(&state.pool, &owner)
+        .await
+        .map_err(internal_error)
+}
+
+pub(crate) async fn get_preferences(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+) -> ApiResult<Json<Preferences>> {
+    current_preferences(&state, &session, &scope)
+        .await
+        .map(Json)
+}
+
+/// Replaces the caller's preferences; omitted sections fall back to the
+/// defaults.
+pub(crate) async fn update_preferences(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(preferences): Json<Preferences>,
+) -> ApiResult<Json<Preferences>> {
+    let owner = auth::preference_owner(&session, &scope).await?;
+    let preferences = preferences.normalized();
+    sqlx::query(
+        r#"
+        INSERT INTO "UserPreference" ("owner", "value")
+        VALUES ($1, $2)
+        ON CONFLICT ("owner") DO UPDATE
+        SET "value" = EXCLUDED."value", "updatedAt" = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(&owner)
+    .bind(SqlJson(&preferences))
+    .execute(&state.pool)
+    .await
+    .map_err(internal_error)?;
+    Ok(Json(preferences))
+}
+
+/// Forgets the caller's preferences, restoring the default layout.
+pub(crate) async fn reset_preferences(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+) -> ApiResult<StatusCode> {
+    let owner = auth::preference_owner(&session, &scope).await?;
+    sqlx::query(r#"DELETE FROM "UserPreference" WHERE "owner" = $1"#)
+        .bind(&owner)
+        .execute(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,208 @@
+//! RUB/USDT rate feed used to sanity-check `amountUsdt` on open payouts.
+//!
+//! Configured from the environment:
+//! `RATE_PROVIDER` (`garantex`, `binance` or `static`; unset disables the
+//! feed), `RATE_STATIC_RUB_PER_USDT` for the static provider,
+//! `RATE_REFRESH_SECONDS` (default 60) and `RATE_TOLERANCE_PERCENT`
+//! (default 2).
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::RwLock,
+    time::{self, MissedTickBehavior},
+};
+
+use crate::env::non_empty_env;
+
+const GARANTEX_TRADES_URL: &str = "https://garantex.org/api/v2/trades?market=usdtrub&limit=1";
+const BINANCE_TICKER_URL: &str = "https://api.binance.com/api/v3/ticker/price?symbol=USDTRUB";
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum RateProvider {
+    Garantex,
+    Binance,
+    Static(f64),
+}
+
+impl RateProvider {
+    fn name(&self) -> &'static str {
+        match self {
+            RateProvider::Garantex => "garantex",
+            RateProvider::Binance => "binance",
+            RateProvider::Static(_) => "static",
+        }
+    }
+
+    /// Returns the price of one USDT in RUB.
+    async fn fetch(&self, client: &Client) -> Result<f64> {
+        let rate = match self {
+            RateProvider::Static(value) => *value,
+            RateProvider::Garantex => {
+                #[derive(Deserialize)]
+                struct Trade {
+                    price: String,
+                }
+                let trades: Vec<Trade> = client
+                    .get(GARANTEX_TRADES_URL)
+                    .send()
+                    .await
+                    .context("Garantex request failed")?
+                    .error_for_status()
+                    .context("Garantex returned an error")?
+                    .json()
+                    .await
+                    .context("Garantex returned an unexpected body")?;
+                trades
+                    .first()
+                    .context("Garantex returned no trades")?
+                    .price
+                    .parse()
+                    .context("Garantex price is not a number")?
+            }
+            RateProvider::Binance => {
+                #[derive(Deserialize)]
+                struct Ticker {
+                    price: String,
+                }
+                let ticker: Ticker = client
+                    .get(BINANCE_TICKER_URL)
+                    .send()
+                    .await
+                    .context("Binance request failed")?
+                    .error_for_status()
+                    .context("Binance returned an error")?
+                    .json()
+                    .await
+                    .context("Binance returned an unexpected body")?;
+                ticker.price.parse().context("Binance price is not a number")?
+            }
+        };
+
+        if !rate.is_finite() || rate <= 0.0 {
+            bail!("{} returned a non-positive rate {rate}", self.name());
+        }
+        Ok(rate)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct RateConfig {
+    provider: Option<RateProvider>,
+    refresh_interval: Duration,
+    tolerance_percent: f64,
+}
+
+impl RateConfig {
+    pub(crate) fn from_env() -> Result<Self> {
+        let provider = match non_empty_env("RATE_PROVIDER").map(|value| value.to_ascii_lowercase()) {
+            None => None,
+            Some(name) => Some(match name.as_str() {
+                "garantex" => RateProvider::Garantex,
+                "binance" => RateProvider::Binance,
+                "static" => {
+                    let value: f64 = non_empty_env("RATE_STATIC_RUB_PER_USDT")
+                        .context("RATE_STATIC_RUB_PER_USDT is required for the static provider")?
+                        .parse()
+                        .context("RATE_STATIC_RUB_PER_USDT must be a number")?;
+                    if !value.is_finite() || value <= 0.0 {
+                        bail!("RATE_STATIC_RUB_PER_USDT must be positive");
+                    }
+                    RateProvider::Static(value)
+                }
+                other => bail!("Unknown RATE_PROVIDER {other}"),
+            }),
+        };
+        let refresh_seconds = non_empty_env("RATE_REFRESH_SECONDS")
+            .map(|value| value.parse::<u64>())
+            .transpose()
+            .context("RATE_REFRESH_SECONDS must be a positive integer")?
+            .unwrap_or(60)
+            .max(5);
+        let tolerance_percent = non_empty_env("RATE_TOLERANCE_PERCENT")
+            .map(|value| value.parse::<f64>())
+            .transpose()
+            .context("RATE_TOLERANCE_PERCENT must be a number")?
+            .unwrap_or(2.0)
+            .abs();
+
+        Ok(Self {
+            provider,
+            refresh_interval: Duration::from_secs(refresh_seconds),
+            tolerance_percent,
+        })
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.provider.is_some()
+    }
+
+    pub(crate) fn initial_snapshot(&self) -> RateSnapshot {
+        RateSnapshot {
+            provider: self.provider.as_ref().map(|provider| provider.name().to_string()),
+            rub_per_usdt: None,
+            usdt_per_rub: None,
+            fetched_at: None,
+            error: None,
+            tolerance_percent: self.tolerance_percent,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RateSnapshot {
+    provider: Option<String>,
+    rub_per_usdt: Option<f64>,
+    usdt_per_rub: Option<f64>,
+    fetched_at: Option<DateTime<Utc>>,
+    /// Last refresh error; the previous rate is kept until a refresh succeeds.
+    error: Option<String>,
+    tolerance_percent: f64,
+}
+
+impl RateSnapshot {
+    /// Percentage by which `amount_usdt` differs from `amount * usdt_per_rub`,
+    /// and whether that exceeds the tolerance.
+    pub(crate) fn check(&self, amount: f64, amount_usdt: f64) -> Option<(f64, bool)> {
+        let expected = amount * self.usdt_per_rub?;
+        if expected <= 0.0 {
+            return None;
+        }
+        let deviation = (amount_usdt - expected) / expected * 100.0;
+        Some((deviation, deviation.abs() > self.tolerance_percent))
+    }
+}
+
+pub(crate) async fn rate_refresh_worker(
+    client: Client,
+    config: RateConfig,
+    snapshot: Arc<RwLock<RateSnapshot>>,
+) {
+    let Some(provider) = config.provider else {
+        return;
+    };
+    let mut interval = time::interval(config.refresh_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+        match provider.fetch(&client).await {
+            Ok(rate) => {
+                let mut current = snapshot.write().await;
+                current.rub_per_usdt = Some(rate);
+                current.usdt_per_rub = Some(1.0 / rate);
+                current.fetched_at = Some(Utc::now());
+                current.error = None;
+            }
+            Err(err) => {
+                eprintln!("[rates] Failed to refresh {} rate: {err:#}", provider.name());
+                snapshot.write().await.error = Some(format!("{err:#}"));
+            }
+        }
+    }
+}
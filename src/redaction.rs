@@ -0,0 +1,230 @@
+//! Redaction of sensitive values before they are written to stdout, the
+//! audit logs or `PayoutCallbackHistory`. Wallets keep only their first and
+//! last digits (`2200********1234`), credentials are replaced outright, and
+//! response bodies are cut to a size worth keeping.
+//!
+//! The rules come from the environment, once at startup:
+//! `REDACT_WALLET_VISIBLE_DIGITS` (default 4 at each end),
+//! `REDACT_BODY_LIMIT_BYTES` (default 2048, `0` keeps bodies whole),
+//! `REDACT_WALLET_KEYS` and `REDACT_SECRET_KEYS` (comma-separated JSON keys
+//! and URL parameters added to the built-in lists; matching ignores case,
+//! `-` and `_`).
+
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use crate::env::non_empty_env;
+
+const DEFAULT_WALLET_KEYS: [&str; 4] = ["wallet", "card", "cardNumber", "accountNumber"];
+const DEFAULT_SECRET_KEYS: [&str; 8] = [
+    "token",
+    "apiKey",
+    "secret",
+    "password",
+    "authorization",
+    "privateKey",
+    "signature",
+    "x-merchant-api-key",
+];
+const REDACTED: &str = "[redacted]";
+/// Survives URL encoding unescaped.
+const REDACTED_IN_URL: &str = "***";
+
+static RULES: OnceLock<RedactionRules> = OnceLock::new();
+
+#[derive(Debug, Clone)]
+pub(crate) struct RedactionRules {
+    wallet_visible_digits: usize,
+    body_limit_bytes: usize,
+    wallet_keys: Vec<String>,
+    secret_keys: Vec<String>,
+}
+
+impl Default for RedactionRules {
+    fn default() -> Self {
+        Self {
+            wallet_visible_digits: 4,
+            body_limit_bytes: 2048,
+            wallet_keys: DEFAULT_WALLET_KEYS
+                .iter()
+                .map(|key| normalize_key(key))
+                .collect(),
+            secret_keys: DEFAULT_SECRET_KEYS
+                .iter()
+                .map(|key| normalize_key(key))
+                .collect(),
+        }
+    }
+}
+
+impl RedactionRules {
+    pub(crate) fn from_env() -> Result<Self> {
+        let mut rules = Self::default();
+        if let Some(value) = non_empty_env("REDACT_WALLET_VISIBLE_DIGITS") {
+            rules.wallet_visible_digits = value
+                .parse::<usize>()
+                .ok()
+                .filter(|digits| *digits <= 8)
+                .context("REDACT_WALLET_VISIBLE_DIGITS must be an integer between 0 and 8")?;
+        }
+        if let Some(value) = non_empty_env("REDACT_BODY_LIMIT_BYTES") {
+            rules.body_limit_bytes = value
+                .parse::<usize>()
+                .context("REDACT_BODY_LIMIT_BYTES must be a non-negative integer")?;
+        }
+        if let Some(value) = non_empty_env("REDACT_WALLET_KEYS") {
+            rules.wallet_keys.extend(split_keys(&value));
+        }
+        if let Some(value) = non_empty_env("REDACT_SECRET_KEYS") {
+            rules.secret_keys.extend(split_keys(&value));
+        }
+        Ok(rules)
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        format!(
+            "wallets keep {} digit(s) at each end, bodies cut at {}, {} wallet key(s), {} secret key(s)",
+            self.wallet_visible_digits,
+            if self.body_limit_bytes == 0 {
+                "no limit".to_string()
+            } else {
+                format!("{} bytes", self.body_limit_bytes)
+            },
+            self.wallet_keys.len(),
+            self.secret_keys.len()
+        )
+    }
+
+    /// Makes these the rules for the rest of the process. Only the first call
+    /// counts.
+    pub(crate) fn install(self) {
+        let _ = RULES.set(self);
+    }
+
+    fn is_wallet_key(&self, key: &str) -> bool {
+        let key = normalize_key(key);
+        self.wallet_keys.contains(&key)
+    }
+
+    fn is_secret_key(&self, key: &str) -> bool {
+        let key = normalize_key(key);
+        self.secret_keys.contains(&key)
+    }
+}
+
+fn rules() -> &'static RedactionRules {
+    RULES.get_or_init(RedactionRules::default)
+}
+
+fn normalize_key(key: &str) -> String {
+    key.chars()
+        .filter(|c| *c != '-' && *c != '_')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn split_keys(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(normalize_key)
+}
+
+/// Masks all but the first and last digits of a wallet, card or account
+/// number. Short values keep at most their last quarter.
+pub(crate) fn wallet(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let visible = rules().wallet_visible_digits;
+    let (head, tail) = if chars.len() > visible * 2 {
+        (visible, visible)
+    } else {
+        (0, (chars.len() / 4).min(visible))
+    };
+    chars
+        .iter()
+        .enumerate()
+        .map(|(idx, c)| {
+            if idx < head || idx >= chars.len() - tail || c.is_whitespace() {
+                *c
+            } else {
+                '*'
+            }
+        })
+        .collect()
+}
+
+/// A copy of `value` with wallets masked and credentials replaced, at any
+/// depth.
+pub(crate) fn json(value: &Value) -> Value {
+    let rules = rules();
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        Value::Null => Value::Null,
+                        _ if rules.is_secret_key(key) => Value::String(REDACTED.to_string()),
+                        Value::String(text) if rules.is_wallet_key(key) => {
+                            Value::String(wallet(text))
+                        }
+                        Value::Number(number) if rules.is_wallet_key(key) => {
+                            Value::String(wallet(&number.to_string()))
+                        }
+                        other => json(other),
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(json).collect()),
+        other => other.clone(),
+    }
+}
+
+/// A response body as it may be stored: redacted when it is JSON and cut at
+/// the configured limit.
+pub(crate) fn body(text: &str) -> String {
+    let text = match serde_json::from_str::<Value>(text) {
+        Ok(value @ (Value::Object(_) | Value::Array(_))) => json(&value).to_string(),
+        _ => text.to_string(),
+    };
+    let limit = rules().body_limit_bytes;
+    if limit == 0 || text.len() <= limit {
+        return text;
+    }
+    let mut end = limit;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}… [{} bytes truncated]", &text[..end], text.len() - end)
+}
+
+/// A URL without credentials: the password and secret query parameters are
+/// replaced. Values that are not URLs are returned as they are.
+pub(crate) fn url(value: &str) -> String {
+    let Ok(mut parsed) = reqwest::Url::parse(value) else {
+        return value.to_string();
+    };
+    let rules = rules();
+    if parsed.password().is_some() {
+        let _ = parsed.set_password(Some(REDACTED_IN_URL));
+    }
+    if parsed.query().is_some() {
+        let pairs: Vec<(String, String)> = parsed
+            .query_pairs()
+            .map(|(key, value)| {
+                let value = if rules.is_secret_key(&key) {
+                    REDACTED_IN_URL.to_string()
+                } else {
+                    value.into_owned()
+                };
+                (key.into_owned(), value)
+            })
+            .collect();
+        parsed.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    parsed.to_string()
+}
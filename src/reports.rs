@@ -0,0 +1,423 @@
+//! Shift handover report. `GET /api/reports/shift?from=&to=` sums up a shift
+//! for the incoming operator: assignments per trader, cancellations per
+//! reason, callbacks dead-lettered during the shift, operator notes, and what
+//! is still open right now: unresolved disputes and payouts stuck without
+//! acceptance for more than `stuckMinutes` (default 30).
+//!
+//! `from` and `to` take RFC 3339 timestamps, `YYYY-MM-DDTHH:MM[:SS]` or a
+//! date; the shift defaults to the last 12 hours. `format=html` renders the
+//! report as a page for printing or pasting into a handover, JSON otherwise.
+
+use std::sync::LazyLock;
+
+use anyhow::{Context, Result};
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+
+use crate::{
+    ApiResult, AppState, currencies::PAYOUT_CURRENCY_SQL, errors::ApiError, frontend, i18n,
+    internal_error, payout_status::PayoutStatus, tenant::TenantScope, timestamps::UtcTimestamp,
+};
+
+const DEFAULT_SHIFT_HOURS: i64 = 12;
+const MAX_SHIFT_DAYS: i64 = 7;
+/// Lists in the report are cut at this many rows; totals count them all.
+const REPORT_ITEM_LIMIT: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ShiftReportQuery {
+    from: Option<String>,
+    to: Option<String>,
+    stuck_minutes: Option<u32>,
+    format: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ShiftReport {
+    pub(crate) from: UtcTimestamp,
+    pub(crate) to: UtcTimestamp,
+    pub(crate) generated_at: UtcTimestamp,
+    pub(crate) assignments: AssignmentSummary,
+    pub(crate) cancellations: CancellationSummary,
+    pub(crate) failed_callbacks: ReportList<FailedCallback>,
+    pub(crate) disputes: ReportList<ReportPayout>,
+    pub(crate) stuck_minutes: u32,
+    pub(crate) stuck: ReportList<ReportPayout>,
+    pub(crate) notes: ReportList<ReportNote>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReportList<T> {
+    pub(crate) total: i64,
+    pub(crate) items: Vec<T>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AssignmentSummary {
+    pub(crate) total: i64,
+    pub(crate) amount: f64,
+    /// Made by the distributor; the rest were assigned by hand.
+    pub(crate) automatic: i64,
+    pub(crate) traders: Vec<TraderAssignments>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TraderAssignments {
+    #[sqlx(rename = "traderId")]
+    pub(crate) trader_id: Option<String>,
+    pub(crate) email: Option<String>,
+    pub(crate) count: i64,
+    pub(crate) amount: f64,
+    pub(crate) automatic: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CancellationSummary {
+    pub(crate) total: i64,
+    pub(crate) amount: f64,
+    pub(crate) reasons: Vec<CancelReasonCount>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CancelReasonCount {
+    /// `None` for cancellations without a reason code.
+    pub(crate) code: Option<String>,
+    pub(crate) label: Option<String>,
+    pub(crate) count: i64,
+    pub(crate) amount: f64,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FailedCallback {
+    #[sqlx(rename = "outboxId")]
+    pub(crate) outbox_id: String,
+    #[sqlx(rename = "payoutId")]
+    pub(crate) payout_id: Option<String>,
+    #[sqlx(rename = "merchantId")]
+    pub(crate) merchant_id: Option<String>,
+    pub(crate) event: Option<String>,
+    pub(crate) attempts: i32,
+    #[sqlx(rename = "lastError")]
+    pub(crate) last_error: Option<String>,
+    #[sqlx(rename = "failedAt")]
+    pub(crate) failed_at: Option<UtcTimestamp>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReportPayout {
+    pub(crate) id: String,
+    #[sqlx(rename = "numericId")]
+    pub(crate) numeric_id: i32,
+    pub(crate) amount: Option<f64>,
+    pub(crate) currency: String,
+    pub(crate) status: PayoutStatus,
+    #[sqlx(rename = "merchantId")]
+    pub(crate) merchant_id: Option<String>,
+    #[sqlx(rename = "traderId")]
+    pub(crate) trader_id: Option<String>,
+    #[sqlx(rename = "disputeMessage")]
+    pub(crate) dispute_message: Option<String>,
+    #[sqlx(rename = "createdAt")]
+    pub(crate) created_at: UtcTimestamp,
+    #[sqlx(rename = "updatedAt")]
+    pub(crate) updated_at: UtcTimestamp,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReportNote {
+    #[sqlx(rename = "payoutId")]
+    pub(crate) payout_id: String,
+    #[sqlx(rename = "numericId")]
+    pub(crate) numeric_id: i32,
+    pub(crate) author: Option<String>,
+    pub(crate) body: String,
+    #[sqlx(rename = "createdAt")]
+    pub(crate) created_at: UtcTimestamp,
+}
+
+/// Rows of a report list with the total before the limit.
+#[derive(Debug, FromRow)]
+struct Counted<T> {
+    #[sqlx(flatten)]
+    item: T,
+    total: i64,
+}
+
+fn into_list<T>(rows: Vec<Counted<T>>) -> ReportList<T> {
+    ReportList {
+        total: rows.first().map(|row| row.total).unwrap_or_default(),
+        items: rows.into_iter().map(|row| row.item).collect(),
+    }
+}
+
+fn parse_report_time(name: &str, value: &str) -> ApiResult<NaiveDateTime> {
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.naive_utc());
+    }
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M"]
+        .into_iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .map(|date| date.and_time(NaiveTime::MIN))
+        })
+        .ok_or_else(|| {
+            ApiError::from((
+                StatusCode::BAD_REQUEST,
+                format!("{name} must be a date or a timestamp"),
+            ))
+        })
+}
+
+const ASSIGNMENTS_QUERY: &str = r#"
+    SELECT
+        a."traderId",
+        u."email",
+        COUNT(*)::bigint AS "count",
+        COALESCE(SUM(p."amount"), 0)::double precision AS "amount",
+        COUNT(*) FILTER (WHERE a."source" = 'auto')::bigint AS "automatic"
+    FROM "PayoutAuditLog" a
+    JOIN "Payout" p ON p."id" = a."payoutId"
+    LEFT JOIN "User" u ON u."id" = a."traderId"
+    WHERE a."action" = 'assigned'
+      AND a."createdAt" >= $1
+      AND a."createdAt" < $2
+      AND ($3::text[] IS NULL OR p."merchantId" = ANY($3::text[]))
+    GROUP BY a."traderId", u."email"
+    ORDER BY "count" DESC, a."traderId"
+"#;
+
+const CANCELLATIONS_QUERY: &str = r#"
+    SELECT
+        p."cancelReasonCode" AS "code",
+        crc."label",
+        COUNT(*)::bigint AS "count",
+        COALESCE(SUM(p."amount"), 0)::double precision AS "amount"
+    FROM "Payout" p
+    LEFT JOIN "CancelReasonCode" crc ON crc."code" = p."cancelReasonCode"
+    WHERE p."cancelledAt" >= $1
+      AND p."cancelledAt" < $2
+      AND ($3::text[] IS NULL OR p."merchantId" = ANY($3::text[]))
+    GROUP BY p."cancelReasonCode", crc."label"
+    ORDER BY "count" DESC, p."cancelReasonCode"
+"#;
+
+const FAILED_CALLBACKS_QUERY: &str = r#"
+    SELECT
+        o."id" AS "outboxId",
+        o."payload"->>'payoutId' AS "payoutId",
+        p."merchantId",
+        o."payload"->'body'->>'event' AS "event",
+        o."attempts",
+        o."lastError",
+        o."failedAt",
+        COUNT(*) OVER ()::bigint AS "total"
+    FROM "OutboxMessage" o
+    LEFT JOIN "Payout" p ON p."id" = o."payload"->>'payoutId'
+    WHERE o."kind" = 'callback'
+      AND o."status" = 'failed'
+      AND o."failedAt" >= $1
+      AND o."failedAt" < $2
+      AND ($3::text[] IS NULL OR p."merchantId" = ANY($3::text[]))
+    ORDER BY o."failedAt" DESC
+    LIMIT $4
+"#;
+
+static DISPUTES_QUERY: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        r#"
+    SELECT
+        p."id", p."numericId", p."amount",
+        {PAYOUT_CURRENCY_SQL} AS "currency",
+        p."status"::text AS "status", p."merchantId",
+        p."traderId", p."disputeMessage", p."createdAt", p."updatedAt",
+        COUNT(*) OVER ()::bigint AS "total"
+    FROM "Payout" p
+    WHERE p."status"::text IN ('DISPUTED', 'DISPUTE')
+      AND ($1::text[] IS NULL OR p."merchantId" = ANY($1::text[]))
+    ORDER BY p."updatedAt"
+    LIMIT $2
+"#
+    )
+});
+
+/// Open payouts nobody accepted, assigned or not, oldest first.
+static STUCK_QUERY: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        r#"
+    SELECT
+        p."id", p."numericId", p."amount",
+        {PAYOUT_CURRENCY_SQL} AS "currency",
+        p."status"::text AS "status", p."merchantId",
+        p."traderId", p."disputeMessage", p."createdAt", p."updatedAt",
+        COUNT(*) OVER ()::bigint AS "total"
+    FROM "Payout" p
+    WHERE p."direction" = 'OUT'
+      AND p."status" = 'CREATED'
+      AND p."acceptedAt" IS NULL
+      AND p."createdAt" <= LOCALTIMESTAMP - make_interval(mins => $1)
+      AND ($2::text[] IS NULL OR p."merchantId" = ANY($2::text[]))
+    ORDER BY p."createdAt"
+    LIMIT $3
+"#
+    )
+});
+
+const NOTES_QUERY: &str = r#"
+    SELECT
+        n."payoutId", p."numericId", n."author", n."body", n."createdAt",
+        COUNT(*) OVER ()::bigint AS "total"
+    FROM "PayoutNote" n
+    JOIN "Payout" p ON p."id" = n."payoutId"
+    WHERE n."createdAt" >= $1
+      AND n."createdAt" < $2
+      AND ($3::text[] IS NULL OR p."merchantId" = ANY($3::text[]))
+    ORDER BY n."createdAt"
+    LIMIT $4
+"#;
+
+pub(crate) async fn build_shift_report(
+    pool: &PgPool,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+    stuck_minutes: u32,
+    merchant_ids: Option<&[String]>,
+) -> Result<ShiftReport> {
+    let traders = sqlx::query_as::<_, TraderAssignments>(ASSIGNMENTS_QUERY)
+        .bind(from)
+        .bind(to)
+        .bind(merchant_ids)
+        .fetch_all(pool)
+        .await
+        .context("Failed to load assignments")?;
+    let reasons = sqlx::query_as::<_, CancelReasonCount>(CANCELLATIONS_QUERY)
+        .bind(from)
+        .bind(to)
+        .bind(merchant_ids)
+        .fetch_all(pool)
+        .await
+        .context("Failed to load cancellations")?;
+    let failed_callbacks = sqlx::query_as::<_, Counted<FailedCallback>>(FAILED_CALLBACKS_QUERY)
+        .bind(from)
+        .bind(to)
+        .bind(merchant_ids)
+        .bind(REPORT_ITEM_LIMIT)
+        .fetch_all(pool)
+        .await
+        .context("Failed to load failed callbacks")?;
+    let disputes = sqlx::query_as::<_, Counted<ReportPayout>>(&DISPUTES_QUERY)
+        .bind(merchant_ids)
+        .bind(REPORT_ITEM_LIMIT)
+        .fetch_all(pool)
+        .await
+        .context("Failed to load disputes")?;
+    let stuck = sqlx::query_as::<_, Counted<ReportPayout>>(&STUCK_QUERY)
+        .bind(stuck_minutes as i32)
+        .bind(merchant_ids)
+        .bind(REPORT_ITEM_LIMIT)
+        .fetch_all(pool)
+        .await
+        .context("Failed to load stuck payouts")?;
+    let notes = sqlx::query_as::<_, Counted<ReportNote>>(NOTES_QUERY)
+        .bind(from)
+        .bind(to)
+        .bind(merchant_ids)
+        .bind(REPORT_ITEM_LIMIT)
+        .fetch_all(pool)
+        .await
+        .context("Failed to load notes")?;
+
+    Ok(ShiftReport {
+        from: from.into(),
+        to: to.into(),
+        generated_at: Utc::now().into(),
+        assignments: AssignmentSummary {
+            total: traders.iter().map(|trader| trader.count).sum(),
+            amount: traders.iter().fold(0.0, |sum, trader| sum + trader.amount),
+            automatic: traders.iter().map(|trader| trader.automatic).sum(),
+            traders,
+        },
+        cancellations: CancellationSummary {
+            total: reasons.iter().map(|reason| reason.count).sum(),
+            amount: reasons.iter().fold(0.0, |sum, reason| sum + reason.amount),
+            reasons,
+        },
+        failed_callbacks: into_list(failed_callbacks),
+        disputes: into_list(disputes),
+        stuck_minutes,
+        stuck: into_list(stuck),
+        notes: into_list(notes),
+    })
+}
+
+pub(crate) async fn get_shift_report(
+    Query(params): Query<ShiftReportQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let html = match params.format.as_deref().map(str::trim) {
+        None | Some("") | Some("json") => false,
+        Some("html") => true,
+        Some(other) => {
+            return Err(ApiError::from((
+                StatusCode::BAD_REQUEST,
+                format!("Unsupported report format {other}"),
+            )));
+        }
+    };
+    let to = match params.to.as_deref() {
+        Some(value) => parse_report_time("to", value)?,
+        None => Utc::now().naive_utc(),
+    };
+    let from = match params.from.as_deref() {
+        Some(value) => parse_report_time("from", value)?,
+        None => to - Duration::hours(DEFAULT_SHIFT_HOURS),
+    };
+    if from >= to {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "from must be before to".to_string(),
+        )));
+    }
+    if to - from > Duration::days(MAX_SHIFT_DAYS) {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            format!("A report covers at most {MAX_SHIFT_DAYS} days"),
+        )));
+    }
+    let stuck_minutes = params.stuck_minutes.unwrap_or(30).max(1);
+
+    let report = build_shift_report(&state.pool, from, to, stuck_minutes, scope.merchant_ids())
+        .await
+        .map_err(internal_error)?;
+    if html {
+        let page = frontend::render_shift_report_page(
+            report,
+            i18n::Lang::from_headers(&headers),
+            frontend::Theme::from_headers(&headers),
+        );
+        Ok(Html(page).into_response())
+    } else {
+        Ok(Json(report).into_response())
+    }
+}
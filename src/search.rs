@@ -0,0 +1,188 @@
+//! `GET /api/search?q=` behind the search box in the top bar: one query
+//! looked up in payouts (id, numericId, externalReference, wallet), traders
+//! (email, numericId) and merchants (name, id), answered as separate groups.
+//! Exact matches come first. Tenant tokens only see their merchants, their
+//! payouts and the traders linked to them.
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::{
+    ApiResult, AppState, currencies::PAYOUT_CURRENCY_SQL, errors::ApiError, internal_error,
+    payout_status::PayoutStatus, tenant::TenantScope, timestamps::UtcTimestamp,
+};
+
+/// Shorter text queries match too much to be useful; numbers are exempt.
+const MIN_QUERY_CHARS: usize = 2;
+const DEFAULT_LIMIT: i64 = 8;
+const MAX_LIMIT: i64 = 50;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SearchQuery {
+    q: String,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PayoutHit {
+    id: String,
+    #[sqlx(rename = "numericId")]
+    numeric_id: i32,
+    status: PayoutStatus,
+    amount: f64,
+    currency: String,
+    wallet: String,
+    #[sqlx(rename = "externalReference")]
+    external_reference: Option<String>,
+    #[sqlx(rename = "merchantId")]
+    merchant_id: String,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TraderHit {
+    id: String,
+    email: String,
+    #[sqlx(rename = "numericId")]
+    numeric_id: i32,
+    banned: bool,
+    #[sqlx(rename = "trafficEnabled")]
+    traffic_enabled: bool,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MerchantHit {
+    id: String,
+    name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SearchResponse {
+    query: String,
+    payouts: Vec<PayoutHit>,
+    traders: Vec<TraderHit>,
+    merchants: Vec<MerchantHit>,
+}
+
+pub(crate) async fn global_search(
+    Query(params): Query<SearchQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<SearchResponse>> {
+    let query = params.q.trim().to_string();
+    let numeric_id = query.trim_start_matches('#').parse::<i32>().ok();
+    if numeric_id.is_none() && query.chars().count() < MIN_QUERY_CHARS {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            format!("Search needs at least {MIN_QUERY_CHARS} characters"),
+        )));
+    }
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let like = format!("%{query}%");
+    let merchant_ids = scope.merchant_ids();
+
+    let payouts = sqlx::query_as::<_, PayoutHit>(&format!(
+        r#"
+        SELECT
+            p."id",
+            p."numericId",
+            p."status"::text AS "status",
+            p."amount",
+            {PAYOUT_CURRENCY_SQL} AS "currency",
+            p."wallet",
+            p."externalReference",
+            p."merchantId",
+            p."createdAt"
+        FROM "Payout" p
+        WHERE p."direction" = 'OUT'
+          AND (
+              p."numericId" = $2
+              OR p."id" ILIKE $3
+              OR p."externalReference" ILIKE $3
+              OR p."wallet" ILIKE $3
+          )
+          AND ($4::text[] IS NULL OR p."merchantId" = ANY($4::text[]))
+        ORDER BY
+            (p."numericId" IS NOT DISTINCT FROM $2
+                OR p."id" = $1
+                OR p."externalReference" IS NOT DISTINCT FROM $1) DESC,
+            p."createdAt" DESC
+        LIMIT $5
+        "#
+    ))
+    .bind(&query)
+    .bind(numeric_id)
+    .bind(&like)
+    .bind(merchant_ids)
+    .bind(limit)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let traders = sqlx::query_as::<_, TraderHit>(
+        r#"
+        SELECT u."id", u."email", u."numericId", u."banned", u."trafficEnabled"
+        FROM "User" u
+        WHERE (u."numericId" = $2 OR u."email" ILIKE $3)
+          AND (
+              $4::text[] IS NULL
+              OR EXISTS (
+                  SELECT 1
+                  FROM "TraderMerchant" tm
+                  WHERE tm."traderId" = u."id"
+                    AND tm."merchantId" = ANY($4::text[])
+              )
+          )
+        ORDER BY
+            (u."numericId" IS NOT DISTINCT FROM $2 OR LOWER(u."email") = LOWER($1)) DESC,
+            u."numericId"
+        LIMIT $5
+        "#,
+    )
+    .bind(&query)
+    .bind(numeric_id)
+    .bind(&like)
+    .bind(merchant_ids)
+    .bind(limit)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let merchants = sqlx::query_as::<_, MerchantHit>(
+        r#"
+        SELECT m."id", m."name"
+        FROM "Merchant" m
+        WHERE (m."name" ILIKE $2 OR m."id" ILIKE $2)
+          AND ($3::text[] IS NULL OR m."id" = ANY($3::text[]))
+        ORDER BY
+            (LOWER(m."name") IS NOT DISTINCT FROM LOWER($1) OR m."id" = $1) DESC,
+            m."name" NULLS LAST,
+            m."id"
+        LIMIT $4
+        "#,
+    )
+    .bind(&query)
+    .bind(&like)
+    .bind(merchant_ids)
+    .bind(limit)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    Ok(Json(SearchResponse {
+        query,
+        payouts,
+        traders,
+        merchants,
+    }))
+}
@@ -0,0 +1,331 @@
+//! Envelope encryption of secrets the service keeps in its own tables:
+//! client certificate keys, callback override header values and trader
+//! webhook signing secrets.
+//!
+//! Every value gets its own random data key. The value is sealed with
+//! AES-256-GCM under the data key, the data key under the key encryption key,
+//! and both are stored together as `enc:v2:<key id>:<data key>:<value>`,
+//! each sealed part being the nonce followed by the ciphertext in hex. The key
+//! encryption key is `SECRETS_KEY`, 32 bytes as 64 hex characters, or the
+//! contents of `SECRETS_KEY_FILE` for keys provisioned by a KMS agent or a
+//! mounted secret. After a rotation the old keys go into
+//! `SECRETS_PREVIOUS_KEYS` (comma-separated) so existing values still open;
+//! at startup [`reseal_stored`] moves them, and any plaintext left from before
+//! encryption was configured, to the current key.
+//!
+//! Without a key, header values and webhook secrets are stored in plaintext
+//! as before, and client certificates cannot be stored at all.
+
+use std::fs;
+
+use aes_gcm::{
+    Aes256Gcm, Key, Nonce,
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+};
+use anyhow::{Context, Result, anyhow, bail};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use crate::env::non_empty_env;
+
+const SEALED_PREFIX: &str = "enc:";
+const FORMAT_VERSION: &str = "v2";
+/// Client keys stored before envelope encryption: sealed directly under the
+/// key encryption key as `v1:<nonce>:<ciphertext>`.
+const LEGACY_VERSION: &str = "v1";
+const NONCE_LEN: usize = 12;
+
+#[derive(Clone)]
+struct KeyEncryptionKey {
+    /// First 8 hex characters of the key's SHA-256, to pick the key on open.
+    id: String,
+    cipher: Aes256Gcm,
+}
+
+impl KeyEncryptionKey {
+    fn parse(name: &str, value: &str) -> Result<Self> {
+        let key = hex::decode(value.trim())
+            .ok()
+            .filter(|key| key.len() == 32)
+            .with_context(|| format!("{name} must be 32 bytes as 64 hex characters"))?;
+        Ok(Self {
+            id: hex::encode(&Sha256::digest(&key)[..4]),
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Secrets {
+    current: KeyEncryptionKey,
+    previous: Vec<KeyEncryptionKey>,
+}
+
+impl Secrets {
+    pub(crate) fn from_env() -> Result<Option<Self>> {
+        let current = match (non_empty_env("SECRETS_KEY"), non_empty_env("SECRETS_KEY_FILE")) {
+            (Some(_), Some(_)) => bail!("Set either SECRETS_KEY or SECRETS_KEY_FILE, not both"),
+            (Some(value), None) => KeyEncryptionKey::parse("SECRETS_KEY", &value)?,
+            (None, Some(path)) => {
+                let value = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read SECRETS_KEY_FILE {path}"))?;
+                KeyEncryptionKey::parse("SECRETS_KEY_FILE", &value)?
+            }
+            (None, None) => return Ok(None),
+        };
+        let previous = non_empty_env("SECRETS_PREVIOUS_KEYS")
+            .map(|value| {
+                value
+                    .split(',')
+                    .filter(|key| !key.trim().is_empty())
+                    .map(|key| KeyEncryptionKey::parse("SECRETS_PREVIOUS_KEYS", key))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .transpose()?
+            .unwrap_or_default();
+        Ok(Some(Self { current, previous }))
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        format!(
+            "key {}, {} previous key(s)",
+            self.current.id,
+            self.previous.len()
+        )
+    }
+
+    fn key(&self, id: &str) -> Option<&KeyEncryptionKey> {
+        std::iter::once(&self.current)
+            .chain(&self.previous)
+            .find(|key| key.id == id)
+    }
+
+    pub(crate) fn encrypt(&self, plaintext: &str) -> Result<String> {
+        let data_key = Aes256Gcm::generate_key(&mut OsRng);
+        let wrapped_key = seal_with(&self.current.cipher, &data_key)?;
+        let sealed = seal_with(&Aes256Gcm::new(&data_key), plaintext.as_bytes())?;
+        Ok(format!(
+            "{SEALED_PREFIX}{FORMAT_VERSION}:{}:{}:{}",
+            self.current.id,
+            hex::encode(wrapped_key),
+            hex::encode(sealed)
+        ))
+    }
+
+    pub(crate) fn decrypt(&self, stored: &str) -> Result<String> {
+        let plaintext = match stored.strip_prefix(SEALED_PREFIX) {
+            Some(sealed) => self.decrypt_envelope(sealed)?,
+            None => self.decrypt_legacy(stored)?,
+        };
+        String::from_utf8(plaintext).context("Secret is not valid UTF-8")
+    }
+
+    fn decrypt_envelope(&self, sealed: &str) -> Result<Vec<u8>> {
+        let parts: Vec<&str> = sealed.split(':').collect();
+        let [FORMAT_VERSION, key_id, wrapped_key, sealed] = parts[..] else {
+            bail!("Secret is not in a known format");
+        };
+        let key = self
+            .key(key_id)
+            .with_context(|| format!("Secret is sealed with unknown key {key_id}"))?;
+        let wrapped_key = hex::decode(wrapped_key).context("Secret has a malformed data key")?;
+        let data_key = open_with(&key.cipher, &wrapped_key)
+            .map_err(|_| anyhow!("Secret data key does not open with key {key_id}"))?;
+        let data_key = Key::<Aes256Gcm>::from_exact_iter(data_key)
+            .context("Secret has a malformed data key")?;
+        let sealed = hex::decode(sealed).context("Secret has a malformed value")?;
+        open_with(&Aes256Gcm::new(&data_key), &sealed)
+            .map_err(|_| anyhow!("Secret does not decrypt with its data key"))
+    }
+
+    fn decrypt_legacy(&self, stored: &str) -> Result<Vec<u8>> {
+        let Some((LEGACY_VERSION, rest)) = stored.split_once(':') else {
+            bail!("Secret is not in a known format");
+        };
+        let (nonce, ciphertext) = rest
+            .split_once(':')
+            .context("Secret is not in a known format")?;
+        let mut sealed = hex::decode(nonce).context("Secret has a malformed nonce")?;
+        sealed.extend(hex::decode(ciphertext).context("Secret has a malformed ciphertext")?);
+        std::iter::once(&self.current)
+            .chain(&self.previous)
+            .find_map(|key| open_with(&key.cipher, &sealed).ok())
+            .context("Secret does not decrypt with any configured key")
+    }
+
+    /// Whether a stored value should be sealed again: plaintext, the legacy
+    /// format or a previous key.
+    fn needs_reseal(&self, stored: &str) -> bool {
+        let current = format!("{SEALED_PREFIX}{FORMAT_VERSION}:{}:", self.current.id);
+        !stored.starts_with(&current)
+    }
+}
+
+/// Returns `nonce || ciphertext`.
+fn seal_with(cipher: &Aes256Gcm, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| anyhow!("Failed to encrypt secret"))?;
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+/// Opens `nonce || ciphertext`.
+fn open_with(cipher: &Aes256Gcm, sealed: &[u8]) -> Result<Vec<u8>, aes_gcm::Error> {
+    if sealed.len() < NONCE_LEN {
+        return Err(aes_gcm::Error);
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
+}
+
+/// Whether a stored value is encrypted rather than legacy plaintext.
+pub(crate) fn is_sealed(stored: &str) -> bool {
+    stored.starts_with(SEALED_PREFIX)
+}
+
+/// Seals a value for storage, or keeps it in plaintext when no key is
+/// configured.
+pub(crate) fn seal(secrets: Option<&Secrets>, plaintext: &str) -> Result<String> {
+    match secrets {
+        Some(secrets) => secrets.encrypt(plaintext),
+        None => Ok(plaintext.to_string()),
+    }
+}
+
+/// Opens a value written by [`seal`]; plaintext passes through.
+pub(crate) fn open(secrets: Option<&Secrets>, stored: &str) -> Result<String> {
+    if !is_sealed(stored) {
+        return Ok(stored.to_string());
+    }
+    secrets
+        .context("Secret is encrypted but SECRETS_KEY is not configured")?
+        .decrypt(stored)
+}
+
+/// Seals the string values of a JSON object, such as override headers.
+pub(crate) fn seal_values(secrets: Option<&Secrets>, object: &Value) -> Result<Value> {
+    map_values(object, |value| seal(secrets, value))
+}
+
+/// Opens the string values of a JSON object written by [`seal_values`].
+pub(crate) fn open_values(secrets: Option<&Secrets>, object: &Value) -> Result<Value> {
+    map_values(object, |value| open(secrets, value))
+}
+
+fn map_values(object: &Value, f: impl Fn(&str) -> Result<String>) -> Result<Value> {
+    let Value::Object(map) = object else {
+        return Ok(object.clone());
+    };
+    map.iter()
+        .map(|(name, value)| {
+            let value = match value {
+                Value::String(value) => Value::String(f(value)?),
+                other => other.clone(),
+            };
+            Ok((name.clone(), value))
+        })
+        .collect::<Result<_>>()
+        .map(Value::Object)
+}
+
+/// Seals plaintext left from before `SECRETS_KEY` was set and moves values
+/// sealed with a previous key to the current one. Rows are only updated if
+/// they did not change in the meantime. Returns the number of rows updated.
+pub(crate) async fn reseal_stored(pool: &PgPool, secrets: &Secrets) -> Result<u64> {
+    let mut updated = 0;
+    let reseal = |stored: &str| secrets.encrypt(&open(Some(secrets), stored)?);
+
+    let keys: Vec<(String, String)> =
+        sqlx::query_as(r#"SELECT "merchantId", "privateKey" FROM "MerchantClientCertificate""#)
+            .fetch_all(pool)
+            .await
+            .context("Failed to load client certificates")?;
+    for (merchant_id, stored) in keys {
+        if !secrets.needs_reseal(&stored) {
+            continue;
+        }
+        // Client keys were never stored in plaintext, but may be in the legacy
+        // format.
+        let sealed = secrets
+            .decrypt(&stored)
+            .and_then(|key| secrets.encrypt(&key))
+            .with_context(|| format!("Client key of merchant {merchant_id}"))?;
+        updated += sqlx::query(
+            r#"
+            UPDATE "MerchantClientCertificate"
+            SET "privateKey" = $3
+            WHERE "merchantId" = $1 AND "privateKey" = $2
+            "#,
+        )
+        .bind(&merchant_id)
+        .bind(&stored)
+        .bind(sealed)
+        .execute(pool)
+        .await
+        .context("Failed to reseal client key")?
+        .rows_affected();
+    }
+
+    let webhooks: Vec<(String, String)> =
+        sqlx::query_as(r#"SELECT "traderId", "secret" FROM "TraderWebhook""#)
+            .fetch_all(pool)
+            .await
+            .context("Failed to load trader webhooks")?;
+    for (trader_id, stored) in webhooks {
+        if !secrets.needs_reseal(&stored) {
+            continue;
+        }
+        let sealed =
+            reseal(&stored).with_context(|| format!("Webhook secret of trader {trader_id}"))?;
+        updated += sqlx::query(
+            r#"
+            UPDATE "TraderWebhook"
+            SET "secret" = $3
+            WHERE "traderId" = $1 AND "secret" = $2
+            "#,
+        )
+        .bind(&trader_id)
+        .bind(&stored)
+        .bind(sealed)
+        .execute(pool)
+        .await
+        .context("Failed to reseal trader webhook secret")?
+        .rows_affected();
+    }
+
+    let overrides: Vec<(String, Value)> =
+        sqlx::query_as(r#"SELECT "merchantId", "headers" FROM "MerchantCallbackOverride""#)
+            .fetch_all(pool)
+            .await
+            .context("Failed to load callback overrides")?;
+    for (merchant_id, stored) in overrides {
+        let needs_reseal = stored.as_object().is_some_and(|headers| {
+            headers
+                .values()
+                .any(|value| value.as_str().is_some_and(|value| secrets.needs_reseal(value)))
+        });
+        if !needs_reseal {
+            continue;
+        }
+        let sealed = map_values(&stored, reseal)
+            .with_context(|| format!("Override headers of merchant {merchant_id}"))?;
+        updated += sqlx::query(
+            r#"
+            UPDATE "MerchantCallbackOverride"
+            SET "headers" = $3
+            WHERE "merchantId" = $1 AND "headers" = $2
+            "#,
+        )
+        .bind(&merchant_id)
+        .bind(&stored)
+        .bind(sealed)
+        .execute(pool)
+        .await
+        .context("Failed to reseal override headers")?
+        .rows_affected();
+    }
+
+    Ok(updated)
+}
@@ -0,0 +1,1173 @@
+//! Operator settings: auto distribution, priority policy and trader limits.
+//! [`SettingsService`] keeps the current values in memory and persists and
+//! broadcasts every change.
+
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use anyhow::Result;
+use axum::{
+    Json,
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Datelike, NaiveTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use tokio::sync::{RwLock, broadcast, watch};
+use tower_sessions::Session;
+
+use crate::{
+    ApiResult, AppState, api::ensure_trader_in_scope, audit, auth, bank_routing, email_alerts,
+    errors::ApiError, events::ServerEvent, internal_error, settings_approval, tenant::TenantScope,
+    timestamps::UtcTimestamp,
+};
+
+/// The longest auto distribution interval, one day.
+pub(crate) const MAX_INTERVAL_SECONDS: u64 = 86_400;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AutoDistributionConfig {
+    pub(crate) enabled: bool,
+    /// At most [`MAX_INTERVAL_SECONDS`].
+    pub(crate) interval_seconds: u64,
+    /// Random delay of up to this many seconds added to each tick, `0` when
+    /// disabled. At most `interval_seconds`.
+    #[serde(default)]
+    pub(crate) interval_jitter_seconds: u64,
+    /// Tick at multiples of the interval since the Unix epoch instead of
+    /// counting from when the worker started.
+    #[serde(default)]
+    pub(crate) interval_anchored: bool,
+    pub(crate) max_assignments_per_trader_per_cycle: Option<u32>,
+    pub(crate) max_payouts_per_cycle: Option<u32>,
+    pub(crate) timezone: String,
+    pub(crate) windows: Vec<DistributionWindow>,
+    /// Only assign payouts the trader's free balance (`balanceRub - frozenRub`)
+    /// covers with `balance_reserve_rub` to spare.
+    #[serde(default)]
+    pub(crate) require_sufficient_balance: bool,
+    #[serde(default)]
+    pub(crate) balance_reserve_rub: f64,
+    /// Add assigned amounts to the trader's `frozenRub`, see [`freeze`].
+    #[serde(default)]
+    pub(crate) freeze_on_assign: bool,
+    /// Duplicate detection window, `0` when disabled, see [`duplicates`].
+    #[serde(default)]
+    pub(crate) duplicate_window_minutes: u32,
+    /// Seconds a trader is skipped after receiving a payout, `0` when disabled.
+    #[serde(default)]
+    pub(crate) assignment_cooldown_seconds: u32,
+    /// Open payouts a trader may hold at once unless `TraderCapacity` sets
+    /// their own cap.
+    #[serde(default)]
+    pub(crate) max_open_payouts_per_trader: Option<u32>,
+    /// Rotate over trader teams first and then within the team, see
+    /// [`teams`](crate::teams).
+    #[serde(default)]
+    pub(crate) balance_across_teams: bool,
+}
+
+impl Default for AutoDistributionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_seconds: 30,
+            interval_jitter_seconds: 0,
+            interval_anchored: false,
+            max_assignments_per_trader_per_cycle: None,
+            max_payouts_per_cycle: None,
+            timezone: "UTC".to_string(),
+            windows: Vec::new(),
+            require_sufficient_balance: false,
+            balance_reserve_rub: 0.0,
+            freeze_on_assign: false,
+            duplicate_window_minutes: 0,
+            assignment_cooldown_seconds: 0,
+            max_open_payouts_per_trader: None,
+            balance_across_teams: false,
+        }
+    }
+}
+
+impl AutoDistributionConfig {
+    /// The open-payout cap of a trader: their own from `TraderCapacity`,
+    /// otherwise the global default.
+    pub(crate) fn open_payout_cap(
+        &self,
+        overrides: &HashMap<String, u32>,
+        trader_id: &str,
+    ) -> Option<u32> {
+        overrides
+            .get(trader_id)
+            .copied()
+            .or(self.max_open_payouts_per_trader)
+    }
+
+    /// Whether a trader with the given balances may take `amount` on top of
+    /// `pending` already handed out to them. Always true unless the balance
+    /// check is enabled.
+    pub(crate) fn balance_covers(
+        &self,
+        balance_rub: Option<f64>,
+        frozen_rub: Option<f64>,
+        pending: f64,
+        amount: f64,
+    ) -> bool {
+        if !self.require_sufficient_balance {
+            return true;
+        }
+        let free = balance_rub.unwrap_or_default() - frozen_rub.unwrap_or_default() - pending;
+        free - self.balance_reserve_rub >= amount
+    }
+}
+
+/// A daily time window in which auto distribution may run. `end` earlier than
+/// `start` means the window runs past midnight; `days` are ISO weekdays
+/// (1 = Monday) of the day the window starts, empty meaning every day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DistributionWindow {
+    #[serde(default)]
+    days: Vec<u8>,
+    start: String,
+    end: String,
+}
+
+impl DistributionWindow {
+    fn sanitized(self) -> Result<Self, String> {
+        let start = parse_window_time(&self.start)?;
+        let end = parse_window_time(&self.end)?;
+        let mut days = self.days;
+        if let Some(day) = days.iter().find(|day| !(1..=7).contains(*day)) {
+            return Err(format!("Invalid weekday {day}, expected 1-7"));
+        }
+        days.sort_unstable();
+        days.dedup();
+        if days.len() == 7 {
+            days.clear();
+        }
+        Ok(Self {
+            days,
+            start: start.format("%H:%M").to_string(),
+            end: end.format("%H:%M").to_string(),
+        })
+    }
+
+    fn applies_to(&self, weekday: u8) -> bool {
+        self.days.is_empty() || self.days.contains(&weekday)
+    }
+
+    fn contains(&self, weekday: u8, time: NaiveTime) -> bool {
+        let (Ok(start), Ok(end)) = (parse_window_time(&self.start), parse_window_time(&self.end))
+        else {
+            return false;
+        };
+        if start == end {
+            return self.applies_to(weekday);
+        }
+        if start < end {
+            return self.applies_to(weekday) && time >= start && time < end;
+        }
+        let previous_day = if weekday == 1 { 7 } else { weekday - 1 };
+        (self.applies_to(weekday) && time >= start) || (self.applies_to(previous_day) && time < end)
+    }
+}
+
+pub(crate) fn parse_window_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .map_err(|_| format!("Invalid time {value:?}, expected HH:MM"))
+}
+
+impl AutoDistributionConfig {
+    /// The config as it would be applied, or why it cannot be.
+    pub(crate) fn sanitized(self) -> Result<Self, String> {
+        let timezone = self.timezone.trim();
+        let timezone = if timezone.is_empty() { "UTC" } else { timezone };
+        let timezone = timezone
+            .parse::<Tz>()
+            .map_err(|_| format!("Unknown timezone {timezone}"))?
+            .name()
+            .to_string();
+        let windows = self
+            .windows
+            .into_iter()
+            .map(DistributionWindow::sanitized)
+            .collect::<Result<Vec<_>, _>>()?;
+        if !self.balance_reserve_rub.is_finite() || self.balance_reserve_rub < 0.0 {
+            return Err("balanceReserveRub must be zero or a positive number".to_string());
+        }
+        if self.interval_seconds > MAX_INTERVAL_SECONDS {
+            return Err(format!(
+                "intervalSeconds must be at most {MAX_INTERVAL_SECONDS}"
+            ));
+        }
+        let interval_seconds = self.interval_seconds.max(1);
+        if self.interval_jitter_seconds > interval_seconds {
+            return Err("intervalJitterSeconds must not exceed intervalSeconds".to_string());
+        }
+
+        Ok(Self {
+            enabled: self.enabled,
+            interval_seconds,
+            interval_jitter_seconds: self.interval_jitter_seconds,
+            interval_anchored: self.interval_anchored,
+            max_assignments_per_trader_per_cycle: self
+                .max_assignments_per_trader_per_cycle
+                .filter(|value| *value > 0),
+            max_payouts_per_cycle: self.max_payouts_per_cycle.filter(|value| *value > 0),
+            timezone,
+            windows,
+            require_sufficient_balance: self.require_sufficient_balance,
+            balance_reserve_rub: self.balance_reserve_rub,
+            freeze_on_assign: self.freeze_on_assign,
+            duplicate_window_minutes: self.duplicate_window_minutes,
+            assignment_cooldown_seconds: self.assignment_cooldown_seconds,
+            max_open_payouts_per_trader: self
+                .max_open_payouts_per_trader
+                .filter(|value| *value > 0),
+            balance_across_teams: self.balance_across_teams,
+        })
+    }
+
+    /// Whether `now` falls into one of the configured windows. No windows
+    /// means distribution is allowed around the clock.
+    pub(crate) fn is_within_schedule(&self, now: DateTime<Utc>) -> bool {
+        if self.windows.is_empty() {
+            return true;
+        }
+        let tz: Tz = self.timezone.parse().unwrap_or(Tz::UTC);
+        let local = now.with_timezone(&tz);
+        let weekday = local.weekday().number_from_monday() as u8;
+        let time = local.time();
+        self.windows
+            .iter()
+            .any(|window| window.contains(weekday, time))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct PriorityPolicy {
+    pub(crate) amount_threshold: Option<f64>,
+    pub(crate) merchant_ids: Vec<String>,
+    pub(crate) max_age_minutes: Option<u32>,
+}
+
+impl PriorityPolicy {
+    pub(crate) fn sanitized(self) -> Result<Self, String> {
+        if let Some(minutes) = self.max_age_minutes
+            && i32::try_from(minutes).is_err()
+        {
+            return Err(format!("maxAgeMinutes must be at most {}", i32::MAX));
+        }
+
+        let mut merchant_ids: Vec<String> = self
+            .merchant_ids
+            .into_iter()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .collect();
+        merchant_ids.sort();
+        merchant_ids.dedup();
+
+        Ok(Self {
+            amount_threshold: self
+                .amount_threshold
+                .filter(|value| value.is_finite() && *value > 0.0),
+            merchant_ids,
+            max_age_minutes: self.max_age_minutes.filter(|value| *value > 0),
+        })
+    }
+
+    /// The age limit as bound into the queue queries. [`Self::sanitized`]
+    /// keeps it within `i32`.
+    pub(crate) fn max_age_minutes_i32(&self) -> Option<i32> {
+        self.max_age_minutes
+            .map(|value| i32::try_from(value).unwrap_or(i32::MAX))
+    }
+}
+
+/// The RUB amounts a trader takes per payout. Either bound may be open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct AmountRange {
+    pub(crate) min_amount: Option<f64>,
+    pub(crate) max_amount: Option<f64>,
+}
+
+impl AmountRange {
+    /// The range as it would be stored, or why it cannot be.
+    pub(crate) fn validated(self) -> Result<Self, String> {
+        let sanitized = self.sanitized();
+        if let (Some(min), Some(max)) = (sanitized.min_amount, sanitized.max_amount)
+            && min > max
+        {
+            return Err("minAmount must not exceed maxAmount".to_string());
+        }
+        Ok(sanitized)
+    }
+
+    pub(crate) fn contains(&self, amount: f64) -> bool {
+        self.min_amount.is_none_or(|min| amount >= min)
+            && self.max_amount.is_none_or(|max| amount <= max)
+    }
+
+    fn is_open(&self) -> bool {
+        self.min_amount.is_none() && self.max_amount.is_none()
+    }
+
+    fn sanitized(self) -> Self {
+        let bound = |value: Option<f64>| value.filter(|value| value.is_finite() && *value > 0.0);
+        Self {
+            min_amount: bound(self.min_amount),
+            max_amount: bound(self.max_amount),
+        }
+    }
+}
+
+impl fmt::Display for AmountRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.min_amount, self.max_amount) {
+            (Some(min), Some(max)) => write!(f, "{min:.2}-{max:.2}"),
+            (Some(min), None) => write!(f, ">= {min:.2}"),
+            (None, Some(max)) => write!(f, "<= {max:.2}"),
+            (None, None) => write!(f, "none"),
+        }
+    }
+}
+
+/// Runtime settings shared by the handlers and the distribution worker. The
+/// auto distribution config lives in a watch channel, so the worker picks up
+/// changes between ticks.
+#[derive(Clone)]
+pub(crate) struct SettingsService {
+    pool: PgPool,
+    event_tx: broadcast::Sender<ServerEvent>,
+    auto_config: watch::Sender<AutoDistributionConfig>,
+    priority_policy: Arc<RwLock<PriorityPolicy>>,
+    limits: Arc<RwLock<HashMap<String, AmountRange>>>,
+    /// Whether risky changes wait for a second admin, see `settings_approval`.
+    approvals: settings_approval::SettingsApprovalConfig,
+}
+
+impl SettingsService {
+    pub(crate) fn new(
+        pool: PgPool,
+        event_tx: broadcast::Sender<ServerEvent>,
+        initial: AutoDistributionConfig,
+        approvals: settings_approval::SettingsApprovalConfig,
+    ) -> Self {
+        Self {
+            pool,
+            event_tx,
+            auto_config: watch::Sender::new(initial),
+            priority_policy: Arc::new(RwLock::new(PriorityPolicy::default())),
+            limits: Arc::new(RwLock::new(HashMap::new())),
+            approvals,
+        }
+    }
+
+    pub(crate) fn requires_approvals(&self) -> bool {
+        self.approvals.is_enabled()
+    }
+
+    pub(crate) fn auto_config(&self) -> AutoDistributionConfig {
+        self.auto_config.borrow().clone()
+    }
+
+    pub(crate) fn subscribe(&self) -> watch::Receiver<AutoDistributionConfig> {
+        self.auto_config.subscribe()
+    }
+
+    pub(crate) async fn priority_policy(&self) -> PriorityPolicy {
+        self.priority_policy.read().await.clone()
+    }
+
+    /// Per-trader payout amount ranges.
+    pub(crate) async fn limits(&self) -> HashMap<String, AmountRange> {
+        self.limits.read().await.clone()
+    }
+}
+
+/// Records a change for the audit export. The change has already taken
+/// effect, so a failed write is logged instead of failing the request.
+pub(crate) async fn audit_change(
+    pool: &PgPool,
+    section: &str,
+    target: Option<&str>,
+    actor: Option<&str>,
+    before: impl Serialize,
+    after: impl Serialize,
+) {
+    let result = match (serde_json::to_value(before), serde_json::to_value(after)) {
+        (Ok(before), Ok(after)) => {
+            audit::record_settings_change(pool, section, target, actor, before, after)
+                .await
+                .map_err(|err| err.to_string())
+        }
+        (Err(err), _) | (_, Err(err)) => Err(err.to_string()),
+    };
+    if let Err(err) = result {
+        eprintln!("[settings] Failed to audit {section} change: {err}");
+    }
+}
+
+pub(crate) async fn get_auto_settings(
+    State(settings): State<SettingsService>,
+) -> ApiResult<Json<AutoDistributionConfig>> {
+    Ok(Json(settings.auto_config()))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UpdateAutoSettingsRequest {
+    enabled: bool,
+    interval_seconds: u64,
+    /// Keeps the current value when omitted; `0` disables jitter.
+    #[serde(default)]
+    interval_jitter_seconds: Option<u64>,
+    /// Keeps the current value when omitted.
+    #[serde(default)]
+    interval_anchored: Option<bool>,
+    /// Keeps the current value when omitted; `0` removes the cap.
+    #[serde(default)]
+    max_assignments_per_trader_per_cycle: Option<u32>,
+    /// Keeps the current value when omitted; `0` removes the cap.
+    #[serde(default)]
+    max_payouts_per_cycle: Option<u32>,
+    /// Keeps the current value when omitted.
+    #[serde(default)]
+    timezone: Option<String>,
+    /// Keeps the current value when omitted; `[]` runs around the clock.
+    #[serde(default)]
+    windows: Option<Vec<DistributionWindow>>,
+    /// Keeps the current value when omitted.
+    #[serde(default)]
+    require_sufficient_balance: Option<bool>,
+    /// Keeps the current value when omitted.
+    #[serde(default)]
+    balance_reserve_rub: Option<f64>,
+    /// Keeps the current value when omitted.
+    #[serde(default)]
+    freeze_on_assign: Option<bool>,
+    /// Keeps the current value when omitted; `0` disables detection.
+    #[serde(default)]
+    duplicate_window_minutes: Option<u32>,
+    /// Keeps the current value when omitted; `0` disables the cooldown.
+    #[serde(default)]
+    assignment_cooldown_seconds: Option<u32>,
+    /// Keeps the current value when omitted; `0` removes the cap.
+    #[serde(default)]
+    max_open_payouts_per_trader: Option<u32>,
+    /// Keeps the current value when omitted.
+    #[serde(default)]
+    balance_across_teams: Option<bool>,
+}
+
+pub(crate) async fn update_auto_settings(
+    State(settings): State<SettingsService>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<UpdateAutoSettingsRequest>,
+) -> ApiResult<Response> {
+    scope.require_unrestricted()?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let current = settings.auto_config();
+    let requested = AutoDistributionConfig {
+        enabled: request.enabled,
+        interval_seconds: request.interval_seconds,
+        interval_jitter_seconds: request
+            .interval_jitter_seconds
+            .unwrap_or(current.interval_jitter_seconds),
+        interval_anchored: request
+            .interval_anchored
+            .unwrap_or(current.interval_anchored),
+        max_assignments_per_trader_per_cycle: request
+            .max_assignments_per_trader_per_cycle
+            .or(current.max_assignments_per_trader_per_cycle),
+        max_payouts_per_cycle: request
+            .max_payouts_per_cycle
+            .or(current.max_payouts_per_cycle),
+        timezone: request.timezone.unwrap_or_else(|| current.timezone.clone()),
+        windows: request.windows.unwrap_or_else(|| current.windows.clone()),
+        require_sufficient_balance: request
+            .require_sufficient_balance
+            .unwrap_or(current.require_sufficient_balance),
+        balance_reserve_rub: request
+            .balance_reserve_rub
+            .unwrap_or(current.balance_reserve_rub),
+        freeze_on_assign: request.freeze_on_assign.unwrap_or(current.freeze_on_assign),
+        duplicate_window_minutes: request
+            .duplicate_window_minutes
+            .unwrap_or(current.duplicate_window_minutes),
+        assignment_cooldown_seconds: request
+            .assignment_cooldown_seconds
+            .unwrap_or(current.assignment_cooldown_seconds),
+        max_open_payouts_per_trader: request
+            .max_open_payouts_per_trader
+            .or(current.max_open_payouts_per_trader),
+        balance_across_teams: request
+            .balance_across_teams
+            .unwrap_or(current.balance_across_teams),
+    };
+    if settings.requires_approvals() {
+        let requested = requested
+            .clone()
+            .sanitized()
+            .map_err(|err| ApiError::from((StatusCode::BAD_REQUEST, err)))?;
+        let risks = settings_approval::auto_config_risks(&current, &requested);
+        if !risks.is_empty() {
+            let change = settings_approval::propose(
+                &settings.pool,
+                &settings.event_tx,
+                settings_approval::AUTO_DISTRIBUTION_SECTION,
+                &session,
+                &current,
+                &requested,
+                risks,
+            )
+            .await?;
+            return Ok((StatusCode::ACCEPTED, Json(change)).into_response());
+        }
+    }
+    let updated = settings.update_auto_config(requested).await?;
+    audit_change(
+        &settings.pool,
+        settings_approval::AUTO_DISTRIBUTION_SECTION,
+        None,
+        actor.as_deref(),
+        &current,
+        &updated,
+    )
+    .await;
+    Ok(Json(updated).into_response())
+}
+
+pub(crate) async fn get_priority_policy(
+    State(settings): State<SettingsService>,
+) -> ApiResult<Json<PriorityPolicy>> {
+    Ok(Json(settings.priority_policy().await))
+}
+
+pub(crate) async fn update_priority_policy(
+    State(settings): State<SettingsService>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<PriorityPolicy>,
+) -> ApiResult<Response> {
+    scope.require_unrestricted()?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let previous = settings.priority_policy().await;
+    if settings.requires_approvals() {
+        let requested = request
+            .clone()
+            .sanitized()
+            .map_err(|err| ApiError::from((StatusCode::BAD_REQUEST, err)))?;
+        let risks = settings_approval::priority_policy_risks(&previous, &requested);
+        if !risks.is_empty() {
+            let change = settings_approval::propose(
+                &settings.pool,
+                &settings.event_tx,
+                settings_approval::PRIORITY_SECTION,
+                &session,
+                &previous,
+                &requested,
+                risks,
+            )
+            .await?;
+            return Ok((StatusCode::ACCEPTED, Json(change)).into_response());
+        }
+    }
+    let updated = settings.update_priority_policy(request).await?;
+    audit_change(
+        &settings.pool,
+        settings_approval::PRIORITY_SECTION,
+        None,
+        actor.as_deref(),
+        &previous,
+        &updated,
+    )
+    .await;
+    Ok(Json(updated).into_response())
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UpdateLimitResponse {
+    trader_id: String,
+    #[serde(flatten)]
+    limit: AmountRange,
+}
+
+/// Replaces the trader's range; a bound left out or `null` is open.
+pub(crate) async fn update_trader_limit(
+    Path(trader_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<AmountRange>,
+) -> ApiResult<Json<UpdateLimitResponse>> {
+    ensure_trader_in_scope(&state.pool, &trader_id, scope.merchant_ids()).await?;
+    let requested = request
+        .validated()
+        .map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let previous = state
+        .settings
+        .limits()
+        .await
+        .get(&trader_id)
+        .copied()
+        .unwrap_or_default();
+    let sanitized = state
+        .settings
+        .update_trader_limit(&trader_id, requested)
+        .await;
+    audit_change(
+        &state.pool,
+        "trader-limit",
+        Some(&trader_id),
+        actor.as_deref(),
+        previous,
+        sanitized,
+    )
+    .await;
+    Ok(Json(UpdateLimitResponse {
+        trader_id,
+        limit: sanitized,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UpdateCapacityRequest {
+    max_open_payouts: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UpdateCapacityResponse {
+    trader_id: String,
+    max_open_payouts: Option<u32>,
+}
+
+/// Sets the trader's own open-payout cap; `null` or `0` falls back to the
+/// global default.
+pub(crate) async fn update_trader_capacity(
+    Path(trader_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<UpdateCapacityRequest>,
+) -> ApiResult<Json<UpdateCapacityResponse>> {
+    ensure_trader_in_scope(&state.pool, &trader_id, scope.merchant_ids()).await?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let previous: Option<i32> = sqlx::query_scalar(
+        r#"SELECT "maxOpenPayouts" FROM "TraderCapacity" WHERE "traderId" = $1"#,
+    )
+    .bind(&trader_id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(internal_error)?;
+    let sanitized = request.max_open_payouts.filter(|value| *value > 0);
+    match sanitized {
+        Some(cap) => {
+            let cap = i32::try_from(cap).map_err(|_| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    "maxOpenPayouts is too large".to_string(),
+                )
+            })?;
+            sqlx::query(
+                r#"
+                INSERT INTO "TraderCapacity" ("traderId", "maxOpenPayouts")
+                VALUES ($1, $2)
+                ON CONFLICT ("traderId") DO UPDATE
+                SET "maxOpenPayouts" = EXCLUDED."maxOpenPayouts",
+                    "updatedAt" = CURRENT_TIMESTAMP
+                "#,
+            )
+            .bind(&trader_id)
+            .bind(cap)
+            .execute(&state.pool)
+            .await
+            .map_err(internal_error)?;
+        }
+        None => {
+            sqlx::query(r#"DELETE FROM "TraderCapacity" WHERE "traderId" = $1"#)
+                .bind(&trader_id)
+                .execute(&state.pool)
+                .await
+                .map_err(internal_error)?;
+        }
+    }
+
+    println!(
+        "[settings] Updated trader capacity: trader={} maxOpenPayouts={:?}",
+        trader_id, sanitized
+    );
+    audit_change(
+        &state.pool,
+        "trader-capacity",
+        Some(&trader_id),
+        actor.as_deref(),
+        serde_json::json!({ "maxOpenPayouts": previous }),
+        serde_json::json!({ "maxOpenPayouts": sanitized }),
+    )
+    .await;
+    let _ = state.event_tx.send(ServerEvent::limits_updated());
+
+    Ok(Json(UpdateCapacityResponse {
+        trader_id,
+        max_open_payouts: sanitized,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BankWeightEntry {
+    trader_id: String,
+    weight: f64,
+    /// Share of the bank's payouts, `weight` over the bank's total.
+    share: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BankWeightGroup {
+    bank: String,
+    weights: Vec<BankWeightEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct UpdateBankWeightsRequest {
+    /// Trader id to weight; replaces all weights of the bank.
+    weights: HashMap<String, f64>,
+}
+
+pub(crate) async fn load_bank_weight_groups(
+    pool: &PgPool,
+    bank: Option<&str>,
+) -> sqlx::Result<Vec<BankWeightGroup>> {
+    let rows: Vec<(String, String, f64)> = sqlx::query_as(
+        r#"
+        SELECT "bank", "traderId", "weight"
+        FROM "TraderBankWeight"
+        WHERE $1::text IS NULL OR "bank" = $1
+        ORDER BY "bank", "weight" DESC, "traderId"
+        "#,
+    )
+    .bind(bank)
+    .fetch_all(pool)
+    .await?;
+
+    let mut groups: Vec<BankWeightGroup> = Vec::new();
+    for (bank, trader_id, weight) in rows {
+        if groups.last().is_none_or(|group| group.bank != bank) {
+            groups.push(BankWeightGroup {
+                bank,
+                weights: Vec::new(),
+            });
+        }
+        if let Some(group) = groups.last_mut() {
+            group.weights.push(BankWeightEntry {
+                trader_id,
+                weight,
+                share: 0.0,
+            });
+        }
+    }
+    for group in &mut groups {
+        let total: f64 = group.weights.iter().map(|entry| entry.weight).sum();
+        for entry in &mut group.weights {
+            entry.share = if total > 0.0 {
+                entry.weight / total
+            } else {
+                0.0
+            };
+        }
+    }
+    Ok(groups)
+}
+
+/// Routing weights apply to every tenant's payouts of a bank, so only
+/// unrestricted tenants may see or change them.
+pub(crate) async fn get_bank_weights(
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<Vec<BankWeightGroup>>> {
+    scope.require_unrestricted()?;
+    let groups = load_bank_weight_groups(&state.pool, None)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(groups))
+}
+
+pub(crate) async fn update_bank_weights(
+    Path(bank): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<UpdateBankWeightsRequest>,
+) -> ApiResult<Json<BankWeightGroup>> {
+    scope.require_unrestricted()?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let bank = bank_routing::normalize_bank(&bank);
+    if bank.is_empty() {
+        return Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            "Bank is required".to_string(),
+        )));
+    }
+    let mut trader_ids = Vec::new();
+    let mut weights = Vec::new();
+    for (trader_id, weight) in request.weights {
+        if !weight.is_finite() || weight < 0.0 {
+            return Err(ApiError::from((
+                StatusCode::BAD_REQUEST,
+                format!("Weight for trader {trader_id} must be zero or a positive number"),
+            )));
+        }
+        if weight > 0.0 {
+            trader_ids.push(trader_id.trim().to_string());
+            weights.push(weight);
+        }
+    }
+
+    let known: i64 =
+        sqlx::query_scalar(r#"SELECT COUNT(*)::bigint FROM "User" WHERE "id" = ANY($1::text[])"#)
+            .bind(&trader_ids)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(internal_error)?;
+    if known != trader_ids.len() as i64 {
+        return Err(ApiError::trader_not_found());
+    }
+
+    let previous = load_bank_weight_groups(&state.pool, Some(&bank))
+        .await
+        .map_err(internal_error)?
+        .pop();
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    sqlx::query(r#"DELETE FROM "TraderBankWeight" WHERE "bank" = $1"#)
+        .bind(&bank)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+    sqlx::query(
+        r#"
+        INSERT INTO "TraderBankWeight" ("bank", "traderId", "weight")
+        SELECT $1, batch."traderId", batch."weight"
+        FROM UNNEST($2::text[], $3::double precision[]) AS batch("traderId", "weight")
+        "#,
+    )
+    .bind(&bank)
+    .bind(&trader_ids)
+    .bind(&weights)
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    tx.commit().await.map_err(internal_error)?;
+
+    println!(
+        "[settings] Bank {bank} routing weights set for {} trader(s)",
+        trader_ids.len()
+    );
+    let _ = state.event_tx.send(ServerEvent::settings_updated());
+    let group = load_bank_weight_groups(&state.pool, Some(&bank))
+        .await
+        .map_err(internal_error)?
+        .pop()
+        .unwrap_or(BankWeightGroup {
+            bank,
+            weights: Vec::new(),
+        });
+    audit_change(
+        &state.pool,
+        "bank-weights",
+        Some(&group.bank),
+        actor.as_deref(),
+        &previous,
+        &group,
+    )
+    .await;
+    Ok(Json(group))
+}
+
+pub(crate) async fn delete_bank_weights(
+    Path(bank): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+) -> ApiResult<StatusCode> {
+    scope.require_unrestricted()?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let bank = bank_routing::normalize_bank(&bank);
+    let previous = load_bank_weight_groups(&state.pool, Some(&bank))
+        .await
+        .map_err(internal_error)?
+        .pop();
+    sqlx::query(r#"DELETE FROM "TraderBankWeight" WHERE "bank" = $1"#)
+        .bind(&bank)
+        .execute(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    println!("[settings] Bank {bank} routing weights removed");
+    audit_change(
+        &state.pool,
+        "bank-weights",
+        Some(&bank),
+        actor.as_deref(),
+        &previous,
+        Option::<BankWeightGroup>::None,
+    )
+    .await;
+    let _ = state.event_tx.send(ServerEvent::settings_updated());
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct EmailRecipient {
+    email: String,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct EmailNotificationsResponse {
+    /// `false` when `SMTP_HOST` is not set and nothing would be sent.
+    configured: bool,
+    recipients: Vec<EmailRecipient>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AddEmailRecipientRequest {
+    email: String,
+}
+
+/// Alerts cover every tenant's payouts, so only unrestricted tenants manage
+/// the recipients.
+pub(crate) async fn get_email_notifications(
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<EmailNotificationsResponse>> {
+    scope.require_unrestricted()?;
+    let recipients = sqlx::query_as::<_, EmailRecipient>(
+        r#"SELECT "email", "createdAt" FROM "EmailRecipient" ORDER BY "email""#,
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(internal_error)?;
+    Ok(Json(EmailNotificationsResponse {
+        configured: state.email.is_some(),
+        recipients,
+    }))
+}
+
+pub(crate) async fn add_email_recipient(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Json(request): Json<AddEmailRecipientRequest>,
+) -> ApiResult<Json<EmailRecipient>> {
+    scope.require_unrestricted()?;
+    let email = email_alerts::normalize_address(&request.email)
+        .map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+    let recipient = sqlx::query_as::<_, EmailRecipient>(
+        r#"
+        INSERT INTO "EmailRecipient" ("email")
+        VALUES ($1)
+        ON CONFLICT ("email") DO UPDATE SET "email" = EXCLUDED."email"
+        RETURNING "email", "createdAt"
+        "#,
+    )
+    .bind(&email)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(internal_error)?;
+    println!("[email] Added alert recipient {email}");
+    Ok(Json(recipient))
+}
+
+pub(crate) async fn delete_email_recipient(
+    Path(email): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<StatusCode> {
+    scope.require_unrestricted()?;
+    let email = email.trim().to_ascii_lowercase();
+    let result = sqlx::query(r#"DELETE FROM "EmailRecipient" WHERE "email" = $1"#)
+        .bind(&email)
+        .execute(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::from((
+            StatusCode::NOT_FOUND,
+            "Recipient not found".to_string(),
+        )));
+    }
+    println!("[email] Removed alert recipient {email}");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TestEmailResponse {
+    recipients: usize,
+}
+
+/// Sends a test message to all recipients to check the SMTP settings.
+pub(crate) async fn send_test_email(
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<TestEmailResponse>> {
+    scope.require_unrestricted()?;
+    let Some(email) = state.email.as_ref() else {
+        return Err(ApiError::from((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Email alerts are not configured".to_string(),
+        )));
+    };
+    let recipients = email
+        .send(
+            &state.pool,
+            "Test message",
+            "Email alerts of the payout distribution service are working.",
+        )
+        .await
+        .map_err(|err| (StatusCode::BAD_GATEWAY, format!("{err:#}")))?;
+    Ok(Json(TestEmailResponse { recipients }))
+}
+
+impl SettingsService {
+    pub(crate) async fn update_auto_config(
+        &self,
+        requested: AutoDistributionConfig,
+    ) -> ApiResult<AutoDistributionConfig> {
+        let new_config = requested
+            .sanitized()
+            .map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+
+        self.auto_config.send_replace(new_config.clone());
+
+        println!(
+            "[settings] Auto distribution {} with interval {} seconds (jitter {} s{}), per-trader cap {:?}, cycle cap {:?}, {} window(s) in {}, balance check {} (reserve {:.2}), freeze on assign {}, duplicate window {} min, cooldown {} s, open payout cap {:?}, team balancing {}",
+            if new_config.enabled {
+                "enabled"
+            } else {
+                "disabled"
+            },
+            new_config.interval_seconds,
+            new_config.interval_jitter_seconds,
+            if new_config.interval_anchored {
+                ", anchored"
+            } else {
+                ""
+            },
+            new_config.max_assignments_per_trader_per_cycle,
+            new_config.max_payouts_per_cycle,
+            new_config.windows.len(),
+            new_config.timezone,
+            if new_config.require_sufficient_balance {
+                "on"
+            } else {
+                "off"
+            },
+            new_config.balance_reserve_rub,
+            if new_config.freeze_on_assign {
+                "on"
+            } else {
+                "off"
+            },
+            new_config.duplicate_window_minutes,
+            new_config.assignment_cooldown_seconds,
+            new_config.max_open_payouts_per_trader,
+            if new_config.balance_across_teams {
+                "on"
+            } else {
+                "off"
+            }
+        );
+
+        email_alerts::record_auto_distribution_expected(&self.pool, new_config.enabled).await;
+        let _ = self.event_tx.send(ServerEvent::settings_updated());
+
+        Ok(new_config)
+    }
+
+    pub(crate) async fn update_priority_policy(
+        &self,
+        requested: PriorityPolicy,
+    ) -> ApiResult<PriorityPolicy> {
+        let new_policy = requested
+            .sanitized()
+            .map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+
+        {
+            let mut policy = self.priority_policy.write().await;
+            *policy = new_policy.clone();
+        }
+
+        println!(
+            "[settings] Updated priority policy: amount>={:?}, merchants={:?}, older than {:?} min",
+            new_policy.amount_threshold, new_policy.merchant_ids, new_policy.max_age_minutes
+        );
+
+        let _ = self.event_tx.send(ServerEvent::settings_updated());
+
+        Ok(new_policy)
+    }
+
+    pub(crate) async fn update_trader_limit(
+        &self,
+        trader_id: &str,
+        limit: AmountRange,
+    ) -> AmountRange {
+        self.update_trader_limits(&[trader_id.to_string()], limit)
+            .await
+    }
+
+    /// Replaces every trader's range, for a configuration import.
+    pub(crate) async fn replace_trader_limits(&self, limits: HashMap<String, AmountRange>) {
+        let count = limits.len();
+        *self.limits.write().await = limits;
+        println!("[settings] Replaced trader limits: {count} trader(s)");
+        let _ = self.event_tx.send(ServerEvent::limits_updated());
+    }
+
+    /// Sets the same range for every trader in `trader_ids`, with a single
+    /// `limits_updated` event.
+    pub(crate) async fn update_trader_limits(
+        &self,
+        trader_ids: &[String],
+        limit: AmountRange,
+    ) -> AmountRange {
+        let sanitized = limit.sanitized();
+
+        {
+            let mut limits = self.limits.write().await;
+            for trader_id in trader_ids {
+                if sanitized.is_open() {
+                    limits.remove(trader_id);
+                } else {
+                    limits.insert(trader_id.clone(), sanitized);
+                }
+            }
+        }
+
+        println!(
+            "[settings] Updated trader limit: trader={} limit={}",
+            trader_ids.join(","),
+            sanitized
+        );
+
+        let _ = self.event_tx.send(ServerEvent::limits_updated());
+
+        sanitized
+    }
+}
@@ -0,0 +1,435 @@
+//! Four-eyes control for the settings that decide how payouts are handed out.
+//! With `SETTINGS_APPROVAL_REQUIRED=on` (off by default) an auto-distribution
+//! update that switches distribution or team balancing on or off, or turns off
+//! a safety limit (the balance check, the balance freeze, duplicate detection,
+//! the cooldown or one of the caps), and any change of the priority policy, is
+//! not applied right away. The update answers `202 Accepted` with a pending
+//! change, and a second admin applies it with
+//! `POST /api/settings/changes/:id/approve` or drops it with `/reject` (which
+//! its requester may use to withdraw it). `GET /api/settings/changes` lists
+//! them, and dashboards hear of each one over SSE.
+//!
+//! Filing, approving and rejecting all need a signed-in operator, so the
+//! control needs `OPERATOR_LOGIN`: a tenant token is shared and does not tell
+//! two admins apart.
+//!
+//! The switch is an environment variable rather than a feature flag, so one
+//! admin cannot lift the control on their own. A change is approved against
+//! the settings it was filed on; when they changed in the meantime it has to
+//! be rejected and filed again.
+
+use anyhow::Result;
+use axum::{
+    Extension, Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use tokio::sync::broadcast;
+use tower_sessions::Session;
+
+use crate::{
+    ApiResult, AppState,
+    api::normalize_optional_text,
+    auth,
+    env::flag_env,
+    errors::ApiError,
+    events::ServerEvent,
+    internal_error,
+    settings::{AutoDistributionConfig, PriorityPolicy, audit_change},
+    tenant::TenantScope,
+    timestamps::UtcTimestamp,
+};
+
+pub(crate) const AUTO_DISTRIBUTION_SECTION: &str = "auto-distribution";
+pub(crate) const PRIORITY_SECTION: &str = "priority";
+const MAX_LISTED_CHANGES: i64 = 200;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct SettingsApprovalConfig {
+    required: bool,
+}
+
+impl SettingsApprovalConfig {
+    pub(crate) fn from_env() -> Result<Self> {
+        Ok(Self {
+            required: flag_env("SETTINGS_APPROVAL_REQUIRED", false)?,
+        })
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.required
+    }
+}
+
+/// What an auto-distribution update does that needs a second admin; empty
+/// when it may apply directly. Both configs are sanitized.
+pub(crate) fn auto_config_risks(
+    current: &AutoDistributionConfig,
+    requested: &AutoDistributionConfig,
+) -> Vec<String> {
+    let mut risks = Vec::new();
+    let switched =
+        |name: &str, on: bool| format!("{name} switched {}", if on { "on" } else { "off" });
+    if current.enabled != requested.enabled {
+        risks.push(switched("auto distribution", requested.enabled));
+    }
+    if current.balance_across_teams != requested.balance_across_teams {
+        risks.push(switched("team balancing", requested.balance_across_teams));
+    }
+    if current.require_sufficient_balance && !requested.require_sufficient_balance {
+        risks.push("balance check turned off".to_string());
+    }
+    if current.freeze_on_assign && !requested.freeze_on_assign {
+        risks.push("balance freeze turned off".to_string());
+    }
+    if current.duplicate_window_minutes > 0 && requested.duplicate_window_minutes == 0 {
+        risks.push("duplicate detection turned off".to_string());
+    }
+    if current.assignment_cooldown_seconds > 0 && requested.assignment_cooldown_seconds == 0 {
+        risks.push("assignment cooldown turned off".to_string());
+    }
+    let caps = [
+        (
+            "per-trader cycle cap",
+            current.max_assignments_per_trader_per_cycle,
+            requested.max_assignments_per_trader_per_cycle,
+        ),
+        (
+            "cycle cap",
+            current.max_payouts_per_cycle,
+            requested.max_payouts_per_cycle,
+        ),
+        (
+            "open payout cap",
+            current.max_open_payouts_per_trader,
+            requested.max_open_payouts_per_trader,
+        ),
+    ];
+    for (name, before, after) in caps {
+        if before.is_some() && after.is_none() {
+            risks.push(format!("{name} removed"));
+        }
+    }
+    risks
+}
+
+/// Any change of the priority policy reorders the queue. Both policies are
+/// sanitized.
+pub(crate) fn priority_policy_risks(
+    current: &PriorityPolicy,
+    requested: &PriorityPolicy,
+) -> Vec<String> {
+    if serde_json::to_value(current).ok() == serde_json::to_value(requested).ok() {
+        Vec::new()
+    } else {
+        vec!["priority policy changed".to_string()]
+    }
+}
+
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SettingsChange {
+    id: String,
+    section: String,
+    /// The settings the change was filed on.
+    before: Value,
+    after: Value,
+    risks: Vec<String>,
+    #[sqlx(rename = "requestedBy")]
+    requested_by: String,
+    /// User ID of the requesting operator, `None` on changes filed before
+    /// approvals were tied to operator sessions.
+    #[sqlx(rename = "requestedById")]
+    #[serde(skip)]
+    requested_by_id: Option<String>,
+    /// `pending`, `approved` or `rejected`.
+    status: String,
+    #[sqlx(rename = "createdAt")]
+    created_at: UtcTimestamp,
+    #[sqlx(rename = "decidedBy")]
+    decided_by: Option<String>,
+    #[sqlx(rename = "decidedAt")]
+    decided_at: Option<UtcTimestamp>,
+}
+
+impl SettingsChange {
+    fn event_data(&self) -> Value {
+        serde_json::json!({
+            "id": self.id,
+            "section": self.section,
+            "risks": self.risks,
+            "requestedBy": self.requested_by,
+            "status": self.status,
+            "decidedBy": self.decided_by,
+        })
+    }
+}
+
+const SETTINGS_CHANGE_COLUMNS: &str = r#"
+    "id", "section", "before", "after", "risks", "requestedBy", "requestedById",
+    "status", "createdAt", "decidedBy", "decidedAt"
+"#;
+
+/// Changes are tied to a person.
+async fn require_operator(session: &Session) -> ApiResult<auth::Operator> {
+    auth::require_operator(session, "Settings approvals need a signed-in operator").await
+}
+
+/// Files a change for a second admin instead of applying it.
+pub(crate) async fn propose(
+    pool: &PgPool,
+    event_tx: &broadcast::Sender<ServerEvent>,
+    section: &str,
+    session: &Session,
+    before: impl Serialize,
+    after: impl Serialize,
+    risks: Vec<String>,
+) -> ApiResult<SettingsChange> {
+    let operator = require_operator(session).await?;
+    let actor = operator.actor();
+    let before = serde_json::to_value(before).map_err(internal_error)?;
+    let after = serde_json::to_value(after).map_err(internal_error)?;
+
+    let change = sqlx::query_as::<_, SettingsChange>(&format!(
+        r#"
+        INSERT INTO "SettingsChangeRequest"
+            ("id", "section", "before", "after", "risks", "requestedBy", "requestedById")
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT ("section") WHERE "status" = 'pending' DO NOTHING
+        RETURNING {SETTINGS_CHANGE_COLUMNS}
+        "#
+    ))
+    .bind(uuid::Uuid::new_v4().to_string())
+    .bind(section)
+    .bind(&before)
+    .bind(&after)
+    .bind(&risks)
+    .bind(&actor)
+    .bind(&operator.user_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(|| {
+        ApiError::from((
+            StatusCode::CONFLICT,
+            format!("Another {section} change already awaits approval, approve or reject it first"),
+        ))
+    })?;
+
+    println!(
+        "[settings-approval] {actor} filed a change to {section} for approval: {}",
+        change.risks.join(", ")
+    );
+    let _ = event_tx.send(ServerEvent::settings_change_requested(change.event_data()));
+    Ok(change)
+}
+
+pub(crate) async fn approve_settings_change(
+    Path(change_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+) -> ApiResult<Json<SettingsChange>> {
+    scope.require_unrestricted()?;
+    let operator = require_operator(&session).await?;
+    let actor = operator.actor();
+
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    let mut change = sqlx::query_as::<_, SettingsChange>(&format!(
+        r#"
+        SELECT {SETTINGS_CHANGE_COLUMNS}
+        FROM "SettingsChangeRequest"
+        WHERE "id" = $1 AND "status" = 'pending'
+        FOR UPDATE
+        "#
+    ))
+    .bind(&change_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(|| {
+        ApiError::from((
+            StatusCode::NOT_FOUND,
+            "No pending settings change with this ID".to_string(),
+        ))
+    })?;
+    // Without the requester's ID there is no telling who filed it.
+    if change
+        .requested_by_id
+        .as_deref()
+        .is_none_or(|id| id == operator.user_id)
+    {
+        return Err(ApiError::from((
+            StatusCode::FORBIDDEN,
+            "A settings change must be approved by an operator other than its requester"
+                .to_string(),
+        )));
+    }
+
+    let outdated = || {
+        ApiError::from((
+            StatusCode::CONFLICT,
+            format!(
+                "The {} settings changed since this was filed, reject it and file it again",
+                change.section
+            ),
+        ))
+    };
+    let parse_error = |err: serde_json::Error| {
+        ApiError::from((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Stored settings change is unreadable: {err}"),
+        ))
+    };
+    let updated = match change.section.as_str() {
+        AUTO_DISTRIBUTION_SECTION => {
+            if serde_json::to_value(state.settings.auto_config()).ok()
+                != Some(change.before.clone())
+            {
+                return Err(outdated());
+            }
+            let requested: AutoDistributionConfig =
+                serde_json::from_value(change.after.clone()).map_err(parse_error)?;
+            let updated = state.settings.update_auto_config(requested).await?;
+            serde_json::to_value(updated).map_err(internal_error)?
+        }
+        PRIORITY_SECTION => {
+            if serde_json::to_value(state.settings.priority_policy().await).ok()
+                != Some(change.before.clone())
+            {
+                return Err(outdated());
+            }
+            let requested: PriorityPolicy =
+                serde_json::from_value(change.after.clone()).map_err(parse_error)?;
+            let updated = state.settings.update_priority_policy(requested).await?;
+            serde_json::to_value(updated).map_err(internal_error)?
+        }
+        other => {
+            return Err(ApiError::from((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown settings section {other}"),
+            )));
+        }
+    };
+
+    let (decided_by, decided_at): (Option<String>, Option<UtcTimestamp>) = sqlx::query_as(
+        r#"
+        UPDATE "SettingsChangeRequest"
+        SET "status" = 'approved', "decidedBy" = $2, "decidedAt" = CURRENT_TIMESTAMP
+        WHERE "id" = $1
+        RETURNING "decidedBy", "decidedAt"
+        "#,
+    )
+    .bind(&change_id)
+    .bind(&actor)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    tx.commit().await.map_err(internal_error)?;
+    change.status = "approved".to_string();
+    change.decided_by = decided_by;
+    change.decided_at = decided_at;
+
+    println!(
+        "[settings-approval] {actor} approved the {} change filed by {}",
+        change.section, change.requested_by
+    );
+    audit_change(
+        &state.pool,
+        &change.section,
+        Some(&change.id),
+        Some(&actor),
+        &change.before,
+        &updated,
+    )
+    .await;
+    let _ = state
+        .event_tx
+        .send(ServerEvent::settings_change_closed(change.event_data()));
+    Ok(Json(change))
+}
+
+pub(crate) async fn reject_settings_change(
+    Path(change_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+) -> ApiResult<Json<SettingsChange>> {
+    scope.require_unrestricted()?;
+    let actor = require_operator(&session).await?.actor();
+
+    let change = sqlx::query_as::<_, SettingsChange>(&format!(
+        r#"
+        UPDATE "SettingsChangeRequest"
+        SET "status" = 'rejected', "decidedBy" = $2, "decidedAt" = CURRENT_TIMESTAMP
+        WHERE "id" = $1 AND "status" = 'pending'
+        RETURNING {SETTINGS_CHANGE_COLUMNS}
+        "#
+    ))
+    .bind(&change_id)
+    .bind(&actor)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(|| {
+        ApiError::from((
+            StatusCode::NOT_FOUND,
+            "No pending settings change with this ID".to_string(),
+        ))
+    })?;
+
+    println!(
+        "[settings-approval] {actor} rejected the {} change filed by {}",
+        change.section, change.requested_by
+    );
+    let _ = state
+        .event_tx
+        .send(ServerEvent::settings_change_closed(change.event_data()));
+    Ok(Json(change))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SettingsChangesQuery {
+    /// Defaults to `pending`; `all` lists every status.
+    #[serde(default)]
+    status: Option<String>,
+}
+
+pub(crate) async fn get_settings_changes(
+    Query(query): Query<SettingsChangesQuery>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<Vec<SettingsChange>>> {
+    scope.require_unrestricted()?;
+    let status = normalize_optional_text(query.status);
+    let status = match status.as_deref() {
+        None | Some("pending") => Some("pending"),
+        Some("all") => None,
+        Some(status @ ("approved" | "rejected")) => Some(status),
+        Some(other) => {
+            return Err(ApiError::from((
+                StatusCode::BAD_REQUEST,
+                format!("Unknown settings change status {other}"),
+            )));
+        }
+    };
+    let query = format!(
+        r#"
+        SELECT {SETTINGS_CHANGE_COLUMNS}
+        FROM "SettingsChangeRequest"
+        WHERE ($1::text IS NULL OR "status" = $1)
+        ORDER BY "createdAt" DESC
+        LIMIT $2
+        "#
+    );
+    sqlx::query_as::<_, SettingsChange>(&query)
+        .bind(status)
+        .bind(MAX_LISTED_CHANGES)
+        .fetch_all(&state.pool)
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
@@ -0,0 +1,177 @@
+//! S3-compatible object storage (AWS S3, MinIO) for payout proof and dispute
+//! files. Requests are authorised with SigV4 query-string presigning so the
+//! dashboard can open files directly and uploads can be streamed through the
+//! same signing path.
+//!
+//! Configured from the environment:
+//! `S3_ENDPOINT`, `S3_BUCKET`, `S3_ACCESS_KEY_ID`, `S3_SECRET_ACCESS_KEY`,
+//! optional `S3_REGION` (default `us-east-1`), `S3_PRESIGN_TTL_SECONDS`
+//! (default 900) and `S3_PATH_STYLE` (default `true`, required by MinIO).
+
+use anyhow::{Context, Result, bail};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Url};
+use sha2::{Digest, Sha256};
+
+use crate::env::{flag_env, non_empty_env};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+#[derive(Debug, Clone)]
+pub(crate) struct S3Storage {
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+    presign_ttl_seconds: u32,
+    path_style: bool,
+}
+
+impl S3Storage {
+    /// Returns `Ok(None)` when storage is not configured at all, and an error
+    /// when it is only partially configured.
+    pub(crate) fn from_env() -> Result<Option<Self>> {
+        let endpoint = match non_empty_env("S3_ENDPOINT") {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let endpoint = Url::parse(&endpoint).context("S3_ENDPOINT is not a valid URL")?;
+        let bucket = non_empty_env("S3_BUCKET").context("S3_BUCKET is not set")?;
+        let access_key_id =
+            non_empty_env("S3_ACCESS_KEY_ID").context("S3_ACCESS_KEY_ID is not set")?;
+        let secret_access_key =
+            non_empty_env("S3_SECRET_ACCESS_KEY").context("S3_SECRET_ACCESS_KEY is not set")?;
+        let region = non_empty_env("S3_REGION").unwrap_or_else(|| "us-east-1".to_string());
+        let presign_ttl_seconds = non_empty_env("S3_PRESIGN_TTL_SECONDS")
+            .map(|value| value.parse::<u32>())
+            .transpose()
+            .context("S3_PRESIGN_TTL_SECONDS must be a positive integer")?
+            .unwrap_or(900)
+            .clamp(1, 604_800);
+        let path_style = flag_env("S3_PATH_STYLE", true)?;
+
+        if endpoint.host_str().is_none() {
+            bail!("S3_ENDPOINT must include a host");
+        }
+
+        Ok(Some(Self {
+            endpoint,
+            bucket,
+            region,
+            access_key_id,
+            secret_access_key,
+            presign_ttl_seconds,
+            path_style,
+        }))
+    }
+
+    pub(crate) fn presign_get(&self, key: &str) -> Result<String> {
+        self.presign("GET", key, self.presign_ttl_seconds)
+    }
+
+    pub(crate) async fn put_object(
+        &self,
+        client: &Client,
+        key: &str,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> Result<()> {
+        let url = self.presign("PUT", key, 300)?;
+        let response = client
+            .put(url)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(body)
+            .send()
+            .await
+            .context("Failed to upload file to object storage")?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            bail!("Object storage rejected upload: HTTP {} {}", status.as_u16(), body);
+        }
+        Ok(())
+    }
+
+    fn presign(&self, method: &str, key: &str, expires_seconds: u32) -> Result<String> {
+        let host = self.endpoint.host_str().context("S3 endpoint has no host")?;
+        let host = match self.endpoint.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_string(),
+        };
+        let (host, canonical_uri) = if self.path_style {
+            (host, format!("/{}/{}", self.bucket, uri_encode(key, false)))
+        } else {
+            (
+                format!("{}.{host}", self.bucket),
+                format!("/{}", uri_encode(key, false)),
+            )
+        };
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date_stamp = now.format("%Y%m%d").to_string();
+        let scope = format!("{date_stamp}/{}/s3/aws4_request", self.region);
+        let credential = format!("{}/{scope}", self.access_key_id);
+
+        let query = [
+            ("X-Amz-Algorithm", "AWS4-HMAC-SHA256".to_string()),
+            ("X-Amz-Credential", credential),
+            ("X-Amz-Date", amz_date.clone()),
+            ("X-Amz-Expires", expires_seconds.to_string()),
+            ("X-Amz-SignedHeaders", "host".to_string()),
+        ]
+        .iter()
+        .map(|(name, value)| format!("{}={}", uri_encode(name, true), uri_encode(value, true)))
+        .collect::<Vec<_>>()
+        .join("&");
+
+        let canonical_request = format!(
+            "{method}\n{canonical_uri}\n{query}\nhost:{host}\n\nhost\n{UNSIGNED_PAYLOAD}"
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let signing_key = [self.region.as_str(), "s3", "aws4_request"].iter().fold(
+            hmac_sha256(
+                format!("AWS4{}", self.secret_access_key).as_bytes(),
+                date_stamp.as_bytes(),
+            ),
+            |key, part| hmac_sha256(&key, part.as_bytes()),
+        );
+        let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+
+        Ok(format!(
+            "{}://{host}{canonical_uri}?{query}&X-Amz-Signature={signature}",
+            self.endpoint.scheme()
+        ))
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// SigV4 URI encoding: everything except unreserved characters is
+/// percent-encoded; `/` is kept in object keys.
+fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
@@ -0,0 +1,462 @@
+//! Trader teams. A trader can belong to one `TraderTeam`, managed through
+//! `/api/teams`. Members share the team's quotas: `maxOpenPayouts` caps the
+//! open payouts of the whole team and `maxAssignmentsPerCycle` the payouts it
+//! takes in one distribution cycle. Disabling a team takes all of its members
+//! out of distribution without touching their own limits.
+//!
+//! With `balanceAcrossTeams` in the auto-distribution settings the regular
+//! rotation goes over the teams first, traders without a team counting as one
+//! more group, and then over the members of the chosen team. Payouts of banks
+//! with routing weights keep following the weights.
+
+use std::collections::{BTreeMap, HashMap};
+
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::{
+    ApiResult, AppState, auth, bank_routing::RotationState, db::TraderRecord, errors::ApiError,
+    events::ServerEvent, internal_error, settings::audit_change, tenant::TenantScope,
+    timestamps::UtcTimestamp,
+};
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TraderTeam {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) enabled: bool,
+    #[sqlx(rename = "maxOpenPayouts")]
+    pub(crate) max_open_payouts: Option<i32>,
+    #[sqlx(rename = "maxAssignmentsPerCycle")]
+    pub(crate) max_assignments_per_cycle: Option<i32>,
+    #[sqlx(skip)]
+    pub(crate) trader_ids: Vec<String>,
+    #[sqlx(rename = "createdAt")]
+    pub(crate) created_at: UtcTimestamp,
+    #[sqlx(rename = "updatedAt")]
+    pub(crate) updated_at: UtcTimestamp,
+}
+
+/// What the traders panel shows about a trader's team.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TeamSummary {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) enabled: bool,
+}
+
+/// All teams and who is in them, loaded once per cycle.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Teams {
+    teams: HashMap<String, TraderTeam>,
+    /// Trader id to team id.
+    members: HashMap<String, String>,
+}
+
+impl Teams {
+    pub(crate) async fn load(pool: &PgPool) -> sqlx::Result<Self> {
+        let teams = load_teams(pool, None).await?;
+        let members = teams
+            .iter()
+            .flat_map(|team| {
+                team.trader_ids
+                    .iter()
+                    .map(|trader_id| (trader_id.clone(), team.id.clone()))
+            })
+            .collect();
+        Ok(Self {
+            teams: teams
+                .into_iter()
+                .map(|team| (team.id.clone(), team))
+                .collect(),
+            members,
+        })
+    }
+
+    pub(crate) fn team_of(&self, trader_id: &str) -> Option<&TraderTeam> {
+        self.teams.get(self.members.get(trader_id)?)
+    }
+
+    pub(crate) fn summary_of(&self, trader_id: &str) -> Option<TeamSummary> {
+        self.team_of(trader_id).map(|team| TeamSummary {
+            id: team.id.clone(),
+            name: team.name.clone(),
+            enabled: team.enabled,
+        })
+    }
+
+    /// Traders of a disabled team are not distributed to.
+    pub(crate) fn is_disabled(&self, trader_id: &str) -> bool {
+        self.team_of(trader_id).is_some_and(|team| !team.enabled)
+    }
+
+    /// Sums the per-trader open payouts into per-team totals.
+    pub(crate) fn open_payouts(&self, per_trader: &HashMap<String, i64>) -> HashMap<String, i64> {
+        let mut per_team: HashMap<String, i64> = HashMap::new();
+        for (trader_id, team_id) in &self.members {
+            *per_team.entry(team_id.clone()).or_default() +=
+                per_trader.get(trader_id).copied().unwrap_or_default();
+        }
+        per_team
+    }
+
+    /// Whether the trader's team lets them take one more payout, given the
+    /// team's open payouts and what it was assigned so far this cycle.
+    pub(crate) fn has_room(
+        &self,
+        trader_id: &str,
+        open_payouts: &HashMap<String, i64>,
+        assigned: &HashMap<String, u32>,
+    ) -> bool {
+        let Some(team) = self.team_of(trader_id) else {
+            return true;
+        };
+        let assigned = assigned.get(&team.id).copied().unwrap_or_default();
+        let open = open_payouts.get(&team.id).copied().unwrap_or_default();
+        team.enabled
+            && team
+                .max_assignments_per_cycle
+                .is_none_or(|cap| i64::from(assigned) < i64::from(cap))
+            && team
+                .max_open_payouts
+                .is_none_or(|cap| open + i64::from(assigned) < i64::from(cap))
+    }
+
+    /// Rotation balanced across teams: the groups take turns and each keeps
+    /// its own position among its members. Returns the index in `traders`.
+    pub(crate) fn pick(
+        &self,
+        state: &mut RotationState,
+        traders: &[TraderRecord],
+        fits: impl Fn(usize) -> bool,
+    ) -> Option<usize> {
+        // Keyed by team id, "" for traders without a team.
+        let mut groups: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (idx, trader) in traders.iter().enumerate() {
+            let group = self.members.get(&trader.id).map_or("", String::as_str);
+            groups.entry(group).or_default().push(idx);
+        }
+        let groups: Vec<(&str, Vec<usize>)> = groups.into_iter().collect();
+
+        for offset in 0..groups.len() {
+            let group_idx = (state.next_team_index + offset) % groups.len();
+            let (group, members) = &groups[group_idx];
+            let cursor = state.team_cursors.get(*group).copied().unwrap_or_default();
+            let found = (0..members.len())
+                .map(|step| (cursor + step) % members.len())
+                .find(|&position| fits(members[position]));
+            if let Some(position) = found {
+                state.next_team_index = (group_idx + 1) % groups.len();
+                state
+                    .team_cursors
+                    .insert(group.to_string(), (position + 1) % members.len());
+                return Some(members[position]);
+            }
+        }
+        None
+    }
+}
+
+async fn load_teams(pool: &PgPool, team_id: Option<&str>) -> sqlx::Result<Vec<TraderTeam>> {
+    let mut teams = sqlx::query_as::<_, TraderTeam>(
+        r#"
+        SELECT "id", "name", "enabled", "maxOpenPayouts", "maxAssignmentsPerCycle",
+               "createdAt", "updatedAt"
+        FROM "TraderTeam"
+        WHERE $1::text IS NULL OR "id" = $1
+        ORDER BY "name", "id"
+        "#,
+    )
+    .bind(team_id)
+    .fetch_all(pool)
+    .await?;
+    let members: Vec<(String, String)> = sqlx::query_as(
+        r#"
+        SELECT "teamId", "traderId"
+        FROM "TraderTeamMember"
+        WHERE $1::text IS NULL OR "teamId" = $1
+        ORDER BY "traderId"
+        "#,
+    )
+    .bind(team_id)
+    .fetch_all(pool)
+    .await?;
+    for (team_id, trader_id) in members {
+        if let Some(team) = teams.iter_mut().find(|team| team.id == team_id) {
+            team.trader_ids.push(trader_id);
+        }
+    }
+    Ok(teams)
+}
+
+async fn load_team(pool: &PgPool, team_id: &str) -> ApiResult<TraderTeam> {
+    load_teams(pool, Some(team_id))
+        .await
+        .map_err(internal_error)?
+        .pop()
+        .ok_or_else(|| ApiError::from((StatusCode::NOT_FOUND, "Team not found".to_string())))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TeamRequest {
+    name: String,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+    #[serde(default)]
+    max_open_payouts: Option<u32>,
+    #[serde(default)]
+    max_assignments_per_cycle: Option<u32>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl TeamRequest {
+    fn validated(self) -> ApiResult<Self> {
+        let name = self.name.trim().to_string();
+        if name.is_empty() {
+            return Err(ApiError::from((
+                StatusCode::BAD_REQUEST,
+                "Team name is required".to_string(),
+            )));
+        }
+        if [self.max_open_payouts, self.max_assignments_per_cycle]
+            .into_iter()
+            .flatten()
+            .any(|cap| cap == 0 || cap > i32::MAX as u32)
+        {
+            return Err(ApiError::from((
+                StatusCode::BAD_REQUEST,
+                "Team caps must be positive integers".to_string(),
+            )));
+        }
+        Ok(Self { name, ..self })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TeamMembersRequest {
+    /// Replaces the team's members; traders in another team are moved.
+    trader_ids: Vec<String>,
+}
+
+/// Teams span every tenant's traders, so only unrestricted tenants may see
+/// or change them.
+pub(crate) async fn get_teams(
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<Vec<TraderTeam>>> {
+    scope.require_unrestricted()?;
+    let teams = load_teams(&state.pool, None)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(teams))
+}
+
+pub(crate) async fn create_team(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<TeamRequest>,
+) -> ApiResult<(StatusCode, Json<TraderTeam>)> {
+    scope.require_unrestricted()?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let request = request.validated()?;
+    let team_id = Uuid::new_v4().to_string();
+    sqlx::query(
+        r#"
+        INSERT INTO "TraderTeam"
+            ("id", "name", "enabled", "maxOpenPayouts", "maxAssignmentsPerCycle")
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(&team_id)
+    .bind(&request.name)
+    .bind(request.enabled)
+    .bind(request.max_open_payouts.map(|cap| cap as i32))
+    .bind(request.max_assignments_per_cycle.map(|cap| cap as i32))
+    .execute(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let team = load_team(&state.pool, &team_id).await?;
+    println!("[settings] Trader team {} ({team_id}) created", team.name);
+    audit_change(
+        &state.pool,
+        "trader-team",
+        Some(&team_id),
+        actor.as_deref(),
+        Option::<TraderTeam>::None,
+        &team,
+    )
+    .await;
+    let _ = state.event_tx.send(ServerEvent::limits_updated());
+    Ok((StatusCode::CREATED, Json(team)))
+}
+
+pub(crate) async fn update_team(
+    Path(team_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<TeamRequest>,
+) -> ApiResult<Json<TraderTeam>> {
+    scope.require_unrestricted()?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let request = request.validated()?;
+    let previous = load_team(&state.pool, &team_id).await?;
+    sqlx::query(
+        r#"
+        UPDATE "TraderTeam"
+        SET "name" = $2,
+            "enabled" = $3,
+            "maxOpenPayouts" = $4,
+            "maxAssignmentsPerCycle" = $5,
+            "updatedAt" = CURRENT_TIMESTAMP
+        WHERE "id" = $1
+        "#,
+    )
+    .bind(&team_id)
+    .bind(&request.name)
+    .bind(request.enabled)
+    .bind(request.max_open_payouts.map(|cap| cap as i32))
+    .bind(request.max_assignments_per_cycle.map(|cap| cap as i32))
+    .execute(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let team = load_team(&state.pool, &team_id).await?;
+    println!(
+        "[settings] Trader team {} ({team_id}) {}, open payout cap {:?}, cycle cap {:?}",
+        team.name,
+        if team.enabled { "enabled" } else { "disabled" },
+        team.max_open_payouts,
+        team.max_assignments_per_cycle
+    );
+    audit_change(
+        &state.pool,
+        "trader-team",
+        Some(&team_id),
+        actor.as_deref(),
+        &previous,
+        &team,
+    )
+    .await;
+    let _ = state.event_tx.send(ServerEvent::limits_updated());
+    Ok(Json(team))
+}
+
+pub(crate) async fn update_team_members(
+    Path(team_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+    Json(request): Json<TeamMembersRequest>,
+) -> ApiResult<Json<TraderTeam>> {
+    scope.require_unrestricted()?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let previous = load_team(&state.pool, &team_id).await?;
+    let mut trader_ids: Vec<String> = request
+        .trader_ids
+        .iter()
+        .map(|trader_id| trader_id.trim().to_string())
+        .filter(|trader_id| !trader_id.is_empty())
+        .collect();
+    trader_ids.sort();
+    trader_ids.dedup();
+
+    let known: i64 =
+        sqlx::query_scalar(r#"SELECT COUNT(*)::bigint FROM "User" WHERE "id" = ANY($1::text[])"#)
+            .bind(&trader_ids)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(internal_error)?;
+    if known != trader_ids.len() as i64 {
+        return Err(ApiError::trader_not_found());
+    }
+
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    sqlx::query(r#"DELETE FROM "TraderTeamMember" WHERE "teamId" = $1"#)
+        .bind(&team_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+    sqlx::query(
+        r#"
+        INSERT INTO "TraderTeamMember" ("traderId", "teamId")
+        SELECT batch."traderId", $1
+        FROM UNNEST($2::text[]) AS batch("traderId")
+        ON CONFLICT ("traderId") DO UPDATE
+        SET "teamId" = EXCLUDED."teamId",
+            "updatedAt" = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(&team_id)
+    .bind(&trader_ids)
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    tx.commit().await.map_err(internal_error)?;
+
+    let team = load_team(&state.pool, &team_id).await?;
+    println!(
+        "[settings] Trader team {} ({team_id}) now has {} member(s)",
+        team.name,
+        team.trader_ids.len()
+    );
+    audit_change(
+        &state.pool,
+        "trader-team",
+        Some(&team_id),
+        actor.as_deref(),
+        &previous,
+        &team,
+    )
+    .await;
+    let _ = state.event_tx.send(ServerEvent::limits_updated());
+    Ok(Json(team))
+}
+
+/// Removes the team; its members go back to distribution on their own.
+pub(crate) async fn delete_team(
+    Path(team_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    scope: TenantScope,
+) -> ApiResult<StatusCode> {
+    scope.require_unrestricted()?;
+    let actor = auth::audit_actor(&session, &scope).await?;
+    let previous = load_team(&state.pool, &team_id).await?;
+    sqlx::query(r#"DELETE FROM "TraderTeam" WHERE "id" = $1"#)
+        .bind(&team_id)
+        .execute(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    println!(
+        "[settings] Trader team {} ({team_id}) removed",
+        previous.name
+    );
+    audit_change(
+        &state.pool,
+        "trader-team",
+        Some(&team_id),
+        actor.as_deref(),
+        &previous,
+        Option::<TraderTeam>::None,
+    )
+    .await;
+    let _ = state.event_tx.send(ServerEvent::limits_updated());
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,85 @@
+//! Telegram notifications through the Bot API. Set `TELEGRAM_BOT_TOKEN` and
+//! `TELEGRAM_CHAT_ID` (a numeric chat id or `@channel`) to enable them;
+//! `TELEGRAM_API_URL` points at a Bot API proxy instead of
+//! `https://api.telegram.org`.
+
+use anyhow::{Context, Result, bail};
+use reqwest::Client;
+use serde::Serialize;
+
+use crate::env::non_empty_env;
+
+/// Telegram rejects longer messages.
+const MAX_MESSAGE_CHARS: usize = 4096;
+
+#[derive(Clone)]
+pub(crate) struct TelegramNotifier {
+    api_url: String,
+    token: String,
+    chat_id: String,
+}
+
+#[derive(Serialize)]
+struct SendMessage<'a> {
+    chat_id: &'a str,
+    text: &'a str,
+    disable_web_page_preview: bool,
+}
+
+impl TelegramNotifier {
+    /// Returns `Ok(None)` when `TELEGRAM_BOT_TOKEN` is not set.
+    pub(crate) fn from_env() -> Result<Option<Self>> {
+        let Some(token) = non_empty_env("TELEGRAM_BOT_TOKEN") else {
+            return Ok(None);
+        };
+        let chat_id = non_empty_env("TELEGRAM_CHAT_ID")
+            .context("TELEGRAM_CHAT_ID is required when TELEGRAM_BOT_TOKEN is set")?;
+        let api_url = non_empty_env("TELEGRAM_API_URL")
+            .unwrap_or_else(|| "https://api.telegram.org".to_string())
+            .trim_end_matches('/')
+            .to_string();
+        if !api_url.starts_with("http://") && !api_url.starts_with("https://") {
+            bail!("TELEGRAM_API_URL must be an http(s) URL");
+        }
+        Ok(Some(Self {
+            api_url,
+            token,
+            chat_id,
+        }))
+    }
+
+    /// One-line summary for the startup log. The token is left out.
+    pub(crate) fn describe(&self) -> String {
+        format!("chat {} via {}", self.chat_id, self.api_url)
+    }
+
+    /// Sends `text` as a plain message, cut to what Telegram accepts.
+    pub(crate) async fn send(&self, client: &Client, text: &str) -> Result<()> {
+        let text = match text.char_indices().nth(MAX_MESSAGE_CHARS) {
+            Some((cut, _)) => &text[..cut],
+            None => text,
+        };
+        // Errors are stripped of the URL, it carries the bot token.
+        let response = client
+            .post(format!("{}/bot{}/sendMessage", self.api_url, self.token))
+            .json(&SendMessage {
+                chat_id: &self.chat_id,
+                text,
+                disable_web_page_preview: true,
+            })
+            .send()
+            .await
+            .map_err(|err| err.without_url())
+            .context("Failed to reach the Telegram API")?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            bail!(
+                "Telegram API returned {status}: {}",
+                body.chars().take(200).collect::<String>()
+            );
+        }
+        println!("[telegram] Sent message to chat {}", self.chat_id);
+        Ok(())
+    }
+}
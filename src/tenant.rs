@@ -0,0 +1,227 @@
+//! Tenants let several merchant groups share one platform database while each
+//! operator team only sees its own merchants. Tenants are configured through
+//! the `TENANTS` environment variable as a JSON array:
+//!
+//! `[{"name": "group-a", "token": "…", "merchantIds": ["m1", "m2"]}, {"name": "admin", "token": "…"}]`
+//!
+//! A tenant without `merchantIds` is unrestricted. When `TENANTS` is unset the
+//! service runs in single-tenant mode and no token is required.
+//!
+//! With operator login enabled (see `auth`) the browser authenticates with its
+//! session instead, and only API clients still send tenant tokens.
+
+use std::{env, sync::Arc};
+
+use anyhow::{Context, Result, bail};
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{StatusCode, header, request::Parts},
+};
+use serde::Deserialize;
+
+use crate::{AppState, ApiResult, auth, cookie_value, errors::ApiError, events::EventAudience};
+
+pub(crate) const TENANT_COOKIE: &str = "tenant_token";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TenantConfig {
+    name: String,
+    token: String,
+    merchant_ids: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TenantRegistry {
+    tenants: Arc<Vec<TenantConfig>>,
+}
+
+impl TenantRegistry {
+    pub(crate) fn from_env() -> Result<Self> {
+        let raw = match env::var("TENANTS") {
+            Ok(value) if !value.trim().is_empty() => value,
+            _ => return Ok(Self::default()),
+        };
+        let tenants: Vec<TenantConfig> =
+            serde_json::from_str(&raw).context("TENANTS must be a JSON array of tenants")?;
+
+        for tenant in &tenants {
+            if tenant.name.trim().is_empty() || tenant.token.trim().is_empty() {
+                bail!("Every tenant needs a non-empty name and token");
+            }
+            if tenant.merchant_ids.as_ref().is_some_and(|ids| ids.is_empty()) {
+                bail!("Tenant {} has an empty merchantIds allowlist", tenant.name);
+            }
+        }
+        for (idx, tenant) in tenants.iter().enumerate() {
+            if tenants[..idx].iter().any(|other| other.token == tenant.token) {
+                bail!("Tenant {} reuses another tenant's token", tenant.name);
+            }
+        }
+
+        Ok(Self {
+            tenants: Arc::new(tenants),
+        })
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.tenants.is_empty()
+    }
+
+    pub(crate) fn tenant_count(&self) -> usize {
+        self.tenants.len()
+    }
+
+    /// Resolves a token to its scope. In single-tenant mode every request is
+    /// unrestricted.
+    pub(crate) fn resolve(&self, token: Option<&str>) -> Option<TenantScope> {
+        if !self.is_enabled() {
+            return Some(TenantScope::unrestricted());
+        }
+        let token = token?.trim();
+        self.tenants
+            .iter()
+            .find(|tenant| tenant.token == token)
+            .map(TenantScope::from_config)
+    }
+
+    /// Scope of a logged-in operator: the named tenant, or unrestricted for
+    /// operators without one.
+    pub(crate) fn resolve_operator(&self, tenant: Option<&str>) -> Option<TenantScope> {
+        let Some(name) = tenant else {
+            return Some(TenantScope::unrestricted());
+        };
+        self.tenants
+            .iter()
+            .find(|tenant| tenant.name == name)
+            .map(TenantScope::from_config)
+    }
+}
+
+/// The merchants the current caller may see. `merchant_ids == None` means no
+/// restriction.
+#[derive(Debug, Clone)]
+pub(crate) struct TenantScope {
+    name: Option<String>,
+    merchant_ids: Option<Vec<String>>,
+}
+
+impl TenantScope {
+    pub(crate) fn unrestricted() -> Self {
+        Self {
+            name: None,
+            merchant_ids: None,
+        }
+    }
+
+    fn from_config(tenant: &TenantConfig) -> Self {
+        Self {
+            name: Some(tenant.name.clone()),
+            merchant_ids: tenant.merchant_ids.clone(),
+        }
+    }
+
+    pub(crate) fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub(crate) fn merchant_ids(&self) -> Option<&[String]> {
+        self.merchant_ids.as_deref()
+    }
+
+    pub(crate) fn allows_merchant(&self, merchant_id: Option<&str>) -> bool {
+        match (&self.merchant_ids, merchant_id) {
+            (None, _) => true,
+            (Some(ids), Some(merchant_id)) => ids.iter().any(|id| id == merchant_id),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Global settings and distribution controls affect every tenant, so only
+    /// unrestricted tokens may use them.
+    pub(crate) fn require_unrestricted(&self) -> ApiResult<()> {
+        if self.merchant_ids.is_some() {
+            return Err(ApiError::from((
+                StatusCode::FORBIDDEN,
+                "This action is only available to unrestricted tenants".to_string(),
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn can_receive(&self, audience: &EventAudience) -> bool {
+        match audience {
+            EventAudience::All => true,
+            EventAudience::Unrestricted => self.merchant_ids.is_none(),
+            EventAudience::Merchants(ids) => {
+                ids.iter().any(|id| self.allows_merchant(Some(id.as_str())))
+            }
+        }
+    }
+}
+
+/// Reads the token from `Authorization: Bearer …` or, for the browser
+/// dashboard and its EventSource, from the tenant cookie.
+pub(crate) fn request_token(parts: &Parts) -> Option<String> {
+    bearer_token(parts).or_else(|| cookie_value(&parts.headers, TENANT_COOKIE))
+}
+
+fn bearer_token(parts: &Parts) -> Option<String> {
+    parts
+        .headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|value| value.trim().to_string())
+}
+
+/// With operator login on, a bearer tenant token still works for API
+/// clients; everything else needs a logged-in operator.
+async fn operator_scope(parts: &Parts, state: &AppState) -> ApiResult<TenantScope> {
+    if state.tenants.is_enabled()
+        && let Some(token) = bearer_token(parts)
+    {
+        return state.tenants.resolve(Some(&token)).ok_or_else(|| {
+            ApiError::from((
+                StatusCode::UNAUTHORIZED,
+                "A valid tenant token is required".to_string(),
+            ))
+        });
+    }
+    let operator = auth::session_operator(parts).await?.ok_or_else(|| {
+        (
+            StatusCode::UNAUTHORIZED,
+            "Sign in at /login first".to_string(),
+        )
+    })?;
+    state
+        .tenants
+        .resolve_operator(operator.tenant.as_deref())
+        .ok_or_else(|| {
+            ApiError::from((
+                StatusCode::FORBIDDEN,
+                format!("Operator {} has an unknown tenant", operator.username),
+            ))
+        })
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for TenantScope {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> ApiResult<Self> {
+        if state.operator_login {
+            return operator_scope(parts, state).await;
+        }
+        state
+            .tenants
+            .resolve(request_token(parts).as_deref())
+            .ok_or_else(|| {
+                ApiError::from((
+                    StatusCode::UNAUTHORIZED,
+                    "A valid tenant token is required".to_string(),
+                ))
+            })
+    }
+}
@@ -0,0 +1,81 @@
+//! Timestamps as the API reports them. The platform's columns are
+//! `timestamp without time zone` holding UTC, so reading them into
+//! `NaiveDateTime` put offset-less strings on the wire and browsers guessed
+//! the zone (Safari as UTC, Chrome as local time). Row types hold a
+//! [`UtcTimestamp`] instead: it reads either column type as a
+//! `DateTime<Utc>` and serializes as RFC 3339 with the `Z` suffix. Requests
+//! may send any RFC 3339 offset back.
+
+use std::{fmt, ops::Deref};
+
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::{
+    Decode, Postgres, Type,
+    error::BoxDynError,
+    postgres::{PgTypeInfo, PgValueRef},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct UtcTimestamp(DateTime<Utc>);
+
+impl From<NaiveDateTime> for UtcTimestamp {
+    fn from(value: NaiveDateTime) -> Self {
+        Self(value.and_utc())
+    }
+}
+
+impl From<DateTime<Utc>> for UtcTimestamp {
+    fn from(value: DateTime<Utc>) -> Self {
+        Self(value)
+    }
+}
+
+impl Deref for UtcTimestamp {
+    type Target = DateTime<Utc>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl fmt::Display for UtcTimestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.to_rfc3339_opts(SecondsFormat::Millis, true))
+    }
+}
+
+impl Serialize for UtcTimestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for UtcTimestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        DateTime::<Utc>::deserialize(deserializer).map(Self)
+    }
+}
+
+/// Decodes `timestamp` (taken as UTC) as well as `timestamptz`.
+impl Type<Postgres> for UtcTimestamp {
+    fn type_info() -> PgTypeInfo {
+        <NaiveDateTime as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <NaiveDateTime as Type<Postgres>>::compatible(ty)
+            || <DateTime<Utc> as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for UtcTimestamp {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        let ty = sqlx::ValueRef::type_info(&value).into_owned();
+        if <DateTime<Utc> as Type<Postgres>>::compatible(&ty) {
+            <DateTime<Utc> as Decode<Postgres>>::decode(value).map(Self)
+        } else {
+            <NaiveDateTime as Decode<Postgres>>::decode(value).map(Self::from)
+        }
+    }
+}
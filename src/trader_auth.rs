@@ -0,0 +1,119 @@
+//! Trader self-service tokens. A trader token only unlocks the `/api/self/*`
+//! endpoints and is never accepted as a tenant token, so traders cannot reach
+//! the operator API with it. The read-only `/trader/:token` page takes a
+//! separate page token, see `trader_page`.
+//!
+//! Tokens are issued by an unrestricted operator through
+//! `POST /api/traders/:id/token`; only their SHA-256 hash is stored and each
+//! trader has at most one active token.
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{StatusCode, header, request::Parts},
+};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{AppState, ApiResult, errors::ApiError, internal_error};
+
+const TOKEN_PREFIX: &str = "trd_";
+
+/// The trader behind a self-service request.
+#[derive(Debug, Clone)]
+pub(crate) struct TraderScope {
+    trader_id: String,
+}
+
+impl TraderScope {
+    pub(crate) fn trader_id(&self) -> &str {
+        &self.trader_id
+    }
+}
+
+pub(crate) fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Issues a new token for the trader, replacing any previous one. The plain
+/// token is only returned here.
+pub(crate) async fn issue_token(pool: &PgPool, trader_id: &str) -> sqlx::Result<String> {
+    let token = format!(
+        "{TOKEN_PREFIX}{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    );
+    sqlx::query(
+        r#"
+        INSERT INTO "TraderApiToken" ("traderId", "tokenHash")
+        VALUES ($1, $2)
+        ON CONFLICT ("traderId") DO UPDATE
+        SET "tokenHash" = EXCLUDED."tokenHash",
+            "createdAt" = CURRENT_TIMESTAMP,
+            "lastUsedAt" = NULL
+        "#,
+    )
+    .bind(trader_id)
+    .bind(hash_token(&token))
+    .execute(pool)
+    .await?;
+    Ok(token)
+}
+
+pub(crate) async fn revoke_token(pool: &PgPool, trader_id: &str) -> sqlx::Result<bool> {
+    let result = sqlx::query(r#"DELETE FROM "TraderApiToken" WHERE "traderId" = $1"#)
+        .bind(trader_id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for TraderScope {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> ApiResult<Self> {
+        let unauthorized = || {
+            ApiError::from((
+                StatusCode::UNAUTHORIZED,
+                "A valid trader token is required".to_string(),
+            ))
+        };
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .ok_or_else(unauthorized)?;
+
+        trader_for_token(&state.pool, token)
+            .await
+            .map_err(internal_error)?
+            .map(|trader_id| TraderScope { trader_id })
+            .ok_or_else(unauthorized)
+    }
+}
+
+/// The trader a token belongs to, `None` for an unknown token or a banned
+/// trader. Records the use.
+pub(crate) async fn trader_for_token(pool: &PgPool, token: &str) -> sqlx::Result<Option<String>> {
+    if !token.starts_with(TOKEN_PREFIX) {
+        return Ok(None);
+    }
+    sqlx::query_scalar(
+        r#"
+        UPDATE "TraderApiToken" t
+        SET "lastUsedAt" = CURRENT_TIMESTAMP
+        FROM "User" u
+        WHERE t."tokenHash" = $1
+          AND u."id" = t."traderId"
+          AND u."banned" = FALSE
+        RETURNING t."traderId"
+        "#,
+    )
+    .bind(hash_token(token))
+    .fetch_optional(pool)
+    .await
+}
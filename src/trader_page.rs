@@ -0,0 +1,252 @@
+//! Read-only page for traders at `/trader/:token`. It shows what is assigned
+//! to the trader right now and the limits the distributor applies to them,
+//! so traders can check for themselves instead of asking an operator. The
+//! page reloads itself every minute.
+//!
+//! The token is a page token of its own, never the trader's self-service
+//! token: a link ends up in browser history and proxy logs, and the
+//! self-service token would let its holder pause the trader or change their
+//! unavailability. Page tokens are issued through
+//! `POST /api/traders/:id/page-token`, revoked with `DELETE` on the same
+//! path, and only their SHA-256 hash is stored; each trader has at most one
+//! active token.
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{Html, IntoResponse, Response},
+};
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::{
+    ApiResult, AppState,
+    api::{SelfAssignment, ensure_trader_in_scope, fetch_self_assignments},
+    availability::{self, UnavailabilityWindow},
+    currencies::{TraderCurrencies, TraderCurrency},
+    db::{fetch_capacity_overrides, fetch_trader_cooldowns},
+    errors::ApiError,
+    frontend, i18n, internal_error,
+    tenant::TenantScope,
+    timestamps::UtcTimestamp,
+    trader_auth::hash_token,
+};
+
+const TOKEN_PREFIX: &str = "tpg_";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TraderPageTokenResponse {
+    trader_id: String,
+    token: String,
+    /// Relative link to the trader page.
+    url: String,
+}
+
+/// `POST /api/traders/:id/page-token`. Replaces any previous token; the
+/// plain token is only returned here.
+pub(crate) async fn issue_page_token(
+    Path(trader_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<Json<TraderPageTokenResponse>> {
+    ensure_trader_in_scope(&state.pool, &trader_id, scope.merchant_ids()).await?;
+    let exists: bool =
+        sqlx::query_scalar(r#"SELECT EXISTS (SELECT 1 FROM "User" WHERE "id" = $1)"#)
+            .bind(&trader_id)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(internal_error)?;
+    if !exists {
+        return Err(ApiError::trader_not_found());
+    }
+
+    let token = format!(
+        "{TOKEN_PREFIX}{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    );
+    sqlx::query(
+        r#"
+        INSERT INTO "TraderPageToken" ("traderId", "tokenHash")
+        VALUES ($1, $2)
+        ON CONFLICT ("traderId") DO UPDATE
+        SET "tokenHash" = EXCLUDED."tokenHash",
+            "createdAt" = CURRENT_TIMESTAMP,
+            "lastUsedAt" = NULL
+        "#,
+    )
+    .bind(&trader_id)
+    .bind(hash_token(&token))
+    .execute(&state.pool)
+    .await
+    .map_err(internal_error)?;
+    println!("[trader-page] Issued a new page token for trader {trader_id}");
+    Ok(Json(TraderPageTokenResponse {
+        trader_id,
+        url: format!("/trader/{token}"),
+        token,
+    }))
+}
+
+/// `DELETE /api/traders/:id/page-token`.
+pub(crate) async fn revoke_page_token(
+    Path(trader_id): Path<String>,
+    State(state): State<AppState>,
+    scope: TenantScope,
+) -> ApiResult<StatusCode> {
+    ensure_trader_in_scope(&state.pool, &trader_id, scope.merchant_ids()).await?;
+    let result = sqlx::query(r#"DELETE FROM "TraderPageToken" WHERE "traderId" = $1"#)
+        .bind(&trader_id)
+        .execute(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::from((
+            StatusCode::NOT_FOUND,
+            "Trader has no active page token".to_string(),
+        )));
+    }
+    println!("[trader-page] Revoked the page token of trader {trader_id}");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The trader a page token belongs to, `None` for an unknown token or a
+/// banned trader. Records the use.
+async fn trader_for_page_token(pool: &PgPool, token: &str) -> sqlx::Result<Option<String>> {
+    if !token.starts_with(TOKEN_PREFIX) {
+        return Ok(None);
+    }
+    sqlx::query_scalar(
+        r#"
+        UPDATE "TraderPageToken" t
+        SET "lastUsedAt" = CURRENT_TIMESTAMP
+        FROM "User" u
+        WHERE t."tokenHash" = $1
+          AND u."id" = t."traderId"
+          AND u."banned" = FALSE
+        RETURNING t."traderId"
+        "#,
+    )
+    .bind(hash_token(token))
+    .fetch_optional(pool)
+    .await
+}
+
+#[derive(Debug, FromRow)]
+struct TraderRow {
+    email: String,
+    #[sqlx(rename = "numericId")]
+    numeric_id: i32,
+    #[sqlx(rename = "balanceRub")]
+    balance_rub: Option<f64>,
+    #[sqlx(rename = "trafficEnabled")]
+    traffic_enabled: bool,
+    paused: bool,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct TraderPage {
+    pub(crate) email: String,
+    pub(crate) numeric_id: i32,
+    pub(crate) balance_rub: Option<f64>,
+    pub(crate) traffic_enabled: bool,
+    pub(crate) paused: bool,
+    /// Payouts on the trader that are not final yet, oldest first.
+    pub(crate) assignments: Vec<SelfAssignment>,
+    /// `None` when the trader may hold any number of payouts.
+    pub(crate) max_open_payouts: Option<u32>,
+    pub(crate) min_amount: Option<f64>,
+    pub(crate) max_amount: Option<f64>,
+    pub(crate) currencies: Vec<TraderCurrency>,
+    pub(crate) cooldown_remaining_seconds: Option<i64>,
+    pub(crate) unavailability: Option<UnavailabilityWindow>,
+    pub(crate) generated_at: UtcTimestamp,
+}
+
+pub(crate) async fn trader_page(
+    Path(token): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let trader_id = trader_for_page_token(&state.pool, token.trim())
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| {
+            ApiError::from((
+                StatusCode::NOT_FOUND,
+                "Unknown or revoked trader link".to_string(),
+            ))
+        })?;
+    let page = load_page(&state, &trader_id).await?;
+    let lang = i18n::Lang::from_headers(&headers);
+    let theme = frontend::Theme::from_headers(&headers);
+    // The token is in the URL: keep the page out of caches and referrers.
+    Ok((
+        [
+            (header::CACHE_CONTROL, "no-store"),
+            (header::REFERRER_POLICY, "no-referrer"),
+        ],
+        Html(frontend::render_trader_page(page, lang, theme)),
+    )
+        .into_response())
+}
+
+async fn load_page(state: &AppState, trader_id: &str) -> ApiResult<TraderPage> {
+    let trader = sqlx::query_as::<_, TraderRow>(
+        r#"
+        SELECT
+            u."email",
+            u."numericId",
+            u."balanceRub",
+            u."trafficEnabled",
+            EXISTS (
+                SELECT 1 FROM "TraderPause" tp WHERE tp."traderId" = u."id"
+            ) AS "paused"
+        FROM "User" u
+        WHERE u."id" = $1
+        "#,
+    )
+    .bind(trader_id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(internal_error)?;
+
+    let assignments = fetch_self_assignments(&state.pool, trader_id)
+        .await
+        .map_err(internal_error)?;
+    let config = state.settings.auto_config();
+    let capacity_overrides = fetch_capacity_overrides(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    let cooldowns = fetch_trader_cooldowns(&state.pool, config.assignment_cooldown_seconds)
+        .await
+        .map_err(internal_error)?;
+    let limits = state.settings.limits().await;
+    let limit = limits.get(trader_id);
+    let currencies = TraderCurrencies::load(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    let mut unavailability = availability::next_windows(&state.pool)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(TraderPage {
+        email: trader.email,
+        numeric_id: trader.numeric_id,
+        balance_rub: trader.balance_rub,
+        traffic_enabled: trader.traffic_enabled,
+        paused: trader.paused,
+        assignments,
+        max_open_payouts: config.open_payout_cap(&capacity_overrides, trader_id),
+        min_amount: limit.and_then(|limit| limit.min_amount),
+        max_amount: limit.and_then(|limit| limit.max_amount),
+        currencies: currencies.of(trader_id).to_vec(),
+        cooldown_remaining_seconds: cooldowns.get(trader_id).copied(),
+        unavailability: unavailability.remove(trader_id),
+        generated_at: Utc::now().into(),
+    })
+}
@@ -0,0 +1,157 @@
+//! Assignment notifications to traders. A trader can get a webhook URL in
+//! `TraderWebhook` (managed through `/api/traders/:id/webhook`); every payout
+//! this service assigns to them is then posted there as an `ASSIGNED` event.
+//!
+//! Notifications go through the outbox like merchant callbacks, so they are
+//! retried with the same backoff and carry the outbox id in
+//! `x-idempotency-key`. The body is signed with the trader's secret:
+//! `x-signature: sha256=<hex HMAC-SHA256 of the raw body>`. The secret is
+//! stored encrypted when [`secrets`] has a key.
+
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use reqwest::{Url, header};
+use serde_json::Value;
+use sha2::Sha256;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    callbacks::{CallbackDispatchResult, callback_result},
+    outbox, secrets,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const SECRET_PREFIX: &str = "whsec_";
+
+/// Builds the notification bodies in SQL so a whole distribution cycle is
+/// queued with one statement; traders without an enabled webhook are skipped.
+const ENQUEUE_QUERY: &str = r#"
+    INSERT INTO "OutboxMessage" ("id", "kind", "payload")
+    SELECT
+        batch."id",
+        $4,
+        jsonb_build_object(
+            'traderId', batch."traderId",
+            'payoutId', p."id",
+            'body', jsonb_build_object(
+                'event', 'ASSIGNED',
+                'traderId', batch."traderId",
+                'assignedAt', CURRENT_TIMESTAMP,
+                'payout', jsonb_build_object(
+                    'id', p."id",
+                    'numericId', p."numericId",
+                    'amount', p."amount",
+                    'bank', p."bank",
+                    'wallet', p."wallet",
+                    'externalReference', p."externalReference"
+                )
+            )
+        )
+    FROM UNNEST($1::text[], $2::text[], $3::text[]) AS batch("id", "payoutId", "traderId")
+    JOIN "TraderWebhook" w
+        ON w."traderId" = batch."traderId"
+       AND w."enabled" = TRUE
+    JOIN "Payout" p
+        ON p."id" = batch."payoutId"
+"#;
+
+/// A fresh signing secret; it is only shown when created or rotated.
+pub(crate) fn new_secret() -> String {
+    format!(
+        "{SECRET_PREFIX}{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    )
+}
+
+pub(crate) fn validate_url(url: &str) -> Result<String, String> {
+    let url = url.trim();
+    if Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https")) {
+        Ok(url.to_string())
+    } else {
+        Err("url must be an absolute http(s) URL".to_string())
+    }
+}
+
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Queues a notification for each `(payoutId, traderId)` whose trader has an
+/// enabled webhook. Returns how many were queued.
+pub(crate) async fn enqueue_assignments(
+    tx: &mut Transaction<'_, Postgres>,
+    assignments: &[(String, String)],
+) -> Result<u64> {
+    if assignments.is_empty() {
+        return Ok(0);
+    }
+    let ids: Vec<String> = assignments
+        .iter()
+        .map(|_| Uuid::new_v4().to_string())
+        .collect();
+    let (payout_ids, trader_ids): (Vec<String>, Vec<String>) = assignments.iter().cloned().unzip();
+    let queued = sqlx::query(ENQUEUE_QUERY)
+        .bind(&ids)
+        .bind(&payout_ids)
+        .bind(&trader_ids)
+        .bind(outbox::KIND_TRADER_WEBHOOK)
+        .execute(&mut **tx)
+        .await
+        .context("Failed to queue trader webhooks")?
+        .rows_affected();
+    Ok(queued)
+}
+
+/// Sends one queued notification. The URL and secret are read at send time,
+/// so a changed webhook applies to pending retries and a removed or disabled
+/// one stops them.
+pub(crate) async fn deliver(
+    state: &AppState,
+    outbox_id: &str,
+    trader_id: &str,
+    body: &Value,
+) -> Result<CallbackDispatchResult> {
+    let webhook: Option<(String, String)> = sqlx::query_as(
+        r#"
+        SELECT "url", "secret"
+        FROM "TraderWebhook"
+        WHERE "traderId" = $1 AND "enabled" = TRUE
+        "#,
+    )
+    .bind(trader_id)
+    .fetch_optional(&state.pool)
+    .await
+    .context("Failed to load trader webhook")?;
+    let Some((url, secret)) = webhook else {
+        return Ok(CallbackDispatchResult::not_attempted(
+            "Trader webhook is not configured",
+            None,
+        ));
+    };
+    let secret = secrets::open(state.secrets.as_ref(), &secret)
+        .context("Failed to decrypt trader webhook secret")?;
+
+    let payload = serde_json::to_vec(body).context("Failed to serialize trader webhook")?;
+    let response = state
+        .callback_clients
+        .default_client()
+        .post(&url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header("x-signature", sign(&secret, &payload))
+        .header("x-idempotency-key", outbox_id)
+        .body(payload)
+        .send()
+        .await;
+    let result = callback_result(url, response).await;
+    if let Some(error) = &result.error {
+        eprintln!("[trader-webhooks] Delivery to trader {trader_id} failed: {error}");
+    }
+    Ok(result)
+}